@@ -86,6 +86,11 @@ impl LlmProvider for AnthropicProvider {
         Some(&self.model)
     }
 
+    /// Anthropic recommends at most 1568 px on the long edge.
+    fn max_image_dimension(&self) -> u32 {
+        1568
+    }
+
     #[instrument(skip(self, request), fields(model))]
     async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse> {
         let body = self.build_request(request);
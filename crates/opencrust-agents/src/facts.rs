@@ -0,0 +1,325 @@
+//! Long-term fact extraction.
+//!
+//! Raw turns pile up in memory as transcripts. This module distills them into
+//! short, durable facts (who the user is, what they prefer, what was promised)
+//! that are injected into the system prompt ahead of raw recall.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use opencrust_common::Result;
+use opencrust_db::{
+    FactKind, FactQuery, FactSource, FactUpsert, MemoryEntry, MemoryFact, MemoryProvider,
+    MemoryRole, NewMemoryFact,
+};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::providers::{ChatMessage, ChatRole, LlmProvider, LlmRequest, MessagePart};
+
+/// Facts below this confidence are discarded rather than stored.
+const MIN_CONFIDENCE: f32 = 0.5;
+
+/// Existing facts shown to the extractor so it can reuse subject keys.
+const KNOWN_FACTS_IN_PROMPT: usize = 50;
+
+const EXTRACTION_PROMPT: &str = "You maintain a long-term profile of the user from their \
+conversations. From the transcript, extract only durable information worth remembering for \
+weeks or months: facts about the user, their preferences, and commitments (things the user \
+asked to be reminded of or the assistant agreed to do). Ignore small talk, one-off questions \
+and anything the assistant said about itself.\n\n\
+Respond with a JSON array only, no prose. Each item: \
+{\"kind\": \"fact\"|\"preference\"|\"commitment\", \"subject\": \"snake_case_slot\", \
+\"content\": \"short third-person sentence\", \"confidence\": 0.0-1.0}.\n\
+The subject names the slot the fact fills (e.g. home_city, diet, partner_name). When a new \
+statement updates a known fact, reuse that fact's subject so the old value is replaced. \
+Return [] when there is nothing durable.";
+
+/// When fact extraction runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FactExtractionMode {
+    /// Extract in the background right after each turn is stored.
+    AfterTurn,
+    /// Extract from pending turns on the scheduler's batch interval.
+    Batch,
+}
+
+impl FactExtractionMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "after_turn" | "turn" => Some(Self::AfterTurn),
+            "batch" => Some(Self::Batch),
+            _ => None,
+        }
+    }
+}
+
+/// A fact candidate as returned by the extraction model.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct ExtractedFact {
+    pub kind: String,
+    #[serde(default)]
+    pub subject: String,
+    pub content: String,
+    #[serde(default = "default_confidence")]
+    pub confidence: f32,
+}
+
+fn default_confidence() -> f32 {
+    0.8
+}
+
+/// A run of turns from one session, fed to the extractor as a single transcript.
+#[derive(Debug, Clone)]
+pub struct FactSourceBatch {
+    pub session_id: String,
+    pub continuity_key: Option<String>,
+    pub user_id: Option<String>,
+    pub entry_ids: Vec<String>,
+    pub transcript: Vec<(MemoryRole, String)>,
+}
+
+impl FactSourceBatch {
+    /// Group pending turn entries by session, preserving their order.
+    pub fn group(entries: Vec<MemoryEntry>) -> Vec<Self> {
+        let mut batches: BTreeMap<String, Self> = BTreeMap::new();
+        for entry in entries {
+            let batch = batches
+                .entry(entry.session_id.clone())
+                .or_insert_with(|| Self {
+                    session_id: entry.session_id.clone(),
+                    continuity_key: entry.continuity_key.clone(),
+                    user_id: entry.user_id.clone(),
+                    entry_ids: Vec::new(),
+                    transcript: Vec::new(),
+                });
+            batch.entry_ids.push(entry.id);
+            batch.transcript.push((entry.role, entry.content));
+        }
+        batches.into_values().collect()
+    }
+
+    fn scope_query(&self) -> FactQuery {
+        fact_scope_query(self.continuity_key.as_deref(), self.user_id.as_deref())
+    }
+}
+
+/// Facts are shared across the continuity bus when one is configured, otherwise
+/// they belong to a single user.
+pub fn fact_scope_query(continuity_key: Option<&str>, user_id: Option<&str>) -> FactQuery {
+    match continuity_key {
+        Some(key) => FactQuery {
+            continuity_key: Some(key.to_string()),
+            ..FactQuery::default()
+        },
+        None => FactQuery {
+            user_id: user_id.map(|u| u.to_string()),
+            ..FactQuery::default()
+        },
+    }
+}
+
+/// Run extraction for one batch and store the results. Returns the number of
+/// facts inserted or updated. The batch's entries are marked as processed even
+/// when nothing durable was found, so they are not re-sent to the model.
+pub async fn extract_and_store(
+    provider: &dyn LlmProvider,
+    memory: &Arc<dyn MemoryProvider>,
+    batch: &FactSourceBatch,
+) -> Result<usize> {
+    if batch.continuity_key.is_none() && batch.user_id.is_none() {
+        // No scope to attach facts to (anonymous session); nothing to do.
+        memory.mark_facts_extracted(&batch.entry_ids).await?;
+        return Ok(0);
+    }
+
+    let known = memory
+        .list_facts(FactQuery {
+            limit: KNOWN_FACTS_IN_PROMPT,
+            ..batch.scope_query()
+        })
+        .await?;
+    let candidates = extract_facts(provider, &batch.transcript, &known).await?;
+
+    let mut stored = 0;
+    for candidate in candidates {
+        let Some(kind) = FactKind::parse(&candidate.kind) else {
+            debug!("skipping fact with unknown kind: {}", candidate.kind);
+            continue;
+        };
+        if candidate.confidence < MIN_CONFIDENCE || candidate.content.trim().is_empty() {
+            continue;
+        }
+
+        let outcome = memory
+            .upsert_fact(NewMemoryFact {
+                continuity_key: batch.continuity_key.clone(),
+                user_id: batch.user_id.clone(),
+                kind,
+                subject: candidate.subject,
+                content: candidate.content,
+                confidence: candidate.confidence,
+                source: FactSource::Extracted,
+                source_session_id: Some(batch.session_id.clone()),
+            })
+            .await?;
+        match outcome {
+            FactUpsert::Inserted { .. } | FactUpsert::Superseded { .. } => stored += 1,
+            FactUpsert::Duplicate { .. } => {}
+            FactUpsert::Conflict { existing_id } => {
+                debug!("extracted fact conflicts with user-authored fact {existing_id}");
+            }
+        }
+    }
+
+    memory.mark_facts_extracted(&batch.entry_ids).await?;
+    Ok(stored)
+}
+
+/// Ask the model for durable facts in a transcript.
+pub async fn extract_facts(
+    provider: &dyn LlmProvider,
+    transcript: &[(MemoryRole, String)],
+    known: &[MemoryFact],
+) -> Result<Vec<ExtractedFact>> {
+    let mut input = String::new();
+    if !known.is_empty() {
+        input.push_str("Known facts (subject: content):\n");
+        for fact in known {
+            input.push_str(&format!("- {}: {}\n", fact.subject, fact.content));
+        }
+        input.push('\n');
+    }
+    input.push_str("Transcript:\n");
+    for (role, text) in transcript {
+        let label = match role {
+            MemoryRole::User => "User",
+            MemoryRole::Assistant => "Assistant",
+            MemoryRole::System => "System",
+            MemoryRole::Tool => "Tool",
+        };
+        input.push_str(&format!("{label}: {text}\n"));
+    }
+
+    let request = LlmRequest {
+        model: String::new(),
+        messages: vec![ChatMessage {
+            role: ChatRole::User,
+            content: MessagePart::Text(input),
+        }],
+        system: Some(EXTRACTION_PROMPT.to_string()),
+        max_tokens: Some(800),
+        temperature: Some(0.0),
+        tools: Vec::new(),
+    };
+
+    let response = provider.complete(&request).await?;
+    let text = crate::runtime::extract_text(&response.content);
+    Ok(parse_extraction(&text))
+}
+
+/// Parse the model's JSON array, tolerating code fences and surrounding prose.
+pub fn parse_extraction(text: &str) -> Vec<ExtractedFact> {
+    let (Some(start), Some(end)) = (text.find('['), text.rfind(']')) else {
+        return Vec::new();
+    };
+    if end < start {
+        return Vec::new();
+    }
+    match serde_json::from_str::<Vec<ExtractedFact>>(&text[start..=end]) {
+        Ok(facts) => facts,
+        Err(e) => {
+            warn!("fact extraction returned invalid JSON: {e}");
+            Vec::new()
+        }
+    }
+}
+
+/// Render facts as a system prompt block, or `None` when there are none.
+pub fn format_facts(facts: &[MemoryFact]) -> Option<String> {
+    if facts.is_empty() {
+        return None;
+    }
+    let mut block = String::from("Known facts about the user:");
+    for fact in facts {
+        let prefix = match fact.kind {
+            FactKind::Fact => "",
+            FactKind::Preference => "(preference) ",
+            FactKind::Commitment => "(commitment) ",
+        };
+        block.push_str(&format!("\n- {prefix}{}", fact.content));
+    }
+    Some(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn parse_extraction_handles_code_fences() {
+        let raw = "```json\n[{\"kind\":\"fact\",\"subject\":\"home_city\",\"content\":\"Lives in Porto\",\"confidence\":0.9}]\n```";
+        let facts = parse_extraction(raw);
+        assert_eq!(facts.len(), 1);
+        assert_eq!(facts[0].subject, "home_city");
+        assert_eq!(facts[0].content, "Lives in Porto");
+    }
+
+    #[test]
+    fn parse_extraction_defaults_confidence_and_rejects_garbage() {
+        let facts = parse_extraction("[{\"kind\":\"preference\",\"content\":\"Likes tea\"}]");
+        assert_eq!(facts[0].confidence, 0.8);
+        assert!(parse_extraction("nothing to see here").is_empty());
+        assert!(parse_extraction("[not json]").is_empty());
+    }
+
+    #[test]
+    fn group_batches_by_session_in_order() {
+        let entry = |id: &str, session: &str, role: MemoryRole| MemoryEntry {
+            id: id.to_string(),
+            session_id: session.to_string(),
+            channel_id: None,
+            user_id: Some("u".to_string()),
+            continuity_key: None,
+            role,
+            content: id.to_string(),
+            embedding: None,
+            embedding_model: None,
+            embedding_dimensions: None,
+            metadata: serde_json::json!({}),
+            created_at: Utc::now(),
+        };
+        let batches = FactSourceBatch::group(vec![
+            entry("a1", "a", MemoryRole::User),
+            entry("b1", "b", MemoryRole::User),
+            entry("a2", "a", MemoryRole::Assistant),
+        ]);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].entry_ids, vec!["a1", "a2"]);
+        assert_eq!(batches[1].entry_ids, vec!["b1"]);
+    }
+
+    #[test]
+    fn format_facts_marks_kinds() {
+        let now = Utc::now();
+        let fact = MemoryFact {
+            id: "1".into(),
+            continuity_key: None,
+            user_id: Some("u".into()),
+            kind: FactKind::Preference,
+            subject: "units".into(),
+            content: "Prefers metric units".into(),
+            confidence: 1.0,
+            source: FactSource::User,
+            source_session_id: None,
+            superseded_by: None,
+            created_at: now,
+            updated_at: now,
+        };
+        let block = format_facts(&[fact]).unwrap();
+        assert!(block.starts_with("Known facts about the user:"));
+        assert!(block.contains("(preference) Prefers metric units"));
+        assert!(format_facts(&[]).is_none());
+    }
+}
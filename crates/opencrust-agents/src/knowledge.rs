@@ -0,0 +1,409 @@
+//! Knowledge-base ingestion.
+//!
+//! Walks configured folders, extracts text from supported documents, splits it
+//! into chunks and embeds them into the [`KnowledgeStore`]. Documents are keyed
+//! by content hash, so re-syncing only re-embeds files that actually changed.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use opencrust_common::{Error, Result};
+use opencrust_db::{
+    KnowledgeHit, KnowledgeQuery, KnowledgeSource, KnowledgeStore, NewKnowledgeChunk, chunk_text,
+};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use crate::embeddings::EmbeddingProvider;
+
+const DEFAULT_CHUNK_CHARS: usize = 1500;
+const DEFAULT_CHUNK_OVERLAP: usize = 200;
+
+/// Files larger than this are skipped rather than read into memory.
+const MAX_FILE_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Chunks sent to the embedding provider per request.
+const EMBED_BATCH: usize = 64;
+
+/// Outcome counts for a sync pass.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct IndexReport {
+    pub indexed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub failed: usize,
+}
+
+/// Indexes documents from disk into a [`KnowledgeStore`] and searches them.
+pub struct KnowledgeIndexer {
+    store: Arc<KnowledgeStore>,
+    embeddings: Option<Arc<dyn EmbeddingProvider>>,
+    chunk_chars: usize,
+    chunk_overlap: usize,
+}
+
+impl KnowledgeIndexer {
+    pub fn new(store: Arc<KnowledgeStore>, embeddings: Option<Arc<dyn EmbeddingProvider>>) -> Self {
+        Self {
+            store,
+            embeddings,
+            chunk_chars: DEFAULT_CHUNK_CHARS,
+            chunk_overlap: DEFAULT_CHUNK_OVERLAP,
+        }
+    }
+
+    pub fn with_chunking(mut self, chunk_chars: usize, chunk_overlap: usize) -> Self {
+        self.chunk_chars = chunk_chars;
+        self.chunk_overlap = chunk_overlap;
+        self
+    }
+
+    pub fn store(&self) -> &Arc<KnowledgeStore> {
+        &self.store
+    }
+
+    /// Register (or re-point) a source folder and index it.
+    pub async fn add_source(&self, name: &str, path: &Path) -> Result<IndexReport> {
+        let root = std::fs::canonicalize(path).map_err(|e| {
+            Error::Config(format!(
+                "knowledge source '{name}' path {} is not readable: {e}",
+                path.display()
+            ))
+        })?;
+        if !root.is_dir() {
+            return Err(Error::Config(format!(
+                "knowledge source '{name}' path {} is not a directory",
+                root.display()
+            )));
+        }
+        let source = self.store.upsert_source(name, &root.to_string_lossy())?;
+        self.sync_source(&source).await
+    }
+
+    /// Bring a source in line with the files on disk: index new and changed
+    /// documents, drop ones that were deleted.
+    pub async fn sync_source(&self, source: &KnowledgeSource) -> Result<IndexReport> {
+        let root = PathBuf::from(&source.path);
+        let files = {
+            let root = root.clone();
+            tokio::task::spawn_blocking(move || collect_files(&root))
+                .await
+                .map_err(|e| Error::Agent(format!("knowledge scan task failed: {e}")))?
+        };
+        let mut known = self.store.document_hashes(&source.id)?;
+        let mut report = IndexReport::default();
+
+        for file in files {
+            let Some(rel) = relative_path(&root, &file) else {
+                continue;
+            };
+            let previous = known.remove(&rel);
+            match self
+                .index_file(source, &file, &rel, previous.as_deref())
+                .await
+            {
+                Ok(true) => report.indexed += 1,
+                Ok(false) => report.unchanged += 1,
+                Err(e) => {
+                    warn!("failed to index {}: {e}", file.display());
+                    report.failed += 1;
+                }
+            }
+        }
+
+        for stale in known.into_keys() {
+            if self.store.remove_document(&source.id, &stale)? {
+                report.removed += 1;
+            }
+        }
+
+        info!(
+            "knowledge source '{}' synced: {} indexed, {} unchanged, {} removed, {} failed",
+            source.name, report.indexed, report.unchanged, report.removed, report.failed
+        );
+        Ok(report)
+    }
+
+    /// Sync every registered source.
+    pub async fn sync_all(&self) -> Result<IndexReport> {
+        let mut total = IndexReport::default();
+        for source in self.store.list_sources()? {
+            match self.sync_source(&source).await {
+                Ok(report) => {
+                    total.indexed += report.indexed;
+                    total.unchanged += report.unchanged;
+                    total.removed += report.removed;
+                    total.failed += report.failed;
+                }
+                Err(e) => warn!("failed to sync knowledge source '{}': {e}", source.name),
+            }
+        }
+        Ok(total)
+    }
+
+    /// React to a change at `path` reported by a file watcher. Re-indexes or
+    /// removes the single document when possible, and falls back to a full
+    /// sync of the owning source for directory-level changes.
+    pub async fn refresh_path(&self, path: &Path) -> Result<()> {
+        let Some(source) = self
+            .store
+            .list_sources()?
+            .into_iter()
+            .find(|s| path.starts_with(&s.path))
+        else {
+            return Ok(());
+        };
+        let root = PathBuf::from(&source.path);
+        let Some(rel) = relative_path(&root, path) else {
+            return Ok(());
+        };
+        if rel.split('/').any(|part| part.starts_with('.')) {
+            return Ok(());
+        }
+
+        if path.is_file() {
+            if !is_indexable(path) {
+                return Ok(());
+            }
+            let previous = self.store.document_hashes(&source.id)?.remove(&rel);
+            if self
+                .index_file(&source, path, &rel, previous.as_deref())
+                .await?
+            {
+                info!("re-indexed {}/{}", source.name, rel);
+            }
+            return Ok(());
+        }
+
+        if !path.exists() {
+            if self.store.remove_document(&source.id, &rel)? {
+                info!("removed {}/{} from knowledge base", source.name, rel);
+                return Ok(());
+            }
+            // A deleted file we never indexed (editor temp files and the like).
+            if path.extension().is_some() {
+                return Ok(());
+            }
+        }
+
+        // A directory was created, moved or deleted.
+        self.sync_source(&source).await.map(|_| ())
+    }
+
+    /// Index one file unless its content hash matches `previous_hash`.
+    /// Returns whether the document was (re)written.
+    async fn index_file(
+        &self,
+        source: &KnowledgeSource,
+        path: &Path,
+        rel: &str,
+        previous_hash: Option<&str>,
+    ) -> Result<bool> {
+        let path_buf = path.to_path_buf();
+        let (hash, text) =
+            tokio::task::spawn_blocking(move || -> Result<(String, Option<String>)> {
+                let bytes = std::fs::read(&path_buf)?;
+                let hash = sha256_hex(&bytes);
+                let text =
+                    opencrust_media::document::extract_text(&bytes, &path_buf.to_string_lossy())?;
+                Ok((hash, text))
+            })
+            .await
+            .map_err(|e| Error::Agent(format!("knowledge extraction task failed: {e}")))??;
+
+        if previous_hash == Some(hash.as_str()) {
+            return Ok(false);
+        }
+        let Some(text) = text else {
+            return Ok(false);
+        };
+
+        let pieces = chunk_text(&text, self.chunk_chars, self.chunk_overlap);
+        let embeddings = self.embed_chunks(&pieces).await?;
+        let model = self.embeddings.as_ref().map(|e| e.model().to_string());
+        let chunks = pieces
+            .into_iter()
+            .zip(embeddings)
+            .map(|(content, embedding)| NewKnowledgeChunk {
+                content,
+                embedding,
+                embedding_model: model.clone(),
+            })
+            .collect();
+
+        self.store
+            .replace_document(&source.id, rel, &hash, chunks)?;
+        debug!("indexed {}/{}", source.name, rel);
+        Ok(true)
+    }
+
+    async fn embed_chunks(&self, pieces: &[String]) -> Result<Vec<Option<Vec<f32>>>> {
+        let Some(provider) = &self.embeddings else {
+            return Ok(vec![None; pieces.len()]);
+        };
+        let mut out = Vec::with_capacity(pieces.len());
+        for batch in pieces.chunks(EMBED_BATCH) {
+            let vectors = provider.embed_documents(batch).await?;
+            if vectors.len() != batch.len() {
+                return Err(Error::Agent(format!(
+                    "embedding provider returned {} vectors for {} chunks",
+                    vectors.len(),
+                    batch.len()
+                )));
+            }
+            out.extend(vectors.into_iter().map(Some));
+        }
+        Ok(out)
+    }
+
+    /// Search the knowledge base, embedding the query when a provider is set.
+    pub async fn search(
+        &self,
+        query: &str,
+        sources: Vec<String>,
+        limit: usize,
+    ) -> Result<Vec<KnowledgeHit>> {
+        let query_embedding = match &self.embeddings {
+            Some(provider) => match provider.embed_query(query).await {
+                Ok(vector) => Some(vector),
+                Err(e) => {
+                    warn!("knowledge query embedding failed, using keyword search: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+        self.store.search(&KnowledgeQuery {
+            query_text: Some(query.to_string()),
+            query_embedding,
+            sources,
+            limit,
+        })
+    }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+fn is_indexable(path: &Path) -> bool {
+    !is_hidden(path) && opencrust_media::document::is_supported(&path.to_string_lossy())
+}
+
+/// Supported files under `root`, skipping hidden entries and symlinked directories.
+fn collect_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("cannot read {}: {e}", dir.display());
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if is_hidden(&path) {
+                continue;
+            }
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() && is_indexable(&path) {
+                match entry.metadata() {
+                    Ok(meta) if meta.len() > MAX_FILE_BYTES => {
+                        debug!("skipping large file {}", path.display());
+                    }
+                    Ok(_) => files.push(path),
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// `path` relative to `root` with `/` separators, as stored in the index.
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let rel = path.strip_prefix(root).ok()?;
+    let parts: Vec<String> = rel
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("/"))
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, data);
+    let mut out = String::with_capacity(64);
+    for byte in digest.as_ref() {
+        let _ = write!(out, "{byte:02x}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn sync_indexes_changes_and_removals() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("travel")).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join("travel/japan.md"), "Hotel in Kyoto is booked").unwrap();
+        std::fs::write(root.join("todo.txt"), "Renew passport").unwrap();
+        std::fs::write(root.join(".git/config"), "ignored").unwrap();
+        std::fs::write(root.join("photo.png"), [0u8, 1, 2]).unwrap();
+
+        let store = Arc::new(KnowledgeStore::in_memory().unwrap());
+        let indexer = KnowledgeIndexer::new(Arc::clone(&store), None);
+        let report = indexer.add_source("notes", root).await.unwrap();
+        assert_eq!(report.indexed, 2);
+
+        let hits = indexer.search("kyoto", Vec::new(), 5).await.unwrap();
+        assert_eq!(hits[0].citation(), "notes/travel/japan.md#1");
+
+        std::fs::remove_file(root.join("todo.txt")).unwrap();
+        let source = store.get_source("notes").unwrap().unwrap();
+        let report = indexer.sync_source(&source).await.unwrap();
+        assert_eq!((report.unchanged, report.removed), (1, 1));
+
+        let file = std::fs::canonicalize(root.join("travel/japan.md")).unwrap();
+        std::fs::write(&file, "Hotel in Osaka instead").unwrap();
+        indexer.refresh_path(&file).await.unwrap();
+        assert!(
+            indexer
+                .search("kyoto", Vec::new(), 5)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            indexer.search("osaka", Vec::new(), 5).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[test]
+    fn relative_paths_use_forward_slashes() {
+        let root = Path::new("/data/notes");
+        assert_eq!(
+            relative_path(root, Path::new("/data/notes/a/b.md")).as_deref(),
+            Some("a/b.md")
+        );
+        assert_eq!(relative_path(root, root), None);
+        assert_eq!(relative_path(root, Path::new("/elsewhere/x.md")), None);
+    }
+}
@@ -4,6 +4,8 @@ pub mod mcp;
 pub mod a2a;
 pub mod anthropic;
 pub mod embeddings;
+pub mod facts;
+pub mod knowledge;
 pub mod ollama;
 pub mod openai;
 pub mod providers;
@@ -12,15 +14,18 @@ pub mod tools;
 
 pub use anthropic::AnthropicProvider;
 pub use embeddings::{CohereEmbeddingProvider, EmbeddingProvider};
+pub use facts::FactExtractionMode;
+pub use knowledge::{IndexReport, KnowledgeIndexer};
 pub use ollama::OllamaProvider;
 pub use openai::OpenAiProvider;
 pub use providers::{
     ChatMessage, ChatRole, ContentBlock, LlmProvider, LlmRequest, LlmResponse, MessagePart,
     StreamEvent, ToolDefinition,
 };
-pub use runtime::AgentRuntime;
+pub use runtime::{AgentRuntime, SessionOverrides, SessionUsage, ToolGuard};
 pub use tools::{
-    BashTool, CancelHeartbeat, FileReadTool, FileWriteTool, ListHeartbeats, ScheduleHeartbeat,
+    AttachmentSender, BashTool, CancelHeartbeat, FileReadTool, FileWriteTool, KnowledgeSearchTool,
+    ListHeartbeats, MemoryForget, MemorySave, MemorySearch, ScheduleHeartbeat, SendAttachmentTool,
     Tool, ToolContext, ToolOutput, WebFetchTool, WebSearchTool,
 };
 
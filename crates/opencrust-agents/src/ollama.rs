@@ -302,6 +302,11 @@ impl LlmProvider for OllamaProvider {
         Some(&self.model)
     }
 
+    /// Local vision models work at low resolutions; keep uploads small.
+    fn max_image_dimension(&self) -> u32 {
+        1024
+    }
+
     async fn available_models(&self) -> Result<Vec<String>> {
         self.list_models().await
     }
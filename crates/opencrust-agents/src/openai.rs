@@ -247,6 +247,11 @@ impl LlmProvider for OpenAiProvider {
         Some(&self.model)
     }
 
+    /// OpenAI scales high-detail images to fit 2048x2048.
+    fn max_image_dimension(&self) -> u32 {
+        2048
+    }
+
     #[instrument(skip(self, request), fields(model))]
     async fn complete(&self, request: &LlmRequest) -> Result<LlmResponse> {
         let body = self.build_request(request);
@@ -31,6 +31,12 @@ pub trait LlmProvider: Send + Sync {
         None
     }
 
+    /// Longest image edge, in pixels, worth sending to this provider.
+    /// Larger images are downscaled before upload.
+    fn max_image_dimension(&self) -> u32 {
+        opencrust_media::image::DEFAULT_MAX_DIMENSION
+    }
+
     /// Return a list of models that can be selected for this provider.
     async fn available_models(&self) -> Result<Vec<String>> {
         Ok(Vec::new())
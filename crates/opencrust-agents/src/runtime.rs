@@ -1,22 +1,59 @@
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
 use futures::StreamExt;
 use futures::future::join_all;
 use opencrust_common::{Error, Result};
 use opencrust_db::{MemoryEntry, MemoryProvider, MemoryRole, NewMemoryEntry, RecallQuery};
+use opencrust_media::MediaStore;
 use tokio::sync::mpsc;
 use tracing::{info, instrument, warn};
 
 use crate::embeddings::EmbeddingProvider;
+use crate::facts::{self, FactExtractionMode, FactSourceBatch};
 use crate::providers::{
     ChatMessage, ChatRole, ContentBlock, LlmProvider, LlmRequest, MessagePart, StreamEvent,
-    ToolDefinition,
+    ToolDefinition, Usage,
 };
 use crate::tools::{Tool, ToolContext, ToolOutput};
 
 /// Maximum number of tool-use round-trips before the loop is forcibly stopped.
 const MAX_TOOL_ITERATIONS: usize = 10;
 
+/// Batch sweeps a turn may fail in before fact extraction skips it for good.
+const MAX_FACT_EXTRACTION_ATTEMPTS: u32 = 3;
+
+/// Per-session settings chosen from chat (`/model`, `/agent`). Unset fields
+/// fall back to the runtime defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionOverrides {
+    /// Named agent the settings came from, for display.
+    pub agent: Option<String>,
+    pub provider_id: Option<String>,
+    pub model: Option<String>,
+    pub system_prompt: Option<String>,
+    pub max_tokens: Option<u32>,
+}
+
+impl SessionOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Decides whether a tool call may run. Returns the reason given to the
+/// model when it may not.
+pub type ToolGuard =
+    Arc<dyn Fn(&ToolContext, &str) -> std::result::Result<(), String> + Send + Sync>;
+
+/// Token usage accumulated for a session since the gateway started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionUsage {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
 /// Manages agent sessions, tool execution, and LLM provider routing.
 pub struct AgentRuntime {
     providers: RwLock<Vec<Arc<dyn LlmProvider>>>,
@@ -30,6 +67,14 @@ pub struct AgentRuntime {
     max_context_tokens: Option<usize>,
     recall_limit: usize,
     summarization_enabled: bool,
+    fact_extraction: Option<FactExtractionMode>,
+    max_injected_facts: usize,
+    /// Failed batch extraction attempts per turn entry id.
+    fact_failures: RwLock<HashMap<String, u32>>,
+    media: Option<Arc<MediaStore>>,
+    session_overrides: RwLock<HashMap<String, SessionOverrides>>,
+    session_usage: RwLock<HashMap<String, SessionUsage>>,
+    tool_guard: RwLock<Option<ToolGuard>>,
 }
 
 impl AgentRuntime {
@@ -46,6 +91,13 @@ impl AgentRuntime {
             max_context_tokens: None,
             recall_limit: 10,
             summarization_enabled: true,
+            fact_extraction: None,
+            max_injected_facts: 30,
+            fact_failures: RwLock::new(HashMap::new()),
+            media: None,
+            session_overrides: RwLock::new(HashMap::new()),
+            session_usage: RwLock::new(HashMap::new()),
+            tool_guard: RwLock::new(None),
         }
     }
 
@@ -84,6 +136,20 @@ impl AgentRuntime {
         self.summarization_enabled = enabled;
     }
 
+    /// Enable distilling turns into long-term facts. `None` disables extraction;
+    /// facts that already exist are still injected into the system prompt.
+    pub fn set_fact_extraction(&mut self, mode: Option<FactExtractionMode>) {
+        self.fact_extraction = mode;
+    }
+
+    pub fn fact_extraction(&self) -> Option<FactExtractionMode> {
+        self.fact_extraction
+    }
+
+    pub fn set_max_injected_facts(&mut self, limit: usize) {
+        self.max_injected_facts = limit;
+    }
+
     pub fn register_provider(&self, provider: Arc<dyn LlmProvider>) {
         let id = provider.provider_id().to_string();
         info!("registered LLM provider: {}", id);
@@ -139,6 +205,65 @@ impl AgentRuntime {
         self.default_provider.read().unwrap().clone()
     }
 
+    /// Replace a session's overrides; empty overrides remove the entry.
+    pub fn set_session_overrides(&self, session_id: &str, overrides: SessionOverrides) {
+        let mut map = self.session_overrides.write().unwrap();
+        if overrides.is_empty() {
+            map.remove(session_id);
+        } else {
+            map.insert(session_id.to_string(), overrides);
+        }
+    }
+
+    pub fn session_overrides(&self, session_id: &str) -> Option<SessionOverrides> {
+        self.session_overrides
+            .read()
+            .unwrap()
+            .get(session_id)
+            .cloned()
+    }
+
+    /// Provider for a session: its override if registered, else the default.
+    fn session_provider(&self, overrides: &SessionOverrides) -> Result<Arc<dyn LlmProvider>> {
+        if let Some(id) = &overrides.provider_id {
+            if let Some(provider) = self.get_provider(id) {
+                return Ok(provider);
+            }
+            warn!("session provider '{id}' is not registered, using the default");
+        }
+        self.default_provider()
+            .ok_or_else(|| Error::Agent("no LLM provider configured".into()))
+    }
+
+    fn record_usage(&self, session_id: &str, usage: Option<&Usage>) {
+        let mut map = self.session_usage.write().unwrap();
+        let entry = map.entry(session_id.to_string()).or_default();
+        entry.requests += 1;
+        if let Some(usage) = usage {
+            entry.input_tokens += u64::from(usage.input_tokens);
+            entry.output_tokens += u64::from(usage.output_tokens);
+        }
+    }
+
+    /// Token usage recorded for a session, if it has made any requests.
+    pub fn session_usage(&self, session_id: &str) -> Option<SessionUsage> {
+        self.session_usage.read().unwrap().get(session_id).copied()
+    }
+
+    /// Usage summed over all sessions.
+    pub fn total_usage(&self) -> SessionUsage {
+        self.session_usage
+            .read()
+            .unwrap()
+            .values()
+            .fold(SessionUsage::default(), |mut total, u| {
+                total.requests += u.requests;
+                total.input_tokens += u.input_tokens;
+                total.output_tokens += u.output_tokens;
+                total
+            })
+    }
+
     pub fn set_memory_provider(&mut self, memory: Arc<dyn MemoryProvider>) {
         self.memory = Some(memory);
         info!("memory provider attached to agent runtime");
@@ -148,6 +273,104 @@ impl AgentRuntime {
         self.memory.is_some()
     }
 
+    /// Shared handle to the memory provider, for management APIs.
+    pub fn memory_provider(&self) -> Option<Arc<dyn MemoryProvider>> {
+        self.memory.clone()
+    }
+
+    /// Attach the media store that `media://` image references resolve against.
+    pub fn set_media_store(&mut self, media: Arc<MediaStore>) {
+        self.media = Some(media);
+    }
+
+    /// Shared handle to the media store, for the media API and channels.
+    pub fn media_store(&self) -> Option<Arc<MediaStore>> {
+        self.media.clone()
+    }
+
+    /// Largest image edge the default provider should receive.
+    pub fn max_image_dimension(&self) -> u32 {
+        self.default_provider()
+            .map(|p| p.max_image_dimension())
+            .unwrap_or(opencrust_media::image::DEFAULT_MAX_DIMENSION)
+    }
+
+    /// Copy `messages` for a provider request, replacing `media://` image
+    /// references with inline `data:` URIs. History keeps the short reference;
+    /// the bytes are only loaded for the request being sent, on a blocking
+    /// thread, and kept in `loaded` so a tool loop reads each file once.
+    /// Missing files become a text note.
+    async fn resolve_media(
+        &self,
+        messages: &[ChatMessage],
+        loaded: &mut HashMap<String, Option<String>>,
+    ) -> Vec<ChatMessage> {
+        let mut wanted: Vec<String> = messages
+            .iter()
+            .filter_map(|message| match &message.content {
+                MessagePart::Parts(blocks) => Some(blocks),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|block| match block {
+                ContentBlock::Image { url }
+                    if opencrust_media::parse_media_url(url).is_some()
+                        && !loaded.contains_key(url) =>
+                {
+                    Some(url.clone())
+                }
+                _ => None,
+            })
+            .collect();
+        wanted.sort();
+        wanted.dedup();
+        if !wanted.is_empty() {
+            let store = self.media.clone();
+            let fetched = tokio::task::spawn_blocking(move || {
+                wanted
+                    .into_iter()
+                    .map(|url| {
+                        let data_uri = store.as_ref().and_then(|store| {
+                            store.to_data_uri(&url).unwrap_or_else(|e| {
+                                warn!("failed to load {url}: {e}");
+                                None
+                            })
+                        });
+                        (url, data_uri)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_else(|e| {
+                warn!("media loading task failed: {e}");
+                Vec::new()
+            });
+            loaded.extend(fetched);
+        }
+
+        let mut resolved = messages.to_vec();
+        for message in &mut resolved {
+            let MessagePart::Parts(blocks) = &mut message.content else {
+                continue;
+            };
+            for block in blocks.iter_mut() {
+                let ContentBlock::Image { url } = block else {
+                    continue;
+                };
+                if opencrust_media::parse_media_url(url).is_none() {
+                    continue;
+                }
+                *block = match loaded.get(url.as_str()).cloned().flatten() {
+                    Some(data_uri) => ContentBlock::Image { url: data_uri },
+                    None => ContentBlock::Text {
+                        text: "[image no longer available]".to_string(),
+                    },
+                };
+            }
+        }
+        resolved
+    }
+
     pub fn set_embedding_provider(&mut self, embeddings: Arc<dyn EmbeddingProvider>) {
         self.embeddings = Some(embeddings);
         info!("embedding provider attached to agent runtime");
@@ -195,7 +418,7 @@ impl AgentRuntime {
         let user_embedding = self.embed_document(user_input).await;
         let assistant_embedding = self.embed_document(assistant_output).await;
 
-        memory
+        let user_entry_id = memory
             .remember(NewMemoryEntry {
                 session_id: session_id.to_string(),
                 channel_id: None,
@@ -209,7 +432,7 @@ impl AgentRuntime {
             })
             .await?;
 
-        memory
+        let assistant_entry_id = memory
             .remember(NewMemoryEntry {
                 session_id: session_id.to_string(),
                 channel_id: None,
@@ -223,9 +446,120 @@ impl AgentRuntime {
             })
             .await?;
 
+        if self.fact_extraction == Some(FactExtractionMode::AfterTurn)
+            && let Some(provider) = self.default_provider()
+        {
+            let memory = Arc::clone(memory);
+            let batch = FactSourceBatch {
+                session_id: session_id.to_string(),
+                continuity_key: continuity_key.map(|s| s.to_string()),
+                user_id: user_id.map(|s| s.to_string()),
+                entry_ids: vec![user_entry_id, assistant_entry_id],
+                transcript: vec![
+                    (MemoryRole::User, user_input.to_string()),
+                    (MemoryRole::Assistant, assistant_output.to_string()),
+                ],
+            };
+            tokio::spawn(async move {
+                if let Err(e) = facts::extract_and_store(provider.as_ref(), &memory, &batch).await {
+                    warn!(
+                        "fact extraction failed for session {}: {e}",
+                        batch.session_id
+                    );
+                }
+            });
+        }
+
         Ok(())
     }
 
+    /// Distill up to `limit` pending turn entries into facts. Used by the
+    /// scheduler in batch mode. Returns the number of facts stored.
+    ///
+    /// A failing batch is logged and left for the next sweep; after
+    /// `MAX_FACT_EXTRACTION_ATTEMPTS` failures its turns are marked extracted
+    /// so they stop holding up the turns behind them.
+    pub async fn extract_pending_facts(&self, limit: usize) -> Result<usize> {
+        let Some(memory) = &self.memory else {
+            return Ok(0);
+        };
+        let provider = self
+            .default_provider()
+            .ok_or_else(|| Error::Agent("no LLM provider configured".into()))?;
+
+        let pending = memory.pending_fact_turns(limit).await?;
+        let mut stored = 0;
+        for batch in FactSourceBatch::group(pending) {
+            match facts::extract_and_store(provider.as_ref(), memory, &batch).await {
+                Ok(count) => {
+                    stored += count;
+                    let mut failures = self.fact_failures.write().unwrap();
+                    for id in &batch.entry_ids {
+                        failures.remove(id);
+                    }
+                }
+                Err(e) => {
+                    warn!(
+                        "fact extraction failed for session {}: {e}",
+                        batch.session_id
+                    );
+                    let exhausted = self.record_fact_failure(&batch.entry_ids);
+                    if exhausted.is_empty() {
+                        continue;
+                    }
+                    warn!(
+                        "giving up fact extraction for {} turns of session {}",
+                        exhausted.len(),
+                        batch.session_id
+                    );
+                    if let Err(e) = memory.mark_facts_extracted(&exhausted).await {
+                        warn!("failed to skip turns of session {}: {e}", batch.session_id);
+                    }
+                }
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Count a failed extraction attempt for each entry and return the ones
+    /// that have used up their attempts.
+    fn record_fact_failure(&self, entry_ids: &[String]) -> Vec<String> {
+        let mut failures = self.fact_failures.write().unwrap();
+        let mut exhausted = Vec::new();
+        for id in entry_ids {
+            let attempts = failures.entry(id.clone()).or_default();
+            *attempts += 1;
+            if *attempts >= MAX_FACT_EXTRACTION_ATTEMPTS {
+                failures.remove(id);
+                exhausted.push(id.clone());
+            }
+        }
+        exhausted
+    }
+
+    /// Known facts for this user/continuity scope, formatted for the system prompt.
+    async fn facts_context(
+        &self,
+        continuity_key: Option<&str>,
+        user_id: Option<&str>,
+    ) -> Option<String> {
+        let memory = self.memory.as_ref()?;
+        if (continuity_key.is_none() && user_id.is_none()) || self.max_injected_facts == 0 {
+            return None;
+        }
+        let query = opencrust_db::FactQuery {
+            limit: self.max_injected_facts,
+            ..facts::fact_scope_query(continuity_key, user_id)
+        };
+        match memory.list_facts(query).await {
+            Ok(found) => facts::format_facts(&found),
+            Err(e) => {
+                warn!("failed to load user facts, continuing without them: {e}");
+                None
+            }
+        }
+    }
+
     pub async fn recall_context(
         &self,
         query_text: &str,
@@ -245,11 +579,26 @@ impl AgentRuntime {
                 query_embedding,
                 session_id: session_id.map(|s| s.to_string()),
                 continuity_key: continuity_key.map(|s| s.to_string()),
+                user_id: None,
                 limit,
             })
             .await
     }
 
+    /// Run a recall query the same way turns do, embedding the query text when
+    /// an embedding provider is attached. Used to debug what recall returns.
+    pub async fn search_memory(&self, mut query: RecallQuery) -> Result<Vec<MemoryEntry>> {
+        let Some(memory) = &self.memory else {
+            return Ok(Vec::new());
+        };
+        if query.query_embedding.is_none()
+            && let Some(text) = query.query_text.as_deref()
+        {
+            query.query_embedding = self.embed_query(text).await;
+        }
+        memory.recall(query).await
+    }
+
     pub fn register_tool(&mut self, tool: Box<dyn Tool>) {
         info!("registered tool: {}", tool.name());
         self.tools.push(tool);
@@ -273,6 +622,33 @@ impl AgentRuntime {
             .map(|t| t.as_ref())
     }
 
+    /// Check every tool call with `guard` before it runs.
+    pub fn set_tool_guard(&self, guard: ToolGuard) {
+        *self.tool_guard.write().unwrap() = Some(guard);
+    }
+
+    /// Execute a tool call, reporting unknown or refused tools as errors.
+    async fn run_tool(
+        &self,
+        context: &ToolContext,
+        name: &str,
+        input: serde_json::Value,
+    ) -> ToolOutput {
+        let Some(tool) = self.find_tool(name) else {
+            return ToolOutput::error(format!("unknown tool: {}", name));
+        };
+        let guard = self.tool_guard.read().unwrap().clone();
+        if let Some(guard) = guard
+            && let Err(reason) = guard(context, name)
+        {
+            info!("tool call refused: {name}: {reason}");
+            return ToolOutput::error(reason);
+        }
+        tool.execute(context, input)
+            .await
+            .unwrap_or_else(|e| ToolOutput::error(e.to_string()))
+    }
+
     /// Run the full conversation loop: recall context, call LLM, execute tools, return response.
     pub async fn process_message(
         &self,
@@ -514,10 +890,12 @@ impl AgentRuntime {
             _ => None,
         };
 
+        let facts_context = self.facts_context(continuity_key, user_id).await;
         let dna = self.dna_content();
         let system = build_system_prompt(
             dna.as_deref(),
             &effective_system_prompt,
+            facts_context.as_deref(),
             memory_context.as_deref(),
             None,
         );
@@ -533,10 +911,11 @@ impl AgentRuntime {
         let max_ctx = self.max_context_tokens.unwrap_or(100_000);
         trim_messages_to_budget(&mut messages, &system, &tool_defs, max_ctx);
 
+        let mut media = HashMap::new();
         for _iteration in 0..MAX_TOOL_ITERATIONS {
             let request = LlmRequest {
                 model: effective_model.clone(),
-                messages: messages.clone(),
+                messages: self.resolve_media(&messages, &mut media).await,
                 system: system.clone(),
                 max_tokens: Some(effective_max_tokens),
                 temperature: None,
@@ -545,6 +924,8 @@ impl AgentRuntime {
 
             let response = provider.complete(&request).await?;
 
+            self.record_usage(session_id, response.usage.as_ref());
+
             let has_tool_use = response
                 .content
                 .iter()
@@ -572,15 +953,10 @@ impl AgentRuntime {
                     let context = crate::tools::ToolContext {
                         session_id: session_id.to_string(),
                         user_id: user_id.map(|s| s.to_string()),
+                        continuity_key: continuity_key.map(|s| s.to_string()),
                         heartbeat_depth: 0,
                     };
-                    let output = match self.find_tool(name) {
-                        Some(tool) => tool
-                            .execute(&context, input.clone())
-                            .await
-                            .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                        None => ToolOutput::error(format!("unknown tool: {}", name)),
-                    };
+                    let output = self.run_tool(&context, name, input.clone()).await;
                     tool_results.push(ContentBlock::ToolResult {
                         tool_use_id: id.clone(),
                         content: output.content,
@@ -656,10 +1032,12 @@ impl AgentRuntime {
             _ => None,
         };
 
+        let facts_context = self.facts_context(continuity_key, user_id).await;
         let dna = self.dna_content();
         let system = build_system_prompt(
             dna.as_deref(),
             &effective_system_prompt,
+            facts_context.as_deref(),
             memory_context.as_deref(),
             session_summary,
         );
@@ -688,6 +1066,7 @@ impl AgentRuntime {
             build_system_prompt(
                 dna.as_deref(),
                 &effective_system_prompt,
+                facts_context.as_deref(),
                 memory_context.as_deref(),
                 new_summary.as_deref(),
             )
@@ -695,10 +1074,11 @@ impl AgentRuntime {
             system
         };
 
+        let mut media = HashMap::new();
         for _iteration in 0..MAX_TOOL_ITERATIONS {
             let request = LlmRequest {
                 model: effective_model.clone(),
-                messages: messages.clone(),
+                messages: self.resolve_media(&messages, &mut media).await,
                 system: system.clone(),
                 max_tokens: Some(effective_max_tokens),
                 temperature: None,
@@ -707,6 +1087,8 @@ impl AgentRuntime {
 
             let response = provider.complete(&request).await?;
 
+            self.record_usage(session_id, response.usage.as_ref());
+
             let has_tool_use = response
                 .content
                 .iter()
@@ -734,15 +1116,10 @@ impl AgentRuntime {
                     let context = crate::tools::ToolContext {
                         session_id: session_id.to_string(),
                         user_id: user_id.map(|s| s.to_string()),
+                        continuity_key: continuity_key.map(|s| s.to_string()),
                         heartbeat_depth: 0,
                     };
-                    let output = match self.find_tool(name) {
-                        Some(tool) => tool
-                            .execute(&context, input.clone())
-                            .await
-                            .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                        None => ToolOutput::error(format!("unknown tool: {}", name)),
-                    };
+                    let output = self.run_tool(&context, name, input.clone()).await;
                     tool_results.push(ContentBlock::ToolResult {
                         tool_use_id: id.clone(),
                         content: output.content,
@@ -774,9 +1151,12 @@ impl AgentRuntime {
         user_id: Option<&str>,
         heartbeat_depth: u8,
     ) -> Result<String> {
-        let provider: Arc<dyn LlmProvider> = self
-            .default_provider()
-            .ok_or_else(|| Error::Agent("no LLM provider configured".into()))?;
+        let overrides = self.session_overrides(session_id).unwrap_or_default();
+        let provider = self.session_provider(&overrides)?;
+        let system_prompt = overrides
+            .system_prompt
+            .clone()
+            .or_else(|| self.system_prompt.clone());
 
         // Build system message: system_prompt + memory context
         let memory_context = match self
@@ -802,10 +1182,12 @@ impl AgentRuntime {
             _ => None,
         };
 
+        let facts_context = self.facts_context(continuity_key, user_id).await;
         let dna = self.dna_content();
         let system = build_system_prompt(
             dna.as_deref(),
-            &self.system_prompt,
+            &system_prompt,
+            facts_context.as_deref(),
             memory_context.as_deref(),
             None,
         );
@@ -822,18 +1204,21 @@ impl AgentRuntime {
         let max_ctx = self.max_context_tokens.unwrap_or(100_000);
         trim_messages_to_budget(&mut messages, &system, &tool_defs, max_ctx);
 
+        let mut media = HashMap::new();
         for _iteration in 0..MAX_TOOL_ITERATIONS {
             let request = LlmRequest {
-                model: String::new(),
-                messages: messages.clone(),
+                model: overrides.model.clone().unwrap_or_default(),
+                messages: self.resolve_media(&messages, &mut media).await,
                 system: system.clone(),
-                max_tokens: Some(self.max_tokens.unwrap_or(4096)),
+                max_tokens: Some(overrides.max_tokens.or(self.max_tokens).unwrap_or(4096)),
                 temperature: None,
                 tools: tool_defs.clone(),
             };
 
             let response = provider.complete(&request).await?;
 
+            self.record_usage(session_id, response.usage.as_ref());
+
             let has_tool_use = response
                 .content
                 .iter()
@@ -872,15 +1257,10 @@ impl AgentRuntime {
                     let context = ToolContext {
                         session_id: session_id.to_string(),
                         user_id: user_id.map(|s| s.to_string()),
+                        continuity_key: continuity_key.map(|s| s.to_string()),
                         heartbeat_depth,
                     };
-                    let output = match self.find_tool(name) {
-                        Some(tool) => tool
-                            .execute(&context, input.clone())
-                            .await
-                            .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                        None => ToolOutput::error(format!("unknown tool: {}", name)),
-                    };
+                    let output = self.run_tool(&context, name, input.clone()).await;
                     tool_results.push(ContentBlock::ToolResult {
                         tool_use_id: id.clone(),
                         content: output.content,
@@ -979,9 +1359,12 @@ impl AgentRuntime {
         continuity_key: Option<&str>,
         user_id: Option<&str>,
     ) -> Result<String> {
-        let provider: Arc<dyn LlmProvider> = self
-            .default_provider()
-            .ok_or_else(|| Error::Agent("no LLM provider configured".into()))?;
+        let overrides = self.session_overrides(session_id).unwrap_or_default();
+        let provider = self.session_provider(&overrides)?;
+        let system_prompt = overrides
+            .system_prompt
+            .clone()
+            .or_else(|| self.system_prompt.clone());
 
         // Build system message (same as process_message)
         let memory_context = match self
@@ -1007,10 +1390,12 @@ impl AgentRuntime {
             _ => None,
         };
 
+        let facts_context = self.facts_context(continuity_key, user_id).await;
         let dna = self.dna_content();
         let system = build_system_prompt(
             dna.as_deref(),
-            &self.system_prompt,
+            &system_prompt,
+            facts_context.as_deref(),
             memory_context.as_deref(),
             None,
         );
@@ -1028,12 +1413,13 @@ impl AgentRuntime {
 
         let mut full_response = String::new();
 
+        let mut media = HashMap::new();
         for _iteration in 0..MAX_TOOL_ITERATIONS {
             let request = LlmRequest {
-                model: String::new(),
-                messages: messages.clone(),
+                model: overrides.model.clone().unwrap_or_default(),
+                messages: self.resolve_media(&messages, &mut media).await,
                 system: system.clone(),
-                max_tokens: Some(self.max_tokens.unwrap_or(4096)),
+                max_tokens: Some(overrides.max_tokens.or(self.max_tokens).unwrap_or(4096)),
                 temperature: None,
                 tools: tool_defs.clone(),
             };
@@ -1069,8 +1455,10 @@ impl AgentRuntime {
                                 }
                             }
                             StreamEvent::MessageDelta {
-                                stop_reason: sr, ..
+                                stop_reason: sr,
+                                usage,
                             } => {
+                                self.record_usage(session_id, usage.as_ref());
                                 _stop_reason = sr;
                             }
                             StreamEvent::MessageStop => break,
@@ -1128,15 +1516,10 @@ impl AgentRuntime {
                         let context = ToolContext {
                             session_id: session_id.to_string(),
                             user_id: user_id.map(|s| s.to_string()),
+                            continuity_key: continuity_key.map(|s| s.to_string()),
                             heartbeat_depth: 0,
                         };
-                        let output = match self.find_tool(name) {
-                            Some(tool) => tool
-                                .execute(&context, input)
-                                .await
-                                .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                            None => ToolOutput::error(format!("unknown tool: {}", name)),
-                        };
+                        let output = self.run_tool(&context, name, input).await;
                         tool_results.push(ContentBlock::ToolResult {
                             tool_use_id: id.clone(),
                             content: output.content,
@@ -1157,6 +1540,7 @@ impl AgentRuntime {
                 Err(_) => {
                     // Streaming not supported — fall back to non-streaming
                     let response = provider.complete(&request).await?;
+                    self.record_usage(session_id, response.usage.as_ref());
 
                     let has_tool_use = response
                         .content
@@ -1195,15 +1579,10 @@ impl AgentRuntime {
                             let context = ToolContext {
                                 session_id: session_id.to_string(),
                                 user_id: user_id.map(|s| s.to_string()),
+                                continuity_key: continuity_key.map(|s| s.to_string()),
                                 heartbeat_depth: 0,
                             };
-                            let output = match self.find_tool(name) {
-                                Some(tool) => tool
-                                    .execute(&context, input.clone())
-                                    .await
-                                    .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                                None => ToolOutput::error(format!("unknown tool: {}", name)),
-                            };
+                            let output = self.run_tool(&context, name, input.clone()).await;
                             tool_results.push(ContentBlock::ToolResult {
                                 tool_use_id: id.clone(),
                                 content: output.content,
@@ -1239,9 +1618,12 @@ impl AgentRuntime {
         user_id: Option<&str>,
         heartbeat_depth: u8,
     ) -> Result<(String, Option<String>)> {
-        let provider: Arc<dyn LlmProvider> = self
-            .default_provider()
-            .ok_or_else(|| Error::Agent("no LLM provider configured".into()))?;
+        let overrides = self.session_overrides(session_id).unwrap_or_default();
+        let provider = self.session_provider(&overrides)?;
+        let system_prompt = overrides
+            .system_prompt
+            .clone()
+            .or_else(|| self.system_prompt.clone());
 
         let memory_context = match self
             .recall_context(
@@ -1266,10 +1648,12 @@ impl AgentRuntime {
             _ => None,
         };
 
+        let facts_context = self.facts_context(continuity_key, user_id).await;
         let dna = self.dna_content();
         let system = build_system_prompt(
             dna.as_deref(),
-            &self.system_prompt,
+            &system_prompt,
+            facts_context.as_deref(),
             memory_context.as_deref(),
             session_summary,
         );
@@ -1298,7 +1682,8 @@ impl AgentRuntime {
         let system = if new_summary.is_some() {
             build_system_prompt(
                 dna.as_deref(),
-                &self.system_prompt,
+                &system_prompt,
+                facts_context.as_deref(),
                 memory_context.as_deref(),
                 new_summary.as_deref(),
             )
@@ -1306,18 +1691,21 @@ impl AgentRuntime {
             system
         };
 
+        let mut media = HashMap::new();
         for _iteration in 0..MAX_TOOL_ITERATIONS {
             let request = LlmRequest {
-                model: String::new(),
-                messages: messages.clone(),
+                model: overrides.model.clone().unwrap_or_default(),
+                messages: self.resolve_media(&messages, &mut media).await,
                 system: system.clone(),
-                max_tokens: Some(self.max_tokens.unwrap_or(4096)),
+                max_tokens: Some(overrides.max_tokens.or(self.max_tokens).unwrap_or(4096)),
                 temperature: None,
                 tools: tool_defs.clone(),
             };
 
             let response = provider.complete(&request).await?;
 
+            self.record_usage(session_id, response.usage.as_ref());
+
             let has_tool_use = response
                 .content
                 .iter()
@@ -1353,15 +1741,10 @@ impl AgentRuntime {
                     let context = ToolContext {
                         session_id: session_id.to_string(),
                         user_id: user_id.map(|s| s.to_string()),
+                        continuity_key: continuity_key.map(|s| s.to_string()),
                         heartbeat_depth,
                     };
-                    let output = match self.find_tool(name) {
-                        Some(tool) => tool
-                            .execute(&context, input.clone())
-                            .await
-                            .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                        None => ToolOutput::error(format!("unknown tool: {}", name)),
-                    };
+                    let output = self.run_tool(&context, name, input.clone()).await;
                     tool_results.push(ContentBlock::ToolResult {
                         tool_use_id: id.clone(),
                         content: output.content,
@@ -1395,9 +1778,12 @@ impl AgentRuntime {
         continuity_key: Option<&str>,
         user_id: Option<&str>,
     ) -> Result<(String, Option<String>)> {
-        let provider: Arc<dyn LlmProvider> = self
-            .default_provider()
-            .ok_or_else(|| Error::Agent("no LLM provider configured".into()))?;
+        let overrides = self.session_overrides(session_id).unwrap_or_default();
+        let provider = self.session_provider(&overrides)?;
+        let system_prompt = overrides
+            .system_prompt
+            .clone()
+            .or_else(|| self.system_prompt.clone());
 
         let memory_context = match self
             .recall_context(
@@ -1422,10 +1808,12 @@ impl AgentRuntime {
             _ => None,
         };
 
+        let facts_context = self.facts_context(continuity_key, user_id).await;
         let dna = self.dna_content();
         let system = build_system_prompt(
             dna.as_deref(),
-            &self.system_prompt,
+            &system_prompt,
+            facts_context.as_deref(),
             memory_context.as_deref(),
             session_summary,
         );
@@ -1453,7 +1841,8 @@ impl AgentRuntime {
         let system = if new_summary.is_some() {
             build_system_prompt(
                 dna.as_deref(),
-                &self.system_prompt,
+                &system_prompt,
+                facts_context.as_deref(),
                 memory_context.as_deref(),
                 new_summary.as_deref(),
             )
@@ -1463,12 +1852,13 @@ impl AgentRuntime {
 
         let mut full_response = String::new();
 
+        let mut media = HashMap::new();
         for _iteration in 0..MAX_TOOL_ITERATIONS {
             let request = LlmRequest {
-                model: String::new(),
-                messages: messages.clone(),
+                model: overrides.model.clone().unwrap_or_default(),
+                messages: self.resolve_media(&messages, &mut media).await,
                 system: system.clone(),
-                max_tokens: Some(self.max_tokens.unwrap_or(4096)),
+                max_tokens: Some(overrides.max_tokens.or(self.max_tokens).unwrap_or(4096)),
                 temperature: None,
                 tools: tool_defs.clone(),
             };
@@ -1502,8 +1892,10 @@ impl AgentRuntime {
                                 }
                             }
                             StreamEvent::MessageDelta {
-                                stop_reason: sr, ..
+                                stop_reason: sr,
+                                usage,
                             } => {
+                                self.record_usage(session_id, usage.as_ref());
                                 _stop_reason = sr;
                             }
                             StreamEvent::MessageStop => break,
@@ -1559,15 +1951,10 @@ impl AgentRuntime {
                         let context = ToolContext {
                             session_id: session_id.to_string(),
                             user_id: user_id.map(|s| s.to_string()),
+                            continuity_key: continuity_key.map(|s| s.to_string()),
                             heartbeat_depth: 0,
                         };
-                        let output = match self.find_tool(name) {
-                            Some(tool) => tool
-                                .execute(&context, input)
-                                .await
-                                .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                            None => ToolOutput::error(format!("unknown tool: {}", name)),
-                        };
+                        let output = self.run_tool(&context, name, input).await;
                         tool_results.push(ContentBlock::ToolResult {
                             tool_use_id: id.clone(),
                             content: output.content,
@@ -1586,6 +1973,7 @@ impl AgentRuntime {
                 }
                 Err(_) => {
                     let response = provider.complete(&request).await?;
+                    self.record_usage(session_id, response.usage.as_ref());
 
                     let has_tool_use = response
                         .content
@@ -1624,15 +2012,10 @@ impl AgentRuntime {
                             let context = ToolContext {
                                 session_id: session_id.to_string(),
                                 user_id: user_id.map(|s| s.to_string()),
+                                continuity_key: continuity_key.map(|s| s.to_string()),
                                 heartbeat_depth: 0,
                             };
-                            let output = match self.find_tool(name) {
-                                Some(tool) => tool
-                                    .execute(&context, input.clone())
-                                    .await
-                                    .unwrap_or_else(|e| ToolOutput::error(e.to_string())),
-                                None => ToolOutput::error(format!("unknown tool: {}", name)),
-                            };
+                            let output = self.run_tool(&context, name, input.clone()).await;
                             tool_results.push(ContentBlock::ToolResult {
                                 tool_use_id: id.clone(),
                                 content: output.content,
@@ -1909,12 +2292,13 @@ fn bootstrap_instruction() -> String {
     )
 }
 
-/// Build the system prompt by combining DNA content, system prompt, memory context,
-/// and conversation summary. When no DNA content exists, a bootstrap instruction is
+/// Build the system prompt by combining DNA content, system prompt, distilled user
+/// facts, memory context, and conversation summary. When no DNA content exists, a bootstrap instruction is
 /// injected so the agent can collect user preferences on first interaction.
 fn build_system_prompt(
     dna_content: Option<&str>,
     system_prompt: &Option<String>,
+    user_facts: Option<&str>,
     memory_context: Option<&str>,
     session_summary: Option<&str>,
 ) -> Option<String> {
@@ -1927,6 +2311,9 @@ fn build_system_prompt(
     if let Some(prompt) = system_prompt {
         parts.push(prompt.clone());
     }
+    if let Some(facts) = user_facts {
+        parts.push(facts.to_string());
+    }
     if let Some(ctx) = memory_context {
         parts.push(ctx.to_string());
     }
@@ -1936,7 +2323,7 @@ fn build_system_prompt(
     Some(parts.join("\n\n"))
 }
 
-fn extract_text(content: &[ContentBlock]) -> String {
+pub(crate) fn extract_text(content: &[ContentBlock]) -> String {
     content
         .iter()
         .filter_map(|block| match block {
@@ -1958,12 +2345,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn session_overrides_are_cleared_when_empty() {
+        let runtime = AgentRuntime::new();
+        let overrides = SessionOverrides {
+            model: Some("small".to_string()),
+            ..Default::default()
+        };
+        runtime.set_session_overrides("s1", overrides.clone());
+        assert_eq!(runtime.session_overrides("s1"), Some(overrides));
+
+        runtime.set_session_overrides("s1", SessionOverrides::default());
+        assert_eq!(runtime.session_overrides("s1"), None);
+    }
+
+    #[test]
+    fn usage_accumulates_per_session() {
+        let runtime = AgentRuntime::new();
+        let usage = Usage {
+            input_tokens: 10,
+            output_tokens: 4,
+        };
+        runtime.record_usage("s1", Some(&usage));
+        runtime.record_usage("s1", None);
+        runtime.record_usage("s2", Some(&usage));
+
+        let s1 = runtime.session_usage("s1").unwrap();
+        assert_eq!((s1.requests, s1.input_tokens, s1.output_tokens), (2, 10, 4));
+        let total = runtime.total_usage();
+        assert_eq!((total.requests, total.input_tokens), (3, 20));
+        assert!(runtime.session_usage("s3").is_none());
+    }
+
     #[test]
     fn build_system_prompt_all_parts() {
         let sys = Some("You are helpful.".to_string());
         let mem = Some("User likes Rust.");
         let sum = Some("We discussed project setup.");
-        let result = build_system_prompt(None, &sys, mem, sum).unwrap();
+        let result = build_system_prompt(None, &sys, None, mem, sum).unwrap();
         assert!(result.contains("You are helpful."));
         assert!(result.contains("User likes Rust."));
         assert!(result.contains("Conversation summary:"));
@@ -1973,7 +2392,7 @@ mod tests {
     #[test]
     fn build_system_prompt_no_summary() {
         let sys = Some("You are helpful.".to_string());
-        let result = build_system_prompt(Some("Be kind."), &sys, None, None).unwrap();
+        let result = build_system_prompt(Some("Be kind."), &sys, None, None, None).unwrap();
         assert!(result.contains("You are helpful."));
         assert!(result.contains("Be kind."));
         assert!(!result.contains("Conversation summary:"));
@@ -1981,7 +2400,7 @@ mod tests {
 
     #[test]
     fn build_system_prompt_summary_only() {
-        let result = build_system_prompt(None, &None, None, Some("A summary.")).unwrap();
+        let result = build_system_prompt(None, &None, None, None, Some("A summary.")).unwrap();
         assert!(result.contains("Conversation summary:"));
         assert!(result.contains("A summary."));
     }
@@ -1989,7 +2408,7 @@ mod tests {
     #[test]
     fn build_system_prompt_bootstrap_when_all_empty() {
         // When no DNA content is provided, bootstrap instruction is injected
-        let result = build_system_prompt(None, &None, None, None).unwrap();
+        let result = build_system_prompt(None, &None, None, None, None).unwrap();
         assert!(result.contains("have not been personalized yet"));
     }
 
@@ -1997,7 +2416,7 @@ mod tests {
     fn build_system_prompt_with_dna_content() {
         let dna = Some("You are a pirate. Always say arrr.");
         let sys = Some("You are helpful.".to_string());
-        let result = build_system_prompt(dna, &sys, None, None).unwrap();
+        let result = build_system_prompt(dna, &sys, None, None, None).unwrap();
         // DNA should come before system prompt
         let dna_pos = result.find("pirate").unwrap();
         let sys_pos = result.find("helpful").unwrap();
@@ -2006,13 +2425,24 @@ mod tests {
 
     #[test]
     fn build_system_prompt_dna_only() {
-        let result = build_system_prompt(Some("You are a pirate."), &None, None, None).unwrap();
+        let result =
+            build_system_prompt(Some("You are a pirate."), &None, None, None, None).unwrap();
         assert_eq!(result, "You are a pirate.");
     }
 
+    #[test]
+    fn build_system_prompt_facts_precede_recall() {
+        let facts = Some("Known facts about the user:\n- Lives in Porto");
+        let mem = Some("Relevant context from memory:\n- talked about trains");
+        let result = build_system_prompt(Some("dna"), &None, facts, mem, None).unwrap();
+        let facts_pos = result.find("Lives in Porto").unwrap();
+        let mem_pos = result.find("talked about trains").unwrap();
+        assert!(facts_pos < mem_pos);
+    }
+
     #[test]
     fn build_system_prompt_bootstrap_when_no_dna() {
-        let result = build_system_prompt(None, &None, None, None).unwrap();
+        let result = build_system_prompt(None, &None, None, None, None).unwrap();
         assert!(result.contains("have not been personalized yet"));
         assert!(result.contains("dna.md"));
     }
@@ -2052,6 +2482,56 @@ mod tests {
         assert_eq!(messages.len(), original_len);
     }
 
+    #[tokio::test]
+    async fn tool_guard_refuses_calls_before_execution() {
+        struct EchoTool;
+        #[async_trait::async_trait]
+        impl Tool for EchoTool {
+            fn name(&self) -> &str {
+                "echo"
+            }
+            fn description(&self) -> &str {
+                "Echo the input"
+            }
+            fn input_schema(&self) -> serde_json::Value {
+                serde_json::json!({"type": "object"})
+            }
+            async fn execute(
+                &self,
+                _context: &ToolContext,
+                input: serde_json::Value,
+            ) -> Result<ToolOutput> {
+                Ok(ToolOutput::success(input.to_string()))
+            }
+        }
+
+        let mut runtime = AgentRuntime::new();
+        runtime.register_tool(Box::new(EchoTool));
+        let context = |user: &str| ToolContext {
+            session_id: "s1".to_string(),
+            user_id: Some(user.to_string()),
+            continuity_key: None,
+            heartbeat_depth: 0,
+        };
+        runtime.set_tool_guard(Arc::new(|context: &ToolContext, tool: &str| match context
+            .user_id
+            .as_deref()
+        {
+            Some("guest") => Err(format!("{tool} is not available to guests")),
+            _ => Ok(()),
+        }));
+
+        let output = runtime
+            .run_tool(&context("member"), "echo", serde_json::json!(1))
+            .await;
+        assert!(!output.is_error);
+        let output = runtime
+            .run_tool(&context("guest"), "echo", serde_json::json!(1))
+            .await;
+        assert!(output.is_error);
+        assert_eq!(output.content, "echo is not available to guests");
+    }
+
     #[tokio::test]
     async fn compact_messages_under_budget_returns_none() {
         struct NeverCallProvider;
@@ -2179,6 +2659,71 @@ mod tests {
         assert!(messages.len() < 3);
     }
 
+    #[tokio::test]
+    async fn extract_pending_facts_skips_a_failing_batch() {
+        struct FlakyExtractor;
+        #[async_trait::async_trait]
+        impl LlmProvider for FlakyExtractor {
+            fn provider_id(&self) -> &str {
+                "mock"
+            }
+            async fn complete(
+                &self,
+                request: &LlmRequest,
+            ) -> Result<crate::providers::LlmResponse> {
+                let MessagePart::Text(input) = &request.messages[0].content else {
+                    panic!("expected a text transcript");
+                };
+                if input.contains("boom") {
+                    return Err(Error::Agent("provider unavailable".into()));
+                }
+                Ok(crate::providers::LlmResponse {
+                    content: vec![ContentBlock::Text {
+                        text: "[{\"kind\":\"fact\",\"subject\":\"home_city\",\"content\":\"Lives in Porto\",\"confidence\":0.9}]".to_string(),
+                    }],
+                    model: String::new(),
+                    usage: None,
+                    stop_reason: None,
+                })
+            }
+            async fn health_check(&self) -> Result<bool> {
+                Ok(true)
+            }
+        }
+
+        let memory: Arc<dyn MemoryProvider> =
+            Arc::new(opencrust_db::MemoryStore::in_memory().unwrap());
+        for (session, content) in [("a", "boom"), ("b", "I live in Porto")] {
+            memory
+                .remember(NewMemoryEntry {
+                    session_id: session.to_string(),
+                    channel_id: None,
+                    user_id: Some(format!("user-{session}")),
+                    continuity_key: None,
+                    role: MemoryRole::User,
+                    content: content.to_string(),
+                    embedding: None,
+                    embedding_model: None,
+                    metadata: serde_json::json!({ "kind": "turn_user" }),
+                })
+                .await
+                .unwrap();
+        }
+        let mut runtime = AgentRuntime::new();
+        runtime.set_memory_provider(Arc::clone(&memory));
+        runtime.register_provider(Arc::new(FlakyExtractor));
+
+        assert_eq!(runtime.extract_pending_facts(10).await.unwrap(), 1);
+        let pending = memory.pending_fact_turns(10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].session_id, "a");
+
+        for _ in 1..MAX_FACT_EXTRACTION_ATTEMPTS {
+            assert_eq!(runtime.extract_pending_facts(10).await.unwrap(), 0);
+        }
+        assert!(memory.pending_fact_turns(10).await.unwrap().is_empty());
+    }
+
     #[test]
     fn estimate_tokens_basic() {
         let messages = vec![make_msg(ChatRole::User, "hello world")]; // 11 chars
@@ -2211,4 +2756,39 @@ mod tests {
         runtime.set_summarization_enabled(false);
         assert!(!runtime.summarization_enabled);
     }
+
+    #[tokio::test]
+    async fn resolve_media_inlines_stored_images() {
+        let dir = std::env::temp_dir().join("opencrust_runtime_media_test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = Arc::new(MediaStore::open(&dir).unwrap());
+        let stored = store.put(b"\xff\xd8\xff\xe0jpeg", None, None).unwrap();
+
+        let mut runtime = AgentRuntime::new();
+        runtime.set_media_store(Arc::clone(&store));
+        let missing = format!("media://{}", "0".repeat(64));
+        let messages = vec![ChatMessage {
+            role: ChatRole::User,
+            content: MessagePart::Parts(vec![
+                ContentBlock::Image { url: stored.url() },
+                ContentBlock::Image { url: missing },
+                ContentBlock::Image {
+                    url: "https://example.com/cat.png".to_string(),
+                },
+            ]),
+        }];
+
+        let mut loaded = HashMap::new();
+        let resolved = runtime.resolve_media(&messages, &mut loaded).await;
+        let MessagePart::Parts(blocks) = &resolved[0].content else {
+            panic!("expected parts");
+        };
+        assert!(
+            matches!(&blocks[0], ContentBlock::Image { url } if url.starts_with("data:image/jpeg;base64,"))
+        );
+        assert!(matches!(&blocks[1], ContentBlock::Text { .. }));
+        assert!(
+            matches!(&blocks[2], ContentBlock::Image { url } if url == "https://example.com/cat.png")
+        );
+    }
 }
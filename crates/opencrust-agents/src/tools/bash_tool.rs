@@ -128,6 +128,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let output = tool
@@ -149,6 +150,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let output = tool
@@ -164,6 +166,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let result = tool.execute(&ctx, serde_json::json!({})).await;
@@ -181,6 +184,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let output = tool
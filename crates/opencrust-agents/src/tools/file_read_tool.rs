@@ -111,6 +111,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let output = tool
@@ -130,6 +131,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let result = tool
@@ -144,6 +146,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let result = tool.execute(&ctx, serde_json::json!({})).await;
@@ -129,6 +129,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let output = tool
@@ -153,6 +154,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         assert!(tool.execute(&ctx, serde_json::json!({})).await.is_err());
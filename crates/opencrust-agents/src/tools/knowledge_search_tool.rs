@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+use opencrust_common::{Error, Result};
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::knowledge::KnowledgeIndexer;
+use crate::tools::{Tool, ToolContext, ToolOutput};
+
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 15;
+
+/// Searches documents ingested into the knowledge base and returns passages
+/// labelled with citations the model can quote back to the user.
+pub struct KnowledgeSearchTool {
+    indexer: Arc<KnowledgeIndexer>,
+}
+
+impl KnowledgeSearchTool {
+    pub fn new(indexer: Arc<KnowledgeIndexer>) -> Self {
+        Self { indexer }
+    }
+}
+
+#[async_trait]
+impl Tool for KnowledgeSearchTool {
+    fn name(&self) -> &'static str {
+        "knowledge_search"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search the user's indexed documents (notes, Markdown, PDFs) for passages relevant to \
+         a question. Use this before answering questions about the user's own files or \
+         projects. Base your answer on the returned passages and cite them with their \
+         bracketed labels, e.g. [notes/travel.md#2]."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to look for, phrased as a question or keywords."
+                },
+                "sources": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Optional source names to restrict the search to."
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum passages to return (default 5, max 15)."
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn execute(&self, _context: &ToolContext, args: serde_json::Value) -> Result<ToolOutput> {
+        let query = args["query"]
+            .as_str()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| Error::Agent("missing or invalid 'query' argument".to_string()))?;
+        let sources: Vec<String> = args["sources"]
+            .as_array()
+            .map(|s| {
+                s.iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let limit = args["limit"]
+            .as_u64()
+            .map(|l| (l as usize).clamp(1, MAX_LIMIT))
+            .unwrap_or(DEFAULT_LIMIT);
+
+        let hits = self.indexer.search(query, sources, limit).await?;
+        if hits.is_empty() {
+            return Ok(ToolOutput::success(
+                "No matching passages in the knowledge base.",
+            ));
+        }
+
+        let mut out = String::new();
+        for hit in hits {
+            out.push_str(&format!("[{}]\n{}\n\n", hit.citation(), hit.content.trim()));
+        }
+        Ok(ToolOutput::success(out.trim_end().to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencrust_db::{KnowledgeStore, NewKnowledgeChunk};
+
+    #[tokio::test]
+    async fn returns_cited_passages() {
+        let store = Arc::new(KnowledgeStore::in_memory().unwrap());
+        let source = store.upsert_source("notes", "/notes").unwrap();
+        store
+            .replace_document(
+                &source.id,
+                "garden.md",
+                "h",
+                vec![NewKnowledgeChunk {
+                    content: "Tomatoes need watering every morning".into(),
+                    embedding: None,
+                    embedding_model: None,
+                }],
+            )
+            .unwrap();
+        let tool = KnowledgeSearchTool::new(Arc::new(KnowledgeIndexer::new(store, None)));
+        let ctx = ToolContext {
+            session_id: "s".into(),
+            user_id: None,
+            continuity_key: None,
+            heartbeat_depth: 0,
+        };
+
+        let out = tool
+            .execute(&ctx, json!({ "query": "tomatoes watering" }))
+            .await
+            .unwrap();
+        assert!(out.content.starts_with("[notes/garden.md#1]"));
+
+        let none = tool
+            .execute(&ctx, json!({ "query": "bicycle" }))
+            .await
+            .unwrap();
+        assert!(none.content.contains("No matching"));
+    }
+}
@@ -0,0 +1,517 @@
+use async_trait::async_trait;
+use opencrust_common::{Error, Result};
+use opencrust_db::{MemoryEntry, MemoryProvider, MemoryRole, NewMemoryEntry, RecallQuery};
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::embeddings::EmbeddingProvider;
+use crate::tools::{Tool, ToolContext, ToolOutput};
+
+/// Maximum length of a single saved memory.
+const MAX_MEMORY_CHARS: usize = 4000;
+
+/// Maximum number of tags per saved memory.
+const MAX_TAGS: usize = 10;
+
+/// Default / maximum number of search results.
+const DEFAULT_SEARCH_LIMIT: usize = 5;
+const MAX_SEARCH_LIMIT: usize = 20;
+
+/// Metadata `kind` for memories written through `memory_save`.
+const NOTE_KIND: &str = "note";
+
+/// Continuity-key prefix for one person's linked accounts. Such a key is
+/// never shared between people, so entries under it are in scope whichever of
+/// the person's accounts saved them.
+pub const PERSON_KEY_PREFIX: &str = "person:";
+
+/// The user an entry must belong to on top of the continuity key: keys such
+/// as the shared memory bus hold every user's memories.
+fn scope_user(context: &ToolContext) -> Option<&String> {
+    match &context.continuity_key {
+        Some(key) if key.starts_with(PERSON_KEY_PREFIX) => None,
+        _ => context.user_id.as_ref(),
+    }
+}
+
+/// Whether a memory entry belongs to the caller's scope: the continuity key
+/// and the user when both are set, otherwise whichever is; otherwise the
+/// session.
+fn in_scope(context: &ToolContext, entry: &MemoryEntry) -> bool {
+    if let Some(key) = &context.continuity_key {
+        return entry.continuity_key.as_ref() == Some(key)
+            && scope_user(context).is_none_or(|user| entry.user_id.as_ref() == Some(user));
+    }
+    if let Some(user) = &context.user_id {
+        return entry.user_id.as_ref() == Some(user);
+    }
+    entry.session_id == context.session_id
+}
+
+fn scoped_query(context: &ToolContext) -> RecallQuery {
+    let (session_id, continuity_key, user_id) = match &context.continuity_key {
+        Some(key) => (None, Some(key.clone()), scope_user(context).cloned()),
+        None => match &context.user_id {
+            Some(user) => (None, None, Some(user.clone())),
+            None => (Some(context.session_id.clone()), None, None),
+        },
+    };
+    RecallQuery {
+        query_text: None,
+        query_embedding: None,
+        session_id,
+        continuity_key,
+        user_id,
+        limit: 0,
+    }
+}
+
+fn entry_tags(entry: &MemoryEntry) -> Vec<String> {
+    entry.metadata["tags"]
+        .as_array()
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|t| t.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_lowercase()
+}
+
+// ---------------------------------------------------------------------------
+// MemorySave
+// ---------------------------------------------------------------------------
+
+/// Tool for deliberately storing something the user wants remembered.
+pub struct MemorySave {
+    memory: Arc<dyn MemoryProvider>,
+    embeddings: Option<Arc<dyn EmbeddingProvider>>,
+}
+
+impl MemorySave {
+    pub fn new(
+        memory: Arc<dyn MemoryProvider>,
+        embeddings: Option<Arc<dyn EmbeddingProvider>>,
+    ) -> Self {
+        Self { memory, embeddings }
+    }
+}
+
+#[async_trait]
+impl Tool for MemorySave {
+    fn name(&self) -> &'static str {
+        "memory_save"
+    }
+
+    fn description(&self) -> &'static str {
+        "Save a piece of information to long-term memory so it can be recalled in later \
+         conversations, including on other channels. Use this when the user asks you to \
+         remember something, or states a detail they will obviously want remembered. \
+         Write the content as a self-contained sentence (e.g. 'The user's flat number is 12')."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "content": {
+                    "type": "string",
+                    "description": "The information to remember, as a self-contained sentence."
+                },
+                "tags": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Optional short tags for filtering later (e.g. ['address', 'home'])."
+                },
+                "importance": {
+                    "type": "number",
+                    "description": "How important this is, from 0.0 (trivia) to 1.0 (critical). Defaults to 0.5."
+                }
+            },
+            "required": ["content"]
+        })
+    }
+
+    async fn execute(&self, context: &ToolContext, args: serde_json::Value) -> Result<ToolOutput> {
+        let content = args["content"]
+            .as_str()
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .ok_or_else(|| Error::Agent("missing or invalid 'content' argument".to_string()))?;
+        if content.chars().count() > MAX_MEMORY_CHARS {
+            return Ok(ToolOutput::error(format!(
+                "memory too long (max {MAX_MEMORY_CHARS} characters); save a shorter summary"
+            )));
+        }
+
+        let tags: Vec<String> = args["tags"]
+            .as_array()
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(|t| t.as_str())
+                    .map(normalize_tag)
+                    .filter(|t| !t.is_empty())
+                    .take(MAX_TAGS)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let importance = args["importance"].as_f64().unwrap_or(0.5).clamp(0.0, 1.0);
+
+        let (embedding, embedding_model) = match &self.embeddings {
+            Some(provider) => (
+                provider
+                    .embed_documents(&[content.to_string()])
+                    .await
+                    .ok()
+                    .and_then(|mut v| v.pop()),
+                Some(provider.model().to_string()),
+            ),
+            None => (None, None),
+        };
+
+        let id = self
+            .memory
+            .remember(NewMemoryEntry {
+                session_id: context.session_id.clone(),
+                channel_id: None,
+                user_id: context.user_id.clone(),
+                continuity_key: context.continuity_key.clone(),
+                role: MemoryRole::System,
+                content: content.to_string(),
+                embedding,
+                embedding_model,
+                metadata: json!({
+                    "kind": NOTE_KIND,
+                    "tags": tags,
+                    "importance": importance,
+                }),
+            })
+            .await?;
+
+        Ok(ToolOutput::success(format!("Saved to memory (id: {id}).")))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MemorySearch
+// ---------------------------------------------------------------------------
+
+/// Tool for explicitly searching long-term memory.
+pub struct MemorySearch {
+    memory: Arc<dyn MemoryProvider>,
+    embeddings: Option<Arc<dyn EmbeddingProvider>>,
+}
+
+impl MemorySearch {
+    pub fn new(
+        memory: Arc<dyn MemoryProvider>,
+        embeddings: Option<Arc<dyn EmbeddingProvider>>,
+    ) -> Self {
+        Self { memory, embeddings }
+    }
+}
+
+#[async_trait]
+impl Tool for MemorySearch {
+    fn name(&self) -> &'static str {
+        "memory_search"
+    }
+
+    fn description(&self) -> &'static str {
+        "Search long-term memory for information from earlier conversations or things the \
+         user asked you to remember. Returns matching memories with their IDs, tags and dates."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to look for (keywords or a natural-language question)."
+                },
+                "tags": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Only return memories carrying all of these tags."
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of results (default 5, max 20)."
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn execute(&self, context: &ToolContext, args: serde_json::Value) -> Result<ToolOutput> {
+        let query = args["query"]
+            .as_str()
+            .map(str::trim)
+            .filter(|q| !q.is_empty())
+            .ok_or_else(|| Error::Agent("missing or invalid 'query' argument".to_string()))?;
+        let limit = args["limit"]
+            .as_u64()
+            .map(|l| (l as usize).clamp(1, MAX_SEARCH_LIMIT))
+            .unwrap_or(DEFAULT_SEARCH_LIMIT);
+        let wanted_tags: Vec<String> = args["tags"]
+            .as_array()
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(|t| t.as_str())
+                    .map(normalize_tag)
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let query_embedding = match &self.embeddings {
+            Some(provider) => provider.embed_query(query).await.ok(),
+            None => None,
+        };
+
+        let mut recall = scoped_query(context);
+        recall.query_text = Some(query.to_string());
+        recall.query_embedding = query_embedding;
+        // Over-fetch so tag filtering still leaves enough results.
+        recall.limit = if wanted_tags.is_empty() {
+            limit
+        } else {
+            MAX_SEARCH_LIMIT * 2
+        };
+
+        let results: Vec<MemoryEntry> = self
+            .memory
+            .recall(recall)
+            .await?
+            .into_iter()
+            .filter(|entry| in_scope(context, entry))
+            .filter(|entry| {
+                let tags = entry_tags(entry);
+                wanted_tags.iter().all(|t| tags.contains(t))
+            })
+            .take(limit)
+            .collect();
+
+        if results.is_empty() {
+            return Ok(ToolOutput::success("No matching memories found."));
+        }
+
+        let mut out = format!("Found {} memories:\n", results.len());
+        for entry in &results {
+            let tags = entry_tags(entry);
+            let tag_str = if tags.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", tags.join(", "))
+            };
+            out.push_str(&format!(
+                "- id: {} ({}){}: {}\n",
+                entry.id,
+                entry.created_at.format("%Y-%m-%d"),
+                tag_str,
+                entry.content
+            ));
+        }
+        Ok(ToolOutput::success(out))
+    }
+}
+
+// ---------------------------------------------------------------------------
+// MemoryForget
+// ---------------------------------------------------------------------------
+
+/// Tool for deleting a memory the user wants forgotten.
+pub struct MemoryForget {
+    memory: Arc<dyn MemoryProvider>,
+}
+
+impl MemoryForget {
+    pub fn new(memory: Arc<dyn MemoryProvider>) -> Self {
+        Self { memory }
+    }
+}
+
+#[async_trait]
+impl Tool for MemoryForget {
+    fn name(&self) -> &'static str {
+        "memory_forget"
+    }
+
+    fn description(&self) -> &'static str {
+        "Delete a memory by its ID (as returned by memory_search). Use this when the user \
+         asks you to forget something or a saved memory is wrong."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "id": {
+                    "type": "string",
+                    "description": "The memory ID to delete."
+                }
+            },
+            "required": ["id"]
+        })
+    }
+
+    async fn execute(&self, context: &ToolContext, args: serde_json::Value) -> Result<ToolOutput> {
+        let id = args["id"]
+            .as_str()
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .ok_or_else(|| Error::Agent("missing or invalid 'id' argument".to_string()))?;
+
+        // Entries outside the caller's scope are reported as missing so the
+        // model cannot probe other users' memories by ID.
+        match self.memory.get_entry(id).await? {
+            Some(entry) if in_scope(context, &entry) => {
+                self.memory.delete_entry(id).await?;
+                Ok(ToolOutput::success(format!("Memory {id} forgotten.")))
+            }
+            _ => Ok(ToolOutput::error(format!("no memory found with id: {id}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencrust_db::MemoryStore;
+
+    fn test_context(user_id: &str) -> ToolContext {
+        ToolContext {
+            session_id: format!("session-{user_id}"),
+            user_id: Some(user_id.to_string()),
+            continuity_key: None,
+            heartbeat_depth: 0,
+        }
+    }
+
+    fn setup() -> (MemorySave, MemorySearch, MemoryForget) {
+        let memory: Arc<dyn MemoryProvider> =
+            Arc::new(MemoryStore::in_memory().expect("in-memory store"));
+        (
+            MemorySave::new(Arc::clone(&memory), None),
+            MemorySearch::new(Arc::clone(&memory), None),
+            MemoryForget::new(memory),
+        )
+    }
+
+    fn saved_id(output: &ToolOutput) -> String {
+        output
+            .content
+            .split("id: ")
+            .nth(1)
+            .and_then(|s| s.strip_suffix(")."))
+            .expect("save output contains id")
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn save_then_search_round_trip() {
+        let (save, search, _) = setup();
+        let ctx = test_context("alice");
+
+        save.execute(
+            &ctx,
+            json!({ "content": "My flat number is 12", "tags": ["#Address"], "importance": 0.9 }),
+        )
+        .await
+        .unwrap();
+
+        let found = search
+            .execute(&ctx, json!({ "query": "flat number" }))
+            .await
+            .unwrap();
+        assert!(found.content.contains("My flat number is 12"));
+        assert!(found.content.contains("[address]"));
+
+        let tagged = search
+            .execute(&ctx, json!({ "query": "flat", "tags": ["work"] }))
+            .await
+            .unwrap();
+        assert_eq!(tagged.content, "No matching memories found.");
+    }
+
+    #[tokio::test]
+    async fn search_and_forget_are_scoped_per_user() {
+        let (save, search, forget) = setup();
+        let alice = test_context("alice");
+        let bob = test_context("bob");
+
+        let saved = save
+            .execute(&alice, json!({ "content": "Alice's locker code is 4321" }))
+            .await
+            .unwrap();
+        let id = saved_id(&saved);
+
+        let bob_search = search
+            .execute(&bob, json!({ "query": "locker code" }))
+            .await
+            .unwrap();
+        assert_eq!(bob_search.content, "No matching memories found.");
+
+        let bob_forget = forget.execute(&bob, json!({ "id": id })).await.unwrap();
+        assert!(bob_forget.is_error);
+
+        let alice_forget = forget.execute(&alice, json!({ "id": id })).await.unwrap();
+        assert!(!alice_forget.is_error);
+        let after = search
+            .execute(&alice, json!({ "query": "locker code" }))
+            .await
+            .unwrap();
+        assert_eq!(after.content, "No matching memories found.");
+    }
+
+    #[tokio::test]
+    async fn save_rejects_empty_content() {
+        let (save, _, _) = setup();
+        let result = save
+            .execute(&test_context("alice"), json!({ "content": "  " }))
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_shared_continuity_key_still_separates_users() {
+        let (save, search, forget) = setup();
+        let shared = |user_id: &str| ToolContext {
+            continuity_key: Some("bus:shared-global".to_string()),
+            ..test_context(user_id)
+        };
+        let (alice, bob) = (shared("alice"), shared("bob"));
+
+        let saved = save
+            .execute(&alice, json!({ "content": "Alice's locker code is 4321" }))
+            .await
+            .unwrap();
+        let id = saved_id(&saved);
+
+        let bob_search = search
+            .execute(&bob, json!({ "query": "locker code" }))
+            .await
+            .unwrap();
+        assert_eq!(bob_search.content, "No matching memories found.");
+        let bob_forget = forget.execute(&bob, json!({ "id": id })).await.unwrap();
+        assert!(bob_forget.is_error);
+
+        // Accounts linked to one person share their notes.
+        let person = |user_id: &str| ToolContext {
+            continuity_key: Some(format!("{PERSON_KEY_PREFIX}p1")),
+            ..test_context(user_id)
+        };
+        save.execute(&person("42"), json!({ "content": "Flat number is 12" }))
+            .await
+            .unwrap();
+        let found = search
+            .execute(&person("U42"), json!({ "query": "flat number" }))
+            .await
+            .unwrap();
+        assert!(found.content.contains("Flat number is 12"));
+    }
+}
@@ -1,14 +1,20 @@
 pub mod bash_tool;
 pub mod file_read_tool;
 pub mod file_write_tool;
+pub mod knowledge_search_tool;
+pub mod memory;
 pub mod schedule;
+pub mod send_attachment_tool;
 pub mod web_fetch_tool;
 pub mod web_search_tool;
 
 pub use bash_tool::BashTool;
 pub use file_read_tool::FileReadTool;
 pub use file_write_tool::FileWriteTool;
+pub use knowledge_search_tool::KnowledgeSearchTool;
+pub use memory::{MemoryForget, MemorySave, MemorySearch};
 pub use schedule::{CancelHeartbeat, ListHeartbeats, ScheduleHeartbeat};
+pub use send_attachment_tool::{AttachmentSender, SendAttachmentTool};
 pub use web_fetch_tool::WebFetchTool;
 pub use web_search_tool::WebSearchTool;
 
@@ -21,6 +27,9 @@ use serde::{Deserialize, Serialize};
 pub struct ToolContext {
     pub session_id: String,
     pub user_id: Option<String>,
+    /// Cross-channel memory bucket for this turn, when shared continuity is on.
+    #[serde(default)]
+    pub continuity_key: Option<String>,
     /// Heartbeat nesting depth. 0 = normal user request, 1+ = heartbeat execution.
     /// Scheduling is allowed up to depth 3 to enable chaining.
     #[serde(default)]
@@ -390,6 +390,7 @@ mod tests {
         ToolContext {
             session_id: session_id.to_string(),
             user_id: Some("u-1".to_string()),
+            continuity_key: None,
             heartbeat_depth: 0,
         }
     }
@@ -466,6 +467,7 @@ mod tests {
         let context = ToolContext {
             session_id: "sess-1".to_string(),
             user_id: Some("u-1".to_string()),
+            continuity_key: None,
             heartbeat_depth: MAX_HEARTBEAT_DEPTH,
         };
 
@@ -488,6 +490,7 @@ mod tests {
         let context = ToolContext {
             session_id: "sess-1".to_string(),
             user_id: Some("u-1".to_string()),
+            continuity_key: None,
             heartbeat_depth: MAX_HEARTBEAT_DEPTH - 1,
         };
 
@@ -561,6 +564,7 @@ mod tests {
                 &ToolContext {
                     session_id: "s2".to_string(),
                     user_id: Some("u2".to_string()),
+                    continuity_key: None,
                     heartbeat_depth: 0,
                 },
                 serde_json::json!({ "delay_seconds": 60, "reason": "s2 ok" }),
@@ -650,6 +654,7 @@ mod tests {
                 &ToolContext {
                     session_id: "s2".to_string(),
                     user_id: Some("u2".to_string()),
+                    continuity_key: None,
                     heartbeat_depth: 0,
                 },
                 serde_json::json!({ "task_id": task_id }),
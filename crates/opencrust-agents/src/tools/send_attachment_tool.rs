@@ -0,0 +1,351 @@
+use async_trait::async_trait;
+use opencrust_common::{Error, OutboundFile, Result};
+use opencrust_media::MediaStore;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::{Tool, ToolContext, ToolOutput};
+
+/// Largest file the tool will read; channels apply their own, usually lower, limits.
+const MAX_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Delivers a file to the chat a session belongs to.
+///
+/// Implemented by the gateway, which knows each session's channel and the
+/// routing metadata (chat id, phone number) needed to reach it.
+#[async_trait]
+pub trait AttachmentSender: Send + Sync {
+    /// Upload `file` to the session's chat and return the channel type used.
+    async fn send_attachment(&self, session_id: &str, file: OutboundFile) -> Result<String>;
+}
+
+/// Send a workspace file or a stored media item to the user in the current chat.
+pub struct SendAttachmentTool {
+    sender: Arc<dyn AttachmentSender>,
+    media: Option<Arc<MediaStore>>,
+    allowed_directories: Option<Vec<PathBuf>>,
+}
+
+impl SendAttachmentTool {
+    pub fn new(
+        sender: Arc<dyn AttachmentSender>,
+        media: Option<Arc<MediaStore>>,
+        allowed_directories: Option<Vec<PathBuf>>,
+    ) -> Self {
+        Self {
+            sender,
+            media,
+            allowed_directories,
+        }
+    }
+
+    fn validate_path(&self, path: &Path) -> Result<()> {
+        // Reject path traversal
+        if path
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir))
+        {
+            return Err(Error::Security("path traversal not allowed".into()));
+        }
+
+        if let Some(allowed) = &self.allowed_directories {
+            let canonical = path
+                .canonicalize()
+                .map_err(|e| Error::Agent(format!("cannot resolve path: {e}")))?;
+            if !allowed.iter().any(|dir| canonical.starts_with(dir)) {
+                return Err(Error::Security("path outside allowed directories".into()));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn read_path(&self, path_str: &str) -> Result<std::result::Result<OutboundFile, String>> {
+        let path = PathBuf::from(path_str);
+        self.validate_path(&path)?;
+
+        let metadata = tokio::fs::metadata(&path)
+            .await
+            .map_err(|e| Error::Agent(format!("cannot read file metadata: {e}")))?;
+        if !metadata.is_file() {
+            return Ok(Err(format!("not a file: {path_str}")));
+        }
+        if metadata.len() > MAX_ATTACHMENT_BYTES {
+            return Ok(Err(format!(
+                "file too large: {} bytes (limit: {} bytes)",
+                metadata.len(),
+                MAX_ATTACHMENT_BYTES
+            )));
+        }
+
+        let data = tokio::fs::read(&path)
+            .await
+            .map_err(|e| Error::Agent(format!("failed to read file: {e}")))?;
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_string());
+        let mime_type = mime_for(&filename, &data).to_string();
+        Ok(Ok(OutboundFile {
+            data,
+            filename,
+            mime_type,
+            caption: None,
+        }))
+    }
+
+    fn read_media(&self, media_id: &str) -> Result<std::result::Result<OutboundFile, String>> {
+        let Some(store) = &self.media else {
+            return Ok(Err("no media store is configured".to_string()));
+        };
+        let id = opencrust_media::parse_media_url(media_id).unwrap_or(media_id);
+        let Some((record, data)) = store.get(id)? else {
+            return Ok(Err(format!("media {media_id} not found")));
+        };
+        let filename = record.filename.unwrap_or_else(|| {
+            format!("{}.{}", &record.id[..12], extension_for(&record.mime_type))
+        });
+        Ok(Ok(OutboundFile {
+            data,
+            filename,
+            mime_type: record.mime_type,
+            caption: None,
+        }))
+    }
+}
+
+/// MIME type from the file extension, falling back to content sniffing.
+fn mime_for(filename: &str, data: &[u8]) -> &'static str {
+    let ext = Path::new(filename)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let by_ext = match ext.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        "svg" => Some("image/svg+xml"),
+        "pdf" => Some("application/pdf"),
+        "mp4" => Some("video/mp4"),
+        "mp3" => Some("audio/mpeg"),
+        "ogg" | "opus" => Some("audio/ogg"),
+        "wav" => Some("audio/wav"),
+        "csv" => Some("text/csv"),
+        "txt" | "log" => Some("text/plain"),
+        "md" => Some("text/markdown"),
+        "html" | "htm" => Some("text/html"),
+        "json" => Some("application/json"),
+        "zip" => Some("application/zip"),
+        "docx" => Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document"),
+        "xlsx" => Some("application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        "pptx" => Some("application/vnd.openxmlformats-officedocument.presentationml.presentation"),
+        _ => None,
+    };
+    by_ext
+        .or_else(|| opencrust_media::store::sniff_mime(data))
+        .unwrap_or("application/octet-stream")
+}
+
+/// File extension for media stored without a filename.
+fn extension_for(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "application/pdf" => "pdf",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "audio/mpeg" => "mp3",
+        "audio/ogg" => "ogg",
+        "audio/wav" => "wav",
+        "application/zip" => "zip",
+        _ => "bin",
+    }
+}
+
+#[async_trait]
+impl Tool for SendAttachmentTool {
+    fn name(&self) -> &str {
+        "send_attachment"
+    }
+
+    fn description(&self) -> &str {
+        "Send a file or image to the user in the current chat. Provide either a file path \
+         (for example a chart or a file written with file_write) or a media store id \
+         (media://...). Images are shown inline where the channel supports it; other \
+         files are sent as documents."
+    }
+
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "Path of the file to send"
+                },
+                "media_id": {
+                    "type": "string",
+                    "description": "Media store reference (media://<id> or the bare id) to send instead of a path"
+                },
+                "filename": {
+                    "type": "string",
+                    "description": "Filename shown to the user (defaults to the file's own name)"
+                },
+                "caption": {
+                    "type": "string",
+                    "description": "Optional caption sent with the file"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, context: &ToolContext, input: serde_json::Value) -> Result<ToolOutput> {
+        let path = input.get("path").and_then(|v| v.as_str());
+        let media_id = input.get("media_id").and_then(|v| v.as_str());
+
+        let file = match (path, media_id) {
+            (Some(path), None) => self.read_path(path).await?,
+            (None, Some(id)) => self.read_media(id)?,
+            _ => {
+                return Ok(ToolOutput::error(
+                    "provide exactly one of 'path' or 'media_id'",
+                ));
+            }
+        };
+        let mut file = match file {
+            Ok(file) => file,
+            Err(message) => return Ok(ToolOutput::error(message)),
+        };
+
+        if let Some(name) = input.get("filename").and_then(|v| v.as_str())
+            && !name.trim().is_empty()
+        {
+            file.filename = name.trim().to_string();
+        }
+        file.caption = input
+            .get("caption")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+
+        let filename = file.filename.clone();
+        let size = file.data.len();
+        match self.sender.send_attachment(&context.session_id, file).await {
+            Ok(channel) => Ok(ToolOutput::success(format!(
+                "sent {filename} ({size} bytes) via {channel}"
+            ))),
+            Err(e) => Ok(ToolOutput::error(format!("failed to send {filename}: {e}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(String, OutboundFile)>>);
+
+    #[async_trait]
+    impl AttachmentSender for Recorder {
+        async fn send_attachment(&self, session_id: &str, file: OutboundFile) -> Result<String> {
+            self.0.lock().unwrap().push((session_id.to_string(), file));
+            Ok("telegram".to_string())
+        }
+    }
+
+    fn ctx() -> ToolContext {
+        ToolContext {
+            session_id: "telegram-42".into(),
+            user_id: None,
+            continuity_key: None,
+            heartbeat_depth: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_file_from_allowed_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("chart.png");
+        std::fs::write(&path, b"\x89PNG\r\n\x1a\nrest").unwrap();
+
+        let recorder = Arc::new(Recorder::default());
+        let tool = SendAttachmentTool::new(
+            recorder.clone(),
+            None,
+            Some(vec![dir.path().canonicalize().unwrap()]),
+        );
+        let output = tool
+            .execute(
+                &ctx(),
+                serde_json::json!({"path": path.to_str().unwrap(), "caption": "Sales"}),
+            )
+            .await
+            .unwrap();
+        assert!(!output.is_error, "{}", output.content);
+
+        let sent = recorder.0.lock().unwrap();
+        assert_eq!(sent[0].0, "telegram-42");
+        assert_eq!(sent[0].1.filename, "chart.png");
+        assert_eq!(sent[0].1.mime_type, "image/png");
+        assert_eq!(sent[0].1.caption.as_deref(), Some("Sales"));
+    }
+
+    #[tokio::test]
+    async fn rejects_paths_outside_allowed_directories() {
+        let allowed = tempfile::tempdir().unwrap();
+        let other = tempfile::NamedTempFile::new().unwrap();
+        let tool = SendAttachmentTool::new(
+            Arc::new(Recorder::default()),
+            None,
+            Some(vec![allowed.path().canonicalize().unwrap()]),
+        );
+        let result = tool
+            .execute(
+                &ctx(),
+                serde_json::json!({"path": other.path().to_str().unwrap()}),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::Security(_))));
+    }
+
+    #[tokio::test]
+    async fn sends_stored_media_by_reference() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(MediaStore::open(dir.path()).unwrap());
+        let stored = store.put(b"%PDF-1.7 report", None, None).unwrap();
+
+        let recorder = Arc::new(Recorder::default());
+        let tool = SendAttachmentTool::new(recorder.clone(), Some(store), None);
+        let output = tool
+            .execute(
+                &ctx(),
+                serde_json::json!({"media_id": stored.url(), "filename": "report.pdf"}),
+            )
+            .await
+            .unwrap();
+        assert!(!output.is_error, "{}", output.content);
+
+        let sent = recorder.0.lock().unwrap();
+        assert_eq!(sent[0].1.filename, "report.pdf");
+        assert_eq!(sent[0].1.mime_type, "application/pdf");
+    }
+
+    #[tokio::test]
+    async fn requires_exactly_one_source() {
+        let tool = SendAttachmentTool::new(Arc::new(Recorder::default()), None, None);
+        let output = tool.execute(&ctx(), serde_json::json!({})).await.unwrap();
+        assert!(output.is_error);
+    }
+
+    #[test]
+    fn mime_prefers_extension_then_content() {
+        assert_eq!(mime_for("data.csv", b"a,b"), "text/csv");
+        assert_eq!(mime_for("REPORT.PDF", b""), "application/pdf");
+        assert_eq!(mime_for("blob", b"\xff\xd8\xffrest"), "image/jpeg");
+        assert_eq!(mime_for("blob", b"??"), "application/octet-stream");
+    }
+}
@@ -118,6 +118,7 @@ mod tests {
         let ctx = ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         };
         let result = rt.block_on(tool.execute(&ctx, serde_json::json!({})));
@@ -136,6 +136,7 @@ mod tests {
         ToolContext {
             session_id: "test".into(),
             user_id: None,
+            continuity_key: None,
             heartbeat_depth: 0,
         }
     }
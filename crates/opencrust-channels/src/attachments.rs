@@ -0,0 +1,323 @@
+//! Shared handling for files users send to the bot. Channels collect the
+//! files of a message unread; once the gateway has admitted the sender, they
+//! are downloaded and turned into input for the agent: images for the vision
+//! input, documents as text via `opencrust_media::document`, and voice notes
+//! via a `SpeechToText` backend.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::traits::IncomingImage;
+use opencrust_common::Error;
+use opencrust_media::SpeechToText;
+use opencrust_media::document::{self, MAX_DOCUMENT_BYTES};
+use reqwest::Client;
+use tracing::warn;
+
+/// Fetches an attachment's bytes when awaited.
+type Fetch = Pin<Box<dyn Future<Output = Result<Vec<u8>, String>> + Send>>;
+
+enum Pending {
+    /// Text for the agent that needs no download, such as a note that a file
+    /// type is not supported.
+    Block(String),
+    Image {
+        filename: String,
+        mime_type: Option<String>,
+        fetch: Fetch,
+    },
+    Voice {
+        filename: String,
+        fetch: Fetch,
+    },
+    Document {
+        filename: String,
+        mime_type: Option<String>,
+        fetch: Fetch,
+    },
+}
+
+/// The attachments of an incoming message, handed to the channel callback
+/// unread. The gateway resolves them once it has admitted the sender, so a
+/// message it drops costs no downloads, document parsing or speech-to-text.
+#[derive(Default)]
+pub struct PendingAttachments {
+    stt: Option<Arc<dyn SpeechToText>>,
+    items: Vec<Pending>,
+}
+
+impl PendingAttachments {
+    pub(crate) fn new(stt: Option<Arc<dyn SpeechToText>>) -> Self {
+        Self {
+            stt,
+            items: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push_block(&mut self, block: String) {
+        self.items.push(Pending::Block(block));
+    }
+
+    /// Add an image for the vision input whose bytes `fetch` produces.
+    pub(crate) fn push_image(
+        &mut self,
+        filename: &str,
+        mime_type: Option<&str>,
+        fetch: impl Future<Output = Result<Vec<u8>, String>> + Send + 'static,
+    ) {
+        self.items.push(Pending::Image {
+            filename: filename.to_string(),
+            mime_type: mime_type.map(str::to_string),
+            fetch: Box::pin(fetch),
+        });
+    }
+
+    /// Add a voice note whose bytes `fetch` produces.
+    pub(crate) fn push_voice(
+        &mut self,
+        filename: &str,
+        fetch: impl Future<Output = Result<Vec<u8>, String>> + Send + 'static,
+    ) {
+        self.items.push(Pending::Voice {
+            filename: filename.to_string(),
+            fetch: Box::pin(fetch),
+        });
+    }
+
+    /// Add a document to extract whose bytes `fetch` produces.
+    pub(crate) fn push_document(
+        &mut self,
+        filename: &str,
+        mime_type: Option<&str>,
+        fetch: impl Future<Output = Result<Vec<u8>, String>> + Send + 'static,
+    ) {
+        self.items.push(Pending::Document {
+            filename: filename.to_string(),
+            mime_type: mime_type.map(str::to_string),
+            fetch: Box::pin(fetch),
+        });
+    }
+
+    /// Add a file to download from `url`: audio is a voice note, images go
+    /// to the vision input and anything else is read as a document.
+    pub(crate) fn push_download(
+        &mut self,
+        client: &Client,
+        url: &str,
+        bearer_token: Option<&str>,
+        filename: &str,
+        mime_type: Option<&str>,
+    ) {
+        let client = client.clone();
+        let url = url.to_string();
+        let bearer_token = bearer_token.map(str::to_string);
+        let fetch = async move { download(&client, &url, bearer_token.as_deref()).await };
+        if is_audio(mime_type) {
+            self.push_voice(filename, fetch);
+        } else if is_image(mime_type) {
+            self.push_image(filename, mime_type, fetch);
+        } else {
+            self.push_document(filename, mime_type, fetch);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Read the attachments: documents are extracted and voice notes
+    /// transcribed, and their text goes in front of `text`. Returns the
+    /// combined text and the images for the model's vision input. A file
+    /// that cannot be read leaves a short note in the text instead.
+    pub async fn resolve(self, text: &str) -> (String, Vec<IncomingImage>) {
+        let mut blocks = Vec::new();
+        let mut images = Vec::new();
+        for item in self.items {
+            match item {
+                Pending::Block(block) => blocks.push(block),
+                Pending::Image {
+                    filename,
+                    mime_type,
+                    fetch,
+                } => match fetch.await {
+                    Ok(data) => images.push(IncomingImage {
+                        data,
+                        mime_type: mime_type.unwrap_or_else(|| "image/jpeg".to_string()),
+                    }),
+                    Err(e) => {
+                        warn!("could not download image {filename}: {e}");
+                        blocks.push(format!("[Image {filename} could not be read: {e}]"));
+                    }
+                },
+                Pending::Voice { filename, fetch } => {
+                    // Without a backend there is nothing to download for.
+                    let data = match &self.stt {
+                        Some(_) => fetch.await,
+                        None => Err(String::new()),
+                    };
+                    blocks.push(transcribe(data, &filename, self.stt.as_deref()).await);
+                }
+                Pending::Document {
+                    filename,
+                    mime_type,
+                    fetch,
+                } => blocks.push(extract(fetch.await, &filename, mime_type.as_deref()).await),
+            }
+        }
+        (with_attachments(text, blocks), images)
+    }
+}
+
+/// Download a file, refusing anything over [`MAX_DOCUMENT_BYTES`].
+pub(crate) async fn download(
+    client: &Client,
+    url: &str,
+    bearer_token: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let mut request = client.get(url);
+    if let Some(token) = bearer_token {
+        request = request.bearer_auth(token);
+    }
+    let mut resp = request
+        .send()
+        .await
+        .map_err(|e| format!("download failed: {e}"))?;
+    if !resp.status().is_success() {
+        return Err(format!("download failed with HTTP {}", resp.status()));
+    }
+    if resp
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_DOCUMENT_BYTES)
+    {
+        return Err(too_large());
+    }
+
+    let mut data = Vec::new();
+    while let Some(chunk) = resp
+        .chunk()
+        .await
+        .map_err(|e| format!("download failed: {e}"))?
+    {
+        if data.len() + chunk.len() > MAX_DOCUMENT_BYTES {
+            return Err(too_large());
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+fn too_large() -> String {
+    format!(
+        "file too large (maximum {}MB)",
+        MAX_DOCUMENT_BYTES / (1024 * 1024)
+    )
+}
+
+/// Whether an attachment is recorded audio rather than a document.
+fn is_audio(mime_type: Option<&str>) -> bool {
+    mime_type.is_some_and(|m| m.starts_with("audio/"))
+}
+
+/// Whether an attachment is an image for the model's vision input.
+fn is_image(mime_type: Option<&str>) -> bool {
+    mime_type.is_some_and(|m| m.starts_with("image/"))
+}
+
+async fn transcribe(
+    data: Result<Vec<u8>, String>,
+    filename: &str,
+    stt: Option<&dyn SpeechToText>,
+) -> String {
+    let Some(stt) = stt else {
+        return "[The user sent a voice message, but speech-to-text is not configured]".to_string();
+    };
+    let result = match data {
+        Ok(data) => stt
+            .transcribe(&data, filename)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(transcript) => transcript,
+        Err(e) => {
+            warn!("could not transcribe {filename} with {}: {e}", stt.name());
+            "[The user sent a voice message that could not be transcribed]".to_string()
+        }
+    }
+}
+
+/// Parsing a large PDF or EPUB takes a while, so it runs on a blocking
+/// thread rather than stalling the runtime's workers.
+async fn extract(data: Result<Vec<u8>, String>, filename: &str, mime_type: Option<&str>) -> String {
+    let result = match data {
+        Ok(data) => {
+            let name = filename.to_string();
+            let mime_type = mime_type.map(str::to_string);
+            tokio::task::spawn_blocking(move || {
+                document::extract_document(&data, &name, mime_type.as_deref())
+                    .map(|doc| doc.to_prompt(None))
+                    .map_err(|e| match e {
+                        Error::Media(msg) => msg,
+                        other => other.to_string(),
+                    })
+            })
+            .await
+            .unwrap_or_else(|e| Err(format!("extraction task failed: {e}")))
+        }
+        Err(e) => Err(e),
+    };
+    result.unwrap_or_else(|e| {
+        warn!("could not read attachment {filename}: {e}");
+        format!("[Attachment {filename} could not be read: {e}]")
+    })
+}
+
+/// Combine the user's text with the text produced from their attachments.
+pub(crate) fn with_attachments(text: &str, blocks: Vec<String>) -> String {
+    let mut parts = blocks;
+    if !text.trim().is_empty() {
+        parts.push(text.to_string());
+    }
+    parts.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attachments_come_before_the_caption() {
+        let combined = with_attachments("what is this?", vec!["```a.txt\nhi\n```".into()]);
+        assert_eq!(combined, "```a.txt\nhi\n```\n\nwhat is this?");
+        assert_eq!(with_attachments("just text", Vec::new()), "just text");
+    }
+
+    #[tokio::test]
+    async fn voice_without_backend_leaves_a_note() {
+        assert!(is_audio(Some("audio/ogg; codecs=opus")));
+        assert!(!is_audio(Some("application/pdf")));
+        let mut pending = PendingAttachments::new(None);
+        pending.push_voice("v.ogg", async { panic!("fetched without a backend") });
+        let (text, images) = pending.resolve("").await;
+        assert!(text.contains("not configured"));
+        assert!(images.is_empty());
+    }
+
+    #[tokio::test]
+    async fn resolve_reads_images_and_documents() {
+        let mut pending = PendingAttachments::new(None);
+        pending.push_image("a.png", Some("image/png"), async { Ok(vec![1, 2, 3]) });
+        pending.push_image("b.jpg", None, async { Err("HTTP 404".to_string()) });
+        pending.push_document("notes.txt", Some("text/plain"), async {
+            Ok(b"hello".to_vec())
+        });
+        let (text, images) = pending.resolve("summarize").await;
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].mime_type, "image/png");
+        assert!(text.contains("[Image b.jpg could not be read: HTTP 404]"));
+        assert!(text.contains("hello"));
+        assert!(text.ends_with("summarize"));
+    }
+}
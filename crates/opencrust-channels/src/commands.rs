@@ -0,0 +1,367 @@
+//! Channel-agnostic chat commands.
+//!
+//! The gateway builds a [`CommandRegistry`] and runs the commands; channel
+//! adapters only use the specs to publish native command menus (Discord slash
+//! commands, Telegram `setMyCommands`, Slack slash commands) and forward each
+//! invocation to the message callback as `/name arg1 arg2` text.
+
+use std::fmt;
+
+/// Who may run a command. Levels are ordered: an owner may run everything an
+/// allowed user may, and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Anyone who can message the bot, including unpaired users.
+    Anyone,
+    /// Users on the allowlist.
+    Allowed,
+    /// The bot owner.
+    Owner,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// Free text. As the last argument it takes the rest of the line.
+    Text,
+    Integer,
+    /// One of a fixed set of lowercase values.
+    Choice(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+}
+
+impl ArgSpec {
+    pub fn text(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            description,
+            kind: ArgKind::Text,
+            required: false,
+        }
+    }
+
+    pub fn integer(name: &'static str, description: &'static str) -> Self {
+        Self {
+            kind: ArgKind::Integer,
+            ..Self::text(name, description)
+        }
+    }
+
+    pub fn choice(
+        name: &'static str,
+        description: &'static str,
+        choices: &'static [&'static str],
+    ) -> Self {
+        Self {
+            kind: ArgKind::Choice(choices),
+            ..Self::text(name, description)
+        }
+    }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+}
+
+/// Name, help text, arguments and permission level of one command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandSpec {
+    /// Lowercase name without the leading slash.
+    pub name: &'static str,
+    pub description: &'static str,
+    pub permission: Permission,
+    pub args: Vec<ArgSpec>,
+}
+
+impl CommandSpec {
+    pub fn new(name: &'static str, description: &'static str, permission: Permission) -> Self {
+        Self {
+            name,
+            description,
+            permission,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, arg: ArgSpec) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    /// Usage line, e.g. `/schedule [action] [id]`.
+    pub fn usage(&self) -> String {
+        let mut usage = format!("/{}", self.name);
+        for arg in &self.args {
+            let name = match arg.kind {
+                ArgKind::Choice(choices) => choices.join("|"),
+                _ => arg.name.to_string(),
+            };
+            if arg.required {
+                usage.push_str(&format!(" <{name}>"));
+            } else {
+                usage.push_str(&format!(" [{name}]"));
+            }
+        }
+        usage
+    }
+
+    /// Parse the text after the command name into typed arguments.
+    fn parse_args(&self, rest: &str) -> Result<CommandArgs, CommandError> {
+        let invalid = |message: String| CommandError::InvalidArgs {
+            usage: self.usage(),
+            message,
+        };
+
+        let mut values = Vec::new();
+        let mut rest = rest.trim();
+        for (idx, arg) in self.args.iter().enumerate() {
+            let is_last = idx + 1 == self.args.len();
+            let token = if is_last && arg.kind == ArgKind::Text {
+                std::mem::take(&mut rest)
+            } else {
+                let (token, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                rest = tail.trim_start();
+                token
+            };
+
+            if token.is_empty() {
+                if arg.required {
+                    return Err(invalid(format!("missing {}", arg.name)));
+                }
+                continue;
+            }
+
+            let value = match arg.kind {
+                ArgKind::Text => ArgValue::Text(token.to_string()),
+                ArgKind::Integer => ArgValue::Integer(
+                    token
+                        .parse()
+                        .map_err(|_| invalid(format!("{} must be a number", arg.name)))?,
+                ),
+                ArgKind::Choice(choices) => {
+                    let lower = token.to_ascii_lowercase();
+                    if !choices.contains(&lower.as_str()) {
+                        return Err(invalid(format!(
+                            "{} must be one of: {}",
+                            arg.name,
+                            choices.join(", ")
+                        )));
+                    }
+                    ArgValue::Text(lower)
+                }
+            };
+            values.push((arg.name, value));
+        }
+
+        if !rest.is_empty() {
+            return Err(invalid("too many arguments".to_string()));
+        }
+        Ok(CommandArgs { values })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgValue {
+    Text(String),
+    Integer(i64),
+}
+
+/// Parsed argument values, looked up by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommandArgs {
+    values: Vec<(&'static str, ArgValue)>,
+}
+
+impl CommandArgs {
+    pub fn get(&self, name: &str) -> Option<&ArgValue> {
+        self.values.iter().find(|(n, _)| *n == name).map(|(_, v)| v)
+    }
+
+    pub fn text(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            ArgValue::Text(s) => Some(s),
+            ArgValue::Integer(_) => None,
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i64> {
+        match self.get(name)? {
+            ArgValue::Integer(n) => Some(*n),
+            ArgValue::Text(_) => None,
+        }
+    }
+}
+
+/// A command line matched against the registry.
+#[derive(Debug)]
+pub struct ParsedCommand<'a> {
+    pub spec: &'a CommandSpec,
+    pub args: CommandArgs,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    Unknown(String),
+    InvalidArgs { usage: String, message: String },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(
+                f,
+                "Unknown command: /{name}\nUse /help for available commands."
+            ),
+            Self::InvalidArgs { usage, message } => {
+                write!(f, "{message}\nUsage: {usage}")
+            }
+        }
+    }
+}
+
+/// The set of commands a bot understands, in help order.
+#[derive(Debug, Clone, Default)]
+pub struct CommandRegistry {
+    commands: Vec<CommandSpec>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command, replacing any existing command with the same name.
+    pub fn register(&mut self, spec: CommandSpec) {
+        if let Some(existing) = self.commands.iter_mut().find(|c| c.name == spec.name) {
+            *existing = spec;
+        } else {
+            self.commands.push(spec);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    pub fn specs(&self) -> &[CommandSpec] {
+        &self.commands
+    }
+
+    /// Match a message against the registry. Returns `None` when the text is
+    /// not a command. A `@botname` suffix on the name (Telegram groups) is
+    /// ignored.
+    pub fn parse<'a>(&'a self, text: &str) -> Option<Result<ParsedCommand<'a>, CommandError>> {
+        let line = text.trim().strip_prefix('/')?;
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let name = name.split('@').next().unwrap_or(name).to_ascii_lowercase();
+        if name.is_empty() {
+            return None;
+        }
+
+        let Some(spec) = self.get(&name) else {
+            return Some(Err(CommandError::Unknown(name)));
+        };
+        Some(
+            spec.parse_args(rest)
+                .map(|args| ParsedCommand { spec, args }),
+        )
+    }
+
+    /// One `/usage - description` line per command available at `level`.
+    pub fn help_text(&self, level: Permission) -> String {
+        self.commands
+            .iter()
+            .filter(|c| c.permission <= level)
+            .map(|c| format!("{} - {}", c.usage(), c.description))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register(CommandSpec::new("help", "Show help", Permission::Allowed));
+        registry.register(
+            CommandSpec::new("schedule", "Manage reminders", Permission::Allowed)
+                .arg(ArgSpec::choice("action", "What to do", &["list", "cancel"]))
+                .arg(ArgSpec::text("id", "Reminder id")),
+        );
+        registry.register(
+            CommandSpec::new("pair", "Invite a user", Permission::Owner)
+                .arg(ArgSpec::integer("minutes", "Code lifetime")),
+        );
+        registry
+    }
+
+    #[test]
+    fn non_commands_are_ignored() {
+        assert!(registry().parse("hello /help").is_none());
+        assert!(registry().parse("/").is_none());
+    }
+
+    #[test]
+    fn parses_typed_arguments() {
+        let registry = registry();
+        let parsed = registry.parse("/schedule CANCEL abc 123").unwrap().unwrap();
+        assert_eq!(parsed.spec.name, "schedule");
+        assert_eq!(parsed.args.text("action"), Some("cancel"));
+        assert_eq!(parsed.args.text("id"), Some("abc 123"));
+
+        let parsed = registry.parse("/pair@OpenCrustBot 10").unwrap().unwrap();
+        assert_eq!(parsed.args.integer("minutes"), Some(10));
+
+        let parsed = registry.parse("/schedule").unwrap().unwrap();
+        assert_eq!(parsed.args, CommandArgs::default());
+    }
+
+    #[test]
+    fn reports_invalid_and_unknown_commands() {
+        let registry = registry();
+        let err = registry.parse("/schedule later").unwrap().unwrap_err();
+        assert!(err.to_string().contains("list, cancel"));
+        assert!(err.to_string().contains("/schedule [list|cancel] [id]"));
+
+        let err = registry.parse("/pair soon").unwrap().unwrap_err();
+        assert!(err.to_string().contains("minutes must be a number"));
+
+        let err = registry.parse("/help me").unwrap().unwrap_err();
+        assert!(err.to_string().contains("too many arguments"));
+
+        assert_eq!(
+            registry.parse("/nope").unwrap().unwrap_err(),
+            CommandError::Unknown("nope".to_string())
+        );
+    }
+
+    #[test]
+    fn help_lists_commands_for_permission_level() {
+        let registry = registry();
+        let allowed = registry.help_text(Permission::Allowed);
+        assert!(allowed.contains("/help - Show help"));
+        assert!(!allowed.contains("/pair"));
+        assert!(
+            registry
+                .help_text(Permission::Owner)
+                .contains("/pair [minutes]")
+        );
+    }
+
+    #[test]
+    fn register_replaces_existing_name() {
+        let mut registry = registry();
+        registry.register(CommandSpec::new("help", "New help", Permission::Anyone));
+        assert_eq!(registry.specs().len(), 3);
+        assert_eq!(registry.get("help").unwrap().description, "New help");
+    }
+}
@@ -1,46 +1,63 @@
 use opencrust_common::Result;
-use serenity::all::{Command, Context, CreateCommand, GuildId};
+use serenity::all::{
+    Command, CommandDataOption, CommandDataOptionValue, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, GuildId,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum DiscordSlashCommand {
-    Start,
-    Help,
-    Clear,
-    Pair,
-    Users,
+use crate::commands::{ArgKind, ArgSpec, CommandSpec};
+
+/// Build Discord slash command definitions from the shared command specs.
+pub fn slash_commands(specs: &[CommandSpec]) -> Vec<CreateCommand> {
+    specs
+        .iter()
+        .map(|spec| {
+            spec.args.iter().fold(
+                CreateCommand::new(spec.name).description(truncate(spec.description)),
+                |command, arg| command.add_option(slash_option(arg)),
+            )
+        })
+        .collect()
 }
 
-impl DiscordSlashCommand {
-    pub fn as_str(self) -> &'static str {
-        match self {
-            Self::Start => "start",
-            Self::Help => "help",
-            Self::Clear => "clear",
-            Self::Pair => "pair",
-            Self::Users => "users",
-        }
+fn slash_option(arg: &ArgSpec) -> CreateCommandOption {
+    let kind = match arg.kind {
+        ArgKind::Integer => CommandOptionType::Integer,
+        ArgKind::Text | ArgKind::Choice(_) => CommandOptionType::String,
+    };
+    let option =
+        CreateCommandOption::new(kind, arg.name, truncate(arg.description)).required(arg.required);
+    match arg.kind {
+        ArgKind::Choice(choices) => choices.iter().fold(option, |option, choice| {
+            option.add_string_choice(*choice, *choice)
+        }),
+        _ => option,
     }
+}
 
-    pub fn from_name(name: &str) -> Option<Self> {
-        match name {
-            "start" => Some(Self::Start),
-            "help" => Some(Self::Help),
-            "clear" => Some(Self::Clear),
-            "pair" => Some(Self::Pair),
-            "users" => Some(Self::Users),
-            _ => None,
-        }
-    }
+/// Discord limits command and option descriptions to 100 characters.
+fn truncate(description: &str) -> String {
+    description.chars().take(100).collect()
 }
 
-pub fn all_commands() -> Vec<CreateCommand> {
-    vec![
-        CreateCommand::new("start").description("Initialize the bot and access flow"),
-        CreateCommand::new("help").description("Show available OpenCrust commands"),
-        CreateCommand::new("clear").description("Clear conversation history for this thread/DM"),
-        CreateCommand::new("pair").description("Generate a pairing code (owner only)"),
-        CreateCommand::new("users").description("List allowed users (owner only)"),
-    ]
+/// Rebuild `/name arg1 arg2` text from a slash command invocation, in the
+/// order the spec declares its arguments.
+pub fn command_text(spec: &CommandSpec, options: &[CommandDataOption]) -> String {
+    let mut text = format!("/{}", spec.name);
+    for arg in &spec.args {
+        let value = options
+            .iter()
+            .find(|o| o.name == arg.name)
+            .and_then(|o| match &o.value {
+                CommandDataOptionValue::String(s) => Some(s.clone()),
+                CommandDataOptionValue::Integer(n) => Some(n.to_string()),
+                _ => None,
+            });
+        if let Some(value) = value {
+            text.push(' ');
+            text.push_str(&value);
+        }
+    }
+    text
 }
 
 /// Register slash commands with Discord for the given guild IDs.
@@ -83,19 +100,35 @@ pub async fn register_commands(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::Permission;
+
+    fn specs() -> Vec<CommandSpec> {
+        vec![
+            CommandSpec::new("help", "Show help", Permission::Allowed),
+            CommandSpec::new("schedule", "Manage reminders", Permission::Allowed)
+                .arg(ArgSpec::choice("action", "What to do", &["list", "cancel"]))
+                .arg(ArgSpec::text("id", "Reminder id")),
+        ]
+    }
 
     #[test]
-    fn all_commands_includes_expected_names() {
-        let commands = all_commands();
-        assert_eq!(commands.len(), 5);
+    fn slash_commands_mirror_specs() {
+        let commands = slash_commands(&specs());
+        assert_eq!(commands.len(), 2);
+        let json = serde_json::to_value(&commands[1]).unwrap();
+        assert_eq!(json["name"], "schedule");
+        assert_eq!(json["options"][0]["choices"][1]["value"], "cancel");
+        assert_eq!(json["options"][1]["type"], 3);
     }
 
     #[test]
-    fn from_name_maps_known_commands() {
-        assert_eq!(
-            DiscordSlashCommand::from_name("start"),
-            Some(DiscordSlashCommand::Start)
-        );
-        assert_eq!(DiscordSlashCommand::from_name("nope"), None);
+    fn command_text_orders_options_by_spec() {
+        let options: Vec<CommandDataOption> = serde_json::from_value(serde_json::json!([
+            {"name": "id", "type": 3, "value": "abc"},
+            {"name": "action", "type": 3, "value": "cancel"},
+        ]))
+        .unwrap();
+        assert_eq!(command_text(&specs()[1], &options), "/schedule cancel abc");
+        assert_eq!(command_text(&specs()[0], &[]), "/help");
     }
 }
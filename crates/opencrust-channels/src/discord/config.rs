@@ -21,6 +21,10 @@ pub struct DiscordConfig {
 
     /// Optional command prefix for text-based commands.
     pub prefix: Option<String>,
+
+    /// Start a thread for each conversation begun in a server channel and
+    /// reply there, so every conversation gets its own session.
+    pub auto_thread: bool,
 }
 
 /// Intermediate struct for deserializing from the settings map.
@@ -31,6 +35,8 @@ struct RawDiscordConfig {
     #[serde(default)]
     guild_ids: Vec<u64>,
     prefix: Option<String>,
+    #[serde(default)]
+    auto_thread: bool,
 }
 
 impl DiscordConfig {
@@ -72,6 +78,7 @@ impl DiscordConfig {
             guild_ids: raw.guild_ids,
             intents,
             prefix: raw.prefix,
+            auto_thread: raw.auto_thread,
         })
     }
 }
@@ -94,6 +101,7 @@ mod tests {
                 serde_json::json!([111111111111111111_u64, 222222222222222222_u64]),
             ),
             ("prefix", serde_json::json!("!")),
+            ("auto_thread", serde_json::json!(true)),
         ]);
 
         let config = DiscordConfig::from_settings(&settings).expect("should parse valid config");
@@ -101,6 +109,7 @@ mod tests {
         assert_eq!(config.application_id, 123456789012345678);
         assert_eq!(config.guild_ids.len(), 2);
         assert_eq!(config.prefix.as_deref(), Some("!"));
+        assert!(config.auto_thread);
     }
 
     #[test]
@@ -144,5 +153,6 @@ mod tests {
         let config = DiscordConfig::from_settings(&settings).expect("should parse");
         assert!(config.guild_ids.is_empty());
         assert!(config.prefix.is_none());
+        assert!(!config.auto_thread);
     }
 }
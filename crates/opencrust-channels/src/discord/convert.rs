@@ -2,6 +2,8 @@ use chrono::Utc;
 use opencrust_common::{ChannelId, Message, MessageContent, MessageDirection, SessionId, UserId};
 use serenity::all as serenity_model;
 
+use crate::markdown::{self, Renderer};
+
 pub const DISCORD_MESSAGE_CHAR_LIMIT: usize = 2000;
 
 /// Convert a serenity Discord message into an OpenCrust `Message`.
@@ -18,13 +20,7 @@ pub fn discord_message_to_opencrust(
     let session_id = SessionId::from_string(format!("discord-{}", msg.channel_id));
 
     let content = if let Some(attachment) = msg.attachments.first() {
-        // Check if the attachment is an image based on content type
-        let is_image = attachment
-            .content_type
-            .as_deref()
-            .is_some_and(|ct| ct.starts_with("image/"));
-
-        if is_image {
+        if is_image(attachment) {
             MessageContent::Image {
                 url: attachment.url.clone(),
                 caption: if msg.content.is_empty() {
@@ -58,10 +54,24 @@ pub fn discord_message_to_opencrust(
             "author_name": msg.author.name,
             "author_discriminator": msg.author.discriminator,
             "is_bot": msg.author.bot,
+            "attachments": msg.attachments.iter().map(|a| serde_json::json!({
+                "url": a.url,
+                "filename": a.filename,
+                "content_type": a.content_type,
+            })).collect::<Vec<_>>(),
+            "reply_to_message_id": msg.referenced_message.as_ref().map(|m| m.id.to_string()),
         }),
     }
 }
 
+/// Whether an attachment is an image, based on its content type.
+pub fn is_image(attachment: &serenity_model::Attachment) -> bool {
+    attachment
+        .content_type
+        .as_deref()
+        .is_some_and(|ct| ct.starts_with("image/"))
+}
+
 /// Convert a reaction add event into an OpenCrust `Message` with `Reaction` content.
 pub fn reaction_to_opencrust(reaction: &serenity_model::Reaction, channel_id_str: &str) -> Message {
     let user_id = reaction
@@ -143,41 +153,106 @@ pub fn opencrust_content_to_text(content: &MessageContent) -> String {
     }
 }
 
-/// Convert generic markdown to Discord-friendly markdown.
-///
-/// Discord supports most common markdown constructs, so this primarily normalizes
-/// line endings and neutralizes mass mentions.
-pub fn to_discord_markdown(input: &str) -> String {
-    input
-        .replace("\r\n", "\n")
-        .replace("@everyone", "@\u{200B}everyone")
-        .replace("@here", "@\u{200B}here")
+/// Longest thread name Discord accepts.
+const THREAD_NAME_MAX_CHARS: usize = 100;
+
+/// Name for a thread started from a message: its first line, shortened to
+/// fit Discord's limit, or the author's name for messages without text.
+pub fn thread_name(content: &str, author: &str) -> String {
+    let first_line = content.lines().map(str::trim).find(|l| !l.is_empty());
+    let Some(line) = first_line else {
+        return format!("Conversation with {author}")
+            .chars()
+            .take(THREAD_NAME_MAX_CHARS)
+            .collect();
+    };
+    if line.chars().count() <= THREAD_NAME_MAX_CHARS {
+        return line.to_string();
+    }
+    let mut name: String = line.chars().take(THREAD_NAME_MAX_CHARS - 1).collect();
+    name.push('…');
+    name
 }
 
-/// Split text into Discord-safe chunks (<= 2000 chars each).
-pub fn split_discord_chunks(input: &str) -> Vec<String> {
-    if input.is_empty() {
-        return vec!["\u{200B}".to_string()];
+/// Longest quoted reply context passed to the agent.
+const QUOTE_MAX_CHARS: usize = 1000;
+
+/// Quote the message a user replied to, so the agent knows what the reply
+/// refers to.
+pub fn quote_reply(author: &str, content: &str) -> String {
+    let content = content.trim();
+    let mut shortened: String = content.chars().take(QUOTE_MAX_CHARS).collect();
+    if shortened.len() < content.len() {
+        shortened.push('…');
     }
+    let quoted = shortened
+        .lines()
+        .map(|line| format!("> {line}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("[Replying to {author}:]\n{quoted}")
+}
 
-    let mut chunks = Vec::new();
-    let mut current = String::new();
-    let mut current_len = 0usize;
+/// Remove mentions of `user_id` (`<@id>` and the nickname form `<@!id>`).
+pub fn strip_user_mention(content: &str, user_id: serenity_model::UserId) -> String {
+    content
+        .replace(&format!("<@{user_id}>"), "")
+        .replace(&format!("<@!{user_id}>"), "")
+        .trim()
+        .to_string()
+}
 
-    for ch in input.chars() {
-        let ch_len = ch.len_utf8();
-        if current_len + ch_len > DISCORD_MESSAGE_CHAR_LIMIT {
-            chunks.push(std::mem::take(&mut current));
-            current_len = 0;
-        }
-        current.push(ch);
-        current_len += ch_len;
+/// Renders the Markdown IR as Discord Markdown, which supports the common
+/// constructs natively. Mass mentions in text are neutralized.
+pub struct DiscordMarkdown;
+
+impl Renderer for DiscordMarkdown {
+    fn text(&self, text: &str) -> String {
+        text.replace("@everyone", "@\u{200B}everyone")
+            .replace("@here", "@\u{200B}here")
+    }
+
+    fn bold(&self, inner: &str) -> String {
+        format!("**{inner}**")
+    }
+
+    fn italic(&self, inner: &str) -> String {
+        format!("*{inner}*")
+    }
+
+    fn strike(&self, inner: &str) -> String {
+        format!("~~{inner}~~")
+    }
+
+    fn code(&self, code: &str) -> String {
+        format!("`{code}`")
     }
 
-    if !current.is_empty() {
-        chunks.push(current);
+    fn code_block(&self, lang: &str, code: &str) -> String {
+        format!("```{lang}\n{code}\n```")
     }
 
+    fn link(&self, text: &str, url: &str) -> String {
+        format!("[{text}]({url})")
+    }
+
+    fn heading(&self, level: u8, inner: &str) -> String {
+        format!("{} {inner}", "#".repeat(usize::from(level.min(3))))
+    }
+}
+
+/// Convert generic markdown to Discord-friendly markdown.
+pub fn to_discord_markdown(input: &str) -> String {
+    markdown::parse(input).render(&DiscordMarkdown)
+}
+
+/// Render markdown into Discord-safe chunks (<= 2000 chars each), keeping
+/// code blocks fenced in every chunk.
+pub fn split_discord_chunks(input: &str) -> Vec<String> {
+    let chunks = markdown::render_chunks(input, &DiscordMarkdown, DISCORD_MESSAGE_CHAR_LIMIT);
+    if chunks.iter().all(|c| c.is_empty()) {
+        return vec!["\u{200B}".to_string()];
+    }
     chunks
 }
 
@@ -245,4 +320,44 @@ mod tests {
         assert_eq!(chunks[0].len(), DISCORD_MESSAGE_CHAR_LIMIT);
         assert_eq!(chunks[1].len(), 10);
     }
+
+    #[test]
+    fn thread_name_uses_first_line() {
+        assert_eq!(
+            thread_name("\n  How do I deploy?\nmore", "ada"),
+            "How do I deploy?"
+        );
+        assert_eq!(thread_name("", "ada"), "Conversation with ada");
+        let long = thread_name(&"x".repeat(150), "ada");
+        assert_eq!(long.chars().count(), THREAD_NAME_MAX_CHARS);
+        assert!(long.ends_with('…'));
+    }
+
+    #[test]
+    fn quote_reply_quotes_each_line() {
+        assert_eq!(
+            quote_reply("ada", "first\nsecond"),
+            "[Replying to ada:]\n> first\n> second"
+        );
+        let quoted = quote_reply("ada", &"y".repeat(QUOTE_MAX_CHARS + 5));
+        assert!(quoted.ends_with('…'));
+    }
+
+    #[test]
+    fn strip_user_mention_removes_both_forms() {
+        let bot = serenity_model::UserId::new(42);
+        assert_eq!(strip_user_mention("<@42> hi <@!42>", bot), "hi");
+        assert_eq!(strip_user_mention("hi <@43>", bot), "hi <@43>");
+    }
+
+    #[test]
+    fn chunking_reopens_code_blocks() {
+        let code = "let x = 1;\n".repeat(300);
+        let chunks = split_discord_chunks(&format!("Here:\n\n```rust\n{code}```"));
+        assert!(chunks.len() > 1);
+        for chunk in &chunks[1..] {
+            assert!(chunk.chars().count() <= DISCORD_MESSAGE_CHAR_LIMIT);
+            assert!(chunk.starts_with("```rust\n") && chunk.ends_with("\n```"));
+        }
+    }
 }
@@ -1,13 +1,18 @@
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
+use opencrust_media::SpeechToText;
 use serenity::all::{
-    self as serenity_model, CommandInteraction, Context, CreateMessage, EditMessage, EventHandler,
-    Interaction as SerenityInteraction, Message as SerenityMessage, MessageId, Ready,
+    self as serenity_model, CommandInteraction, Context, CreateMessage, CreateThread, EditMessage,
+    EventHandler, Interaction as SerenityInteraction, Message as SerenityMessage, MessageId, Ready,
 };
 use tokio::sync::{broadcast, mpsc};
 use tracing::{info, warn};
 
+use crate::attachments::PendingAttachments;
+use crate::commands::CommandSpec;
+use crate::group::{GroupMessage, GroupPolicy};
+use crate::progressive::{MessageEditor, ProgressiveMessage};
 use crate::traits::{ChannelEvent, ChannelStatus};
 
 use super::{DiscordOnMessageFn, commands, convert};
@@ -25,6 +30,21 @@ pub struct DiscordHandler {
 
     /// Callback for processing incoming user messages.
     on_message: DiscordOnMessageFn,
+
+    /// HTTP client for downloading attachments from the Discord CDN.
+    http: reqwest::Client,
+
+    /// Transcribes voice messages, if configured.
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+
+    /// Commands registered as slash commands on connect.
+    commands: Vec<CommandSpec>,
+
+    /// Start a thread for conversations begun in a server channel.
+    auto_thread: bool,
+
+    /// Decides which server channel messages are answered.
+    group_policy: Arc<GroupPolicy>,
 }
 
 impl DiscordHandler {
@@ -39,9 +59,34 @@ impl DiscordHandler {
             channel_id,
             guild_ids,
             on_message,
+            http: reqwest::Client::new(),
+            speech_to_text: None,
+            commands: Vec::new(),
+            auto_thread: false,
+            group_policy: Arc::default(),
         }
     }
 
+    pub fn with_speech_to_text(mut self, stt: Option<Arc<dyn SpeechToText>>) -> Self {
+        self.speech_to_text = stt;
+        self
+    }
+
+    pub fn with_commands(mut self, commands: Vec<CommandSpec>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    pub fn with_auto_thread(mut self, auto_thread: bool) -> Self {
+        self.auto_thread = auto_thread;
+        self
+    }
+
+    pub fn with_group_policy(mut self, policy: Arc<GroupPolicy>) -> Self {
+        self.group_policy = policy;
+        self
+    }
+
     fn emit(&self, event: ChannelEvent) {
         if let Err(e) = self.event_tx.send(event) {
             warn!("no subscribers for channel event: {e}");
@@ -55,9 +100,10 @@ impl DiscordHandler {
         user_id: String,
         user_name: String,
         text: String,
-    ) {
-        if text.trim().is_empty() {
-            return;
+        attachments: PendingAttachments,
+    ) -> bool {
+        if text.trim().is_empty() && attachments.is_empty() {
+            return false;
         }
 
         // Keep typing indicator alive while callback/streaming is in progress.
@@ -83,36 +129,20 @@ impl DiscordHandler {
                 cb_user_id,
                 cb_user_name,
                 cb_text,
+                attachments,
                 Some(delta_tx),
             )
             .await
         });
 
-        let mut accumulated = String::new();
-        let mut sent: Vec<(MessageId, String)> = Vec::new();
-        let mut first_delta_at: Option<Instant> = None;
-        let mut last_update = Instant::now();
-
-        while let Some(delta) = delta_rx.recv().await {
-            accumulated.push_str(&delta);
-            if first_delta_at.is_none() {
-                first_delta_at = Some(Instant::now());
-            }
-
-            if first_delta_at
-                .map(|t| t.elapsed() >= Duration::from_secs(1))
-                .unwrap_or(false)
-                && last_update.elapsed() >= Duration::from_millis(1000)
-            {
-                if let Err(e) =
-                    sync_discord_chunks(ctx, channel_id, &accumulated, &mut sent, false).await
-                {
-                    warn!("failed to stream Discord update: {e}");
-                    break;
-                }
-                last_update = Instant::now();
-            }
-        }
+        let mut reply = ProgressiveMessage::new(
+            DiscordEditor {
+                http: ctx.http.clone(),
+                channel_id,
+            },
+            DISCORD_EDIT_INTERVAL,
+        );
+        reply.stream(&mut delta_rx).await;
 
         typing_handle.abort();
 
@@ -122,29 +152,88 @@ impl DiscordHandler {
 
         match result {
             Ok(final_text) => {
-                if let Err(e) =
-                    sync_discord_chunks(ctx, channel_id, &final_text, &mut sent, true).await
-                {
+                if let Err(e) = reply.finish(&final_text).await {
                     warn!("failed to send Discord final response: {e}");
                 }
             }
-            Err(e) if e == "__blocked__" => {}
+            Err(e) if e == "__blocked__" => {
+                reply.cancel().await;
+                return false;
+            }
             Err(e) => {
                 let err_text = format!("Sorry, an error occurred: {e}");
-                if let Err(send_err) =
-                    sync_discord_chunks(ctx, channel_id, &err_text, &mut sent, true).await
-                {
+                if let Err(send_err) = reply.finish(&err_text).await {
                     warn!("failed to send Discord error response: {send_err}");
                 }
             }
         }
+        true
+    }
+
+    /// Whether a server message replies to the bot: it answers one of the
+    /// bot's messages or was posted in a thread the bot started.
+    async fn replies_to_bot(
+        &self,
+        ctx: &Context,
+        msg: &SerenityMessage,
+        bot_id: serenity_model::UserId,
+    ) -> bool {
+        if msg
+            .referenced_message
+            .as_deref()
+            .is_some_and(|replied| replied.author.id == bot_id)
+        {
+            return true;
+        }
+        match msg.channel_id.to_channel(ctx).await {
+            Ok(serenity_model::Channel::Guild(channel)) => {
+                channel.thread_metadata.is_some() && channel.owner_id == Some(bot_id)
+            }
+            _ => false,
+        }
+    }
+
+    /// Start a thread under `msg` when auto-threading applies: the message
+    /// was posted in a server channel, not already inside a thread.
+    async fn start_thread(
+        &self,
+        ctx: &Context,
+        msg: &SerenityMessage,
+    ) -> Option<serenity_model::ChannelId> {
+        if !self.auto_thread || msg.guild_id.is_none() {
+            return None;
+        }
+        let in_thread = match msg.channel_id.to_channel(ctx).await {
+            Ok(serenity_model::Channel::Guild(channel)) => channel.thread_metadata.is_some(),
+            Ok(_) => true,
+            Err(e) => {
+                warn!("failed to look up discord channel {}: {e}", msg.channel_id);
+                true
+            }
+        };
+        if in_thread {
+            return None;
+        }
+
+        let name = convert::thread_name(&msg.content, &msg.author.name);
+        match msg
+            .channel_id
+            .create_thread_from_message(&ctx.http, msg.id, CreateThread::new(name))
+            .await
+        {
+            Ok(thread) => Some(thread.id),
+            Err(e) => {
+                warn!("failed to create discord thread: {e}");
+                None
+            }
+        }
     }
 
     async fn process_slash_command(
         &self,
         ctx: &Context,
         command: &CommandInteraction,
-        slash: commands::DiscordSlashCommand,
+        text: String,
     ) {
         if let Err(e) = command.defer(&ctx.http).await {
             warn!("failed to defer slash command response: {e}");
@@ -157,14 +246,13 @@ impl DiscordHandler {
             .as_ref()
             .and_then(|m| m.nick.clone())
             .unwrap_or_else(|| command.user.name.clone());
-        let text = format!("/{}", slash.as_str());
-
         let on_message = Arc::clone(&self.on_message);
         let result = on_message(
             command.channel_id.to_string(),
             user_id,
             user_name,
             text,
+            PendingAttachments::default(),
             None,
         )
         .await;
@@ -175,7 +263,6 @@ impl DiscordHandler {
             Err(e) => format!("Sorry, an error occurred: {e}"),
         };
 
-        let response_text = convert::to_discord_markdown(&response_text);
         let chunks = convert::split_discord_chunks(&response_text);
         let first_chunk = chunks
             .first()
@@ -225,7 +312,7 @@ impl EventHandler for DiscordHandler {
             ready.guilds.len()
         );
 
-        let command_defs = commands::all_commands();
+        let command_defs = commands::slash_commands(&self.commands);
         if let Err(e) = commands::register_commands(&ctx, &self.guild_ids, &command_defs).await {
             warn!("failed to register discord slash commands: {e}");
         } else {
@@ -257,17 +344,88 @@ impl EventHandler for DiscordHandler {
             "received discord message"
         );
 
-        self.process_message(
-            &ctx,
-            msg.channel_id,
-            msg.author.id.to_string(),
-            msg.author
+        // In servers, the policy decides before attachments are downloaded.
+        let bot_id = ctx.cache.current_user().id;
+        let mut content = msg.content.clone();
+        let mut group_context = None;
+        if msg.guild_id.is_some() {
+            content = convert::strip_user_mention(&content, bot_id);
+            let sender = msg
+                .author
                 .global_name
                 .clone()
-                .unwrap_or_else(|| msg.author.name.clone()),
-            msg.content.clone(),
-        )
-        .await;
+                .unwrap_or_else(|| msg.author.name.clone());
+            let admitted = self.group_policy.admit(&GroupMessage {
+                group_id: &msg.channel_id.to_string(),
+                sender_name: &sender,
+                text: &content,
+                mentioned: msg.mentions_user_id(bot_id),
+                replies_to_bot: self.replies_to_bot(&ctx, &msg, bot_id).await,
+            });
+            match admitted {
+                Some(context) => group_context = Some(context),
+                None => return,
+            }
+        }
+
+        // Files are downloaded and read once the gateway has admitted the
+        // sender.
+        let mut pending = PendingAttachments::new(self.speech_to_text.clone());
+        for attachment in &msg.attachments {
+            pending.push_download(
+                &self.http,
+                &attachment.url,
+                None,
+                &attachment.filename,
+                attachment.content_type.as_deref(),
+            );
+        }
+
+        // Quote the message being replied to so the agent sees the context.
+        let mut text = content;
+        if let Some(replied) = msg.referenced_message.as_deref()
+            && !replied.content.trim().is_empty()
+        {
+            let author = replied
+                .author
+                .global_name
+                .as_deref()
+                .unwrap_or(&replied.author.name);
+            text = format!(
+                "{}\n\n{text}",
+                convert::quote_reply(author, &replied.content)
+            );
+        }
+        if text.trim().is_empty() && pending.is_empty() {
+            return;
+        }
+        let text = match &group_context {
+            Some(context) => context.prepend_to(&text),
+            None => text,
+        };
+
+        let thread = self.start_thread(&ctx, &msg).await;
+        let answered = self
+            .process_message(
+                &ctx,
+                thread.unwrap_or(msg.channel_id),
+                msg.author.id.to_string(),
+                msg.author
+                    .global_name
+                    .clone()
+                    .unwrap_or_else(|| msg.author.name.clone()),
+                text,
+                pending,
+            )
+            .await;
+
+        // Don't leave empty threads behind for messages the bot ignored.
+        if !answered
+            && let Some(thread) = thread
+            && let Err(e) = thread.delete(&ctx.http).await
+        {
+            warn!("failed to delete unused discord thread: {e}");
+        }
     }
 
     /// Fired when a slash command interaction is created.
@@ -276,11 +434,12 @@ impl EventHandler for DiscordHandler {
             return;
         };
 
-        let Some(slash) = commands::DiscordSlashCommand::from_name(&command.data.name) else {
+        let Some(spec) = self.commands.iter().find(|c| c.name == command.data.name) else {
             return;
         };
+        let text = commands::command_text(spec, &command.data.options);
 
-        self.process_slash_command(&ctx, &command, slash).await;
+        self.process_slash_command(&ctx, &command, text).await;
     }
 
     /// Fired when a reaction is added to a message.
@@ -296,51 +455,65 @@ impl EventHandler for DiscordHandler {
         self.emit(ChannelEvent::MessageReceived(opencrust_msg));
     }
 
-    /// Fired when a thread is created.
-    async fn thread_create(&self, _ctx: Context, thread: serenity_model::GuildChannel) {
+    /// Fired when a thread is created. The bot joins it so replies there
+    /// reach it; each thread is its own session.
+    async fn thread_create(&self, ctx: Context, thread: serenity_model::GuildChannel) {
         info!(
             thread_id = %thread.id,
             thread_name = %thread.name,
             "new discord thread created"
         );
+        if thread.member.is_none()
+            && let Err(e) = thread.id.join_thread(&ctx.http).await
+        {
+            warn!("failed to join discord thread {}: {e}", thread.id);
+        }
     }
 }
 
-async fn sync_discord_chunks(
-    ctx: &Context,
+/// Discord allows five message edits per five seconds in a channel.
+const DISCORD_EDIT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Posts and edits reply messages in one Discord channel.
+struct DiscordEditor {
+    http: Arc<serenity_model::Http>,
     channel_id: serenity_model::ChannelId,
-    text: &str,
-    sent: &mut Vec<(MessageId, String)>,
-    is_final: bool,
-) -> std::result::Result<(), String> {
-    let formatted = convert::to_discord_markdown(text);
-    let chunks = convert::split_discord_chunks(&formatted);
-
-    for (idx, chunk) in chunks.iter().enumerate() {
-        if idx < sent.len() {
-            if sent[idx].1 != *chunk {
-                channel_id
-                    .edit_message(&ctx.http, sent[idx].0, EditMessage::new().content(chunk))
-                    .await
-                    .map_err(|e| format!("failed to edit Discord message: {e}"))?;
-                sent[idx].1 = chunk.clone();
-            }
-        } else {
-            let msg = channel_id
-                .send_message(&ctx.http, CreateMessage::new().content(chunk))
-                .await
-                .map_err(|e| format!("failed to send Discord chunk: {e}"))?;
-            sent.push((msg.id, chunk.clone()));
-        }
+}
+
+#[serenity::async_trait]
+impl MessageEditor for DiscordEditor {
+    type Id = MessageId;
+
+    fn max_chars(&self) -> usize {
+        convert::DISCORD_MESSAGE_CHAR_LIMIT
     }
 
-    if is_final && sent.len() > chunks.len() {
-        for (id, _) in sent.drain(chunks.len()..) {
-            let _ = channel_id.delete_message(&ctx.http, id).await;
-        }
+    fn chunks(&self, text: &str) -> Vec<String> {
+        convert::split_discord_chunks(text)
+    }
+
+    async fn post(&self, text: &str) -> std::result::Result<MessageId, String> {
+        self.channel_id
+            .send_message(&self.http, CreateMessage::new().content(text))
+            .await
+            .map(|msg| msg.id)
+            .map_err(|e| format!("failed to send Discord message: {e}"))
     }
 
-    Ok(())
+    async fn edit(&self, id: &MessageId, text: &str) -> std::result::Result<(), String> {
+        self.channel_id
+            .edit_message(&self.http, *id, EditMessage::new().content(text))
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("failed to edit Discord message: {e}"))
+    }
+
+    async fn delete(&self, id: &MessageId) -> std::result::Result<(), String> {
+        self.channel_id
+            .delete_message(&self.http, *id)
+            .await
+            .map_err(|e| format!("failed to delete Discord message: {e}"))
+    }
 }
 
 #[cfg(test)]
@@ -351,7 +524,7 @@ mod tests {
     #[test]
     fn handler_construction() {
         let (tx, _rx) = broadcast::channel::<ChannelEvent>(16);
-        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
+        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _images, _delta_tx| {
             Box::pin(async { Ok("test".to_string()) })
         });
         let handler = DiscordHandler::new(tx, "discord".to_string(), vec![], on_msg);
@@ -362,7 +535,7 @@ mod tests {
     #[test]
     fn emit_with_no_subscribers_does_not_panic() {
         let (tx, _) = broadcast::channel::<ChannelEvent>(16);
-        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
+        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _images, _delta_tx| {
             Box::pin(async { Ok("test".to_string()) })
         });
         let handler = DiscordHandler::new(tx, "discord".to_string(), vec![], on_msg);
@@ -14,18 +14,26 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use async_trait::async_trait;
-use opencrust_common::{Error, Message, Result};
+use opencrust_common::{Error, Message, OutboundFile, Result};
+use opencrust_media::SpeechToText;
 use serenity::all::{self as serenity_model, CreateAttachment, CreateMessage};
 use tokio::sync::{broadcast, mpsc};
 use tracing::{error, info};
 
+use crate::attachments::PendingAttachments;
+use crate::commands::CommandSpec;
+use crate::group::GroupPolicy;
 use crate::traits::{ChannelEvent, ChannelLifecycle, ChannelSender, ChannelStatus};
 use config::DiscordConfig;
 use handler::DiscordHandler;
 
-/// Callback invoked when the bot receives a text message from Discord.
+/// Callback invoked when the bot receives a message from Discord.
 ///
-/// Arguments: `(channel_id, user_id, user_name, text, delta_sender)`.
+/// Arguments: `(channel_id, user_id, user_name, text, attachments, delta_sender)`.
+/// `channel_id` is the thread ID for messages in a thread. `attachments`
+/// holds the message's files, read with [`PendingAttachments::resolve`] once
+/// the sender is admitted; the message being replied to is already folded
+/// into `text`.
 /// Return `Err("__blocked__")` to silently drop unauthorized messages.
 pub type DiscordOnMessageFn = Arc<
     dyn Fn(
@@ -33,6 +41,7 @@ pub type DiscordOnMessageFn = Arc<
             String,
             String,
             String,
+            PendingAttachments,
             Option<mpsc::Sender<String>>,
         ) -> Pin<Box<dyn Future<Output = std::result::Result<String, String>> + Send>>
         + Send
@@ -64,6 +73,15 @@ pub struct DiscordChannel {
 
     /// Shard manager for graceful shutdown.
     shard_manager: Option<std::sync::Arc<serenity_model::ShardManager>>,
+
+    /// Transcribes voice messages, if configured.
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+
+    /// Commands registered as slash commands on connect.
+    commands: Vec<CommandSpec>,
+
+    /// Decides which server channel messages are answered.
+    group_policy: Arc<GroupPolicy>,
 }
 
 impl std::fmt::Debug for DiscordChannel {
@@ -86,17 +104,39 @@ impl DiscordChannel {
             http: None,
             client_handle: None,
             shard_manager: None,
+            speech_to_text: None,
+            commands: Vec::new(),
+            group_policy: Arc::default(),
         }
     }
 
+    /// Transcribe incoming voice messages with this backend.
+    pub fn with_speech_to_text(mut self, stt: Option<Arc<dyn SpeechToText>>) -> Self {
+        self.speech_to_text = stt;
+        self
+    }
+
+    /// Register these commands as Discord slash commands on connect.
+    pub fn with_commands(mut self, commands: Vec<CommandSpec>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Decide which server channel messages are answered with this policy.
+    pub fn with_group_policy(mut self, policy: Arc<GroupPolicy>) -> Self {
+        self.group_policy = policy;
+        self
+    }
+
     /// Create a `DiscordChannel` from the generic `ChannelConfig` settings.
     pub fn from_settings(
         settings: &std::collections::HashMap<String, serde_json::Value>,
     ) -> Result<Self> {
-        let noop: DiscordOnMessageFn =
-            Arc::new(|_channel_id, _user_id, _user_name, _text, _delta_tx| {
+        let noop: DiscordOnMessageFn = Arc::new(
+            |_channel_id, _user_id, _user_name, _text, _attachments, _delta_tx| {
                 Box::pin(async { Err("discord callback not configured".to_string()) })
-            });
+            },
+        );
         Self::from_settings_with_callback(settings, noop)
     }
 
@@ -176,6 +216,14 @@ impl ChannelSender for DiscordSender {
     async fn send_message(&self, message: &Message) -> Result<()> {
         discord_send_message(&self.http, message).await
     }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        discord_send_attachment(&self.http, metadata, file).await
+    }
 }
 
 #[async_trait]
@@ -203,7 +251,11 @@ impl ChannelLifecycle for DiscordChannel {
             "discord".to_string(),
             self.config.guild_ids.clone(),
             Arc::clone(&self.on_message),
-        );
+        )
+        .with_speech_to_text(self.speech_to_text.clone())
+        .with_commands(self.commands.clone())
+        .with_auto_thread(self.config.auto_thread)
+        .with_group_policy(Arc::clone(&self.group_policy));
 
         let mut client =
             serenity_model::Client::builder(&self.config.bot_token, self.config.intents)
@@ -279,21 +331,67 @@ impl ChannelSender for DiscordChannel {
             .ok_or_else(|| Error::Channel("not connected to Discord".into()))?;
         discord_send_message(http, message).await
     }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        let http = self
+            .http
+            .as_ref()
+            .ok_or_else(|| Error::Channel("not connected to Discord".into()))?;
+        discord_send_attachment(http, metadata, file).await
+    }
 }
 
-/// Shared send logic used by both `DiscordChannel` and `DiscordSender`.
-async fn discord_send_message(http: &serenity_model::Http, message: &Message) -> Result<()> {
-    let discord_channel_id = message
-        .metadata
+/// Upload limit for bots in servers without boosts.
+const DISCORD_MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+
+fn discord_channel_id(metadata: &serde_json::Value) -> Result<serenity_model::ChannelId> {
+    metadata
         .get("discord_channel_id")
         .and_then(|v| v.as_str())
         .and_then(|s| s.parse::<u64>().ok())
+        .map(serenity_model::ChannelId::new)
         .ok_or_else(|| {
             Error::Channel("message metadata must contain 'discord_channel_id' to send".into())
-        })?;
+        })
+}
+
+/// Upload a file with its caption as the message text. Discord previews
+/// images and video itself, so every file goes out as a plain attachment.
+async fn discord_send_attachment(
+    http: &serenity_model::Http,
+    metadata: &serde_json::Value,
+    file: OutboundFile,
+) -> Result<()> {
+    let channel = discord_channel_id(metadata)?;
+    if file.data.len() > DISCORD_MAX_UPLOAD_BYTES {
+        return Err(Error::Channel(format!(
+            "file too large for discord ({} bytes, maximum {DISCORD_MAX_UPLOAD_BYTES})",
+            file.data.len()
+        )));
+    }
+
+    let mut builder =
+        CreateMessage::new().add_file(CreateAttachment::bytes(file.data, file.filename));
+    if let Some(caption) = file.caption.filter(|c| !c.is_empty())
+        && let Some(first) = convert::split_discord_chunks(&caption).into_iter().next()
+    {
+        builder = builder.content(first);
+    }
+    channel
+        .send_message(http, builder)
+        .await
+        .map_err(|e| Error::Channel(format!("failed to send file: {e}")))?;
+    Ok(())
+}
 
-    let channel = serenity_model::ChannelId::new(discord_channel_id);
-    let text = convert::to_discord_markdown(&convert::opencrust_content_to_text(&message.content));
+/// Shared send logic used by both `DiscordChannel` and `DiscordSender`.
+async fn discord_send_message(http: &serenity_model::Http, message: &Message) -> Result<()> {
+    let channel = discord_channel_id(&message.metadata)?;
+    let text = convert::opencrust_content_to_text(&message.content);
     let chunks = convert::split_discord_chunks(&text);
     for chunk in chunks {
         let builder = CreateMessage::new().content(chunk);
@@ -318,41 +416,46 @@ mod tests {
             guild_ids: vec![],
             intents: serenity_model::GatewayIntents::default(),
             prefix: None,
+            auto_thread: false,
         }
     }
 
     #[test]
     fn new_channel_starts_disconnected() {
-        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
-            Box::pin(async { Ok("test".to_string()) })
-        });
+        let on_msg: DiscordOnMessageFn =
+            Arc::new(|_ch, _uid, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            });
         let channel = DiscordChannel::new(test_config(), on_msg);
         assert_eq!(channel.status(), ChannelStatus::Disconnected);
     }
 
     #[test]
     fn channel_type_returns_discord() {
-        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
-            Box::pin(async { Ok("test".to_string()) })
-        });
+        let on_msg: DiscordOnMessageFn =
+            Arc::new(|_ch, _uid, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            });
         let channel = DiscordChannel::new(test_config(), on_msg);
         assert_eq!(channel.channel_type(), "discord");
     }
 
     #[test]
     fn display_name_returns_discord() {
-        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
-            Box::pin(async { Ok("test".to_string()) })
-        });
+        let on_msg: DiscordOnMessageFn =
+            Arc::new(|_ch, _uid, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            });
         let channel = DiscordChannel::new(test_config(), on_msg);
         assert_eq!(channel.display_name(), "Discord");
     }
 
     #[test]
     fn subscribe_returns_receiver() {
-        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
-            Box::pin(async { Ok("test".to_string()) })
-        });
+        let on_msg: DiscordOnMessageFn =
+            Arc::new(|_ch, _uid, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            });
         let channel = DiscordChannel::new(test_config(), on_msg);
         let _rx = channel.subscribe();
         // Should not panic — validates broadcast channel is working
@@ -386,9 +489,10 @@ mod tests {
 
     #[tokio::test]
     async fn send_message_without_connection_fails() {
-        let on_msg: DiscordOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
-            Box::pin(async { Ok("test".to_string()) })
-        });
+        let on_msg: DiscordOnMessageFn =
+            Arc::new(|_ch, _uid, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            });
         let channel = DiscordChannel::new(test_config(), on_msg);
         let msg = opencrust_common::Message::text(
             opencrust_common::SessionId::from_string("test"),
@@ -1,3 +1,9 @@
+#[cfg(any(feature = "discord", feature = "slack", feature = "whatsapp"))]
+mod attachments;
+pub mod commands;
+pub mod group;
+pub mod markdown;
+pub mod progressive;
 pub mod protocol;
 pub mod registry;
 #[cfg(feature = "telegram")]
@@ -15,6 +21,13 @@ pub mod slack;
 #[cfg(feature = "whatsapp")]
 pub mod whatsapp;
 
+#[cfg(any(feature = "discord", feature = "slack", feature = "whatsapp"))]
+pub use attachments::PendingAttachments;
+pub use commands::{
+    ArgKind, ArgSpec, ArgValue, CommandArgs, CommandError, CommandRegistry, CommandSpec,
+    ParsedCommand, Permission,
+};
+pub use group::{GroupContext, GroupMessage, GroupMode, GroupPolicy, GroupPolicyConfig};
 #[cfg(all(target_os = "macos", feature = "imessage"))]
 pub use imessage::{IMessageChannel, IMessageOnMessageFn};
 pub use protocol::{
@@ -25,9 +38,12 @@ pub use registry::ChannelRegistry;
 #[cfg(feature = "slack")]
 pub use slack::{SlackChannel, SlackOnMessageFn};
 #[cfg(feature = "telegram")]
-pub use telegram::{MediaAttachment, OnMessageFn, TelegramChannel};
-pub use traits::{Channel, ChannelEvent, ChannelLifecycle, ChannelSender, ChannelStatus};
+pub use telegram::{MediaAttachment, OnMessageFn, PendingMedia, TelegramChannel};
+pub use traits::{
+    Channel, ChannelEvent, ChannelLifecycle, ChannelSender, ChannelStatus, IncomingImage,
+    VoiceReplier,
+};
 #[cfg(feature = "whatsapp-web")]
-pub use whatsapp::web::WhatsAppWebChannel;
+pub use whatsapp::web::{WhatsAppWebChannel, WhatsAppWebOnMessageFn, WhatsAppWebStatus};
 #[cfg(feature = "whatsapp")]
 pub use whatsapp::{WhatsAppChannel, WhatsAppOnMessageFn};
@@ -0,0 +1,639 @@
+//! Markdown intermediate representation shared by the channel formatters.
+//!
+//! Agent replies are Markdown. [`parse`] turns them into a small [`Document`]
+//! of paragraphs, headings and fenced code blocks; a [`Renderer`] writes that
+//! document in a channel's dialect (Telegram MarkdownV2, Slack mrkdwn,
+//! Discord Markdown). [`Document::split`] cuts long documents into pieces
+//! that fit a message limit, breaking between blocks first, then between
+//! lines and words, and re-opening code fences in every piece.
+
+/// Inline span inside a paragraph or heading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Strike(Vec<Inline>),
+    Code(String),
+    Link { text: Vec<Inline>, url: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    /// Consecutive non-blank lines; line breaks stay inside the text.
+    Paragraph(Vec<Inline>),
+    Heading {
+        level: u8,
+        content: Vec<Inline>,
+    },
+    /// Fenced code block. `lang` is empty when the fence has no info string.
+    CodeBlock {
+        lang: String,
+        code: String,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub blocks: Vec<Block>,
+}
+
+/// Writes the IR in one markup dialect. Each method receives already
+/// rendered inner content, except `text`, `code` and `code_block`, which get
+/// raw text to escape as the dialect requires.
+pub trait Renderer {
+    fn text(&self, text: &str) -> String;
+    fn bold(&self, inner: &str) -> String;
+    fn italic(&self, inner: &str) -> String;
+    fn strike(&self, inner: &str) -> String;
+    fn code(&self, code: &str) -> String;
+    fn code_block(&self, lang: &str, code: &str) -> String;
+    fn link(&self, text: &str, url: &str) -> String;
+
+    /// Most chat dialects have no headings, so they are shown in bold.
+    fn heading(&self, _level: u8, inner: &str) -> String {
+        self.bold(inner)
+    }
+}
+
+/// Renders the text without any markup, for plain-text fallbacks.
+pub struct PlainText;
+
+impl Renderer for PlainText {
+    fn text(&self, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn bold(&self, inner: &str) -> String {
+        inner.to_string()
+    }
+
+    fn italic(&self, inner: &str) -> String {
+        inner.to_string()
+    }
+
+    fn strike(&self, inner: &str) -> String {
+        inner.to_string()
+    }
+
+    fn code(&self, code: &str) -> String {
+        code.to_string()
+    }
+
+    fn code_block(&self, _lang: &str, code: &str) -> String {
+        code.to_string()
+    }
+
+    fn link(&self, text: &str, url: &str) -> String {
+        if text == url {
+            url.to_string()
+        } else {
+            format!("{text} ({url})")
+        }
+    }
+
+    fn heading(&self, _level: u8, inner: &str) -> String {
+        inner.to_string()
+    }
+}
+
+/// Parse Markdown, render it with `renderer` and split the result into
+/// messages of at most `max_chars` characters. Always returns at least one
+/// (possibly empty) message.
+pub fn render_chunks(markdown: &str, renderer: &dyn Renderer, max_chars: usize) -> Vec<String> {
+    let chunks: Vec<String> = parse(markdown)
+        .split(renderer, max_chars)
+        .iter()
+        .map(|doc| doc.render(renderer))
+        .collect();
+    if chunks.is_empty() {
+        vec![String::new()]
+    } else {
+        chunks
+    }
+}
+
+/// Parse the Markdown subset chat replies use: paragraphs, `#` headings,
+/// fenced code blocks, `**bold**`, `*italic*`, `~~strike~~`, `` `code` `` and
+/// `[links](url)`. Anything else is kept as text.
+pub fn parse(markdown: &str) -> Document {
+    let markdown = markdown.replace("\r\n", "\n");
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut lines = markdown.lines();
+
+    let flush = |paragraph: &mut Vec<&str>, blocks: &mut Vec<Block>| {
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(parse_inline(&paragraph.join("\n"))));
+            paragraph.clear();
+        }
+    };
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if let Some(info) = trimmed.strip_prefix("```") {
+            flush(&mut paragraph, &mut blocks);
+            let mut code = Vec::new();
+            for line in lines.by_ref() {
+                if line.trim_start().starts_with("```") {
+                    break;
+                }
+                code.push(line);
+            }
+            blocks.push(Block::CodeBlock {
+                lang: info.trim().to_string(),
+                code: code.join("\n"),
+            });
+        } else if line.trim().is_empty() {
+            flush(&mut paragraph, &mut blocks);
+        } else if let Some((level, title)) = heading(trimmed) {
+            flush(&mut paragraph, &mut blocks);
+            blocks.push(Block::Heading {
+                level,
+                content: parse_inline(title),
+            });
+        } else {
+            paragraph.push(line);
+        }
+    }
+    flush(&mut paragraph, &mut blocks);
+
+    Document { blocks }
+}
+
+fn heading(line: &str) -> Option<(u8, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let title = line[level..].strip_prefix(' ')?;
+    Some((level as u8, title.trim()))
+}
+
+fn parse_inline(text: &str) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = Vec::new();
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (span, next) = inline_span(&chars, i);
+        match span {
+            Some(span) => {
+                if !plain.is_empty() {
+                    out.push(Inline::Text(std::mem::take(&mut plain)));
+                }
+                out.push(span);
+                i = next;
+            }
+            None => {
+                plain.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    if !plain.is_empty() {
+        out.push(Inline::Text(plain));
+    }
+    out
+}
+
+/// Try to read a formatted span starting at `i`. Returns the span and the
+/// index after it, or `None` when the characters are plain text.
+fn inline_span(chars: &[char], i: usize) -> (Option<Inline>, usize) {
+    let at = |s: &str, pos: usize| {
+        s.chars()
+            .enumerate()
+            .all(|(k, c)| chars.get(pos + k) == Some(&c))
+    };
+    let collect = |from: usize, to: usize| chars[from..to].iter().collect::<String>();
+
+    match chars[i] {
+        '`' => {
+            let ticks = chars[i..].iter().take_while(|&&c| c == '`').count();
+            let fence = "`".repeat(ticks);
+            let start = i + ticks;
+            let mut j = start;
+            while j < chars.len() {
+                if at(&fence, j) && chars.get(j + ticks) != Some(&'`') {
+                    return (Some(Inline::Code(collect(start, j))), j + ticks);
+                }
+                j += 1;
+            }
+            (None, i)
+        }
+        '*' | '_' if at(&chars[i].to_string().repeat(2), i) => {
+            let marker = chars[i].to_string().repeat(2);
+            delimited(chars, i, &marker, Inline::Bold)
+        }
+        '~' if at("~~", i) => delimited(chars, i, "~~", Inline::Strike),
+        '*' => delimited(chars, i, "*", Inline::Italic),
+        '_' if i == 0 || !chars[i - 1].is_alphanumeric() => {
+            let (span, next) = delimited(chars, i, "_", Inline::Italic);
+            if span.is_some() && chars.get(next).is_some_and(|c| c.is_alphanumeric()) {
+                return (None, i);
+            }
+            (span, next)
+        }
+        '[' => {
+            let Some(close) = (i + 1..chars.len()).find(|&j| chars[j] == ']') else {
+                return (None, i);
+            };
+            if chars.get(close + 1) != Some(&'(') {
+                return (None, i);
+            }
+            let Some(end) = (close + 2..chars.len()).find(|&j| chars[j] == ')') else {
+                return (None, i);
+            };
+            let url = collect(close + 2, end);
+            if url.is_empty() || url.contains(char::is_whitespace) {
+                return (None, i);
+            }
+            (
+                Some(Inline::Link {
+                    text: parse_inline(&collect(i + 1, close)),
+                    url,
+                }),
+                end + 1,
+            )
+        }
+        _ => (None, i),
+    }
+}
+
+/// A span wrapped in `marker` on both sides. The content must not start or
+/// end with whitespace, so `2 * 3 * 4` stays plain text.
+fn delimited(
+    chars: &[char],
+    i: usize,
+    marker: &str,
+    wrap: fn(Vec<Inline>) -> Inline,
+) -> (Option<Inline>, usize) {
+    let width = marker.chars().count();
+    let start = i + width;
+    if chars.get(start).is_none_or(|c| c.is_whitespace()) {
+        return (None, i);
+    }
+    let marker: Vec<char> = marker.chars().collect();
+    let mut j = start + 1;
+    while j + width <= chars.len() {
+        if chars[j..j + width] == marker[..] && !chars[j - 1].is_whitespace() {
+            let inner: String = chars[start..j].iter().collect();
+            return (Some(wrap(parse_inline(&inner))), j + width);
+        }
+        j += 1;
+    }
+    (None, i)
+}
+
+impl Document {
+    pub fn render(&self, renderer: &dyn Renderer) -> String {
+        self.blocks
+            .iter()
+            .map(|block| render_block(block, renderer))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Split into documents whose rendering is at most `max_chars`
+    /// characters. Blocks are kept whole where possible; oversized code
+    /// blocks are cut between lines into separately fenced blocks, and
+    /// oversized paragraphs between lines or words.
+    pub fn split(&self, renderer: &dyn Renderer, max_chars: usize) -> Vec<Document> {
+        let max_chars = max_chars.max(1);
+        let mut docs = Vec::new();
+        let mut current = Document::default();
+        let mut current_len = 0;
+
+        for block in &self.blocks {
+            for piece in fit_block(block, renderer, max_chars) {
+                let len = char_len(&render_block(&piece, renderer));
+                let sep = if current.blocks.is_empty() { 0 } else { 2 };
+                if !current.blocks.is_empty() && current_len + sep + len > max_chars {
+                    docs.push(std::mem::take(&mut current));
+                    current_len = 0;
+                }
+                current_len += if current.blocks.is_empty() {
+                    len
+                } else {
+                    2 + len
+                };
+                current.blocks.push(piece);
+            }
+        }
+        if !current.blocks.is_empty() {
+            docs.push(current);
+        }
+        docs
+    }
+}
+
+fn render_block(block: &Block, r: &dyn Renderer) -> String {
+    match block {
+        Block::Paragraph(inlines) => render_inlines(inlines, r),
+        Block::Heading { level, content } => r.heading(*level, &render_inlines(content, r)),
+        Block::CodeBlock { lang, code } => r.code_block(lang, code),
+    }
+}
+
+fn render_inlines(inlines: &[Inline], r: &dyn Renderer) -> String {
+    inlines
+        .iter()
+        .map(|inline| render_inline(inline, r))
+        .collect()
+}
+
+fn render_inline(inline: &Inline, r: &dyn Renderer) -> String {
+    match inline {
+        Inline::Text(text) => r.text(text),
+        Inline::Bold(inner) => r.bold(&render_inlines(inner, r)),
+        Inline::Italic(inner) => r.italic(&render_inlines(inner, r)),
+        Inline::Strike(inner) => r.strike(&render_inlines(inner, r)),
+        Inline::Code(code) => r.code(code),
+        Inline::Link { text, url } => r.link(&render_inlines(text, r), url),
+    }
+}
+
+/// The text of an inline span without any formatting.
+fn plain(inline: &Inline) -> String {
+    match inline {
+        Inline::Text(text) | Inline::Code(text) => text.clone(),
+        Inline::Bold(inner) | Inline::Italic(inner) | Inline::Strike(inner) => {
+            inner.iter().map(plain).collect()
+        }
+        Inline::Link { text, url } => {
+            format!("{} ({url})", text.iter().map(plain).collect::<String>())
+        }
+    }
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Break one block into pieces that each render within `max_chars`.
+fn fit_block(block: &Block, r: &dyn Renderer, max_chars: usize) -> Vec<Block> {
+    if char_len(&render_block(block, r)) <= max_chars {
+        return vec![block.clone()];
+    }
+    match block {
+        Block::CodeBlock { lang, code } => fit_text(code, max_chars, |piece| {
+            char_len(&r.code_block(lang, piece.trim_end_matches('\n')))
+        })
+        .into_iter()
+        .map(|piece| Block::CodeBlock {
+            lang: lang.clone(),
+            code: piece.trim_end_matches('\n').to_string(),
+        })
+        .collect(),
+        Block::Paragraph(inlines)
+        | Block::Heading {
+            content: inlines, ..
+        } => fit_inlines(inlines, r, max_chars)
+            .into_iter()
+            .map(Block::Paragraph)
+            .collect(),
+    }
+}
+
+/// Pack inline spans into paragraphs that render within `max_chars`. Text is
+/// broken into words; a cut prefers the last line break in the second half
+/// of a piece. Spans too long for any piece lose their formatting.
+fn fit_inlines(inlines: &[Inline], r: &dyn Renderer, max_chars: usize) -> Vec<Vec<Inline>> {
+    let mut units: Vec<Inline> = Vec::new();
+    for inline in inlines {
+        let inline = if char_len(&render_inline(inline, r)) > max_chars {
+            Inline::Text(plain(inline))
+        } else {
+            inline.clone()
+        };
+        match inline {
+            Inline::Text(text) => {
+                for word in text.split_inclusive(char::is_whitespace) {
+                    for piece in fit_text(word, max_chars, |t| char_len(&r.text(t))) {
+                        units.push(Inline::Text(piece));
+                    }
+                }
+            }
+            other => units.push(other),
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<Inline> = Vec::new();
+    let mut current_len = 0;
+    for unit in units {
+        let len = char_len(&render_inline(&unit, r));
+        if !current.is_empty() && current_len + len > max_chars {
+            let line_break = current
+                .iter()
+                .rposition(|u| matches!(u, Inline::Text(t) if t.ends_with('\n')))
+                .filter(|&idx| idx + 1 >= current.len() / 2 && idx + 1 < current.len());
+            let carry = match line_break {
+                Some(idx) => current.split_off(idx + 1),
+                None => Vec::new(),
+            };
+            pieces.push(tidy(std::mem::replace(&mut current, carry)));
+            current_len = current.iter().map(|u| char_len(&render_inline(u, r))).sum();
+        }
+        current_len += len;
+        current.push(unit);
+    }
+    if !current.is_empty() {
+        pieces.push(tidy(current));
+    }
+    pieces
+}
+
+/// Merge adjacent text units and trim whitespace left at the cut points.
+fn tidy(units: Vec<Inline>) -> Vec<Inline> {
+    let mut out: Vec<Inline> = Vec::new();
+    for unit in units {
+        match (out.last_mut(), unit) {
+            (Some(Inline::Text(prev)), Inline::Text(text)) => prev.push_str(&text),
+            (_, unit) => out.push(unit),
+        }
+    }
+    if let Some(Inline::Text(first)) = out.first_mut() {
+        *first = first.trim_start().to_string();
+    }
+    if let Some(Inline::Text(last)) = out.last_mut() {
+        *last = last.trim_end().to_string();
+    }
+    out.retain(|u| !matches!(u, Inline::Text(t) if t.is_empty()));
+    out
+}
+
+/// Split `text` into pieces for which `measure` is at most `max_chars`,
+/// breaking at line and word boundaries where possible.
+fn fit_text(text: &str, max_chars: usize, measure: impl Fn(&str) -> usize) -> Vec<String> {
+    let total = measure(text);
+    if total <= max_chars || char_len(text) <= 1 {
+        return vec![text.to_string()];
+    }
+    let mut size = (char_len(text) * max_chars / total).max(1);
+    loop {
+        let pieces = crate::progressive::split_message(text, size);
+        if size == 1 || pieces.iter().all(|p| measure(p) <= max_chars) {
+            return pieces;
+        }
+        size = (size * 3 / 4).max(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders the IR back as standard Markdown.
+    struct Commonmark;
+
+    impl Renderer for Commonmark {
+        fn text(&self, text: &str) -> String {
+            text.to_string()
+        }
+        fn bold(&self, inner: &str) -> String {
+            format!("**{inner}**")
+        }
+        fn italic(&self, inner: &str) -> String {
+            format!("*{inner}*")
+        }
+        fn strike(&self, inner: &str) -> String {
+            format!("~~{inner}~~")
+        }
+        fn code(&self, code: &str) -> String {
+            format!("`{code}`")
+        }
+        fn code_block(&self, lang: &str, code: &str) -> String {
+            format!("```{lang}\n{code}\n```")
+        }
+        fn link(&self, text: &str, url: &str) -> String {
+            format!("[{text}]({url})")
+        }
+    }
+
+    fn text(s: &str) -> Inline {
+        Inline::Text(s.to_string())
+    }
+
+    #[test]
+    fn parses_blocks() {
+        let doc = parse("# Title\n\nfirst line\nsecond line\n\n```rust\nfn main() {}\n```\ntail");
+        assert_eq!(
+            doc.blocks,
+            vec![
+                Block::Heading {
+                    level: 1,
+                    content: vec![text("Title")]
+                },
+                Block::Paragraph(vec![text("first line\nsecond line")]),
+                Block::CodeBlock {
+                    lang: "rust".into(),
+                    code: "fn main() {}".into()
+                },
+                Block::Paragraph(vec![text("tail")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_inline_spans() {
+        assert_eq!(
+            parse_inline("a **b *c* d** `d` ~~e~~ [f](https://x.y) _g_"),
+            vec![
+                text("a "),
+                Inline::Bold(vec![
+                    text("b "),
+                    Inline::Italic(vec![text("c")]),
+                    text(" d")
+                ]),
+                text(" "),
+                Inline::Code("d".into()),
+                text(" "),
+                Inline::Strike(vec![text("e")]),
+                text(" "),
+                Inline::Link {
+                    text: vec![text("f")],
+                    url: "https://x.y".into()
+                },
+                text(" "),
+                Inline::Italic(vec![text("g")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn stray_markers_stay_text() {
+        assert_eq!(parse_inline("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+        assert_eq!(
+            parse_inline("snake_case_name"),
+            vec![text("snake_case_name")]
+        );
+        assert_eq!(parse_inline("**open"), vec![text("**open")]);
+        assert_eq!(parse_inline("[not a link]"), vec![text("[not a link]")]);
+    }
+
+    #[test]
+    fn unclosed_fence_runs_to_the_end() {
+        let doc = parse("```\nlet x = 1;");
+        assert_eq!(doc.render(&Commonmark), "```\nlet x = 1;\n```");
+    }
+
+    #[test]
+    fn split_keeps_blocks_whole_when_possible() {
+        let doc = parse("one two\n\nthree four\n\nfive");
+        let chunks: Vec<String> = doc
+            .split(&Commonmark, 20)
+            .iter()
+            .map(|d| d.render(&Commonmark))
+            .collect();
+        assert_eq!(chunks, vec!["one two\n\nthree four", "five"]);
+    }
+
+    #[test]
+    fn split_reopens_code_fences() {
+        let code = (1..=6).map(|n| format!("line {n}")).collect::<Vec<_>>();
+        let markdown = format!("```py\n{}\n```", code.join("\n"));
+        let chunks = render_chunks(&markdown, &Commonmark, 30);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= 30, "{chunk:?}");
+            assert!(chunk.starts_with("```py\n") && chunk.ends_with("\n```"));
+        }
+        let rejoined: Vec<&str> = chunks
+            .iter()
+            .flat_map(|c| {
+                c.trim_start_matches("```py\n")
+                    .trim_end_matches("\n```")
+                    .lines()
+            })
+            .collect();
+        assert_eq!(rejoined, code);
+    }
+
+    #[test]
+    fn split_long_paragraph_between_words() {
+        let markdown = "alpha beta gamma **delta** epsilon zeta eta theta";
+        let chunks = render_chunks(markdown, &Commonmark, 20);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= 20, "{chunk:?}");
+        }
+        assert_eq!(chunks.join(" "), markdown);
+    }
+
+    #[test]
+    fn split_hard_cuts_long_words() {
+        let chunks = render_chunks(&"x".repeat(25), &Commonmark, 10);
+        assert_eq!(chunks, vec!["x".repeat(10), "x".repeat(10), "x".repeat(5)]);
+        assert_eq!(render_chunks("", &Commonmark, 10), vec![String::new()]);
+    }
+
+    #[test]
+    fn plain_text_strips_markup() {
+        let doc = parse("# Hi\n\n**bold** and [docs](https://d.example)");
+        assert_eq!(
+            doc.render(&PlainText),
+            "Hi\n\nbold and docs (https://d.example)"
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Streaming replies shown by editing messages in place.
+//!
+//! A [`ProgressiveMessage`] posts a placeholder while the agent is working,
+//! edits it as text streams in (no more often than the platform allows), and
+//! spills into follow-up messages once the text passes the platform's length
+//! limit. Platforms plug in through [`MessageEditor`].
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Shown until the first text arrives.
+pub const PLACEHOLDER_TEXT: &str = "…";
+
+/// How long to wait for output before posting the placeholder. Replies that
+/// finish sooner (including refusals) go out as one message.
+const FIRST_POST_DELAY: Duration = Duration::from_secs(1);
+
+/// Post, edit and delete operations for one chat.
+#[async_trait]
+pub trait MessageEditor: Send + Sync {
+    /// Platform message handle, e.g. a Discord message id or a Slack `ts`.
+    type Id: Clone + Send + Sync;
+
+    /// Longest message the platform accepts, in characters.
+    fn max_chars(&self) -> usize;
+
+    /// Convert Markdown to the platform's markup, split into messages no
+    /// longer than [`Self::max_chars`]. Platforms with markup render through
+    /// [`crate::markdown::render_chunks`] so formatting survives the split.
+    fn chunks(&self, text: &str) -> Vec<String> {
+        split_message(text, self.max_chars())
+    }
+
+    async fn post(&self, text: &str) -> Result<Self::Id, String>;
+    async fn edit(&self, id: &Self::Id, text: &str) -> Result<(), String>;
+    async fn delete(&self, id: &Self::Id) -> Result<(), String>;
+}
+
+/// A reply that grows in place as text streams in.
+pub struct ProgressiveMessage<E: MessageEditor> {
+    editor: E,
+    min_interval: Duration,
+    first_post_at: Instant,
+    last_sync: Option<Instant>,
+    sent: Vec<(E::Id, String)>,
+}
+
+impl<E: MessageEditor> ProgressiveMessage<E> {
+    /// `min_interval` is the shortest gap between edits, chosen to stay under
+    /// the platform's rate limit.
+    pub fn new(editor: E, min_interval: Duration) -> Self {
+        Self {
+            editor,
+            min_interval,
+            first_post_at: Instant::now() + FIRST_POST_DELAY,
+            last_sync: None,
+            sent: Vec::new(),
+        }
+    }
+
+    /// Post the placeholder unless something is already visible.
+    pub async fn start(&mut self) -> Result<(), String> {
+        if self.sent.is_empty() {
+            let id = self.editor.post(PLACEHOLDER_TEXT).await?;
+            self.sent.push((id, PLACEHOLDER_TEXT.to_string()));
+            self.last_sync = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Show `text` if the throttle allows it. Returns whether anything was sent.
+    pub async fn update(&mut self, text: &str) -> Result<bool, String> {
+        let now = Instant::now();
+        if now < self.first_post_at
+            || self
+                .last_sync
+                .is_some_and(|last| now.duration_since(last) < self.min_interval)
+        {
+            return Ok(false);
+        }
+        self.sync(text, false).await?;
+        Ok(true)
+    }
+
+    /// Show the final text, ignoring the throttle, and delete follow-up
+    /// messages the final text no longer needs.
+    pub async fn finish(&mut self, text: &str) -> Result<(), String> {
+        self.sync(text, true).await
+    }
+
+    /// Remove everything posted so far, e.g. when the reply is dropped.
+    pub async fn cancel(&mut self) {
+        for (id, _) in self.sent.drain(..) {
+            if let Err(e) = self.editor.delete(&id).await {
+                warn!("failed to delete streamed message: {e}");
+            }
+        }
+    }
+
+    /// Drain `deltas`, posting the placeholder if no text arrives within a
+    /// second and editing as text accumulates. Returns the streamed text.
+    ///
+    /// Edit failures stop further edits but the channel is still drained, so
+    /// the producer never blocks on a full buffer.
+    pub async fn stream(&mut self, deltas: &mut mpsc::Receiver<String>) -> String {
+        let mut accumulated = String::new();
+        let mut failed = false;
+        loop {
+            tokio::select! {
+                delta = deltas.recv() => {
+                    let Some(delta) = delta else { break };
+                    accumulated.push_str(&delta);
+                    if !failed && let Err(e) = self.update(&accumulated).await {
+                        warn!("failed to stream message update: {e}");
+                        failed = true;
+                    }
+                }
+                _ = tokio::time::sleep_until(self.first_post_at),
+                    if !failed && self.sent.is_empty() =>
+                {
+                    if let Err(e) = self.start().await {
+                        warn!("failed to post placeholder: {e}");
+                        failed = true;
+                    }
+                }
+            }
+        }
+        accumulated
+    }
+
+    async fn sync(&mut self, text: &str, is_final: bool) -> Result<(), String> {
+        let chunks = self.editor.chunks(text);
+
+        for (idx, chunk) in chunks.iter().enumerate() {
+            if let Some((id, shown)) = self.sent.get_mut(idx) {
+                if shown != chunk {
+                    self.editor.edit(id, chunk).await?;
+                    *shown = chunk.clone();
+                }
+            } else {
+                let id = self.editor.post(chunk).await?;
+                self.sent.push((id, chunk.clone()));
+            }
+        }
+
+        if is_final && self.sent.len() > chunks.len() {
+            for (id, _) in self.sent.drain(chunks.len()..) {
+                if let Err(e) = self.editor.delete(&id).await {
+                    warn!("failed to delete surplus message: {e}");
+                }
+            }
+        }
+
+        self.last_sync = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// Split text into chunks of at most `max_chars` characters, preferring to
+/// break after a newline, then after whitespace, in the second half of a chunk.
+pub fn split_message(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut rest = text;
+
+    while rest.chars().count() > max_chars {
+        let window_end = rest
+            .char_indices()
+            .nth(max_chars)
+            .map(|(i, _)| i)
+            .unwrap_or(rest.len());
+        let window = &rest[..window_end];
+        let half = window
+            .char_indices()
+            .nth(max_chars / 2)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        let cut = window
+            .rfind('\n')
+            .filter(|&i| i >= half)
+            .or_else(|| window.rfind(char::is_whitespace).filter(|&i| i >= half))
+            .map(|i| i + window[i..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(window_end);
+        chunks.push(rest[..cut].to_string());
+        rest = &rest[cut..];
+    }
+
+    if !rest.is_empty() || chunks.is_empty() {
+        chunks.push(rest.to_string());
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeEditor {
+        ops: Mutex<Vec<String>>,
+        next_id: Mutex<u32>,
+    }
+
+    #[async_trait]
+    impl MessageEditor for FakeEditor {
+        type Id = u32;
+
+        fn max_chars(&self) -> usize {
+            10
+        }
+
+        async fn post(&self, text: &str) -> Result<u32, String> {
+            let mut next = self.next_id.lock().unwrap();
+            *next += 1;
+            self.ops.lock().unwrap().push(format!("post {next} {text}"));
+            Ok(*next)
+        }
+
+        async fn edit(&self, id: &u32, text: &str) -> Result<(), String> {
+            self.ops.lock().unwrap().push(format!("edit {id} {text}"));
+            Ok(())
+        }
+
+        async fn delete(&self, id: &u32) -> Result<(), String> {
+            self.ops.lock().unwrap().push(format!("delete {id}"));
+            Ok(())
+        }
+    }
+
+    fn unthrottled() -> ProgressiveMessage<FakeEditor> {
+        let mut message = ProgressiveMessage::new(FakeEditor::default(), Duration::ZERO);
+        message.first_post_at = Instant::now();
+        message
+    }
+
+    fn ops(message: &ProgressiveMessage<FakeEditor>) -> Vec<String> {
+        message.editor.ops.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn edits_in_place_and_spills_into_follow_ups() {
+        let mut message = unthrottled();
+        message.start().await.unwrap();
+        assert!(message.update("hello").await.unwrap());
+        assert!(message.update("hello world").await.unwrap());
+        message.finish("short").await.unwrap();
+
+        assert_eq!(
+            ops(&message),
+            vec![
+                "post 1 …",
+                "edit 1 hello",
+                "edit 1 hello ",
+                "post 2 world",
+                "edit 1 short",
+                "delete 2",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn updates_are_throttled() {
+        let mut message = ProgressiveMessage::new(FakeEditor::default(), Duration::from_secs(60));
+        assert!(!message.update("too early").await.unwrap());
+
+        message.first_post_at = Instant::now();
+        assert!(message.update("first").await.unwrap());
+        assert!(!message.update("second").await.unwrap());
+        message.finish("final").await.unwrap();
+        assert_eq!(ops(&message), vec!["post 1 first", "edit 1 final"]);
+    }
+
+    #[tokio::test]
+    async fn cancel_deletes_everything_posted() {
+        let mut message = unthrottled();
+        message.update("0123456789abc").await.unwrap();
+        message.cancel().await;
+        assert_eq!(
+            ops(&message),
+            vec!["post 1 0123456789", "post 2 abc", "delete 1", "delete 2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_returns_accumulated_text() {
+        let (tx, mut rx) = mpsc::channel(8);
+        tx.send("ab".to_string()).await.unwrap();
+        tx.send("cd".to_string()).await.unwrap();
+        drop(tx);
+        let mut message = ProgressiveMessage::new(FakeEditor::default(), Duration::ZERO);
+        assert_eq!(message.stream(&mut rx).await, "abcd");
+        // Finished before the first-post delay: nothing was shown yet.
+        assert!(ops(&message).is_empty());
+    }
+
+    #[test]
+    fn split_prefers_line_and_word_breaks() {
+        assert_eq!(split_message("", 10), vec![""]);
+        assert_eq!(
+            split_message("line one\nline two", 12),
+            vec!["line one\n", "line two"]
+        );
+        assert_eq!(
+            split_message("aaaa bbbb cccc", 10),
+            vec!["aaaa bbbb ", "cccc"]
+        );
+        assert_eq!(
+            split_message("abcdefghijkl", 5),
+            vec!["abcde", "fghij", "kl"]
+        );
+        assert_eq!(split_message("ééééé", 2), vec!["éé", "éé", "é"]);
+    }
+}
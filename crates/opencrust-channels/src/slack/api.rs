@@ -10,6 +10,9 @@ struct SlackApiResponse {
     error: Option<String>,
     url: Option<String>,
     ts: Option<String>,
+    upload_url: Option<String>,
+    file_id: Option<String>,
+    user_id: Option<String>,
 }
 
 /// Call `apps.connections.open` to get a WebSocket URL for Socket Mode.
@@ -36,20 +39,50 @@ pub async fn open_connection(client: &Client, app_token: &str) -> Result<String,
         .ok_or_else(|| "apps.connections.open: no url in response".to_string())
 }
 
-/// Post a new message to a Slack channel. Returns the message `ts` (timestamp ID).
+/// Call `auth.test` and return the bot's own user ID, used to recognise
+/// mentions of the bot.
+pub async fn auth_test(client: &Client, bot_token: &str) -> Result<String, String> {
+    let resp = client
+        .post(format!("{SLACK_API_BASE}/auth.test"))
+        .bearer_auth(bot_token)
+        .send()
+        .await
+        .map_err(|e| format!("auth.test request failed: {e}"))?;
+
+    let body: SlackApiResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("auth.test parse failed: {e}"))?;
+
+    if !body.ok {
+        let err = body.error.unwrap_or_else(|| "unknown".to_string());
+        return Err(format!("auth.test error: {err}"));
+    }
+
+    body.user_id
+        .ok_or_else(|| "auth.test: no user_id in response".to_string())
+}
+
+/// Post a new message to a Slack channel, inside `thread_ts` when given.
+/// Returns the message `ts` (timestamp ID).
 pub async fn post_message(
     client: &Client,
     bot_token: &str,
     channel: &str,
+    thread_ts: Option<&str>,
     text: &str,
 ) -> Result<String, String> {
+    let mut body = serde_json::json!({
+        "channel": channel,
+        "text": text,
+    });
+    if let Some(thread_ts) = thread_ts {
+        body["thread_ts"] = thread_ts.into();
+    }
     let resp = client
         .post(format!("{SLACK_API_BASE}/chat.postMessage"))
         .bearer_auth(bot_token)
-        .json(&serde_json::json!({
-            "channel": channel,
-            "text": text,
-        }))
+        .json(&body)
         .send()
         .await
         .map_err(|e| format!("chat.postMessage request failed: {e}"))?;
@@ -100,3 +133,186 @@ pub async fn update_message(
 
     Ok(())
 }
+
+/// Delete a message the bot posted.
+pub async fn delete_message(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    ts: &str,
+) -> Result<(), String> {
+    let resp = client
+        .post(format!("{SLACK_API_BASE}/chat.delete"))
+        .bearer_auth(bot_token)
+        .json(&serde_json::json!({
+            "channel": channel,
+            "ts": ts,
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("chat.delete request failed: {e}"))?;
+
+    let body: SlackApiResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("chat.delete parse failed: {e}"))?;
+
+    if !body.ok {
+        let err = body.error.unwrap_or_else(|| "unknown".to_string());
+        return Err(format!("chat.delete error: {err}"));
+    }
+
+    Ok(())
+}
+
+/// Add an emoji reaction (name without colons) to a message.
+pub async fn add_reaction(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    ts: &str,
+    name: &str,
+) -> Result<(), String> {
+    reaction_call(client, bot_token, "reactions.add", channel, ts, name).await
+}
+
+/// Remove an emoji reaction the bot added earlier.
+pub async fn remove_reaction(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    ts: &str,
+    name: &str,
+) -> Result<(), String> {
+    reaction_call(client, bot_token, "reactions.remove", channel, ts, name).await
+}
+
+async fn reaction_call(
+    client: &Client,
+    bot_token: &str,
+    method: &str,
+    channel: &str,
+    ts: &str,
+    name: &str,
+) -> Result<(), String> {
+    let resp = client
+        .post(format!("{SLACK_API_BASE}/{method}"))
+        .bearer_auth(bot_token)
+        .json(&serde_json::json!({
+            "channel": channel,
+            "timestamp": ts,
+            "name": name,
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("{method} request failed: {e}"))?;
+
+    let body: SlackApiResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("{method} parse failed: {e}"))?;
+
+    if !body.ok {
+        let err = body.error.unwrap_or_else(|| "unknown".to_string());
+        return Err(format!("{method} error: {err}"));
+    }
+
+    Ok(())
+}
+
+/// Reply to a slash command through its `response_url`. The reply is only
+/// visible to the user who ran the command.
+pub async fn respond_to_command(
+    client: &Client,
+    response_url: &str,
+    text: &str,
+) -> Result<(), String> {
+    let resp = client
+        .post(response_url)
+        .json(&serde_json::json!({
+            "response_type": "ephemeral",
+            "text": text,
+        }))
+        .send()
+        .await
+        .map_err(|e| format!("slash command response failed: {e}"))?;
+
+    if !resp.status().is_success() {
+        return Err(format!("slash command response error: {}", resp.status()));
+    }
+    Ok(())
+}
+
+/// Share a file in a channel using Slack's external upload flow: reserve an
+/// upload URL, send the bytes there, then complete the upload into `channel`
+/// (inside `thread_ts` when given).
+pub async fn upload_file(
+    client: &Client,
+    bot_token: &str,
+    channel: &str,
+    thread_ts: Option<&str>,
+    data: Vec<u8>,
+    filename: &str,
+    initial_comment: Option<&str>,
+) -> Result<(), String> {
+    let resp = client
+        .post(format!("{SLACK_API_BASE}/files.getUploadURLExternal"))
+        .bearer_auth(bot_token)
+        .form(&[("filename", filename), ("length", &data.len().to_string())])
+        .send()
+        .await
+        .map_err(|e| format!("files.getUploadURLExternal request failed: {e}"))?;
+
+    let body: SlackApiResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("files.getUploadURLExternal parse failed: {e}"))?;
+
+    if !body.ok {
+        let err = body.error.unwrap_or_else(|| "unknown".to_string());
+        return Err(format!("files.getUploadURLExternal error: {err}"));
+    }
+    let (Some(upload_url), Some(file_id)) = (body.upload_url, body.file_id) else {
+        return Err("files.getUploadURLExternal: no upload_url in response".to_string());
+    };
+
+    let resp = client
+        .post(&upload_url)
+        .body(data)
+        .send()
+        .await
+        .map_err(|e| format!("file upload failed: {e}"))?;
+    if !resp.status().is_success() {
+        return Err(format!("file upload failed with HTTP {}", resp.status()));
+    }
+
+    let mut complete = serde_json::json!({
+        "files": [{ "id": file_id, "title": filename }],
+        "channel_id": channel,
+    });
+    if let Some(comment) = initial_comment.filter(|c| !c.is_empty()) {
+        complete["initial_comment"] = comment.into();
+    }
+    if let Some(thread_ts) = thread_ts {
+        complete["thread_ts"] = thread_ts.into();
+    }
+    let resp = client
+        .post(format!("{SLACK_API_BASE}/files.completeUploadExternal"))
+        .bearer_auth(bot_token)
+        .json(&complete)
+        .send()
+        .await
+        .map_err(|e| format!("files.completeUploadExternal request failed: {e}"))?;
+
+    let body: SlackApiResponse = resp
+        .json()
+        .await
+        .map_err(|e| format!("files.completeUploadExternal parse failed: {e}"))?;
+
+    if !body.ok {
+        let err = body.error.unwrap_or_else(|| "unknown".to_string());
+        return Err(format!("files.completeUploadExternal error: {err}"));
+    }
+
+    Ok(())
+}
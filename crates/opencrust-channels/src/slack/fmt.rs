@@ -1,79 +1,58 @@
-/// Convert standard markdown to Slack mrkdwn format.
+use crate::markdown::{self, Renderer};
+
+/// Renders the Markdown IR as Slack mrkdwn.
 ///
-/// - `**bold**` → `*bold*`  (Slack uses single asterisks)
-/// - `` `code` `` and ` ```blocks``` ` pass through unchanged
-/// - Escapes `&`, `<`, `>` outside code blocks
-pub fn to_slack_mrkdwn(input: &str) -> String {
-    let mut result = String::with_capacity(input.len());
-    let chars: Vec<char> = input.chars().collect();
-    let len = chars.len();
-    let mut i = 0;
-
-    while i < len {
-        // Fenced code block: ```...```
-        if i + 2 < len && chars[i] == '`' && chars[i + 1] == '`' && chars[i + 2] == '`' {
-            result.push_str("```");
-            i += 3;
-            // Copy everything until closing ```
-            loop {
-                if i + 2 < len && chars[i] == '`' && chars[i + 1] == '`' && chars[i + 2] == '`' {
-                    result.push_str("```");
-                    i += 3;
-                    break;
-                }
-                if i >= len {
-                    break;
-                }
-                result.push(chars[i]);
-                i += 1;
-            }
-            continue;
-        }
-
-        // Inline code: `...`
-        if chars[i] == '`' {
-            result.push('`');
-            i += 1;
-            while i < len && chars[i] != '`' {
-                result.push(chars[i]);
-                i += 1;
-            }
-            if i < len {
-                result.push('`');
-                i += 1;
-            }
-            continue;
-        }
-
-        // Bold: **text** → *text*
-        if i + 1 < len && chars[i] == '*' && chars[i + 1] == '*' {
-            result.push('*');
-            i += 2;
-            while i < len && !(i + 1 < len && chars[i] == '*' && chars[i + 1] == '*') {
-                result.push(escape_char(chars[i]));
-                i += 1;
-            }
-            result.push('*');
-            if i + 1 < len {
-                i += 2; // skip closing **
-            }
-            continue;
-        }
-
-        // Escape & < > in plain text
-        result.push(escape_char(chars[i]));
-        i += 1;
+/// - `**bold**` → `*bold*`, `*italic*` → `_italic_`, `~~strike~~` → `~strike~`
+/// - `[text](url)` → `<url|text>`
+/// - Headings become bold lines
+/// - Escapes `&`, `<`, `>` as Slack requires for message text
+pub struct SlackMrkdwn;
+
+impl Renderer for SlackMrkdwn {
+    fn text(&self, text: &str) -> String {
+        escape(text)
+    }
+
+    fn bold(&self, inner: &str) -> String {
+        format!("*{inner}*")
+    }
+
+    fn italic(&self, inner: &str) -> String {
+        format!("_{inner}_")
+    }
+
+    fn strike(&self, inner: &str) -> String {
+        format!("~{inner}~")
+    }
+
+    fn code(&self, code: &str) -> String {
+        format!("`{}`", escape(code))
+    }
+
+    fn code_block(&self, lang: &str, code: &str) -> String {
+        format!("```{lang}\n{}\n```", escape(code))
     }
 
-    result
+    fn link(&self, text: &str, url: &str) -> String {
+        format!("<{url}|{text}>")
+    }
 }
 
-fn escape_char(c: char) -> char {
-    // Slack mrkdwn doesn't use HTML entities in the same way, but the Web API
-    // requires &, <, > to be escaped when sending via JSON payload.
-    // However, Slack's chat.postMessage with JSON body handles this automatically.
-    // We just pass through as-is — the API handles encoding.
-    c
+/// Convert standard markdown to Slack mrkdwn format.
+pub fn to_slack_mrkdwn(input: &str) -> String {
+    markdown::parse(input).render(&SlackMrkdwn)
+}
+
+/// Convert markdown to mrkdwn messages of at most `max_chars` characters,
+/// keeping code blocks fenced in every message.
+pub fn split_slack_chunks(input: &str, max_chars: usize) -> Vec<String> {
+    markdown::render_chunks(input, &SlackMrkdwn, max_chars)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
 }
 
 #[cfg(test)]
@@ -111,4 +90,12 @@ mod tests {
         let output = to_slack_mrkdwn(input);
         assert_eq!(output, "Try `code` and *bold* together.");
     }
+
+    #[test]
+    fn links_and_escapes() {
+        assert_eq!(
+            to_slack_mrkdwn("see [docs](https://x.dev) & a <b>"),
+            "see <https://x.dev|docs> &amp; a &lt;b&gt;"
+        );
+    }
 }
@@ -1,6 +1,7 @@
 pub mod api;
 pub mod fmt;
 
+use std::collections::HashSet;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
@@ -12,21 +13,30 @@ use reqwest::Client;
 use tokio::sync::{mpsc, watch};
 use tracing::{error, info, warn};
 
+use crate::attachments::PendingAttachments;
+use crate::group::{GroupMessage, GroupPolicy};
+use crate::progressive::{MessageEditor, ProgressiveMessage};
 use crate::traits::{ChannelLifecycle, ChannelSender, ChannelStatus};
-use opencrust_common::{Message, MessageContent, Result};
+use opencrust_common::{Message, MessageContent, OutboundFile, Result};
+use opencrust_media::SpeechToText;
 
-/// Callback invoked when the bot receives a text message from Slack.
+/// Callback invoked when the bot receives a message from Slack.
 ///
-/// Arguments: `(channel_id, user_id, user_name, text, delta_sender)`.
+/// Arguments: `(channel_id, thread_ts, user_id, user_name, text, attachments, delta_sender)`.
+/// `thread_ts` is the thread the reply goes to; it is `None` for top-level
+/// direct messages and slash commands. `attachments` holds the shared files,
+/// read with [`PendingAttachments::resolve`] once the sender is admitted.
 /// When `delta_sender` is `Some`, the callback should send text deltas through it
 /// for streaming display. The callback still returns the final complete text.
 /// Return `Err("__blocked__")` to silently drop the message (unauthorized user).
 pub type SlackOnMessageFn = Arc<
     dyn Fn(
             String,
+            Option<String>,
             String,
             String,
             String,
+            PendingAttachments,
             Option<mpsc::Sender<String>>,
         ) -> Pin<Box<dyn Future<Output = std::result::Result<String, String>> + Send>>
         + Send
@@ -39,6 +49,8 @@ pub struct SlackChannel {
     display: String,
     status: ChannelStatus,
     on_message: SlackOnMessageFn,
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+    group_policy: Arc<GroupPolicy>,
     shutdown_tx: Option<watch::Sender<bool>>,
 }
 
@@ -50,9 +62,23 @@ impl SlackChannel {
             display: "Slack".to_string(),
             status: ChannelStatus::Disconnected,
             on_message,
+            speech_to_text: None,
+            group_policy: Arc::default(),
             shutdown_tx: None,
         }
     }
+
+    /// Transcribe shared audio clips with this backend.
+    pub fn with_speech_to_text(mut self, stt: Option<Arc<dyn SpeechToText>>) -> Self {
+        self.speech_to_text = stt;
+        self
+    }
+
+    /// Decide which channel messages are answered with this policy.
+    pub fn with_group_policy(mut self, policy: Arc<GroupPolicy>) -> Self {
+        self.group_policy = policy;
+        self
+    }
 }
 
 /// Lightweight send-only handle for Slack. Holds a bot token for API calls.
@@ -69,6 +95,14 @@ impl ChannelSender for SlackSender {
     async fn send_message(&self, message: &Message) -> Result<()> {
         slack_send_message(&self.bot_token, message).await
     }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        slack_send_attachment(&self.bot_token, metadata, file).await
+    }
 }
 
 #[async_trait]
@@ -88,12 +122,23 @@ impl ChannelLifecycle for SlackChannel {
         let bot_token = self.bot_token.clone();
         let app_token = self.app_token.clone();
         let on_message = Arc::clone(&self.on_message);
+        let speech_to_text = self.speech_to_text.clone();
+        let group_policy = Arc::clone(&self.group_policy);
 
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
         self.shutdown_tx = Some(shutdown_tx);
 
         tokio::spawn(async move {
-            run_socket_mode(client, bot_token, app_token, on_message, shutdown_rx).await;
+            run_socket_mode(
+                client,
+                bot_token,
+                app_token,
+                on_message,
+                speech_to_text,
+                group_policy,
+                shutdown_rx,
+            )
+            .await;
         });
 
         self.status = ChannelStatus::Connected;
@@ -124,17 +169,64 @@ impl ChannelSender for SlackChannel {
     async fn send_message(&self, message: &Message) -> Result<()> {
         slack_send_message(&self.bot_token, message).await
     }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        slack_send_attachment(&self.bot_token, metadata, file).await
+    }
 }
 
-/// Shared send logic used by both `SlackChannel` and `SlackSender`.
-async fn slack_send_message(bot_token: &str, message: &Message) -> Result<()> {
-    let channel_id = message
-        .metadata
+/// Slack's per-file upload limit.
+const SLACK_MAX_UPLOAD_BYTES: usize = 1024 * 1024 * 1024;
+
+fn slack_channel_id(metadata: &serde_json::Value) -> Result<&str> {
+    metadata
         .get("slack_channel_id")
         .and_then(|v| v.as_str())
         .ok_or_else(|| {
             opencrust_common::Error::Channel("missing slack_channel_id in metadata".into())
-        })?;
+        })
+}
+
+/// Thread the session lives in, if any.
+fn slack_thread_ts(metadata: &serde_json::Value) -> Option<&str> {
+    metadata.get("slack_thread_ts").and_then(|v| v.as_str())
+}
+
+/// Share a file in the channel, with the caption as its comment.
+async fn slack_send_attachment(
+    bot_token: &str,
+    metadata: &serde_json::Value,
+    file: OutboundFile,
+) -> Result<()> {
+    let channel_id = slack_channel_id(metadata)?;
+    if file.data.len() > SLACK_MAX_UPLOAD_BYTES {
+        return Err(opencrust_common::Error::Channel(format!(
+            "file too large for slack ({} bytes)",
+            file.data.len()
+        )));
+    }
+
+    let comment = file.caption.as_deref().map(fmt::to_slack_mrkdwn);
+    api::upload_file(
+        &Client::new(),
+        bot_token,
+        channel_id,
+        slack_thread_ts(metadata),
+        file.data,
+        &file.filename,
+        comment.as_deref(),
+    )
+    .await
+    .map_err(|e| opencrust_common::Error::Channel(format!("slack upload failed: {e}")))
+}
+
+/// Shared send logic used by both `SlackChannel` and `SlackSender`.
+async fn slack_send_message(bot_token: &str, message: &Message) -> Result<()> {
+    let channel_id = slack_channel_id(&message.metadata)?;
 
     let text = match &message.content {
         MessageContent::Text(t) => t.clone(),
@@ -146,10 +238,17 @@ async fn slack_send_message(bot_token: &str, message: &Message) -> Result<()> {
     };
 
     let client = Client::new();
-    let formatted = fmt::to_slack_mrkdwn(&text);
-    api::post_message(&client, bot_token, channel_id, &formatted)
+    for chunk in fmt::split_slack_chunks(&text, SLACK_MESSAGE_CHAR_LIMIT) {
+        api::post_message(
+            &client,
+            bot_token,
+            channel_id,
+            slack_thread_ts(&message.metadata),
+            &chunk,
+        )
         .await
         .map_err(|e| opencrust_common::Error::Channel(format!("slack send failed: {e}")))?;
+    }
 
     Ok(())
 }
@@ -160,15 +259,35 @@ async fn run_socket_mode(
     bot_token: String,
     app_token: String,
     on_message: SlackOnMessageFn,
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+    group_policy: Arc<GroupPolicy>,
     mut shutdown_rx: watch::Receiver<bool>,
 ) {
+    let bot_user_id = match api::auth_test(&client, &bot_token).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            warn!("slack: {e}; mentions of the bot will not be recognised");
+            None
+        }
+    };
+    let ctx = Arc::new(SlackContext {
+        client,
+        bot_token,
+        on_message,
+        speech_to_text,
+        group_policy,
+        bot_user_id,
+        threads: std::sync::Mutex::new(HashSet::new()),
+    });
+    let client = &ctx.client;
+
     loop {
         if *shutdown_rx.borrow() {
             info!("slack: shutdown requested, stopping Socket Mode");
             return;
         }
 
-        let ws_url = match api::open_connection(&client, &app_token).await {
+        let ws_url = match api::open_connection(client, &app_token).await {
             Ok(url) => url,
             Err(e) => {
                 warn!("slack: failed to open connection: {e}, retrying in 5s");
@@ -204,13 +323,7 @@ async fn run_socket_mode(
                     match msg {
                         Some(Ok(ws_msg)) => {
                             if let tokio_tungstenite::tungstenite::Message::Text(text) = ws_msg {
-                                let handled = handle_socket_event(
-                                    &text,
-                                    &client,
-                                    &bot_token,
-                                    &on_message,
-                                    &ws_write,
-                                ).await;
+                                let handled = handle_socket_event(&text, &ctx, &ws_write).await;
                                 if let HandleResult::Reconnect = handled {
                                     should_reconnect = true;
                                     break;
@@ -250,6 +363,74 @@ async fn run_socket_mode(
     }
 }
 
+/// State shared by the Socket Mode loop and the tasks it spawns.
+struct SlackContext {
+    client: Client,
+    bot_token: String,
+    on_message: SlackOnMessageFn,
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+    group_policy: Arc<GroupPolicy>,
+    /// The bot's own user ID, from `auth.test`.
+    bot_user_id: Option<String>,
+    /// Threads the bot has replied in, as `channel:thread_ts`. Follow-ups in
+    /// these threads are answered without a fresh mention.
+    threads: std::sync::Mutex<HashSet<String>>,
+}
+
+/// Forget old threads past this many, so the set stays bounded.
+const MAX_TRACKED_THREADS: usize = 10_000;
+
+impl SlackContext {
+    fn is_active_thread(&self, channel_id: &str, thread_ts: &str) -> bool {
+        self.threads
+            .lock()
+            .unwrap()
+            .contains(&format!("{channel_id}:{thread_ts}"))
+    }
+
+    fn mark_active_thread(&self, channel_id: &str, thread_ts: &str) {
+        let mut threads = self.threads.lock().unwrap();
+        if threads.len() >= MAX_TRACKED_THREADS {
+            threads.clear();
+        }
+        threads.insert(format!("{channel_id}:{thread_ts}"));
+    }
+
+    /// Swap the status reaction on the triggering message. Failures (e.g.
+    /// a missing `reactions:write` scope) are logged and otherwise ignored.
+    async fn set_reaction(&self, message: &SlackIncoming, from: Option<&str>, to: Option<&str>) {
+        if let Some(name) = from
+            && let Err(e) = api::remove_reaction(
+                &self.client,
+                &self.bot_token,
+                &message.channel_id,
+                &message.ts,
+                name,
+            )
+            .await
+        {
+            tracing::debug!("slack: {e}");
+        }
+        if let Some(name) = to
+            && let Err(e) = api::add_reaction(
+                &self.client,
+                &self.bot_token,
+                &message.channel_id,
+                &message.ts,
+                name,
+            )
+            .await
+        {
+            tracing::debug!("slack: {e}");
+        }
+    }
+}
+
+/// Reaction shown while a message is being answered.
+const REACTION_WORKING: &str = "eyes";
+const REACTION_DONE: &str = "white_check_mark";
+const REACTION_FAILED: &str = "x";
+
 enum HandleResult {
     Ok,
     Reconnect,
@@ -266,11 +447,241 @@ type WsWriter = Arc<
     >,
 >;
 
+/// A file shared alongside a Slack message.
+#[derive(Debug, PartialEq)]
+struct SlackFile {
+    name: String,
+    url: String,
+    mime_type: Option<String>,
+}
+
+/// Files attached to a message event that the bot can download.
+fn slack_files(event: &serde_json::Value) -> Vec<SlackFile> {
+    event
+        .get("files")
+        .and_then(|v| v.as_array())
+        .map(|files| {
+            files
+                .iter()
+                .filter_map(|f| {
+                    let url = f
+                        .get("url_private_download")
+                        .or_else(|| f.get("url_private"))
+                        .and_then(|v| v.as_str())?;
+                    Some(SlackFile {
+                        name: f
+                            .get("name")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("file")
+                            .to_string(),
+                        url: url.to_string(),
+                        mime_type: f
+                            .get("mimetype")
+                            .and_then(|v| v.as_str())
+                            .map(str::to_string),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A user message the bot may answer.
+#[derive(Debug, PartialEq)]
+struct SlackIncoming {
+    channel_id: String,
+    user_id: String,
+    text: String,
+    /// `ts` of the triggering message, used for status reactions.
+    ts: String,
+    /// Thread to reply in; `None` for top-level direct messages.
+    thread_ts: Option<String>,
+    files: Vec<SlackFile>,
+    /// Sent as a direct message; always answered.
+    direct: bool,
+    mentioned: bool,
+    /// A follow-up in a thread the bot already replied in.
+    in_bot_thread: bool,
+}
+
+/// Turn a user message event into a `SlackIncoming`. Direct messages are
+/// always answered. Channel messages carry what the group policy needs:
+/// mentions arrive as `app_mention` events, thread follow-ups are flagged,
+/// and replies go to a thread under the message.
+fn incoming_message(
+    event: &serde_json::Value,
+    bot_user_id: Option<&str>,
+    is_active_thread: impl Fn(&str, &str) -> bool,
+) -> Option<SlackIncoming> {
+    let field = |name: &str| event.get(name).and_then(|v| v.as_str());
+
+    // Skip bot messages and edits/joins; file uploads arrive as `file_share`
+    if event.get("bot_id").is_some() || field("subtype").is_some_and(|s| s != "file_share") {
+        return None;
+    }
+
+    let channel_id = field("channel")?.to_string();
+    let user_id = field("user")?.to_string();
+    let ts = field("ts")?.to_string();
+    let thread_root = field("thread_ts").map(str::to_string);
+    let raw_text = field("text").unwrap_or("");
+
+    let direct = field("channel_type") == Some("im");
+    let (thread_ts, mentioned, in_bot_thread) = match field("type")? {
+        "app_mention" => (Some(thread_root.unwrap_or_else(|| ts.clone())), true, false),
+        "message" if direct => (thread_root, false, false),
+        "message" => {
+            // Mentions in channels are handled through their `app_mention` event.
+            if bot_user_id.is_some_and(|id| raw_text.contains(&format!("<@{id}>"))) {
+                return None;
+            }
+            let in_bot_thread = thread_root
+                .as_deref()
+                .is_some_and(|root| is_active_thread(&channel_id, root));
+            (
+                Some(thread_root.unwrap_or_else(|| ts.clone())),
+                false,
+                in_bot_thread,
+            )
+        }
+        _ => return None,
+    };
+
+    let text = strip_mentions(raw_text, bot_user_id);
+    let files = slack_files(event);
+    if text.is_empty() && files.is_empty() {
+        return None;
+    }
+
+    Some(SlackIncoming {
+        channel_id,
+        user_id,
+        text,
+        ts,
+        thread_ts,
+        files,
+        direct,
+        mentioned,
+        in_bot_thread,
+    })
+}
+
+/// Remove mentions of the bot (`<@U123>`) from message text. Without a known
+/// bot ID, only a leading mention is removed.
+fn strip_mentions(text: &str, bot_user_id: Option<&str>) -> String {
+    match bot_user_id {
+        Some(id) => text.replace(&format!("<@{id}>"), "").trim().to_string(),
+        None => {
+            let text = text.trim_start();
+            match text
+                .strip_prefix("<@")
+                .and_then(|rest| rest.split_once('>'))
+            {
+                Some((_, rest)) => rest.trim().to_string(),
+                None => text.trim().to_string(),
+            }
+        }
+    }
+}
+
+/// `chat.update` is rate limited to about 50 calls a minute per workspace.
+const SLACK_EDIT_INTERVAL: Duration = Duration::from_millis(1200);
+
+/// Slack truncates message text beyond 4000 characters.
+const SLACK_MESSAGE_CHAR_LIMIT: usize = 4000;
+
+/// Posts and edits reply messages in one Slack channel or thread.
+struct SlackEditor {
+    client: Client,
+    bot_token: String,
+    channel_id: String,
+    thread_ts: Option<String>,
+}
+
+#[async_trait]
+impl MessageEditor for SlackEditor {
+    type Id = String;
+
+    fn max_chars(&self) -> usize {
+        SLACK_MESSAGE_CHAR_LIMIT
+    }
+
+    fn chunks(&self, text: &str) -> Vec<String> {
+        fmt::split_slack_chunks(text, SLACK_MESSAGE_CHAR_LIMIT)
+    }
+
+    async fn post(&self, text: &str) -> std::result::Result<String, String> {
+        api::post_message(
+            &self.client,
+            &self.bot_token,
+            &self.channel_id,
+            self.thread_ts.as_deref(),
+            text,
+        )
+        .await
+    }
+
+    async fn edit(&self, ts: &String, text: &str) -> std::result::Result<(), String> {
+        api::update_message(&self.client, &self.bot_token, &self.channel_id, ts, text).await
+    }
+
+    async fn delete(&self, ts: &String) -> std::result::Result<(), String> {
+        api::delete_message(&self.client, &self.bot_token, &self.channel_id, ts).await
+    }
+}
+
+/// A slash command invocation delivered over Socket Mode.
+#[derive(Debug, PartialEq)]
+struct SlackSlashCommand {
+    command: String,
+    channel_id: String,
+    user_id: String,
+    user_name: String,
+    /// The command and its arguments as one line, e.g. `/model gpt-4o`.
+    text: String,
+    response_url: String,
+}
+
+fn slash_command(payload: &serde_json::Value) -> Option<SlackSlashCommand> {
+    let field = |name: &str| payload.get(name).and_then(|v| v.as_str());
+    let command = field("command")?.to_string();
+    let user_id = field("user_id")?.to_string();
+    let args = field("text").unwrap_or("").trim();
+    Some(SlackSlashCommand {
+        text: if args.is_empty() {
+            command.clone()
+        } else {
+            format!("{command} {args}")
+        },
+        command,
+        channel_id: field("channel_id")?.to_string(),
+        user_name: field("user_name").unwrap_or(&user_id).to_string(),
+        user_id,
+        response_url: field("response_url")?.to_string(),
+    })
+}
+
+/// Acknowledge a Socket Mode envelope so Slack does not redeliver it.
+async fn ack_envelope(envelope: &serde_json::Value, ws_write: &WsWriter) {
+    let Some(envelope_id) = envelope.get("envelope_id").and_then(|v| v.as_str()) else {
+        return;
+    };
+    let ack = serde_json::json!({ "envelope_id": envelope_id });
+    use futures::SinkExt;
+    let mut writer = ws_write.lock().await;
+    if let Err(e) = writer
+        .send(tokio_tungstenite::tungstenite::Message::Text(
+            ack.to_string().into(),
+        ))
+        .await
+    {
+        warn!("slack: failed to send ack: {e}");
+    }
+}
+
 async fn handle_socket_event(
     raw: &str,
-    client: &Client,
-    bot_token: &str,
-    on_message: &SlackOnMessageFn,
+    ctx: &Arc<SlackContext>,
     ws_write: &WsWriter,
 ) -> HandleResult {
     let envelope: serde_json::Value = match serde_json::from_str(raw) {
@@ -294,19 +705,7 @@ async fn handle_socket_event(
         }
         "events_api" => {
             // Acknowledge the envelope immediately
-            if let Some(envelope_id) = envelope.get("envelope_id").and_then(|v| v.as_str()) {
-                let ack = serde_json::json!({ "envelope_id": envelope_id });
-                use futures::SinkExt;
-                let mut writer = ws_write.lock().await;
-                if let Err(e) = writer
-                    .send(tokio_tungstenite::tungstenite::Message::Text(
-                        ack.to_string().into(),
-                    ))
-                    .await
-                {
-                    warn!("slack: failed to send ack: {e}");
-                }
-            }
+            ack_envelope(&envelope, ws_write).await;
 
             // Extract the event payload
             let payload = match envelope.get("payload") {
@@ -319,148 +718,71 @@ async fn handle_socket_event(
                 None => return HandleResult::Ok,
             };
 
-            let event_type = event.get("type").and_then(|v| v.as_str()).unwrap_or("");
-            if event_type != "message" {
-                return HandleResult::Ok;
-            }
-
-            // Skip bot messages and message_changed subtypes
-            if event.get("bot_id").is_some() || event.get("subtype").is_some() {
-                return HandleResult::Ok;
-            }
-
-            let channel_id = event
-                .get("channel")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            let user_id = event
-                .get("user")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-            let text = event
-                .get("text")
-                .and_then(|v| v.as_str())
-                .unwrap_or("")
-                .to_string();
-
-            if text.trim().is_empty() {
+            let Some(mut message) = incoming_message(event, ctx.bot_user_id.as_deref(), |c, t| {
+                ctx.is_active_thread(c, t)
+            }) else {
                 return HandleResult::Ok;
+            };
+            if !message.direct {
+                let admitted = ctx.group_policy.admit(&GroupMessage {
+                    group_id: &message.channel_id,
+                    sender_name: &format!("<@{}>", message.user_id),
+                    text: &message.text,
+                    mentioned: message.mentioned,
+                    replies_to_bot: message.in_bot_thread,
+                });
+                match admitted {
+                    Some(context) => message.text = context.prepend_to(&message.text),
+                    None => return HandleResult::Ok,
+                }
             }
 
             info!(
-                "slack: message from {} in {}: {} chars",
-                user_id,
-                channel_id,
-                text.len()
+                "slack: message from {} in {}: {} chars, {} file(s)",
+                message.user_id,
+                message.channel_id,
+                message.text.len(),
+                message.files.len()
             );
 
-            // Spawn message processing with streaming
-            let client = client.clone();
-            let bot_token = bot_token.to_string();
-            let on_message = Arc::clone(on_message);
-
-            tokio::spawn(async move {
-                let (delta_tx, mut delta_rx) = mpsc::channel::<String>(64);
-
-                let cb_channel = channel_id.clone();
-                let cb_user = user_id.clone();
-                let cb_text = text.clone();
-
-                let callback_handle = tokio::spawn(async move {
-                    // user_name = user_id for now (would need users.info call for display name)
-                    on_message(
-                        cb_channel,
-                        cb_user.clone(),
-                        cb_user,
-                        cb_text,
-                        Some(delta_tx),
-                    )
-                    .await
-                });
-
-                // Stream deltas: post initial message, then update it
-                let mut accumulated = String::new();
-                let mut msg_ts: Option<String> = None;
-                let mut last_update = tokio::time::Instant::now();
-                let mut first_delta_at: Option<tokio::time::Instant> = None;
+            tokio::spawn(process_message(Arc::clone(ctx), message));
 
-                while let Some(delta) = delta_rx.recv().await {
-                    accumulated.push_str(&delta);
-                    if first_delta_at.is_none() {
-                        first_delta_at = Some(tokio::time::Instant::now());
-                    }
+            HandleResult::Ok
+        }
+        "slash_commands" => {
+            ack_envelope(&envelope, ws_write).await;
 
-                    if msg_ts.is_none() {
-                        // Buffer 1s before sending first message
-                        if first_delta_at.unwrap().elapsed() >= Duration::from_secs(1) {
-                            match api::post_message(&client, &bot_token, &channel_id, &accumulated)
-                                .await
-                            {
-                                Ok(ts) => {
-                                    msg_ts = Some(ts);
-                                    last_update = tokio::time::Instant::now();
-                                }
-                                Err(e) => {
-                                    error!("slack: failed to post streaming message: {e}");
-                                    break;
-                                }
-                            }
-                        }
-                    } else if last_update.elapsed() >= Duration::from_millis(1000)
-                        && let Some(ts) = &msg_ts
-                    {
-                        let _ =
-                            api::update_message(&client, &bot_token, &channel_id, ts, &accumulated)
-                                .await;
-                        last_update = tokio::time::Instant::now();
-                    }
-                }
+            let Some(command) = envelope.get("payload").and_then(slash_command) else {
+                return HandleResult::Ok;
+            };
+            info!(
+                "slack: {} from {} in {}",
+                command.command, command.user_id, command.channel_id
+            );
 
-                // Get final result
-                let result = callback_handle
-                    .await
-                    .unwrap_or_else(|e| Err(format!("task panic: {e}")));
-
-                match result {
-                    Ok(final_text) => {
-                        let formatted = fmt::to_slack_mrkdwn(&final_text);
-                        if let Some(ts) = &msg_ts {
-                            let _ = api::update_message(
-                                &client,
-                                &bot_token,
-                                &channel_id,
-                                ts,
-                                &formatted,
-                            )
-                            .await;
-                        } else {
-                            // No streaming happened — send final message directly
-                            let _ = api::post_message(&client, &bot_token, &channel_id, &formatted)
-                                .await;
-                        }
-                    }
+            let ctx = Arc::clone(ctx);
+            tokio::spawn(async move {
+                let reply = match (ctx.on_message)(
+                    command.channel_id,
+                    None,
+                    command.user_id,
+                    command.user_name,
+                    command.text,
+                    PendingAttachments::default(),
+                    None,
+                )
+                .await
+                {
+                    Ok(text) => fmt::to_slack_mrkdwn(&text),
                     Err(e) if e == "__blocked__" => {
-                        // Silently drop — unauthorized user
-                    }
-                    Err(e) => {
-                        let error_text = format!("Sorry, an error occurred: {e}");
-                        if let Some(ts) = &msg_ts {
-                            let _ = api::update_message(
-                                &client,
-                                &bot_token,
-                                &channel_id,
-                                ts,
-                                &error_text,
-                            )
-                            .await;
-                        } else {
-                            let _ =
-                                api::post_message(&client, &bot_token, &channel_id, &error_text)
-                                    .await;
-                        }
+                        "You are not authorized to use this bot.".to_string()
                     }
+                    Err(e) => format!("Sorry, an error occurred: {e}"),
+                };
+                if let Err(e) =
+                    api::respond_to_command(&ctx.client, &command.response_url, &reply).await
+                {
+                    warn!("slack: {e}");
                 }
             });
 
@@ -473,18 +795,213 @@ async fn handle_socket_event(
     }
 }
 
+/// Answer one message: mark it with a reaction, read its files, stream the
+/// reply into its thread, then swap the reaction for the outcome.
+async fn process_message(ctx: Arc<SlackContext>, message: SlackIncoming) {
+    ctx.set_reaction(&message, None, Some(REACTION_WORKING))
+        .await;
+
+    let mut pending = PendingAttachments::new(ctx.speech_to_text.clone());
+    for file in &message.files {
+        pending.push_download(
+            &ctx.client,
+            &file.url,
+            Some(&ctx.bot_token),
+            &file.name,
+            file.mime_type.as_deref(),
+        );
+    }
+    let text = message.text.clone();
+
+    let (delta_tx, mut delta_rx) = mpsc::channel::<String>(64);
+
+    let on_message = Arc::clone(&ctx.on_message);
+    let cb_channel = message.channel_id.clone();
+    let cb_thread = message.thread_ts.clone();
+    let cb_user = message.user_id.clone();
+    let callback_handle = tokio::spawn(async move {
+        // user_name = user_id for now (would need users.info call for display name)
+        on_message(
+            cb_channel,
+            cb_thread,
+            cb_user.clone(),
+            cb_user,
+            text,
+            pending,
+            Some(delta_tx),
+        )
+        .await
+    });
+
+    let mut reply = ProgressiveMessage::new(
+        SlackEditor {
+            client: ctx.client.clone(),
+            bot_token: ctx.bot_token.clone(),
+            channel_id: message.channel_id.clone(),
+            thread_ts: message.thread_ts.clone(),
+        },
+        SLACK_EDIT_INTERVAL,
+    );
+    reply.stream(&mut delta_rx).await;
+
+    let result = callback_handle
+        .await
+        .unwrap_or_else(|e| Err(format!("task panic: {e}")));
+
+    let outcome = match result {
+        Ok(final_text) => match reply.finish(&final_text).await {
+            Ok(()) => Some(REACTION_DONE),
+            Err(e) => {
+                error!("slack: failed to send response: {e}");
+                Some(REACTION_FAILED)
+            }
+        },
+        Err(e) if e == "__blocked__" => {
+            reply.cancel().await;
+            None
+        }
+        Err(e) => {
+            let error_text = format!("Sorry, an error occurred: {e}");
+            if let Err(e) = reply.finish(&error_text).await {
+                error!("slack: failed to send error response: {e}");
+            }
+            Some(REACTION_FAILED)
+        }
+    };
+
+    if outcome.is_some()
+        && let Some(thread_ts) = &message.thread_ts
+    {
+        ctx.mark_active_thread(&message.channel_id, thread_ts);
+    }
+    ctx.set_reaction(&message, Some(REACTION_WORKING), outcome)
+        .await;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn channel_type_is_slack() {
-        let on_msg: SlackOnMessageFn = Arc::new(|_ch, _uid, _user, _text, _delta_tx| {
-            Box::pin(async { Ok("test".to_string()) })
-        });
+        let on_msg: SlackOnMessageFn = Arc::new(
+            |_ch, _thread, _uid, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            },
+        );
         let channel = SlackChannel::new("xoxb-fake".to_string(), "xapp-fake".to_string(), on_msg);
         assert_eq!(channel.channel_type(), "slack");
         assert_eq!(channel.display_name(), "Slack");
         assert_eq!(channel.status(), ChannelStatus::Disconnected);
     }
+
+    #[test]
+    fn slack_files_prefers_download_url() {
+        let event = serde_json::json!({
+            "subtype": "file_share",
+            "files": [
+                {
+                    "name": "report.pdf",
+                    "mimetype": "application/pdf",
+                    "url_private": "https://files.slack.com/view",
+                    "url_private_download": "https://files.slack.com/download"
+                },
+                { "name": "external-link" }
+            ]
+        });
+        assert_eq!(
+            slack_files(&event),
+            vec![SlackFile {
+                name: "report.pdf".into(),
+                url: "https://files.slack.com/download".into(),
+                mime_type: Some("application/pdf".into()),
+            }]
+        );
+        assert!(slack_files(&serde_json::json!({ "text": "hi" })).is_empty());
+    }
+
+    #[test]
+    fn direct_messages_reply_top_level_or_in_their_thread() {
+        let dm = serde_json::json!({
+            "type": "message", "channel_type": "im", "channel": "D1",
+            "user": "U1", "ts": "1.1", "text": "hello"
+        });
+        let message = incoming_message(&dm, Some("UBOT"), |_, _| false).unwrap();
+        assert_eq!(message.thread_ts, None);
+        assert_eq!(message.ts, "1.1");
+
+        let mut threaded = dm.clone();
+        threaded["thread_ts"] = "0.5".into();
+        let message = incoming_message(&threaded, Some("UBOT"), |_, _| false).unwrap();
+        assert_eq!(message.thread_ts.as_deref(), Some("0.5"));
+    }
+
+    #[test]
+    fn channel_messages_report_mentions_and_thread_follow_ups() {
+        let mention = serde_json::json!({
+            "type": "app_mention", "channel": "C1", "user": "U1",
+            "ts": "2.2", "text": "<@UBOT> summarise this"
+        });
+        let message = incoming_message(&mention, Some("UBOT"), |_, _| false).unwrap();
+        assert_eq!(message.text, "summarise this");
+        assert_eq!(message.thread_ts.as_deref(), Some("2.2"));
+        assert!(message.mentioned && !message.direct);
+
+        let plain = serde_json::json!({
+            "type": "message", "channel_type": "channel", "channel": "C1",
+            "user": "U1", "ts": "3.3", "text": "chatter"
+        });
+        let message = incoming_message(&plain, Some("UBOT"), |_, _| true).unwrap();
+        assert!(!message.mentioned && !message.in_bot_thread);
+        assert_eq!(message.thread_ts.as_deref(), Some("3.3"));
+
+        let mut follow_up = plain.clone();
+        follow_up["thread_ts"] = "2.2".into();
+        let message = incoming_message(&follow_up, Some("UBOT"), |_, _| false).unwrap();
+        assert!(!message.in_bot_thread);
+        let message =
+            incoming_message(&follow_up, Some("UBOT"), |c, t| c == "C1" && t == "2.2").unwrap();
+        assert_eq!(message.thread_ts.as_deref(), Some("2.2"));
+        assert!(message.in_bot_thread);
+
+        // The mention itself arrives as `app_mention`; skip the duplicate.
+        follow_up["text"] = "<@UBOT> again".into();
+        assert!(incoming_message(&follow_up, Some("UBOT"), |_, _| true).is_none());
+
+        let mut from_bot = mention.clone();
+        from_bot["bot_id"] = "B1".into();
+        assert!(incoming_message(&from_bot, Some("UBOT"), |_, _| true).is_none());
+    }
+
+    #[test]
+    fn strip_mentions_without_bot_id_removes_leading_mention() {
+        assert_eq!(strip_mentions("<@U9> hi <@U8>", None), "hi <@U8>");
+        assert_eq!(strip_mentions("hi <@UBOT>!", Some("UBOT")), "hi !");
+    }
+
+    #[test]
+    fn slash_command_joins_command_and_text() {
+        let payload = serde_json::json!({
+            "command": "/model",
+            "text": " gpt-4o ",
+            "channel_id": "C1",
+            "user_id": "U1",
+            "user_name": "ada",
+            "response_url": "https://hooks.slack.com/commands/1"
+        });
+        let command = slash_command(&payload).unwrap();
+        assert_eq!(command.text, "/model gpt-4o");
+        assert_eq!(command.user_name, "ada");
+
+        let bare = serde_json::json!({
+            "command": "/help",
+            "channel_id": "C1",
+            "user_id": "U1",
+            "response_url": "https://hooks.slack.com/commands/1"
+        });
+        let command = slash_command(&bare).unwrap();
+        assert_eq!(command.text, "/help");
+        assert_eq!(command.user_name, "U1");
+        assert!(slash_command(&serde_json::json!({ "command": "/help" })).is_none());
+    }
 }
@@ -6,13 +6,16 @@ use std::time::Duration;
 use async_trait::async_trait;
 use teloxide::dispatching::UpdateFilterExt;
 use teloxide::prelude::*;
-use teloxide::types::{ChatAction, InputFile, ParseMode};
+use teloxide::types::{BotCommand, ChatAction, InputFile, MessageId, ParseMode, UserId};
 use tokio::sync::{mpsc, watch};
 use tracing::{error, info, warn};
 
-use crate::telegram_fmt::to_telegram_markdown;
-use crate::traits::{ChannelLifecycle, ChannelSender, ChannelStatus};
-use opencrust_common::{Message, MessageContent, Result};
+use crate::commands::CommandSpec;
+use crate::group::{GroupMessage, GroupPolicy};
+use crate::markdown::{self, PlainText};
+use crate::telegram_fmt::TelegramMarkdownV2;
+use crate::traits::{ChannelLifecycle, ChannelSender, ChannelStatus, VoiceReplier};
+use opencrust_common::{Message, MessageContent, OutboundFile, Result};
 
 /// Media attachment extracted from an incoming Telegram message.
 #[derive(Debug, Clone)]
@@ -31,11 +34,17 @@ pub enum MediaAttachment {
         data: Vec<u8>,
         duration: u32,
     },
+    Video {
+        data: Vec<u8>,
+        caption: Option<String>,
+        duration: u32,
+    },
 }
 
 /// Callback invoked when the bot receives a message.
 ///
 /// Arguments: `(chat_id, user_id_string, user_display_name, text, attachment, delta_sender)`.
+/// The attachment is downloaded only when the callback asks for it.
 /// When `delta_sender` is `Some`, the callback should send text deltas through it
 /// for streaming display. The callback still returns the final complete text.
 /// Return `Err("__blocked__")` to silently drop the message (unauthorized user).
@@ -45,7 +54,7 @@ pub type OnMessageFn = Arc<
             String,
             String,
             String,
-            Option<MediaAttachment>,
+            Option<PendingMedia>,
             Option<mpsc::Sender<String>>,
         ) -> Pin<Box<dyn Future<Output = std::result::Result<String, String>> + Send>>
         + Send
@@ -57,6 +66,9 @@ pub struct TelegramChannel {
     display: String,
     status: ChannelStatus,
     on_message: OnMessageFn,
+    voice_replier: Option<Arc<dyn VoiceReplier>>,
+    commands: Vec<CommandSpec>,
+    group_policy: Arc<GroupPolicy>,
     bot: Option<Bot>,
     shutdown_tx: Option<watch::Sender<bool>>,
 }
@@ -68,10 +80,92 @@ impl TelegramChannel {
             display: "Telegram".to_string(),
             status: ChannelStatus::Disconnected,
             on_message,
+            voice_replier: None,
+            commands: Vec::new(),
+            group_policy: Arc::default(),
             bot: None,
             shutdown_tx: None,
         }
     }
+
+    /// Follow text replies with a voice message when the replier provides one.
+    pub fn with_voice_replier(mut self, replier: Option<Arc<dyn VoiceReplier>>) -> Self {
+        self.voice_replier = replier;
+        self
+    }
+
+    /// Publish these commands in the Telegram command menu on connect.
+    pub fn with_commands(mut self, commands: Vec<CommandSpec>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Decide which group messages are answered with this policy.
+    pub fn with_group_policy(mut self, policy: Arc<GroupPolicy>) -> Self {
+        self.group_policy = policy;
+        self
+    }
+}
+
+/// The bot's own account, for recognising mentions and replies in groups.
+#[derive(Debug, Clone)]
+struct BotIdentity {
+    id: UserId,
+    username: String,
+}
+
+/// Remove `@username` mentions of the bot (case-insensitive) from `text`.
+/// Returns the remaining text and whether there was one.
+fn strip_bot_mention(text: &str, username: &str) -> (String, bool) {
+    let needle = format!("@{}", username.to_lowercase());
+    let lower = text.to_lowercase();
+    // Lowercasing can change byte lengths outside ASCII; give up on those.
+    if username.is_empty() || lower.len() != text.len() || !lower.contains(&needle) {
+        return (text.to_string(), false);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = 0;
+    for (start, _) in lower.match_indices(&needle) {
+        out.push_str(&text[rest..start]);
+        rest = start + needle.len();
+    }
+    out.push_str(&text[rest..]);
+    (out.trim().to_string(), true)
+}
+
+/// Text of a group message without the bot's mention, whether it mentions
+/// the bot and whether it replies to one of the bot's messages.
+fn group_addressing(
+    msg: &teloxide::types::Message,
+    me: Option<&BotIdentity>,
+) -> (String, bool, bool) {
+    let raw = msg.text().or(msg.caption()).unwrap_or("");
+    let Some(me) = me else {
+        return (raw.to_string(), false, false);
+    };
+    let (text, mentioned) = strip_bot_mention(raw, &me.username);
+    let replies_to_bot = msg
+        .reply_to_message()
+        .and_then(|replied| replied.from.as_ref())
+        .is_some_and(|user| user.id == me.id);
+    (text, mentioned, replies_to_bot)
+}
+
+/// Telegram command menu entries. Telegram shows only a name and a short
+/// description, so the usage line stands in for argument docs.
+fn telegram_bot_commands(specs: &[CommandSpec]) -> Vec<BotCommand> {
+    specs
+        .iter()
+        .map(|spec| {
+            let usage = spec.usage();
+            let args = usage.trim_start_matches('/').trim_start_matches(spec.name);
+            let description: String = format!("{}{args}", spec.description)
+                .chars()
+                .take(256)
+                .collect();
+            BotCommand::new(spec.name, description)
+        })
+        .collect()
 }
 
 /// Download a file from Telegram by its file_id.
@@ -124,69 +218,96 @@ fn extract_message_info(msg: &teloxide::types::Message) -> Option<(i64, String,
     Some((chat_id.0, user_id, user_name))
 }
 
+/// The media of an incoming Telegram message, not downloaded yet. The
+/// callback fetches it with [`PendingMedia::download`] once the sender is
+/// admitted, so a dropped message costs no downloads.
+pub struct PendingMedia {
+    bot: Bot,
+    file_id: teloxide::types::FileId,
+    kind: &'static str,
+    build: Box<dyn FnOnce(Vec<u8>) -> MediaAttachment + Send>,
+}
+
+impl PendingMedia {
+    fn new(
+        bot: &Bot,
+        file_id: &teloxide::types::FileId,
+        kind: &'static str,
+        build: impl FnOnce(Vec<u8>) -> MediaAttachment + Send + 'static,
+    ) -> Self {
+        Self {
+            bot: bot.clone(),
+            file_id: file_id.clone(),
+            kind,
+            build: Box::new(build),
+        }
+    }
+
+    /// `photo`, `document`, `video` or `voice`.
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// Download the file from Telegram.
+    pub async fn download(self) -> std::result::Result<MediaAttachment, String> {
+        let data = download_telegram_file(&self.bot, &self.file_id).await?;
+        Ok((self.build)(data))
+    }
+}
+
 /// Extract text and optional media attachment from a Telegram message.
 /// Returns None if the message type is unsupported.
-async fn extract_content(
+fn extract_content(
     bot: &Bot,
     msg: &teloxide::types::Message,
-) -> Option<(String, Option<MediaAttachment>)> {
+) -> Option<(String, Option<PendingMedia>)> {
+    let caption = msg.caption().map(|c| c.to_string());
+    let text = caption.clone().unwrap_or_default();
+
     // Photos (take the largest resolution)
     if let Some(photo) = msg.photo().and_then(|p| p.last()) {
-        {
-            let caption = msg.caption().map(|c| c.to_string());
-            match download_telegram_file(bot, &photo.file.id).await {
-                Ok(data) => {
-                    let text = caption.clone().unwrap_or_default();
-                    return Some((text, Some(MediaAttachment::Photo { data, caption })));
-                }
-                Err(e) => {
-                    warn!("telegram: failed to download photo: {e}");
-                    return None;
-                }
-            }
-        }
+        let media = PendingMedia::new(bot, &photo.file.id, "photo", move |data| {
+            MediaAttachment::Photo { data, caption }
+        });
+        return Some((text, Some(media)));
     }
 
     // Documents
     if let Some(doc) = msg.document() {
-        let caption = msg.caption().map(|c| c.to_string());
-        match download_telegram_file(bot, &doc.file.id).await {
-            Ok(data) => {
-                let text = caption.clone().unwrap_or_default();
-                let filename = doc.file_name.clone();
-                let mime_type = doc.mime_type.as_ref().map(|m| m.to_string());
-                return Some((
-                    text,
-                    Some(MediaAttachment::Document {
-                        data,
-                        filename,
-                        mime_type,
-                        caption,
-                    }),
-                ));
-            }
-            Err(e) => {
-                warn!("telegram: failed to download document: {e}");
-                return None;
+        let filename = doc.file_name.clone();
+        let mime_type = doc.mime_type.as_ref().map(|m| m.to_string());
+        let media = PendingMedia::new(bot, &doc.file.id, "document", move |data| {
+            MediaAttachment::Document {
+                data,
+                filename,
+                mime_type,
+                caption,
             }
-        }
+        });
+        return Some((text, Some(media)));
+    }
+
+    // Videos and round video notes
+    let video = msg
+        .video()
+        .map(|v| (&v.file.id, v.duration.seconds()))
+        .or_else(|| msg.video_note().map(|v| (&v.file.id, v.duration.seconds())));
+    if let Some((file_id, duration)) = video {
+        let media = PendingMedia::new(bot, file_id, "video", move |data| MediaAttachment::Video {
+            data,
+            caption,
+            duration,
+        });
+        return Some((text, Some(media)));
     }
 
     // Voice messages
     if let Some(voice) = msg.voice() {
-        match download_telegram_file(bot, &voice.file.id).await {
-            Ok(data) => {
-                let duration = voice.duration.seconds();
-                return Some((
-                    String::new(),
-                    Some(MediaAttachment::Voice { data, duration }),
-                ));
-            }
-            Err(e) => {
-                warn!("telegram: failed to download voice: {e}");
-                return None;
-            }
-        }
+        let duration = voice.duration.seconds();
+        let media = PendingMedia::new(bot, &voice.file.id, "voice", move |data| {
+            MediaAttachment::Voice { data, duration }
+        });
+        return Some((String::new(), Some(media)));
     }
 
     // Plain text
@@ -212,6 +333,18 @@ impl ChannelSender for TelegramSender {
     async fn send_message(&self, message: &Message) -> Result<()> {
         telegram_send_message(&self.bot, message).await
     }
+
+    async fn send_voice(&self, metadata: &serde_json::Value, audio: Vec<u8>) -> Result<()> {
+        telegram_send_voice(&self.bot, telegram_chat_id(metadata)?, audio).await
+    }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        telegram_send_file(&self.bot, telegram_chat_id(metadata)?, file).await
+    }
 }
 
 #[async_trait]
@@ -230,15 +363,39 @@ impl ChannelLifecycle for TelegramChannel {
         let bot = Bot::new(&self.bot_token);
         self.bot = Some(bot.clone());
 
+        if !self.commands.is_empty()
+            && let Err(e) = bot
+                .set_my_commands(telegram_bot_commands(&self.commands))
+                .await
+        {
+            warn!("failed to publish telegram command menu: {e}");
+        }
+
         let (shutdown_tx, shutdown_rx) = watch::channel(false);
         self.shutdown_tx = Some(shutdown_tx);
 
+        let me = match bot.get_me().await {
+            Ok(me) => Some(BotIdentity {
+                id: me.id,
+                username: me.username().to_string(),
+            }),
+            Err(e) => {
+                warn!("telegram getMe failed: {e}; mentions of the bot will not be recognised");
+                None
+            }
+        };
+
         let on_message = Arc::clone(&self.on_message);
+        let voice_replier = self.voice_replier.clone();
+        let group_policy = Arc::clone(&self.group_policy);
 
         tokio::spawn(async move {
             let handler = Update::filter_message().endpoint(
                 move |bot: Bot, msg: teloxide::types::Message| {
                     let on_message = Arc::clone(&on_message);
+                    let voice_replier = voice_replier.clone();
+                    let group_policy = Arc::clone(&group_policy);
+                    let me = me.clone();
                     async move {
                         let (chat_id_raw, user_id, user_name) =
                             match extract_message_info(&msg) {
@@ -246,21 +403,42 @@ impl ChannelLifecycle for TelegramChannel {
                                 None => return respond(()),
                             };
 
+                        // In groups, the policy decides before any media is
+                        // downloaded.
+                        let group_context = if msg.chat.is_private() {
+                            None
+                        } else {
+                            let (text, mentioned, replies_to_bot) =
+                                group_addressing(&msg, me.as_ref());
+                            match group_policy.admit(&GroupMessage {
+                                group_id: &chat_id_raw.to_string(),
+                                sender_name: &user_name,
+                                text: &text,
+                                mentioned,
+                                replies_to_bot,
+                            }) {
+                                Some(context) => Some(context),
+                                None => return respond(()),
+                            }
+                        };
+
                         // Extract content (text + optional media)
-                        let (text, attachment) = match extract_content(&bot, &msg).await {
+                        let (text, attachment) = match extract_content(&bot, &msg) {
                             Some(content) => content,
                             None => return respond(()),
                         };
+                        let text = match (&group_context, &me) {
+                            (Some(context), Some(me)) => {
+                                context.prepend_to(&strip_bot_mention(&text, &me.username).0)
+                            }
+                            (Some(context), None) => context.prepend_to(&text),
+                            (None, _) => text,
+                        };
 
                         // ChatId wrapper for teloxide calls
                         let chat_id = ChatId(chat_id_raw);
 
-                        let kind = match &attachment {
-                            Some(MediaAttachment::Photo { .. }) => "photo",
-                            Some(MediaAttachment::Document { .. }) => "document",
-                            Some(MediaAttachment::Voice { .. }) => "voice",
-                            None => "text",
-                        };
+                        let kind = attachment.as_ref().map_or("text", PendingMedia::kind);
                         info!(
                             "telegram {kind} from {} [uid={}] (chat {}): {} chars",
                             user_name,
@@ -299,7 +477,7 @@ impl ChannelLifecycle for TelegramChannel {
                         // responses appear as a single formatted message instead of
                         // flashing the first word then replacing it.
                         let mut accumulated = String::new();
-                        let mut msg_id: Option<teloxide::types::MessageId> = None;
+                        let mut msg_id: Option<MessageId> = None;
                         let mut last_edit = tokio::time::Instant::now();
                         let mut first_delta_at: Option<tokio::time::Instant> = None;
 
@@ -353,31 +531,22 @@ impl ChannelLifecycle for TelegramChannel {
 
                         match result {
                             Ok(final_text) => {
-                                if let Some(id) = msg_id {
-                                    // Final edit with MarkdownV2 formatting
-                                    let formatted = to_telegram_markdown(&final_text);
-                                    let edit_result = bot
-                                        .edit_message_text(chat_id, id, &formatted)
-                                        .parse_mode(ParseMode::MarkdownV2)
-                                        .await;
-                                    if edit_result.is_err() {
-                                        // Fallback: plain text
-                                        let _ = bot
-                                            .edit_message_text(chat_id, id, &final_text)
-                                            .await;
-                                    }
-                                } else {
-                                    // No streaming happened (command response) - send directly
-                                    let formatted = to_telegram_markdown(&final_text);
-                                    let send_result = bot
-                                        .send_message(chat_id, &formatted)
-                                        .parse_mode(ParseMode::MarkdownV2)
-                                        .await;
-                                    if send_result.is_err() {
-                                        // Fallback: plain text
-                                        let _ =
-                                            bot.send_message(chat_id, &final_text).await;
-                                    }
+                                // Final text with MarkdownV2 formatting, replacing the
+                                // streamed preview if there is one
+                                if let Err(e) =
+                                    telegram_send_markdown(&bot, chat_id, msg_id, &final_text)
+                                        .await
+                                {
+                                    error!("failed to send telegram reply: {e}");
+                                }
+
+                                if let Some(replier) = &voice_replier
+                                    && let Some(audio) = replier
+                                        .voice_reply(&user_id, kind == "voice", &final_text)
+                                        .await
+                                    && let Err(e) = telegram_send_voice(&bot, chat_id, audio).await
+                                {
+                                    warn!("telegram voice reply failed: {e}");
                                 }
                             }
                             Err(e) if e == "__blocked__" => {
@@ -469,33 +638,175 @@ impl ChannelSender for TelegramChannel {
             .ok_or_else(|| opencrust_common::Error::Channel("telegram bot not connected".into()))?;
         telegram_send_message(bot, message).await
     }
+
+    async fn send_voice(&self, metadata: &serde_json::Value, audio: Vec<u8>) -> Result<()> {
+        let bot = self
+            .bot
+            .as_ref()
+            .ok_or_else(|| opencrust_common::Error::Channel("telegram bot not connected".into()))?;
+        telegram_send_voice(bot, telegram_chat_id(metadata)?, audio).await
+    }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        let bot = self
+            .bot
+            .as_ref()
+            .ok_or_else(|| opencrust_common::Error::Channel("telegram bot not connected".into()))?;
+        telegram_send_file(bot, telegram_chat_id(metadata)?, file).await
+    }
 }
 
-/// Shared send logic used by both `TelegramChannel` and `TelegramSender`.
-async fn telegram_send_message(bot: &Bot, message: &Message) -> Result<()> {
-    let chat_id: i64 = message
-        .metadata
+fn telegram_chat_id(metadata: &serde_json::Value) -> Result<ChatId> {
+    metadata
         .get("telegram_chat_id")
         .and_then(|v| v.as_i64())
+        .map(ChatId)
         .ok_or_else(|| {
             opencrust_common::Error::Channel("missing telegram_chat_id in metadata".into())
+        })
+}
+
+/// Upload Ogg/Opus audio as a Telegram voice message.
+async fn telegram_send_voice(bot: &Bot, chat_id: ChatId, audio: Vec<u8>) -> Result<()> {
+    let _ = bot.send_chat_action(chat_id, ChatAction::UploadVoice).await;
+    bot.send_voice(chat_id, InputFile::memory(audio).file_name("reply.ogg"))
+        .await
+        .map_err(|e| {
+            opencrust_common::Error::Channel(format!("telegram send_voice failed: {e}"))
         })?;
+    Ok(())
+}
+
+/// Bot API upload limits: photos up to 10MB, everything else up to 50MB.
+const TELEGRAM_MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+const TELEGRAM_MAX_UPLOAD_BYTES: usize = 50 * 1024 * 1024;
+const TELEGRAM_MAX_CAPTION_CHARS: usize = 1024;
+
+/// How a file is presented in a Telegram chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TelegramUpload {
+    Photo,
+    Video,
+    Voice,
+    Audio,
+    Document,
+}
+
+/// Pick the upload method for a file. Photos over the photo limit fall back
+/// to documents, which also keeps them uncompressed.
+fn telegram_upload_kind(file: &OutboundFile) -> Result<TelegramUpload> {
+    if file.data.len() > TELEGRAM_MAX_UPLOAD_BYTES {
+        return Err(opencrust_common::Error::Channel(format!(
+            "file too large for telegram ({} bytes, maximum {TELEGRAM_MAX_UPLOAD_BYTES})",
+            file.data.len()
+        )));
+    }
+    Ok(
+        if file.is_image() && file.data.len() <= TELEGRAM_MAX_PHOTO_BYTES {
+            TelegramUpload::Photo
+        } else if file.is_video() {
+            TelegramUpload::Video
+        } else if file.mime_type == "audio/ogg" {
+            TelegramUpload::Voice
+        } else if file.is_audio() {
+            TelegramUpload::Audio
+        } else {
+            TelegramUpload::Document
+        },
+    )
+}
+
+/// Upload a file as a photo, video, voice note, audio track or document.
+async fn telegram_send_file(bot: &Bot, chat_id: ChatId, file: OutboundFile) -> Result<()> {
+    let kind = telegram_upload_kind(&file)?;
+    let caption: String = file
+        .caption
+        .as_deref()
+        .unwrap_or_default()
+        .chars()
+        .take(TELEGRAM_MAX_CAPTION_CHARS)
+        .collect();
+    let input = InputFile::memory(file.data).file_name(file.filename);
+    let action = match kind {
+        TelegramUpload::Photo => ChatAction::UploadPhoto,
+        TelegramUpload::Video => ChatAction::UploadVideo,
+        TelegramUpload::Voice => ChatAction::UploadVoice,
+        TelegramUpload::Audio | TelegramUpload::Document => ChatAction::UploadDocument,
+    };
+    let _ = bot.send_chat_action(chat_id, action).await;
+
+    let result = match kind {
+        TelegramUpload::Photo => bot.send_photo(chat_id, input).caption(caption).await,
+        TelegramUpload::Video => bot.send_video(chat_id, input).caption(caption).await,
+        TelegramUpload::Voice => bot.send_voice(chat_id, input).caption(caption).await,
+        TelegramUpload::Audio => bot.send_audio(chat_id, input).caption(caption).await,
+        TelegramUpload::Document => bot.send_document(chat_id, input).caption(caption).await,
+    };
+    result.map_err(|e| {
+        opencrust_common::Error::Channel(format!("telegram file upload failed: {e}"))
+    })?;
+    Ok(())
+}
+
+/// Telegram rejects text messages longer than this many characters.
+const TELEGRAM_MESSAGE_CHAR_LIMIT: usize = 4096;
+
+/// Send Markdown as MarkdownV2, split into messages Telegram accepts. The
+/// first message replaces `existing` (a streamed preview) when given. A
+/// message Telegram fails to parse is resent as plain text.
+async fn telegram_send_markdown(
+    bot: &Bot,
+    chat_id: ChatId,
+    existing: Option<MessageId>,
+    text: &str,
+) -> std::result::Result<(), teloxide::RequestError> {
+    let chunks = markdown::parse(text).split(&TelegramMarkdownV2, TELEGRAM_MESSAGE_CHAR_LIMIT);
+    if chunks.is_empty() {
+        return Ok(());
+    }
+
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let formatted = chunk.render(&TelegramMarkdownV2);
+        let plain = chunk.render(&PlainText);
+        match existing.filter(|_| idx == 0) {
+            Some(id) => {
+                let edit = bot
+                    .edit_message_text(chat_id, id, &formatted)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await;
+                if edit.is_err() {
+                    bot.edit_message_text(chat_id, id, &plain).await?;
+                }
+            }
+            None => {
+                let send = bot
+                    .send_message(chat_id, &formatted)
+                    .parse_mode(ParseMode::MarkdownV2)
+                    .await;
+                if send.is_err() {
+                    bot.send_message(chat_id, &plain).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
 
-    let tg_chat_id = ChatId(chat_id);
+/// Shared send logic used by both `TelegramChannel` and `TelegramSender`.
+async fn telegram_send_message(bot: &Bot, message: &Message) -> Result<()> {
+    let tg_chat_id = telegram_chat_id(&message.metadata)?;
 
     match &message.content {
         MessageContent::Text(text) => {
-            let formatted = to_telegram_markdown(text);
-            let send_result = bot
-                .send_message(tg_chat_id, &formatted)
-                .parse_mode(ParseMode::MarkdownV2)
-                .await;
-            if send_result.is_err() {
-                // Fallback: plain text
-                bot.send_message(tg_chat_id, text).await.map_err(|e| {
+            telegram_send_markdown(bot, tg_chat_id, None, text)
+                .await
+                .map_err(|e| {
                     opencrust_common::Error::Channel(format!("telegram send failed: {e}"))
                 })?;
-            }
         }
         MessageContent::Image { url, caption } => {
             bot.send_photo(
@@ -549,6 +860,83 @@ mod tests {
         assert_eq!(channel.status(), ChannelStatus::Disconnected);
     }
 
+    #[test]
+    fn upload_kind_follows_mime_type_and_size() {
+        let file = |mime: &str, len: usize| OutboundFile {
+            data: vec![0; len],
+            filename: "f".into(),
+            mime_type: mime.into(),
+            caption: None,
+        };
+        let kind = |f: OutboundFile| telegram_upload_kind(&f).unwrap();
+        assert_eq!(kind(file("image/png", 10)), TelegramUpload::Photo);
+        assert_eq!(
+            kind(file("image/png", TELEGRAM_MAX_PHOTO_BYTES + 1)),
+            TelegramUpload::Document
+        );
+        assert_eq!(kind(file("video/mp4", 10)), TelegramUpload::Video);
+        assert_eq!(kind(file("audio/ogg", 10)), TelegramUpload::Voice);
+        assert_eq!(kind(file("audio/mpeg", 10)), TelegramUpload::Audio);
+        assert_eq!(kind(file("application/pdf", 10)), TelegramUpload::Document);
+        assert!(telegram_upload_kind(&file("text/csv", TELEGRAM_MAX_UPLOAD_BYTES + 1)).is_err());
+    }
+
+    #[test]
+    fn bot_commands_include_usage() {
+        use crate::commands::{ArgSpec, Permission};
+        let specs = vec![
+            CommandSpec::new("help", "Show help", Permission::Allowed),
+            CommandSpec::new("model", "Show or switch the model", Permission::Allowed)
+                .arg(ArgSpec::text("name", "Model name")),
+        ];
+        let commands = telegram_bot_commands(&specs);
+        assert_eq!(commands[0].command, "help");
+        assert_eq!(commands[0].description, "Show help");
+        assert_eq!(commands[1].description, "Show or switch the model [name]");
+    }
+
+    #[test]
+    fn group_addressing_finds_mentions_and_replies_to_the_bot() {
+        let json = r#"{
+            "message_id": 6,
+            "date": 1620000000,
+            "chat": {"id": -987654321, "type": "supergroup", "title": "My Group"},
+            "from": {"id": 222, "is_bot": false, "first_name": "Bob"},
+            "text": "@CrustBot what now?",
+            "reply_to_message": {
+                "message_id": 5,
+                "date": 1620000000,
+                "chat": {"id": -987654321, "type": "supergroup", "title": "My Group"},
+                "from": {"id": 999, "is_bot": true, "first_name": "Crust", "username": "crustbot"},
+                "text": "done"
+            }
+        }"#;
+        let msg: teloxide::types::Message =
+            serde_json::from_str(json).expect("failed to parse json");
+        let me = BotIdentity {
+            id: UserId(999),
+            username: "crustbot".to_string(),
+        };
+
+        let (text, mentioned, replies_to_bot) = group_addressing(&msg, Some(&me));
+        assert_eq!(text, "what now?");
+        assert!(mentioned);
+        assert!(replies_to_bot);
+
+        let (text, mentioned, replies_to_bot) = group_addressing(&msg, None);
+        assert_eq!(text, "@CrustBot what now?");
+        assert!(!mentioned && !replies_to_bot);
+
+        assert_eq!(
+            strip_bot_mention("/help@crustbot", "crustbot"),
+            ("/help".to_string(), true)
+        );
+        assert_eq!(
+            strip_bot_mention("hi @someone", "crustbot"),
+            ("hi @someone".to_string(), false)
+        );
+    }
+
     #[test]
     fn test_extract_message_info_private() {
         // Construct a private message JSON
@@ -1,102 +1,65 @@
-/// Convert standard markdown to Telegram MarkdownV2 format.
+use crate::markdown::{self, Renderer};
+
+/// Renders the Markdown IR as Telegram MarkdownV2.
 ///
 /// Telegram MarkdownV2 requires escaping these characters outside of
 /// formatting entities: `_`, `*`, `[`, `]`, `(`, `)`, `~`, `` ` ``, `>`,
 /// `#`, `+`, `-`, `=`, `|`, `{`, `}`, `.`, `!`
 ///
-/// This function handles:
-/// - Code blocks (``` ... ```) — preserved as-is
-/// - Inline code (` ... `) — preserved as-is
-/// - Bold (**text**) → *text*
-/// - Italic (*text* or _text_) → _text_
-/// - Escaping special chars in plain text
-pub fn to_telegram_markdown(input: &str) -> String {
-    let mut result = String::with_capacity(input.len() * 2);
-    let chars: Vec<char> = input.chars().collect();
-    let len = chars.len();
-    let mut i = 0;
-
-    while i < len {
-        // Fenced code block: ```...```
-        if i + 2 < len && chars[i] == '`' && chars[i + 1] == '`' && chars[i + 2] == '`' {
-            // Find the language tag (rest of line after ```)
-            let block_start = i;
-            i += 3;
-
-            // Skip optional language tag
-            while i < len && chars[i] != '\n' {
-                i += 1;
-            }
-
-            let header = &input[byte_offset(&chars, block_start)..byte_offset(&chars, i)];
-
-            // Find closing ```
-            let content_start = i;
-            let mut found_close = false;
-            while i < len {
-                if i + 2 < len && chars[i] == '`' && chars[i + 1] == '`' && chars[i + 2] == '`' {
-                    let content =
-                        &input[byte_offset(&chars, content_start)..byte_offset(&chars, i)];
-                    result.push_str(header);
-                    result.push_str(content);
-                    result.push_str("```");
-                    i += 3;
-                    found_close = true;
-                    break;
-                }
-                i += 1;
-            }
-            if !found_close {
-                // Unclosed code block — just push the rest
-                let content = &input[byte_offset(&chars, content_start)..byte_offset(&chars, len)];
-                result.push_str(header);
-                result.push_str(content);
-            }
-            continue;
-        }
+/// Inside code only `` ` `` and `\` are escaped, and inside link URLs only
+/// `)` and `\`. Headings become bold lines.
+pub struct TelegramMarkdownV2;
 
-        // Inline code: `...`
-        if chars[i] == '`' {
-            result.push('`');
-            i += 1;
-            while i < len && chars[i] != '`' {
-                result.push(chars[i]);
-                i += 1;
-            }
-            if i < len {
-                result.push('`');
-                i += 1;
-            }
-            continue;
-        }
+impl Renderer for TelegramMarkdownV2 {
+    fn text(&self, text: &str) -> String {
+        escape_with(text, |c| c == '\\' || is_special(c))
+    }
 
-        // Bold: **text** → *text*
-        if i + 1 < len && chars[i] == '*' && chars[i + 1] == '*' {
-            result.push('*');
-            i += 2;
-            while i < len && !(i + 1 < len && chars[i] == '*' && chars[i + 1] == '*') {
-                if is_special(chars[i]) && chars[i] != '*' {
-                    result.push('\\');
-                }
-                result.push(chars[i]);
-                i += 1;
-            }
-            result.push('*');
-            if i + 1 < len {
-                i += 2; // skip closing **
-            }
-            continue;
-        }
+    fn bold(&self, inner: &str) -> String {
+        format!("*{inner}*")
+    }
 
-        // Escape special characters in plain text
-        if is_special(chars[i]) {
-            result.push('\\');
-        }
-        result.push(chars[i]);
-        i += 1;
+    fn italic(&self, inner: &str) -> String {
+        format!("_{inner}_")
+    }
+
+    fn strike(&self, inner: &str) -> String {
+        format!("~{inner}~")
+    }
+
+    fn code(&self, code: &str) -> String {
+        format!("`{}`", escape_with(code, |c| matches!(c, '`' | '\\')))
+    }
+
+    fn code_block(&self, lang: &str, code: &str) -> String {
+        format!(
+            "```{lang}\n{}\n```",
+            escape_with(code, |c| matches!(c, '`' | '\\'))
+        )
     }
 
-    result
+    fn link(&self, text: &str, url: &str) -> String {
+        format!(
+            "[{text}]({})",
+            escape_with(url, |c| matches!(c, ')' | '\\'))
+        )
+    }
+}
+
+/// Convert standard markdown to Telegram MarkdownV2 format.
+pub fn to_telegram_markdown(input: &str) -> String {
+    markdown::parse(input).render(&TelegramMarkdownV2)
+}
+
+fn escape_with(text: &str, needs_escape: impl Fn(char) -> bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if needs_escape(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
 }
 
 fn is_special(c: char) -> bool {
@@ -122,10 +85,6 @@ fn is_special(c: char) -> bool {
     )
 }
 
-fn byte_offset(chars: &[char], char_index: usize) -> usize {
-    chars[..char_index].iter().map(|c| c.len_utf8()).sum()
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,4 +123,12 @@ mod tests {
         let output = to_telegram_markdown(input);
         assert_eq!(output, "Hello\\! Try `code` and *bold*\\.");
     }
+
+    #[test]
+    fn links_headings_and_code_escapes() {
+        assert_eq!(
+            to_telegram_markdown("# Title\n\n[a.b](https://x.y) and `C:\\dir`"),
+            "*Title*\n\n[a\\.b](https://x.y) and `C:\\\\dir`"
+        );
+    }
 }
@@ -1,5 +1,5 @@
 use async_trait::async_trait;
-use opencrust_common::{Message, Result};
+use opencrust_common::{Error, Message, OutboundFile, Result};
 use serde::{Deserialize, Serialize};
 
 /// Lifecycle management for a messaging channel (connect, disconnect, status).
@@ -35,6 +35,49 @@ pub trait ChannelSender: Send + Sync {
 
     /// Send a message through this channel.
     async fn send_message(&self, message: &Message) -> Result<()>;
+
+    /// Send Ogg/Opus audio as a voice message. `metadata` addresses the chat
+    /// with the same keys `send_message` reads from `Message::metadata`.
+    async fn send_voice(&self, metadata: &serde_json::Value, audio: Vec<u8>) -> Result<()> {
+        let _ = (metadata, audio);
+        Err(Error::Channel(format!(
+            "{} does not support voice messages",
+            self.channel_type()
+        )))
+    }
+
+    /// Upload a file to the chat addressed by `metadata`. Each channel picks
+    /// the presentation (photo, video, document) and enforces its own limits.
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        let _ = (metadata, file);
+        Err(Error::Channel(format!(
+            "{} does not support file uploads",
+            self.channel_type()
+        )))
+    }
+}
+
+/// Decides whether a reply is also spoken, and synthesizes it.
+///
+/// Implemented by the gateway, which owns reply preferences and the
+/// text-to-speech backend; channels that support voice notes call it after
+/// sending the text reply.
+#[async_trait]
+pub trait VoiceReplier: Send + Sync {
+    /// Ogg/Opus audio for `reply`, or `None` to answer in text only.
+    /// `user_spoke` is true when the incoming message was a voice note.
+    async fn voice_reply(&self, user_id: &str, user_spoke: bool, reply: &str) -> Option<Vec<u8>>;
+}
+
+/// An image a user sent, downloaded for the model's vision input.
+#[derive(Debug, Clone)]
+pub struct IncomingImage {
+    pub data: Vec<u8>,
+    pub mime_type: String,
 }
 
 /// Convenience trait combining lifecycle and send capabilities.
@@ -1,92 +1,606 @@
+use opencrust_common::OutboundFile;
 use reqwest::Client;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tracing::warn;
 
-const GRAPH_API_BASE: &str = "https://graph.facebook.com/v21.0";
+pub const DEFAULT_GRAPH_API_BASE: &str = "https://graph.facebook.com/v21.0";
 
-#[derive(Serialize)]
-struct WhatsAppTextBody {
-    body: String,
+/// Cloud API limits for interactive messages.
+const MAX_REPLY_BUTTONS: usize = 3;
+const MAX_BUTTON_TITLE_CHARS: usize = 20;
+const MAX_LIST_ROWS: usize = 10;
+const MAX_ROW_TITLE_CHARS: usize = 24;
+const MAX_ROW_DESCRIPTION_CHARS: usize = 72;
+
+/// A quick-reply button. The user's tap comes back as an interactive
+/// `button_reply` carrying the same `id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplyButton {
+    pub id: String,
+    pub title: String,
 }
 
-#[derive(Serialize)]
-struct WhatsAppMessage {
-    messaging_product: String,
-    recipient_type: String,
-    to: String,
-    #[serde(rename = "type")]
-    msg_type: String,
-    text: WhatsAppTextBody,
+/// One selectable row in a list message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListRow {
+    pub id: String,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
 }
 
-#[derive(Serialize)]
-struct WhatsAppReadReceipt {
-    messaging_product: String,
-    status: String,
-    message_id: String,
+/// A titled group of rows in a list message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListSection {
+    pub title: String,
+    pub rows: Vec<ListRow>,
 }
 
-/// Send a text message to a WhatsApp user.
-pub async fn send_text_message(
-    client: &Client,
-    token: &str,
-    phone_number_id: &str,
-    to: &str,
-    text: &str,
-) -> Result<(), String> {
-    let msg = WhatsAppMessage {
-        messaging_product: "whatsapp".to_string(),
-        recipient_type: "individual".to_string(),
-        to: to.to_string(),
-        msg_type: "text".to_string(),
-        text: WhatsAppTextBody {
-            body: text.to_string(),
-        },
-    };
+/// Cloud API endpoints for one WhatsApp Business phone number.
+#[derive(Clone)]
+pub struct GraphApi {
+    client: Client,
+    base: String,
+    token: String,
+    phone_number_id: String,
+}
 
-    let resp = client
-        .post(format!("{GRAPH_API_BASE}/{phone_number_id}/messages"))
-        .bearer_auth(token)
-        .json(&msg)
-        .send()
-        .await
-        .map_err(|e| format!("WhatsApp send_text_message failed: {e}"))?;
+impl GraphApi {
+    pub fn new(client: Client, token: &str, phone_number_id: &str) -> Self {
+        Self {
+            client,
+            base: DEFAULT_GRAPH_API_BASE.to_string(),
+            token: token.to_string(),
+            phone_number_id: phone_number_id.to_string(),
+        }
+    }
 
-    let status = resp.status();
-    if !status.is_success() {
-        let body = resp.text().await.unwrap_or_default();
-        warn!("WhatsApp send_text_message error {status}: {body}");
-        return Err(format!("WhatsApp API error {status}: {body}"));
+    /// Use a different Graph API base URL, e.g. a local mock server.
+    pub fn with_base(mut self, base: &str) -> Self {
+        self.base = base.trim_end_matches('/').to_string();
+        self
     }
 
-    Ok(())
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn phone_number_id(&self) -> &str {
+        &self.phone_number_id
+    }
+
+    /// POST a payload to the `messages` endpoint. `what` names the call in
+    /// errors and logs.
+    async fn post_message(&self, msg: &serde_json::Value, what: &str) -> Result<(), String> {
+        let resp = self
+            .client
+            .post(format!("{}/{}/messages", self.base, self.phone_number_id))
+            .bearer_auth(&self.token)
+            .json(msg)
+            .send()
+            .await
+            .map_err(|e| format!("WhatsApp {what} failed: {e}"))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            warn!("WhatsApp {what} error {status}: {body}");
+            return Err(format!("WhatsApp API error {status}: {body}"));
+        }
+
+        Ok(())
+    }
+
+    /// Send a text message to a WhatsApp user.
+    pub async fn send_text_message(&self, to: &str, text: &str) -> Result<(), String> {
+        let msg = serde_json::json!({
+            "messaging_product": "whatsapp",
+            "recipient_type": "individual",
+            "to": to,
+            "type": "text",
+            "text": { "body": text },
+        });
+        self.post_message(&msg, "send_text_message").await
+    }
+
+    /// Mark a message as read. Failures are logged, not returned.
+    pub async fn mark_as_read(&self, message_id: &str) -> Result<(), String> {
+        let receipt = serde_json::json!({
+            "messaging_product": "whatsapp",
+            "status": "read",
+            "message_id": message_id,
+        });
+        if let Err(e) = self.post_message(&receipt, "mark_as_read").await {
+            warn!("{e}");
+        }
+        Ok(())
+    }
+
+    /// Resolve a media ID from an incoming message to a short-lived download
+    /// URL. The URL must be fetched with the same bearer token.
+    pub async fn media_url(&self, media_id: &str) -> Result<String, String> {
+        let resp = self
+            .client
+            .get(format!("{}/{media_id}", self.base))
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .map_err(|e| format!("WhatsApp media lookup failed: {e}"))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("WhatsApp API error {status}: {body}"));
+        }
+
+        let body: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| format!("WhatsApp media lookup returned invalid JSON: {e}"))?;
+        body.get("url")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| "WhatsApp media lookup response has no url".to_string())
+    }
+
+    /// Upload media to WhatsApp and return its media ID for use in a message.
+    pub async fn upload_media(
+        &self,
+        data: Vec<u8>,
+        filename: &str,
+        mime_type: &str,
+    ) -> Result<String, String> {
+        let part = reqwest::multipart::Part::bytes(data)
+            .file_name(filename.to_string())
+            .mime_str(mime_type)
+            .map_err(|e| format!("WhatsApp upload_media failed: {e}"))?;
+        let form = reqwest::multipart::Form::new()
+            .text("messaging_product", "whatsapp")
+            .text("type", mime_type.to_string())
+            .part("file", part);
+
+        let resp = self
+            .client
+            .post(format!("{}/{}/media", self.base, self.phone_number_id))
+            .bearer_auth(&self.token)
+            .multipart(form)
+            .send()
+            .await
+            .map_err(|e| format!("WhatsApp upload_media failed: {e}"))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(format!("WhatsApp API error {status}: {body}"));
+        }
+
+        let body: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| format!("WhatsApp upload_media returned invalid JSON: {e}"))?;
+        body.get("id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| "WhatsApp upload_media response has no id".to_string())
+    }
+
+    /// Send Ogg/Opus audio as a voice message. WhatsApp shows `audio/ogg`
+    /// uploads encoded with Opus as playable voice notes.
+    pub async fn send_voice_message(&self, to: &str, audio: Vec<u8>) -> Result<(), String> {
+        let media_id = self.upload_media(audio, "reply.ogg", "audio/ogg").await?;
+        let msg = serde_json::json!({
+            "messaging_product": "whatsapp",
+            "recipient_type": "individual",
+            "to": to,
+            "type": "audio",
+            "audio": { "id": media_id },
+        });
+        self.post_message(&msg, "send_voice_message").await
+    }
+
+    /// Upload a file and send it as an image, video, audio or document message.
+    pub async fn send_media_message(&self, to: &str, file: OutboundFile) -> Result<(), String> {
+        let kind = media_message_type(&file)?;
+        let media_id = self
+            .upload_media(file.data.clone(), &file.filename, &file.mime_type)
+            .await?;
+        let media = media_object(
+            kind,
+            serde_json::json!({ "id": media_id }),
+            file.caption.as_deref(),
+            Some(&file.filename),
+        );
+        self.post_message(&media_message(to, kind, media), "send_media_message")
+            .await
+    }
+
+    /// Send media the Cloud API fetches itself from a public `link`. `kind`
+    /// is `image`, `video`, `audio` or `document`.
+    pub async fn send_media_link(
+        &self,
+        to: &str,
+        kind: &str,
+        link: &str,
+        caption: Option<&str>,
+        filename: Option<&str>,
+    ) -> Result<(), String> {
+        let media = media_object(kind, serde_json::json!({ "link": link }), caption, filename);
+        self.post_message(&media_message(to, kind, media), "send_media_link")
+            .await
+    }
+
+    /// Send up to three quick-reply buttons under `body`.
+    pub async fn send_reply_buttons(
+        &self,
+        to: &str,
+        body: &str,
+        buttons: &[ReplyButton],
+    ) -> Result<(), String> {
+        let msg = reply_buttons_message(to, body, buttons)?;
+        self.post_message(&msg, "send_reply_buttons").await
+    }
+
+    /// Send a list message: `button` opens a menu of up to ten rows.
+    pub async fn send_list(
+        &self,
+        to: &str,
+        body: &str,
+        button: &str,
+        sections: &[ListSection],
+    ) -> Result<(), String> {
+        let msg = list_message(to, body, button, sections)?;
+        self.post_message(&msg, "send_list").await
+    }
 }
 
-/// Mark a message as read.
-pub async fn mark_as_read(
-    client: &Client,
-    token: &str,
-    phone_number_id: &str,
-    message_id: &str,
-) -> Result<(), String> {
-    let receipt = WhatsAppReadReceipt {
-        messaging_product: "whatsapp".to_string(),
-        status: "read".to_string(),
-        message_id: message_id.to_string(),
+/// Cloud API media limits per message type.
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+const MAX_AUDIO_VIDEO_BYTES: usize = 16 * 1024 * 1024;
+const MAX_DOCUMENT_BYTES: usize = 100 * 1024 * 1024;
+
+/// Message type for an outbound file. JPEG and PNG go out as images, MP4 as
+/// video, audio as audio; anything else (or an oversized image) as a document.
+pub fn media_message_type(file: &OutboundFile) -> Result<&'static str, String> {
+    let len = file.data.len();
+    let kind = match file.mime_type.as_str() {
+        "image/jpeg" | "image/png" if len <= MAX_IMAGE_BYTES => "image",
+        "video/mp4" if len <= MAX_AUDIO_VIDEO_BYTES => "video",
+        mime if mime.starts_with("audio/") && len <= MAX_AUDIO_VIDEO_BYTES => "audio",
+        _ => "document",
     };
+    if len > MAX_DOCUMENT_BYTES {
+        return Err(format!(
+            "file too large for WhatsApp ({len} bytes, maximum {MAX_DOCUMENT_BYTES})"
+        ));
+    }
+    Ok(kind)
+}
 
-    let resp = client
-        .post(format!("{GRAPH_API_BASE}/{phone_number_id}/messages"))
-        .bearer_auth(token)
-        .json(&receipt)
-        .send()
+/// Add a caption and filename to a media object (`{"id": ..}` or
+/// `{"link": ..}`). Audio messages cannot carry a caption; only documents
+/// keep their filename.
+fn media_object(
+    kind: &str,
+    mut media: serde_json::Value,
+    caption: Option<&str>,
+    filename: Option<&str>,
+) -> serde_json::Value {
+    if kind != "audio"
+        && let Some(caption) = caption.filter(|c| !c.is_empty())
+    {
+        media["caption"] = caption.into();
+    }
+    if kind == "document"
+        && let Some(filename) = filename
+    {
+        media["filename"] = filename.into();
+    }
+    media
+}
+
+/// Build the `messages` payload for a media object.
+fn media_message(to: &str, kind: &str, media: serde_json::Value) -> serde_json::Value {
+    let mut msg = serde_json::json!({
+        "messaging_product": "whatsapp",
+        "recipient_type": "individual",
+        "to": to,
+        "type": kind,
+    });
+    msg[kind] = media;
+    msg
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).collect()
+}
+
+fn interactive_message(to: &str, interactive: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "messaging_product": "whatsapp",
+        "recipient_type": "individual",
+        "to": to,
+        "type": "interactive",
+        "interactive": interactive,
+    })
+}
+
+/// Build a reply-button message. Titles longer than WhatsApp allows are
+/// shortened; more than three buttons is an error.
+fn reply_buttons_message(
+    to: &str,
+    body: &str,
+    buttons: &[ReplyButton],
+) -> Result<serde_json::Value, String> {
+    if buttons.is_empty() || buttons.len() > MAX_REPLY_BUTTONS {
+        return Err(format!(
+            "WhatsApp reply buttons need 1 to {MAX_REPLY_BUTTONS} buttons, got {}",
+            buttons.len()
+        ));
+    }
+    let buttons: Vec<_> = buttons
+        .iter()
+        .map(|b| {
+            serde_json::json!({
+                "type": "reply",
+                "reply": { "id": b.id, "title": truncate(&b.title, MAX_BUTTON_TITLE_CHARS) },
+            })
+        })
+        .collect();
+    Ok(interactive_message(
+        to,
+        serde_json::json!({
+            "type": "button",
+            "body": { "text": body },
+            "action": { "buttons": buttons },
+        }),
+    ))
+}
+
+/// Build a list message. Row text is shortened to WhatsApp's limits; more
+/// than ten rows in total is an error.
+fn list_message(
+    to: &str,
+    body: &str,
+    button: &str,
+    sections: &[ListSection],
+) -> Result<serde_json::Value, String> {
+    let rows: usize = sections.iter().map(|s| s.rows.len()).sum();
+    if rows == 0 || rows > MAX_LIST_ROWS {
+        return Err(format!(
+            "WhatsApp lists need 1 to {MAX_LIST_ROWS} rows, got {rows}"
+        ));
+    }
+    let sections: Vec<_> = sections
+        .iter()
+        .map(|section| {
+            let rows: Vec<_> = section
+                .rows
+                .iter()
+                .map(|row| ListRow {
+                    id: row.id.clone(),
+                    title: truncate(&row.title, MAX_ROW_TITLE_CHARS),
+                    description: row
+                        .description
+                        .as_deref()
+                        .map(|d| truncate(d, MAX_ROW_DESCRIPTION_CHARS)),
+                })
+                .collect();
+            serde_json::json!({ "title": truncate(&section.title, MAX_ROW_TITLE_CHARS), "rows": rows })
+        })
+        .collect();
+    Ok(interactive_message(
+        to,
+        serde_json::json!({
+            "type": "list",
+            "body": { "text": body },
+            "action": {
+                "button": truncate(button, MAX_BUTTON_TITLE_CHARS),
+                "sections": sections,
+            },
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::{Path, State};
+    use axum::routing::{get, post};
+    use axum::{Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    fn file(mime: &str, len: usize) -> OutboundFile {
+        OutboundFile {
+            data: vec![0; len],
+            filename: "report.pdf".into(),
+            mime_type: mime.into(),
+            caption: Some("Q3 numbers".into()),
+        }
+    }
+
+    #[test]
+    fn media_type_follows_mime_and_size_limits() {
+        assert_eq!(media_message_type(&file("image/png", 10)).unwrap(), "image");
+        assert_eq!(
+            media_message_type(&file("image/png", MAX_IMAGE_BYTES + 1)).unwrap(),
+            "document"
+        );
+        assert_eq!(
+            media_message_type(&file("image/webp", 10)).unwrap(),
+            "document"
+        );
+        assert_eq!(media_message_type(&file("video/mp4", 10)).unwrap(), "video");
+        assert_eq!(
+            media_message_type(&file("audio/mpeg", 10)).unwrap(),
+            "audio"
+        );
+        assert!(media_message_type(&file("application/zip", MAX_DOCUMENT_BYTES + 1)).is_err());
+    }
+
+    #[test]
+    fn media_object_sets_caption_and_filename() {
+        let doc = media_message(
+            "15551234",
+            "document",
+            media_object(
+                "document",
+                serde_json::json!({ "id": "m1" }),
+                Some("Q3 numbers"),
+                Some("report.pdf"),
+            ),
+        );
+        assert_eq!(doc["type"], "document");
+        assert_eq!(doc["document"]["id"], "m1");
+        assert_eq!(doc["document"]["filename"], "report.pdf");
+        assert_eq!(doc["document"]["caption"], "Q3 numbers");
+
+        let audio = media_object(
+            "audio",
+            serde_json::json!({ "link": "https://example.com/a.ogg" }),
+            Some("ignored"),
+            Some("a.ogg"),
+        );
+        assert!(audio.get("caption").is_none());
+        assert!(audio.get("filename").is_none());
+    }
+
+    #[test]
+    fn reply_buttons_are_limited_and_truncated() {
+        let button = |id: &str| ReplyButton {
+            id: id.into(),
+            title: "A very long button title indeed".into(),
+        };
+        let msg = reply_buttons_message("1555", "Pick one", &[button("a"), button("b")]).unwrap();
+        assert_eq!(msg["type"], "interactive");
+        assert_eq!(msg["interactive"]["type"], "button");
+        let buttons = msg["interactive"]["action"]["buttons"].as_array().unwrap();
+        assert_eq!(buttons.len(), 2);
+        assert_eq!(buttons[0]["reply"]["id"], "a");
+        assert_eq!(
+            buttons[0]["reply"]["title"]
+                .as_str()
+                .unwrap()
+                .chars()
+                .count(),
+            MAX_BUTTON_TITLE_CHARS
+        );
+
+        assert!(reply_buttons_message("1555", "Pick", &[]).is_err());
+        let four: Vec<_> = ["a", "b", "c", "d"].iter().map(|id| button(id)).collect();
+        assert!(reply_buttons_message("1555", "Pick", &four).is_err());
+    }
+
+    #[test]
+    fn list_message_counts_rows_across_sections() {
+        let section = |n: usize| ListSection {
+            title: "Plans".into(),
+            rows: (0..n)
+                .map(|i| ListRow {
+                    id: format!("row-{i}"),
+                    title: format!("Row {i}"),
+                    description: None,
+                })
+                .collect(),
+        };
+        let msg =
+            list_message("1555", "Choose a plan", "Plans", &[section(2), section(3)]).unwrap();
+        assert_eq!(msg["interactive"]["type"], "list");
+        assert_eq!(msg["interactive"]["action"]["button"], "Plans");
+        let sections = msg["interactive"]["action"]["sections"].as_array().unwrap();
+        assert_eq!(sections[1]["rows"][2]["id"], "row-2");
+        assert!(sections[0]["rows"][0].get("description").is_none());
+
+        assert!(list_message("1555", "Choose", "Plans", &[section(6), section(5)]).is_err());
+    }
+
+    type Sent = Arc<Mutex<Vec<serde_json::Value>>>;
+
+    /// A Graph API stand-in that records every `messages` payload and
+    /// answers media uploads with `uploaded-1`.
+    async fn mock_graph() -> (GraphApi, Sent) {
+        let sent: Sent = Arc::default();
+        let app = Router::new()
+            .route(
+                "/{phone}/messages",
+                post(
+                    |State(sent): State<Sent>,
+                     Path(_phone): Path<String>,
+                     Json(body): Json<serde_json::Value>| async move {
+                        sent.lock().unwrap().push(body);
+                        Json(serde_json::json!({ "messages": [{ "id": "wamid.out" }] }))
+                    },
+                ),
+            )
+            .route(
+                "/{phone}/media",
+                post(|| async { Json(serde_json::json!({ "id": "uploaded-1" })) }),
+            )
+            .route(
+                "/{media_id}",
+                get(|Path(id): Path<String>| async move {
+                    Json(serde_json::json!({ "url": format!("https://cdn.example/{id}") }))
+                }),
+            )
+            .with_state(Arc::clone(&sent));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        let api = GraphApi::new(Client::new(), "test-token", "106540352242922").with_base(&base);
+        (api, sent)
+    }
+
+    #[tokio::test]
+    async fn sends_media_and_interactive_messages_to_graph_api() {
+        let (api, sent) = mock_graph().await;
+
+        api.send_media_message("1555", file("application/pdf", 4))
+            .await
+            .unwrap();
+        api.send_media_link(
+            "1555",
+            "image",
+            "https://example.com/cat.png",
+            Some("Cat"),
+            None,
+        )
         .await
-        .map_err(|e| format!("WhatsApp mark_as_read failed: {e}"))?;
+        .unwrap();
+        api.send_reply_buttons(
+            "1555",
+            "Confirm?",
+            &[ReplyButton {
+                id: "yes".into(),
+                title: "Yes".into(),
+            }],
+        )
+        .await
+        .unwrap();
 
-    if !resp.status().is_success() {
-        let body = resp.text().await.unwrap_or_default();
-        warn!("WhatsApp mark_as_read error: {body}");
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0]["document"]["id"], "uploaded-1");
+        assert_eq!(sent[1]["image"]["link"], "https://example.com/cat.png");
+        assert_eq!(sent[1]["image"]["caption"], "Cat");
+        assert_eq!(
+            sent[2]["interactive"]["action"]["buttons"][0]["reply"]["id"],
+            "yes"
+        );
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn resolves_media_ids_through_graph_api() {
+        let (api, _sent) = mock_graph().await;
+        assert_eq!(
+            api.media_url("1013859600285441").await.unwrap(),
+            "https://cdn.example/1013859600285441"
+        );
+    }
 }
@@ -1,5 +1,7 @@
 pub mod api;
 #[cfg(feature = "whatsapp-web")]
+pub mod sidecar;
+#[cfg(feature = "whatsapp-web")]
 pub mod web;
 pub mod webhook;
 
@@ -12,12 +14,18 @@ use reqwest::Client;
 use tokio::sync::mpsc;
 use tracing::info;
 
-use crate::traits::{ChannelLifecycle, ChannelSender, ChannelStatus};
-use opencrust_common::{Message, MessageContent, Result};
+use crate::attachments::PendingAttachments;
+use crate::traits::{ChannelLifecycle, ChannelSender, ChannelStatus, VoiceReplier};
+use api::{GraphApi, ListSection, ReplyButton};
+use opencrust_common::{Message, MessageContent, OutboundFile, Result};
+use opencrust_media::SpeechToText;
+use webhook::RecentIds;
 
-/// Callback invoked when the bot receives a text message from WhatsApp.
+/// Callback invoked when the bot receives a message from WhatsApp.
 ///
-/// Arguments: `(from_number, user_name, text, delta_tx)`.
+/// Arguments: `(from_number, user_name, text, attachments, delta_tx)`.
+/// `attachments` holds the media the user sent, read with
+/// [`PendingAttachments::resolve`] once the sender is admitted.
 /// `delta_tx` is always `None` for WhatsApp (no streaming support).
 /// Return `Err("__blocked__")` to silently drop the message (unauthorized user).
 pub type WhatsAppOnMessageFn = Arc<
@@ -25,6 +33,7 @@ pub type WhatsAppOnMessageFn = Arc<
             String,
             String,
             String,
+            PendingAttachments,
             Option<mpsc::Sender<String>>,
         ) -> Pin<Box<dyn Future<Output = std::result::Result<String, String>> + Send>>
         + Send
@@ -32,15 +41,20 @@ pub type WhatsAppOnMessageFn = Arc<
 >;
 
 pub struct WhatsAppChannel {
-    client: Client,
-    access_token: String,
-    phone_number_id: String,
+    api: GraphApi,
     verify_token: String,
     display: String,
     status: ChannelStatus,
     on_message: WhatsAppOnMessageFn,
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+    voice_replier: Option<Arc<dyn VoiceReplier>>,
+    app_secret: Option<String>,
+    seen_messages: std::sync::Mutex<RecentIds>,
 }
 
+/// How many recent message IDs each channel remembers to drop replays.
+const SEEN_MESSAGE_CAPACITY: usize = 10_000;
+
 impl WhatsAppChannel {
     pub fn new(
         access_token: String,
@@ -49,24 +63,78 @@ impl WhatsAppChannel {
         on_message: WhatsAppOnMessageFn,
     ) -> Self {
         Self {
-            client: Client::new(),
-            access_token,
-            phone_number_id,
+            api: GraphApi::new(Client::new(), &access_token, &phone_number_id),
             verify_token,
             display: "WhatsApp".to_string(),
             status: ChannelStatus::Disconnected,
             on_message,
+            speech_to_text: None,
+            voice_replier: None,
+            app_secret: None,
+            seen_messages: std::sync::Mutex::new(RecentIds::new(SEEN_MESSAGE_CAPACITY)),
         }
     }
 
+    /// Talk to a different Graph API base URL instead of Meta's, e.g. a
+    /// local mock server in tests.
+    pub fn with_api_base(mut self, base: &str) -> Self {
+        self.api = self.api.with_base(base);
+        self
+    }
+
+    /// Meta app secret used to verify webhook signatures. Without one, all
+    /// webhook deliveries are rejected.
+    pub fn with_app_secret(mut self, app_secret: Option<String>) -> Self {
+        self.app_secret = app_secret.filter(|s| !s.is_empty());
+        self
+    }
+
+    /// App secret for webhook signature verification, if configured.
+    pub fn app_secret(&self) -> Option<&str> {
+        self.app_secret.as_deref()
+    }
+
+    /// Record a webhook message ID. Returns false for IDs already handled,
+    /// so retried or replayed deliveries are ignored.
+    pub fn first_delivery(&self, message_id: &str) -> bool {
+        self.seen_messages.lock().unwrap().insert(message_id)
+    }
+
+    /// Transcribe incoming voice notes with this backend.
+    pub fn with_speech_to_text(mut self, stt: Option<Arc<dyn SpeechToText>>) -> Self {
+        self.speech_to_text = stt;
+        self
+    }
+
+    /// Speech-to-text backend for voice notes, if configured.
+    pub fn speech_to_text(&self) -> Option<Arc<dyn SpeechToText>> {
+        self.speech_to_text.clone()
+    }
+
+    /// Follow text replies with a voice message when the replier provides one.
+    pub fn with_voice_replier(mut self, replier: Option<Arc<dyn VoiceReplier>>) -> Self {
+        self.voice_replier = replier;
+        self
+    }
+
+    /// Voice reply policy and synthesizer, if configured.
+    pub fn voice_replier(&self) -> Option<&dyn VoiceReplier> {
+        self.voice_replier.as_deref()
+    }
+
+    /// Cloud API endpoints for this phone number.
+    pub fn api(&self) -> &GraphApi {
+        &self.api
+    }
+
     /// Access token for the WhatsApp Cloud API.
     pub fn access_token(&self) -> &str {
-        &self.access_token
+        self.api.token()
     }
 
     /// Phone number ID for this WhatsApp Business account.
     pub fn phone_number_id(&self) -> &str {
-        &self.phone_number_id
+        self.api.phone_number_id()
     }
 
     /// Verify token used for webhook verification.
@@ -76,7 +144,7 @@ impl WhatsAppChannel {
 
     /// HTTP client shared across requests.
     pub fn client(&self) -> &Client {
-        &self.client
+        self.api.client()
     }
 
     /// Process an incoming message from the webhook. Returns the response text.
@@ -85,11 +153,13 @@ impl WhatsAppChannel {
         from: &str,
         user_name: &str,
         text: &str,
+        attachments: PendingAttachments,
     ) -> std::result::Result<String, String> {
         (self.on_message)(
             from.to_string(),
             user_name.to_string(),
             text.to_string(),
+            attachments,
             None, // No streaming for WhatsApp
         )
         .await
@@ -98,9 +168,7 @@ impl WhatsAppChannel {
 
 /// Lightweight send-only handle for WhatsApp Business API.
 pub struct WhatsAppSender {
-    client: Client,
-    access_token: String,
-    phone_number_id: String,
+    api: GraphApi,
 }
 
 #[async_trait]
@@ -110,13 +178,19 @@ impl ChannelSender for WhatsAppSender {
     }
 
     async fn send_message(&self, message: &Message) -> Result<()> {
-        whatsapp_send_message(
-            &self.client,
-            &self.access_token,
-            &self.phone_number_id,
-            message,
-        )
-        .await
+        whatsapp_send_message(&self.api, message).await
+    }
+
+    async fn send_voice(&self, metadata: &serde_json::Value, audio: Vec<u8>) -> Result<()> {
+        whatsapp_send_voice(&self.api, metadata, audio).await
+    }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        whatsapp_send_file(&self.api, metadata, file).await
     }
 }
 
@@ -128,9 +202,7 @@ impl ChannelLifecycle for WhatsAppChannel {
 
     fn create_sender(&self) -> Box<dyn ChannelSender> {
         Box::new(WhatsAppSender {
-            client: Client::new(),
-            access_token: self.access_token.clone(),
-            phone_number_id: self.phone_number_id.clone(),
+            api: self.api.clone(),
         })
     }
 
@@ -159,45 +231,123 @@ impl ChannelSender for WhatsAppChannel {
     }
 
     async fn send_message(&self, message: &Message) -> Result<()> {
-        whatsapp_send_message(
-            &self.client,
-            &self.access_token,
-            &self.phone_number_id,
-            message,
-        )
-        .await
+        whatsapp_send_message(&self.api, message).await
+    }
+
+    async fn send_voice(&self, metadata: &serde_json::Value, audio: Vec<u8>) -> Result<()> {
+        whatsapp_send_voice(&self.api, metadata, audio).await
+    }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        whatsapp_send_file(&self.api, metadata, file).await
     }
 }
 
-/// Shared send logic used by both `WhatsAppChannel` and `WhatsAppSender`.
-async fn whatsapp_send_message(
-    client: &Client,
-    access_token: &str,
-    phone_number_id: &str,
-    message: &Message,
-) -> Result<()> {
-    let to = message
-        .metadata
+fn whatsapp_recipient(metadata: &serde_json::Value) -> Result<&str> {
+    metadata
         .get("whatsapp_from")
         .and_then(|v| v.as_str())
-        .ok_or_else(|| {
-            opencrust_common::Error::Channel("missing whatsapp_from in metadata".into())
-        })?;
+        .ok_or_else(|| opencrust_common::Error::Channel("missing whatsapp_from in metadata".into()))
+}
+
+fn send_failed(e: String) -> opencrust_common::Error {
+    opencrust_common::Error::Channel(format!("whatsapp send failed: {e}"))
+}
+
+async fn whatsapp_send_voice(
+    api: &GraphApi,
+    metadata: &serde_json::Value,
+    audio: Vec<u8>,
+) -> Result<()> {
+    let to = whatsapp_recipient(metadata)?;
+    api.send_voice_message(to, audio).await.map_err(send_failed)
+}
+
+async fn whatsapp_send_file(
+    api: &GraphApi,
+    metadata: &serde_json::Value,
+    file: OutboundFile,
+) -> Result<()> {
+    let to = whatsapp_recipient(metadata)?;
+    api.send_media_message(to, file).await.map_err(send_failed)
+}
+
+/// Interactive options for a text message, from its metadata: reply buttons
+/// under `whatsapp_buttons`, or a list under `whatsapp_list` as
+/// `{"button": .., "sections": [..]}`.
+enum Interactive {
+    Buttons(Vec<ReplyButton>),
+    List {
+        button: String,
+        sections: Vec<ListSection>,
+    },
+}
+
+fn interactive_options(metadata: &serde_json::Value) -> Result<Option<Interactive>> {
+    let invalid = |e: serde_json::Error| {
+        opencrust_common::Error::Channel(format!("invalid whatsapp interactive metadata: {e}"))
+    };
+    if let Some(buttons) = metadata.get("whatsapp_buttons") {
+        let buttons = serde_json::from_value(buttons.clone()).map_err(invalid)?;
+        return Ok(Some(Interactive::Buttons(buttons)));
+    }
+    if let Some(list) = metadata.get("whatsapp_list") {
+        let button = list
+            .get("button")
+            .and_then(|v| v.as_str())
+            .unwrap_or("Options")
+            .to_string();
+        let sections = serde_json::from_value(
+            list.get("sections")
+                .cloned()
+                .unwrap_or(serde_json::Value::Null),
+        )
+        .map_err(invalid)?;
+        return Ok(Some(Interactive::List { button, sections }));
+    }
+    Ok(None)
+}
 
-    let text = match &message.content {
-        MessageContent::Text(t) => t.clone(),
+/// Shared send logic used by both `WhatsAppChannel` and `WhatsAppSender`.
+/// Media content is sent by link, so its URL must be publicly reachable.
+async fn whatsapp_send_message(api: &GraphApi, message: &Message) -> Result<()> {
+    let to = whatsapp_recipient(&message.metadata)?;
+
+    let sent = match &message.content {
+        MessageContent::Text(text) => match interactive_options(&message.metadata)? {
+            Some(Interactive::Buttons(buttons)) => api.send_reply_buttons(to, text, &buttons).await,
+            Some(Interactive::List { button, sections }) => {
+                api.send_list(to, text, &button, &sections).await
+            }
+            None => api.send_text_message(to, text).await,
+        },
+        MessageContent::Image { url, caption } => {
+            api.send_media_link(to, "image", url, caption.as_deref(), None)
+                .await
+        }
+        MessageContent::Video { url, caption } => {
+            api.send_media_link(to, "video", url, caption.as_deref(), None)
+                .await
+        }
+        MessageContent::Audio { url, .. } => {
+            api.send_media_link(to, "audio", url, None, None).await
+        }
+        MessageContent::File { url, filename } => {
+            api.send_media_link(to, "document", url, None, Some(filename))
+                .await
+        }
         _ => {
             return Err(opencrust_common::Error::Channel(
-                "only text messages are supported for whatsapp send".into(),
+                "unsupported message content for whatsapp send".into(),
             ));
         }
     };
 
-    api::send_text_message(client, access_token, phone_number_id, to, &text)
-        .await
-        .map_err(|e| opencrust_common::Error::Channel(format!("whatsapp send failed: {e}")))?;
-
-    Ok(())
+    sent.map_err(send_failed)
 }
 
 #[cfg(test)]
@@ -207,7 +357,9 @@ mod tests {
     #[test]
     fn channel_type_is_whatsapp() {
         let on_msg: WhatsAppOnMessageFn =
-            Arc::new(|_from, _user, _text, _delta_tx| Box::pin(async { Ok("test".to_string()) }));
+            Arc::new(|_from, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            });
         let channel = WhatsAppChannel::new(
             "fake-token".to_string(),
             "123456".to_string(),
@@ -0,0 +1,352 @@
+//! Line-delimited JSON protocol between `WhatsAppWebChannel` and the Baileys
+//! sidecar (`sidecar/whatsapp-web/index.mjs`). The sidecar writes events to
+//! stdout and reads commands from stdin, one JSON object per line. Media
+//! travels as file paths, never inline.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Protocol version the sidecar must announce in its `hello` event.
+/// Version 1 sidecars sent no `hello` and only carried text.
+pub const SIDECAR_PROTOCOL_VERSION: u32 = 2;
+
+/// Events written by the sidecar.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SidecarEvent {
+    /// First line after start-up.
+    Hello {
+        protocol: u32,
+        #[serde(default)]
+        version: String,
+    },
+    /// A new pairing QR code is waiting to be scanned.
+    Qr {
+        data: String,
+    },
+    /// Logged in and connected. `jid` is the bot's own account.
+    Ready {
+        #[serde(default)]
+        jid: Option<String>,
+    },
+    Message(Box<WebMessage>),
+    /// Someone reacted to a message; an empty `emoji` removes the reaction.
+    Reaction {
+        chat: String,
+        sender: String,
+        #[serde(default)]
+        name: String,
+        /// The message reacted to.
+        id: String,
+        emoji: String,
+    },
+    /// Delivery state of a message the bot sent.
+    Receipt {
+        chat: String,
+        id: String,
+        status: ReceiptStatus,
+    },
+    /// A command failed.
+    Error {
+        message: String,
+    },
+    Disconnected {
+        #[serde(default)]
+        reason: String,
+    },
+    Pong,
+}
+
+/// An incoming chat message.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WebMessage {
+    pub id: String,
+    /// Chat JID: the sender's for direct chats, the group's otherwise.
+    pub chat: String,
+    /// JID of the person who wrote the message.
+    pub sender: String,
+    /// Push name of the sender.
+    #[serde(default)]
+    pub name: String,
+    /// Message text, or the caption of a media message.
+    #[serde(default)]
+    pub text: String,
+    /// Set for group chats.
+    #[serde(default)]
+    pub group: Option<GroupMetadata>,
+    /// JIDs mentioned with `@`.
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    /// Whether the bot's own account is among `mentions`.
+    #[serde(default)]
+    pub mentions_me: bool,
+    #[serde(default)]
+    pub quoted: Option<QuotedMessage>,
+    #[serde(default)]
+    pub media: Option<WebMedia>,
+}
+
+impl WebMessage {
+    pub fn is_group(&self) -> bool {
+        self.group.is_some()
+    }
+
+    /// Whether the message quotes one of the bot's own messages.
+    pub fn replies_to_me(&self) -> bool {
+        self.quoted.as_ref().is_some_and(|q| q.from_me)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GroupMetadata {
+    pub id: String,
+    #[serde(default)]
+    pub subject: String,
+    #[serde(default)]
+    pub participants: Vec<String>,
+}
+
+/// The message an incoming message replies to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotedMessage {
+    pub id: String,
+    #[serde(default)]
+    pub from_me: bool,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaKind {
+    Image,
+    Sticker,
+    Audio,
+    Video,
+    Document,
+}
+
+/// Media the sidecar downloaded into its media directory. `path` is unset
+/// when the download failed or was refused; `error` then says why.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebMedia {
+    pub kind: MediaKind,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// Recorded as a voice note rather than shared as an audio file.
+    #[serde(default)]
+    pub voice: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptStatus {
+    Delivered,
+    Read,
+    Played,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Presence {
+    Available,
+    Composing,
+    Paused,
+}
+
+/// Commands written to the sidecar.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SidecarCommand {
+    Send {
+        to: String,
+        text: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        mentions: Vec<String>,
+        /// Quote this recent message in the reply.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        quoted_id: Option<String>,
+    },
+    /// Send a file by local path or `http(s)` URL.
+    SendMedia {
+        to: String,
+        kind: MediaKind,
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        caption: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filename: Option<String>,
+        /// Send audio as a voice note.
+        #[serde(default)]
+        voice: bool,
+        /// Remove the local file once sent.
+        #[serde(default)]
+        delete_after: bool,
+    },
+    /// React to a message; an empty `emoji` removes the reaction.
+    React {
+        chat: String,
+        id: String,
+        emoji: String,
+        /// Author of the message in group chats.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<String>,
+        #[serde(default)]
+        from_me: bool,
+    },
+    /// Send read receipts.
+    Read {
+        chat: String,
+        ids: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sender: Option<String>,
+    },
+    Presence {
+        to: String,
+        state: Presence,
+    },
+    Ping,
+}
+
+impl SidecarCommand {
+    /// Serialize as one protocol line (without the trailing newline).
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Parse one stdout line. Returns `None` for blank lines, log noise and
+/// event types this version does not know.
+pub fn parse_event(line: &str) -> Option<SidecarEvent> {
+    serde_json::from_str(line.trim()).ok()
+}
+
+/// Delay before the first restart of a sidecar that exited.
+pub const INITIAL_RESTART_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound for the restart delay.
+pub const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+/// A sidecar that ran this long is considered healthy; the delay resets.
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+/// Delay before restarting a sidecar that ran for `ran_for`, given the
+/// delay used last time. Crash loops back off exponentially; a sidecar that
+/// ran stably restarts quickly.
+pub fn restart_delay(previous: Duration, ran_for: Duration) -> Duration {
+    if ran_for >= STABLE_RUN {
+        INITIAL_RESTART_DELAY
+    } else {
+        (previous * 2).clamp(INITIAL_RESTART_DELAY, MAX_RESTART_DELAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_group_message_with_media_and_quote() {
+        let line = r#"{"type":"message","id":"3EB0A1","chat":"120363041234567890@g.us","sender":"4915112345678@s.whatsapp.net","name":"Ana","text":"@4915700000000 what is this?","group":{"id":"120363041234567890@g.us","subject":"Plants","participants":["4915112345678@s.whatsapp.net","4915700000000@s.whatsapp.net"]},"mentions":["4915700000000@s.whatsapp.net"],"mentions_me":true,"quoted":{"id":"3EB0FF","from_me":true},"media":{"kind":"image","path":"/tmp/media/3EB0A1.jpg","mime_type":"image/jpeg"}}"#;
+        let Some(SidecarEvent::Message(msg)) = parse_event(line) else {
+            panic!("expected a message event");
+        };
+        assert!(msg.is_group());
+        assert!(msg.mentions_me);
+        assert!(msg.replies_to_me());
+        assert_eq!(msg.group.unwrap().subject, "Plants");
+        let media = msg.media.unwrap();
+        assert_eq!(media.kind, MediaKind::Image);
+        assert_eq!(media.path.as_deref(), Some("/tmp/media/3EB0A1.jpg"));
+        assert!(!media.voice);
+    }
+
+    #[test]
+    fn parses_v1_message_lines_without_new_fields() {
+        let event = parse_event(
+            r#"{"type":"message","id":"a","chat":"1@s.whatsapp.net","sender":"1@s.whatsapp.net","text":"hi"}"#,
+        );
+        let Some(SidecarEvent::Message(msg)) = event else {
+            panic!("expected a message event");
+        };
+        assert!(!msg.is_group());
+        assert!(msg.media.is_none());
+
+        assert_eq!(
+            parse_event(r#"{"type":"hello","protocol":2,"version":"0.2.0"}"#),
+            Some(SidecarEvent::Hello {
+                protocol: 2,
+                version: "0.2.0".into()
+            })
+        );
+        assert_eq!(parse_event("fatal: boom"), None);
+        assert_eq!(parse_event(r#"{"type":"something_new"}"#), None);
+    }
+
+    #[test]
+    fn commands_serialize_to_protocol_lines() {
+        let send = SidecarCommand::Send {
+            to: "1@s.whatsapp.net".into(),
+            text: "hi".into(),
+            mentions: Vec::new(),
+            quoted_id: None,
+        };
+        assert_eq!(
+            send.to_line(),
+            r#"{"type":"send","to":"1@s.whatsapp.net","text":"hi"}"#
+        );
+
+        let presence = SidecarCommand::Presence {
+            to: "1@s.whatsapp.net".into(),
+            state: Presence::Composing,
+        };
+        assert_eq!(
+            presence.to_line(),
+            r#"{"type":"presence","to":"1@s.whatsapp.net","state":"composing"}"#
+        );
+
+        let media: serde_json::Value = serde_json::from_str(
+            &SidecarCommand::SendMedia {
+                to: "1@s.whatsapp.net".into(),
+                kind: MediaKind::Audio,
+                url: "/tmp/reply.ogg".into(),
+                mime_type: Some("audio/ogg; codecs=opus".into()),
+                caption: None,
+                filename: None,
+                voice: true,
+                delete_after: true,
+            }
+            .to_line(),
+        )
+        .unwrap();
+        assert_eq!(media["type"], "send_media");
+        assert_eq!(media["kind"], "audio");
+        assert_eq!(media["voice"], true);
+        assert!(media.get("caption").is_none());
+    }
+
+    #[test]
+    fn restart_delay_backs_off_and_resets_after_stable_run() {
+        let quick = Duration::from_secs(2);
+        let mut delay = INITIAL_RESTART_DELAY;
+        delay = restart_delay(delay, quick);
+        assert_eq!(delay, Duration::from_secs(2));
+        delay = restart_delay(delay, quick);
+        assert_eq!(delay, Duration::from_secs(4));
+        for _ in 0..10 {
+            delay = restart_delay(delay, quick);
+        }
+        assert_eq!(delay, MAX_RESTART_DELAY);
+        assert_eq!(
+            restart_delay(delay, Duration::from_secs(600)),
+            INITIAL_RESTART_DELAY
+        );
+    }
+}
@@ -1,24 +1,123 @@
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::process::Stdio;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
-use tokio::sync::mpsc;
-use tracing::{info, warn};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
 
+use crate::attachments::PendingAttachments;
+use crate::group::{GroupMessage, GroupPolicy};
 use crate::traits::{ChannelLifecycle, ChannelSender, ChannelStatus};
-use opencrust_common::{Message, MessageContent, Result};
+use opencrust_common::{Message, MessageContent, OutboundFile, Result};
+use opencrust_media::SpeechToText;
 
-use super::WhatsAppOnMessageFn;
+use super::sidecar::{
+    self, INITIAL_RESTART_DELAY, MediaKind, Presence, SIDECAR_PROTOCOL_VERSION, SidecarCommand,
+    SidecarEvent, WebMedia, WebMessage,
+};
 
-/// Shared sender handle that gets populated when the sidecar process starts.
+/// Callback invoked when the bot receives a message through WhatsApp Web.
+///
+/// Arguments: `(chat_jid, sender_jid, user_name, text, attachments, delta_tx)`.
+/// `chat_jid` is where replies go: the sender for direct chats, the group
+/// otherwise. `attachments` holds the media the user sent, read with
+/// [`PendingAttachments::resolve`] once the sender is admitted. `delta_tx`
+/// is always `None` (no streaming support).
+/// Return `Err("__blocked__")` to silently drop the message (unauthorized user).
+pub type WhatsAppWebOnMessageFn = Arc<
+    dyn Fn(
+            String,
+            String,
+            String,
+            String,
+            PendingAttachments,
+            Option<mpsc::Sender<String>>,
+        ) -> Pin<Box<dyn Future<Output = std::result::Result<String, String>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Shared sender handle that gets populated while a sidecar process runs.
 type SharedStdinTx = Arc<tokio::sync::Mutex<Option<mpsc::Sender<String>>>>;
 
+/// Where the sidecar is in the QR pairing flow.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairingState {
+    /// The sidecar is starting and has not reported yet.
+    #[default]
+    Starting,
+    /// A QR code is shown in the terminal, waiting to be scanned.
+    AwaitingScan,
+    /// Linked to a phone and connected.
+    Paired,
+    /// The phone unlinked this session; a new QR code follows on restart.
+    LoggedOut,
+}
+
+/// Point-in-time view of a WhatsApp Web channel, as reported by `/api/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebStatusSnapshot {
+    pub status: ChannelStatus,
+    pub pairing: PairingState,
+    /// The linked account, once paired.
+    pub jid: Option<String>,
+    /// When the current QR code was issued. The code itself is only shown
+    /// in the terminal.
+    pub qr_issued_at: Option<DateTime<Utc>>,
+    pub sidecar_version: Option<String>,
+    pub protocol_version: Option<u32>,
+    /// How often the sidecar was restarted after exiting.
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+impl Default for WebStatusSnapshot {
+    fn default() -> Self {
+        Self {
+            status: ChannelStatus::Disconnected,
+            pairing: PairingState::default(),
+            jid: None,
+            qr_issued_at: None,
+            sidecar_version: None,
+            protocol_version: None,
+            restarts: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// Live status of a WhatsApp Web channel, updated by its supervisor task.
+/// Cheap to clone; all clones see the same state.
+#[derive(Debug, Clone, Default)]
+pub struct WhatsAppWebStatus {
+    inner: Arc<std::sync::Mutex<WebStatusSnapshot>>,
+}
+
+impl WhatsAppWebStatus {
+    pub fn snapshot(&self) -> WebStatusSnapshot {
+        self.inner.lock().unwrap().clone()
+    }
+
+    fn update(&self, f: impl FnOnce(&mut WebStatusSnapshot)) {
+        f(&mut self.inner.lock().unwrap());
+    }
+}
+
 /// Lightweight send-only handle for WhatsApp Web.
 pub struct WhatsAppWebSender {
     shared_stdin_tx: SharedStdinTx,
+    media_dir: PathBuf,
 }
 
 #[async_trait]
@@ -28,44 +127,76 @@ impl ChannelSender for WhatsAppWebSender {
     }
 
     async fn send_message(&self, message: &Message) -> Result<()> {
-        let guard = self.shared_stdin_tx.lock().await;
-        let tx = guard.as_ref().ok_or_else(|| {
-            opencrust_common::Error::Channel("whatsapp-web sidecar not connected".into())
-        })?;
-        whatsapp_web_send_message(tx, message).await
+        whatsapp_web_send_message(&self.shared_stdin_tx, message).await
+    }
+
+    async fn send_voice(&self, metadata: &serde_json::Value, audio: Vec<u8>) -> Result<()> {
+        whatsapp_web_send_voice(&self.shared_stdin_tx, &self.media_dir, metadata, audio).await
+    }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        whatsapp_web_send_file(&self.shared_stdin_tx, &self.media_dir, metadata, file).await
     }
 }
 
 /// Sidecar-driven WhatsApp Web channel using Baileys (QR code pairing).
+/// The sidecar is restarted with backoff whenever it exits.
 pub struct WhatsAppWebChannel {
-    child: Option<Child>,
-    stdin_tx: Option<mpsc::Sender<String>>,
     /// Shared sender handle exposed via `create_sender()`.
     shared_stdin_tx: SharedStdinTx,
-    status: ChannelStatus,
+    status: WhatsAppWebStatus,
     display: String,
-    on_message: WhatsAppOnMessageFn,
+    on_message: WhatsAppWebOnMessageFn,
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+    group_policy: Arc<GroupPolicy>,
     auth_dir: PathBuf,
     sidecar_dir: PathBuf,
+    media_dir: PathBuf,
+    shutdown_tx: Option<watch::Sender<bool>>,
+    supervisor: Option<JoinHandle<()>>,
 }
 
 impl WhatsAppWebChannel {
-    pub fn new(on_message: WhatsAppOnMessageFn) -> Self {
+    pub fn new(on_message: WhatsAppWebOnMessageFn) -> Self {
         let home = dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
         let config_dir = home.join(".opencrust");
 
         Self {
-            child: None,
-            stdin_tx: None,
             shared_stdin_tx: Arc::new(tokio::sync::Mutex::new(None)),
-            status: ChannelStatus::Disconnected,
+            status: WhatsAppWebStatus::default(),
             display: "WhatsApp Web".to_string(),
             on_message,
+            speech_to_text: None,
+            group_policy: Arc::default(),
             auth_dir: config_dir.join("whatsapp-web-auth"),
             sidecar_dir: config_dir.join("sidecar").join("whatsapp-web"),
+            media_dir: config_dir.join("whatsapp-web-media"),
+            shutdown_tx: None,
+            supervisor: None,
         }
     }
 
+    /// Transcribe incoming voice notes with this backend.
+    pub fn with_speech_to_text(mut self, stt: Option<Arc<dyn SpeechToText>>) -> Self {
+        self.speech_to_text = stt;
+        self
+    }
+
+    /// Decide which group messages are answered with this policy.
+    pub fn with_group_policy(mut self, policy: Arc<GroupPolicy>) -> Self {
+        self.group_policy = policy;
+        self
+    }
+
+    /// Live pairing and process status, for reporting.
+    pub fn status_handle(&self) -> WhatsAppWebStatus {
+        self.status.clone()
+    }
+
     // Embedded sidecar files - written to disk on first connect if not found elsewhere.
     const EMBEDDED_INDEX_MJS: &'static str =
         include_str!("../../../../sidecar/whatsapp-web/index.mjs");
@@ -164,61 +295,190 @@ impl ChannelLifecycle for WhatsAppWebChannel {
     fn create_sender(&self) -> Box<dyn ChannelSender> {
         Box::new(WhatsAppWebSender {
             shared_stdin_tx: Arc::clone(&self.shared_stdin_tx),
+            media_dir: self.media_dir.clone(),
         })
     }
 
     async fn connect(&mut self) -> Result<()> {
-        self.status = ChannelStatus::Connecting;
+        self.status.update(|s| s.status = ChannelStatus::Connecting);
 
         let sidecar_dir = self.resolve_sidecar_dir()?;
         self.ensure_npm_install(&sidecar_dir).await?;
 
-        // Create auth directory if needed
-        if !self.auth_dir.exists() {
-            std::fs::create_dir_all(&self.auth_dir).map_err(|e| {
-                opencrust_common::Error::Channel(format!(
-                    "failed to create auth dir {}: {e}",
-                    self.auth_dir.display()
-                ))
+        for dir in [&self.auth_dir, &self.media_dir] {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                opencrust_common::Error::Channel(format!("failed to create {}: {e}", dir.display()))
             })?;
         }
 
-        let mut child = Command::new("node")
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        self.shutdown_tx = Some(shutdown_tx);
+
+        let supervisor = Supervisor {
+            sidecar_dir,
+            auth_dir: self.auth_dir.clone(),
+            media_dir: self.media_dir.clone(),
+            shared_stdin_tx: Arc::clone(&self.shared_stdin_tx),
+            ctx: Arc::new(WebContext {
+                on_message: Arc::clone(&self.on_message),
+                speech_to_text: self.speech_to_text.clone(),
+                group_policy: Arc::clone(&self.group_policy),
+                status: self.status.clone(),
+            }),
+        };
+        self.supervisor = Some(tokio::spawn(supervisor.run(shutdown_rx)));
+
+        info!("whatsapp-web channel started");
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<()> {
+        if let Some(tx) = self.shutdown_tx.take() {
+            let _ = tx.send(true);
+        }
+        if let Some(supervisor) = self.supervisor.take()
+            && tokio::time::timeout(Duration::from_secs(10), supervisor)
+                .await
+                .is_err()
+        {
+            warn!("whatsapp-web: supervisor did not stop in time");
+        }
+
+        self.status
+            .update(|s| s.status = ChannelStatus::Disconnected);
+        info!("whatsapp-web channel disconnected");
+        Ok(())
+    }
+
+    fn status(&self) -> ChannelStatus {
+        self.status.snapshot().status
+    }
+}
+
+#[async_trait]
+impl ChannelSender for WhatsAppWebChannel {
+    fn channel_type(&self) -> &str {
+        "whatsapp-web"
+    }
+
+    async fn send_message(&self, message: &Message) -> Result<()> {
+        whatsapp_web_send_message(&self.shared_stdin_tx, message).await
+    }
+
+    async fn send_voice(&self, metadata: &serde_json::Value, audio: Vec<u8>) -> Result<()> {
+        whatsapp_web_send_voice(&self.shared_stdin_tx, &self.media_dir, metadata, audio).await
+    }
+
+    async fn send_attachment(
+        &self,
+        metadata: &serde_json::Value,
+        file: OutboundFile,
+    ) -> Result<()> {
+        whatsapp_web_send_file(&self.shared_stdin_tx, &self.media_dir, metadata, file).await
+    }
+}
+
+/// What message handling needs, shared across sidecar restarts.
+struct WebContext {
+    on_message: WhatsAppWebOnMessageFn,
+    speech_to_text: Option<Arc<dyn SpeechToText>>,
+    group_policy: Arc<GroupPolicy>,
+    status: WhatsAppWebStatus,
+}
+
+/// How one sidecar run ended.
+enum RunOutcome {
+    /// The channel is shutting down.
+    Shutdown,
+    /// The process exited or closed stdout; restart it.
+    Exited(String),
+    /// Restarting would not help (e.g. a protocol version mismatch).
+    Fatal(String),
+}
+
+/// Starts the sidecar and restarts it with backoff until shutdown.
+struct Supervisor {
+    sidecar_dir: PathBuf,
+    auth_dir: PathBuf,
+    media_dir: PathBuf,
+    shared_stdin_tx: SharedStdinTx,
+    ctx: Arc<WebContext>,
+}
+
+impl Supervisor {
+    async fn run(self, mut shutdown_rx: watch::Receiver<bool>) {
+        let status = &self.ctx.status;
+        let mut delay = INITIAL_RESTART_DELAY;
+        loop {
+            let started = Instant::now();
+            let outcome = self.run_once(&mut shutdown_rx).await;
+            *self.shared_stdin_tx.lock().await = None;
+
+            let reason = match outcome {
+                RunOutcome::Shutdown => return,
+                RunOutcome::Fatal(e) => {
+                    warn!("whatsapp-web: {e}");
+                    status.update(|s| {
+                        s.status = ChannelStatus::Error(e.clone());
+                        s.last_error = Some(e);
+                    });
+                    return;
+                }
+                RunOutcome::Exited(reason) => reason,
+            };
+            if *shutdown_rx.borrow() {
+                return;
+            }
+
+            delay = sidecar::restart_delay(delay, started.elapsed());
+            warn!("whatsapp-web: sidecar exited ({reason}), restarting in {delay:?}");
+            status.update(|s| {
+                s.status = ChannelStatus::Reconnecting;
+                s.restarts += 1;
+                s.last_error = Some(reason);
+            });
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = shutdown_rx.changed() => return,
+            }
+        }
+    }
+
+    fn spawn(&self) -> std::result::Result<Child, String> {
+        Command::new("node")
             .arg("index.mjs")
-            .current_dir(&sidecar_dir)
+            .current_dir(&self.sidecar_dir)
             .env("WHATSAPP_AUTH_DIR", &self.auth_dir)
+            .env("WHATSAPP_MEDIA_DIR", &self.media_dir)
+            .env(
+                "WHATSAPP_MAX_MEDIA_BYTES",
+                opencrust_media::document::MAX_DOCUMENT_BYTES.to_string(),
+            )
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .stderr(Stdio::inherit()) // QR art and errors go to terminal
+            .kill_on_drop(true)
             .spawn()
             .map_err(|e| {
-                opencrust_common::Error::Channel(format!(
-                    "failed to spawn whatsapp-web sidecar: {e} (is Node.js installed?)"
-                ))
-            })?;
-
-        let stdout = child
-            .stdout
-            .take()
-            .ok_or_else(|| opencrust_common::Error::Channel("no stdout from sidecar".into()))?;
+                format!("failed to spawn whatsapp-web sidecar: {e} (is Node.js installed?)")
+            })
+    }
 
-        let child_stdin = child
-            .stdin
-            .take()
-            .ok_or_else(|| opencrust_common::Error::Channel("no stdin to sidecar".into()))?;
+    /// Run the sidecar until it exits or the channel shuts down.
+    async fn run_once(&self, shutdown_rx: &mut watch::Receiver<bool>) -> RunOutcome {
+        let status = &self.ctx.status;
+        status.update(|s| s.pairing = PairingState::Starting);
 
-        self.child = Some(child);
+        let mut child = match self.spawn() {
+            Ok(child) => child,
+            Err(e) => return RunOutcome::Exited(e),
+        };
+        let (Some(stdout), Some(child_stdin)) = (child.stdout.take(), child.stdin.take()) else {
+            return RunOutcome::Exited("no stdio pipes to sidecar".into());
+        };
 
         // Writer task: forward commands from channel to sidecar stdin
         let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(64);
-        self.stdin_tx = Some(stdin_tx.clone());
-
-        // Populate shared sender handle for create_sender() consumers
-        {
-            let mut shared = self.shared_stdin_tx.lock().await;
-            *shared = Some(stdin_tx.clone());
-        }
-
         tokio::spawn(async move {
             let mut writer = child_stdin;
             while let Some(line) = stdin_rx.recv().await {
@@ -232,180 +492,559 @@ impl ChannelLifecycle for WhatsAppWebChannel {
             }
         });
 
-        // Reader task: parse events from sidecar stdout
-        let on_message = Arc::clone(&self.on_message);
-        let reply_tx = stdin_tx;
-
-        tokio::spawn(async move {
-            let reader = BufReader::new(stdout);
-            let mut lines = reader.lines();
+        let mut lines = BufReader::new(stdout).lines();
+        let mut greeted = false;
+        loop {
+            let line = tokio::select! {
+                line = lines.next_line() => line,
+                _ = shutdown_rx.changed() => {
+                    // Closing stdin tells the sidecar to exit.
+                    *self.shared_stdin_tx.lock().await = None;
+                    drop(stdin_tx);
+                    if tokio::time::timeout(Duration::from_secs(5), child.wait()).await.is_err() {
+                        warn!("whatsapp-web: sidecar did not exit in time, killing");
+                        child.kill().await.ok();
+                    }
+                    return RunOutcome::Shutdown;
+                }
+            };
+            let Ok(Some(line)) = line else {
+                break;
+            };
+            let Some(event) = sidecar::parse_event(&line) else {
+                continue;
+            };
 
-            while let Ok(Some(line)) = lines.next_line().await {
-                let event: serde_json::Value = match serde_json::from_str(&line) {
-                    Ok(v) => v,
-                    Err(_) => continue,
+            if !greeted {
+                // The first event must be a matching `hello`.
+                let protocol = match &event {
+                    SidecarEvent::Hello { protocol, .. } => *protocol,
+                    _ => 1,
                 };
-
-                let event_type = event.get("type").and_then(|v| v.as_str()).unwrap_or("");
-
-                match event_type {
-                    "qr" => {
-                        info!("whatsapp-web: QR code generated, waiting for scan...");
-                    }
-                    "ready" => {
-                        info!("whatsapp-web: connected and ready");
-                    }
-                    "message" => {
-                        let from = event
-                            .get("from")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("")
-                            .to_string();
-                        let name = event
-                            .get("name")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("")
-                            .to_string();
-                        let text = event
-                            .get("text")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("")
-                            .to_string();
-
-                        if from.is_empty() || text.is_empty() {
-                            continue;
-                        }
-
-                        let reply_tx = reply_tx.clone();
-                        let on_message = Arc::clone(&on_message);
-
-                        tokio::spawn(async move {
-                            match (on_message)(from.clone(), name, text, None).await {
-                                Ok(response) => {
-                                    let cmd = serde_json::json!({
-                                        "type": "send",
-                                        "to": from,
-                                        "text": response,
-                                    });
-                                    let _ = reply_tx
-                                        .send(serde_json::to_string(&cmd).unwrap_or_default())
-                                        .await;
-                                }
-                                Err(e) => {
-                                    if e != "__blocked__" {
-                                        warn!("whatsapp-web: message handler error: {e}");
-                                    }
-                                }
-                            }
-                        });
-                    }
-                    "disconnected" => {
-                        let reason = event
-                            .get("reason")
-                            .and_then(|v| v.as_str())
-                            .unwrap_or("unknown");
-                        warn!("whatsapp-web: disconnected ({reason})");
-                    }
-                    "pong" => {}
-                    _ => {}
+                if protocol != SIDECAR_PROTOCOL_VERSION {
+                    child.kill().await.ok();
+                    return RunOutcome::Fatal(format!(
+                        "sidecar in {} speaks protocol {protocol}, expected \
+                         {SIDECAR_PROTOCOL_VERSION}; remove it to use the bundled sidecar",
+                        self.sidecar_dir.display()
+                    ));
                 }
+                greeted = true;
+                *self.shared_stdin_tx.lock().await = Some(stdin_tx.clone());
             }
-            info!("whatsapp-web: sidecar stdout closed");
-        });
 
-        self.status = ChannelStatus::Connected;
-        info!("whatsapp-web channel started");
-        Ok(())
+            handle_event(&self.ctx, &stdin_tx, event);
+        }
+
+        let exit = match tokio::time::timeout(Duration::from_secs(5), child.wait()).await {
+            Ok(Ok(exit)) => exit.to_string(),
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => {
+                child.kill().await.ok();
+                "stdout closed".to_string()
+            }
+        };
+        status.update(|s| s.status = ChannelStatus::Reconnecting);
+        RunOutcome::Exited(exit)
     }
+}
 
-    async fn disconnect(&mut self) -> Result<()> {
-        // Drop stdin sender - sidecar detects stdin close and exits gracefully
-        self.stdin_tx.take();
+fn send_command(tx: &mpsc::Sender<String>, cmd: SidecarCommand) {
+    if let Err(e) = tx.try_send(cmd.to_line()) {
+        warn!("whatsapp-web: could not queue sidecar command: {e}");
+    }
+}
 
-        // Clear shared sender handle so pending senders get an error
-        {
-            let mut shared = self.shared_stdin_tx.lock().await;
-            *shared = None;
+fn handle_event(ctx: &Arc<WebContext>, tx: &mpsc::Sender<String>, event: SidecarEvent) {
+    let status = &ctx.status;
+    match event {
+        SidecarEvent::Hello { protocol, version } => {
+            info!("whatsapp-web: sidecar {version} started (protocol {protocol})");
+            status.update(|s| {
+                s.protocol_version = Some(protocol);
+                s.sidecar_version = Some(version);
+            });
         }
-
-        if let Some(mut child) = self.child.take() {
-            // Wait with timeout, then force kill
-            match tokio::time::timeout(std::time::Duration::from_secs(5), child.wait()).await {
-                Ok(_) => {}
-                Err(_) => {
-                    warn!("whatsapp-web: sidecar did not exit in time, killing");
-                    child.kill().await.ok();
+        SidecarEvent::Qr { .. } => {
+            info!("whatsapp-web: QR code generated, waiting for scan...");
+            status.update(|s| {
+                s.pairing = PairingState::AwaitingScan;
+                s.qr_issued_at = Some(Utc::now());
+            });
+        }
+        SidecarEvent::Ready { jid } => {
+            info!("whatsapp-web: connected and ready");
+            status.update(|s| {
+                s.status = ChannelStatus::Connected;
+                s.pairing = PairingState::Paired;
+                s.jid = jid;
+                s.qr_issued_at = None;
+                s.last_error = None;
+            });
+        }
+        SidecarEvent::Message(msg) => {
+            tokio::spawn(handle_message(Arc::clone(ctx), tx.clone(), *msg));
+        }
+        SidecarEvent::Reaction {
+            chat,
+            sender,
+            name,
+            emoji,
+            ..
+        } => {
+            // Reactions in direct chats reach the agent like a short message.
+            if emoji.is_empty() || chat.ends_with("@g.us") {
+                return;
+            }
+            let msg = WebMessage {
+                chat,
+                sender,
+                name,
+                text: format!("[The user reacted with {emoji} to an earlier message]"),
+                ..Default::default()
+            };
+            tokio::spawn(handle_message(Arc::clone(ctx), tx.clone(), msg));
+        }
+        SidecarEvent::Receipt { chat, id, status } => {
+            debug!("whatsapp-web: message {id} in {chat} is {status:?}");
+        }
+        SidecarEvent::Error { message } => {
+            warn!("whatsapp-web: sidecar error: {message}");
+        }
+        SidecarEvent::Disconnected { reason } => {
+            warn!("whatsapp-web: disconnected ({reason})");
+            status.update(|s| {
+                s.status = ChannelStatus::Reconnecting;
+                if reason == "logged_out" {
+                    s.pairing = PairingState::LoggedOut;
+                    s.jid = None;
                 }
+            });
+        }
+        SidecarEvent::Pong => {}
+    }
+}
+
+/// Answer one incoming message: mark it read, show typing, hand it to the
+/// callback and send the reply into the same chat.
+async fn handle_message(ctx: Arc<WebContext>, tx: mpsc::Sender<String>, mut msg: WebMessage) {
+    if msg.text.trim().is_empty() && msg.media.is_none() {
+        return;
+    }
+    if msg.is_group() {
+        let text = strip_own_mention(&msg.text, ctx.status.snapshot().jid.as_deref());
+        let sender_name = if msg.name.is_empty() {
+            &msg.sender
+        } else {
+            &msg.name
+        };
+        let admitted = ctx.group_policy.admit(&GroupMessage {
+            group_id: &msg.chat,
+            sender_name,
+            text: &text,
+            mentioned: msg.mentions_me,
+            replies_to_bot: msg.replies_to_me(),
+        });
+        match admitted {
+            Some(context) => msg.text = context.prepend_to(&text),
+            None => {
+                discard_media(msg.media.as_ref());
+                return;
             }
         }
+    }
+    let group_sender = msg.is_group().then(|| msg.sender.clone());
 
-        self.status = ChannelStatus::Disconnected;
-        info!("whatsapp-web channel disconnected");
-        Ok(())
+    if !msg.id.is_empty() {
+        send_command(
+            &tx,
+            SidecarCommand::Read {
+                chat: msg.chat.clone(),
+                ids: vec![msg.id.clone()],
+                sender: group_sender.clone(),
+            },
+        );
     }
+    send_command(
+        &tx,
+        SidecarCommand::Presence {
+            to: msg.chat.clone(),
+            state: Presence::Composing,
+        },
+    );
 
-    fn status(&self) -> ChannelStatus {
-        self.status.clone()
+    let attachments = match &msg.media {
+        Some(media) => media_input(&ctx, media),
+        None => PendingAttachments::default(),
+    };
+    let result = (ctx.on_message)(
+        msg.chat.clone(),
+        msg.sender.clone(),
+        msg.name.clone(),
+        msg.text.clone(),
+        attachments,
+        None,
+    )
+    .await;
+    discard_media(msg.media.as_ref());
+
+    send_command(
+        &tx,
+        SidecarCommand::Presence {
+            to: msg.chat.clone(),
+            state: Presence::Paused,
+        },
+    );
+    match result {
+        Ok(response) => send_command(
+            &tx,
+            SidecarCommand::Send {
+                to: msg.chat,
+                text: response,
+                mentions: Vec::new(),
+                // Quote the question in groups so the reply is easy to follow.
+                quoted_id: group_sender.and(Some(msg.id)).filter(|id| !id.is_empty()),
+            },
+        ),
+        Err(e) if e == "__blocked__" => {}
+        Err(e) => warn!("whatsapp-web: message handler error: {e}"),
     }
 }
 
-#[async_trait]
-impl ChannelSender for WhatsAppWebChannel {
-    fn channel_type(&self) -> &str {
-        "whatsapp-web"
+/// Remove `@<number>` mentions of the bot's own account from `text`.
+/// Device suffixes in the JID (`4915700000000:12@s.whatsapp.net`) are ignored.
+fn strip_own_mention(text: &str, own_jid: Option<&str>) -> String {
+    let number = own_jid
+        .and_then(|jid| jid.split(['@', ':']).next())
+        .filter(|n| !n.is_empty());
+    match number {
+        Some(number) => text.replace(&format!("@{number}"), "").trim().to_string(),
+        None => text.to_string(),
     }
+}
 
-    async fn send_message(&self, message: &Message) -> Result<()> {
-        let tx = self.stdin_tx.as_ref().ok_or_else(|| {
-            opencrust_common::Error::Channel("whatsapp-web sidecar not connected".into())
-        })?;
-        whatsapp_web_send_message(tx, message).await
+/// Turn a media file the sidecar saved into attachments for the agent. The
+/// file is read only when the gateway resolves them.
+fn media_input(ctx: &WebContext, media: &WebMedia) -> PendingAttachments {
+    let mut pending = PendingAttachments::new(ctx.speech_to_text.clone());
+    let filename = media.filename.clone().unwrap_or_else(|| match media.kind {
+        MediaKind::Image | MediaKind::Sticker => "image.jpg".to_string(),
+        MediaKind::Audio if media.voice => "voice.ogg".to_string(),
+        MediaKind::Audio => "audio".to_string(),
+        MediaKind::Video => "video.mp4".to_string(),
+        MediaKind::Document => "document".to_string(),
+    });
+    let path = match (&media.path, &media.error) {
+        (Some(path), _) => path.clone(),
+        (None, error) => {
+            let e = error.as_deref().unwrap_or("no file");
+            warn!("whatsapp-web: could not read {filename}: {e}");
+            pending.push_block(format!("[Attachment {filename} could not be read: {e}]"));
+            return pending;
+        }
+    };
+    let fetch = async move { tokio::fs::read(path).await.map_err(|e| e.to_string()) };
+
+    let mime_type = media.mime_type.as_deref();
+    match media.kind {
+        MediaKind::Image | MediaKind::Sticker => pending.push_image(&filename, mime_type, fetch),
+        MediaKind::Video => pending.push_block(format!(
+            "[The user sent a video ({filename}), which is not supported]"
+        )),
+        MediaKind::Audio => pending.push_voice(&filename, fetch),
+        MediaKind::Document => pending.push_document(&filename, mime_type, fetch),
     }
+    pending
 }
 
-/// Shared send logic used by both `WhatsAppWebChannel` and `WhatsAppWebSender`.
-async fn whatsapp_web_send_message(tx: &mpsc::Sender<String>, message: &Message) -> Result<()> {
-    let to = message
-        .metadata
+/// Remove a media file the sidecar saved once the channel is done with it.
+fn discard_media(media: Option<&WebMedia>) {
+    if let Some(path) = media.and_then(|m| m.path.as_deref())
+        && let Err(e) = std::fs::remove_file(path)
+    {
+        debug!("whatsapp-web: could not remove {path}: {e}");
+    }
+}
+
+async fn command_tx(shared: &SharedStdinTx) -> Result<mpsc::Sender<String>> {
+    shared.lock().await.clone().ok_or_else(|| {
+        opencrust_common::Error::Channel("whatsapp-web sidecar not connected".into())
+    })
+}
+
+async fn send_to_sidecar(shared: &SharedStdinTx, cmd: SidecarCommand) -> Result<()> {
+    command_tx(shared)
+        .await?
+        .send(cmd.to_line())
+        .await
+        .map_err(|e| opencrust_common::Error::Channel(format!("failed to send to sidecar: {e}")))
+}
+
+fn whatsapp_recipient(metadata: &serde_json::Value) -> Result<&str> {
+    metadata
         .get("whatsapp_from")
         .and_then(|v| v.as_str())
-        .ok_or_else(|| {
-            opencrust_common::Error::Channel("missing whatsapp_from in metadata".into())
-        })?;
+        .ok_or_else(|| opencrust_common::Error::Channel("missing whatsapp_from in metadata".into()))
+}
+
+/// Shared send logic used by both `WhatsAppWebChannel` and `WhatsAppWebSender`.
+/// Media content is sent from a local path or `http(s)` URL.
+async fn whatsapp_web_send_message(shared: &SharedStdinTx, message: &Message) -> Result<()> {
+    let to = whatsapp_recipient(&message.metadata)?.to_string();
+    let media =
+        |kind: MediaKind, url: &str, caption: Option<&String>, filename: Option<&String>| {
+            SidecarCommand::SendMedia {
+                to: to.clone(),
+                kind,
+                url: url.to_string(),
+                mime_type: None,
+                caption: caption.cloned(),
+                filename: filename.cloned(),
+                voice: false,
+                delete_after: false,
+            }
+        };
 
-    let text = match &message.content {
-        MessageContent::Text(t) => t.clone(),
+    let cmd = match &message.content {
+        MessageContent::Text(text) => SidecarCommand::Send {
+            to: to.clone(),
+            text: text.clone(),
+            mentions: Vec::new(),
+            quoted_id: None,
+        },
+        MessageContent::Image { url, caption } => {
+            media(MediaKind::Image, url, caption.as_ref(), None)
+        }
+        MessageContent::Video { url, caption } => {
+            media(MediaKind::Video, url, caption.as_ref(), None)
+        }
+        MessageContent::Audio { url, .. } => media(MediaKind::Audio, url, None, None),
+        MessageContent::File { url, filename } => {
+            media(MediaKind::Document, url, None, Some(filename))
+        }
+        MessageContent::Reaction {
+            emoji,
+            target_message_id,
+        } => SidecarCommand::React {
+            chat: to.clone(),
+            id: target_message_id.clone(),
+            emoji: emoji.clone(),
+            sender: None,
+            from_me: false,
+        },
         _ => {
             return Err(opencrust_common::Error::Channel(
-                "only text messages are supported for whatsapp-web send".into(),
+                "unsupported message content for whatsapp-web send".into(),
             ));
         }
     };
 
-    let cmd = serde_json::json!({
-        "type": "send",
-        "to": to,
-        "text": text,
-    });
+    send_to_sidecar(shared, cmd).await
+}
 
-    tx.send(serde_json::to_string(&cmd).unwrap_or_default())
-        .await
-        .map_err(|e| opencrust_common::Error::Channel(format!("failed to send to sidecar: {e}")))?;
+/// Write outbound bytes into the media directory for the sidecar to send;
+/// it deletes the file afterwards.
+async fn stage_outbound(media_dir: &Path, filename: &str, data: &[u8]) -> Result<PathBuf> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let dir = media_dir.join("out");
+    let name = format!(
+        "{}-{}-{}",
+        Utc::now().timestamp_millis(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        Path::new(filename)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("file")
+    );
+    let path = dir.join(name);
+    let write = async {
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(&path, data).await
+    };
+    write.await.map_err(|e| {
+        opencrust_common::Error::Channel(format!("failed to stage {filename} for sidecar: {e}"))
+    })?;
+    Ok(path)
+}
+
+async fn whatsapp_web_send_file(
+    shared: &SharedStdinTx,
+    media_dir: &Path,
+    metadata: &serde_json::Value,
+    file: OutboundFile,
+) -> Result<()> {
+    let to = whatsapp_recipient(metadata)?.to_string();
+    let kind = if file.is_image() {
+        MediaKind::Image
+    } else if file.is_video() {
+        MediaKind::Video
+    } else if file.is_audio() {
+        MediaKind::Audio
+    } else {
+        MediaKind::Document
+    };
+    let path = stage_outbound(media_dir, &file.filename, &file.data).await?;
+    send_to_sidecar(
+        shared,
+        SidecarCommand::SendMedia {
+            to,
+            kind,
+            url: path.to_string_lossy().into_owned(),
+            mime_type: Some(file.mime_type),
+            caption: file.caption.filter(|_| kind != MediaKind::Audio),
+            filename: Some(file.filename),
+            voice: false,
+            delete_after: true,
+        },
+    )
+    .await
+}
 
-    Ok(())
+async fn whatsapp_web_send_voice(
+    shared: &SharedStdinTx,
+    media_dir: &Path,
+    metadata: &serde_json::Value,
+    audio: Vec<u8>,
+) -> Result<()> {
+    let to = whatsapp_recipient(metadata)?.to_string();
+    let path = stage_outbound(media_dir, "reply.ogg", &audio).await?;
+    send_to_sidecar(
+        shared,
+        SidecarCommand::SendMedia {
+            to,
+            kind: MediaKind::Audio,
+            url: path.to_string_lossy().into_owned(),
+            mime_type: Some("audio/ogg; codecs=opus".to_string()),
+            caption: None,
+            filename: None,
+            voice: true,
+            delete_after: true,
+        },
+    )
+    .await
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn channel() -> WhatsAppWebChannel {
+        let on_msg: WhatsAppWebOnMessageFn =
+            Arc::new(|_chat, _sender, _user, _text, _attachments, _delta_tx| {
+                Box::pin(async { Ok("test".to_string()) })
+            });
+        WhatsAppWebChannel::new(on_msg)
+    }
+
     #[test]
     fn channel_type_is_whatsapp_web() {
-        let on_msg: WhatsAppOnMessageFn =
-            Arc::new(|_from, _user, _text, _delta_tx| Box::pin(async { Ok("test".to_string()) }));
-        let channel = WhatsAppWebChannel::new(on_msg);
+        let channel = channel();
         assert_eq!(channel.channel_type(), "whatsapp-web");
         assert_eq!(channel.display_name(), "WhatsApp Web");
         assert_eq!(channel.status(), ChannelStatus::Disconnected);
     }
+
+    #[test]
+    fn status_follows_pairing_events() {
+        let channel = channel();
+        let ctx = Arc::new(WebContext {
+            on_message: Arc::clone(&channel.on_message),
+            speech_to_text: None,
+            group_policy: Arc::default(),
+            status: channel.status_handle(),
+        });
+        let (tx, _rx) = mpsc::channel(8);
+
+        handle_event(
+            &ctx,
+            &tx,
+            SidecarEvent::Qr {
+                data: "2@abc".into(),
+            },
+        );
+        let snapshot = channel.status_handle().snapshot();
+        assert_eq!(snapshot.pairing, PairingState::AwaitingScan);
+        assert!(snapshot.qr_issued_at.is_some());
+
+        handle_event(
+            &ctx,
+            &tx,
+            SidecarEvent::Ready {
+                jid: Some("4915700000000@s.whatsapp.net".into()),
+            },
+        );
+        assert_eq!(channel.status(), ChannelStatus::Connected);
+        let snapshot = channel.status_handle().snapshot();
+        assert_eq!(snapshot.pairing, PairingState::Paired);
+        assert!(snapshot.qr_issued_at.is_none());
+
+        handle_event(
+            &ctx,
+            &tx,
+            SidecarEvent::Disconnected {
+                reason: "logged_out".into(),
+            },
+        );
+        assert_eq!(
+            channel.status_handle().snapshot().pairing,
+            PairingState::LoggedOut
+        );
+    }
+
+    #[tokio::test]
+    async fn group_messages_need_a_mention_or_reply() {
+        let (seen_tx, mut seen_rx) = mpsc::channel(4);
+        let on_message: WhatsAppWebOnMessageFn =
+            Arc::new(move |chat, sender, _user, text, _attachments, _delta_tx| {
+                let seen_tx = seen_tx.clone();
+                Box::pin(async move {
+                    let _ = seen_tx.send((chat, sender, text)).await;
+                    Ok("hello".to_string())
+                })
+            });
+        let ctx = Arc::new(WebContext {
+            on_message,
+            speech_to_text: None,
+            group_policy: Arc::default(),
+            status: WhatsAppWebStatus::default(),
+        });
+        ctx.status
+            .update(|s| s.jid = Some("4915700000000:3@s.whatsapp.net".into()));
+        let (tx, mut rx) = mpsc::channel(16);
+        let group = WebMessage {
+            id: "3EB0A1".into(),
+            chat: "120363041234567890@g.us".into(),
+            sender: "4915112345678@s.whatsapp.net".into(),
+            text: "anyone around?".into(),
+            group: Some(Default::default()),
+            ..Default::default()
+        };
+
+        handle_message(Arc::clone(&ctx), tx.clone(), group.clone()).await;
+        assert!(seen_rx.try_recv().is_err());
+        assert!(rx.try_recv().is_err());
+
+        let mention = WebMessage {
+            text: "@4915700000000 anyone around?".into(),
+            mentions_me: true,
+            ..group
+        };
+        handle_message(ctx, tx, mention).await;
+        let (chat, sender, text) = seen_rx.try_recv().unwrap();
+        assert_eq!(chat, "120363041234567890@g.us");
+        assert_eq!(sender, "4915112345678@s.whatsapp.net");
+        assert_eq!(text, "anyone around?");
+
+        let commands: Vec<serde_json::Value> = std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|line| serde_json::from_str(&line).unwrap())
+            .collect();
+        let types: Vec<_> = commands
+            .iter()
+            .map(|c| c["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(types, ["read", "presence", "presence", "send"]);
+        assert_eq!(commands[0]["sender"], "4915112345678@s.whatsapp.net");
+        assert_eq!(commands[3]["to"], "120363041234567890@g.us");
+        assert_eq!(commands[3]["quoted_id"], "3EB0A1");
+    }
 }
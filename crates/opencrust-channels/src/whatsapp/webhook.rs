@@ -1,14 +1,17 @@
+use std::collections::{HashSet, VecDeque};
 use std::sync::Arc;
 
-use axum::Json;
+use axum::body::Bytes;
 use axum::extract::{Query, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
+use ring::hmac;
 use serde::Deserialize;
 use tracing::{info, warn};
 
 use super::WhatsAppChannel;
-use super::api;
+use crate::attachments::{self, PendingAttachments};
+use opencrust_common::MessageContent;
 
 /// Shared state passed to WhatsApp webhook handlers.
 pub type WhatsAppState = Arc<Vec<Arc<WhatsAppChannel>>>;
@@ -23,6 +26,299 @@ pub struct VerifyParams {
     pub challenge: Option<String>,
 }
 
+/// Header carrying Meta's HMAC-SHA256 signature of the request body.
+const SIGNATURE_HEADER: &str = "x-hub-signature-256";
+
+/// Messages sent longer ago than this are dropped as replays. Seen message
+/// IDs are only kept in memory, so this is what stops a captured delivery
+/// from being replayed after a restart.
+const MAX_MESSAGE_AGE_SECS: i64 = 300;
+
+/// Check an `X-Hub-Signature-256` header (`sha256=<hex>`) against the raw
+/// request body, signed with the app secret. Comparison is constant-time.
+pub fn verify_signature(app_secret: &str, body: &[u8], header: Option<&str>) -> bool {
+    let Some(signature) = header
+        .and_then(|h| h.strip_prefix("sha256="))
+        .and_then(decode_hex)
+    else {
+        return false;
+    };
+    let key = hmac::Key::new(hmac::HMAC_SHA256, app_secret.as_bytes());
+    hmac::verify(&key, body, &signature).is_ok()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Message IDs seen recently, to drop webhook deliveries that are retried
+/// or replayed. Holds the newest `capacity` IDs.
+#[derive(Debug)]
+pub(crate) struct RecentIds {
+    capacity: usize,
+    order: VecDeque<String>,
+    ids: HashSet<String>,
+}
+
+impl RecentIds {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::new(),
+            ids: HashSet::new(),
+        }
+    }
+
+    /// Record `id`. Returns false if it was already seen.
+    pub(crate) fn insert(&mut self, id: &str) -> bool {
+        if !self.ids.insert(id.to_string()) {
+            return false;
+        }
+        self.order.push_back(id.to_string());
+        if self.order.len() > self.capacity
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+        true
+    }
+}
+
+/// Media types the webhook downloads and hands to the agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Image,
+    Audio,
+    Document,
+}
+
+/// An image, audio or document message from the Cloud API webhook. The
+/// file itself is fetched through the Graph media endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhatsAppMedia {
+    pub kind: MediaKind,
+    pub media_id: String,
+    pub filename: String,
+    pub mime_type: Option<String>,
+    pub caption: Option<String>,
+}
+
+impl WhatsAppMedia {
+    fn from_json(kind: MediaKind, value: &serde_json::Value) -> Option<Self> {
+        let field = |name: &str| value.get(name).and_then(|v| v.as_str()).map(str::to_string);
+        // Voice notes arrive as Ogg/Opus and images without a filename.
+        let default_filename = match kind {
+            MediaKind::Image => "image.jpg",
+            MediaKind::Audio => "voice.ogg",
+            MediaKind::Document => "document",
+        };
+        Some(Self {
+            kind,
+            media_id: field("id")?,
+            filename: field("filename").unwrap_or_else(|| default_filename.to_string()),
+            mime_type: field("mime_type"),
+            caption: field("caption"),
+        })
+    }
+}
+
+/// What an incoming message carries.
+#[derive(Debug, Clone)]
+pub enum InboundPayload {
+    /// Text (including interactive button and list replies), a location or
+    /// a reaction.
+    Content(MessageContent),
+    /// Media still to be downloaded.
+    Media(WhatsAppMedia),
+}
+
+/// One message from a webhook delivery.
+#[derive(Debug, Clone)]
+pub struct InboundMessage {
+    /// Business phone number the message was sent to.
+    pub phone_number_id: String,
+    pub message_id: String,
+    pub from: String,
+    /// Profile name from the delivery's contacts, or the sender's number.
+    pub user_name: String,
+    /// When the user sent the message, in Unix seconds.
+    pub timestamp: Option<i64>,
+    pub payload: InboundPayload,
+}
+
+impl InboundMessage {
+    /// Whether the message is too old (or undated) to be a fresh delivery.
+    fn is_stale(&self, now: i64) -> bool {
+        self.timestamp
+            .is_none_or(|sent| now - sent > MAX_MESSAGE_AGE_SECS)
+    }
+}
+
+/// The business phone numbers a webhook body is addressed to.
+fn phone_number_ids(body: &serde_json::Value) -> Vec<&str> {
+    let mut ids: Vec<&str> = body
+        .get("entry")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("changes").and_then(|v| v.as_array()))
+        .flatten()
+        .filter_map(|change| change.pointer("/value/metadata/phone_number_id"))
+        .filter_map(|v| v.as_str())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+/// Extract the messages from a webhook body, shaped as
+/// `{ "entry": [{ "changes": [{ "value": { "messages": [...] } }] }] }`.
+/// Unsupported types (video, stickers, system notices), empty texts and
+/// removed reactions are skipped.
+pub fn parse_messages(body: &serde_json::Value) -> Vec<InboundMessage> {
+    let mut parsed = Vec::new();
+    let values = body
+        .get("entry")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("changes").and_then(|v| v.as_array()))
+        .flatten()
+        .filter_map(|change| change.get("value"));
+
+    for value in values {
+        // Get the phone_number_id this message was sent to
+        let phone_number_id = value
+            .get("metadata")
+            .and_then(|m| m.get("phone_number_id"))
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let Some(messages) = value.get("messages").and_then(|v| v.as_array()) else {
+            continue;
+        };
+        // Get contacts for display names
+        let contacts = value.get("contacts").and_then(|v| v.as_array());
+
+        for msg in messages {
+            let Some(payload) = parse_payload(msg) else {
+                continue;
+            };
+            let from = str_field(msg, "from").unwrap_or_default();
+            let user_name = contacts
+                .and_then(|c| {
+                    c.iter().find_map(|contact| {
+                        if contact.get("wa_id").and_then(|v| v.as_str())? != from {
+                            return None;
+                        }
+                        contact
+                            .get("profile")
+                            .and_then(|p| p.get("name"))
+                            .and_then(|v| v.as_str())
+                            .map(str::to_string)
+                    })
+                })
+                .unwrap_or_else(|| from.clone());
+            parsed.push(InboundMessage {
+                phone_number_id: phone_number_id.to_string(),
+                message_id: str_field(msg, "id").unwrap_or_default(),
+                from,
+                user_name,
+                timestamp: str_field(msg, "timestamp").and_then(|t| t.parse().ok()),
+                payload,
+            });
+        }
+    }
+    parsed
+}
+
+fn str_field(value: &serde_json::Value, name: &str) -> Option<String> {
+    value.get(name).and_then(|v| v.as_str()).map(str::to_string)
+}
+
+fn parse_payload(msg: &serde_json::Value) -> Option<InboundPayload> {
+    let msg_type = msg.get("type").and_then(|v| v.as_str()).unwrap_or("");
+    let body = msg.get(msg_type)?;
+    let text = |text: String| {
+        (!text.trim().is_empty()).then_some(InboundPayload::Content(MessageContent::Text(text)))
+    };
+    match msg_type {
+        "text" => text(str_field(body, "body")?),
+        "image" => WhatsAppMedia::from_json(MediaKind::Image, body).map(InboundPayload::Media),
+        "audio" => WhatsAppMedia::from_json(MediaKind::Audio, body).map(InboundPayload::Media),
+        "document" => {
+            WhatsAppMedia::from_json(MediaKind::Document, body).map(InboundPayload::Media)
+        }
+        "location" => Some(InboundPayload::Content(MessageContent::Location {
+            latitude: body.get("latitude")?.as_f64()?,
+            longitude: body.get("longitude")?.as_f64()?,
+        })),
+        // An empty emoji means the user removed their reaction.
+        "reaction" => {
+            let emoji = str_field(body, "emoji").filter(|e| !e.is_empty())?;
+            Some(InboundPayload::Content(MessageContent::Reaction {
+                emoji,
+                target_message_id: str_field(body, "message_id").unwrap_or_default(),
+            }))
+        }
+        // Taps on reply buttons and list rows read as the chosen title.
+        "interactive" => {
+            let kind = str_field(body, "type")?;
+            text(str_field(body.get(&kind)?, "title")?)
+        }
+        // Quick-reply buttons on template messages.
+        "button" => text(str_field(body, "text")?),
+        _ => None,
+    }
+}
+
+/// Text for content the agent cannot take directly.
+fn content_text(content: &MessageContent) -> String {
+    match content {
+        MessageContent::Text(text) => text.clone(),
+        MessageContent::Location {
+            latitude,
+            longitude,
+        } => format!("[The user shared a location: {latitude}, {longitude}]"),
+        MessageContent::Reaction { emoji, .. } => {
+            format!("[The user reacted with {emoji} to an earlier message]")
+        }
+        _ => String::new(),
+    }
+}
+
+/// Turn a message payload into the text and attachments handed to the
+/// agent. Media is looked up and downloaded through the Graph API only once
+/// the gateway resolves the attachments.
+pub(crate) fn agent_input(
+    channel: &WhatsAppChannel,
+    payload: InboundPayload,
+) -> (String, PendingAttachments) {
+    let mut pending = PendingAttachments::new(channel.speech_to_text());
+    let media = match payload {
+        InboundPayload::Content(content) => return (content_text(&content), pending),
+        InboundPayload::Media(media) => media,
+    };
+    let api = channel.api().clone();
+    let media_id = media.media_id.clone();
+    let fetch = async move {
+        let url = api.media_url(&media_id).await?;
+        attachments::download(api.client(), &url, Some(api.token())).await
+    };
+    let mime_type = media.mime_type.as_deref();
+    match media.kind {
+        MediaKind::Image => pending.push_image(&media.filename, mime_type, fetch),
+        MediaKind::Audio => pending.push_voice(&media.filename, fetch),
+        MediaKind::Document => pending.push_document(&media.filename, mime_type, fetch),
+    }
+    (media.caption.unwrap_or_default(), pending)
+}
+
 /// GET handler for WhatsApp webhook verification.
 pub async fn whatsapp_verify(
     State(channels): State<WhatsAppState>,
@@ -48,164 +344,459 @@ pub async fn whatsapp_verify(
     }
 }
 
-/// POST handler for incoming WhatsApp messages.
+/// POST handler for incoming WhatsApp messages. Every phone number the body
+/// is addressed to must belong to a configured channel, and the request must
+/// carry a valid `X-Hub-Signature-256` made with that channel's app secret.
 pub async fn whatsapp_webhook(
     State(channels): State<WhatsAppState>,
-    Json(body): Json<serde_json::Value>,
+    headers: HeaderMap,
+    raw_body: Bytes,
 ) -> impl IntoResponse {
-    // WhatsApp sends: { "entry": [{ "changes": [{ "value": { "messages": [...] } }] }] }
-    let entries = match body.get("entry").and_then(|v| v.as_array()) {
-        Some(e) => e,
-        None => return StatusCode::OK,
+    // Authenticate the raw bytes before parsing anything: only channels
+    // whose secret signed the body may be addressed by it.
+    let signature = headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+    let signed_for: Vec<&Arc<WhatsAppChannel>> = channels
+        .iter()
+        .filter(|ch| {
+            ch.app_secret()
+                .is_some_and(|secret| verify_signature(secret, &raw_body, signature))
+        })
+        .collect();
+    if signed_for.is_empty() {
+        warn!("whatsapp: rejecting webhook with missing or invalid signature");
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let body: serde_json::Value = match serde_json::from_slice(&raw_body) {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("whatsapp: invalid webhook body: {e}");
+            return StatusCode::BAD_REQUEST;
+        }
     };
+    let ids = phone_number_ids(&body);
+    if ids.is_empty() {
+        warn!("whatsapp: rejecting webhook without a phone_number_id");
+        return StatusCode::BAD_REQUEST;
+    }
+    for id in ids {
+        if signed_for.iter().any(|ch| ch.phone_number_id() == id) {
+            continue;
+        }
+        if channels.iter().any(|ch| ch.phone_number_id() == id) {
+            warn!("whatsapp: rejecting webhook for {id} not signed with its app_secret");
+            return StatusCode::UNAUTHORIZED;
+        }
+        warn!("whatsapp: rejecting webhook for unknown phone_number_id {id}");
+        return StatusCode::NOT_FOUND;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    for inbound in parse_messages(&body) {
+        info!(
+            "whatsapp: message {} from {} ({})",
+            inbound.message_id, inbound.user_name, inbound.from
+        );
 
-    for entry in entries {
-        let changes = match entry.get("changes").and_then(|v| v.as_array()) {
-            Some(c) => c,
-            None => continue,
+        let Some(channel) = channels
+            .iter()
+            .find(|ch| ch.phone_number_id() == inbound.phone_number_id)
+        else {
+            continue;
         };
 
-        for change in changes {
-            let value = match change.get("value") {
-                Some(v) => v,
-                None => continue,
-            };
+        if inbound.is_stale(now) {
+            warn!(
+                "whatsapp: ignoring message {} sent more than {MAX_MESSAGE_AGE_SECS}s ago",
+                inbound.message_id
+            );
+            continue;
+        }
+        if !channel.first_delivery(&inbound.message_id) {
+            info!(
+                "whatsapp: ignoring repeated delivery of {}",
+                inbound.message_id
+            );
+            continue;
+        }
 
-            // Get the phone_number_id this message was sent to
-            let metadata_phone_id = value
-                .get("metadata")
-                .and_then(|m| m.get("phone_number_id"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
+        // Mark as read
+        let api = channel.api().clone();
+        let read_msg_id = inbound.message_id.clone();
+        tokio::spawn(async move {
+            let _ = api.mark_as_read(&read_msg_id).await;
+        });
 
-            let messages = match value.get("messages").and_then(|v| v.as_array()) {
-                Some(m) => m,
-                None => continue,
-            };
+        tokio::spawn(respond(Arc::clone(channel), inbound));
+    }
 
-            // Get contacts for display names
-            let contacts = value.get("contacts").and_then(|v| v.as_array());
-
-            for msg in messages {
-                let msg_type = msg.get("type").and_then(|v| v.as_str()).unwrap_or("");
-                if msg_type != "text" {
-                    continue;
-                }
-
-                let from = msg
-                    .get("from")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let message_id = msg
-                    .get("id")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-                let text = msg
-                    .get("text")
-                    .and_then(|v| v.get("body"))
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string();
-
-                if text.trim().is_empty() {
-                    continue;
-                }
-
-                // Try to get display name from contacts
-                let user_name = contacts
-                    .and_then(|c| {
-                        c.iter().find_map(|contact| {
-                            let wa_id = contact.get("wa_id").and_then(|v| v.as_str())?;
-                            if wa_id == from {
-                                contact
-                                    .get("profile")
-                                    .and_then(|p| p.get("name"))
-                                    .and_then(|v| v.as_str())
-                                    .map(|s| s.to_string())
-                            } else {
-                                None
-                            }
-                        })
-                    })
-                    .unwrap_or_else(|| from.clone());
-
-                info!(
-                    "whatsapp: message from {} ({}): {} chars",
-                    user_name,
-                    from,
-                    text.len()
-                );
-
-                // Find the matching channel by phone_number_id
-                let channel = channels
-                    .iter()
-                    .find(|ch| ch.phone_number_id() == metadata_phone_id)
-                    .or_else(|| channels.first());
-
-                let Some(channel) = channel else {
-                    warn!(
-                        "whatsapp: no channel configured for phone_number_id {metadata_phone_id}"
-                    );
-                    continue;
-                };
-
-                // Mark as read
-                let client = channel.client();
-                let token = channel.access_token();
-                let phone_id = channel.phone_number_id().to_string();
-
-                let read_client = client.clone();
-                let read_token = token.to_string();
-                let read_phone_id = phone_id.clone();
-                let read_msg_id = message_id.clone();
-                tokio::spawn(async move {
-                    let _ =
-                        api::mark_as_read(&read_client, &read_token, &read_phone_id, &read_msg_id)
-                            .await;
-                });
+    StatusCode::OK
+}
 
-                // Process message
-                let channel = Arc::clone(channel);
-                let from_clone = from.clone();
-                tokio::spawn(async move {
-                    match channel
-                        .handle_incoming(&from_clone, &user_name, &text)
-                        .await
-                    {
-                        Ok(response) => {
-                            if let Err(e) = api::send_text_message(
-                                channel.client(),
-                                channel.access_token(),
-                                channel.phone_number_id(),
-                                &from_clone,
-                                &response,
-                            )
-                            .await
-                            {
-                                warn!("whatsapp: failed to send reply: {e}");
-                            }
-                        }
-                        Err(e) if e == "__blocked__" => {
-                            // Silently drop — unauthorized user
-                        }
-                        Err(e) => {
-                            warn!("whatsapp: error processing message: {e}");
-                            let _ = api::send_text_message(
-                                channel.client(),
-                                channel.access_token(),
-                                channel.phone_number_id(),
-                                &from_clone,
-                                "Sorry, an error occurred processing your message.",
-                            )
-                            .await;
-                        }
-                    }
-                });
+/// Run one incoming message through the channel callback and send the reply.
+async fn respond(channel: Arc<WhatsAppChannel>, inbound: InboundMessage) {
+    let api = channel.api();
+    let from = inbound.from;
+    let user_spoke = matches!(
+        &inbound.payload,
+        InboundPayload::Media(media) if media.kind == MediaKind::Audio
+    );
+    let (text, attachments) = agent_input(&channel, inbound.payload);
+
+    match channel
+        .handle_incoming(&from, &inbound.user_name, &text, attachments)
+        .await
+    {
+        Ok(response) => {
+            if let Err(e) = api.send_text_message(&from, &response).await {
+                warn!("whatsapp: failed to send reply: {e}");
             }
+
+            if let Some(replier) = channel.voice_replier()
+                && let Some(audio) = replier.voice_reply(&from, user_spoke, &response).await
+                && let Err(e) = api.send_voice_message(&from, audio).await
+            {
+                warn!("whatsapp: failed to send voice reply: {e}");
+            }
+        }
+        Err(e) if e == "__blocked__" => {
+            // Silently drop — unauthorized user
+        }
+        Err(e) => {
+            warn!("whatsapp: error processing message: {e}");
+            let _ = api
+                .send_text_message(&from, "Sorry, an error occurred processing your message.")
+                .await;
         }
     }
+}
 
-    StatusCode::OK
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Cloud API text message webhook and its signature, made with the
+    /// app secret `fixture-app-secret`.
+    const FIXTURE_BODY: &[u8] = include_bytes!("fixtures/text_message.json");
+    const FIXTURE_SIGNATURE: &str = include_str!("fixtures/text_message.sig");
+    const FIXTURE_SECRET: &str = "fixture-app-secret";
+
+    #[test]
+    fn accepts_captured_signature() {
+        assert!(verify_signature(
+            FIXTURE_SECRET,
+            FIXTURE_BODY,
+            Some(FIXTURE_SIGNATURE)
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_wrong_or_tampered_signatures() {
+        assert!(!verify_signature(FIXTURE_SECRET, FIXTURE_BODY, None));
+        assert!(!verify_signature(
+            "another-secret",
+            FIXTURE_BODY,
+            Some(FIXTURE_SIGNATURE)
+        ));
+        assert!(!verify_signature(
+            FIXTURE_SECRET,
+            FIXTURE_BODY,
+            Some(FIXTURE_SIGNATURE.trim_start_matches("sha256="))
+        ));
+        assert!(!verify_signature(
+            FIXTURE_SECRET,
+            FIXTURE_BODY,
+            Some("sha256=zz")
+        ));
+
+        let tampered = String::from_utf8_lossy(FIXTURE_BODY).replace("16505551234", "15550000000");
+        assert!(!verify_signature(
+            FIXTURE_SECRET,
+            tampered.as_bytes(),
+            Some(FIXTURE_SIGNATURE)
+        ));
+    }
+
+    #[test]
+    fn recent_ids_drop_repeats_and_forget_oldest() {
+        let mut seen = RecentIds::new(2);
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("a"));
+        assert!(seen.insert("b"));
+        assert!(seen.insert("c"));
+        // "a" was evicted once capacity was exceeded.
+        assert!(seen.insert("a"));
+        assert!(!seen.insert("c"));
+    }
+
+    /// Wrap Cloud API message objects in a webhook delivery body.
+    fn delivery(messages: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "object": "whatsapp_business_account",
+            "entry": [{
+                "id": "102290129340398",
+                "changes": [{
+                    "field": "messages",
+                    "value": {
+                        "messaging_product": "whatsapp",
+                        "metadata": { "phone_number_id": "106540352242922" },
+                        "contacts": [{ "profile": { "name": "Sheena Nelson" }, "wa_id": "16505551234" }],
+                        "messages": messages,
+                    },
+                }],
+            }],
+        })
+    }
+
+    #[test]
+    fn parses_captured_text_message() {
+        let body: serde_json::Value = serde_json::from_slice(FIXTURE_BODY).unwrap();
+        let messages = parse_messages(&body);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].phone_number_id, "106540352242922");
+        assert_eq!(messages[0].from, "16505551234");
+        assert_eq!(messages[0].user_name, "Sheena Nelson");
+        assert!(matches!(
+            &messages[0].payload,
+            InboundPayload::Content(MessageContent::Text(t)) if t == "Does it come in another color?"
+        ));
+    }
+
+    #[test]
+    fn parses_media_location_reaction_and_interactive_replies() {
+        let body = delivery(serde_json::json!([
+            { "from": "16505551234", "id": "w1", "type": "image",
+              "image": { "id": "img-1", "mime_type": "image/png", "caption": "Which plant?" } },
+            { "from": "16505551234", "id": "w2", "type": "audio",
+              "audio": { "id": "aud-1", "mime_type": "audio/ogg; codecs=opus", "voice": true } },
+            { "from": "16505551234", "id": "w3", "type": "location",
+              "location": { "latitude": 52.52, "longitude": 13.405, "name": "Berlin" } },
+            { "from": "16505551234", "id": "w4", "type": "reaction",
+              "reaction": { "message_id": "wamid.out", "emoji": "👍" } },
+            { "from": "16505551234", "id": "w5", "type": "interactive",
+              "interactive": { "type": "button_reply", "button_reply": { "id": "yes", "title": "Yes please" } } },
+            { "from": "16505551234", "id": "w6", "type": "interactive",
+              "interactive": { "type": "list_reply", "list_reply": { "id": "pro", "title": "Pro plan" } } },
+            { "from": "16505551234", "id": "w7", "type": "reaction",
+              "reaction": { "message_id": "wamid.out", "emoji": "" } },
+            { "from": "16505551234", "id": "w8", "type": "sticker",
+              "sticker": { "id": "st-1", "mime_type": "image/webp" } },
+        ]));
+        let messages = parse_messages(&body);
+        let ids: Vec<_> = messages.iter().map(|m| m.message_id.as_str()).collect();
+        assert_eq!(ids, ["w1", "w2", "w3", "w4", "w5", "w6"]);
+
+        let InboundPayload::Media(image) = &messages[0].payload else {
+            panic!("expected media");
+        };
+        assert_eq!(image.kind, MediaKind::Image);
+        assert_eq!(image.media_id, "img-1");
+        assert_eq!(image.caption.as_deref(), Some("Which plant?"));
+
+        let InboundPayload::Media(voice) = &messages[1].payload else {
+            panic!("expected media");
+        };
+        assert_eq!(voice.kind, MediaKind::Audio);
+        assert_eq!(voice.filename, "voice.ogg");
+
+        assert!(matches!(
+            messages[2].payload,
+            InboundPayload::Content(MessageContent::Location { latitude, longitude })
+                if latitude == 52.52 && longitude == 13.405
+        ));
+        assert!(matches!(
+            &messages[3].payload,
+            InboundPayload::Content(MessageContent::Reaction { emoji, target_message_id })
+                if emoji == "👍" && target_message_id == "wamid.out"
+        ));
+        assert!(matches!(
+            &messages[4].payload,
+            InboundPayload::Content(MessageContent::Text(t)) if t == "Yes please"
+        ));
+        assert!(matches!(
+            &messages[5].payload,
+            InboundPayload::Content(MessageContent::Text(t)) if t == "Pro plan"
+        ));
+    }
+
+    type Sent = Arc<std::sync::Mutex<Vec<serde_json::Value>>>;
+
+    /// A Graph API stand-in: media IDs resolve to `/files/<id>`, which
+    /// serves a few PNG bytes, and `messages` payloads are recorded.
+    async fn mock_graph() -> (String, Sent) {
+        use axum::extract::Path;
+        use axum::routing::{get, post};
+        use axum::{Json, Router};
+
+        let sent: Sent = Arc::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let files_base = base.clone();
+        let app = Router::new()
+            .route(
+                "/{phone}/messages",
+                post(
+                    |State(sent): State<Sent>, Json(body): Json<serde_json::Value>| async move {
+                        sent.lock().unwrap().push(body);
+                        Json(serde_json::json!({}))
+                    },
+                ),
+            )
+            .route(
+                "/{media_id}",
+                get(move |Path(id): Path<String>| async move {
+                    Json(serde_json::json!({ "url": format!("{files_base}/files/{id}") }))
+                }),
+            )
+            .route("/files/{id}", get(|| async { &b"\x89PNG\r\n"[..] }))
+            .with_state(Arc::clone(&sent));
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (base, sent)
+    }
+
+    fn sign(body: &[u8]) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, FIXTURE_SECRET.as_bytes());
+        let tag = hmac::sign(&key, body);
+        let hex: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+        format!("sha256={hex}")
+    }
+
+    #[tokio::test]
+    async fn webhook_downloads_images_and_replies_through_graph_api() {
+        let (base, sent) = mock_graph().await;
+        let (seen_tx, mut seen_rx) = tokio::sync::mpsc::channel(1);
+        let on_message: super::super::WhatsAppOnMessageFn =
+            Arc::new(move |_from, _user, text, attachments, _delta_tx| {
+                let seen_tx = seen_tx.clone();
+                Box::pin(async move {
+                    let _ = seen_tx.send(attachments.resolve(&text).await).await;
+                    Ok("A monstera.".to_string())
+                })
+            });
+        let channel = WhatsAppChannel::new(
+            "test-token".into(),
+            "106540352242922".into(),
+            "verify".into(),
+            on_message,
+        )
+        .with_app_secret(Some(FIXTURE_SECRET.into()))
+        .with_api_base(&base);
+        let channels: WhatsAppState = Arc::new(vec![Arc::new(channel)]);
+
+        let body = delivery(serde_json::json!([
+            { "from": "16505551234", "id": "wamid.img", "type": "image",
+              "timestamp": chrono::Utc::now().timestamp().to_string(),
+              "image": { "id": "img-1", "mime_type": "image/png", "caption": "Which plant?" } },
+        ]))
+        .to_string();
+        let mut headers = HeaderMap::new();
+        headers.insert(SIGNATURE_HEADER, sign(body.as_bytes()).parse().unwrap());
+
+        let status = whatsapp_webhook(State(channels), headers, Bytes::from(body))
+            .await
+            .into_response()
+            .status();
+        assert_eq!(status, StatusCode::OK);
+
+        let (text, images) =
+            tokio::time::timeout(std::time::Duration::from_secs(5), seen_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(text, "Which plant?");
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].data, b"\x89PNG\r\n");
+        assert_eq!(images[0].mime_type, "image/png");
+
+        // The read receipt and the reply both go to the messages endpoint.
+        for _ in 0..50 {
+            if sent.lock().unwrap().len() >= 2 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        let sent = sent.lock().unwrap();
+        let reply = sent
+            .iter()
+            .find(|m| m["type"] == "text")
+            .expect("reply sent");
+        assert_eq!(reply["to"], "16505551234");
+        assert_eq!(reply["text"]["body"], "A monstera.");
+        assert!(sent.iter().any(|m| m["status"] == "read"));
+    }
+
+    #[tokio::test]
+    async fn webhook_verifies_with_the_addressed_channels_secret() {
+        let channel = |phone: &str, secret: &str| {
+            let on_message: super::super::WhatsAppOnMessageFn =
+                Arc::new(|_from, _user, _text, _attachments, _delta_tx| {
+                    Box::pin(async { Ok(String::new()) })
+                });
+            Arc::new(
+                WhatsAppChannel::new("token".into(), phone.into(), "verify".into(), on_message)
+                    .with_app_secret(Some(secret.into())),
+            )
+        };
+        let channels: WhatsAppState = Arc::new(vec![
+            channel("106540352242922", "another-secret"),
+            channel("200000000000000", FIXTURE_SECRET),
+        ]);
+        let post = |body: Vec<u8>| {
+            let mut headers = HeaderMap::new();
+            headers.insert(SIGNATURE_HEADER, sign(&body).parse().unwrap());
+            let channels = Arc::clone(&channels);
+            async move {
+                whatsapp_webhook(State(channels), headers, Bytes::from(body))
+                    .await
+                    .into_response()
+                    .status()
+            }
+        };
+
+        // Signed with the other channel's secret.
+        let body = delivery(serde_json::json!([]));
+        assert_eq!(
+            post(body.to_string().into_bytes()).await,
+            StatusCode::UNAUTHORIZED
+        );
+
+        let unknown = body
+            .to_string()
+            .replace("106540352242922", "300000000000000");
+        assert_eq!(post(unknown.into_bytes()).await, StatusCode::NOT_FOUND);
+
+        let own = body
+            .to_string()
+            .replace("106540352242922", "200000000000000");
+        assert_eq!(post(own.into_bytes()).await, StatusCode::OK);
+
+        // Unsigned bodies are refused before they are parsed.
+        let unsigned = whatsapp_webhook(
+            State(Arc::clone(&channels)),
+            HeaderMap::new(),
+            Bytes::from_static(b"not json"),
+        )
+        .await
+        .into_response()
+        .status();
+        assert_eq!(unsigned, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn old_or_undated_messages_are_stale() {
+        let body = delivery(serde_json::json!([
+            { "from": "1", "id": "fresh", "type": "text", "timestamp": "1000",
+              "text": { "body": "hi" } },
+            { "from": "1", "id": "old", "type": "text", "timestamp": "600",
+              "text": { "body": "hi" } },
+            { "from": "1", "id": "undated", "type": "text", "text": { "body": "hi" } },
+        ]));
+        let stale: Vec<bool> = parse_messages(&body)
+            .iter()
+            .map(|m| m.is_stale(1000 + MAX_MESSAGE_AGE_SECS))
+            .collect();
+        assert_eq!(stale, [false, true, true]);
+    }
 }
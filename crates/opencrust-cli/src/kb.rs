@@ -0,0 +1,80 @@
+use anyhow::Result;
+use opencrust_gateway::bootstrap::open_knowledge_indexer;
+
+use crate::KbCommands;
+
+/// Characters of each passage shown in search results.
+const PREVIEW_CHARS: usize = 300;
+
+fn print_report(label: &str, report: &opencrust_agents::IndexReport) {
+    println!(
+        "{label}: {} indexed, {} unchanged, {} removed, {} failed",
+        report.indexed, report.unchanged, report.removed, report.failed
+    );
+}
+
+pub async fn run(action: KbCommands, config: &opencrust_config::AppConfig) -> Result<()> {
+    let indexer = open_knowledge_indexer(config)?;
+    match action {
+        KbCommands::List => {
+            let sources = indexer.store().list_sources()?;
+            println!("Knowledge sources:");
+            if sources.is_empty() {
+                println!("  (none)");
+            }
+            for source in sources {
+                println!(
+                    "  {} - {} ({} documents, {} chunks)",
+                    source.name, source.path, source.documents, source.chunks
+                );
+            }
+        }
+        KbCommands::Add { name, path } => {
+            let report = indexer.add_source(&name, &path).await?;
+            print_report(&format!("added source '{name}'"), &report);
+        }
+        KbCommands::Remove { name } => {
+            if indexer.store().remove_source(&name)? {
+                println!("removed knowledge source: {}", name);
+            } else {
+                println!("knowledge source '{}' not found", name);
+            }
+        }
+        KbCommands::Sync { name } => match name {
+            Some(name) => {
+                let Some(source) = indexer.store().get_source(&name)? else {
+                    anyhow::bail!("knowledge source '{name}' not found");
+                };
+                let report = indexer.sync_source(&source).await?;
+                print_report(&format!("synced '{name}'"), &report);
+            }
+            None => {
+                let report = indexer.sync_all().await?;
+                print_report("synced all sources", &report);
+            }
+        },
+        KbCommands::Search {
+            query,
+            source,
+            limit,
+        } => {
+            let hits = indexer.search(&query, source, limit).await?;
+            if hits.is_empty() {
+                println!("No matching passages.");
+            }
+            for hit in hits {
+                let mut preview: String = hit.content.chars().take(PREVIEW_CHARS).collect();
+                if hit.content.chars().count() > PREVIEW_CHARS {
+                    preview.push('…');
+                }
+                println!(
+                    "[{}] score {:.3}\n{}\n",
+                    hit.citation(),
+                    hit.score,
+                    preview.trim()
+                );
+            }
+        }
+    }
+    Ok(())
+}
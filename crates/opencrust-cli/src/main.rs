@@ -1,4 +1,6 @@
 mod banner;
+mod kb;
+mod memory;
 mod migrate;
 mod update;
 mod wizard;
@@ -7,7 +9,7 @@ use std::io::IsTerminal;
 use std::path::PathBuf;
 
 use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use opencrust_security::RedactingWriter;
 use tracing_subscriber::EnvFilter;
 
@@ -86,12 +88,24 @@ enum Commands {
         action: SkillCommands,
     },
 
+    /// Manage the document knowledge base
+    Kb {
+        #[command(subcommand)]
+        action: KbCommands,
+    },
+
     /// Manage MCP servers
     Mcp {
         #[command(subcommand)]
         action: McpCommands,
     },
 
+    /// Inspect, export and prune long-term memory
+    Memory {
+        #[command(subcommand)]
+        action: MemoryCommands,
+    },
+
     /// Migrate data from other platforms
     Migrate {
         #[command(subcommand)]
@@ -152,6 +166,86 @@ enum McpCommands {
     Prompts { name: String },
 }
 
+#[derive(Subcommand)]
+enum KbCommands {
+    /// List knowledge sources with document and chunk counts
+    List,
+    /// Add a directory as a knowledge source and index it
+    Add { name: String, path: PathBuf },
+    /// Remove a knowledge source and everything indexed from it
+    Remove { name: String },
+    /// Re-index one source, or all of them
+    Sync { name: Option<String> },
+    /// Search indexed documents
+    Search {
+        query: String,
+
+        /// Restrict to this source (repeatable)
+        #[arg(long)]
+        source: Vec<String>,
+
+        /// Maximum passages to show
+        #[arg(long, default_value = "5")]
+        limit: usize,
+    },
+}
+
+#[derive(Args)]
+struct MemoryScope {
+    /// Only entries from this session
+    #[arg(long)]
+    session: Option<String>,
+
+    /// Only entries from this user
+    #[arg(long)]
+    user: Option<String>,
+
+    /// Only entries in this continuity bucket
+    #[arg(long)]
+    continuity: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum MemoryCommands {
+    /// List stored memory entries, newest first
+    List {
+        #[command(flatten)]
+        scope: MemoryScope,
+
+        /// Maximum entries to show
+        #[arg(long, default_value = "20")]
+        limit: usize,
+
+        /// Skip this many entries
+        #[arg(long, default_value = "0")]
+        offset: usize,
+    },
+    /// Search memory by text (semantic search needs the running gateway's API)
+    Search {
+        query: String,
+
+        #[command(flatten)]
+        scope: MemoryScope,
+
+        /// Maximum entries to show
+        #[arg(long, default_value = "10")]
+        limit: usize,
+    },
+    /// Delete a memory entry by ID
+    Delete { id: String },
+    /// Export entries and facts as JSONL
+    Export {
+        #[command(flatten)]
+        scope: MemoryScope,
+
+        /// Write to this file instead of stdout
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+    /// Import a JSONL export (existing IDs are skipped)
+    Import { file: PathBuf },
+}
+
 #[derive(Subcommand)]
 enum MigrateCommands {
     /// Import data from OpenClaw
@@ -846,6 +940,18 @@ async fn async_main(
                 }
             }
         }
+        Commands::Kb { action } => {
+            init_tracing(&cli.log_level);
+            if let Err(e) = kb::run(action, &config).await {
+                println!("kb command failed: {}", e);
+            }
+        }
+        Commands::Memory { action } => {
+            init_tracing(&cli.log_level);
+            if let Err(e) = memory::run(action, &config).await {
+                println!("memory command failed: {}", e);
+            }
+        }
         Commands::Migrate { action } => {
             init_tracing(&cli.log_level);
             match action {
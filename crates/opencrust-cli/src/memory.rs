@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use opencrust_db::{MemoryEntry, MemoryListQuery, MemoryRecord, MemoryStore, RecallQuery};
+
+use crate::{MemoryCommands, MemoryScope};
+
+/// Characters of content shown per entry in listings.
+const PREVIEW_CHARS: usize = 100;
+
+/// Same location the gateway opens in bootstrap.
+fn memory_db_path(config: &opencrust_config::AppConfig) -> PathBuf {
+    config
+        .data_dir
+        .clone()
+        .unwrap_or_else(|| opencrust_config::ConfigLoader::default_config_dir().join("data"))
+        .join("memory.db")
+}
+
+fn open_store(config: &opencrust_config::AppConfig, create: bool) -> Result<MemoryStore> {
+    let path = memory_db_path(config);
+    if !create && !path.exists() {
+        anyhow::bail!("no memory database at {}", path.display());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    Ok(MemoryStore::open(&path)?)
+}
+
+fn list_query(scope: MemoryScope) -> MemoryListQuery {
+    MemoryListQuery {
+        session_id: scope.session,
+        continuity_key: scope.continuity,
+        user_id: scope.user,
+        ..MemoryListQuery::default()
+    }
+}
+
+fn print_entries(entries: &[MemoryEntry]) {
+    if entries.is_empty() {
+        println!("  (none)");
+    }
+    for entry in entries {
+        let mut preview: String = entry
+            .content
+            .chars()
+            .take(PREVIEW_CHARS)
+            .map(|c| if c == '\n' { ' ' } else { c })
+            .collect();
+        if entry.content.chars().count() > PREVIEW_CHARS {
+            preview.push('…');
+        }
+        println!(
+            "  {} {} [{}] {:?}: {}",
+            entry.id,
+            entry.created_at.format("%Y-%m-%d %H:%M"),
+            entry.session_id,
+            entry.role,
+            preview
+        );
+    }
+}
+
+pub async fn run(action: MemoryCommands, config: &opencrust_config::AppConfig) -> Result<()> {
+    match action {
+        MemoryCommands::List {
+            scope,
+            limit,
+            offset,
+        } => {
+            let store = open_store(config, false)?;
+            let entries = store
+                .list_entries(MemoryListQuery {
+                    limit,
+                    offset,
+                    ..list_query(scope)
+                })
+                .await?;
+            println!("Memory entries:");
+            print_entries(&entries);
+        }
+        MemoryCommands::Search {
+            query,
+            scope,
+            limit,
+        } => {
+            let store = open_store(config, false)?;
+            let entries = store
+                .recall(RecallQuery {
+                    query_text: Some(query),
+                    query_embedding: None,
+                    session_id: scope.session,
+                    continuity_key: scope.continuity,
+                    user_id: scope.user,
+                    limit,
+                })
+                .await?;
+            println!("Matching entries:");
+            print_entries(&entries);
+        }
+        MemoryCommands::Delete { id } => {
+            let store = open_store(config, false)?;
+            if store.delete_entry(&id).await? {
+                println!("deleted memory entry: {}", id);
+            } else {
+                println!("memory entry '{}' not found", id);
+            }
+        }
+        MemoryCommands::Export { scope, output } => {
+            let store = open_store(config, false)?;
+            let records = store.export_records(list_query(scope)).await?;
+            let jsonl = MemoryRecord::to_jsonl(&records)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, jsonl)
+                        .with_context(|| format!("failed to write {}", path.display()))?;
+                    eprintln!("exported {} records to {}", records.len(), path.display());
+                }
+                None => print!("{jsonl}"),
+            }
+        }
+        MemoryCommands::Import { file } => {
+            let text = std::fs::read_to_string(&file)
+                .with_context(|| format!("failed to read {}", file.display()))?;
+            let records = MemoryRecord::parse_jsonl(&text)?;
+            let store = open_store(config, true)?;
+            let report = store.import_records(records).await?;
+            println!(
+                "imported {} entries and {} facts ({} already present)",
+                report.entries, report.facts, report.skipped
+            );
+        }
+    }
+    Ok(())
+}
@@ -3,5 +3,5 @@ pub mod message;
 pub mod types;
 
 pub use error::{Error, Result};
-pub use message::{Message, MessageContent, MessageDirection};
+pub use message::{Message, MessageContent, MessageDirection, OutboundFile};
 pub use types::{ChannelId, SessionId, UserId};
@@ -25,6 +25,7 @@ pub enum MessageDirection {
 pub enum MessageContent {
     Text(String),
     Image {
+        /// `http(s)://`, `data:` or a `media://` media store reference.
         url: String,
         caption: Option<String>,
     },
@@ -72,6 +73,35 @@ impl Message {
     }
 }
 
+/// A file uploaded to a chat, as opposed to [`MessageContent`] variants that
+/// point at a URL. Channels decide how to present it (photo, video, voice or
+/// document) and enforce their own size limits.
+#[derive(Debug, Clone)]
+pub struct OutboundFile {
+    pub data: Vec<u8>,
+    pub filename: String,
+    pub mime_type: String,
+    pub caption: Option<String>,
+}
+
+impl OutboundFile {
+    /// Still images most chat apps render inline.
+    pub fn is_image(&self) -> bool {
+        matches!(
+            self.mime_type.as_str(),
+            "image/jpeg" | "image/png" | "image/webp"
+        )
+    }
+
+    pub fn is_video(&self) -> bool {
+        self.mime_type == "video/mp4"
+    }
+
+    pub fn is_audio(&self) -> bool {
+        self.mime_type.starts_with("audio/")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,6 +142,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn outbound_file_kinds_follow_mime_type() {
+        let file = |mime: &str| OutboundFile {
+            data: Vec::new(),
+            filename: "f".into(),
+            mime_type: mime.into(),
+            caption: None,
+        };
+        assert!(file("image/png").is_image());
+        assert!(!file("image/gif").is_image());
+        assert!(file("video/mp4").is_video());
+        assert!(file("audio/ogg").is_audio());
+        assert!(!file("application/pdf").is_image());
+    }
+
     #[test]
     fn message_direction_serializes() {
         let json = serde_json::to_string(&MessageDirection::Incoming).unwrap();
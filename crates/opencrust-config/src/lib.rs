@@ -4,7 +4,10 @@ pub mod watcher;
 
 pub use loader::ConfigLoader;
 pub use model::{
-    AgentConfig, AppConfig, ChannelConfig, EmbeddingProviderConfig, GatewayConfig,
-    LlmProviderConfig, McpServerConfig, MemoryConfig, NamedAgentConfig,
+    AgentConfig, AppConfig, BusyPolicy, ChannelConfig, ChannelRateLimitConfig, ChatRateLimitConfig,
+    EmbeddingProviderConfig, FactsConfig, GatewayConfig, KnowledgeConfig, KnowledgeSourceConfig,
+    LlmProviderConfig, McpServerConfig, MediaConfig, MemoryConfig, MessageRateConfig,
+    NamedAgentConfig, OutboxConfig, RoleConfig, SpeechConfig, SttConfig, TtsConfig, TurnConfig,
+    VoiceReplyMode,
 };
 pub use watcher::ConfigWatcher;
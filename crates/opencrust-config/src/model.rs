@@ -19,6 +19,15 @@ pub struct AppConfig {
     #[serde(default)]
     pub memory: MemoryConfig,
 
+    #[serde(default)]
+    pub knowledge: KnowledgeConfig,
+
+    #[serde(default)]
+    pub speech: SpeechConfig,
+
+    #[serde(default)]
+    pub media: MediaConfig,
+
     #[serde(default)]
     pub agent: AgentConfig,
 
@@ -35,6 +44,23 @@ pub struct AppConfig {
     /// If empty, the single `agent:` block is used as "default".
     #[serde(default)]
     pub agents: HashMap<String, NamedAgentConfig>,
+
+    /// What each role (`owner`, `admin`, `member`, `guest`) may use.
+    #[serde(default)]
+    pub roles: HashMap<String, RoleConfig>,
+
+    /// How fast chat users and chats may send messages to the agent.
+    #[serde(default)]
+    pub chat_rate_limit: ChatRateLimitConfig,
+
+    /// How messages arriving close together in one chat become turns.
+    #[serde(default)]
+    pub turns: TurnConfig,
+
+    /// Delivery of messages the gateway sends on its own, such as
+    /// scheduled heartbeat results.
+    #[serde(default)]
+    pub outbox: OutboxConfig,
 }
 
 impl Default for AppConfig {
@@ -45,11 +71,18 @@ impl Default for AppConfig {
             llm: HashMap::new(),
             embeddings: HashMap::new(),
             memory: MemoryConfig::default(),
+            knowledge: KnowledgeConfig::default(),
+            speech: SpeechConfig::default(),
+            media: MediaConfig::default(),
             agent: AgentConfig::default(),
             data_dir: None,
             log_level: Some("info".to_string()),
             mcp: HashMap::new(),
             agents: HashMap::new(),
+            roles: HashMap::new(),
+            chat_rate_limit: ChatRateLimitConfig::default(),
+            turns: TurnConfig::default(),
+            outbox: OutboxConfig::default(),
         }
     }
 }
@@ -166,6 +199,10 @@ pub struct MemoryConfig {
     /// Default: true when memory is enabled.
     #[serde(default)]
     pub summarization: Option<bool>,
+
+    /// Long-term fact extraction into a user profile.
+    #[serde(default)]
+    pub facts: FactsConfig,
 }
 
 impl Default for MemoryConfig {
@@ -176,10 +213,274 @@ impl Default for MemoryConfig {
             shared_continuity: false,
             recall_limit: None,
             summarization: None,
+            facts: FactsConfig::default(),
+        }
+    }
+}
+
+/// Distill durable facts, preferences and commitments from conversation turns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FactsConfig {
+    /// Run the extraction pass (costs one extra LLM call per turn or batch).
+    /// Existing facts are injected into the prompt either way. Default: false.
+    #[serde(default)]
+    pub extract: bool,
+
+    /// `after_turn` (background, right after each reply) or `batch`
+    /// (scheduler sweep every `batch_interval_secs`). Default: batch.
+    #[serde(default = "default_facts_mode")]
+    pub mode: String,
+
+    /// Seconds between batch extraction sweeps (default: 600).
+    #[serde(default = "default_facts_batch_interval")]
+    pub batch_interval_secs: u64,
+
+    /// Max facts injected into the system prompt per turn (default: 30).
+    #[serde(default = "default_facts_max_injected")]
+    pub max_injected: usize,
+}
+
+impl Default for FactsConfig {
+    fn default() -> Self {
+        Self {
+            extract: false,
+            mode: default_facts_mode(),
+            batch_interval_secs: default_facts_batch_interval(),
+            max_injected: default_facts_max_injected(),
+        }
+    }
+}
+
+fn default_facts_mode() -> String {
+    "batch".to_string()
+}
+
+fn default_facts_batch_interval() -> u64 {
+    600
+}
+
+fn default_facts_max_injected() -> usize {
+    30
+}
+
+/// Folders of notes and documents indexed for the `knowledge_search` tool.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Embedding provider key from `embeddings:`. Falls back to
+    /// `memory.embedding_provider`; without one, search is keyword-only.
+    pub embedding_provider: Option<String>,
+
+    #[serde(default)]
+    pub sources: Vec<KnowledgeSourceConfig>,
+
+    /// Maximum characters per chunk (default: 1500).
+    #[serde(default = "default_knowledge_chunk_chars")]
+    pub chunk_chars: usize,
+
+    /// Characters repeated from the end of the previous chunk (default: 200).
+    #[serde(default = "default_knowledge_chunk_overlap")]
+    pub chunk_overlap: usize,
+
+    /// Re-index files as they change on disk (default: true).
+    #[serde(default = "default_knowledge_watch")]
+    pub watch: bool,
+}
+
+impl Default for KnowledgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            embedding_provider: None,
+            sources: Vec::new(),
+            chunk_chars: default_knowledge_chunk_chars(),
+            chunk_overlap: default_knowledge_chunk_overlap(),
+            watch: default_knowledge_watch(),
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeSourceConfig {
+    /// Short name used in citations and to filter searches.
+    pub name: String,
+    pub path: PathBuf,
+}
+
+fn default_knowledge_chunk_chars() -> usize {
+    1500
+}
+
+fn default_knowledge_chunk_overlap() -> usize {
+    200
+}
+
+fn default_knowledge_watch() -> bool {
+    true
+}
+
+/// Content-addressed store for received attachments under `data_dir/media`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaConfig {
+    /// Largest single file accepted, in megabytes (default: 25).
+    #[serde(default = "default_media_max_file_mb")]
+    pub max_file_mb: u64,
+
+    /// Total store size before least recently used files are evicted,
+    /// in megabytes (default: 1024).
+    #[serde(default = "default_media_max_total_mb")]
+    pub max_total_mb: u64,
+
+    /// Files not accessed for this many days are removed (default: 30, 0 keeps them).
+    #[serde(default = "default_media_retention_days")]
+    pub retention_days: u64,
+
+    /// Longest image edge sent to vision models. Defaults to the provider's limit.
+    pub image_max_dimension: Option<u32>,
+
+    /// Encoding for resized images: `jpeg` (default) or `webp`.
+    #[serde(default = "default_media_image_format")]
+    pub image_format: String,
+
+    /// Keyframes sampled from a video attachment (default: 4).
+    #[serde(default = "default_media_video_frames")]
+    pub video_frames: usize,
+
+    /// Directories the `send_attachment` tool may send files from. Empty
+    /// means the `workspace` folder of the data directory.
+    #[serde(default)]
+    pub attachment_dirs: Vec<PathBuf>,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        Self {
+            max_file_mb: default_media_max_file_mb(),
+            max_total_mb: default_media_max_total_mb(),
+            retention_days: default_media_retention_days(),
+            image_max_dimension: None,
+            image_format: default_media_image_format(),
+            video_frames: default_media_video_frames(),
+            attachment_dirs: Vec::new(),
+        }
+    }
+}
+
+fn default_media_max_file_mb() -> u64 {
+    25
+}
+
+fn default_media_max_total_mb() -> u64 {
+    1024
+}
+
+fn default_media_retention_days() -> u64 {
+    30
+}
+
+fn default_media_image_format() -> String {
+    "jpeg".to_string()
+}
+
+fn default_media_video_frames() -> usize {
+    4
+}
+
+/// Voice message handling.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpeechConfig {
+    /// Speech-to-text backend. When unset, `OPENAI_API_KEY` or `GROQ_API_KEY`
+    /// enables the hosted Whisper APIs.
+    pub stt: Option<SttConfig>,
+
+    /// Text-to-speech backend for voice replies. Voice replies are off without it.
+    pub tts: Option<TtsConfig>,
+
+    /// Default for when to answer with a voice message. Channels can override
+    /// it with a `voice_replies` setting and users with the `/voice` command.
+    #[serde(default)]
+    pub voice_replies: VoiceReplyMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoiceReplyMode {
+    /// Reply in voice when the user sent a voice message.
+    #[default]
+    WhenSpoken,
+    Always,
+    Never,
+}
+
+impl VoiceReplyMode {
+    /// Parse a setting or command argument (`auto` is accepted for `when_spoken`).
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "when_spoken" | "auto" => Some(Self::WhenSpoken),
+            "always" | "on" => Some(Self::Always),
+            "never" | "off" => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WhenSpoken => "when_spoken",
+            Self::Always => "always",
+            Self::Never => "never",
+        }
+    }
+
+    pub fn applies(&self, user_spoke: bool) -> bool {
+        match self {
+            Self::WhenSpoken => user_spoke,
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TtsConfig {
+    /// `openai` (any OpenAI-compatible `/audio/speech` server) or `piper` (local CLI).
+    pub provider: String,
+
+    /// API model name (default: `tts-1`), or the `.onnx` voice model for piper.
+    pub model: Option<String>,
+
+    /// API voice name (default: `alloy`).
+    pub voice: Option<String>,
+    pub api_key: Option<String>,
+
+    /// API base URL (default: `https://api.openai.com/v1`).
+    pub base_url: Option<String>,
+
+    /// piper executable (default: `piper`).
+    pub binary: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SttConfig {
+    /// `openai` (any OpenAI-compatible `/audio/transcriptions` server, such as
+    /// Groq or a local faster-whisper) or `whisper-cpp` (local CLI).
+    pub provider: String,
+
+    /// API model name, or the ggml model file for whisper.cpp.
+    pub model: Option<String>,
+    pub api_key: Option<String>,
+
+    /// API base URL (default: `https://api.openai.com/v1`).
+    pub base_url: Option<String>,
+
+    /// whisper.cpp executable (default: `whisper-cli`).
+    pub binary: Option<String>,
+
+    /// ISO-639-1 language hint; detected automatically when unset.
+    pub language: Option<String>,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AgentConfig {
     pub system_prompt: Option<String>,
@@ -206,6 +507,124 @@ pub struct NamedAgentConfig {
     pub tools: Vec<String>,
 }
 
+/// Permissions of one role. Unset lists allow everything; the owner is never
+/// restricted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoleConfig {
+    /// Named agents the role may use.
+    pub agents: Option<Vec<String>>,
+    /// Tools the agent may call for the role.
+    pub tools: Option<Vec<String>>,
+    /// Chat commands the role may run, by name without the slash.
+    pub commands: Option<Vec<String>>,
+    /// Tokens each user may spend per day (UTC) before the bot stops answering.
+    pub daily_tokens: Option<u64>,
+}
+
+/// Message rate limits for channel users, applied before the agent is
+/// called. Roles without an entry use the built-in defaults; owners and
+/// admins are unlimited unless listed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatRateLimitConfig {
+    /// Per-user limits by role name.
+    #[serde(default)]
+    pub roles: HashMap<String, MessageRateConfig>,
+    /// Limit for each chat, all of its users together.
+    #[serde(default)]
+    pub chat: Option<MessageRateConfig>,
+    /// Overrides keyed by channel type, e.g. `telegram`.
+    #[serde(default)]
+    pub channels: HashMap<String, ChannelRateLimitConfig>,
+    /// First cooldown after a sender runs out of messages; it doubles each
+    /// time they do so again within an hour.
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    #[serde(default = "default_max_cooldown_secs")]
+    pub max_cooldown_secs: u64,
+}
+
+impl Default for ChatRateLimitConfig {
+    fn default() -> Self {
+        Self {
+            roles: HashMap::new(),
+            chat: None,
+            channels: HashMap::new(),
+            cooldown_secs: default_cooldown_secs(),
+            max_cooldown_secs: default_max_cooldown_secs(),
+        }
+    }
+}
+
+/// Per-channel overrides of [`ChatRateLimitConfig`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelRateLimitConfig {
+    #[serde(default)]
+    pub roles: HashMap<String, MessageRateConfig>,
+    #[serde(default)]
+    pub chat: Option<MessageRateConfig>,
+}
+
+/// A message rate. `per_minute: 0` turns the limit off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageRateConfig {
+    pub per_minute: u32,
+    /// Messages that may be sent at once. Defaults to `per_minute`.
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+/// Channel messages in one session are answered one turn at a time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnConfig {
+    /// Wait this long after a text message for more before starting the
+    /// turn, and answer a burst as one message. `0` (the default) answers
+    /// every message on its own.
+    #[serde(default)]
+    pub debounce_ms: u64,
+    /// What a message arriving while a turn is running does.
+    #[serde(default)]
+    pub busy: BusyPolicy,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BusyPolicy {
+    /// Wait for the running turn and answer afterwards.
+    #[default]
+    Queue,
+    /// Cancel the running turn and answer both messages together.
+    Interrupt,
+}
+
+/// Outbound messages are queued in the sessions database and retried with
+/// exponential backoff until they are delivered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxConfig {
+    /// Delivery attempts before a message is dead-lettered.
+    #[serde(default = "default_outbox_max_attempts")]
+    pub max_attempts: u32,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_outbox_max_attempts(),
+        }
+    }
+}
+
+fn default_outbox_max_attempts() -> u32 {
+    8
+}
+
+fn default_cooldown_secs() -> u64 {
+    30
+}
+
+fn default_max_cooldown_secs() -> u64 {
+    3600
+}
+
 fn default_memory_enabled() -> bool {
     true
 }
@@ -239,7 +658,7 @@ pub struct McpServerConfig {
 
 #[cfg(test)]
 mod tests {
-    use super::AppConfig;
+    use super::{AppConfig, VoiceReplyMode};
 
     #[test]
     fn app_config_defaults_include_memory_block() {
@@ -275,6 +694,13 @@ embeddings:
             Some("cohere-main")
         );
         assert!(config.memory.shared_continuity);
+        assert!(!config.memory.facts.extract);
+        assert_eq!(config.memory.facts.mode, "batch");
+        assert!(!config.knowledge.enabled);
+        assert_eq!(config.knowledge.chunk_chars, 1500);
+        assert!(config.speech.stt.is_none());
+        assert_eq!(config.speech.voice_replies, VoiceReplyMode::WhenSpoken);
+        assert_eq!(config.media.max_file_mb, 25);
 
         let cohere = config
             .embeddings
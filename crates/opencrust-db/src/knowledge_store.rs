@@ -0,0 +1,696 @@
+use chrono::{DateTime, Utc};
+use opencrust_common::{Error, Result};
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::VectorStore;
+use crate::memory_store::{
+    blob_to_embedding, cosine_similarity, embedding_to_blob, parse_timestamp, text_match_score,
+};
+use crate::migrations::KNOWLEDGE_SCHEMA_V1;
+
+const DEFAULT_SEARCH_LIMIT: usize = 5;
+const MAX_SEARCH_LIMIT: usize = 50;
+/// Upper bound on chunks scored in Rust when sqlite-vec is unavailable.
+const MAX_SCAN_CHUNKS: i64 = 20_000;
+/// Query terms used to pre-filter chunks for keyword-only search.
+const MAX_QUERY_TERMS: usize = 8;
+const MIN_CHUNK_CHARS: usize = 200;
+
+/// A folder of documents indexed into the knowledge base.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeSource {
+    pub id: String,
+    pub name: String,
+    pub path: String,
+    pub documents: usize,
+    pub chunks: usize,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Insert shape for one chunk of a document.
+#[derive(Debug, Clone)]
+pub struct NewKnowledgeChunk {
+    pub content: String,
+    pub embedding: Option<Vec<f32>>,
+    pub embedding_model: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KnowledgeQuery {
+    pub query_text: Option<String>,
+    pub query_embedding: Option<Vec<f32>>,
+    /// Restrict to these source names; empty searches every source.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    #[serde(default)]
+    pub limit: usize,
+}
+
+/// A matching chunk with enough context to cite it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KnowledgeHit {
+    pub chunk_id: String,
+    pub source: String,
+    /// Document path relative to the source root.
+    pub path: String,
+    pub ordinal: usize,
+    pub content: String,
+    pub score: f32,
+}
+
+impl KnowledgeHit {
+    /// Stable citation label, e.g. `notes/travel/japan.md#2`.
+    pub fn citation(&self) -> String {
+        format!("{}/{}#{}", self.source, self.path, self.ordinal + 1)
+    }
+}
+
+/// Storage for ingested documents, split into embedded chunks for retrieval.
+pub struct KnowledgeStore {
+    conn: Mutex<Connection>,
+    /// Optional vector store for KNN search via sqlite-vec.
+    vector_store: Option<VectorStore>,
+}
+
+impl KnowledgeStore {
+    pub fn open(db_path: &Path) -> Result<Self> {
+        info!("opening knowledge store at {}", db_path.display());
+        let conn = Connection::open(db_path)
+            .map_err(|e| Error::Database(format!("failed to open knowledge database: {e}")))?;
+
+        conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA foreign_keys=ON;")
+            .map_err(|e| Error::Database(format!("failed to set pragmas: {e}")))?;
+
+        let vector_store = match VectorStore::open(db_path) {
+            Ok(vs) if vs.vec_enabled() => Some(vs),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("knowledge vector store init failed (continuing without KNN): {e}");
+                None
+            }
+        };
+
+        let store = Self {
+            conn: Mutex::new(conn),
+            vector_store,
+        };
+        store.run_migrations()?;
+        Ok(store)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| Error::Database(format!("failed to open in-memory database: {e}")))?;
+        conn.execute_batch("PRAGMA foreign_keys=ON;")
+            .map_err(|e| Error::Database(format!("failed to set pragmas: {e}")))?;
+
+        let store = Self {
+            conn: Mutex::new(conn),
+            vector_store: None,
+        };
+        store.run_migrations()?;
+        Ok(store)
+    }
+
+    fn run_migrations(&self) -> Result<()> {
+        self.connection()?
+            .execute_batch(KNOWLEDGE_SCHEMA_V1.sql)
+            .map_err(|e| Error::Database(format!("knowledge migration failed: {e}")))
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| Error::Database("knowledge database lock poisoned".into()))
+    }
+
+    /// Register a source, or update its path if the name already exists.
+    pub fn upsert_source(&self, name: &str, path: &str) -> Result<KnowledgeSource> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::Database(
+                "knowledge source name cannot be empty".into(),
+            ));
+        }
+        {
+            let conn = self.connection()?;
+            conn.execute(
+                "INSERT INTO kb_sources (id, name, path, created_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(name) DO UPDATE SET path = excluded.path",
+                params![
+                    Uuid::new_v4().to_string(),
+                    name,
+                    path,
+                    Utc::now().to_rfc3339()
+                ],
+            )
+            .map_err(|e| Error::Database(format!("failed to save knowledge source: {e}")))?;
+        }
+        self.get_source(name)?
+            .ok_or_else(|| Error::Database(format!("knowledge source '{name}' vanished")))
+    }
+
+    pub fn get_source(&self, name: &str) -> Result<Option<KnowledgeSource>> {
+        Ok(self.query_sources(Some(name))?.into_iter().next())
+    }
+
+    pub fn list_sources(&self) -> Result<Vec<KnowledgeSource>> {
+        self.query_sources(None)
+    }
+
+    fn query_sources(&self, name: Option<&str>) -> Result<Vec<KnowledgeSource>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.id, s.name, s.path, s.created_at,
+                        (SELECT COUNT(*) FROM kb_documents d WHERE d.source_id = s.id),
+                        (SELECT COUNT(*) FROM kb_chunks c
+                           JOIN kb_documents d ON d.id = c.document_id
+                          WHERE d.source_id = s.id)
+                 FROM kb_sources s
+                 WHERE (?1 IS NULL OR s.name = ?1)
+                 ORDER BY s.name",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare source query: {e}")))?;
+
+        let rows = stmt
+            .query_map(params![name], |row| {
+                let created_raw: String = row.get(3)?;
+                let created_at = parse_timestamp(&created_raw).map_err(|e| {
+                    rusqlite::Error::ToSqlConversionFailure(Box::new(std::io::Error::other(
+                        e.to_string(),
+                    )))
+                })?;
+                Ok(KnowledgeSource {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    path: row.get(2)?,
+                    documents: row.get::<_, i64>(4)? as usize,
+                    chunks: row.get::<_, i64>(5)? as usize,
+                    created_at,
+                })
+            })
+            .map_err(|e| Error::Database(format!("failed to query sources: {e}")))?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Database(format!("failed to collect sources: {e}")))
+    }
+
+    /// Remove a source with all of its documents and chunks.
+    pub fn remove_source(&self, name: &str) -> Result<bool> {
+        let chunk_ids = self.chunk_ids(
+            "SELECT c.id FROM kb_chunks c
+               JOIN kb_documents d ON d.id = c.document_id
+               JOIN kb_sources s ON s.id = d.source_id
+              WHERE s.name = ?1",
+            params![name],
+        )?;
+        let deleted = self
+            .connection()?
+            .execute("DELETE FROM kb_sources WHERE name = ?1", params![name])
+            .map_err(|e| Error::Database(format!("failed to delete knowledge source: {e}")))?;
+        self.forget_embeddings(&chunk_ids);
+        Ok(deleted > 0)
+    }
+
+    /// Content hash of every indexed document in a source, keyed by relative path.
+    pub fn document_hashes(&self, source_id: &str) -> Result<HashMap<String, String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare("SELECT path, content_hash FROM kb_documents WHERE source_id = ?1")
+            .map_err(|e| Error::Database(format!("failed to prepare document query: {e}")))?;
+        let rows = stmt
+            .query_map(params![source_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| Error::Database(format!("failed to query documents: {e}")))?;
+        rows.collect::<std::result::Result<HashMap<_, _>, _>>()
+            .map_err(|e| Error::Database(format!("failed to collect documents: {e}")))
+    }
+
+    /// Store a document's chunks, replacing any previous version of it.
+    pub fn replace_document(
+        &self,
+        source_id: &str,
+        path: &str,
+        content_hash: &str,
+        chunks: Vec<NewKnowledgeChunk>,
+    ) -> Result<String> {
+        let old_chunks = self.document_chunk_ids(source_id, path)?;
+        let document_id = Uuid::new_v4().to_string();
+        let mut embedded = Vec::new();
+        {
+            let mut conn = self.connection()?;
+            let tx = conn
+                .transaction()
+                .map_err(|e| Error::Database(format!("failed to begin document write: {e}")))?;
+            tx.execute(
+                "DELETE FROM kb_documents WHERE source_id = ?1 AND path = ?2",
+                params![source_id, path],
+            )
+            .map_err(|e| Error::Database(format!("failed to replace document: {e}")))?;
+            tx.execute(
+                "INSERT INTO kb_documents (id, source_id, path, content_hash, indexed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    document_id,
+                    source_id,
+                    path,
+                    content_hash,
+                    Utc::now().to_rfc3339()
+                ],
+            )
+            .map_err(|e| Error::Database(format!("failed to insert document: {e}")))?;
+
+            for (ordinal, chunk) in chunks.into_iter().enumerate() {
+                let chunk_id = Uuid::new_v4().to_string();
+                tx.execute(
+                    "INSERT INTO kb_chunks (
+                        id, document_id, ordinal, content, embedding, embedding_model,
+                        embedding_dimensions
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        chunk_id,
+                        document_id,
+                        ordinal as i64,
+                        chunk.content,
+                        chunk.embedding.as_deref().map(embedding_to_blob),
+                        chunk.embedding_model,
+                        chunk.embedding.as_ref().map(|e| e.len() as i64),
+                    ],
+                )
+                .map_err(|e| Error::Database(format!("failed to insert chunk: {e}")))?;
+                if let Some(embedding) = chunk.embedding {
+                    embedded.push((chunk_id, embedding));
+                }
+            }
+
+            tx.commit()
+                .map_err(|e| Error::Database(format!("failed to commit document: {e}")))?;
+        }
+
+        self.forget_embeddings(&old_chunks);
+        if let Some(vs) = &self.vector_store {
+            for (id, embedding) in embedded {
+                let dims = embedding.len();
+                if let Err(e) = vs
+                    .ensure_vec_table(dims)
+                    .and_then(|_| vs.insert_embedding(&id, &embedding, dims))
+                {
+                    warn!("failed to index chunk embedding {id}: {e}");
+                }
+            }
+        }
+        Ok(document_id)
+    }
+
+    pub fn remove_document(&self, source_id: &str, path: &str) -> Result<bool> {
+        let chunk_ids = self.document_chunk_ids(source_id, path)?;
+        let deleted = self
+            .connection()?
+            .execute(
+                "DELETE FROM kb_documents WHERE source_id = ?1 AND path = ?2",
+                params![source_id, path],
+            )
+            .map_err(|e| Error::Database(format!("failed to delete document: {e}")))?;
+        self.forget_embeddings(&chunk_ids);
+        Ok(deleted > 0)
+    }
+
+    fn document_chunk_ids(&self, source_id: &str, path: &str) -> Result<Vec<String>> {
+        self.chunk_ids(
+            "SELECT c.id FROM kb_chunks c
+               JOIN kb_documents d ON d.id = c.document_id
+              WHERE d.source_id = ?1 AND d.path = ?2",
+            params![source_id, path],
+        )
+    }
+
+    fn chunk_ids(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<String>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(sql)
+            .map_err(|e| Error::Database(format!("failed to prepare chunk lookup: {e}")))?;
+        let rows = stmt
+            .query_map(params, |row| row.get(0))
+            .map_err(|e| Error::Database(format!("failed to look up chunks: {e}")))?;
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Database(format!("failed to collect chunks: {e}")))
+    }
+
+    fn forget_embeddings(&self, chunk_ids: &[String]) {
+        let Some(vs) = &self.vector_store else {
+            return;
+        };
+        for id in chunk_ids {
+            if let Err(e) = vs.delete_embedding(id) {
+                warn!("failed to delete chunk embedding {id}: {e}");
+            }
+        }
+    }
+
+    /// Find the chunks most relevant to a query, by embedding similarity when
+    /// one is given and keyword overlap otherwise.
+    pub fn search(&self, query: &KnowledgeQuery) -> Result<Vec<KnowledgeHit>> {
+        let limit = if query.limit == 0 {
+            DEFAULT_SEARCH_LIMIT
+        } else {
+            query.limit.min(MAX_SEARCH_LIMIT)
+        };
+
+        if let (Some(vs), Some(qe)) = (&self.vector_store, &query.query_embedding)
+            && let Ok(nearest) = vs.search_nearest(qe, qe.len(), limit.saturating_mul(4))
+            && !nearest.is_empty()
+        {
+            let ids: Vec<String> = nearest.into_iter().map(|(id, _)| id).collect();
+            let candidates = self.candidates(query, Some(&ids), &[])?;
+            if !candidates.is_empty() {
+                return Ok(rank(candidates, query, limit));
+            }
+        }
+
+        let terms: Vec<String> = if query.query_embedding.is_some() {
+            Vec::new()
+        } else {
+            let Some(text) = query.query_text.as_deref() else {
+                return Ok(Vec::new());
+            };
+            text.split_whitespace()
+                .map(|t| t.to_lowercase())
+                .filter(|t| t.chars().count() > 2)
+                .take(MAX_QUERY_TERMS)
+                .collect()
+        };
+        if query.query_embedding.is_none() && terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let candidates = self.candidates(query, None, &terms)?;
+        Ok(rank(candidates, query, limit))
+    }
+
+    fn candidates(
+        &self,
+        query: &KnowledgeQuery,
+        ids: Option<&[String]>,
+        terms: &[String],
+    ) -> Result<Vec<(KnowledgeHit, Option<Vec<f32>>)>> {
+        let mut sql = String::from(
+            "SELECT c.id, s.name, d.path, c.ordinal, c.content, c.embedding
+             FROM kb_chunks c
+             JOIN kb_documents d ON d.id = c.document_id
+             JOIN kb_sources s ON s.id = d.source_id
+             WHERE 1 = 1",
+        );
+        let mut args: Vec<String> = Vec::new();
+        if !query.sources.is_empty() {
+            let list = vec!["?"; query.sources.len()].join(", ");
+            sql.push_str(&format!(" AND s.name IN ({list})"));
+            args.extend(query.sources.iter().cloned());
+        }
+        if let Some(ids) = ids {
+            let list = vec!["?"; ids.len()].join(", ");
+            sql.push_str(&format!(" AND c.id IN ({list})"));
+            args.extend(ids.iter().cloned());
+        }
+        if !terms.is_empty() {
+            let likes = vec!["lower(c.content) LIKE '%' || ? || '%'"; terms.len()].join(" OR ");
+            sql.push_str(&format!(" AND ({likes})"));
+            args.extend(terms.iter().cloned());
+        }
+        sql.push_str(&format!(" LIMIT {MAX_SCAN_CHUNKS}"));
+
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(&sql)
+            .map_err(|e| Error::Database(format!("failed to prepare knowledge search: {e}")))?;
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(args.iter()), |row| {
+                let blob: Option<Vec<u8>> = row.get(5)?;
+                Ok((
+                    KnowledgeHit {
+                        chunk_id: row.get(0)?,
+                        source: row.get(1)?,
+                        path: row.get(2)?,
+                        ordinal: row.get::<_, i64>(3)? as usize,
+                        content: row.get(4)?,
+                        score: 0.0,
+                    },
+                    blob,
+                ))
+            })
+            .map_err(|e| Error::Database(format!("failed to search knowledge: {e}")))?;
+
+        let mut out = Vec::new();
+        for row in rows {
+            let (hit, blob) =
+                row.map_err(|e| Error::Database(format!("failed to collect chunks: {e}")))?;
+            let embedding = blob.as_deref().map(blob_to_embedding).transpose()?;
+            out.push((hit, embedding));
+        }
+        Ok(out)
+    }
+}
+
+fn rank(
+    candidates: Vec<(KnowledgeHit, Option<Vec<f32>>)>,
+    query: &KnowledgeQuery,
+    limit: usize,
+) -> Vec<KnowledgeHit> {
+    let mut scored: Vec<KnowledgeHit> = candidates
+        .into_iter()
+        .map(|(mut hit, embedding)| {
+            let text_score = text_match_score(query.query_text.as_deref(), &hit.content);
+            hit.score = match (&query.query_embedding, &embedding) {
+                (Some(needle), Some(candidate)) => {
+                    cosine_similarity(needle, candidate) * 0.8 + text_score * 0.2
+                }
+                _ => text_score,
+            };
+            hit
+        })
+        .filter(|hit| hit.score > 0.0)
+        .collect();
+    scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    scored.truncate(limit);
+    scored
+}
+
+/// Split text into chunks of at most `max_chars` characters, breaking on
+/// paragraphs first and whitespace second. Each chunk after the first starts
+/// with up to `overlap` characters from the end of the previous one so facts
+/// spanning a boundary stay retrievable.
+pub fn chunk_text(text: &str, max_chars: usize, overlap: usize) -> Vec<String> {
+    let max_chars = max_chars.max(MIN_CHUNK_CHARS);
+    let overlap = overlap.min(max_chars / 2);
+    let text = text.replace("\r\n", "\n");
+
+    let mut pieces: Vec<String> = Vec::new();
+    for paragraph in text.split("\n\n") {
+        let paragraph = paragraph.trim();
+        if paragraph.is_empty() {
+            continue;
+        }
+        if paragraph.chars().count() <= max_chars {
+            pieces.push(paragraph.to_string());
+        } else {
+            pieces.extend(split_long(paragraph, max_chars));
+        }
+    }
+
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for piece in pieces {
+        let piece_len = piece.chars().count();
+        if !current.is_empty() && current.chars().count() + 2 + piece_len > max_chars {
+            let tail = overlap_tail(&current, overlap);
+            chunks.push(std::mem::take(&mut current));
+            if !tail.is_empty() && tail.chars().count() + 2 + piece_len <= max_chars {
+                current = tail;
+            }
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(&piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Break an oversized paragraph at whitespace, hard-splitting single words
+/// that are longer than a chunk.
+fn split_long(paragraph: &str, max_chars: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+    for word in paragraph.split_whitespace() {
+        let word_len = word.chars().count();
+        if current_len > 0 && current_len + 1 + word_len > max_chars {
+            out.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if word_len > max_chars {
+            let chars: Vec<char> = word.chars().collect();
+            for part in chars.chunks(max_chars) {
+                out.push(part.iter().collect());
+            }
+            continue;
+        }
+        if current_len > 0 {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.push_str(word);
+        current_len += word_len;
+    }
+    if !current.is_empty() {
+        out.push(current);
+    }
+    out
+}
+
+/// The last `overlap` characters of a chunk, starting at a word boundary.
+fn overlap_tail(chunk: &str, overlap: usize) -> String {
+    if overlap == 0 {
+        return String::new();
+    }
+    let chars: Vec<char> = chunk.chars().collect();
+    if chars.len() <= overlap {
+        return String::new();
+    }
+    let tail: String = chars[chars.len() - overlap..].iter().collect();
+    match tail.find(char::is_whitespace) {
+        Some(pos) => tail[pos..].trim().to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(content: &str, embedding: Option<Vec<f32>>) -> NewKnowledgeChunk {
+        NewKnowledgeChunk {
+            content: content.to_string(),
+            embedding,
+            embedding_model: Some("unit-test".to_string()),
+        }
+    }
+
+    #[test]
+    fn chunk_text_respects_paragraphs_and_size() {
+        let para = "word ".repeat(60);
+        let text = format!("{para}\n\n{para}\r\n\r\n{para}");
+        let chunks = chunk_text(&text, 400, 50);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 400));
+        // Later chunks carry a little of the previous one.
+        assert!(chunks[1].len() > chunks[0].len());
+    }
+
+    #[test]
+    fn chunk_text_splits_long_paragraphs_and_words() {
+        let long = format!("{} {}", "a".repeat(450), "b ".repeat(300));
+        let chunks = chunk_text(&long, 200, 0);
+        assert!(chunks.iter().all(|c| c.chars().count() <= 200));
+        assert!(chunks[0].chars().all(|c| c == 'a'));
+        assert!(chunk_text("   \n\n  ", 200, 0).is_empty());
+    }
+
+    #[test]
+    fn replace_document_and_keyword_search() {
+        let store = KnowledgeStore::in_memory().expect("store should open");
+        let source = store
+            .upsert_source("notes", "/tmp/notes")
+            .expect("source should save");
+        store
+            .replace_document(
+                &source.id,
+                "travel/japan.md",
+                "h1",
+                vec![
+                    chunk("Flights to Tokyo leave on Friday", None),
+                    chunk("Hotel in Kyoto is booked", None),
+                ],
+            )
+            .expect("document should save");
+
+        let hits = store
+            .search(&KnowledgeQuery {
+                query_text: Some("kyoto hotel".into()),
+                ..KnowledgeQuery::default()
+            })
+            .expect("search should succeed");
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].citation(), "notes/travel/japan.md#2");
+
+        // Re-indexing replaces the old chunks rather than appending.
+        store
+            .replace_document(
+                &source.id,
+                "travel/japan.md",
+                "h2",
+                vec![chunk("Trip cancelled", None)],
+            )
+            .expect("document should save");
+        let listed = store.list_sources().expect("list should succeed");
+        assert_eq!((listed[0].documents, listed[0].chunks), (1, 1));
+        assert_eq!(
+            store.document_hashes(&source.id).unwrap()["travel/japan.md"],
+            "h2"
+        );
+    }
+
+    #[test]
+    fn embedding_search_filters_by_source() {
+        let store = KnowledgeStore::in_memory().expect("store should open");
+        let notes = store.upsert_source("notes", "/n").unwrap();
+        let work = store.upsert_source("work", "/w").unwrap();
+        store
+            .replace_document(
+                &notes.id,
+                "a.md",
+                "x",
+                vec![chunk("alpha", Some(vec![1.0, 0.0]))],
+            )
+            .unwrap();
+        store
+            .replace_document(
+                &work.id,
+                "b.md",
+                "y",
+                vec![chunk("beta", Some(vec![0.9, 0.1]))],
+            )
+            .unwrap();
+
+        let query = KnowledgeQuery {
+            query_text: Some("anything".into()),
+            query_embedding: Some(vec![1.0, 0.0]),
+            ..KnowledgeQuery::default()
+        };
+        let hits = store.search(&query).unwrap();
+        assert_eq!(hits[0].source, "notes");
+        assert_eq!(hits.len(), 2);
+
+        let scoped = store
+            .search(&KnowledgeQuery {
+                sources: vec!["work".into()],
+                ..query
+            })
+            .unwrap();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].content, "beta");
+
+        assert!(store.remove_source("work").unwrap());
+        assert!(store.remove_document(&notes.id, "a.md").unwrap());
+        assert!(store.list_sources().unwrap()[0].chunks == 0);
+    }
+}
@@ -1,11 +1,19 @@
+pub mod knowledge_store;
 pub mod memory_store;
 pub mod migrations;
 pub mod session_store;
 pub mod vector_store;
 
+pub use knowledge_store::{
+    KnowledgeHit, KnowledgeQuery, KnowledgeSource, KnowledgeStore, NewKnowledgeChunk, chunk_text,
+};
 pub use memory_store::{
-    CompactionReport, MemoryEntry, MemoryProvider, MemoryRole, MemoryStore, NewMemoryEntry,
-    RecallQuery, SessionContext,
+    CompactionReport, FactKind, FactQuery, FactSource, FactUpsert, ImportReport, MemoryEntry,
+    MemoryFact, MemoryListQuery, MemoryProvider, MemoryRecord, MemoryRole, MemoryStore,
+    NewMemoryEntry, NewMemoryFact, RecallQuery, RescopeReport, SessionContext,
+};
+pub use session_store::{
+    IdentityLink, Invite, InviteClaim, NewOutboundMessage, OutboundMessage, OutboundStatus,
+    RoleAssignment, ScheduledTask, SessionStore,
 };
-pub use session_store::{ScheduledTask, SessionStore};
 pub use vector_store::VectorStore;
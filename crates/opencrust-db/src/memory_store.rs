@@ -4,16 +4,19 @@ use opencrust_common::{Error, Result};
 use rusqlite::{Connection, params};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{Mutex, MutexGuard};
 use tracing::info;
 use uuid::Uuid;
 
 use crate::VectorStore;
-use crate::migrations::MEMORY_SCHEMA_V1;
+use crate::migrations::{MEMORY_FACTS_V2, MEMORY_SCHEMA_V1};
 
 const DEFAULT_RECALL_LIMIT: usize = 20;
 const MAX_RECALL_LIMIT: usize = 200;
+const DEFAULT_FACT_LIMIT: usize = 50;
+const MAX_FACT_LIMIT: usize = 500;
 
 /// Persisted memory entry used for retrieval and context assembly.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -83,6 +86,8 @@ pub struct RecallQuery {
     pub query_embedding: Option<Vec<f32>>,
     pub session_id: Option<String>,
     pub continuity_key: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
     pub limit: usize,
 }
 
@@ -98,6 +103,191 @@ pub struct CompactionReport {
     pub before: DateTime<Utc>,
 }
 
+/// Filter for browsing and exporting raw memory entries. Unset fields match
+/// everything; `limit` and `offset` page through results newest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryListQuery {
+    pub session_id: Option<String>,
+    pub continuity_key: Option<String>,
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+}
+
+/// One line of a JSONL memory export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MemoryRecord {
+    Entry(MemoryEntry),
+    Fact(MemoryFact),
+}
+
+impl MemoryRecord {
+    /// Parse a JSONL export. Blank lines are ignored; the first malformed line
+    /// fails the whole parse so a partial import never happens silently.
+    pub fn parse_jsonl(text: &str) -> Result<Vec<Self>> {
+        text.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    Error::Database(format!("invalid memory record on line {}: {e}", n + 1))
+                })
+            })
+            .collect()
+    }
+
+    pub fn to_jsonl(records: &[Self]) -> Result<String> {
+        let mut out = String::new();
+        for record in records {
+            out.push_str(&serde_json::to_string(record)?);
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+/// Counts from [`MemoryProvider::import_records`]. Records whose ID already
+/// exists are skipped, so re-importing an export is a no-op.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub entries: usize,
+    pub facts: usize,
+    pub skipped: usize,
+}
+
+/// Counts from [`MemoryProvider::rescope_user`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RescopeReport {
+    pub entries: usize,
+    pub facts: usize,
+}
+
+/// Category of a distilled long-term fact.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FactKind {
+    /// Something true about the user ("lives in Lisbon", "has a dog named Rex").
+    Fact,
+    /// How the user likes things done ("prefers metric units").
+    Preference,
+    /// Something the user or assistant agreed to do ("remind me to renew my passport").
+    Commitment,
+}
+
+impl FactKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Fact => "fact",
+            Self::Preference => "preference",
+            Self::Commitment => "commitment",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fact" => Some(Self::Fact),
+            "preference" => Some(Self::Preference),
+            "commitment" => Some(Self::Commitment),
+            _ => None,
+        }
+    }
+}
+
+/// Where a fact came from. User-authored facts are never overwritten by extraction.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FactSource {
+    Extracted,
+    User,
+}
+
+impl FactSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Extracted => "extracted",
+            Self::User => "user",
+        }
+    }
+
+    fn from_db(value: &str) -> Self {
+        match value {
+            "user" => Self::User,
+            _ => Self::Extracted,
+        }
+    }
+}
+
+/// Durable fact about a user, distilled from conversation turns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryFact {
+    pub id: String,
+    pub continuity_key: Option<String>,
+    pub user_id: Option<String>,
+    pub kind: FactKind,
+    /// Normalized slot the fact fills (e.g. `home_city`). Two active facts in the
+    /// same scope never share a subject; a newer one supersedes the older.
+    pub subject: String,
+    pub content: String,
+    pub confidence: f32,
+    pub source: FactSource,
+    pub source_session_id: Option<String>,
+    pub superseded_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Insert shape for a new fact.
+///
+/// Dedupe and contradiction checks run within the fact's scope: the continuity
+/// key when one is set (shared memory bus), otherwise the user ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewMemoryFact {
+    pub continuity_key: Option<String>,
+    pub user_id: Option<String>,
+    pub kind: FactKind,
+    #[serde(default)]
+    pub subject: String,
+    pub content: String,
+    #[serde(default = "default_fact_confidence")]
+    pub confidence: f32,
+    pub source: FactSource,
+    #[serde(default)]
+    pub source_session_id: Option<String>,
+}
+
+fn default_fact_confidence() -> f32 {
+    1.0
+}
+
+/// Filter for listing facts. At least one of `continuity_key` / `user_id`
+/// should be set; with neither, facts from every scope are returned.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FactQuery {
+    pub continuity_key: Option<String>,
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub include_superseded: bool,
+    #[serde(default)]
+    pub limit: usize,
+}
+
+/// Outcome of [`MemoryProvider::upsert_fact`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum FactUpsert {
+    /// Stored as a new fact.
+    Inserted { id: String },
+    /// Same content already known in this scope; the existing fact was refreshed.
+    Duplicate { id: String },
+    /// Stored, replacing an active fact with the same subject but different content.
+    Superseded { id: String, previous_id: String },
+    /// An extracted fact contradicted a user-authored one and was dropped.
+    Conflict { existing_id: String },
+}
+
 #[async_trait]
 pub trait MemoryProvider: Send + Sync {
     async fn remember(&self, entry: NewMemoryEntry) -> Result<String>;
@@ -110,6 +300,29 @@ pub trait MemoryProvider: Send + Sync {
     ) -> Result<Vec<MemoryEntry>>;
     async fn compact(&self, before: DateTime<Utc>) -> Result<CompactionReport>;
     async fn delete_session_memory(&self, session_id: &str) -> Result<usize>;
+    async fn get_entry(&self, id: &str) -> Result<Option<MemoryEntry>>;
+    async fn delete_entry(&self, id: &str) -> Result<bool>;
+    async fn list_entries(&self, query: MemoryListQuery) -> Result<Vec<MemoryEntry>>;
+    /// Every entry and fact in scope, oldest first. Paging fields are ignored.
+    async fn export_records(&self, scope: MemoryListQuery) -> Result<Vec<MemoryRecord>>;
+    async fn import_records(&self, records: Vec<MemoryRecord>) -> Result<ImportReport>;
+    async fn upsert_fact(&self, fact: NewMemoryFact) -> Result<FactUpsert>;
+    async fn list_facts(&self, query: FactQuery) -> Result<Vec<MemoryFact>>;
+    async fn get_fact(&self, id: &str) -> Result<Option<MemoryFact>>;
+    async fn update_fact(&self, id: &str, content: &str, kind: Option<FactKind>) -> Result<bool>;
+    async fn delete_fact(&self, id: &str) -> Result<bool>;
+    /// Turn entries that have not yet been through fact extraction, oldest first.
+    async fn pending_fact_turns(&self, limit: usize) -> Result<Vec<MemoryEntry>>;
+    async fn mark_facts_extracted(&self, entry_ids: &[String]) -> Result<usize>;
+    /// Move one user's entries and facts from the `from_key` scope (`None`:
+    /// scoped by user ID alone) to the `to_key` continuity key. A moved fact
+    /// that shares its subject with a newer active fact is superseded by it.
+    async fn rescope_user(
+        &self,
+        user_id: &str,
+        from_key: Option<&str>,
+        to_key: &str,
+    ) -> Result<RescopeReport>;
 }
 
 /// Backing store for long-term and session-scoped memory data.
@@ -165,6 +378,17 @@ impl MemoryStore {
         let conn = self.connection()?;
         conn.execute_batch(MEMORY_SCHEMA_V1.sql)
             .map_err(|e| Error::Database(format!("memory migration failed: {e}")))?;
+        conn.execute_batch(MEMORY_FACTS_V2.sql)
+            .map_err(|e| Error::Database(format!("memory facts migration failed: {e}")))?;
+
+        // Marks turns already distilled into facts so batch extraction can resume.
+        if let Err(e) = conn.execute(
+            "ALTER TABLE memory_entries ADD COLUMN facts_extracted_at TEXT",
+            [],
+        ) && !e.to_string().contains("duplicate column")
+        {
+            return Err(Error::Database(format!("memory migration failed: {e}")));
+        }
 
         Ok(())
     }
@@ -227,6 +451,488 @@ impl MemoryStore {
         .map_err(|e| Error::Database(format!("failed to delete session memory: {e}")))
     }
 
+    pub async fn get_entry(&self, id: &str) -> Result<Option<MemoryEntry>> {
+        Ok(self.fetch_entries_by_ids(&[id])?.pop())
+    }
+
+    pub async fn delete_entry(&self, id: &str) -> Result<bool> {
+        let deleted = {
+            let conn = self.connection()?;
+            conn.execute("DELETE FROM memory_entries WHERE id = ?", params![id])
+                .map_err(|e| Error::Database(format!("failed to delete memory entry: {e}")))?
+        };
+        if deleted > 0
+            && let Some(vs) = &self.vector_store
+            && let Err(e) = vs.delete_embedding(id)
+        {
+            tracing::warn!("failed to delete vec embedding for {id}: {e}");
+        }
+        Ok(deleted > 0)
+    }
+
+    pub async fn list_entries(&self, query: MemoryListQuery) -> Result<Vec<MemoryEntry>> {
+        let limit = clamp_limit(query.limit).min(MAX_RECALL_LIMIT) as i64;
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, session_id, channel_id, user_id, continuity_key, role, content,
+                        embedding, embedding_model, embedding_dimensions, metadata, created_at
+                 FROM memory_entries
+                 WHERE (?1 IS NULL OR session_id = ?1)
+                   AND (?2 IS NULL OR continuity_key = ?2)
+                   AND (?3 IS NULL OR user_id = ?3)
+                 ORDER BY datetime(created_at) DESC, id
+                 LIMIT ?4 OFFSET ?5",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare memory list: {e}")))?;
+
+        let rows = stmt
+            .query_map(
+                params![
+                    query.session_id,
+                    query.continuity_key,
+                    query.user_id,
+                    limit,
+                    query.offset as i64
+                ],
+                row_to_entry,
+            )
+            .map_err(|e| Error::Database(format!("failed to list memory entries: {e}")))?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Database(format!("failed to collect memory entries: {e}")))
+    }
+
+    pub async fn export_records(&self, scope: MemoryListQuery) -> Result<Vec<MemoryRecord>> {
+        let conn = self.connection()?;
+        let mut records = Vec::new();
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, session_id, channel_id, user_id, continuity_key, role, content,
+                        embedding, embedding_model, embedding_dimensions, metadata, created_at
+                 FROM memory_entries
+                 WHERE (?1 IS NULL OR session_id = ?1)
+                   AND (?2 IS NULL OR continuity_key = ?2)
+                   AND (?3 IS NULL OR user_id = ?3)
+                 ORDER BY datetime(created_at) ASC, id",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare memory export: {e}")))?;
+        let entries = stmt
+            .query_map(
+                params![scope.session_id, scope.continuity_key, scope.user_id],
+                row_to_entry,
+            )
+            .map_err(|e| Error::Database(format!("failed to export memory entries: {e}")))?;
+        for entry in entries {
+            let entry = entry
+                .map_err(|e| Error::Database(format!("failed to collect memory entries: {e}")))?;
+            records.push(MemoryRecord::Entry(entry));
+        }
+
+        // Facts are not tied to a session, only to the session they came from.
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {FACT_COLUMNS} FROM memory_facts
+                 WHERE (?1 IS NULL OR source_session_id = ?1)
+                   AND (?2 IS NULL OR continuity_key = ?2)
+                   AND (?3 IS NULL OR user_id = ?3)
+                 ORDER BY datetime(created_at) ASC, id"
+            ))
+            .map_err(|e| Error::Database(format!("failed to prepare fact export: {e}")))?;
+        let facts = stmt
+            .query_map(
+                params![scope.session_id, scope.continuity_key, scope.user_id],
+                row_to_fact,
+            )
+            .map_err(|e| Error::Database(format!("failed to export facts: {e}")))?;
+        for fact in facts {
+            let fact =
+                fact.map_err(|e| Error::Database(format!("failed to collect facts: {e}")))?;
+            records.push(MemoryRecord::Fact(fact));
+        }
+
+        Ok(records)
+    }
+
+    /// Restore exported records, keeping their IDs and timestamps. Imported
+    /// turns are marked as already distilled so they are not re-extracted.
+    pub async fn import_records(&self, records: Vec<MemoryRecord>) -> Result<ImportReport> {
+        let mut report = ImportReport::default();
+        let mut embedded = Vec::new();
+        {
+            let mut conn = self.connection()?;
+            let tx = conn
+                .transaction()
+                .map_err(|e| Error::Database(format!("failed to begin import: {e}")))?;
+            let now = Utc::now().to_rfc3339();
+
+            for record in records {
+                let inserted = match &record {
+                    MemoryRecord::Entry(entry) => {
+                        let metadata_json =
+                            serde_json::to_string(&entry.metadata).map_err(|e| {
+                                Error::Database(format!("failed to serialize memory metadata: {e}"))
+                            })?;
+                        tx.execute(
+                            "INSERT OR IGNORE INTO memory_entries (
+                                id, session_id, channel_id, user_id, continuity_key, role,
+                                content, embedding, embedding_model, embedding_dimensions,
+                                metadata, created_at, facts_extracted_at
+                            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                            params![
+                                entry.id,
+                                entry.session_id,
+                                entry.channel_id,
+                                entry.user_id,
+                                entry.continuity_key,
+                                entry.role.as_str(),
+                                entry.content,
+                                entry.embedding.as_ref().map(|e| embedding_to_blob(e)),
+                                entry.embedding_model,
+                                entry.embedding.as_ref().map(|e| e.len() as i64),
+                                metadata_json,
+                                entry.created_at.to_rfc3339(),
+                                now,
+                            ],
+                        )
+                        .map_err(|e| Error::Database(format!("failed to import entry: {e}")))?
+                    }
+                    MemoryRecord::Fact(fact) => tx
+                        .execute(
+                            &format!(
+                                "INSERT OR IGNORE INTO memory_facts ({FACT_COLUMNS})
+                                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+                            ),
+                            params![
+                                fact.id,
+                                fact.continuity_key,
+                                fact.user_id,
+                                fact.kind.as_str(),
+                                fact.subject,
+                                fact.content,
+                                fact.confidence,
+                                fact.source.as_str(),
+                                fact.source_session_id,
+                                fact.superseded_by,
+                                fact.created_at.to_rfc3339(),
+                                fact.updated_at.to_rfc3339(),
+                            ],
+                        )
+                        .map_err(|e| Error::Database(format!("failed to import fact: {e}")))?,
+                };
+
+                match record {
+                    _ if inserted == 0 => report.skipped += 1,
+                    MemoryRecord::Entry(entry) => {
+                        report.entries += 1;
+                        if let Some(embedding) = entry.embedding {
+                            embedded.push((entry.id, embedding));
+                        }
+                    }
+                    MemoryRecord::Fact(_) => report.facts += 1,
+                }
+            }
+
+            tx.commit()
+                .map_err(|e| Error::Database(format!("failed to commit import: {e}")))?;
+        }
+
+        if let Some(vs) = &self.vector_store {
+            for (id, embedding) in embedded {
+                let dims = embedding.len();
+                if let Err(e) = vs
+                    .ensure_vec_table(dims)
+                    .and_then(|_| vs.insert_embedding(&id, &embedding, dims))
+                {
+                    tracing::warn!("failed to index imported embedding {id}: {e}");
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    pub async fn upsert_fact(&self, fact: NewMemoryFact) -> Result<FactUpsert> {
+        self.upsert_fact_sync(fact)
+    }
+
+    pub async fn list_facts(&self, query: FactQuery) -> Result<Vec<MemoryFact>> {
+        let limit = if query.limit == 0 {
+            DEFAULT_FACT_LIMIT
+        } else {
+            query.limit.min(MAX_FACT_LIMIT)
+        } as i64;
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {FACT_COLUMNS} FROM memory_facts
+                 WHERE (?1 IS NULL OR continuity_key = ?1)
+                   AND (?2 IS NULL OR user_id = ?2)
+                   AND (?3 OR superseded_by IS NULL)
+                 ORDER BY datetime(updated_at) DESC
+                 LIMIT ?4"
+            ))
+            .map_err(|e| Error::Database(format!("failed to prepare fact query: {e}")))?;
+
+        let rows = stmt
+            .query_map(
+                params![
+                    query.continuity_key,
+                    query.user_id,
+                    query.include_superseded,
+                    limit
+                ],
+                row_to_fact,
+            )
+            .map_err(|e| Error::Database(format!("failed to query facts: {e}")))?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Database(format!("failed to collect facts: {e}")))
+    }
+
+    pub async fn get_fact(&self, id: &str) -> Result<Option<MemoryFact>> {
+        let conn = self.connection()?;
+        conn.query_row(
+            &format!("SELECT {FACT_COLUMNS} FROM memory_facts WHERE id = ?"),
+            params![id],
+            row_to_fact,
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            other => Err(Error::Database(format!("failed to load fact: {other}"))),
+        })
+    }
+
+    /// Edit a fact in place. Edited facts become user-authored so that later
+    /// extraction passes cannot silently revert them.
+    pub async fn update_fact(
+        &self,
+        id: &str,
+        content: &str,
+        kind: Option<FactKind>,
+    ) -> Result<bool> {
+        if content.trim().is_empty() {
+            return Err(Error::Database("fact content cannot be empty".into()));
+        }
+        let conn = self.connection()?;
+        let updated = conn
+            .execute(
+                "UPDATE memory_facts
+                 SET content = ?2, kind = COALESCE(?3, kind), source = 'user',
+                     confidence = 1.0, updated_at = ?4
+                 WHERE id = ?1",
+                params![
+                    id,
+                    content.trim(),
+                    kind.map(|k| k.as_str()),
+                    Utc::now().to_rfc3339()
+                ],
+            )
+            .map_err(|e| Error::Database(format!("failed to update fact: {e}")))?;
+        Ok(updated > 0)
+    }
+
+    pub async fn delete_fact(&self, id: &str) -> Result<bool> {
+        let conn = self.connection()?;
+        let deleted = conn
+            .execute("DELETE FROM memory_facts WHERE id = ?", params![id])
+            .map_err(|e| Error::Database(format!("failed to delete fact: {e}")))?;
+        Ok(deleted > 0)
+    }
+
+    pub async fn pending_fact_turns(&self, limit: usize) -> Result<Vec<MemoryEntry>> {
+        let conn = self.connection()?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, session_id, channel_id, user_id, continuity_key, role, content,
+                        embedding, embedding_model, embedding_dimensions, metadata, created_at
+                 FROM memory_entries
+                 WHERE facts_extracted_at IS NULL
+                   AND json_extract(metadata, '$.kind') IN ('turn_user', 'turn_assistant')
+                 ORDER BY datetime(created_at) ASC
+                 LIMIT ?1",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare pending turns: {e}")))?;
+
+        let rows = stmt
+            .query_map(params![clamp_limit(limit) as i64], row_to_entry)
+            .map_err(|e| Error::Database(format!("failed to query pending turns: {e}")))?;
+
+        rows.collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Database(format!("failed to collect pending turns: {e}")))
+    }
+
+    pub async fn mark_facts_extracted(&self, entry_ids: &[String]) -> Result<usize> {
+        if entry_ids.is_empty() {
+            return Ok(0);
+        }
+        let conn = self.connection()?;
+        let placeholders: String = entry_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        let sql = format!(
+            "UPDATE memory_entries SET facts_extracted_at = ? WHERE id IN ({placeholders})"
+        );
+        let now = Utc::now().to_rfc3339();
+        let params = std::iter::once(now.as_str()).chain(entry_ids.iter().map(String::as_str));
+        conn.execute(&sql, rusqlite::params_from_iter(params))
+            .map_err(|e| Error::Database(format!("failed to mark turns extracted: {e}")))
+    }
+
+    pub async fn rescope_user(
+        &self,
+        user_id: &str,
+        from_key: Option<&str>,
+        to_key: &str,
+    ) -> Result<RescopeReport> {
+        let mut conn = self.connection()?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| Error::Database(format!("failed to begin rescope: {e}")))?;
+        let entries = tx
+            .execute(
+                "UPDATE memory_entries SET continuity_key = ?3
+                 WHERE user_id = ?1 AND continuity_key IS ?2",
+                params![user_id, from_key, to_key],
+            )
+            .map_err(|e| Error::Database(format!("failed to rescope entries: {e}")))?;
+        let facts = tx
+            .execute(
+                "UPDATE memory_facts SET continuity_key = ?3
+                 WHERE user_id = ?1 AND continuity_key IS ?2",
+                params![user_id, from_key, to_key],
+            )
+            .map_err(|e| Error::Database(format!("failed to rescope facts: {e}")))?;
+
+        // Both scopes may hold an active fact for the same subject; keep the
+        // most recently updated one.
+        let active: Vec<(String, String)> = {
+            let mut stmt = tx
+                .prepare(
+                    "SELECT id, subject FROM memory_facts
+                     WHERE continuity_key = ?1 AND superseded_by IS NULL AND subject != ''
+                     ORDER BY datetime(updated_at) DESC, id DESC",
+                )
+                .map_err(|e| Error::Database(format!("failed to prepare fact lookup: {e}")))?;
+            stmt.query_map(params![to_key], |row| Ok((row.get(0)?, row.get(1)?)))
+                .map_err(|e| Error::Database(format!("failed to look up facts: {e}")))?
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| Error::Database(format!("failed to collect facts: {e}")))?
+        };
+        let now = Utc::now().to_rfc3339();
+        let mut newest: HashMap<&str, &str> = HashMap::new();
+        for (id, subject) in &active {
+            match newest.get(subject.as_str()) {
+                Some(current) => {
+                    tx.execute(
+                        "UPDATE memory_facts SET superseded_by = ?2, updated_at = ?3 WHERE id = ?1",
+                        params![id, current, now],
+                    )
+                    .map_err(|e| Error::Database(format!("failed to supersede fact: {e}")))?;
+                }
+                None => {
+                    newest.insert(subject, id);
+                }
+            }
+        }
+
+        tx.commit()
+            .map_err(|e| Error::Database(format!("failed to commit rescope: {e}")))?;
+        Ok(RescopeReport { entries, facts })
+    }
+
+    fn upsert_fact_sync(&self, fact: NewMemoryFact) -> Result<FactUpsert> {
+        let content = fact.content.trim();
+        if content.is_empty() {
+            return Err(Error::Database("fact content cannot be empty".into()));
+        }
+        let subject = normalize_subject(&fact.subject);
+        let normalized = normalize_fact_text(content);
+        let now = Utc::now().to_rfc3339();
+
+        let conn = self.connection()?;
+        let active: Vec<(String, String, String, String)> = {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, subject, content, source FROM memory_facts
+                     WHERE superseded_by IS NULL
+                       AND ((?1 IS NOT NULL AND continuity_key = ?1)
+                         OR (?1 IS NULL AND continuity_key IS NULL AND user_id IS ?2))",
+                )
+                .map_err(|e| Error::Database(format!("failed to prepare fact lookup: {e}")))?;
+            stmt.query_map(params![fact.continuity_key, fact.user_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .map_err(|e| Error::Database(format!("failed to look up facts: {e}")))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| Error::Database(format!("failed to collect facts: {e}")))?
+        };
+
+        if let Some((id, ..)) = active
+            .iter()
+            .find(|(_, _, existing, _)| normalize_fact_text(existing) == normalized)
+        {
+            conn.execute(
+                "UPDATE memory_facts
+                 SET updated_at = ?2, confidence = max(confidence, ?3)
+                 WHERE id = ?1",
+                params![id, now, fact.confidence],
+            )
+            .map_err(|e| Error::Database(format!("failed to refresh fact: {e}")))?;
+            return Ok(FactUpsert::Duplicate { id: id.clone() });
+        }
+
+        let contradicted = if subject.is_empty() {
+            None
+        } else {
+            active.iter().find(|(_, s, _, _)| *s == subject)
+        };
+        if let Some((existing_id, _, _, source)) = contradicted
+            && FactSource::from_db(source) == FactSource::User
+            && fact.source == FactSource::Extracted
+        {
+            return Ok(FactUpsert::Conflict {
+                existing_id: existing_id.clone(),
+            });
+        }
+
+        let id = Uuid::new_v4().to_string();
+        conn.execute(
+            "INSERT INTO memory_facts (
+                id, continuity_key, user_id, kind, subject, content, confidence,
+                source, source_session_id, created_at, updated_at
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?10)",
+            params![
+                id,
+                fact.continuity_key,
+                fact.user_id,
+                fact.kind.as_str(),
+                subject,
+                content,
+                fact.confidence.clamp(0.0, 1.0),
+                fact.source.as_str(),
+                fact.source_session_id,
+                now,
+            ],
+        )
+        .map_err(|e| Error::Database(format!("failed to insert fact: {e}")))?;
+
+        match contradicted {
+            Some((previous_id, ..)) => {
+                conn.execute(
+                    "UPDATE memory_facts SET superseded_by = ?2, updated_at = ?3 WHERE id = ?1",
+                    params![previous_id, id, now],
+                )
+                .map_err(|e| Error::Database(format!("failed to supersede fact: {e}")))?;
+                Ok(FactUpsert::Superseded {
+                    id,
+                    previous_id: previous_id.clone(),
+                })
+            }
+            None => Ok(FactUpsert::Inserted { id }),
+        }
+    }
+
     fn remember_sync(&self, entry: NewMemoryEntry) -> Result<String> {
         if entry.content.trim().is_empty() {
             return Err(Error::Database("memory content cannot be empty".into()));
@@ -289,7 +995,22 @@ impl MemoryStore {
             {
                 let candidate_ids: Vec<&str> =
                     knn_results.iter().map(|(id, _)| id.as_str()).collect();
-                let candidates = self.fetch_entries_by_ids(&candidate_ids)?;
+                let mut candidates = self.fetch_entries_by_ids(&candidate_ids)?;
+                // The vec table is global; keep only entries in the requested scope.
+                candidates.retain(|entry| {
+                    query
+                        .session_id
+                        .as_ref()
+                        .is_none_or(|s| *s == entry.session_id)
+                        && query
+                            .continuity_key
+                            .as_ref()
+                            .is_none_or(|k| entry.continuity_key.as_ref() == Some(k))
+                        && query
+                            .user_id
+                            .as_ref()
+                            .is_none_or(|u| entry.user_id.as_ref() == Some(u))
+                });
 
                 if !candidates.is_empty() {
                     return self.score_and_rank(candidates, &query, limit);
@@ -301,6 +1022,7 @@ impl MemoryStore {
         let candidates = self.query_candidates_sync(
             query.session_id.as_deref(),
             query.continuity_key.as_deref(),
+            query.user_id.as_deref(),
             query.query_text.as_deref(),
             limit.saturating_mul(4),
         )?;
@@ -329,11 +1051,14 @@ impl MemoryStore {
                 let text_score = text_match_score(query.query_text.as_deref(), &entry.content);
                 let recency_score = recency_score(entry.created_at);
 
-                let score = if query.query_embedding.is_some() {
+                let base = if query.query_embedding.is_some() {
                     semantic_score * 0.7 + text_score * 0.2 + recency_score * 0.1
                 } else {
                     text_score * 0.7 + recency_score * 0.3
                 };
+                // Entries saved deliberately may carry an importance in [0, 1].
+                let importance = entry.metadata["importance"].as_f64().unwrap_or(0.0) as f32;
+                let score = base + importance.clamp(0.0, 1.0) * 0.1;
 
                 (score, entry)
             })
@@ -381,13 +1106,14 @@ impl MemoryStore {
         continuity_key: Option<&str>,
         limit: usize,
     ) -> Result<Vec<MemoryEntry>> {
-        self.query_candidates_sync(session_id, continuity_key, None, limit)
+        self.query_candidates_sync(session_id, continuity_key, None, None, limit)
     }
 
     fn query_candidates_sync(
         &self,
         session_id: Option<&str>,
         continuity_key: Option<&str>,
+        user_id: Option<&str>,
         query_text: Option<&str>,
         limit: usize,
     ) -> Result<Vec<MemoryEntry>> {
@@ -401,15 +1127,16 @@ impl MemoryStore {
                  FROM memory_entries
                  WHERE (?1 IS NULL OR session_id = ?1)
                    AND (?2 IS NULL OR continuity_key = ?2)
-                   AND (?3 IS NULL OR lower(content) LIKE '%' || lower(?3) || '%')
+                   AND (?3 IS NULL OR user_id = ?3)
+                   AND (?4 IS NULL OR lower(content) LIKE '%' || lower(?4) || '%')
                  ORDER BY datetime(created_at) DESC
-                 LIMIT ?4",
+                 LIMIT ?5",
             )
             .map_err(|e| Error::Database(format!("failed to prepare recall query: {e}")))?;
 
         let rows = stmt
             .query_map(
-                params![session_id, continuity_key, query_text, query_limit],
+                params![session_id, continuity_key, user_id, query_text, query_limit],
                 row_to_entry,
             )
             .map_err(|e| Error::Database(format!("failed to execute recall query: {e}")))?;
@@ -449,6 +1176,118 @@ impl MemoryProvider for MemoryStore {
     async fn delete_session_memory(&self, session_id: &str) -> Result<usize> {
         self.delete_session_memory(session_id).await
     }
+
+    async fn get_entry(&self, id: &str) -> Result<Option<MemoryEntry>> {
+        self.get_entry(id).await
+    }
+
+    async fn delete_entry(&self, id: &str) -> Result<bool> {
+        self.delete_entry(id).await
+    }
+
+    async fn list_entries(&self, query: MemoryListQuery) -> Result<Vec<MemoryEntry>> {
+        self.list_entries(query).await
+    }
+
+    async fn export_records(&self, scope: MemoryListQuery) -> Result<Vec<MemoryRecord>> {
+        self.export_records(scope).await
+    }
+
+    async fn import_records(&self, records: Vec<MemoryRecord>) -> Result<ImportReport> {
+        self.import_records(records).await
+    }
+
+    async fn upsert_fact(&self, fact: NewMemoryFact) -> Result<FactUpsert> {
+        self.upsert_fact(fact).await
+    }
+
+    async fn list_facts(&self, query: FactQuery) -> Result<Vec<MemoryFact>> {
+        self.list_facts(query).await
+    }
+
+    async fn get_fact(&self, id: &str) -> Result<Option<MemoryFact>> {
+        self.get_fact(id).await
+    }
+
+    async fn update_fact(&self, id: &str, content: &str, kind: Option<FactKind>) -> Result<bool> {
+        self.update_fact(id, content, kind).await
+    }
+
+    async fn delete_fact(&self, id: &str) -> Result<bool> {
+        self.delete_fact(id).await
+    }
+
+    async fn pending_fact_turns(&self, limit: usize) -> Result<Vec<MemoryEntry>> {
+        self.pending_fact_turns(limit).await
+    }
+
+    async fn mark_facts_extracted(&self, entry_ids: &[String]) -> Result<usize> {
+        self.mark_facts_extracted(entry_ids).await
+    }
+
+    async fn rescope_user(
+        &self,
+        user_id: &str,
+        from_key: Option<&str>,
+        to_key: &str,
+    ) -> Result<RescopeReport> {
+        self.rescope_user(user_id, from_key, to_key).await
+    }
+}
+
+const FACT_COLUMNS: &str = "id, continuity_key, user_id, kind, subject, content, confidence, \
+     source, source_session_id, superseded_by, created_at, updated_at";
+
+fn row_to_fact(row: &rusqlite::Row<'_>) -> rusqlite::Result<MemoryFact> {
+    let kind_str: String = row.get(3)?;
+    let kind = FactKind::parse(&kind_str).unwrap_or(FactKind::Fact);
+    let source_str: String = row.get(7)?;
+
+    let created_at_str: String = row.get(10)?;
+    let updated_at_str: String = row.get(11)?;
+    let parse = |raw: &str| {
+        parse_timestamp(raw).map_err(|e| {
+            rusqlite::Error::ToSqlConversionFailure(Box::new(std::io::Error::other(e.to_string())))
+        })
+    };
+
+    Ok(MemoryFact {
+        id: row.get(0)?,
+        continuity_key: row.get(1)?,
+        user_id: row.get(2)?,
+        kind,
+        subject: row.get(4)?,
+        content: row.get(5)?,
+        confidence: row.get::<_, f64>(6)? as f32,
+        source: FactSource::from_db(&source_str),
+        source_session_id: row.get(8)?,
+        superseded_by: row.get(9)?,
+        created_at: parse(&created_at_str)?,
+        updated_at: parse(&updated_at_str)?,
+    })
+}
+
+/// Canonical form used to detect duplicate facts: lowercase, single spaces,
+/// no trailing punctuation.
+fn normalize_fact_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end_matches(['.', '!', '?', ';', ','])
+        .to_lowercase()
+}
+
+/// Subjects are slot keys like `home_city`; fold anything else into that shape.
+fn normalize_subject(subject: &str) -> String {
+    let mut out = String::with_capacity(subject.len());
+    for ch in subject.trim().chars() {
+        if ch.is_alphanumeric() {
+            out.extend(ch.to_lowercase());
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    out.trim_matches('_').to_string()
 }
 
 fn row_to_entry(row: &rusqlite::Row<'_>) -> rusqlite::Result<MemoryEntry> {
@@ -498,7 +1337,7 @@ fn clamp_limit(limit: usize) -> usize {
     }
 }
 
-fn parse_timestamp(raw: &str) -> Result<DateTime<Utc>> {
+pub(crate) fn parse_timestamp(raw: &str) -> Result<DateTime<Utc>> {
     if let Ok(ts) = DateTime::parse_from_rfc3339(raw) {
         return Ok(ts.with_timezone(&Utc));
     }
@@ -510,7 +1349,7 @@ fn parse_timestamp(raw: &str) -> Result<DateTime<Utc>> {
     Err(Error::Database(format!("invalid timestamp format: {raw}")))
 }
 
-fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
+pub(crate) fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
     let mut bytes = Vec::with_capacity(embedding.len() * 4);
     for v in embedding {
         bytes.extend(v.to_le_bytes());
@@ -518,7 +1357,7 @@ fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
     bytes
 }
 
-fn blob_to_embedding(blob: &[u8]) -> Result<Vec<f32>> {
+pub(crate) fn blob_to_embedding(blob: &[u8]) -> Result<Vec<f32>> {
     if !blob.len().is_multiple_of(4) {
         return Err(Error::Database("invalid embedding blob length".into()));
     }
@@ -530,7 +1369,7 @@ fn blob_to_embedding(blob: &[u8]) -> Result<Vec<f32>> {
     Ok(out)
 }
 
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() || a.is_empty() {
         return 0.0;
     }
@@ -552,7 +1391,7 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     dot / (norm_a.sqrt() * norm_b.sqrt())
 }
 
-fn text_match_score(query_text: Option<&str>, content: &str) -> f32 {
+pub(crate) fn text_match_score(query_text: Option<&str>, content: &str) -> f32 {
     let Some(query) = query_text else {
         return 0.0;
     };
@@ -592,9 +1431,32 @@ impl<T> Pipe for T {}
 
 #[cfg(test)]
 mod tests {
-    use super::{MemoryRole, MemoryStore, NewMemoryEntry, RecallQuery};
+    use super::{
+        FactKind, FactQuery, FactSource, FactUpsert, ImportReport, MemoryListQuery, MemoryRecord,
+        MemoryRole, MemoryStore, NewMemoryEntry, NewMemoryFact, RecallQuery, RescopeReport,
+    };
     use chrono::{Duration, Utc};
 
+    fn fact(subject: &str, content: &str, source: FactSource) -> NewMemoryFact {
+        NewMemoryFact {
+            continuity_key: None,
+            user_id: Some("user-1".to_string()),
+            kind: FactKind::Fact,
+            subject: subject.to_string(),
+            content: content.to_string(),
+            confidence: 0.9,
+            source,
+            source_session_id: Some("session-a".to_string()),
+        }
+    }
+
+    fn user_facts() -> FactQuery {
+        FactQuery {
+            user_id: Some("user-1".to_string()),
+            ..FactQuery::default()
+        }
+    }
+
     fn entry(
         session_id: &str,
         continuity_key: Option<&str>,
@@ -738,6 +1600,7 @@ mod tests {
                 query_embedding: Some(vec![0.95, 0.05, 0.0]),
                 session_id: Some("session-a".to_string()),
                 continuity_key: None,
+                user_id: None,
                 limit: 1,
             })
             .await
@@ -784,4 +1647,303 @@ mod tests {
             .expect("delete should succeed");
         assert_eq!(deleted, 1);
     }
+
+    #[tokio::test]
+    async fn list_entries_filters_and_pages() {
+        let store = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        for (session, content) in [
+            ("session-a", "one"),
+            ("session-a", "two"),
+            ("session-b", "three"),
+        ] {
+            store
+                .remember(entry(session, None, content, MemoryRole::User, None))
+                .await
+                .expect("remember should succeed");
+        }
+
+        let session_a = store
+            .list_entries(MemoryListQuery {
+                session_id: Some("session-a".to_string()),
+                ..MemoryListQuery::default()
+            })
+            .await
+            .expect("list should succeed");
+        assert_eq!(session_a.len(), 2);
+
+        let page = store
+            .list_entries(MemoryListQuery {
+                user_id: Some("user-1".to_string()),
+                limit: 2,
+                offset: 2,
+                ..MemoryListQuery::default()
+            })
+            .await
+            .expect("list should succeed");
+        assert_eq!(page.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn export_import_round_trip_is_idempotent() {
+        let source = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        source
+            .remember(entry(
+                "session-a",
+                None,
+                "lives in Porto",
+                MemoryRole::User,
+                Some(vec![1.0, 0.0]),
+            ))
+            .await
+            .expect("remember should succeed");
+        source
+            .upsert_fact(fact("home_city", "Lives in Porto", FactSource::Extracted))
+            .await
+            .expect("upsert should succeed");
+
+        let records = source
+            .export_records(MemoryListQuery::default())
+            .await
+            .expect("export should succeed");
+        assert_eq!(records.len(), 2);
+        assert!(matches!(records[0], MemoryRecord::Entry(_)));
+        let jsonl = MemoryRecord::to_jsonl(&records).expect("records serialize");
+        let parsed = MemoryRecord::parse_jsonl(&format!("{jsonl}\n")).expect("records parse");
+        assert!(MemoryRecord::parse_jsonl("{\"type\":\"entry\"}").is_err());
+
+        let target = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        let report = target
+            .import_records(parsed.clone())
+            .await
+            .expect("import should succeed");
+        assert_eq!(
+            report,
+            ImportReport {
+                entries: 1,
+                facts: 1,
+                skipped: 0
+            }
+        );
+        let again = target
+            .import_records(parsed)
+            .await
+            .expect("import should succeed");
+        assert_eq!(again.skipped, 2);
+
+        let imported = target
+            .list_entries(MemoryListQuery::default())
+            .await
+            .expect("list should succeed");
+        assert_eq!(imported[0].content, "lives in Porto");
+        assert_eq!(imported[0].embedding, Some(vec![1.0, 0.0]));
+        assert!(
+            target
+                .pending_fact_turns(10)
+                .await
+                .expect("pending should succeed")
+                .is_empty()
+        );
+        assert_eq!(target.list_facts(user_facts()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn upsert_fact_dedupes_equivalent_content() {
+        let store = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        let first = store
+            .upsert_fact(fact("home_city", "Lives in Lisbon.", FactSource::Extracted))
+            .await
+            .expect("upsert should succeed");
+        let FactUpsert::Inserted { id } = first else {
+            panic!("expected insert, got {first:?}");
+        };
+
+        let second = store
+            .upsert_fact(fact(
+                "Home City",
+                "lives in   lisbon",
+                FactSource::Extracted,
+            ))
+            .await
+            .expect("upsert should succeed");
+        assert_eq!(second, FactUpsert::Duplicate { id });
+
+        let facts = store.list_facts(user_facts()).await.unwrap();
+        assert_eq!(facts.len(), 1);
+        assert_eq!(facts[0].subject, "home_city");
+    }
+
+    #[tokio::test]
+    async fn upsert_fact_supersedes_contradicting_subject() {
+        let store = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        store
+            .upsert_fact(fact("home_city", "Lives in Lisbon", FactSource::Extracted))
+            .await
+            .unwrap();
+        let outcome = store
+            .upsert_fact(fact("home_city", "Lives in Porto", FactSource::Extracted))
+            .await
+            .unwrap();
+        assert!(matches!(outcome, FactUpsert::Superseded { .. }));
+
+        let active = store.list_facts(user_facts()).await.unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].content, "Lives in Porto");
+
+        let all = store
+            .list_facts(FactQuery {
+                include_superseded: true,
+                ..user_facts()
+            })
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(
+            all.iter()
+                .any(|f| f.superseded_by.as_deref() == Some(&active[0].id))
+        );
+    }
+
+    #[tokio::test]
+    async fn extracted_fact_does_not_override_user_edit() {
+        let store = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        let FactUpsert::Inserted { id } = store
+            .upsert_fact(fact(
+                "flat_number",
+                "Flat number is 11",
+                FactSource::Extracted,
+            ))
+            .await
+            .unwrap()
+        else {
+            panic!("expected insert");
+        };
+        assert!(
+            store
+                .update_fact(&id, "Flat number is 12", Some(FactKind::Fact))
+                .await
+                .unwrap()
+        );
+
+        let outcome = store
+            .upsert_fact(fact(
+                "flat_number",
+                "Flat number is 11",
+                FactSource::Extracted,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            FactUpsert::Conflict {
+                existing_id: id.clone()
+            }
+        );
+
+        let stored = store.get_fact(&id).await.unwrap().expect("fact exists");
+        assert_eq!(stored.content, "Flat number is 12");
+        assert_eq!(stored.source, FactSource::User);
+
+        assert!(store.delete_fact(&id).await.unwrap());
+        assert!(store.get_fact(&id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn pending_fact_turns_skip_marked_entries() {
+        let store = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        let mut user_turn = entry(
+            "session-a",
+            None,
+            "I moved to Porto",
+            MemoryRole::User,
+            None,
+        );
+        user_turn.metadata = serde_json::json!({ "kind": "turn_user" });
+        let user_id = store.remember(user_turn).await.unwrap();
+        let mut system = entry(
+            "session-a",
+            None,
+            "Session started",
+            MemoryRole::System,
+            None,
+        );
+        system.metadata = serde_json::json!({ "kind": "session_started" });
+        store.remember(system).await.unwrap();
+
+        let pending = store.pending_fact_turns(10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, user_id);
+
+        assert_eq!(store.mark_facts_extracted(&[user_id]).await.unwrap(), 1);
+        assert!(store.pending_fact_turns(10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rescope_user_moves_entries_and_facts_to_the_new_key() {
+        let store = MemoryStore::in_memory().expect("failed to create in-memory memory store");
+        store
+            .remember(entry(
+                "session-a",
+                None,
+                "I moved to Porto",
+                MemoryRole::User,
+                None,
+            ))
+            .await
+            .unwrap();
+        store
+            .upsert_fact(fact("home_city", "Lives in Porto", FactSource::Extracted))
+            .await
+            .unwrap();
+        // The identity already knows an older home city from another account.
+        let older = store
+            .upsert_fact(NewMemoryFact {
+                continuity_key: Some("person:p1".to_string()),
+                user_id: Some("U42".to_string()),
+                ..fact("home_city", "Lives in Lisbon", FactSource::Extracted)
+            })
+            .await
+            .unwrap();
+        let FactUpsert::Inserted { id: older } = older else {
+            panic!("expected insert");
+        };
+        store
+            .connection()
+            .unwrap()
+            .execute(
+                "UPDATE memory_facts SET updated_at = '2020-01-01T00:00:00+00:00' WHERE id = ?1",
+                [&older],
+            )
+            .unwrap();
+
+        let report = store
+            .rescope_user("user-1", None, "person:p1")
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            RescopeReport {
+                entries: 1,
+                facts: 1
+            }
+        );
+
+        let entries = store
+            .list_entries(MemoryListQuery {
+                continuity_key: Some("person:p1".to_string()),
+                ..MemoryListQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+
+        let scope = FactQuery {
+            continuity_key: Some("person:p1".to_string()),
+            ..FactQuery::default()
+        };
+        let active = store.list_facts(scope).await.unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].content, "Lives in Porto");
+        let older = store.get_fact(&older).await.unwrap().expect("fact exists");
+        assert_eq!(older.superseded_by.as_deref(), Some(active[0].id.as_str()));
+    }
 }
@@ -39,3 +39,69 @@ pub const MEMORY_SCHEMA_V1: Migration = Migration {
     name: "memory_schema_v1",
     sql: MEMORY_SCHEMA_V1_SQL,
 };
+
+pub const MEMORY_FACTS_V2_SQL: &str = "
+CREATE TABLE IF NOT EXISTS memory_facts (
+    id TEXT PRIMARY KEY,
+    continuity_key TEXT,
+    user_id TEXT,
+    kind TEXT NOT NULL,
+    subject TEXT NOT NULL DEFAULT '',
+    content TEXT NOT NULL,
+    confidence REAL NOT NULL DEFAULT 1.0,
+    source TEXT NOT NULL DEFAULT 'extracted',
+    source_session_id TEXT,
+    superseded_by TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE INDEX IF NOT EXISTS idx_facts_scope_active
+    ON memory_facts(continuity_key, user_id, superseded_by);
+
+CREATE INDEX IF NOT EXISTS idx_facts_subject
+    ON memory_facts(subject);
+";
+
+pub const MEMORY_FACTS_V2: Migration = Migration {
+    version: 2,
+    name: "memory_facts_v2",
+    sql: MEMORY_FACTS_V2_SQL,
+};
+
+pub const KNOWLEDGE_SCHEMA_V1_SQL: &str = "
+CREATE TABLE IF NOT EXISTS kb_sources (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    path TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+CREATE TABLE IF NOT EXISTS kb_documents (
+    id TEXT PRIMARY KEY,
+    source_id TEXT NOT NULL REFERENCES kb_sources(id) ON DELETE CASCADE,
+    path TEXT NOT NULL,
+    content_hash TEXT NOT NULL,
+    indexed_at TEXT NOT NULL,
+    UNIQUE(source_id, path)
+);
+
+CREATE TABLE IF NOT EXISTS kb_chunks (
+    id TEXT PRIMARY KEY,
+    document_id TEXT NOT NULL REFERENCES kb_documents(id) ON DELETE CASCADE,
+    ordinal INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    embedding BLOB,
+    embedding_model TEXT,
+    embedding_dimensions INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS idx_kb_chunks_document
+    ON kb_chunks(document_id, ordinal);
+";
+
+pub const KNOWLEDGE_SCHEMA_V1: Migration = Migration {
+    version: 1,
+    name: "knowledge_schema_v1",
+    sql: KNOWLEDGE_SCHEMA_V1_SQL,
+};
@@ -13,6 +13,131 @@ pub struct StoredMessage {
     pub metadata: serde_json::Value,
 }
 
+/// A channel account linked to a canonical identity (one person).
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityLink {
+    pub identity_id: String,
+    pub channel: String,
+    pub user_id: String,
+    pub linked_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The role given to a user. `subject` is a `channel:user_id` account or a
+/// `person:<identity>` for linked accounts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleAssignment {
+    pub subject: String,
+    pub role: String,
+    /// Subject of whoever assigned the role, if known.
+    pub granted_by: Option<String>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A persistent invite code. Whoever claims it gets `role` and, if set,
+/// `agent` as their starting agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invite {
+    pub code: String,
+    pub role: String,
+    pub agent: Option<String>,
+    /// `None` allows any number of claims.
+    pub max_uses: Option<u32>,
+    pub uses: u32,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Subject of whoever created the invite, if known.
+    pub created_by: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Invite {
+    /// Whether the invite can still be claimed at `now`.
+    pub fn is_usable(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.revoked_at.is_none()
+            && self.expires_at.is_none_or(|at| at > now)
+            && self.max_uses.is_none_or(|max| self.uses < max)
+    }
+}
+
+/// One use of an invite code, kept as an audit trail.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InviteClaim {
+    pub code: String,
+    pub channel: String,
+    pub user_id: String,
+    pub claimed_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// First retry delay of an outbound message; each further retry doubles it.
+const OUTBOX_RETRY_BASE_SECS: i64 = 10;
+/// Longest delay between two delivery attempts.
+const OUTBOX_RETRY_MAX_SECS: i64 = 3600;
+const OUTBOX_COLUMNS: &str = "id, idempotency_key, channel, session_id, content, metadata, status,
+     attempts, max_attempts, next_attempt_at, last_error, created_at, delivered_at";
+
+/// A message to put in the outbound queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewOutboundMessage {
+    /// Queueing the same key twice keeps the first message only, so a
+    /// producer that runs again after a failure does not send twice.
+    pub idempotency_key: String,
+    pub channel: String,
+    pub session_id: String,
+    pub content: String,
+    /// Routing metadata, as `ChannelSender::send_message` reads it from
+    /// `Message::metadata`.
+    pub metadata: serde_json::Value,
+    /// Delivery attempts before the message is dead-lettered.
+    pub max_attempts: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboundStatus {
+    /// Waiting for its first or next delivery attempt.
+    Pending,
+    Delivered,
+    /// Gave up after `max_attempts` failed attempts.
+    Dead,
+}
+
+impl OutboundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Delivered => "delivered",
+            Self::Dead => "dead",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pending" => Some(Self::Pending),
+            "delivered" => Some(Self::Delivered),
+            "dead" => Some(Self::Dead),
+            _ => None,
+        }
+    }
+}
+
+/// A message in the outbound queue.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboundMessage {
+    pub id: i64,
+    pub idempotency_key: String,
+    pub channel: String,
+    pub session_id: String,
+    pub content: String,
+    pub metadata: serde_json::Value,
+    pub status: OutboundStatus,
+    pub attempts: u32,
+    pub max_attempts: u32,
+    pub next_attempt_at: chrono::DateTime<chrono::Utc>,
+    /// Error of the most recent failed attempt.
+    pub last_error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub delivered_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
 /// Persistent storage for conversation sessions and message history.
 pub struct SessionStore {
     conn: Connection,
@@ -77,7 +202,76 @@ impl SessionStore {
                 );
 
                 CREATE INDEX IF NOT EXISTS idx_tasks_execute_at
-                    ON scheduled_tasks(execute_at) WHERE status = 'pending';",
+                    ON scheduled_tasks(execute_at) WHERE status = 'pending';
+
+                CREATE TABLE IF NOT EXISTS user_preferences (
+                    channel TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    key TEXT NOT NULL,
+                    value TEXT NOT NULL,
+                    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    PRIMARY KEY (channel, user_id, key)
+                );
+
+                CREATE TABLE IF NOT EXISTS identity_links (
+                    channel TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    identity_id TEXT NOT NULL,
+                    linked_at TEXT NOT NULL,
+                    PRIMARY KEY (channel, user_id)
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_identity_links_identity
+                    ON identity_links(identity_id);
+
+                CREATE TABLE IF NOT EXISTS user_roles (
+                    subject TEXT PRIMARY KEY,
+                    role TEXT NOT NULL,
+                    granted_by TEXT,
+                    updated_at TEXT NOT NULL
+                );
+
+                CREATE TABLE IF NOT EXISTS invites (
+                    code TEXT PRIMARY KEY,
+                    role TEXT NOT NULL,
+                    agent TEXT,
+                    max_uses INTEGER,
+                    uses INTEGER NOT NULL DEFAULT 0,
+                    expires_at TEXT,
+                    created_by TEXT,
+                    created_at TEXT NOT NULL,
+                    revoked_at TEXT
+                );
+
+                CREATE TABLE IF NOT EXISTS invite_claims (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    code TEXT NOT NULL,
+                    channel TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    claimed_at TEXT NOT NULL
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_invite_claims_code
+                    ON invite_claims(code);
+
+                CREATE TABLE IF NOT EXISTS outbox (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    idempotency_key TEXT NOT NULL UNIQUE,
+                    channel TEXT NOT NULL,
+                    session_id TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    metadata TEXT NOT NULL DEFAULT '{}',
+                    status TEXT NOT NULL DEFAULT 'pending',
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    max_attempts INTEGER NOT NULL,
+                    next_attempt_at TEXT NOT NULL,
+                    last_error TEXT,
+                    created_at TEXT NOT NULL,
+                    delivered_at TEXT
+                );
+
+                CREATE INDEX IF NOT EXISTS idx_outbox_due
+                    ON outbox(status, next_attempt_at);",
             )
             .map_err(|e| Error::Database(format!("migration failed: {e}")))?;
 
@@ -305,6 +499,487 @@ impl SessionStore {
         Ok(())
     }
 
+    /// Store a per-user preference for a channel (e.g. voice reply mode).
+    pub fn set_user_preference(
+        &self,
+        channel: &str,
+        user_id: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO user_preferences (channel, user_id, key, value)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(channel, user_id, key) DO UPDATE SET
+                   value = excluded.value,
+                   updated_at = datetime('now')",
+                params![channel, user_id, key, value],
+            )
+            .map_err(|e| Error::Database(format!("failed to save user preference: {e}")))?;
+        Ok(())
+    }
+
+    /// Load a per-user preference, if the user has set one.
+    pub fn get_user_preference(
+        &self,
+        channel: &str,
+        user_id: &str,
+        key: &str,
+    ) -> Result<Option<String>> {
+        match self.conn.query_row(
+            "SELECT value FROM user_preferences WHERE channel = ?1 AND user_id = ?2 AND key = ?3",
+            params![channel, user_id, key],
+            |row| row.get(0),
+        ) {
+            Ok(value) => Ok(Some(value)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(Error::Database(format!(
+                "failed to load user preference: {e}"
+            ))),
+        }
+    }
+
+    /// Attach a channel account to `identity_id`, moving it away from any
+    /// identity it belonged to before.
+    pub fn link_identity(&self, identity_id: &str, channel: &str, user_id: &str) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO identity_links (channel, user_id, identity_id, linked_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(channel, user_id) DO UPDATE SET
+                   identity_id = excluded.identity_id,
+                   linked_at = excluded.linked_at",
+                params![
+                    channel,
+                    user_id,
+                    identity_id,
+                    chrono::Utc::now().to_rfc3339()
+                ],
+            )
+            .map_err(|e| Error::Database(format!("failed to link identity: {e}")))?;
+        Ok(())
+    }
+
+    /// Detach a channel account from its identity. Returns whether it was linked.
+    pub fn unlink_identity(&self, channel: &str, user_id: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM identity_links WHERE channel = ?1 AND user_id = ?2",
+                params![channel, user_id],
+            )
+            .map_err(|e| Error::Database(format!("failed to unlink identity: {e}")))?;
+        Ok(removed > 0)
+    }
+
+    /// The identity a channel account is linked to, if any.
+    pub fn identity_for(&self, channel: &str, user_id: &str) -> Result<Option<String>> {
+        match self.conn.query_row(
+            "SELECT identity_id FROM identity_links WHERE channel = ?1 AND user_id = ?2",
+            params![channel, user_id],
+            |row| row.get(0),
+        ) {
+            Ok(id) => Ok(Some(id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(Error::Database(format!("failed to load identity: {e}"))),
+        }
+    }
+
+    /// Accounts linked to one identity, or to every identity when `None`.
+    pub fn identity_links(&self, identity_id: Option<&str>) -> Result<Vec<IdentityLink>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT identity_id, channel, user_id, linked_at
+                 FROM identity_links
+                 WHERE ?1 IS NULL OR identity_id = ?1
+                 ORDER BY linked_at, rowid",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare identity query: {e}")))?;
+        let rows = stmt
+            .query_map(params![identity_id], |row| {
+                let linked_at: String = row.get(3)?;
+                Ok(IdentityLink {
+                    identity_id: row.get(0)?,
+                    channel: row.get(1)?,
+                    user_id: row.get(2)?,
+                    linked_at: parse_timestamp(&linked_at),
+                })
+            })
+            .map_err(|e| Error::Database(format!("failed to load identity links: {e}")))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| Error::Database(format!("failed to read identity link row: {e}")))
+    }
+
+    /// Give `subject` a role, replacing the one it had.
+    pub fn set_role(&self, subject: &str, role: &str, granted_by: Option<&str>) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO user_roles (subject, role, granted_by, updated_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(subject) DO UPDATE SET
+                   role = excluded.role,
+                   granted_by = excluded.granted_by,
+                   updated_at = excluded.updated_at",
+                params![subject, role, granted_by, chrono::Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| Error::Database(format!("failed to set role: {e}")))?;
+        Ok(())
+    }
+
+    /// Take away the role of `subject`. Returns whether it had one.
+    pub fn remove_role(&self, subject: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM user_roles WHERE subject = ?1",
+                params![subject],
+            )
+            .map_err(|e| Error::Database(format!("failed to remove role: {e}")))?;
+        Ok(removed > 0)
+    }
+
+    /// Every role assignment, oldest first.
+    pub fn role_assignments(&self) -> Result<Vec<RoleAssignment>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT subject, role, granted_by, updated_at
+                 FROM user_roles
+                 ORDER BY updated_at, rowid",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare role query: {e}")))?;
+        let rows = stmt
+            .query_map([], |row| {
+                let updated_at: String = row.get(3)?;
+                Ok(RoleAssignment {
+                    subject: row.get(0)?,
+                    role: row.get(1)?,
+                    granted_by: row.get(2)?,
+                    updated_at: parse_timestamp(&updated_at),
+                })
+            })
+            .map_err(|e| Error::Database(format!("failed to load roles: {e}")))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| Error::Database(format!("failed to read role row: {e}")))
+    }
+
+    /// Store a new invite.
+    pub fn create_invite(&self, invite: &Invite) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO invites
+                   (code, role, agent, max_uses, uses, expires_at, created_by, created_at, revoked_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    invite.code,
+                    invite.role,
+                    invite.agent,
+                    invite.max_uses,
+                    invite.uses,
+                    invite.expires_at.map(|at| at.to_rfc3339()),
+                    invite.created_by,
+                    invite.created_at.to_rfc3339(),
+                    invite.revoked_at.map(|at| at.to_rfc3339()),
+                ],
+            )
+            .map_err(|e| Error::Database(format!("failed to create invite: {e}")))?;
+        Ok(())
+    }
+
+    /// One invite by code, or every invite (newest first) when `None`.
+    pub fn invites(&self, code: Option<&str>) -> Result<Vec<Invite>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT code, role, agent, max_uses, uses, expires_at, created_by, created_at, revoked_at
+                 FROM invites
+                 WHERE ?1 IS NULL OR code = ?1
+                 ORDER BY created_at DESC, rowid DESC",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare invite query: {e}")))?;
+        let rows = stmt
+            .query_map(params![code], |row| {
+                let expires_at: Option<String> = row.get(5)?;
+                let created_at: String = row.get(7)?;
+                let revoked_at: Option<String> = row.get(8)?;
+                Ok(Invite {
+                    code: row.get(0)?,
+                    role: row.get(1)?,
+                    agent: row.get(2)?,
+                    max_uses: row.get(3)?,
+                    uses: row.get(4)?,
+                    expires_at: expires_at.as_deref().map(parse_timestamp),
+                    created_by: row.get(6)?,
+                    created_at: parse_timestamp(&created_at),
+                    revoked_at: revoked_at.as_deref().map(parse_timestamp),
+                })
+            })
+            .map_err(|e| Error::Database(format!("failed to load invites: {e}")))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| Error::Database(format!("failed to read invite row: {e}")))
+    }
+
+    /// Revoke an invite so it can no longer be claimed. Returns whether it
+    /// existed and was not revoked yet.
+    pub fn revoke_invite(&self, code: &str) -> Result<bool> {
+        let revoked = self
+            .conn
+            .execute(
+                "UPDATE invites SET revoked_at = ?2 WHERE code = ?1 AND revoked_at IS NULL",
+                params![code, chrono::Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| Error::Database(format!("failed to revoke invite: {e}")))?;
+        Ok(revoked > 0)
+    }
+
+    /// Use an invite for a channel account and record the claim. Returns
+    /// the invite as it was before the claim, or `None` if it is unknown,
+    /// revoked, expired or used up.
+    pub fn claim_invite(&self, code: &str, channel: &str, user_id: &str) -> Result<Option<Invite>> {
+        let now = chrono::Utc::now();
+        let Some(invite) = self.invites(Some(code))?.pop() else {
+            return Ok(None);
+        };
+        if !invite.is_usable(now) {
+            return Ok(None);
+        }
+        // Guard on the use count read above so concurrent claims cannot
+        // exceed max_uses.
+        let claimed = self
+            .conn
+            .execute(
+                "UPDATE invites SET uses = uses + 1 WHERE code = ?1 AND uses = ?2",
+                params![code, invite.uses],
+            )
+            .map_err(|e| Error::Database(format!("failed to claim invite: {e}")))?;
+        if claimed == 0 {
+            return Ok(None);
+        }
+        self.conn
+            .execute(
+                "INSERT INTO invite_claims (code, channel, user_id, claimed_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![code, channel, user_id, now.to_rfc3339()],
+            )
+            .map_err(|e| Error::Database(format!("failed to record invite claim: {e}")))?;
+        Ok(Some(invite))
+    }
+
+    /// Claims of one invite, or of every invite when `None`, oldest first.
+    pub fn invite_claims(&self, code: Option<&str>) -> Result<Vec<InviteClaim>> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT code, channel, user_id, claimed_at
+                 FROM invite_claims
+                 WHERE ?1 IS NULL OR code = ?1
+                 ORDER BY id",
+            )
+            .map_err(|e| Error::Database(format!("failed to prepare claim query: {e}")))?;
+        let rows = stmt
+            .query_map(params![code], |row| {
+                let claimed_at: String = row.get(3)?;
+                Ok(InviteClaim {
+                    code: row.get(0)?,
+                    channel: row.get(1)?,
+                    user_id: row.get(2)?,
+                    claimed_at: parse_timestamp(&claimed_at),
+                })
+            })
+            .map_err(|e| Error::Database(format!("failed to load invite claims: {e}")))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| Error::Database(format!("failed to read invite claim row: {e}")))
+    }
+
+    /// Put a message in the outbound queue, due now. Returns `false` if a
+    /// message with the same idempotency key was queued before.
+    pub fn enqueue_outbound(&self, message: &NewOutboundMessage) -> Result<bool> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let inserted = self
+            .conn
+            .execute(
+                "INSERT OR IGNORE INTO outbox
+                   (idempotency_key, channel, session_id, content, metadata, max_attempts,
+                    next_attempt_at, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+                params![
+                    message.idempotency_key,
+                    message.channel,
+                    message.session_id,
+                    message.content,
+                    message.metadata.to_string(),
+                    message.max_attempts.max(1),
+                    now,
+                ],
+            )
+            .map_err(|e| Error::Database(format!("failed to enqueue outbound message: {e}")))?;
+        Ok(inserted > 0)
+    }
+
+    /// Pending messages whose next attempt is due, oldest first.
+    pub fn due_outbound(&self, limit: usize) -> Result<Vec<OutboundMessage>> {
+        self.query_outbound(
+            &format!(
+                "SELECT {OUTBOX_COLUMNS} FROM outbox
+                 WHERE status = 'pending'
+                   AND datetime(next_attempt_at) <= datetime('now')
+                 ORDER BY next_attempt_at, id
+                 LIMIT ?1"
+            ),
+            params![limit as i64],
+        )
+    }
+
+    /// Messages in the outbound queue, newest first, optionally only those
+    /// with `status`.
+    pub fn outbound_messages(
+        &self,
+        status: Option<OutboundStatus>,
+        limit: usize,
+    ) -> Result<Vec<OutboundMessage>> {
+        self.query_outbound(
+            &format!(
+                "SELECT {OUTBOX_COLUMNS} FROM outbox
+                 WHERE ?1 IS NULL OR status = ?1
+                 ORDER BY id DESC
+                 LIMIT ?2"
+            ),
+            params![status.map(|s| s.as_str()), limit as i64],
+        )
+    }
+
+    /// One message of the outbound queue by id.
+    pub fn outbound_message(&self, id: i64) -> Result<Option<OutboundMessage>> {
+        Ok(self
+            .query_outbound(
+                &format!("SELECT {OUTBOX_COLUMNS} FROM outbox WHERE id = ?1"),
+                params![id],
+            )?
+            .pop())
+    }
+
+    fn query_outbound(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<OutboundMessage>> {
+        let mut stmt = self
+            .conn
+            .prepare(sql)
+            .map_err(|e| Error::Database(format!("failed to prepare outbox query: {e}")))?;
+        let rows = stmt
+            .query_map(params, |row| {
+                let metadata: String = row.get(5)?;
+                let status: String = row.get(6)?;
+                let next_attempt_at: String = row.get(9)?;
+                let created_at: String = row.get(11)?;
+                let delivered_at: Option<String> = row.get(12)?;
+                Ok(OutboundMessage {
+                    id: row.get(0)?,
+                    idempotency_key: row.get(1)?,
+                    channel: row.get(2)?,
+                    session_id: row.get(3)?,
+                    content: row.get(4)?,
+                    metadata: serde_json::from_str(&metadata).unwrap_or(serde_json::Value::Null),
+                    status: OutboundStatus::parse(&status).unwrap_or(OutboundStatus::Pending),
+                    attempts: row.get(7)?,
+                    max_attempts: row.get(8)?,
+                    next_attempt_at: parse_timestamp(&next_attempt_at),
+                    last_error: row.get(10)?,
+                    created_at: parse_timestamp(&created_at),
+                    delivered_at: delivered_at.as_deref().map(parse_timestamp),
+                })
+            })
+            .map_err(|e| Error::Database(format!("failed to load outbound messages: {e}")))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| Error::Database(format!("failed to read outbound message row: {e}")))
+    }
+
+    /// Record a successful delivery.
+    pub fn mark_outbound_delivered(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute(
+                "UPDATE outbox
+                 SET status = 'delivered', attempts = attempts + 1, delivered_at = ?2
+                 WHERE id = ?1",
+                params![id, chrono::Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| Error::Database(format!("failed to mark message delivered: {e}")))?;
+        Ok(())
+    }
+
+    /// Record a failed delivery attempt. The message is retried with
+    /// exponential backoff (10s, 20s, 40s, ... up to an hour) until it has
+    /// had `max_attempts` attempts, then dead-lettered. Returns whether it
+    /// will be retried.
+    pub fn retry_or_dead_letter_outbound(&self, id: i64, error: &str) -> Result<bool> {
+        let (attempts, max_attempts): (u32, u32) = self
+            .conn
+            .query_row(
+                "SELECT attempts, max_attempts FROM outbox WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|e| Error::Database(format!("failed to read delivery attempts: {e}")))?;
+
+        let attempts = attempts + 1;
+        let retry = attempts < max_attempts;
+        let backoff_secs =
+            (OUTBOX_RETRY_BASE_SECS << (attempts - 1).min(16)).min(OUTBOX_RETRY_MAX_SECS);
+        let next_attempt = chrono::Utc::now() + chrono::Duration::seconds(backoff_secs);
+        let status = if retry {
+            OutboundStatus::Pending
+        } else {
+            OutboundStatus::Dead
+        };
+        self.conn
+            .execute(
+                "UPDATE outbox
+                 SET status = ?2, attempts = ?3, next_attempt_at = ?4, last_error = ?5
+                 WHERE id = ?1",
+                params![
+                    id,
+                    status.as_str(),
+                    attempts,
+                    next_attempt.to_rfc3339(),
+                    error
+                ],
+            )
+            .map_err(|e| Error::Database(format!("failed to record delivery failure: {e}")))?;
+        Ok(retry)
+    }
+
+    /// Give a dead-lettered message a fresh set of attempts, starting now.
+    /// Returns whether the message was dead.
+    pub fn requeue_outbound(&self, id: i64) -> Result<bool> {
+        let requeued = self
+            .conn
+            .execute(
+                "UPDATE outbox SET status = 'pending', attempts = 0, next_attempt_at = ?2
+                 WHERE id = ?1 AND status = 'dead'",
+                params![id, chrono::Utc::now().to_rfc3339()],
+            )
+            .map_err(|e| Error::Database(format!("failed to requeue outbound message: {e}")))?;
+        Ok(requeued > 0)
+    }
+
+    /// Delete delivered messages older than `older_than_days`. Dead letters
+    /// are kept until they are requeued or handled by hand.
+    pub fn cleanup_delivered_outbound(&self, older_than_days: i64) -> Result<usize> {
+        let deleted = self
+            .conn
+            .execute(
+                "DELETE FROM outbox
+                 WHERE status = 'delivered'
+                   AND datetime(delivered_at) < datetime('now', ?1)",
+                params![format!("-{older_than_days} days")],
+            )
+            .map_err(|e| Error::Database(format!("failed to cleanup outbox: {e}")))?;
+        Ok(deleted)
+    }
+
     /// Load the metadata JSON for a session.
     pub fn load_session_metadata(&self, session_id: &str) -> Result<Option<serde_json::Value>> {
         let mut stmt = self
@@ -609,7 +1284,7 @@ fn parse_timestamp(value: &str) -> chrono::DateTime<chrono::Utc> {
 
 #[cfg(test)]
 mod tests {
-    use super::{ScheduledTask, SessionStore};
+    use super::{Invite, NewOutboundMessage, OutboundStatus, ScheduledTask, SessionStore};
     use chrono::Duration;
 
     #[test]
@@ -656,6 +1331,242 @@ mod tests {
         assert_eq!(messages[1].content, "hi there");
     }
 
+    #[test]
+    fn identity_links_move_accounts_between_identities() {
+        let store = SessionStore::in_memory().expect("in-memory store should open");
+        assert_eq!(store.identity_for("telegram", "42").unwrap(), None);
+
+        store.link_identity("p1", "telegram", "42").unwrap();
+        store.link_identity("p1", "slack", "U42").unwrap();
+        store.link_identity("p2", "discord", "7").unwrap();
+        assert_eq!(
+            store.identity_for("slack", "U42").unwrap().as_deref(),
+            Some("p1")
+        );
+        let accounts: Vec<_> = store
+            .identity_links(Some("p1"))
+            .unwrap()
+            .into_iter()
+            .map(|link| (link.channel, link.user_id))
+            .collect();
+        assert_eq!(
+            accounts,
+            vec![
+                ("telegram".to_string(), "42".to_string()),
+                ("slack".to_string(), "U42".to_string())
+            ]
+        );
+
+        // Linking again moves the account.
+        store.link_identity("p2", "slack", "U42").unwrap();
+        assert_eq!(store.identity_links(Some("p1")).unwrap().len(), 1);
+        assert_eq!(store.identity_links(None).unwrap().len(), 3);
+
+        assert!(store.unlink_identity("slack", "U42").unwrap());
+        assert!(!store.unlink_identity("slack", "U42").unwrap());
+        assert_eq!(store.identity_for("slack", "U42").unwrap(), None);
+    }
+
+    #[test]
+    fn roles_are_replaced_and_removed() {
+        let store = SessionStore::in_memory().expect("in-memory store should open");
+        store.set_role("telegram:1", "owner", None).unwrap();
+        store
+            .set_role("slack:U2", "guest", Some("telegram:1"))
+            .unwrap();
+        store
+            .set_role("slack:U2", "member", Some("telegram:1"))
+            .unwrap();
+
+        let roles: Vec<_> = store
+            .role_assignments()
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.subject, a.role, a.granted_by))
+            .collect();
+        assert_eq!(
+            roles,
+            vec![
+                ("telegram:1".to_string(), "owner".to_string(), None),
+                (
+                    "slack:U2".to_string(),
+                    "member".to_string(),
+                    Some("telegram:1".to_string())
+                ),
+            ]
+        );
+
+        assert!(store.remove_role("slack:U2").unwrap());
+        assert!(!store.remove_role("slack:U2").unwrap());
+        assert_eq!(store.role_assignments().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn invites_are_limited_by_uses_expiry_and_revocation() {
+        let store = SessionStore::in_memory().expect("in-memory store should open");
+        let invite = |code: &str, max_uses, expires_at| Invite {
+            code: code.to_string(),
+            role: "member".to_string(),
+            agent: Some("helper".to_string()),
+            max_uses,
+            uses: 0,
+            expires_at,
+            created_by: Some("telegram:1".to_string()),
+            created_at: chrono::Utc::now(),
+            revoked_at: None,
+        };
+        store
+            .create_invite(&invite("TWOUSES2", Some(2), None))
+            .unwrap();
+        store
+            .create_invite(&invite(
+                "EXPIRED2",
+                None,
+                Some(chrono::Utc::now() - Duration::minutes(1)),
+            ))
+            .unwrap();
+        store
+            .create_invite(&invite("REVOKED2", None, None))
+            .unwrap();
+
+        assert!(
+            store
+                .claim_invite("TWOUSES2", "slack", "U1")
+                .unwrap()
+                .is_some()
+        );
+        let claimed = store.claim_invite("TWOUSES2", "telegram", "2").unwrap();
+        assert_eq!(claimed.unwrap().agent.as_deref(), Some("helper"));
+        assert!(
+            store
+                .claim_invite("TWOUSES2", "telegram", "3")
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            store
+                .claim_invite("EXPIRED2", "slack", "U1")
+                .unwrap()
+                .is_none()
+        );
+        assert!(store.revoke_invite("REVOKED2").unwrap());
+        assert!(!store.revoke_invite("REVOKED2").unwrap());
+        assert!(
+            store
+                .claim_invite("REVOKED2", "slack", "U1")
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            store
+                .claim_invite("UNKNOWN2", "slack", "U1")
+                .unwrap()
+                .is_none()
+        );
+
+        assert_eq!(store.invites(None).unwrap().len(), 3);
+        assert_eq!(store.invites(Some("TWOUSES2")).unwrap()[0].uses, 2);
+        let claims: Vec<_> = store
+            .invite_claims(None)
+            .unwrap()
+            .into_iter()
+            .map(|c| (c.channel, c.user_id))
+            .collect();
+        assert_eq!(
+            claims,
+            vec![
+                ("slack".to_string(), "U1".to_string()),
+                ("telegram".to_string(), "2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn outbound_messages_are_retried_then_dead_lettered() {
+        let store = SessionStore::in_memory().expect("in-memory store should open");
+        let message = |key: &str| NewOutboundMessage {
+            idempotency_key: key.to_string(),
+            channel: "telegram".to_string(),
+            session_id: "telegram-42".to_string(),
+            content: "Good morning!".to_string(),
+            metadata: serde_json::json!({"telegram_chat_id": 42}),
+            max_attempts: 2,
+        };
+        assert!(store.enqueue_outbound(&message("task:1")).unwrap());
+        assert!(!store.enqueue_outbound(&message("task:1")).unwrap());
+        assert!(store.enqueue_outbound(&message("task:2")).unwrap());
+
+        let due = store.due_outbound(10).unwrap();
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[0].idempotency_key, "task:1");
+        assert_eq!(due[0].metadata["telegram_chat_id"], 42);
+        let (first, second) = (due[0].id, due[1].id);
+
+        store.mark_outbound_delivered(first).unwrap();
+        assert!(
+            store
+                .retry_or_dead_letter_outbound(second, "telegram is down")
+                .unwrap()
+        );
+        // Backing off: not due again yet.
+        assert!(store.due_outbound(10).unwrap().is_empty());
+        assert!(
+            !store
+                .retry_or_dead_letter_outbound(second, "telegram is still down")
+                .unwrap()
+        );
+
+        let dead = store
+            .outbound_messages(Some(OutboundStatus::Dead), 10)
+            .unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 2);
+        assert_eq!(
+            dead[0].last_error.as_deref(),
+            Some("telegram is still down")
+        );
+        let delivered = store.outbound_message(first).unwrap().unwrap();
+        assert_eq!(delivered.status, OutboundStatus::Delivered);
+        assert!(delivered.delivered_at.is_some());
+
+        assert!(store.requeue_outbound(second).unwrap());
+        assert!(!store.requeue_outbound(first).unwrap());
+        assert_eq!(store.due_outbound(10).unwrap()[0].id, second);
+        assert_eq!(store.cleanup_delivered_outbound(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn user_preferences_round_trip() {
+        let store = SessionStore::in_memory().expect("in-memory store should open");
+        assert_eq!(
+            store
+                .get_user_preference("telegram", "42", "voice_replies")
+                .unwrap(),
+            None
+        );
+
+        store
+            .set_user_preference("telegram", "42", "voice_replies", "always")
+            .unwrap();
+        store
+            .set_user_preference("telegram", "42", "voice_replies", "never")
+            .unwrap();
+
+        assert_eq!(
+            store
+                .get_user_preference("telegram", "42", "voice_replies")
+                .unwrap()
+                .as_deref(),
+            Some("never")
+        );
+        assert_eq!(
+            store
+                .get_user_preference("whatsapp", "42", "voice_replies")
+                .unwrap(),
+            None
+        );
+    }
+
     #[test]
     fn schedule_and_poll_tasks() {
         let store = SessionStore::in_memory().expect("in-memory store should open");
@@ -170,6 +170,55 @@ impl VectorStore {
         Ok(())
     }
 
+    /// Remove an entry's embedding from every vec0 table and the ID mapping.
+    pub fn delete_embedding(&self, id: &str) -> Result<()> {
+        if !self.vec_enabled {
+            return Ok(());
+        }
+
+        let conn = self.connection()?;
+        let rowid: Option<i64> = match conn.query_row(
+            "SELECT rowid FROM vec_id_map WHERE entry_id = ?",
+            params![id],
+            |row| row.get(0),
+        ) {
+            Ok(rowid) => Some(rowid),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(Error::Database(format!("failed to get vec rowid: {e}"))),
+        };
+        let Some(rowid) = rowid else {
+            return Ok(());
+        };
+
+        // vec0 also creates shadow tables (`_chunks`, `_rowids`, ...); only the
+        // virtual tables themselves are named `vec_embeddings_<dims>`.
+        let tables: Vec<String> = {
+            let mut stmt = conn
+                .prepare("SELECT name FROM sqlite_master WHERE name LIKE 'vec_embeddings_%'")
+                .map_err(|e| Error::Database(format!("failed to list vec tables: {e}")))?;
+            stmt.query_map([], |row| row.get::<_, String>(0))
+                .map_err(|e| Error::Database(format!("failed to list vec tables: {e}")))?
+                .filter_map(|r| r.ok())
+                .filter(|name| {
+                    name.trim_start_matches("vec_embeddings_")
+                        .chars()
+                        .all(|c| c.is_ascii_digit())
+                })
+                .collect()
+        };
+        for table in tables {
+            conn.execute(
+                &format!("DELETE FROM [{table}] WHERE rowid = ?"),
+                params![rowid],
+            )
+            .map_err(|e| Error::Database(format!("failed to delete vec embedding: {e}")))?;
+        }
+        conn.execute("DELETE FROM vec_id_map WHERE rowid = ?", params![rowid])
+            .map_err(|e| Error::Database(format!("failed to delete vec id mapping: {e}")))?;
+
+        Ok(())
+    }
+
     /// KNN search: find the nearest `limit` embeddings to `query`.
     /// Returns `(entry_id, distance)` pairs ordered by distance ascending.
     pub fn search_nearest(
@@ -264,5 +313,11 @@ mod tests {
         let results = store.search_nearest(&[0.9, 0.1, 0.0], 3, 2).unwrap();
         assert_eq!(results.len(), 2);
         assert_eq!(results[0].0, "id-1"); // closest
+
+        // Delete
+        store.delete_embedding("id-1").unwrap();
+        let results = store.search_nearest(&[0.9, 0.1, 0.0], 3, 2).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, "id-2");
     }
 }
@@ -0,0 +1,682 @@
+//! Role-based access control for chat users.
+//!
+//! A channel account, or a person when accounts are linked, holds one
+//! [`Role`]. Assignments live in the `user_roles` table and are mirrored in
+//! [`AccessControl`]; the `roles:` config section limits which agents, tools
+//! and commands each role may use and how many tokens it may spend a day.
+//! Invites hand out roles: their codes live in the `invites` table, so they
+//! outlast restarts, and every claim is recorded in `invite_claims`.
+//! Messages to the agent are rate limited per user and per chat, with limits
+//! by role from the `chat_rate_limit:` config section.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use dashmap::DashMap;
+use opencrust_agents::ToolContext;
+use opencrust_common::{Error, Result};
+use opencrust_config::{ChatRateLimitConfig, MessageRateConfig, RoleConfig};
+use opencrust_db::{Invite, RoleAssignment, SessionStore};
+use opencrust_security::{
+    Allowlist, Cooldown, Rate, RateLimiter, Role, Verdict, generate_invite_code,
+};
+use tracing::{info, warn};
+
+use crate::state::{AppState, account_key, split_account_key};
+
+/// Commands guests may run unless the config says otherwise.
+const GUEST_COMMANDS: &[&str] = &["start", "help", "clear", "usage", "link", "unlink"];
+
+/// Role assignments, per-role permissions and daily token spend.
+pub struct AccessControl {
+    roles: DashMap<String, Role>,
+    permissions: HashMap<Role, RoleConfig>,
+    /// `allowlist.json` from before roles existed. Its users get a role the
+    /// first time they write and are then dropped from it.
+    legacy: Mutex<Option<Allowlist>>,
+    /// Tokens spent today, keyed by subject.
+    spent: DashMap<String, (NaiveDate, u64)>,
+    /// Session token totals already charged to someone.
+    charged: DashMap<String, u64>,
+    limiter: RateLimiter,
+}
+
+/// A message turned away by the rate limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    /// The chat as a whole is over its limit, rather than the sender.
+    pub chat: bool,
+    pub retry_after: Duration,
+    /// First rejection of this cooldown; later ones should stay silent.
+    pub notify: bool,
+}
+
+impl AccessControl {
+    /// Build from the `roles:` config section. Guests get no tools and only
+    /// basic commands unless configured; other roles are unrestricted.
+    pub fn new(config: &HashMap<String, RoleConfig>) -> Self {
+        let mut permissions = HashMap::from([(
+            Role::Guest,
+            RoleConfig {
+                agents: Some(Vec::new()),
+                tools: Some(Vec::new()),
+                commands: Some(GUEST_COMMANDS.iter().map(|c| c.to_string()).collect()),
+                daily_tokens: None,
+            },
+        )]);
+        for (name, rules) in config {
+            match name.parse::<Role>() {
+                Ok(Role::Owner) => warn!("roles.owner is ignored: the owner is never restricted"),
+                Ok(role) => {
+                    permissions.insert(role, rules.clone());
+                }
+                Err(e) => warn!("ignoring roles.{name}: {e}"),
+            }
+        }
+        Self {
+            roles: DashMap::new(),
+            permissions,
+            legacy: Mutex::new(None),
+            spent: DashMap::new(),
+            charged: DashMap::new(),
+            limiter: RateLimiter::new(),
+        }
+    }
+
+    /// Fill the in-memory map from stored assignments.
+    pub(crate) fn load(&self, assignments: Vec<RoleAssignment>) {
+        for assignment in assignments {
+            match assignment.role.parse() {
+                Ok(role) => {
+                    self.roles.insert(assignment.subject, role);
+                }
+                Err(e) => warn!("ignoring role of {}: {e}", assignment.subject),
+            }
+        }
+    }
+
+    /// Use a legacy allowlist to give its users roles on first contact.
+    pub fn set_legacy_allowlist(&self, allowlist: Allowlist) {
+        *self.legacy.lock().unwrap() = Some(allowlist);
+    }
+
+    /// The role a legacy allowlist gave a raw user id.
+    fn legacy_role(&self, user_id: &str) -> Option<Role> {
+        let legacy = self.legacy.lock().unwrap();
+        let list = legacy.as_ref()?;
+        if list.is_owner(user_id) {
+            Some(Role::Owner)
+        } else if list.is_allowed(user_id) {
+            Some(Role::Member)
+        } else {
+            None
+        }
+    }
+
+    fn forget_legacy(&self, user_id: &str) {
+        if let Some(list) = self.legacy.lock().unwrap().as_mut() {
+            list.forget(user_id);
+        }
+    }
+
+    pub fn role(&self, subject: &str) -> Option<Role> {
+        self.roles.get(subject).map(|role| *role)
+    }
+
+    pub(crate) fn set(&self, subject: &str, role: Option<Role>) {
+        match role {
+            Some(role) => {
+                self.roles.insert(subject.to_string(), role);
+            }
+            None => {
+                self.roles.remove(subject);
+            }
+        }
+    }
+
+    /// Whether anyone is an owner yet, here or in the legacy allowlist.
+    pub fn has_owner(&self) -> bool {
+        self.roles.iter().any(|entry| *entry.value() == Role::Owner)
+            || self
+                .legacy
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|list| list.owner().is_some())
+    }
+
+    /// Every assignment, highest role first.
+    pub fn assignments(&self) -> Vec<(String, Role)> {
+        let mut all: Vec<(String, Role)> = self
+            .roles
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        all.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        all
+    }
+
+    fn rules(&self, role: Role) -> Option<&RoleConfig> {
+        if role == Role::Owner {
+            return None;
+        }
+        self.permissions.get(&role)
+    }
+
+    pub fn allows_agent(&self, role: Role, agent: &str) -> bool {
+        self.rules(role)
+            .is_none_or(|rules| listed(&rules.agents, agent))
+    }
+
+    pub fn allows_tool(&self, role: Role, tool: &str) -> bool {
+        self.rules(role)
+            .is_none_or(|rules| listed(&rules.tools, tool))
+    }
+
+    pub fn allows_command(&self, role: Role, command: &str) -> bool {
+        self.rules(role)
+            .is_none_or(|rules| listed(&rules.commands, command))
+    }
+
+    /// Tokens `subject` may still spend today, or `None` without a budget.
+    pub fn tokens_left(&self, role: Role, subject: &str) -> Option<u64> {
+        let budget = self.rules(role)?.daily_tokens?;
+        let today = Utc::now().date_naive();
+        let spent = self
+            .spent
+            .get(subject)
+            .filter(|entry| entry.0 == today)
+            .map_or(0, |entry| entry.1);
+        Some(budget.saturating_sub(spent))
+    }
+
+    fn charge(&self, subject: &str, tokens: u64) {
+        let today = Utc::now().date_naive();
+        let mut entry = self.spent.entry(subject.to_string()).or_insert((today, 0));
+        if entry.0 != today {
+            *entry = (today, 0);
+        }
+        entry.1 += tokens;
+    }
+}
+
+/// Per-user message rate of `role` on `channel`: the channel override, the
+/// global entry, or the built-in default for members and guests.
+fn user_rate(config: &ChatRateLimitConfig, channel: &str, role: Role) -> Option<Rate> {
+    let configured = config
+        .channels
+        .get(channel)
+        .and_then(|limits| limits.roles.get(role.as_str()))
+        .or_else(|| config.roles.get(role.as_str()));
+    match configured {
+        Some(rate) => to_rate(rate),
+        None => match role {
+            Role::Member => Some(Rate {
+                per_minute: 20,
+                burst: 10,
+            }),
+            Role::Guest => Some(Rate {
+                per_minute: 5,
+                burst: 3,
+            }),
+            Role::Owner | Role::Admin => None,
+        },
+    }
+}
+
+fn chat_rate(config: &ChatRateLimitConfig, channel: &str) -> Option<Rate> {
+    config
+        .channels
+        .get(channel)
+        .and_then(|limits| limits.chat.as_ref())
+        .or(config.chat.as_ref())
+        .and_then(to_rate)
+}
+
+fn to_rate(config: &MessageRateConfig) -> Option<Rate> {
+    (config.per_minute > 0).then(|| Rate {
+        per_minute: config.per_minute,
+        burst: config.burst.unwrap_or(config.per_minute),
+    })
+}
+
+/// Unset lists allow everything.
+fn listed(list: &Option<Vec<String>>, name: &str) -> bool {
+    list.as_ref()
+        .is_none_or(|names| names.iter().any(|n| n == name))
+}
+
+impl AppState {
+    /// Subjects whose role applies to an account: the linked person and
+    /// every account linked to it, or just the account.
+    fn role_subjects(&self, channel: &str, user_id: &str) -> Vec<String> {
+        let mut subjects = vec![self.identity_key(channel, user_id)];
+        subjects.extend(
+            self.linked_accounts(channel, user_id)
+                .iter()
+                .map(|(channel, user_id)| account_key(channel, user_id)),
+        );
+        subjects
+    }
+
+    /// The role stored for an account, the highest among linked accounts.
+    pub fn stored_role(&self, channel: &str, user_id: &str) -> Option<Role> {
+        self.role_subjects(channel, user_id)
+            .iter()
+            .filter_map(|subject| self.access.role(subject))
+            .max()
+    }
+
+    /// The role of an account, falling back to the legacy allowlist.
+    pub fn role_of(&self, channel: &str, user_id: &str) -> Option<Role> {
+        self.stored_role(channel, user_id)
+            .or_else(|| self.access.legacy_role(user_id))
+    }
+
+    /// Give an account a role, or take it away with `None`. For linked
+    /// accounts the role is held by the person.
+    pub async fn assign_role(
+        &self,
+        channel: &str,
+        user_id: &str,
+        role: Option<Role>,
+        granted_by: Option<&str>,
+    ) -> Result<()> {
+        let subject = self.identity_key(channel, user_id);
+        // Linked accounts may still carry roles from before they were
+        // linked; drop them so they cannot outrank this one.
+        let others: Vec<String> = self
+            .role_subjects(channel, user_id)
+            .into_iter()
+            .filter(|s| *s != subject && self.access.role(s).is_some())
+            .collect();
+        if let Some(store) = &self.session_store {
+            let guard = store.lock().await;
+            for other in &others {
+                guard.remove_role(other)?;
+            }
+            match role {
+                Some(role) => guard.set_role(&subject, role.as_str(), granted_by)?,
+                None => {
+                    guard.remove_role(&subject)?;
+                }
+            }
+        }
+        for other in &others {
+            self.access.set(other, None);
+        }
+        self.access.set(&subject, role);
+        self.access.forget_legacy(user_id);
+        info!(
+            "role of {subject} set to {} by {}",
+            role.map_or("none", Role::as_str),
+            granted_by.unwrap_or("api")
+        );
+        Ok(())
+    }
+
+    /// Store the role a legacy allowlist gives an account that has none yet.
+    pub async fn migrate_legacy_role(&self, channel: &str, user_id: &str) {
+        if self.stored_role(channel, user_id).is_some() {
+            return;
+        }
+        if let Some(role) = self.access.legacy_role(user_id)
+            && let Err(e) = self.assign_role(channel, user_id, Some(role), None).await
+        {
+            warn!("failed to migrate allowlist entry {channel}:{user_id}: {e}");
+        }
+    }
+
+    /// Tool guard for the agent runtime: chat users may only call the tools
+    /// their role allows. Sessions without a channel route (web, API) are
+    /// not restricted.
+    pub fn check_tool(&self, context: &ToolContext, tool: &str) -> std::result::Result<(), String> {
+        let Some(user_id) = context.user_id.as_deref() else {
+            return Ok(());
+        };
+        let Some(channel) = self
+            .session_routes
+            .get(&context.session_id)
+            .map(|route| route.channel.clone())
+        else {
+            return Ok(());
+        };
+        match self.role_of(&channel, user_id) {
+            Some(role) if self.access.allows_tool(role, tool) => Ok(()),
+            Some(role) => Err(format!(
+                "The {tool} tool is not available to this user (role: {role})."
+            )),
+            None => Err(format!("The {tool} tool is not available to this user.")),
+        }
+    }
+
+    /// Tokens an account may still spend today, or `None` without a budget.
+    pub fn tokens_left(&self, channel: &str, user_id: &str) -> Option<u64> {
+        let role = self.role_of(channel, user_id)?;
+        self.access
+            .tokens_left(role, &self.identity_key(channel, user_id))
+    }
+
+    /// Count a message to the agent against the sender's and the chat's
+    /// rate limits.
+    pub fn check_rate(
+        &self,
+        channel: &str,
+        user_id: &str,
+        session_id: &str,
+        role: Role,
+    ) -> std::result::Result<(), RateLimited> {
+        let config = self.current_config().chat_rate_limit;
+        let base = Duration::from_secs(config.cooldown_secs);
+        let cooldown = Cooldown {
+            base,
+            max: Duration::from_secs(config.max_cooldown_secs).max(base),
+        };
+        let user_key = format!("user:{}", self.identity_key(channel, user_id));
+        let chat_key = format!("chat:{session_id}");
+        // Both limits are checked together, so a message the chat's limit
+        // rejects is not charged to the sender.
+        let limits: Vec<(&str, Rate)> = [
+            (user_key.as_str(), user_rate(&config, channel, role)),
+            (chat_key.as_str(), chat_rate(&config, channel)),
+        ]
+        .into_iter()
+        .filter_map(|(key, rate)| Some((key, rate?)))
+        .collect();
+        if let Some((
+            index,
+            Verdict::Limited {
+                retry_after,
+                notify,
+            },
+        )) = self.access.limiter.check_all(&limits, cooldown)
+        {
+            let key = limits[index].0;
+            if notify {
+                warn!("{key} hit the message rate limit; cooling down for {retry_after:?}");
+            }
+            return Err(RateLimited {
+                chat: key == chat_key,
+                retry_after,
+                notify,
+            });
+        }
+        Ok(())
+    }
+
+    /// Charge the tokens a session used since the last charge to the
+    /// account that sent its latest message.
+    pub(crate) fn charge_session_tokens(&self, session_id: &str, channel: &str, user_id: &str) {
+        let Some(usage) = self.agents.session_usage(session_id) else {
+            return;
+        };
+        let total = usage.input_tokens + usage.output_tokens;
+        let previous = self
+            .access
+            .charged
+            .insert(session_id.to_string(), total)
+            .unwrap_or(0);
+        if total > previous {
+            self.access
+                .charge(&self.identity_key(channel, user_id), total - previous);
+        }
+    }
+
+    /// Check the settings of a new invite. `creator` is the role of whoever
+    /// makes it, or `None` for the API.
+    pub fn check_invite(
+        &self,
+        creator: Option<Role>,
+        role: Role,
+        agent: Option<&str>,
+        max_uses: Option<u32>,
+    ) -> std::result::Result<(), String> {
+        if let Some(creator) = creator
+            && !creator.can_manage(role)
+        {
+            return Err(format!("the {creator} role cannot invite users as {role}"));
+        }
+        if max_uses == Some(0) {
+            return Err("max_uses must be at least 1".to_string());
+        }
+        if let Some(agent) = agent {
+            if !self.current_config().agents.contains_key(agent) {
+                return Err(format!("unknown agent: {agent}"));
+            }
+            if !self.access.allows_agent(role, agent) {
+                return Err(format!("the {role} role cannot use the {agent} agent"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Store a new invite for `role`. Call [`AppState::check_invite`] first.
+    pub async fn create_invite(
+        &self,
+        role: Role,
+        agent: Option<&str>,
+        max_uses: Option<u32>,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: Option<&str>,
+    ) -> Result<Invite> {
+        let store = self.invite_store()?;
+        let invite = Invite {
+            code: generate_invite_code(),
+            role: role.as_str().to_string(),
+            agent: agent.map(str::to_string),
+            max_uses,
+            uses: 0,
+            expires_at,
+            created_by: created_by.map(str::to_string),
+            created_at: Utc::now(),
+            revoked_at: None,
+        };
+        store.lock().await.create_invite(&invite)?;
+        info!(
+            "invite {} for {role} created by {}",
+            invite.code,
+            created_by.unwrap_or("api")
+        );
+        Ok(invite)
+    }
+
+    /// Every invite, newest first.
+    pub async fn invites(&self) -> Result<Vec<Invite>> {
+        self.invite_store()?.lock().await.invites(None)
+    }
+
+    /// Revoke an invite. Returns whether there was one left to revoke.
+    pub async fn revoke_invite(&self, code: &str) -> Result<bool> {
+        let revoked = self.invite_store()?.lock().await.revoke_invite(code)?;
+        if revoked {
+            info!("invite {code} revoked");
+        }
+        Ok(revoked)
+    }
+
+    /// Use an invite for an account: records the claim and gives the
+    /// account the invite's role. Returns the invite, or `None` when the
+    /// code is unknown, revoked, expired or used up.
+    pub async fn claim_invite(
+        &self,
+        code: &str,
+        channel: &str,
+        user_id: &str,
+    ) -> Result<Option<Invite>> {
+        let store = self.invite_store()?;
+        let claimed = store.lock().await.claim_invite(code, channel, user_id)?;
+        let Some(invite) = claimed else {
+            warn!("{channel}:{user_id} sent unusable invite code {code}");
+            return Ok(None);
+        };
+        let role: Role = invite
+            .role
+            .parse()
+            .map_err(|e| Error::Database(format!("invite {code}: {e}")))?;
+        self.assign_role(channel, user_id, Some(role), invite.created_by.as_deref())
+            .await?;
+        info!(
+            "invite {code} claimed by {channel}:{user_id} as {role} (use {} of {})",
+            invite.uses + 1,
+            invite
+                .max_uses
+                .map_or_else(|| "unlimited".to_string(), |max| max.to_string())
+        );
+        Ok(Some(invite))
+    }
+
+    fn invite_store(&self) -> Result<&Arc<tokio::sync::Mutex<SessionStore>>> {
+        self.session_store
+            .as_ref()
+            .ok_or_else(|| Error::Database("invites need the sessions database".to_string()))
+    }
+}
+
+/// Parse a `/role` target: `channel:user_id`, or a bare user id on `channel`.
+pub(crate) fn parse_subject(target: &str, channel: &str) -> (String, String) {
+    match split_account_key(target) {
+        Some((target_channel, user_id)) if !target_channel.is_empty() && !user_id.is_empty() => {
+            (target_channel, user_id)
+        }
+        _ => (channel.to_string(), target.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencrust_agents::AgentRuntime;
+    use opencrust_channels::ChannelRegistry;
+    use opencrust_config::AppConfig;
+
+    fn state(roles: serde_json::Value) -> AppState {
+        let config = AppConfig {
+            roles: serde_json::from_value(roles).unwrap(),
+            ..AppConfig::default()
+        };
+        AppState::new(config, AgentRuntime::new(), ChannelRegistry::new())
+    }
+
+    #[test]
+    fn permissions_follow_the_role_config() {
+        let state = state(serde_json::json!({
+            "member": {"tools": ["web_search"], "daily_tokens": 1000},
+            "owner": {"tools": []}
+        }));
+        let access = &state.access;
+        assert!(access.allows_tool(Role::Owner, "bash"));
+        assert!(access.allows_tool(Role::Admin, "bash"));
+        assert!(access.allows_tool(Role::Member, "web_search"));
+        assert!(!access.allows_tool(Role::Member, "bash"));
+        assert!(!access.allows_tool(Role::Guest, "web_search"));
+        assert!(access.allows_command(Role::Guest, "help"));
+        assert!(!access.allows_command(Role::Guest, "model"));
+        assert!(!access.allows_agent(Role::Guest, "coder"));
+
+        assert_eq!(access.tokens_left(Role::Member, "telegram:1"), Some(1000));
+        access.charge("telegram:1", 400);
+        assert_eq!(access.tokens_left(Role::Member, "telegram:1"), Some(600));
+        assert_eq!(access.tokens_left(Role::Admin, "telegram:1"), None);
+    }
+
+    #[test]
+    fn rate_limits_apply_per_user_then_per_chat() {
+        let config = AppConfig {
+            chat_rate_limit: serde_json::from_value(serde_json::json!({
+                "roles": {"member": {"per_minute": 1, "burst": 2}},
+                "chat": {"per_minute": 1, "burst": 3},
+                "channels": {"slack": {"roles": {"member": {"per_minute": 0}}}}
+            }))
+            .unwrap(),
+            ..AppConfig::default()
+        };
+        let state = AppState::new(config, AgentRuntime::new(), ChannelRegistry::new());
+        let check = |channel: &str, user: &str, session: &str, role: Role| {
+            state.check_rate(channel, user, session, role)
+        };
+
+        assert!(check("telegram", "1", "telegram-1", Role::Member).is_ok());
+        assert!(check("telegram", "1", "telegram-1", Role::Member).is_ok());
+        let limited = check("telegram", "1", "telegram-1", Role::Member).unwrap_err();
+        assert!(!limited.chat && limited.notify);
+        assert_eq!(limited.retry_after, Duration::from_secs(30));
+        assert!(
+            !check("telegram", "1", "telegram-1", Role::Member)
+                .unwrap_err()
+                .notify
+        );
+
+        // A second member in the same chat runs into the chat limit.
+        assert!(check("telegram", "2", "telegram-1", Role::Member).is_ok());
+        assert!(
+            check("telegram", "2", "telegram-1", Role::Member)
+                .unwrap_err()
+                .chat
+        );
+
+        // Owners have no per-user limit and slack members none either.
+        for i in 0..3 {
+            assert!(check("telegram", "owner", "telegram-9", Role::Owner).is_ok());
+            assert!(check("slack", "U1", &format!("slack-{i}"), Role::Member).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn linked_accounts_share_the_highest_role() {
+        let state = state(serde_json::json!({}));
+        state
+            .assign_role("telegram", "1", Some(Role::Admin), None)
+            .await
+            .unwrap();
+        state
+            .assign_role("slack", "U1", Some(Role::Guest), None)
+            .await
+            .unwrap();
+        state
+            .link_identity("slack", "U1", "telegram", "1")
+            .await
+            .unwrap();
+        assert_eq!(state.role_of("slack", "U1"), Some(Role::Admin));
+
+        // Assigning to a linked account replaces the roles of all of them.
+        state
+            .assign_role("slack", "U1", Some(Role::Member), None)
+            .await
+            .unwrap();
+        assert_eq!(state.role_of("telegram", "1"), Some(Role::Member));
+        assert_eq!(state.access.assignments().len(), 1);
+    }
+
+    #[test]
+    fn tools_are_checked_against_the_session_user() {
+        let state = state(serde_json::json!({}));
+        state.access.set("telegram:guest", Some(Role::Guest));
+        state.access.set("telegram:friend", Some(Role::Member));
+        state.set_session_route("telegram-1", "telegram", serde_json::json!({}));
+        let context = |user: &str, session: &str| ToolContext {
+            session_id: session.to_string(),
+            user_id: Some(user.to_string()),
+            continuity_key: None,
+            heartbeat_depth: 0,
+        };
+
+        assert!(
+            state
+                .check_tool(&context("friend", "telegram-1"), "bash")
+                .is_ok()
+        );
+        assert!(
+            state
+                .check_tool(&context("guest", "telegram-1"), "bash")
+                .is_err()
+        );
+        assert!(
+            state
+                .check_tool(&context("stranger", "telegram-1"), "bash")
+                .is_err()
+        );
+        assert!(state.check_tool(&context("guest", "web-1"), "bash").is_ok());
+    }
+}
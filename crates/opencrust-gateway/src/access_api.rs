@@ -0,0 +1,361 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use opencrust_db::{Invite, InviteClaim, SessionStore};
+use opencrust_security::{Role, parse_invite_code};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::memory_api::ApiError;
+use crate::state::SharedState;
+
+#[derive(Deserialize)]
+pub struct SetRoleRequest {
+    pub role: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateInviteRequest {
+    pub role: String,
+    pub agent: Option<String>,
+    /// Omit for no limit.
+    pub max_uses: Option<u32>,
+    /// Omit for an invite that never expires.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// GET /api/roles — every role assignment, highest role first.
+pub async fn list_roles(State(state): State<SharedState>) -> Response {
+    let roles: Vec<serde_json::Value> = state
+        .access
+        .assignments()
+        .into_iter()
+        .map(|(subject, role)| serde_json::json!({ "subject": subject, "role": role }))
+        .collect();
+    Json(serde_json::json!({ "roles": roles })).into_response()
+}
+
+/// GET /api/roles/:channel/:user_id — the effective role of one account.
+pub async fn get_role(
+    State(state): State<SharedState>,
+    Path((channel, user_id)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    let role = state
+        .role_of(&channel, &user_id)
+        .ok_or_else(|| ApiError::not_found("role"))?;
+    Ok(Json(serde_json::json!({
+        "subject": state.identity_key(&channel, &user_id),
+        "role": role,
+    }))
+    .into_response())
+}
+
+/// PUT /api/roles/:channel/:user_id — give an account a role.
+pub async fn set_role(
+    State(state): State<SharedState>,
+    Path((channel, user_id)): Path<(String, String)>,
+    Json(body): Json<SetRoleRequest>,
+) -> Result<Response, ApiError> {
+    let role: Role = body
+        .role
+        .parse()
+        .map_err(|e: String| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+    state
+        .assign_role(&channel, &user_id, Some(role), None)
+        .await
+        .map_err(internal)?;
+    Ok(Json(serde_json::json!({
+        "subject": state.identity_key(&channel, &user_id),
+        "role": role,
+    }))
+    .into_response())
+}
+
+/// DELETE /api/roles/:channel/:user_id — take an account's role away.
+pub async fn delete_role(
+    State(state): State<SharedState>,
+    Path((channel, user_id)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    if state.stored_role(&channel, &user_id).is_none() {
+        return Err(ApiError::not_found("role"));
+    }
+    state
+        .assign_role(&channel, &user_id, None, None)
+        .await
+        .map_err(internal)?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// GET /api/invites — every invite, newest first.
+pub async fn list_invites(State(state): State<SharedState>) -> Result<Response, ApiError> {
+    require_store(&state)?;
+    let invites = state.invites().await.map_err(internal)?;
+    let now = Utc::now();
+    let invites: Vec<serde_json::Value> = invites
+        .iter()
+        .map(|invite| invite_json(invite, now))
+        .collect();
+    Ok(Json(serde_json::json!({ "invites": invites })).into_response())
+}
+
+/// POST /api/invites — create an invite.
+pub async fn create_invite(
+    State(state): State<SharedState>,
+    Json(body): Json<CreateInviteRequest>,
+) -> Result<Response, ApiError> {
+    require_store(&state)?;
+    let bad_request = |e: String| ApiError::new(StatusCode::BAD_REQUEST, e);
+    let role: Role = body.role.parse().map_err(bad_request)?;
+    let agent = body.agent.as_deref();
+    state
+        .check_invite(None, role, agent, body.max_uses)
+        .map_err(bad_request)?;
+    let invite = state
+        .create_invite(role, agent, body.max_uses, body.expires_at, None)
+        .await
+        .map_err(internal)?;
+    Ok((StatusCode::CREATED, Json(invite_json(&invite, Utc::now()))).into_response())
+}
+
+/// DELETE /api/invites/:code — revoke an invite.
+pub async fn revoke_invite(
+    State(state): State<SharedState>,
+    Path(code): Path<String>,
+) -> Result<Response, ApiError> {
+    require_store(&state)?;
+    let code = parse_invite_code(&code).ok_or_else(|| ApiError::not_found("invite"))?;
+    if !state.revoke_invite(&code).await.map_err(internal)? {
+        return Err(ApiError::not_found("invite"));
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// GET /api/invites/:code/claims — who used an invite, oldest first.
+pub async fn list_invite_claims(
+    State(state): State<SharedState>,
+    Path(code): Path<String>,
+) -> Result<Response, ApiError> {
+    let store = require_store(&state)?;
+    let code = parse_invite_code(&code).ok_or_else(|| ApiError::not_found("invite"))?;
+    let guard = store.lock().await;
+    if guard.invites(Some(&code)).map_err(internal)?.is_empty() {
+        return Err(ApiError::not_found("invite"));
+    }
+    let claims: Vec<serde_json::Value> = guard
+        .invite_claims(Some(&code))
+        .map_err(internal)?
+        .iter()
+        .map(claim_json)
+        .collect();
+    Ok(Json(serde_json::json!({ "claims": claims })).into_response())
+}
+
+fn invite_json(invite: &Invite, now: DateTime<Utc>) -> serde_json::Value {
+    serde_json::json!({
+        "code": invite.code,
+        "role": invite.role,
+        "agent": invite.agent,
+        "max_uses": invite.max_uses,
+        "uses": invite.uses,
+        "expires_at": invite.expires_at,
+        "created_by": invite.created_by,
+        "created_at": invite.created_at,
+        "revoked_at": invite.revoked_at,
+        "usable": invite.is_usable(now),
+    })
+}
+
+fn claim_json(claim: &InviteClaim) -> serde_json::Value {
+    serde_json::json!({
+        "channel": claim.channel,
+        "user_id": claim.user_id,
+        "claimed_at": claim.claimed_at,
+    })
+}
+
+fn require_store(state: &SharedState) -> Result<&Arc<tokio::sync::Mutex<SessionStore>>, ApiError> {
+    state.session_store.as_ref().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "invites need the sessions database",
+        )
+    })
+}
+
+fn internal(e: opencrust_common::Error) -> ApiError {
+    warn!("access api error: {e}");
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::get;
+    use opencrust_agents::AgentRuntime;
+    use opencrust_channels::ChannelRegistry;
+    use opencrust_config::AppConfig;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn roles_can_be_set_listed_and_removed() {
+        let state: SharedState = Arc::new(crate::state::AppState::new(
+            AppConfig::default(),
+            AgentRuntime::new(),
+            ChannelRegistry::new(),
+        ));
+        let router = Router::new()
+            .route("/api/roles", get(list_roles))
+            .route(
+                "/api/roles/{channel}/{user_id}",
+                get(get_role).put(set_role).delete(delete_role),
+            )
+            .with_state(Arc::clone(&state));
+        let request = |method: &str, uri: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+
+        let resp = router
+            .clone()
+            .oneshot(request("PUT", "/api/roles/slack/U1", r#"{"role":"root"}"#))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = router
+            .clone()
+            .oneshot(request("PUT", "/api/roles/slack/U1", r#"{"role":"admin"}"#))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(state.role_of("slack", "U1"), Some(Role::Admin));
+
+        let resp = router
+            .clone()
+            .oneshot(request("GET", "/api/roles", ""))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["roles"][0]["subject"], "slack:U1");
+        assert_eq!(json["roles"][0]["role"], "admin");
+
+        let resp = router
+            .clone()
+            .oneshot(request("DELETE", "/api/roles/slack/U1", ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = router
+            .oneshot(request("GET", "/api/roles/slack/U1", ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn invites_can_be_created_claimed_and_revoked() {
+        let mut state = crate::state::AppState::new(
+            AppConfig::default(),
+            AgentRuntime::new(),
+            ChannelRegistry::new(),
+        );
+        state
+            .set_session_store(Arc::new(tokio::sync::Mutex::new(
+                SessionStore::in_memory().unwrap(),
+            )))
+            .await;
+        let state: SharedState = Arc::new(state);
+        let router = Router::new()
+            .route("/api/invites", get(list_invites).post(create_invite))
+            .route("/api/invites/{code}", axum::routing::delete(revoke_invite))
+            .route("/api/invites/{code}/claims", get(list_invite_claims))
+            .with_state(Arc::clone(&state));
+        let request = |method: &str, uri: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let json = |resp: Response| async move {
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let resp = router
+            .clone()
+            .oneshot(request(
+                "POST",
+                "/api/invites",
+                r#"{"role":"guest","agent":"nope"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = router
+            .clone()
+            .oneshot(request(
+                "POST",
+                "/api/invites",
+                r#"{"role":"guest","max_uses":1}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let invite = json(resp).await;
+        let code = invite["code"].as_str().unwrap().to_string();
+        assert_eq!(invite["usable"], true);
+
+        let claimed = state.claim_invite(&code, "telegram", "9").await.unwrap();
+        assert!(claimed.is_some());
+        assert_eq!(state.role_of("telegram", "9"), Some(Role::Guest));
+
+        let resp = router
+            .clone()
+            .oneshot(request("GET", &format!("/api/invites/{code}/claims"), ""))
+            .await
+            .unwrap();
+        let claims = json(resp).await;
+        assert_eq!(claims["claims"][0]["user_id"], "9");
+
+        let resp = router
+            .clone()
+            .oneshot(request("GET", "/api/invites", ""))
+            .await
+            .unwrap();
+        let list = json(resp).await;
+        assert_eq!(list["invites"][0]["uses"], 1);
+        assert_eq!(list["invites"][0]["usable"], false);
+
+        let resp = router
+            .clone()
+            .oneshot(request("DELETE", &format!("/api/invites/{code}"), ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let resp = router
+            .oneshot(request("DELETE", &format!("/api/invites/{code}"), ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
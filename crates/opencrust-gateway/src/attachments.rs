@@ -0,0 +1,160 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use opencrust_agents::AttachmentSender;
+use opencrust_channels::ChannelSender;
+use opencrust_common::{Error, OutboundFile, Result};
+use opencrust_config::MediaConfig;
+use tracing::warn;
+
+use crate::state::{AppState, SessionRoute};
+
+/// [`AttachmentSender`] that uploads through the channel a session's last
+/// message arrived on, using the route recorded by the channel callbacks.
+pub struct ChannelAttachmentSender {
+    senders: Arc<DashMap<String, Arc<dyn ChannelSender>>>,
+    routes: Arc<DashMap<String, SessionRoute>>,
+}
+
+impl ChannelAttachmentSender {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            senders: Arc::clone(&state.channel_senders),
+            routes: Arc::clone(&state.session_routes),
+        }
+    }
+}
+
+#[async_trait]
+impl AttachmentSender for ChannelAttachmentSender {
+    async fn send_attachment(&self, session_id: &str, file: OutboundFile) -> Result<String> {
+        let route = self
+            .routes
+            .get(session_id)
+            .map(|r| r.value().clone())
+            .ok_or_else(|| {
+                Error::Channel(format!(
+                    "session {session_id} has no chat to deliver to; \
+                     attachments can only be sent in channel conversations"
+                ))
+            })?;
+        let sender = self
+            .senders
+            .get(&route.channel)
+            .map(|s| Arc::clone(s.value()))
+            .ok_or_else(|| Error::Channel(format!("channel {} is not running", route.channel)))?;
+        sender.send_attachment(&route.metadata, file).await?;
+        Ok(route.channel)
+    }
+}
+
+/// Directories `send_attachment` may read from: `media.attachment_dirs`, or
+/// the `workspace` folder of the data directory when none are set. The
+/// working directory is never a default, since the gateway may run from a
+/// home directory holding config, vault and SSH keys. Entries are
+/// canonicalized so symlinked paths compare correctly.
+pub fn attachment_dirs(config: &MediaConfig, data_dir: &Path) -> Vec<PathBuf> {
+    let dirs = if config.attachment_dirs.is_empty() {
+        let workspace = data_dir.join("workspace");
+        if let Err(e) = std::fs::create_dir_all(&workspace) {
+            warn!("failed to create attachment workspace: {e}");
+        }
+        vec![workspace]
+    } else {
+        config.attachment_dirs.clone()
+    };
+    dirs.into_iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opencrust_common::Message;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct FakeSender(Mutex<Vec<(serde_json::Value, String)>>);
+
+    #[async_trait]
+    impl ChannelSender for FakeSender {
+        fn channel_type(&self) -> &str {
+            "telegram"
+        }
+
+        async fn send_message(&self, _message: &Message) -> Result<()> {
+            Ok(())
+        }
+
+        async fn send_attachment(
+            &self,
+            metadata: &serde_json::Value,
+            file: OutboundFile,
+        ) -> Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push((metadata.clone(), file.filename));
+            Ok(())
+        }
+    }
+
+    fn file() -> OutboundFile {
+        OutboundFile {
+            data: b"a,b\n1,2\n".to_vec(),
+            filename: "data.csv".into(),
+            mime_type: "text/csv".into(),
+            caption: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_through_the_session_route() {
+        let fake = Arc::new(FakeSender::default());
+        let sender = ChannelAttachmentSender {
+            senders: Arc::new(DashMap::new()),
+            routes: Arc::new(DashMap::new()),
+        };
+        sender
+            .senders
+            .insert("telegram".into(), fake.clone() as Arc<dyn ChannelSender>);
+        sender.routes.insert(
+            "telegram-42".into(),
+            SessionRoute {
+                channel: "telegram".into(),
+                metadata: serde_json::json!({"telegram_chat_id": 42}),
+            },
+        );
+
+        let channel = sender.send_attachment("telegram-42", file()).await.unwrap();
+        assert_eq!(channel, "telegram");
+        let sent = fake.0.lock().unwrap();
+        assert_eq!(sent[0].0["telegram_chat_id"], 42);
+        assert_eq!(sent[0].1, "data.csv");
+    }
+
+    #[tokio::test]
+    async fn sessions_without_a_route_are_rejected() {
+        let sender = ChannelAttachmentSender {
+            senders: Arc::new(DashMap::new()),
+            routes: Arc::new(DashMap::new()),
+        };
+        let err = sender.send_attachment("web-1", file()).await.unwrap_err();
+        assert!(err.to_string().contains("no chat"));
+    }
+
+    #[test]
+    fn default_attachment_dir_is_the_data_workspace() {
+        let data_dir = std::env::temp_dir().join("opencrust_attachment_dirs_test");
+        let _ = std::fs::remove_dir_all(&data_dir);
+        let dirs = attachment_dirs(&MediaConfig::default(), &data_dir);
+        let workspace = data_dir.join("workspace").canonicalize().unwrap();
+        assert_eq!(dirs, vec![workspace]);
+
+        let cwd = std::env::current_dir().unwrap().canonicalize().unwrap();
+        assert!(!dirs.iter().any(|dir| cwd.starts_with(dir)));
+    }
+}
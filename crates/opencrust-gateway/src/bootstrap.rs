@@ -1,23 +1,32 @@
 use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use opencrust_agents::tools::Tool;
+use opencrust_agents::tools::{Tool, ToolContext};
 use opencrust_agents::{
-    AgentRuntime, AnthropicProvider, BashTool, ChatMessage, CohereEmbeddingProvider, FileReadTool,
-    FileWriteTool, McpManager, OllamaProvider, OpenAiProvider, WebFetchTool, WebSearchTool,
+    AgentRuntime, AnthropicProvider, BashTool, ChatMessage, CohereEmbeddingProvider,
+    EmbeddingProvider, FactExtractionMode, FileReadTool, FileWriteTool, KnowledgeIndexer,
+    McpManager, MemoryForget, MemorySave, MemorySearch, OllamaProvider, OpenAiProvider,
+    WebFetchTool, WebSearchTool,
 };
-#[cfg(target_os = "macos")]
-use opencrust_channels::{IMessageChannel, IMessageOnMessageFn};
 use opencrust_channels::{
-    MediaAttachment, SlackChannel, SlackOnMessageFn, TelegramChannel, WhatsAppChannel,
-    WhatsAppOnMessageFn, WhatsAppWebChannel,
+    GroupPolicy, IncomingImage, MediaAttachment, PendingAttachments, PendingMedia, SlackChannel,
+    SlackOnMessageFn, TelegramChannel, WhatsAppChannel, WhatsAppOnMessageFn, WhatsAppWebChannel,
+    WhatsAppWebOnMessageFn,
 };
+#[cfg(target_os = "macos")]
+use opencrust_channels::{IMessageChannel, IMessageOnMessageFn};
 use opencrust_config::AppConfig;
-use opencrust_db::MemoryStore;
-use opencrust_security::{Allowlist, PairingManager};
+use opencrust_db::{KnowledgeStore, MemoryProvider, MemoryStore};
+use opencrust_media::{
+    OpenAiCompatibleStt, OpenAiCompatibleTts, PiperTts, SpeechToText, TextToSpeech, WhisperCppStt,
+};
+use opencrust_security::Allowlist;
 use tracing::{info, warn};
 
-use crate::state::SharedState;
+use crate::commands::{self, CommandContext};
+use crate::state::{AppState, SharedState};
+use crate::turns::TurnMessage;
+use crate::voice::voice_replier;
 
 /// Default vault path under the user's home directory.
 pub(crate) fn default_vault_path() -> Option<PathBuf> {
@@ -32,6 +41,43 @@ fn default_allowlist_path() -> PathBuf {
     opencrust_config::ConfigLoader::default_config_dir().join("allowlist.json")
 }
 
+/// Hand the `allowlist.json` from before roles existed to access control,
+/// so its users keep their access and get a role when they next write.
+pub fn load_legacy_allowlist(state: &AppState) {
+    let path = default_allowlist_path();
+    if path.is_file() {
+        state
+            .access
+            .set_legacy_allowlist(Allowlist::load_or_create(&path));
+    }
+}
+
+/// Refuse tool calls that the calling chat user's role does not allow.
+pub fn install_tool_guard(state: &SharedState) {
+    let weak = Arc::downgrade(state);
+    state
+        .agents
+        .set_tool_guard(Arc::new(move |context: &ToolContext, tool: &str| {
+            weak.upgrade()
+                .map_or(Ok(()), |state| state.check_tool(context, tool))
+        }));
+}
+
+/// Group participation rules from a channel's `group_policy` setting. An
+/// invalid entry is reported and the default policy used instead.
+fn group_policy(
+    channel_name: &str,
+    settings: &std::collections::HashMap<String, serde_json::Value>,
+) -> Arc<GroupPolicy> {
+    match GroupPolicy::from_settings(settings) {
+        Ok(policy) => Arc::new(policy),
+        Err(e) => {
+            warn!("channel {channel_name}: {e}; answering mentions and replies only");
+            Arc::new(GroupPolicy::default())
+        }
+    }
+}
+
 /// Resolve an API key using the priority chain: vault -> config -> env var.
 pub(crate) fn resolve_api_key(
     config_key: Option<&str>,
@@ -417,39 +463,27 @@ pub fn build_agent_runtime(config: &AppConfig) -> AgentRuntime {
         let memory_db_path = data_dir.join("memory.db");
         match MemoryStore::open(&memory_db_path) {
             Ok(store) => {
-                let store = Arc::new(store);
-                runtime.set_memory_provider(store);
+                let store: Arc<dyn MemoryProvider> = Arc::new(store);
+                runtime.set_memory_provider(Arc::clone(&store));
                 info!("memory store opened at {}", memory_db_path.display());
 
                 // Attach embedding provider if configured
-                if let Some(embed_name) = &config.memory.embedding_provider
-                    && let Some(embed_config) = config.embeddings.get(embed_name)
-                {
-                    match embed_config.provider.as_str() {
-                        "cohere" => {
-                            let api_key = resolve_api_key(
-                                embed_config.api_key.as_deref(),
-                                "COHERE_API_KEY",
-                                "COHERE_API_KEY",
-                            );
-
-                            if let Some(key) = api_key {
-                                let provider = CohereEmbeddingProvider::new(
-                                    key,
-                                    embed_config.model.clone(),
-                                    embed_config.base_url.clone(),
-                                );
-                                runtime.set_embedding_provider(Arc::new(provider));
-                                info!("configured cohere embedding provider: {embed_name}");
-                            } else {
-                                warn!("skipping cohere embedding provider: no API key");
-                            }
-                        }
-                        other => {
-                            warn!("unknown embedding provider type: {other}");
-                        }
-                    }
+                let embeddings = config
+                    .memory
+                    .embedding_provider
+                    .as_deref()
+                    .and_then(|name| build_embedding_provider(config, name));
+                if let Some(provider) = &embeddings {
+                    runtime.set_embedding_provider(Arc::clone(provider));
                 }
+
+                // Agent-callable memory tools (deliberate save/search/forget)
+                runtime.register_tool(Box::new(MemorySave::new(
+                    Arc::clone(&store),
+                    embeddings.clone(),
+                )));
+                runtime.register_tool(Box::new(MemorySearch::new(Arc::clone(&store), embeddings)));
+                runtime.register_tool(Box::new(MemoryForget::new(store)));
             }
             Err(e) => {
                 warn!("failed to open memory store: {e}");
@@ -473,6 +507,16 @@ pub fn build_agent_runtime(config: &AppConfig) -> AgentRuntime {
     if let Some(enabled) = config.memory.summarization {
         runtime.set_summarization_enabled(enabled);
     }
+    runtime.set_max_injected_facts(config.memory.facts.max_injected);
+    if config.memory.enabled && config.memory.facts.extract {
+        match FactExtractionMode::parse(&config.memory.facts.mode) {
+            Some(mode) => runtime.set_fact_extraction(Some(mode)),
+            None => warn!(
+                "unknown memory.facts.mode '{}', fact extraction disabled",
+                config.memory.facts.mode
+            ),
+        }
+    }
 
     // --- Skills ---
     let skills_dir = opencrust_config::ConfigLoader::default_config_dir().join("skills");
@@ -525,6 +569,85 @@ pub fn build_agent_runtime(config: &AppConfig) -> AgentRuntime {
 /// Build MCP tools from merged config (config.yml + mcp.json).
 ///
 /// Returns the manager and a flat list of bridged tools ready for registration.
+/// Build a named embedding provider from the `embeddings:` config section.
+pub fn build_embedding_provider(
+    config: &AppConfig,
+    name: &str,
+) -> Option<Arc<dyn EmbeddingProvider>> {
+    let Some(embed_config) = config.embeddings.get(name) else {
+        warn!("embedding provider '{name}' is not configured");
+        return None;
+    };
+    match embed_config.provider.as_str() {
+        "cohere" => {
+            let api_key = resolve_api_key(
+                embed_config.api_key.as_deref(),
+                "COHERE_API_KEY",
+                "COHERE_API_KEY",
+            );
+
+            if let Some(key) = api_key {
+                info!("configured cohere embedding provider: {name}");
+                Some(Arc::new(CohereEmbeddingProvider::new(
+                    key,
+                    embed_config.model.clone(),
+                    embed_config.base_url.clone(),
+                )))
+            } else {
+                warn!("skipping cohere embedding provider: no API key");
+                None
+            }
+        }
+        other => {
+            warn!("unknown embedding provider type: {other}");
+            None
+        }
+    }
+}
+
+/// Open the knowledge base and its indexer when `knowledge.enabled` is set.
+/// Sources are registered and synced by the caller, since that is async.
+pub fn build_knowledge_indexer(config: &AppConfig) -> Option<Arc<KnowledgeIndexer>> {
+    if !config.knowledge.enabled {
+        return None;
+    }
+    match open_knowledge_indexer(config) {
+        Ok(indexer) => Some(indexer),
+        Err(e) => {
+            warn!("failed to open knowledge store: {e}");
+            None
+        }
+    }
+}
+
+/// Open `knowledge.db` in the data directory regardless of `knowledge.enabled`,
+/// so the CLI can manage sources before the feature is switched on.
+pub fn open_knowledge_indexer(
+    config: &AppConfig,
+) -> opencrust_common::Result<Arc<KnowledgeIndexer>> {
+    let data_dir = config
+        .data_dir
+        .clone()
+        .unwrap_or_else(|| opencrust_config::ConfigLoader::default_config_dir().join("data"));
+    std::fs::create_dir_all(&data_dir)?;
+
+    let store = Arc::new(KnowledgeStore::open(&data_dir.join("knowledge.db"))?);
+    let embeddings = config
+        .knowledge
+        .embedding_provider
+        .as_deref()
+        .or(config.memory.embedding_provider.as_deref())
+        .and_then(|name| build_embedding_provider(config, name));
+    if embeddings.is_none() {
+        info!("knowledge base has no embedding provider, search will be keyword-only");
+    }
+
+    Ok(Arc::new(
+        KnowledgeIndexer::new(store, embeddings)
+            .with_chunking(config.knowledge.chunk_chars, config.knowledge.chunk_overlap),
+    ))
+}
+
 pub async fn build_mcp_tools(config: &AppConfig) -> (McpManager, Vec<Box<dyn Tool>>) {
     let loader = match opencrust_config::ConfigLoader::new() {
         Ok(l) => l,
@@ -666,132 +789,88 @@ pub fn build_discord_channels(
             settings.insert("application_id".to_string(), serde_json::json!(id));
         }
 
-        let allowlist = Arc::new(Mutex::new(Allowlist::load_or_create(
-            &default_allowlist_path(),
-        )));
-        let pairing = Arc::new(Mutex::new(PairingManager::new(
-            std::time::Duration::from_secs(300),
-        )));
-
         let state_for_cb = Arc::clone(state);
-        let allowlist_for_cb = Arc::clone(&allowlist);
-        let pairing_for_cb = Arc::clone(&pairing);
 
         let on_message: opencrust_channels::discord::DiscordOnMessageFn = Arc::new(
             move |channel_id: String,
                   user_id: String,
                   user_name: String,
                   text: String,
+                  attachments: PendingAttachments,
                   delta_tx: Option<tokio::sync::mpsc::Sender<String>>| {
                 let state = Arc::clone(&state_for_cb);
-                let allowlist = Arc::clone(&allowlist_for_cb);
-                let pairing = Arc::clone(&pairing_for_cb);
                 Box::pin(async move {
-                    if let Some(cmd) = text.strip_prefix('/') {
-                        let cmd = cmd.split_whitespace().next().unwrap_or("");
-                        return handle_discord_command(
-                            cmd,
-                            &user_id,
-                            &user_name,
-                            &channel_id,
-                            &allowlist,
-                            &pairing,
-                            &state,
-                        );
+                    let ctx = CommandContext {
+                        state: &state,
+                        channel: "discord",
+                        session_id: &format!("discord-{channel_id}"),
+                        user_id: &user_id,
+                        user_name: &user_name,
+                        route: serde_json::json!({"discord_channel_id": channel_id}),
+                    };
+                    if let Some(reply) = commands::dispatch(&ctx, &text).await {
+                        return reply;
                     }
 
-                    {
-                        let mut list = allowlist.lock().unwrap();
-                        if list.needs_owner() {
-                            list.claim_owner(&user_id);
-                            info!("discord: auto-paired owner {} ({})", user_name, user_id);
-                            return Ok(format!(
-                                "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
-                                 Use /pair to generate a code for adding other users.\n\
-                                 Use /help for available commands.",
-                                user_name
-                            ));
-                        }
-
-                        if !list.is_allowed(&user_id) {
-                            let trimmed = text.trim();
-                            if trimmed.len() == 6 && trimmed.chars().all(|c| c.is_ascii_digit()) {
-                                let claimed = pairing.lock().unwrap().claim(trimmed, &user_id);
-                                if claimed.is_some() {
-                                    list.add(&user_id);
-                                    info!(
-                                        "discord: paired user {} ({}) via code",
-                                        user_name, user_id
-                                    );
-                                    return Ok(format!(
-                                        "Welcome, {}! You now have access to this bot.",
-                                        user_name
-                                    ));
-                                }
-                            }
-
-                            warn!(
-                                "discord: unauthorized user {} ({}) in channel {}",
-                                user_name, user_id, channel_id
-                            );
-                            return Err("__blocked__".to_string());
-                        }
+                    if let Some(reply) = commands::admit(&ctx, &text).await {
+                        return reply;
                     }
 
                     let session_id = format!("discord-{channel_id}");
+                    state.set_session_route(
+                        &session_id,
+                        "discord",
+                        serde_json::json!({"discord_channel_id": channel_id}),
+                    );
 
-                    let text = opencrust_security::InputValidator::sanitize(&text);
+                    // Fetch the Discord CDN attachments now that the
+                    // message has passed the checks above.
+                    let (text, images) = attachments.resolve(&text).await;
+                    let (image_urls, media) = incoming_images(&state, images).await?;
+                    let message = TurnMessage {
+                        user_id: user_id.clone(),
+                        text,
+                        image_urls,
+                        media,
+                    };
+                    let Some(mut turn) = state.begin_turn(&session_id, message).await else {
+                        return Err(commands::BLOCKED.to_string());
+                    };
+
+                    let text = opencrust_security::InputValidator::sanitize(&turn.message.text);
                     if opencrust_security::InputValidator::check_prompt_injection(&text) {
                         return Err(
                             "input rejected: potential prompt injection detected".to_string()
                         );
                     }
 
-                    state
-                        .hydrate_session_history(&session_id, Some("discord"), Some(&user_id))
-                        .await;
-                    let history: Vec<ChatMessage> = state.session_history(&session_id);
-                    let continuity_key = state.continuity_key(Some(&user_id));
-                    let summary = state.session_summary(&session_id);
-
-                    let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                        state
-                            .agents
-                            .process_message_streaming_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                delta_sender,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&user_id),
-                            )
-                            .await
+                    let image_urls = std::mem::take(&mut turn.message.image_urls);
+                    let media = std::mem::take(&mut turn.message.media);
+                    let text = if text.trim().is_empty() && !image_urls.is_empty() {
+                        "Describe this image.".to_string()
                     } else {
-                        state
-                            .agents
-                            .process_message_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&user_id),
-                            )
-                            .await
-                    }
-                    .map_err(|e| e.to_string())?;
+                        text
+                    };
 
-                    if let Some(s) = new_summary {
-                        state.update_session_summary(&session_id, &s);
-                    }
+                    let response = turn
+                        .run(agent_turn(
+                            &state,
+                            "discord",
+                            &session_id,
+                            &user_id,
+                            &text,
+                            image_urls,
+                            delta_tx,
+                        ))
+                        .await?;
 
                     state
-                        .persist_turn(
+                        .persist_turn_with_media(
                             &session_id,
                             Some("discord"),
                             Some(&user_id),
                             &text,
+                            &media,
                             &response,
                             Some(serde_json::json!({"discord_channel_id": channel_id})),
                         )
@@ -806,6 +885,10 @@ pub fn build_discord_channels(
             &settings, on_message,
         ) {
             Ok(channel) => {
+                let channel = channel
+                    .with_speech_to_text(state.speech_to_text.clone())
+                    .with_commands(state.commands.specs().to_vec())
+                    .with_group_policy(group_policy(name, &settings));
                 channels.push(Box::new(channel) as Box<dyn opencrust_channels::Channel>);
                 info!("configured discord channel: {name}");
             }
@@ -818,83 +901,322 @@ pub fn build_discord_channels(
     channels
 }
 
-/// Transcribe voice audio using the Whisper API.
-///
-/// Tries OpenAI first, then Groq. Returns an error with a helpful message
-/// if neither API key is configured.
-async fn transcribe_voice(audio_bytes: &[u8]) -> std::result::Result<String, String> {
-    let openai_key = resolve_api_key(None, "OPENAI_API_KEY", "OPENAI_API_KEY");
-    let groq_key = resolve_api_key(None, "GROQ_API_KEY", "GROQ_API_KEY");
-
-    if let Some(key) = openai_key {
-        return whisper_transcribe(
-            audio_bytes,
-            &key,
-            "https://api.openai.com/v1/audio/transcriptions",
-            "whisper-1",
-        )
-        .await;
+/// Build the speech-to-text backend from `speech.stt`. Without that section,
+/// falls back to the hosted Whisper APIs when `OPENAI_API_KEY` or
+/// `GROQ_API_KEY` is available.
+pub fn build_speech_to_text(config: &AppConfig) -> Option<Arc<dyn SpeechToText>> {
+    let Some(stt) = &config.speech.stt else {
+        if let Some(key) = resolve_api_key(None, "OPENAI_API_KEY", "OPENAI_API_KEY") {
+            return Some(Arc::new(OpenAiCompatibleStt::new(None, Some(key), None)));
+        }
+        if let Some(key) = resolve_api_key(None, "GROQ_API_KEY", "GROQ_API_KEY") {
+            return Some(Arc::new(OpenAiCompatibleStt::new(
+                Some("https://api.groq.com/openai/v1".to_string()),
+                Some(key),
+                Some("whisper-large-v3-turbo".to_string()),
+            )));
+        }
+        return None;
+    };
+
+    match stt.provider.as_str() {
+        "openai" => {
+            let api_key = resolve_api_key(stt.api_key.as_deref(), "STT_API_KEY", "STT_API_KEY")
+                .or_else(|| {
+                    // Only fall back to the OpenAI key when talking to OpenAI itself
+                    stt.base_url
+                        .is_none()
+                        .then(|| resolve_api_key(None, "OPENAI_API_KEY", "OPENAI_API_KEY"))
+                        .flatten()
+                });
+            info!("configured openai-compatible speech-to-text");
+            Some(Arc::new(
+                OpenAiCompatibleStt::new(stt.base_url.clone(), api_key, stt.model.clone())
+                    .with_language(stt.language.clone()),
+            ))
+        }
+        "whisper-cpp" => {
+            let Some(model) = &stt.model else {
+                warn!("speech.stt: whisper-cpp needs `model` set to a ggml model file");
+                return None;
+            };
+            let work_dir = std::env::temp_dir().join("opencrust-speech");
+            match WhisperCppStt::new(
+                stt.binary.clone(),
+                PathBuf::from(model),
+                stt.language.clone(),
+                work_dir,
+            ) {
+                Ok(backend) => {
+                    if !opencrust_media::MediaProcessor::ffmpeg_available() {
+                        warn!("speech.stt: whisper-cpp needs ffmpeg on PATH to convert audio");
+                    }
+                    info!("configured whisper.cpp speech-to-text");
+                    Some(Arc::new(backend))
+                }
+                Err(e) => {
+                    warn!("speech.stt: {e}");
+                    None
+                }
+            }
+        }
+        other => {
+            warn!("unknown speech-to-text provider: {other}");
+            None
+        }
     }
+}
 
-    if let Some(key) = groq_key {
-        return whisper_transcribe(
-            audio_bytes,
-            &key,
-            "https://api.groq.com/openai/v1/audio/transcriptions",
-            "whisper-large-v3-turbo",
-        )
-        .await;
+/// Build the text-to-speech backend used for voice replies from `speech.tts`.
+pub fn build_text_to_speech(config: &AppConfig) -> Option<Arc<dyn TextToSpeech>> {
+    let tts = config.speech.tts.as_ref()?;
+
+    match tts.provider.as_str() {
+        "openai" => {
+            let api_key = resolve_api_key(tts.api_key.as_deref(), "TTS_API_KEY", "TTS_API_KEY")
+                .or_else(|| {
+                    // Only fall back to the OpenAI key when talking to OpenAI itself
+                    tts.base_url
+                        .is_none()
+                        .then(|| resolve_api_key(None, "OPENAI_API_KEY", "OPENAI_API_KEY"))
+                        .flatten()
+                });
+            info!("configured openai-compatible text-to-speech");
+            Some(Arc::new(OpenAiCompatibleTts::new(
+                tts.base_url.clone(),
+                api_key,
+                tts.model.clone(),
+                tts.voice.clone(),
+            )))
+        }
+        "piper" => {
+            let Some(model) = &tts.model else {
+                warn!("speech.tts: piper needs `model` set to an .onnx voice file");
+                return None;
+            };
+            let work_dir = std::env::temp_dir().join("opencrust-speech");
+            match PiperTts::new(tts.binary.clone(), PathBuf::from(model), work_dir) {
+                Ok(backend) => {
+                    if !opencrust_media::MediaProcessor::ffmpeg_available() {
+                        warn!("speech.tts: piper needs ffmpeg on PATH to encode voice notes");
+                    }
+                    info!("configured piper text-to-speech");
+                    Some(Arc::new(backend))
+                }
+                Err(e) => {
+                    warn!("speech.tts: {e}");
+                    None
+                }
+            }
+        }
+        other => {
+            warn!("unknown text-to-speech provider: {other}");
+            None
+        }
     }
+}
 
-    Err("Voice messages require an OpenAI or Groq API key. \
-         Groq offers free Whisper transcription at groq.com"
-        .to_string())
+/// Turn a photo or video into images for a vision request: photos are
+/// rotated and downscaled, videos are sampled into keyframes. Returns the
+/// image URLs, the `media://` references to keep in history, and the text
+/// that accompanies them.
+async fn visual_attachment(
+    state: &SharedState,
+    attachment: MediaAttachment,
+) -> std::result::Result<(Vec<String>, Vec<String>, String), String> {
+    let media_config = state.current_config().media;
+    let max_dimension = media_config
+        .image_max_dimension
+        .unwrap_or_else(|| state.agents.max_image_dimension());
+    let work_dir = std::env::temp_dir().join("opencrust-media");
+
+    let mut urls = Vec::new();
+    let mut media = Vec::new();
+    let text = match attachment {
+        MediaAttachment::Photo { data, caption } => {
+            let options = opencrust_media::ImageOptions {
+                max_dimension,
+                format: opencrust_media::ImageFormat::parse(&media_config.image_format)
+                    .unwrap_or(opencrust_media::ImageFormat::Jpeg),
+            };
+            let (data, mime_type) =
+                match opencrust_media::prepare_image(&data, &options, &work_dir).await {
+                    Ok(prepared) => (prepared.data, prepared.mime_type),
+                    Err(e) => {
+                        warn!("image preprocessing failed, sending original: {e}");
+                        (data, "image/jpeg".to_string())
+                    }
+                };
+            let (url, refs) = store_image(state, &data, &mime_type);
+            urls.push(url);
+            media.extend(refs);
+            caption.unwrap_or_else(|| "Describe this image.".to_string())
+        }
+        MediaAttachment::Video { data, caption, .. } => {
+            let (duration, frames) = opencrust_media::video_keyframes(
+                &data,
+                media_config.video_frames,
+                max_dimension,
+                &work_dir,
+            )
+            .await
+            .map_err(|e| format!("could not read the video: {e}"))?;
+            for frame in &frames {
+                let (url, refs) = store_image(state, &frame.data, &frame.mime_type);
+                urls.push(url);
+                media.extend(refs);
+            }
+            format!(
+                "[Video: {} frames sampled evenly from {duration:.0} seconds]\n\n{}",
+                frames.len(),
+                caption.unwrap_or_else(|| "Describe this video.".to_string())
+            )
+        }
+        _ => return Err("unsupported attachment".to_string()),
+    };
+    Ok((urls, media, text))
 }
 
-async fn whisper_transcribe(
-    audio_bytes: &[u8],
-    api_key: &str,
-    endpoint: &str,
-    model: &str,
-) -> std::result::Result<String, String> {
-    let client = reqwest::Client::new();
-
-    let file_part = reqwest::multipart::Part::bytes(audio_bytes.to_vec())
-        .file_name("voice.ogg")
-        .mime_str("audio/ogg")
-        .map_err(|e| format!("failed to build multipart: {e}"))?;
-
-    let form = reqwest::multipart::Form::new()
-        .part("file", file_part)
-        .text("model", model.to_string());
-
-    let response = client
-        .post(endpoint)
-        .header("Authorization", format!("Bearer {api_key}"))
-        .multipart(form)
-        .send()
-        .await
-        .map_err(|e| format!("whisper request failed: {e}"))?;
-
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        return Err(format!("whisper API error: status={status}, body={body}"));
+/// Put an incoming image in the media store and return the URL to give the
+/// model plus the `media://` references to keep in history. Without a store
+/// the image is inlined as a `data:` URI and not kept.
+fn store_image(state: &SharedState, data: &[u8], mime_type: &str) -> (String, Vec<String>) {
+    if let Some(store) = state.agents.media_store() {
+        match store.put(data, None, Some(mime_type)) {
+            Ok(stored) => return (stored.url(), vec![stored.url()]),
+            Err(e) => warn!("failed to store image: {e}"),
+        }
     }
+    use base64::Engine;
+    let b64 = base64::engine::general_purpose::STANDARD.encode(data);
+    (format!("data:{mime_type};base64,{b64}"), Vec::new())
+}
 
-    #[derive(serde::Deserialize)]
-    struct WhisperResponse {
-        text: String,
+/// Put incoming images through [`visual_attachment`]. Returns the image
+/// URLs for the model and the `media://` references to keep in history.
+async fn incoming_images(
+    state: &SharedState,
+    images: Vec<IncomingImage>,
+) -> std::result::Result<(Vec<String>, Vec<String>), String> {
+    let mut urls = Vec::new();
+    let mut media = Vec::new();
+    for image in images {
+        let (image_urls, refs, _) = visual_attachment(
+            state,
+            MediaAttachment::Photo {
+                data: image.data,
+                caption: None,
+            },
+        )
+        .await?;
+        urls.extend(image_urls);
+        media.extend(refs);
     }
+    Ok((urls, media))
+}
 
-    let result: WhisperResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("failed to parse whisper response: {e}"))?;
+/// Run one agent turn for a channel message, with `image_urls` as vision
+/// input when present and streaming through `delta_tx` when given. Loads
+/// and updates the session summary; the caller persists the turn.
+async fn agent_turn(
+    state: &SharedState,
+    channel: &str,
+    session_id: &str,
+    user_id: &str,
+    text: &str,
+    image_urls: Vec<String>,
+    delta_tx: Option<tokio::sync::mpsc::Sender<String>>,
+) -> std::result::Result<String, String> {
+    state
+        .hydrate_session_history(session_id, Some(channel), Some(user_id))
+        .await;
+    let history: Vec<ChatMessage> = state.session_history(session_id);
+    let continuity_key = state.continuity_key(Some(channel), Some(user_id));
+    let summary = state.session_summary(session_id);
+
+    let (response, new_summary) = if image_urls.is_empty() {
+        match delta_tx {
+            Some(delta_sender) => {
+                state
+                    .agents
+                    .process_message_streaming_with_context_and_summary(
+                        session_id,
+                        text,
+                        &history,
+                        delta_sender,
+                        summary.as_deref(),
+                        continuity_key.as_deref(),
+                        Some(user_id),
+                    )
+                    .await
+            }
+            None => {
+                state
+                    .agents
+                    .process_message_with_context_and_summary(
+                        session_id,
+                        text,
+                        &history,
+                        summary.as_deref(),
+                        continuity_key.as_deref(),
+                        Some(user_id),
+                    )
+                    .await
+            }
+        }
+    } else {
+        let mut blocks: Vec<opencrust_agents::ContentBlock> = image_urls
+            .into_iter()
+            .map(|url| opencrust_agents::ContentBlock::Image { url })
+            .collect();
+        blocks.push(opencrust_agents::ContentBlock::Text {
+            text: text.to_string(),
+        });
+        match delta_tx {
+            Some(delta_sender) => {
+                state
+                    .agents
+                    .process_message_streaming_with_blocks_and_summary(
+                        session_id,
+                        blocks,
+                        text,
+                        &history,
+                        delta_sender,
+                        summary.as_deref(),
+                        continuity_key.as_deref(),
+                        Some(user_id),
+                    )
+                    .await
+            }
+            None => {
+                state
+                    .agents
+                    .process_message_with_blocks_and_summary(
+                        session_id,
+                        blocks,
+                        text,
+                        &history,
+                        summary.as_deref(),
+                        continuity_key.as_deref(),
+                        Some(user_id),
+                    )
+                    .await
+            }
+        }
+    }
+    .map_err(|e| e.to_string())?;
 
-    Ok(result.text)
+    if let Some(s) = new_summary {
+        state.update_session_summary(session_id, &s);
+    }
+    Ok(response)
 }
 
+/// Reply to a Telegram voice note when no `speech.stt` backend is set up.
+const NO_SPEECH_TO_TEXT: &str = "Voice messages need a speech-to-text backend. \
+     Configure `speech.stt` or set OPENAI_API_KEY / GROQ_API_KEY \
+     (Groq offers free Whisper transcription at groq.com)";
+
 /// Build Telegram channels from config. Must be called after state is
 /// wrapped in `Arc` so the message callback can capture a `SharedState`.
 pub fn build_telegram_channels(
@@ -925,382 +1247,169 @@ pub fn build_telegram_channels(
             continue;
         };
 
-        let allowlist = Arc::new(Mutex::new(Allowlist::load_or_create(
-            &default_allowlist_path(),
-        )));
-
-        let pairing = Arc::new(Mutex::new(PairingManager::new(
-            std::time::Duration::from_secs(300),
-        )));
-
         let state_for_cb = Arc::clone(state);
-        let allowlist_for_cb = Arc::clone(&allowlist);
-        let pairing_for_cb = Arc::clone(&pairing);
 
         let on_message: opencrust_channels::OnMessageFn = Arc::new(
             move |chat_id: i64,
                   user_id: String,
                   user_name: String,
                   text: String,
-                  attachment: Option<MediaAttachment>,
+                  attachment: Option<PendingMedia>,
                   delta_tx: Option<tokio::sync::mpsc::Sender<String>>| {
                 let state = Arc::clone(&state_for_cb);
-                let allowlist = Arc::clone(&allowlist_for_cb);
-                let pairing = Arc::clone(&pairing_for_cb);
                 Box::pin(async move {
-                    // --- Command handling (text-only) ---
-                    if let Some(cmd) = text.strip_prefix('/') {
-                        let cmd = cmd.split_whitespace().next().unwrap_or("");
-                        return handle_command(
-                            cmd, &text, &user_id, &user_name, chat_id, &allowlist, &pairing, &state,
-                        );
+                    let ctx = CommandContext {
+                        state: &state,
+                        channel: "telegram",
+                        session_id: &format!("telegram-{chat_id}"),
+                        user_id: &user_id,
+                        user_name: &user_name,
+                        route: serde_json::json!({"telegram_chat_id": chat_id}),
+                    };
+                    if let Some(reply) = commands::dispatch(&ctx, &text).await {
+                        return reply;
                     }
 
-                    // --- Auth / pairing ---
-                    {
-                        let mut list = allowlist.lock().unwrap();
-                        if list.needs_owner() {
-                            list.claim_owner(&user_id);
-                            info!("telegram: auto-paired owner {} ({})", user_name, user_id);
-                            return Ok(format!(
-                                "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
-                                 Use /pair to generate a code for adding other users.\n\
-                                 Use /help for available commands.",
-                                user_name
-                            ));
-                        }
-
-                        if !list.is_allowed(&user_id) {
-                            let trimmed = text.trim();
-                            if trimmed.len() == 6 && trimmed.chars().all(|c| c.is_ascii_digit()) {
-                                let claimed = pairing.lock().unwrap().claim(trimmed, &user_id);
-                                if claimed.is_some() {
-                                    list.add(&user_id);
-                                    info!(
-                                        "telegram: paired user {} ({}) via code",
-                                        user_name, user_id
-                                    );
-                                    return Ok(format!(
-                                        "Welcome, {}! You now have access to this bot.",
-                                        user_name
-                                    ));
-                                }
-                            }
-
-                            warn!(
-                                "telegram: unauthorized user {} ({}) in chat {}",
-                                user_name, user_id, chat_id
-                            );
-                            return Err("__blocked__".to_string());
-                        }
+                    if let Some(reply) = commands::admit(&ctx, &text).await {
+                        return reply;
                     }
 
                     let session_id = format!("telegram-{chat_id}");
+                    state.set_session_route(
+                        &session_id,
+                        "telegram",
+                        serde_json::json!({"telegram_chat_id": chat_id}),
+                    );
+
+                    // Telegram hands over a file id; the download happens
+                    // here, past the role and rate checks. A voice note is
+                    // not worth fetching with nobody to transcribe it.
+                    let stt = state.speech_to_text.clone();
+                    let attachment = match attachment {
+                        Some(pending) if pending.kind() == "voice" && stt.is_none() => {
+                            return Err(NO_SPEECH_TO_TEXT.to_string());
+                        }
+                        Some(pending) => {
+                            let kind = pending.kind();
+                            let media = pending.download().await.map_err(|e| {
+                                warn!("telegram: failed to download {kind}: {e}");
+                                format!("could not download the {kind}")
+                            })?;
+                            Some(media)
+                        }
+                        None => None,
+                    };
 
-                    // --- Handle media or text ---
-                    match attachment {
+                    // Voice notes, photos, videos and documents become text
+                    // and images for the model.
+                    let (text, image_urls, media) = match attachment {
                         Some(MediaAttachment::Voice { data, duration }) => {
-                            let transcript = transcribe_voice(&data).await?;
+                            let Some(stt) = stt else {
+                                return Err(NO_SPEECH_TO_TEXT.to_string());
+                            };
+                            let transcript = stt
+                                .transcribe(&data, "voice.ogg")
+                                .await
+                                .map_err(|e| format!("transcription failed: {e}"))?;
                             info!(
                                 "telegram voice transcribed: {} chars from {}s audio",
                                 transcript.len(),
                                 duration
                             );
-
-                            let text = opencrust_security::InputValidator::sanitize(&transcript);
-                            if opencrust_security::InputValidator::check_prompt_injection(&text) {
-                                return Err("input rejected: potential prompt injection detected"
-                                    .to_string());
-                            }
-
-                            state
-                                .hydrate_session_history(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                )
-                                .await;
-                            let history: Vec<ChatMessage> = state.session_history(&session_id);
-                            let continuity_key = state.continuity_key(Some(&user_id));
-                            let summary = state.session_summary(&session_id);
-
-                            let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                                state
-                                    .agents
-                                    .process_message_streaming_with_context_and_summary(
-                                        &session_id,
-                                        &text,
-                                        &history,
-                                        delta_sender,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            } else {
-                                state
-                                    .agents
-                                    .process_message_with_context_and_summary(
-                                        &session_id,
-                                        &text,
-                                        &history,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            }
-                            .map_err(|e| e.to_string())?;
-
-                            if let Some(s) = new_summary {
-                                state.update_session_summary(&session_id, &s);
-                            }
-
-                            state
-                                .persist_turn(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                    &text,
-                                    &response,
-                                    Some(serde_json::json!({"telegram_chat_id": chat_id})),
-                                )
-                                .await;
-                            Ok(response)
+                            (transcript, Vec::new(), Vec::new())
                         }
-                        Some(MediaAttachment::Photo { data, caption }) => {
-                            use base64::Engine;
-                            let b64 = base64::engine::general_purpose::STANDARD.encode(&data);
-                            let data_url = format!("data:image/jpeg;base64,{b64}");
-                            let caption_text =
-                                caption.unwrap_or_else(|| "Describe this image.".to_string());
-
-                            let blocks = vec![
-                                opencrust_agents::ContentBlock::Image { url: data_url },
-                                opencrust_agents::ContentBlock::Text {
-                                    text: caption_text.clone(),
-                                },
-                            ];
-
-                            state
-                                .hydrate_session_history(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                )
-                                .await;
-                            let history: Vec<ChatMessage> = state.session_history(&session_id);
-                            let continuity_key = state.continuity_key(Some(&user_id));
-                            let summary = state.session_summary(&session_id);
-
-                            let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                                state
-                                    .agents
-                                    .process_message_streaming_with_blocks_and_summary(
-                                        &session_id,
-                                        blocks,
-                                        &caption_text,
-                                        &history,
-                                        delta_sender,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            } else {
-                                state
-                                    .agents
-                                    .process_message_with_blocks_and_summary(
-                                        &session_id,
-                                        blocks,
-                                        &caption_text,
-                                        &history,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            }
-                            .map_err(|e| e.to_string())?;
-
-                            if let Some(s) = new_summary {
-                                state.update_session_summary(&session_id, &s);
-                            }
-
-                            state
-                                .persist_turn(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                    &caption_text,
-                                    &response,
-                                    Some(serde_json::json!({"telegram_chat_id": chat_id})),
-                                )
-                                .await;
-                            Ok(response)
+                        Some(
+                            attachment @ (MediaAttachment::Photo { .. }
+                            | MediaAttachment::Video { .. }),
+                        ) => {
+                            let (image_urls, media, caption_text) =
+                                visual_attachment(&state, attachment).await?;
+                            (caption_text, image_urls, media)
                         }
                         Some(MediaAttachment::Document {
                             data,
                             filename,
-                            mime_type: _,
+                            mime_type,
                             caption,
                         }) => {
-                            if data.len() > 10 * 1024 * 1024 {
-                                return Err("File too large. Maximum size is 10MB.".to_string());
-                            }
-
                             let fname = filename.unwrap_or_else(|| "file".to_string());
-                            let ext = fname.rsplit('.').next().unwrap_or("").to_lowercase();
-                            let text_exts = [
-                                "txt", "md", "json", "csv", "log", "py", "rs", "js", "ts", "toml",
-                                "yaml", "yml", "xml", "html",
-                            ];
-
-                            if !text_exts.contains(&ext.as_str()) {
-                                return Err(format!(
-                                    "Unsupported file type (.{ext}). Supported: \
-                                     txt, md, json, csv, py, rs, js, ts, toml, yaml, yml, xml, html"
-                                ));
-                            }
-
-                            let file_content = String::from_utf8(data).map_err(|_| {
-                                "File does not appear to be valid UTF-8 text.".to_string()
-                            })?;
-                            let user_text = format!(
-                                "```{fname}\n{file_content}\n```\n\n{}",
-                                caption.unwrap_or_default()
-                            );
-
-                            let text = opencrust_security::InputValidator::sanitize(&user_text);
-                            if opencrust_security::InputValidator::check_prompt_injection(&text) {
-                                return Err("input rejected: potential prompt injection detected"
-                                    .to_string());
-                            }
-
-                            state
-                                .hydrate_session_history(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                )
-                                .await;
-                            let history: Vec<ChatMessage> = state.session_history(&session_id);
-                            let continuity_key = state.continuity_key(Some(&user_id));
-                            let summary = state.session_summary(&session_id);
-
-                            let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                                state
-                                    .agents
-                                    .process_message_streaming_with_context_and_summary(
-                                        &session_id,
-                                        &text,
-                                        &history,
-                                        delta_sender,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            } else {
-                                state
-                                    .agents
-                                    .process_message_with_context_and_summary(
-                                        &session_id,
-                                        &text,
-                                        &history,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            }
-                            .map_err(|e| e.to_string())?;
-
-                            if let Some(s) = new_summary {
-                                state.update_session_summary(&session_id, &s);
-                            }
-
-                            state
-                                .persist_turn(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                    &text,
-                                    &response,
-                                    Some(serde_json::json!({"telegram_chat_id": chat_id})),
-                                )
-                                .await;
-                            Ok(response)
-                        }
-                        None => {
-                            // Existing text-only path
-                            let text = opencrust_security::InputValidator::sanitize(&text);
-                            if opencrust_security::InputValidator::check_prompt_injection(&text) {
-                                return Err("input rejected: potential prompt injection detected"
-                                    .to_string());
-                            }
-
-                            state
-                                .hydrate_session_history(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                )
-                                .await;
-                            let history: Vec<ChatMessage> = state.session_history(&session_id);
-                            let continuity_key = state.continuity_key(Some(&user_id));
-                            let summary = state.session_summary(&session_id);
-
-                            let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                                state
-                                    .agents
-                                    .process_message_streaming_with_context_and_summary(
-                                        &session_id,
-                                        &text,
-                                        &history,
-                                        delta_sender,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            } else {
-                                state
-                                    .agents
-                                    .process_message_with_context_and_summary(
-                                        &session_id,
-                                        &text,
-                                        &history,
-                                        summary.as_deref(),
-                                        continuity_key.as_deref(),
-                                        Some(&user_id),
-                                    )
-                                    .await
-                            }
-                            .map_err(|e| e.to_string())?;
-
-                            if let Some(s) = new_summary {
-                                state.update_session_summary(&session_id, &s);
-                            }
-
-                            state
-                                .persist_turn(
-                                    &session_id,
-                                    Some("telegram"),
-                                    Some(&user_id),
-                                    &text,
-                                    &response,
-                                    Some(serde_json::json!({"telegram_chat_id": chat_id})),
+                            // Large PDFs take a while to parse; keep them off
+                            // the runtime's worker threads.
+                            let document = tokio::task::spawn_blocking(move || {
+                                opencrust_media::document::extract_document(
+                                    &data,
+                                    &fname,
+                                    mime_type.as_deref(),
                                 )
-                                .await;
-                            Ok(response)
+                            })
+                            .await
+                            .map_err(|e| format!("document extraction task failed: {e}"))?
+                            .map_err(|e| match e {
+                                opencrust_common::Error::Media(msg) => msg,
+                                other => other.to_string(),
+                            })?;
+                            (
+                                document.to_prompt(caption.as_deref()),
+                                Vec::new(),
+                                Vec::new(),
+                            )
                         }
+                        None => (text, Vec::new(), Vec::new()),
+                    };
+
+                    let message = TurnMessage {
+                        user_id: user_id.clone(),
+                        text,
+                        image_urls,
+                        media,
+                    };
+                    let Some(mut turn) = state.begin_turn(&session_id, message).await else {
+                        return Err(commands::BLOCKED.to_string());
+                    };
+
+                    let text = opencrust_security::InputValidator::sanitize(&turn.message.text);
+                    if opencrust_security::InputValidator::check_prompt_injection(&text) {
+                        return Err(
+                            "input rejected: potential prompt injection detected".to_string()
+                        );
                     }
+
+                    let image_urls = std::mem::take(&mut turn.message.image_urls);
+                    let media = std::mem::take(&mut turn.message.media);
+
+                    let response = turn
+                        .run(agent_turn(
+                            &state,
+                            "telegram",
+                            &session_id,
+                            &user_id,
+                            &text,
+                            image_urls,
+                            delta_tx,
+                        ))
+                        .await?;
+
+                    state
+                        .persist_turn_with_media(
+                            &session_id,
+                            Some("telegram"),
+                            Some(&user_id),
+                            &text,
+                            &media,
+                            &response,
+                            Some(serde_json::json!({"telegram_chat_id": chat_id})),
+                        )
+                        .await;
+
+                    Ok(response)
                 })
             },
         );
 
-        let channel = TelegramChannel::new(bot_token, on_message);
+        let channel = TelegramChannel::new(bot_token, on_message)
+            .with_voice_replier(voice_replier(state, "telegram", &channel_config.settings))
+            .with_commands(state.commands.specs().to_vec())
+            .with_group_policy(group_policy(name, &channel_config.settings));
         channels.push(Box::new(channel) as Box<dyn opencrust_channels::Channel>);
         info!("configured telegram channel: {name}");
     }
@@ -1308,221 +1417,6 @@ pub fn build_telegram_channels(
     channels
 }
 
-#[allow(clippy::too_many_arguments)]
-fn handle_command(
-    cmd: &str,
-    _full_text: &str,
-    user_id: &str,
-    user_name: &str,
-    chat_id: i64,
-    allowlist: &Arc<Mutex<Allowlist>>,
-    pairing: &Arc<Mutex<PairingManager>>,
-    state: &SharedState,
-) -> std::result::Result<String, String> {
-    let list = allowlist.lock().unwrap();
-    let is_owner = list.is_owner(user_id);
-    let is_allowed = list.is_allowed(user_id);
-    drop(list);
-
-    match cmd {
-        "start" => {
-            if is_allowed {
-                Ok(
-                    "Welcome to OpenCrust! Send me a message and I will respond.\n\n\
-                    Commands:\n\
-                    /help - show this help\n\
-                    /clear - reset conversation history\n\
-                    /pair - generate invite code (owner only)"
-                        .to_string(),
-                )
-            } else {
-                let mut list = allowlist.lock().unwrap();
-                if list.needs_owner() {
-                    list.claim_owner(user_id);
-                    info!("telegram: auto-paired owner {} ({})", user_name, user_id);
-                    Ok(format!(
-                        "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
-                         Use /pair to generate a code for adding other users.",
-                        user_name
-                    ))
-                } else {
-                    Ok("This bot is private. Send the 6-digit pairing code you received to get access.".to_string())
-                }
-            }
-        }
-        "help" => {
-            if !is_allowed {
-                return Err("__blocked__".to_string());
-            }
-            let mut help = "OpenCrust Commands:\n\
-                /help - show this help\n\
-                /clear - reset conversation history"
-                .to_string();
-            if is_owner {
-                help.push_str(
-                    "\n/pair - generate a 6-digit invite code\n/users - list allowed users",
-                );
-            }
-            Ok(help)
-        }
-        "clear" => {
-            if !is_allowed {
-                return Err("__blocked__".to_string());
-            }
-            let session_id = format!("telegram-{chat_id}");
-            if let Some(mut session) = state.sessions.get_mut(&session_id) {
-                session.history.clear();
-            }
-            Ok("Conversation history cleared.".to_string())
-        }
-        "pair" => {
-            if !is_owner {
-                if !is_allowed {
-                    return Err("__blocked__".to_string());
-                }
-                return Ok("Only the bot owner can generate pairing codes.".to_string());
-            }
-            let code = pairing.lock().unwrap().generate("telegram");
-            Ok(format!(
-                "Pairing code: {code}\n\n\
-                 Share this with the person you want to invite. \
-                 They should send this code to the bot within 5 minutes."
-            ))
-        }
-        "users" => {
-            if !is_owner {
-                if !is_allowed {
-                    return Err("__blocked__".to_string());
-                }
-                return Ok("Only the bot owner can list users.".to_string());
-            }
-            let list = allowlist.lock().unwrap();
-            let users = list.list_users();
-            let owner = list.owner().unwrap_or("none");
-            Ok(format!(
-                "Owner: {owner}\nAllowed users ({}):\n{}",
-                users.len(),
-                users.join("\n")
-            ))
-        }
-        _ => {
-            if !is_allowed {
-                return Err("__blocked__".to_string());
-            }
-            Ok(format!(
-                "Unknown command: /{cmd}\nUse /help for available commands."
-            ))
-        }
-    }
-}
-
-#[allow(clippy::too_many_arguments)]
-fn handle_discord_command(
-    cmd: &str,
-    user_id: &str,
-    user_name: &str,
-    channel_id: &str,
-    allowlist: &Arc<Mutex<Allowlist>>,
-    pairing: &Arc<Mutex<PairingManager>>,
-    state: &SharedState,
-) -> std::result::Result<String, String> {
-    let list = allowlist.lock().unwrap();
-    let is_owner = list.is_owner(user_id);
-    let is_allowed = list.is_allowed(user_id);
-    drop(list);
-
-    match cmd {
-        "start" => {
-            if is_allowed {
-                Ok(
-                    "Welcome to OpenCrust! Send me a message and I will respond.\n\n\
-                    Commands:\n\
-                    /help - show this help\n\
-                    /clear - reset conversation history\n\
-                    /pair - generate invite code (owner only)"
-                        .to_string(),
-                )
-            } else {
-                let mut list = allowlist.lock().unwrap();
-                if list.needs_owner() {
-                    list.claim_owner(user_id);
-                    info!("discord: auto-paired owner {} ({})", user_name, user_id);
-                    Ok(format!(
-                        "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
-                         Use /pair to generate a code for adding other users.",
-                        user_name
-                    ))
-                } else {
-                    Ok("This bot is private. Send the 6-digit pairing code you received to get access.".to_string())
-                }
-            }
-        }
-        "help" => {
-            if !is_allowed {
-                return Err("__blocked__".to_string());
-            }
-            let mut help = "OpenCrust Commands:\n\
-                /help - show this help\n\
-                /clear - reset conversation history"
-                .to_string();
-            if is_owner {
-                help.push_str(
-                    "\n/pair - generate a 6-digit invite code\n/users - list allowed users",
-                );
-            }
-            Ok(help)
-        }
-        "clear" => {
-            if !is_allowed {
-                return Err("__blocked__".to_string());
-            }
-            let session_id = format!("discord-{channel_id}");
-            if let Some(mut session) = state.sessions.get_mut(&session_id) {
-                session.history.clear();
-            }
-            Ok("Conversation history cleared.".to_string())
-        }
-        "pair" => {
-            if !is_owner {
-                if !is_allowed {
-                    return Err("__blocked__".to_string());
-                }
-                return Ok("Only the bot owner can generate pairing codes.".to_string());
-            }
-            let code = pairing.lock().unwrap().generate("discord");
-            Ok(format!(
-                "Pairing code: {code}\n\n\
-                 Share this with the person you want to invite. \
-                 They should send this code to the bot within 5 minutes."
-            ))
-        }
-        "users" => {
-            if !is_owner {
-                if !is_allowed {
-                    return Err("__blocked__".to_string());
-                }
-                return Ok("Only the bot owner can list users.".to_string());
-            }
-            let list = allowlist.lock().unwrap();
-            let users = list.list_users();
-            let owner = list.owner().unwrap_or("none");
-            Ok(format!(
-                "Owner: {owner}\nAllowed users ({}):\n{}",
-                users.len(),
-                users.join("\n")
-            ))
-        }
-        _ => {
-            if !is_allowed {
-                return Err("__blocked__".to_string());
-            }
-            Ok(format!(
-                "Unknown command: /{cmd}\nUse /help for available commands."
-            ))
-        }
-    }
-}
-
 /// Build Slack channels from config. Must be called after state is
 /// wrapped in `Arc` so the message callback can capture a `SharedState`.
 pub fn build_slack_channels(
@@ -1570,123 +1464,93 @@ pub fn build_slack_channels(
             continue;
         };
 
-        let allowlist = Arc::new(Mutex::new(Allowlist::load_or_create(
-            &default_allowlist_path(),
-        )));
-
-        let pairing = Arc::new(Mutex::new(PairingManager::new(
-            std::time::Duration::from_secs(300),
-        )));
-
         let state_for_cb = Arc::clone(state);
-        let allowlist_for_cb = Arc::clone(&allowlist);
-        let pairing_for_cb = Arc::clone(&pairing);
 
         let on_message: SlackOnMessageFn = Arc::new(
             move |channel_id: String,
+                  thread_ts: Option<String>,
                   user_id: String,
                   user_name: String,
                   text: String,
+                  attachments: PendingAttachments,
                   delta_tx: Option<tokio::sync::mpsc::Sender<String>>| {
                 let state = Arc::clone(&state_for_cb);
-                let allowlist = Arc::clone(&allowlist_for_cb);
-                let pairing = Arc::clone(&pairing_for_cb);
                 Box::pin(async move {
-                    // Allowlist / pairing check
-                    {
-                        let mut list = allowlist.lock().unwrap();
-                        if list.needs_owner() {
-                            list.claim_owner(&user_id);
-                            info!("slack: auto-paired owner {} ({})", user_name, user_id);
-                            return Ok(format!(
-                                "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
-                                 Use /pair to generate a code for adding other users.\n\
-                                 Use /help for available commands.",
-                                user_name
-                            ));
-                        }
-
-                        if !list.is_allowed(&user_id) {
-                            let trimmed = text.trim();
-                            if trimmed.len() == 6 && trimmed.chars().all(|c| c.is_ascii_digit()) {
-                                let claimed = pairing.lock().unwrap().claim(trimmed, &user_id);
-                                if claimed.is_some() {
-                                    list.add(&user_id);
-                                    info!(
-                                        "slack: paired user {} ({}) via code",
-                                        user_name, user_id
-                                    );
-                                    return Ok(format!(
-                                        "Welcome, {}! You now have access to this bot.",
-                                        user_name
-                                    ));
-                                }
-                            }
-
-                            warn!(
-                                "slack: unauthorized user {} ({}) in channel {}",
-                                user_name, user_id, channel_id
-                            );
-                            return Err("__blocked__".to_string());
-                        }
+                    // One session per thread; top-level DMs share the channel session.
+                    let session_id = match &thread_ts {
+                        Some(ts) => format!("slack-{channel_id}-{ts}"),
+                        None => format!("slack-{channel_id}"),
+                    };
+                    let route = slack_route(&channel_id, thread_ts.as_deref());
+
+                    let ctx = CommandContext {
+                        state: &state,
+                        channel: "slack",
+                        session_id: &session_id,
+                        user_id: &user_id,
+                        user_name: &user_name,
+                        route: route.clone(),
+                    };
+                    if let Some(reply) = commands::dispatch(&ctx, &text).await {
+                        return reply;
                     }
 
-                    let session_id = format!("slack-{channel_id}");
+                    if let Some(reply) = commands::admit(&ctx, &text).await {
+                        return reply;
+                    }
 
-                    let text = opencrust_security::InputValidator::sanitize(&text);
+                    state.set_session_route(&session_id, "slack", route.clone());
+
+                    // Shared files are downloaded with the bot token here,
+                    // after the role and rate checks.
+                    let (text, images) = attachments.resolve(&text).await;
+                    let (image_urls, media) = incoming_images(&state, images).await?;
+                    let message = TurnMessage {
+                        user_id: user_id.clone(),
+                        text,
+                        image_urls,
+                        media,
+                    };
+                    let Some(mut turn) = state.begin_turn(&session_id, message).await else {
+                        return Err(commands::BLOCKED.to_string());
+                    };
+
+                    let text = opencrust_security::InputValidator::sanitize(&turn.message.text);
                     if opencrust_security::InputValidator::check_prompt_injection(&text) {
                         return Err(
                             "input rejected: potential prompt injection detected".to_string()
                         );
                     }
 
-                    state
-                        .hydrate_session_history(&session_id, Some("slack"), Some(&user_id))
-                        .await;
-                    let history: Vec<ChatMessage> = state.session_history(&session_id);
-                    let continuity_key = state.continuity_key(Some(&user_id));
-                    let summary = state.session_summary(&session_id);
-
-                    let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                        state
-                            .agents
-                            .process_message_streaming_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                delta_sender,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&user_id),
-                            )
-                            .await
+                    let image_urls = std::mem::take(&mut turn.message.image_urls);
+                    let media = std::mem::take(&mut turn.message.media);
+                    let text = if text.trim().is_empty() && !image_urls.is_empty() {
+                        "Describe this image.".to_string()
                     } else {
-                        state
-                            .agents
-                            .process_message_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&user_id),
-                            )
-                            .await
-                    }
-                    .map_err(|e| e.to_string())?;
+                        text
+                    };
 
-                    if let Some(s) = new_summary {
-                        state.update_session_summary(&session_id, &s);
-                    }
+                    let response = turn
+                        .run(agent_turn(
+                            &state,
+                            "slack",
+                            &session_id,
+                            &user_id,
+                            &text,
+                            image_urls,
+                            delta_tx,
+                        ))
+                        .await?;
 
                     state
-                        .persist_turn(
+                        .persist_turn_with_media(
                             &session_id,
                             Some("slack"),
                             Some(&user_id),
                             &text,
+                            &media,
                             &response,
-                            Some(serde_json::json!({"slack_channel_id": channel_id})),
+                            Some(route),
                         )
                         .await;
 
@@ -1695,7 +1559,9 @@ pub fn build_slack_channels(
             },
         );
 
-        let channel = SlackChannel::new(bot_token, app_token, on_message);
+        let channel = SlackChannel::new(bot_token, app_token, on_message)
+            .with_speech_to_text(state.speech_to_text.clone())
+            .with_group_policy(group_policy(name, &channel_config.settings));
         channels.push(Box::new(channel) as Box<dyn opencrust_channels::Channel>);
         info!("configured slack channel: {name}");
     }
@@ -1703,6 +1569,16 @@ pub fn build_slack_channels(
     channels
 }
 
+/// Route metadata for a Slack session: the channel and, for threaded
+/// sessions, the thread replies belong in.
+fn slack_route(channel_id: &str, thread_ts: Option<&str>) -> serde_json::Value {
+    let mut route = serde_json::json!({ "slack_channel_id": channel_id });
+    if let Some(ts) = thread_ts {
+        route["slack_thread_ts"] = ts.into();
+    }
+    route
+}
+
 /// Build WhatsApp channels from config. Must be called after state is
 /// wrapped in `Arc` so the message callback can capture a `SharedState`.
 pub fn build_whatsapp_channels(
@@ -1771,123 +1647,102 @@ pub fn build_whatsapp_channels(
             .or_else(|| std::env::var("WHATSAPP_VERIFY_TOKEN").ok())
             .unwrap_or_else(|| "opencrust-verify".to_string());
 
-        let allowlist = Arc::new(Mutex::new(Allowlist::load_or_create(
-            &default_allowlist_path(),
-        )));
-
-        let pairing = Arc::new(Mutex::new(PairingManager::new(
-            std::time::Duration::from_secs(300),
-        )));
+        let app_secret = resolve_api_key(
+            channel_config
+                .settings
+                .get("app_secret")
+                .and_then(|v| v.as_str()),
+            "WHATSAPP_APP_SECRET",
+            "WHATSAPP_APP_SECRET",
+        );
+        if app_secret.is_none() {
+            warn!(
+                "whatsapp channel '{name}' has no app_secret; incoming webhooks will be \
+                 rejected (set app_secret in config, the vault or WHATSAPP_APP_SECRET)"
+            );
+        }
 
         let state_for_cb = Arc::clone(state);
-        let allowlist_for_cb = Arc::clone(&allowlist);
-        let pairing_for_cb = Arc::clone(&pairing);
 
         let on_message: WhatsAppOnMessageFn = Arc::new(
             move |from_number: String,
                   user_name: String,
                   text: String,
+                  attachments: PendingAttachments,
                   delta_tx: Option<tokio::sync::mpsc::Sender<String>>| {
                 let state = Arc::clone(&state_for_cb);
-                let allowlist = Arc::clone(&allowlist_for_cb);
-                let pairing = Arc::clone(&pairing_for_cb);
                 Box::pin(async move {
-                    // Allowlist / pairing check
-                    {
-                        let mut list = allowlist.lock().unwrap();
-                        if list.needs_owner() {
-                            list.claim_owner(&from_number);
-                            info!(
-                                "whatsapp: auto-paired owner {} ({})",
-                                user_name, from_number
-                            );
-                            return Ok(format!(
-                                "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
-                                 Send /pair to generate a code for adding other users.\n\
-                                 Send /help for available commands.",
-                                user_name
-                            ));
-                        }
+                    let ctx = CommandContext {
+                        state: &state,
+                        channel: "whatsapp",
+                        session_id: &format!("whatsapp-{from_number}"),
+                        user_id: &from_number,
+                        user_name: &user_name,
+                        route: serde_json::json!({"whatsapp_from": from_number}),
+                    };
+                    if let Some(reply) = commands::dispatch(&ctx, &text).await {
+                        return reply;
+                    }
 
-                        if !list.is_allowed(&from_number) {
-                            let trimmed = text.trim();
-                            if trimmed.len() == 6 && trimmed.chars().all(|c| c.is_ascii_digit()) {
-                                let claimed = pairing.lock().unwrap().claim(trimmed, &from_number);
-                                if claimed.is_some() {
-                                    list.add(&from_number);
-                                    info!(
-                                        "whatsapp: paired user {} ({}) via code",
-                                        user_name, from_number
-                                    );
-                                    return Ok(format!(
-                                        "Welcome, {}! You now have access to this bot.",
-                                        user_name
-                                    ));
-                                }
-                            }
-
-                            warn!(
-                                "whatsapp: unauthorized user {} ({})",
-                                user_name, from_number
-                            );
-                            return Err("__blocked__".to_string());
-                        }
+                    if let Some(reply) = commands::admit(&ctx, &text).await {
+                        return reply;
                     }
 
                     let session_id = format!("whatsapp-{from_number}");
+                    state.set_session_route(
+                        &session_id,
+                        "whatsapp",
+                        serde_json::json!({"whatsapp_from": from_number}),
+                    );
 
-                    let text = opencrust_security::InputValidator::sanitize(&text);
+                    // Media ids resolve to Graph API downloads only for
+                    // admitted senders.
+                    let (text, images) = attachments.resolve(&text).await;
+                    let (image_urls, media) = incoming_images(&state, images).await?;
+                    let message = TurnMessage {
+                        user_id: from_number.clone(),
+                        text,
+                        image_urls,
+                        media,
+                    };
+                    let Some(mut turn) = state.begin_turn(&session_id, message).await else {
+                        return Err(commands::BLOCKED.to_string());
+                    };
+
+                    let text = opencrust_security::InputValidator::sanitize(&turn.message.text);
                     if opencrust_security::InputValidator::check_prompt_injection(&text) {
                         return Err(
                             "input rejected: potential prompt injection detected".to_string()
                         );
                     }
 
-                    state
-                        .hydrate_session_history(&session_id, Some("whatsapp"), Some(&from_number))
-                        .await;
-                    let history: Vec<ChatMessage> = state.session_history(&session_id);
-                    let continuity_key = state.continuity_key(Some(&from_number));
-                    let summary = state.session_summary(&session_id);
-
-                    let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                        state
-                            .agents
-                            .process_message_streaming_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                delta_sender,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&from_number),
-                            )
-                            .await
+                    let image_urls = std::mem::take(&mut turn.message.image_urls);
+                    let media = std::mem::take(&mut turn.message.media);
+                    let text = if text.trim().is_empty() && !image_urls.is_empty() {
+                        "Describe this image.".to_string()
                     } else {
-                        state
-                            .agents
-                            .process_message_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&from_number),
-                            )
-                            .await
-                    }
-                    .map_err(|e| e.to_string())?;
+                        text
+                    };
 
-                    if let Some(s) = new_summary {
-                        state.update_session_summary(&session_id, &s);
-                    }
+                    let response = turn
+                        .run(agent_turn(
+                            &state,
+                            "whatsapp",
+                            &session_id,
+                            &from_number,
+                            &text,
+                            image_urls,
+                            delta_tx,
+                        ))
+                        .await?;
 
                     state
-                        .persist_turn(
+                        .persist_turn_with_media(
                             &session_id,
                             Some("whatsapp"),
                             Some(&from_number),
                             &text,
+                            &media,
                             &response,
                             Some(serde_json::json!({"whatsapp_from": from_number})),
                         )
@@ -1898,12 +1753,12 @@ pub fn build_whatsapp_channels(
             },
         );
 
-        let channel = Arc::new(WhatsAppChannel::new(
-            access_token,
-            phone_number_id,
-            verify_token,
-            on_message,
-        ));
+        let channel = Arc::new(
+            WhatsAppChannel::new(access_token, phone_number_id, verify_token, on_message)
+                .with_speech_to_text(state.speech_to_text.clone())
+                .with_voice_replier(voice_replier(state, "whatsapp", &channel_config.settings))
+                .with_app_secret(app_secret),
+        );
         channels.push(channel);
         info!("configured whatsapp channel: {name}");
     }
@@ -1947,125 +1802,89 @@ pub fn build_whatsapp_web_channels(
             continue;
         }
 
-        let allowlist = Arc::new(Mutex::new(Allowlist::load_or_create(
-            &default_allowlist_path(),
-        )));
-
-        let pairing = Arc::new(Mutex::new(PairingManager::new(
-            std::time::Duration::from_secs(300),
-        )));
-
         let state_for_cb = Arc::clone(state);
-        let allowlist_for_cb = Arc::clone(&allowlist);
-        let pairing_for_cb = Arc::clone(&pairing);
 
-        let on_message: WhatsAppOnMessageFn = Arc::new(
-            move |from_jid: String,
+        let on_message: WhatsAppWebOnMessageFn = Arc::new(
+            move |chat_jid: String,
+                  sender_jid: String,
                   user_name: String,
                   text: String,
+                  attachments: PendingAttachments,
                   delta_tx: Option<tokio::sync::mpsc::Sender<String>>| {
                 let state = Arc::clone(&state_for_cb);
-                let allowlist = Arc::clone(&allowlist_for_cb);
-                let pairing = Arc::clone(&pairing_for_cb);
                 Box::pin(async move {
-                    // Allowlist / pairing check
-                    {
-                        let mut list = allowlist.lock().unwrap();
-                        if list.needs_owner() {
-                            list.claim_owner(&from_jid);
-                            info!(
-                                "whatsapp-web: auto-paired owner {} ({})",
-                                user_name, from_jid
-                            );
-                            return Ok(format!(
-                                "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
-                                 Send /pair to generate a code for adding other users.\n\
-                                 Send /help for available commands.",
-                                user_name
-                            ));
-                        }
-
-                        if !list.is_allowed(&from_jid) {
-                            let trimmed = text.trim();
-                            if trimmed.len() == 6 && trimmed.chars().all(|c| c.is_ascii_digit()) {
-                                let claimed = pairing.lock().unwrap().claim(trimmed, &from_jid);
-                                if claimed.is_some() {
-                                    list.add(&from_jid);
-                                    info!(
-                                        "whatsapp-web: paired user {} ({}) via code",
-                                        user_name, from_jid
-                                    );
-                                    return Ok(format!(
-                                        "Welcome, {}! You now have access to this bot.",
-                                        user_name
-                                    ));
-                                }
-                            }
-
-                            warn!(
-                                "whatsapp-web: unauthorized user {} ({})",
-                                user_name, from_jid
-                            );
-                            return Err("__blocked__".to_string());
-                        }
+                    // One session per chat; in groups, access is checked per sender.
+                    let session_id = format!("whatsapp-web-{chat_jid}");
+                    let route = serde_json::json!({"whatsapp_from": chat_jid});
+
+                    let ctx = CommandContext {
+                        state: &state,
+                        channel: "whatsapp-web",
+                        session_id: &session_id,
+                        user_id: &sender_jid,
+                        user_name: &user_name,
+                        route: route.clone(),
+                    };
+                    if let Some(reply) = commands::dispatch(&ctx, &text).await {
+                        return reply;
                     }
 
-                    let session_id = format!("whatsapp-web-{from_jid}");
+                    if let Some(reply) = commands::admit(&ctx, &text).await {
+                        return reply;
+                    }
 
-                    let text = opencrust_security::InputValidator::sanitize(&text);
+                    state.set_session_route(&session_id, "whatsapp-web", route.clone());
+
+                    // The sidecar has saved any media to disk; it is read
+                    // here and deleted once this callback returns.
+                    let (text, images) = attachments.resolve(&text).await;
+                    let (image_urls, media) = incoming_images(&state, images).await?;
+                    let message = TurnMessage {
+                        user_id: sender_jid.clone(),
+                        text,
+                        image_urls,
+                        media,
+                    };
+                    let Some(mut turn) = state.begin_turn(&session_id, message).await else {
+                        return Err(commands::BLOCKED.to_string());
+                    };
+
+                    let text = opencrust_security::InputValidator::sanitize(&turn.message.text);
                     if opencrust_security::InputValidator::check_prompt_injection(&text) {
                         return Err(
                             "input rejected: potential prompt injection detected".to_string()
                         );
                     }
 
-                    state
-                        .hydrate_session_history(&session_id, Some("whatsapp-web"), Some(&from_jid))
-                        .await;
-                    let history: Vec<ChatMessage> = state.session_history(&session_id);
-                    let continuity_key = state.continuity_key(Some(&from_jid));
-                    let summary = state.session_summary(&session_id);
-
-                    let (response, new_summary) = if let Some(delta_sender) = delta_tx {
-                        state
-                            .agents
-                            .process_message_streaming_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                delta_sender,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&from_jid),
-                            )
-                            .await
+                    let image_urls = std::mem::take(&mut turn.message.image_urls);
+                    let media = std::mem::take(&mut turn.message.media);
+                    let text = if text.trim().is_empty() && !image_urls.is_empty() {
+                        "Describe this image.".to_string()
                     } else {
-                        state
-                            .agents
-                            .process_message_with_context_and_summary(
-                                &session_id,
-                                &text,
-                                &history,
-                                summary.as_deref(),
-                                continuity_key.as_deref(),
-                                Some(&from_jid),
-                            )
-                            .await
-                    }
-                    .map_err(|e| e.to_string())?;
+                        text
+                    };
 
-                    if let Some(s) = new_summary {
-                        state.update_session_summary(&session_id, &s);
-                    }
+                    let response = turn
+                        .run(agent_turn(
+                            &state,
+                            "whatsapp-web",
+                            &session_id,
+                            &sender_jid,
+                            &text,
+                            image_urls,
+                            delta_tx,
+                        ))
+                        .await?;
 
                     state
-                        .persist_turn(
+                        .persist_turn_with_media(
                             &session_id,
                             Some("whatsapp-web"),
-                            Some(&from_jid),
+                            Some(&sender_jid),
                             &text,
+                            &media,
                             &response,
-                            Some(serde_json::json!({"whatsapp_from": from_jid})),
+                            Some(route),
                         )
                         .await;
 
@@ -2074,7 +1893,12 @@ pub fn build_whatsapp_web_channels(
             },
         );
 
-        let channel = WhatsAppWebChannel::new(on_message);
+        let channel = WhatsAppWebChannel::new(on_message)
+            .with_speech_to_text(state.speech_to_text.clone())
+            .with_group_policy(group_policy(name, &channel_config.settings));
+        state
+            .whatsapp_web_status
+            .insert(name.clone(), channel.status_handle());
         channels.push(channel);
         info!("configured whatsapp-web channel: {name}");
     }
@@ -2103,17 +1927,7 @@ pub fn build_imessage_channels(
             .and_then(|v| v.as_u64())
             .unwrap_or(2);
 
-        let allowlist = Arc::new(Mutex::new(Allowlist::load_or_create(
-            &default_allowlist_path(),
-        )));
-
-        let pairing = Arc::new(Mutex::new(PairingManager::new(
-            std::time::Duration::from_secs(300),
-        )));
-
         let state_for_cb = Arc::clone(state);
-        let allowlist_for_cb = Arc::clone(&allowlist);
-        let pairing_for_cb = Arc::clone(&pairing);
 
         let on_message: IMessageOnMessageFn = Arc::new(
             move |session_key: String,
@@ -2121,73 +1935,58 @@ pub fn build_imessage_channels(
                   text: String,
                   _delta_tx: Option<tokio::sync::mpsc::Sender<String>>| {
                 let state = Arc::clone(&state_for_cb);
-                let allowlist = Arc::clone(&allowlist_for_cb);
-                let pairing = Arc::clone(&pairing_for_cb);
                 Box::pin(async move {
-                    // Allowlist / pairing check (always against the actual sender)
-                    {
-                        let mut list = allowlist.lock().unwrap();
-                        if list.needs_owner() {
-                            list.claim_owner(&sender_id);
-                            info!("imessage: auto-paired owner {sender_id}");
-                            return Ok("Welcome! You are now the owner of this OpenCrust bot.\n\n\
-                                 Send /pair to generate a code for adding other users.\n\
-                                 Send /help for available commands."
-                                .to_string());
-                        }
+                    let ctx = CommandContext {
+                        state: &state,
+                        channel: "imessage",
+                        session_id: &format!("imessage-{session_key}"),
+                        user_id: &sender_id,
+                        user_name: &sender_id,
+                        route: serde_json::json!({"imessage_sender": sender_id}),
+                    };
+                    if let Some(reply) = commands::dispatch(&ctx, &text).await {
+                        return reply;
+                    }
 
-                        if !list.is_allowed(&sender_id) {
-                            let trimmed = text.trim();
-                            if trimmed.len() == 6 && trimmed.chars().all(|c| c.is_ascii_digit()) {
-                                let claimed = pairing.lock().unwrap().claim(trimmed, &sender_id);
-                                if claimed.is_some() {
-                                    list.add(&sender_id);
-                                    info!("imessage: paired user {sender_id} via code");
-                                    return Ok(
-                                        "Welcome! You now have access to this bot.".to_string()
-                                    );
-                                }
-                            }
-
-                            warn!("imessage: unauthorized user {sender_id}");
-                            return Err("__blocked__".to_string());
-                        }
+                    if let Some(reply) = commands::admit(&ctx, &text).await {
+                        return reply;
                     }
 
                     // session_key is group_name for groups, sender handle for DMs
                     let session_id = format!("imessage-{session_key}");
+                    state.set_session_route(
+                        &session_id,
+                        "imessage",
+                        serde_json::json!({"imessage_sender": sender_id}),
+                    );
+
+                    let message = TurnMessage {
+                        user_id: sender_id.clone(),
+                        text,
+                        ..TurnMessage::default()
+                    };
+                    let Some(mut turn) = state.begin_turn(&session_id, message).await else {
+                        return Err(commands::BLOCKED.to_string());
+                    };
 
-                    let text = opencrust_security::InputValidator::sanitize(&text);
+                    let text = opencrust_security::InputValidator::sanitize(&turn.message.text);
                     if opencrust_security::InputValidator::check_prompt_injection(&text) {
                         return Err(
                             "input rejected: potential prompt injection detected".to_string()
                         );
                     }
 
-                    state
-                        .hydrate_session_history(&session_id, Some("imessage"), Some(&sender_id))
-                        .await;
-                    let history: Vec<opencrust_agents::ChatMessage> =
-                        state.session_history(&session_id);
-                    let continuity_key = state.continuity_key(Some(&sender_id));
-                    let summary = state.session_summary(&session_id);
-
-                    let (response, new_summary) = state
-                        .agents
-                        .process_message_with_context_and_summary(
+                    let response = turn
+                        .run(agent_turn(
+                            &state,
+                            "imessage",
                             &session_id,
+                            &sender_id,
                             &text,
-                            &history,
-                            summary.as_deref(),
-                            continuity_key.as_deref(),
-                            Some(&sender_id),
-                        )
-                        .await
-                        .map_err(|e| e.to_string())?;
-
-                    if let Some(s) = new_summary {
-                        state.update_session_summary(&session_id, &s);
-                    }
+                            Vec::new(),
+                            None,
+                        ))
+                        .await?;
 
                     state
                         .persist_turn(
@@ -0,0 +1,1444 @@
+//! Chat commands shared by every channel.
+//!
+//! Each channel callback builds a [`CommandContext`] and hands incoming text
+//! to [`dispatch`] before the message reaches the agent. Permission checks,
+//! argument parsing and help output all come from the [`CommandRegistry`]
+//! built by [`builtin_commands`], so a command behaves the same on Telegram,
+//! Discord, Slack, WhatsApp and iMessage.
+
+use chrono::Utc;
+use opencrust_agents::{ChatMessage, ChatRole, ContentBlock, MessagePart, SessionOverrides};
+use opencrust_channels::{ArgSpec, CommandRegistry, CommandSpec, ParsedCommand, Permission};
+use opencrust_common::OutboundFile;
+use opencrust_config::NamedAgentConfig;
+use opencrust_db::{FactQuery, RecallQuery};
+use opencrust_security::{Role, parse_invite_code};
+use tracing::{info, warn};
+
+use crate::access::parse_subject;
+use crate::state::{LINK_CODE_TTL, SharedState, account_key, split_account_key};
+use crate::voice::voice_command;
+
+/// Callback error that makes a channel drop the message without replying.
+pub(crate) const BLOCKED: &str = "__blocked__";
+
+/// How many memory facts, search hits or reminders a listing shows.
+const LIST_LIMIT: usize = 20;
+
+/// Lifetime of invites created without `expires=`.
+const INVITE_LIFETIME_DAYS: i64 = 7;
+
+/// Lifetime of the single-use codes from `/pair`.
+const PAIR_CODE_MINUTES: i64 = 5;
+
+/// The commands every channel understands, in `/help` order.
+pub fn builtin_commands() -> CommandRegistry {
+    let mut registry = CommandRegistry::new();
+    for spec in [
+        CommandSpec::new("start", "Start talking to the bot", Permission::Anyone)
+            .arg(ArgSpec::text("payload", "Invite code from a deep link")),
+        CommandSpec::new("help", "Show available commands", Permission::Allowed),
+        CommandSpec::new("clear", "Reset conversation history", Permission::Allowed),
+        CommandSpec::new(
+            "voice",
+            "Choose when replies come as voice messages",
+            Permission::Allowed,
+        )
+        .arg(ArgSpec::choice(
+            "mode",
+            "When to reply with voice",
+            &["auto", "always", "never"],
+        )),
+        CommandSpec::new(
+            "model",
+            "Show or switch the model for this chat",
+            Permission::Allowed,
+        )
+        .arg(ArgSpec::text(
+            "name",
+            "Provider id, provider/model, model name, or reset",
+        )),
+        CommandSpec::new(
+            "agent",
+            "List agents or switch this chat to one",
+            Permission::Allowed,
+        )
+        .arg(ArgSpec::text("name", "Agent name, or reset")),
+        CommandSpec::new(
+            "memory",
+            "List, search or forget memories",
+            Permission::Allowed,
+        )
+        .arg(ArgSpec::choice(
+            "action",
+            "What to do",
+            &["list", "search", "forget"],
+        ))
+        .arg(ArgSpec::text("query", "Search text or memory id")),
+        CommandSpec::new("schedule", "List or cancel reminders", Permission::Allowed)
+            .arg(ArgSpec::choice("action", "What to do", &["list", "cancel"]))
+            .arg(ArgSpec::text("id", "Reminder id to cancel")),
+        CommandSpec::new("usage", "Show token usage", Permission::Allowed),
+        CommandSpec::new(
+            "export",
+            "Send this conversation as a file",
+            Permission::Allowed,
+        ),
+        CommandSpec::new(
+            "link",
+            "Link this account with your account on another channel",
+            Permission::Allowed,
+        )
+        .arg(ArgSpec::text(
+            "code",
+            "Code from /link on the other channel",
+        )),
+        CommandSpec::new(
+            "unlink",
+            "Stop sharing memory with your other accounts",
+            Permission::Allowed,
+        ),
+        CommandSpec::new(
+            "pair",
+            "Generate a single-use invite code",
+            Permission::Owner,
+        ),
+        CommandSpec::new(
+            "invites",
+            "List, create or revoke invites",
+            Permission::Owner,
+        )
+        .arg(ArgSpec::choice(
+            "action",
+            "What to do",
+            &["list", "create", "revoke"],
+        ))
+        .arg(ArgSpec::text(
+            "options",
+            "Role and options (uses=N expires=7d agent=name), or the code to revoke",
+        )),
+        CommandSpec::new("users", "List users and their roles", Permission::Owner),
+        CommandSpec::new("role", "Change a user's role", Permission::Owner)
+            .arg(ArgSpec::text("user", "User id, or channel:user_id"))
+            .arg(ArgSpec::choice(
+                "role",
+                "New role",
+                &["owner", "admin", "member", "guest", "none"],
+            )),
+    ] {
+        registry.register(spec);
+    }
+    registry
+}
+
+/// Everything a command needs to know about the message that invoked it.
+pub struct CommandContext<'a> {
+    pub state: &'a SharedState,
+    /// Channel type, e.g. `telegram` or `whatsapp-web`.
+    pub channel: &'a str,
+    pub session_id: &'a str,
+    pub user_id: &'a str,
+    pub user_name: &'a str,
+    /// Routing metadata the channel's sender needs to reach this chat.
+    pub route: serde_json::Value,
+}
+
+impl CommandContext<'_> {
+    fn role(&self) -> Option<Role> {
+        self.state.role_of(self.channel, self.user_id)
+    }
+
+    /// Command level of the user: owners and admins may run owner commands,
+    /// members and guests allowed ones.
+    fn permission(&self) -> Permission {
+        match self.role() {
+            Some(Role::Owner | Role::Admin) => Permission::Owner,
+            Some(Role::Member | Role::Guest) => Permission::Allowed,
+            None => Permission::Anyone,
+        }
+    }
+}
+
+/// Run `text` as a command. Returns `None` when it is not a command, so the
+/// caller can pass it on to the agent.
+///
+/// Users without a role only get a reply from commands open to
+/// [`Permission::Anyone`]; everything else is dropped as `__blocked__`. The
+/// `roles:` config can further limit which commands a role may run.
+pub async fn dispatch(
+    ctx: &CommandContext<'_>,
+    text: &str,
+) -> Option<std::result::Result<String, String>> {
+    let parsed = ctx.state.commands.parse(text)?;
+    let level = ctx.permission();
+
+    let command = match parsed {
+        Ok(command) => command,
+        Err(_) if level == Permission::Anyone => return Some(Err(BLOCKED.to_string())),
+        Err(e) => return Some(Ok(e.to_string())),
+    };
+    if command.spec.permission > level {
+        if level == Permission::Anyone {
+            return Some(Err(BLOCKED.to_string()));
+        }
+        return Some(Ok(format!(
+            "Only owners and admins can use /{}.",
+            command.spec.name
+        )));
+    }
+    if let Some(role) = ctx.role()
+        && !ctx.state.access.allows_command(role, command.spec.name)
+    {
+        return Some(Ok(format!(
+            "Your role ({role}) cannot use /{}.",
+            command.spec.name
+        )));
+    }
+
+    Some(Ok(run(ctx, &command, level).await))
+}
+
+async fn run(ctx: &CommandContext<'_>, command: &ParsedCommand<'_>, level: Permission) -> String {
+    let args = &command.args;
+    match command.spec.name {
+        "start" => start(ctx, level, args.text("payload")).await,
+        "help" => format!(
+            "OpenCrust Commands:\n{}",
+            ctx.state.commands.help_text(level)
+        ),
+        "clear" => {
+            if let Some(mut session) = ctx.state.sessions.get_mut(ctx.session_id) {
+                session.history.clear();
+            }
+            "Conversation history cleared.".to_string()
+        }
+        "voice" => voice_command(ctx.state, ctx.channel, ctx.user_id, args.text("mode")).await,
+        "model" => model(ctx, args.text("name")),
+        "agent" => agent(ctx, args.text("name")),
+        "memory" => memory(ctx, args.text("action"), args.text("query")).await,
+        "schedule" => schedule(ctx, args.text("action"), args.text("id")).await,
+        "usage" => usage(ctx, level),
+        "export" => export(ctx).await,
+        "link" => link(ctx, args.text("code")).await,
+        "unlink" => match ctx.state.unlink_identity(ctx.channel, ctx.user_id).await {
+            Ok(true) => "This account is no longer linked to your other accounts.".to_string(),
+            Ok(false) => "This account is not linked.".to_string(),
+            Err(e) => {
+                warn!("unlink failed for {}:{}: {e}", ctx.channel, ctx.user_id);
+                "Could not unlink this account.".to_string()
+            }
+        },
+        "pair" => pair(ctx).await,
+        "invites" => invites(ctx, args.text("action"), args.text("options")).await,
+        "users" => {
+            let users = ctx.state.access.assignments();
+            let lines: Vec<String> = users
+                .iter()
+                .map(|(subject, role)| format!("- {subject}: {role}"))
+                .collect();
+            format!("Users ({}):\n{}", users.len(), lines.join("\n"))
+        }
+        "role" => set_role(ctx, args.text("user"), args.text("role")).await,
+        other => format!("/{other} is not available here."),
+    }
+}
+
+/// `/link` without a code hands out one; `/link <code>` on another channel
+/// joins the two accounts under one identity.
+async fn link(ctx: &CommandContext<'_>, code: Option<&str>) -> String {
+    let me = account_key(ctx.channel, ctx.user_id);
+    let Some(code) = code else {
+        let code = ctx.state.link_codes.lock().unwrap().generate(&me);
+        return format!(
+            "Link code: {code}\n\n\
+             Send /link {code} to the bot from your other account within {} minutes.{}",
+            LINK_CODE_TTL.as_secs() / 60,
+            linked_list(ctx)
+        );
+    };
+
+    let claimed = ctx.state.link_codes.lock().unwrap().claim(code, &me);
+    let Some((channel, user_id)) = claimed.as_deref().and_then(split_account_key) else {
+        return "Invalid or expired link code.".to_string();
+    };
+    if (channel.as_str(), user_id.as_str()) == (ctx.channel, ctx.user_id) {
+        return "Send the code from your other account, not this one.".to_string();
+    }
+    match ctx
+        .state
+        .link_identity(ctx.channel, ctx.user_id, &channel, &user_id)
+        .await
+    {
+        Ok(identity) => {
+            info!(
+                "linked {}:{} to {channel}:{user_id} as {identity}",
+                ctx.channel, ctx.user_id
+            );
+            format!(
+                "Linked. Your accounts now share memory and conversation context.{}",
+                linked_list(ctx)
+            )
+        }
+        Err(e) => {
+            warn!("linking {}:{} failed: {e}", ctx.channel, ctx.user_id);
+            "Could not link the accounts.".to_string()
+        }
+    }
+}
+
+fn linked_list(ctx: &CommandContext<'_>) -> String {
+    let accounts = ctx.state.linked_accounts(ctx.channel, ctx.user_id);
+    if accounts.is_empty() {
+        return String::new();
+    }
+    let lines: Vec<String> = accounts
+        .iter()
+        .map(|(channel, user_id)| format!("- {channel}: {user_id}"))
+        .collect();
+    format!("\n\nLinked accounts:\n{}", lines.join("\n"))
+}
+
+/// `/start`, optionally with an invite code as payload (Telegram deep links
+/// such as `t.me/<bot>?start=<code>` send one).
+async fn start(ctx: &CommandContext<'_>, level: Permission, payload: Option<&str>) -> String {
+    if level >= Permission::Allowed {
+        return format!(
+            "Welcome to OpenCrust! Send me a message and I will respond.\n\nCommands:\n{}",
+            ctx.state.commands.help_text(level)
+        );
+    }
+
+    if let Some(reply) = claim_ownership(ctx).await {
+        return reply;
+    }
+    if let Some(code) = payload.and_then(parse_invite_code)
+        && let Some(reply) = claim_invite(ctx, &code).await
+    {
+        return reply;
+    }
+    "This bot is private. Send the invite code you received to get access.".to_string()
+}
+
+/// Make the first user to write the owner. Returns the welcome message, or
+/// `None` when the bot already has an owner.
+async fn claim_ownership(ctx: &CommandContext<'_>) -> Option<String> {
+    if ctx.state.access.has_owner() {
+        return None;
+    }
+    if let Err(e) = ctx
+        .state
+        .assign_role(ctx.channel, ctx.user_id, Some(Role::Owner), None)
+        .await
+    {
+        warn!("{}: failed to store owner role: {e}", ctx.channel);
+    }
+    info!(
+        "{}: auto-paired owner {} ({})",
+        ctx.channel, ctx.user_name, ctx.user_id
+    );
+    Some(format!(
+        "Welcome, {}! You are now the owner of this OpenCrust bot.\n\n\
+         Use /pair to generate a code for adding other users.\n\
+         Use /help for available commands.",
+        ctx.user_name
+    ))
+}
+
+/// Give a user without a role the role of an invite, and switch the chat to
+/// the invite's agent. Returns the welcome message, or `None` when the code
+/// cannot be used.
+async fn claim_invite(ctx: &CommandContext<'_>, code: &str) -> Option<String> {
+    let invite = match ctx.state.claim_invite(code, ctx.channel, ctx.user_id).await {
+        Ok(invite) => invite?,
+        Err(e) => {
+            warn!("{}: failed to claim invite {code}: {e}", ctx.channel);
+            return None;
+        }
+    };
+    let mut reply = format!(
+        "Welcome, {}! You now have access to this bot as {}.",
+        ctx.user_name, invite.role
+    );
+    if let Some(agent) = &invite.agent
+        && let Some(named) = ctx.state.current_config().agents.get(agent)
+    {
+        use_agent(ctx, agent, named);
+        reply.push_str(&format!(" This chat uses the {agent} agent."));
+    }
+    Some(reply)
+}
+
+/// Decide whether a message that is not a command goes to the agent.
+/// Returns the reply to send instead, or `None` to go ahead.
+///
+/// The first user becomes the owner and an invite code (including one from
+/// `/pair`) gives the sender the invite's role; everyone else without a
+/// role is dropped as `__blocked__`. Users over their daily
+/// token budget, or in a chat that uses an agent their role may not, get an
+/// explanation, as do users and chats over their message rate (once per
+/// cooldown; later messages are dropped).
+pub async fn admit(
+    ctx: &CommandContext<'_>,
+    text: &str,
+) -> Option<std::result::Result<String, String>> {
+    ctx.state
+        .migrate_legacy_role(ctx.channel, ctx.user_id)
+        .await;
+    let Some(role) = ctx.role() else {
+        if let Some(reply) = claim_ownership(ctx).await {
+            return Some(Ok(reply));
+        }
+        if let Some(code) = parse_invite_code(text)
+            && let Some(reply) = claim_invite(ctx, &code).await
+        {
+            return Some(Ok(reply));
+        }
+        warn!(
+            "{}: unauthorized user {} ({}) in session {}",
+            ctx.channel, ctx.user_name, ctx.user_id, ctx.session_id
+        );
+        return Some(Err(BLOCKED.to_string()));
+    };
+
+    if let Some(agent) = ctx
+        .state
+        .agents
+        .session_overrides(ctx.session_id)
+        .and_then(|overrides| overrides.agent)
+        && !ctx.state.access.allows_agent(role, &agent)
+    {
+        return Some(Ok(format!(
+            "This chat uses the {agent} agent, which your role ({role}) cannot use."
+        )));
+    }
+    if ctx.state.tokens_left(ctx.channel, ctx.user_id) == Some(0) {
+        return Some(Ok(
+            "You have used today's token budget. Try again tomorrow.".to_string(),
+        ));
+    }
+    if let Err(limited) = ctx
+        .state
+        .check_rate(ctx.channel, ctx.user_id, ctx.session_id, role)
+    {
+        if !limited.notify {
+            return Some(Err(BLOCKED.to_string()));
+        }
+        let wait = wait_text(limited.retry_after);
+        return Some(Ok(if limited.chat {
+            format!("This chat is sending messages too quickly. Please wait {wait}.")
+        } else {
+            format!("Slow down a little! You can send your next message in {wait}.")
+        }));
+    }
+    None
+}
+
+/// A wait rounded up to seconds, or to minutes from two minutes on.
+fn wait_text(wait: std::time::Duration) -> String {
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    match secs {
+        0..=1 => "a second".to_string(),
+        2..=119 => format!("{secs} seconds"),
+        _ => format!("{} minutes", secs.div_ceil(60)),
+    }
+}
+
+/// `/invites [list|create|revoke]`. `create <role> [uses=N] [expires=7d]
+/// [agent=name]` makes a code that lasts a week unless `expires` says
+/// otherwise (`expires=never` for no limit).
+async fn invites(ctx: &CommandContext<'_>, action: Option<&str>, options: Option<&str>) -> String {
+    if ctx.state.session_store.is_none() {
+        return "Invites are unavailable (no session store).".to_string();
+    }
+    match action.unwrap_or("list") {
+        "create" => create_invite(ctx, options.unwrap_or_default()).await,
+        "revoke" => {
+            let Some(code) = options.and_then(parse_invite_code) else {
+                return "Usage: /invites revoke <code>".to_string();
+            };
+            match ctx.state.revoke_invite(&code).await {
+                Ok(true) => format!("Invite {code} revoked."),
+                Ok(false) => format!("No active invite {code}."),
+                Err(e) => {
+                    warn!("revoking invite {code} failed: {e}");
+                    "Could not revoke the invite.".to_string()
+                }
+            }
+        }
+        _ => {
+            let invites = match ctx.state.invites().await {
+                Ok(invites) => invites,
+                Err(e) => {
+                    warn!("listing invites failed: {e}");
+                    return "Could not load invites.".to_string();
+                }
+            };
+            if invites.is_empty() {
+                return "No invites yet. Use /invites create <role> to make one.".to_string();
+            }
+            let now = Utc::now();
+            let list = invites
+                .iter()
+                .take(LIST_LIMIT)
+                .map(|invite| {
+                    let uses = match invite.max_uses {
+                        Some(max) => format!("{}/{max} uses", invite.uses),
+                        None => format!("{} uses", invite.uses),
+                    };
+                    let status = if invite.revoked_at.is_some() {
+                        " (revoked)".to_string()
+                    } else if invite.max_uses.is_some_and(|max| invite.uses >= max) {
+                        " (used up)".to_string()
+                    } else if !invite.is_usable(now) {
+                        " (expired)".to_string()
+                    } else {
+                        invite
+                            .expires_at
+                            .map(|at| format!(", expires {}", at.format("%Y-%m-%d %H:%M UTC")))
+                            .unwrap_or_default()
+                    };
+                    let agent = invite
+                        .agent
+                        .as_deref()
+                        .map(|a| format!(", agent {a}"))
+                        .unwrap_or_default();
+                    format!("- {}: {}{agent}, {uses}{status}", invite.code, invite.role)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("Invites:\n{list}\n\nUse /invites revoke <code> to revoke one.")
+        }
+    }
+}
+
+/// `/pair`: a single-use member invite that expires after
+/// [`PAIR_CODE_MINUTES`].
+async fn pair(ctx: &CommandContext<'_>) -> String {
+    if ctx.state.session_store.is_none() {
+        return "Pairing is unavailable (no session store).".to_string();
+    }
+    let creator = ctx.state.identity_key(ctx.channel, ctx.user_id);
+    let expires_at = Utc::now() + chrono::Duration::minutes(PAIR_CODE_MINUTES);
+    match ctx
+        .state
+        .create_invite(
+            Role::Member,
+            None,
+            Some(1),
+            Some(expires_at),
+            Some(&creator),
+        )
+        .await
+    {
+        Ok(invite) => format!(
+            "Pairing code: {}\n\n\
+             Share this with the person you want to invite. \
+             They should send this code to the bot within {PAIR_CODE_MINUTES} minutes.",
+            invite.code
+        ),
+        Err(e) => {
+            warn!("creating pairing code failed: {e}");
+            "Could not create a pairing code.".to_string()
+        }
+    }
+}
+
+async fn create_invite(ctx: &CommandContext<'_>, options: &str) -> String {
+    const USAGE: &str = "Usage: /invites create <admin|member|guest> [uses=N] [expires=30m|12h|7d|never] [agent=name]";
+    let mut words = options.split_whitespace();
+    let Some(role) = words.next().and_then(|w| w.parse::<Role>().ok()) else {
+        return USAGE.to_string();
+    };
+    let mut max_uses = None;
+    let mut expires_at = Some(Utc::now() + chrono::Duration::days(INVITE_LIFETIME_DAYS));
+    let mut agent = None;
+    for word in words {
+        match word.split_once('=') {
+            Some(("uses", n)) => match n.parse() {
+                Ok(n) => max_uses = Some(n),
+                Err(_) => return USAGE.to_string(),
+            },
+            Some(("expires", "never")) => expires_at = None,
+            Some(("expires", lifetime)) => match parse_lifetime(lifetime) {
+                Some(lifetime) => expires_at = Some(Utc::now() + lifetime),
+                None => return USAGE.to_string(),
+            },
+            Some(("agent", name)) => agent = Some(name),
+            _ => return USAGE.to_string(),
+        }
+    }
+    if let Err(e) = ctx.state.check_invite(ctx.role(), role, agent, max_uses) {
+        return format!("Cannot create that invite: {e}.");
+    }
+
+    let creator = ctx.state.identity_key(ctx.channel, ctx.user_id);
+    match ctx
+        .state
+        .create_invite(role, agent, max_uses, expires_at, Some(&creator))
+        .await
+    {
+        Ok(invite) => {
+            let uses = invite
+                .max_uses
+                .map_or_else(|| "any number of".to_string(), |max| max.to_string());
+            let expiry = invite
+                .expires_at
+                .map(|at| format!("until {}", at.format("%Y-%m-%d %H:%M UTC")))
+                .unwrap_or_else(|| "with no expiry".to_string());
+            format!(
+                "Invite code: {}\n\n\
+                 Valid for {uses} people {expiry}. They should send this code to the bot \
+                 on any channel to join as {role}.",
+                invite.code
+            )
+        }
+        Err(e) => {
+            warn!("creating invite failed: {e}");
+            "Could not create the invite.".to_string()
+        }
+    }
+}
+
+/// Parse a lifetime such as `30m`, `12h` or `7d`.
+fn parse_lifetime(text: &str) -> Option<chrono::Duration> {
+    let unit = text.chars().last()?;
+    let n: i64 = text[..text.len() - unit.len_utf8()]
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)?;
+    match unit {
+        'm' => chrono::Duration::try_minutes(n),
+        'h' => chrono::Duration::try_hours(n),
+        'd' => chrono::Duration::try_days(n),
+        _ => None,
+    }
+}
+
+/// `/role <user> <role>`: owners change any role, admins only those of
+/// members and guests. `none` takes the role away.
+async fn set_role(ctx: &CommandContext<'_>, target: Option<&str>, role: Option<&str>) -> String {
+    let (Some(target), Some(role)) = (target, role) else {
+        return "Usage: /role <user> <owner|admin|member|guest|none>".to_string();
+    };
+    let Some(actor) = ctx.role() else {
+        return "You have no role.".to_string();
+    };
+    let new_role = role.parse::<Role>().ok();
+    let (channel, user_id) = parse_subject(target, ctx.channel);
+    if (channel.as_str(), user_id.as_str()) == (ctx.channel, ctx.user_id) {
+        return "You cannot change your own role.".to_string();
+    }
+    let current = ctx.state.stored_role(&channel, &user_id);
+    if current.is_some_and(|r| !actor.can_manage(r))
+        || new_role.is_some_and(|r| !actor.can_manage(r))
+    {
+        return format!("Your role ({actor}) cannot make that change.");
+    }
+
+    let granted_by = account_key(ctx.channel, ctx.user_id);
+    match ctx
+        .state
+        .assign_role(&channel, &user_id, new_role, Some(&granted_by))
+        .await
+    {
+        Ok(()) => match new_role {
+            Some(role) => format!("{channel}:{user_id} is now {role}."),
+            None => format!("{channel}:{user_id} no longer has a role."),
+        },
+        Err(e) => {
+            warn!("changing the role of {channel}:{user_id} failed: {e}");
+            "Could not change the role.".to_string()
+        }
+    }
+}
+
+fn model(ctx: &CommandContext<'_>, name: Option<&str>) -> String {
+    let agents = &ctx.state.agents;
+    let mut overrides = agents.session_overrides(ctx.session_id).unwrap_or_default();
+    let providers = agents.provider_ids();
+
+    let Some(name) = name else {
+        let provider = overrides
+            .provider_id
+            .clone()
+            .or_else(|| agents.default_provider_id())
+            .unwrap_or_else(|| "none".to_string());
+        let model = overrides.model.as_deref().unwrap_or("provider default");
+        return format!(
+            "Provider: {provider}\nModel: {model}\nAvailable providers: {}\n\n\
+             Use /model <provider>, /model <provider>/<model>, /model <model>, or /model reset.",
+            providers.join(", ")
+        );
+    };
+
+    if name.eq_ignore_ascii_case("reset") {
+        overrides.provider_id = None;
+        overrides.model = None;
+        agents.set_session_overrides(ctx.session_id, overrides);
+        return "Model reset to the default for this chat.".to_string();
+    }
+
+    if providers.iter().any(|p| p == name) {
+        overrides.provider_id = Some(name.to_string());
+        overrides.model = None;
+    } else if let Some((provider, model)) = name.split_once('/')
+        && providers.iter().any(|p| p == provider)
+    {
+        overrides.provider_id = Some(provider.to_string());
+        overrides.model = Some(model.to_string());
+    } else {
+        overrides.model = Some(name.to_string());
+    }
+
+    let reply = format!(
+        "This chat now uses {}{}.",
+        overrides
+            .provider_id
+            .clone()
+            .or_else(|| agents.default_provider_id())
+            .unwrap_or_else(|| "the default provider".to_string()),
+        overrides
+            .model
+            .as_deref()
+            .map(|m| format!(" with model {m}"))
+            .unwrap_or_default()
+    );
+    agents.set_session_overrides(ctx.session_id, overrides);
+    reply
+}
+
+fn agent(ctx: &CommandContext<'_>, name: Option<&str>) -> String {
+    let config = ctx.state.current_config();
+    let agents = &ctx.state.agents;
+    let current = agents
+        .session_overrides(ctx.session_id)
+        .and_then(|o| o.agent);
+    let role = ctx.role();
+    let allowed = |name: &str| role.is_none_or(|role| ctx.state.access.allows_agent(role, name));
+
+    let Some(name) = name else {
+        let mut names: Vec<&String> = config.agents.keys().filter(|n| allowed(n)).collect();
+        if names.is_empty() {
+            return "No named agents are configured.".to_string();
+        }
+        names.sort();
+        let list = names
+            .iter()
+            .map(|n| {
+                if current.as_deref() == Some(n.as_str()) {
+                    format!("- {n} (active)")
+                } else {
+                    format!("- {n}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        return format!("Agents:\n{list}\n\nUse /agent <name> or /agent reset.");
+    };
+
+    if name.eq_ignore_ascii_case("reset") {
+        agents.set_session_overrides(ctx.session_id, SessionOverrides::default());
+        return "This chat is back on the default agent.".to_string();
+    }
+
+    let Some(named) = config.agents.get(name).filter(|_| allowed(name)) else {
+        return format!("Unknown agent: {name}\nUse /agent to list agents.");
+    };
+    use_agent(ctx, name, named);
+    format!("This chat now uses the {name} agent.")
+}
+
+fn use_agent(ctx: &CommandContext<'_>, name: &str, named: &NamedAgentConfig) {
+    ctx.state.agents.set_session_overrides(
+        ctx.session_id,
+        SessionOverrides {
+            agent: Some(name.to_string()),
+            provider_id: named.provider.clone(),
+            model: named.model.clone(),
+            system_prompt: named.system_prompt.clone(),
+            max_tokens: named.max_tokens,
+        },
+    );
+}
+
+async fn memory(ctx: &CommandContext<'_>, action: Option<&str>, query: Option<&str>) -> String {
+    let state = ctx.state;
+    let Some(provider) = state.agents.memory_provider() else {
+        return "Memory is not enabled.".to_string();
+    };
+    let continuity_key = state.continuity_key(Some(ctx.channel), Some(ctx.user_id));
+
+    match (action.unwrap_or("list"), query) {
+        ("search", None) => "Usage: /memory search <text>".to_string(),
+        ("search", Some(text)) => {
+            let query = RecallQuery {
+                query_text: Some(text.to_string()),
+                query_embedding: None,
+                session_id: None,
+                continuity_key,
+                user_id: Some(ctx.user_id.to_string()),
+                limit: LIST_LIMIT,
+            };
+            match state.agents.search_memory(query).await {
+                Ok(entries) if entries.is_empty() => format!("Nothing found for \"{text}\"."),
+                Ok(entries) => entries
+                    .iter()
+                    .map(|e| format!("- {}", preview(&e.content)))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                Err(e) => {
+                    warn!("memory search failed: {e}");
+                    "Memory search failed.".to_string()
+                }
+            }
+        }
+        (action, id) => {
+            let facts = match provider
+                .list_facts(FactQuery {
+                    continuity_key,
+                    user_id: Some(ctx.user_id.to_string()),
+                    include_superseded: false,
+                    limit: if action == "forget" { 0 } else { LIST_LIMIT },
+                })
+                .await
+            {
+                Ok(facts) => facts,
+                Err(e) => {
+                    warn!("failed to list memory facts: {e}");
+                    return "Failed to load memories.".to_string();
+                }
+            };
+
+            if action == "forget" {
+                let Some(id) = id else {
+                    return "Usage: /memory forget <id>".to_string();
+                };
+                let Some(fact) = facts.iter().find(|f| f.id.starts_with(id)) else {
+                    return format!("No memory with id {id}.");
+                };
+                return match provider.delete_fact(&fact.id).await {
+                    Ok(true) => format!("Forgot: {}", preview(&fact.content)),
+                    Ok(false) => format!("No memory with id {id}."),
+                    Err(e) => {
+                        warn!("failed to delete memory fact: {e}");
+                        "Failed to forget that memory.".to_string()
+                    }
+                };
+            }
+
+            if facts.is_empty() {
+                return "I don't have any memories about you yet.".to_string();
+            }
+            let list = facts
+                .iter()
+                .map(|f| format!("- [{}] {}", short_id(&f.id), preview(&f.content)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("Memories:\n{list}\n\nUse /memory forget <id> to remove one.")
+        }
+    }
+}
+
+async fn schedule(ctx: &CommandContext<'_>, action: Option<&str>, id: Option<&str>) -> String {
+    let Some(store) = &ctx.state.session_store else {
+        return "Reminders are unavailable (no session store).".to_string();
+    };
+    let store = store.lock().await;
+    let tasks = match store.list_pending_tasks(ctx.session_id) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            warn!("failed to list scheduled tasks: {e}");
+            return "Failed to load reminders.".to_string();
+        }
+    };
+
+    if action == Some("cancel") {
+        let Some(id) = id else {
+            return "Usage: /schedule cancel <id>".to_string();
+        };
+        let Some(task) = tasks.iter().find(|t| t.id.starts_with(id)) else {
+            return format!("No pending reminder with id {id}.");
+        };
+        return match store.cancel_task(&task.id, ctx.session_id) {
+            Ok(true) => format!("Cancelled reminder {}.", short_id(&task.id)),
+            Ok(false) => format!("No pending reminder with id {id}."),
+            Err(e) => {
+                warn!("failed to cancel scheduled task: {e}");
+                "Failed to cancel the reminder.".to_string()
+            }
+        };
+    }
+
+    if tasks.is_empty() {
+        return "No pending reminders.".to_string();
+    }
+    let list = tasks
+        .iter()
+        .take(LIST_LIMIT)
+        .map(|t| {
+            let repeat = t
+                .recurrence_type
+                .as_deref()
+                .map(|r| format!(" (repeats {r})"))
+                .unwrap_or_default();
+            format!(
+                "- [{}] {}{repeat}: {}",
+                short_id(&t.id),
+                t.execute_at.format("%Y-%m-%d %H:%M UTC"),
+                preview(&t.payload)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("Pending reminders:\n{list}\n\nUse /schedule cancel <id> to cancel one.")
+}
+
+fn usage(ctx: &CommandContext<'_>, level: Permission) -> String {
+    let agents = &ctx.state.agents;
+    let session = agents.session_usage(ctx.session_id).unwrap_or_default();
+    let mut reply = format!(
+        "This chat: {} requests, {} input tokens, {} output tokens.",
+        session.requests, session.input_tokens, session.output_tokens
+    );
+    if level == Permission::Owner {
+        let total = agents.total_usage();
+        reply.push_str(&format!(
+            "\nAll chats since start: {} requests, {} input tokens, {} output tokens.",
+            total.requests, total.input_tokens, total.output_tokens
+        ));
+    }
+    reply
+}
+
+async fn export(ctx: &CommandContext<'_>) -> String {
+    let history = ctx.state.session_history(ctx.session_id);
+    if history.is_empty() {
+        return "Nothing to export yet.".to_string();
+    }
+    let transcript = transcript_markdown(ctx.session_id, &history);
+
+    let Some(sender) = ctx
+        .state
+        .channel_senders
+        .get(ctx.channel)
+        .map(|s| std::sync::Arc::clone(s.value()))
+    else {
+        return transcript;
+    };
+    let file = OutboundFile {
+        data: transcript.clone().into_bytes(),
+        filename: format!("conversation-{}.md", Utc::now().format("%Y%m%d-%H%M%S")),
+        mime_type: "text/markdown".to_string(),
+        caption: None,
+    };
+    match sender.send_attachment(&ctx.route, file).await {
+        Ok(()) => format!("Exported {} messages.", history.len()),
+        Err(e) => {
+            warn!("{}: transcript upload failed: {e}", ctx.channel);
+            transcript
+        }
+    }
+}
+
+/// Markdown transcript of the text in a session's history.
+fn transcript_markdown(session_id: &str, history: &[ChatMessage]) -> String {
+    let mut out = format!(
+        "# Conversation {session_id}\n\nExported {}\n",
+        Utc::now().format("%Y-%m-%d %H:%M UTC")
+    );
+    for message in history {
+        let speaker = match message.role {
+            ChatRole::User => "User",
+            ChatRole::Assistant => "Assistant",
+            _ => continue,
+        };
+        let text = match &message.content {
+            MessagePart::Text(text) => text.clone(),
+            MessagePart::Parts(blocks) => blocks
+                .iter()
+                .filter_map(|b| match b {
+                    ContentBlock::Text { text } => Some(text.as_str()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        out.push_str(&format!("\n**{speaker}:**\n\n{}\n", text.trim()));
+    }
+    out
+}
+
+fn short_id(id: &str) -> &str {
+    &id[..id.len().min(8)]
+}
+
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or("").trim();
+    if line.chars().count() > 120 {
+        format!("{}...", line.chars().take(120).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use opencrust_agents::AgentRuntime;
+    use opencrust_channels::ChannelRegistry;
+    use opencrust_config::{AppConfig, MessageRateConfig};
+    use opencrust_db::SessionStore;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct Fixture {
+        state: SharedState,
+    }
+
+    impl Fixture {
+        fn new(config: AppConfig) -> Self {
+            Self::with_roles(AppState::new(
+                config,
+                AgentRuntime::new(),
+                ChannelRegistry::new(),
+            ))
+        }
+
+        /// A fixture whose invites and roles live in an in-memory database.
+        async fn with_store() -> Self {
+            let mut state = AppState::new(
+                AppConfig::default(),
+                AgentRuntime::new(),
+                ChannelRegistry::new(),
+            );
+            state
+                .set_session_store(Arc::new(tokio::sync::Mutex::new(
+                    SessionStore::in_memory().unwrap(),
+                )))
+                .await;
+            Self::with_roles(state)
+        }
+
+        fn with_roles(state: AppState) -> Self {
+            state.access.set("telegram:owner", Some(Role::Owner));
+            state.access.set("telegram:admin", Some(Role::Admin));
+            state.access.set("telegram:friend", Some(Role::Member));
+            state.access.set("telegram:guest", Some(Role::Guest));
+            Self {
+                state: Arc::new(state),
+            }
+        }
+
+        fn context<'a>(&'a self, user_id: &'a str) -> CommandContext<'a> {
+            CommandContext {
+                state: &self.state,
+                channel: "telegram",
+                session_id: "telegram-1",
+                user_id,
+                user_name: user_id,
+                route: serde_json::json!({"telegram_chat_id": 1}),
+            }
+        }
+
+        async fn run(&self, user_id: &str, text: &str) -> Option<Result<String, String>> {
+            self.run_on("telegram", user_id, text).await
+        }
+
+        async fn run_on(
+            &self,
+            channel: &str,
+            user_id: &str,
+            text: &str,
+        ) -> Option<Result<String, String>> {
+            let ctx = CommandContext {
+                state: &self.state,
+                channel,
+                session_id: "telegram-1",
+                user_id,
+                user_name: user_id,
+                route: serde_json::json!({"telegram_chat_id": 1}),
+            };
+            dispatch(&ctx, text).await
+        }
+    }
+
+    #[tokio::test]
+    async fn plain_text_is_not_a_command() {
+        let fixture = Fixture::new(AppConfig::default());
+        assert!(fixture.run("friend", "hello").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn help_depends_on_permission() {
+        let fixture = Fixture::new(AppConfig::default());
+        let help = fixture.run("friend", "/help").await.unwrap().unwrap();
+        assert!(help.contains("/clear"));
+        assert!(!help.contains("/pair"));
+
+        let help = fixture.run("owner", "/help").await.unwrap().unwrap();
+        assert!(help.contains("/pair"));
+    }
+
+    #[tokio::test]
+    async fn strangers_only_reach_open_commands() {
+        let fixture = Fixture::new(AppConfig::default());
+        assert_eq!(
+            fixture.run("stranger", "/help").await,
+            Some(Err(BLOCKED.to_string()))
+        );
+        assert_eq!(
+            fixture.run("stranger", "/bogus").await,
+            Some(Err(BLOCKED.to_string()))
+        );
+        let reply = fixture.run("stranger", "/start").await.unwrap().unwrap();
+        assert!(reply.contains("private"));
+    }
+
+    #[tokio::test]
+    async fn owner_commands_are_refused_for_allowed_users() {
+        let fixture = Fixture::with_store().await;
+        let reply = fixture.run("friend", "/pair").await.unwrap().unwrap();
+        assert_eq!(reply, "Only owners and admins can use /pair.");
+
+        let reply = fixture.run("owner", "/pair").await.unwrap().unwrap();
+        assert!(reply.starts_with("Pairing code: "));
+        let reply = fixture.run("admin", "/pair").await.unwrap().unwrap();
+        assert!(reply.starts_with("Pairing code: "));
+    }
+
+    #[tokio::test]
+    async fn roles_limit_commands_and_role_changes() {
+        let fixture = Fixture::new(AppConfig::default());
+        let reply = fixture.run("guest", "/model").await.unwrap().unwrap();
+        assert_eq!(reply, "Your role (guest) cannot use /model.");
+        assert!(fixture.run("guest", "/clear").await.unwrap().is_ok());
+
+        let reply = fixture
+            .run("admin", "/role guest member")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply, "telegram:guest is now member.");
+        let reply = fixture
+            .run("admin", "/role friend admin")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply, "Your role (admin) cannot make that change.");
+        let reply = fixture
+            .run("admin", "/role owner none")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply, "Your role (admin) cannot make that change.");
+
+        fixture.run("owner", "/role slack:U9 guest").await;
+        assert_eq!(fixture.state.role_of("slack", "U9"), Some(Role::Guest));
+        let users = fixture.run("owner", "/users").await.unwrap().unwrap();
+        assert!(users.starts_with("Users (5):\n- telegram:owner: owner"));
+    }
+
+    #[tokio::test]
+    async fn strangers_need_a_pairing_code() {
+        let fixture = Fixture::with_store().await;
+        let stranger = fixture.context("stranger");
+        assert_eq!(
+            admit(&stranger, "hello").await,
+            Some(Err(BLOCKED.to_string()))
+        );
+
+        let reply = fixture.run("admin", "/pair").await.unwrap().unwrap();
+        let code = reply
+            .lines()
+            .next()
+            .unwrap()
+            .trim_start_matches("Pairing code: ")
+            .to_string();
+        let invites = fixture.state.invites().await.unwrap();
+        assert_eq!(invites[0].max_uses, Some(1));
+        assert_eq!(invites[0].created_by.as_deref(), Some("telegram:admin"));
+
+        let reply = admit(&stranger, &code).await.unwrap().unwrap();
+        assert!(reply.contains("You now have access"));
+        assert_eq!(
+            fixture.state.role_of("telegram", "stranger"),
+            Some(Role::Member)
+        );
+        assert_eq!(admit(&stranger, "hello").await, None);
+
+        // The code is spent once someone has used it.
+        let latecomer = fixture.context("latecomer");
+        assert_eq!(
+            admit(&latecomer, &code).await,
+            Some(Err(BLOCKED.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn invites_admit_users_until_used_up_or_revoked() {
+        let mut state = AppState::new(
+            AppConfig::default(),
+            AgentRuntime::new(),
+            ChannelRegistry::new(),
+        );
+        state
+            .set_session_store(Arc::new(tokio::sync::Mutex::new(
+                SessionStore::in_memory().unwrap(),
+            )))
+            .await;
+        state.access.set("telegram:owner", Some(Role::Owner));
+        state.access.set("telegram:admin", Some(Role::Admin));
+        let fixture = &Fixture {
+            state: Arc::new(state),
+        };
+        let create = |options: &'static str| async move {
+            let reply = fixture
+                .run("admin", &format!("/invites create {options}"))
+                .await
+                .unwrap()
+                .unwrap();
+            reply
+                .lines()
+                .next()
+                .unwrap()
+                .trim_start_matches("Invite code: ")
+                .to_string()
+        };
+
+        let reply = fixture
+            .run("admin", "/invites create admin")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            reply,
+            "Cannot create that invite: the admin role cannot invite users as admin."
+        );
+
+        let code = create("guest uses=2 expires=1d").await;
+        let reply = admit(&fixture.context("ana"), &code.to_lowercase())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.contains("as guest"));
+        let reply = fixture
+            .run_on("slack", "U1", &format!("/start {code}"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.contains("as guest"));
+        assert_eq!(fixture.state.role_of("slack", "U1"), Some(Role::Guest));
+        assert_eq!(
+            admit(&fixture.context("bob"), &code).await,
+            Some(Err(BLOCKED.to_string()))
+        );
+
+        let other = create("member expires=never").await;
+        let reply = fixture
+            .run("admin", &format!("/invites revoke {other}"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply, format!("Invite {other} revoked."));
+        assert_eq!(
+            admit(&fixture.context("bob"), &other).await,
+            Some(Err(BLOCKED.to_string()))
+        );
+
+        let list = fixture.run("admin", "/invites").await.unwrap().unwrap();
+        assert!(list.contains(&format!("- {other}: member, 0 uses (revoked)")));
+        assert!(list.contains(&format!("- {code}: guest, 2/2 uses (used up)")));
+        let claims = fixture
+            .state
+            .session_store
+            .as_ref()
+            .unwrap()
+            .lock()
+            .await
+            .invite_claims(Some(&code))
+            .unwrap();
+        assert_eq!(claims.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fast_senders_are_told_to_slow_down_once() {
+        let mut config = AppConfig::default();
+        config.chat_rate_limit.roles.insert(
+            "member".to_string(),
+            MessageRateConfig {
+                per_minute: 1,
+                burst: Some(1),
+            },
+        );
+        let fixture = Fixture::new(config);
+        let friend = fixture.context("friend");
+        assert_eq!(admit(&friend, "hi").await, None);
+        assert_eq!(
+            admit(&friend, "hi again").await,
+            Some(Ok(
+                "Slow down a little! You can send your next message in 30 seconds.".to_string()
+            ))
+        );
+        assert_eq!(
+            admit(&friend, "hello?").await,
+            Some(Err(BLOCKED.to_string()))
+        );
+        assert_eq!(admit(&fixture.context("owner"), "hi").await, None);
+        assert_eq!(wait_text(Duration::from_millis(29_500)), "30 seconds");
+        assert_eq!(wait_text(Duration::from_secs(600)), "10 minutes");
+    }
+
+    #[tokio::test]
+    async fn first_user_becomes_owner() {
+        let state = AppState::new(
+            AppConfig::default(),
+            AgentRuntime::new(),
+            ChannelRegistry::new(),
+        );
+        let fixture = Fixture {
+            state: Arc::new(state),
+        };
+        let reply = admit(&fixture.context("first"), "hi")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.contains("You are now the owner"));
+        assert_eq!(
+            admit(&fixture.context("second"), "hi").await,
+            Some(Err(BLOCKED.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_arguments_show_usage() {
+        let fixture = Fixture::new(AppConfig::default());
+        let reply = fixture.run("friend", "/voice loud").await.unwrap().unwrap();
+        assert!(reply.contains("Usage: /voice [auto|always|never]"));
+    }
+
+    #[tokio::test]
+    async fn model_and_agent_set_session_overrides() {
+        let mut config = AppConfig::default();
+        config.agents.insert(
+            "coder".to_string(),
+            opencrust_config::NamedAgentConfig {
+                provider: None,
+                model: Some("big-model".to_string()),
+                system_prompt: Some("You write code.".to_string()),
+                max_tokens: Some(2048),
+                max_context_tokens: None,
+                tools: vec![],
+            },
+        );
+        let fixture = Fixture::new(config);
+        let agents = &fixture.state.agents;
+
+        fixture.run("friend", "/model small-model").await;
+        assert_eq!(
+            agents
+                .session_overrides("telegram-1")
+                .unwrap()
+                .model
+                .as_deref(),
+            Some("small-model")
+        );
+        fixture.run("friend", "/model reset").await;
+        assert!(agents.session_overrides("telegram-1").is_none());
+
+        let reply = fixture
+            .run("friend", "/agent coder")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.contains("coder"));
+        let overrides = agents.session_overrides("telegram-1").unwrap();
+        assert_eq!(overrides.system_prompt.as_deref(), Some("You write code."));
+        assert_eq!(overrides.max_tokens, Some(2048));
+
+        let reply = fixture.run("friend", "/agent").await.unwrap().unwrap();
+        assert!(reply.contains("- coder (active)"));
+    }
+
+    #[tokio::test]
+    async fn usage_totals_are_owner_only() {
+        let fixture = Fixture::new(AppConfig::default());
+        let reply = fixture.run("friend", "/usage").await.unwrap().unwrap();
+        assert!(reply.starts_with("This chat: 0 requests"));
+        assert!(!reply.contains("All chats"));
+
+        let reply = fixture.run("owner", "/usage").await.unwrap().unwrap();
+        assert!(reply.contains("All chats"));
+    }
+
+    #[tokio::test]
+    async fn link_joins_accounts_across_channels() {
+        let fixture = Fixture::new(AppConfig::default());
+        let reply = fixture.run("friend", "/link").await.unwrap().unwrap();
+        let code = reply
+            .strip_prefix("Link code: ")
+            .and_then(|rest| rest.split_whitespace().next())
+            .unwrap()
+            .to_string();
+
+        let reply = fixture
+            .run("friend", &format!("/link {code}"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.contains("other account"));
+
+        let reply = fixture.run("friend", "/link").await.unwrap().unwrap();
+        let code = reply.split_whitespace().nth(2).unwrap().to_string();
+        fixture.state.access.set("slack:friend", Some(Role::Guest));
+        let reply = fixture
+            .run_on("slack", "friend", &format!("/link {code}"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(reply.contains("- slack: friend\n- telegram: friend"));
+        assert_eq!(
+            fixture.state.continuity_key(Some("slack"), Some("friend")),
+            fixture
+                .state
+                .continuity_key(Some("telegram"), Some("friend"))
+        );
+
+        let reply = fixture
+            .run_on("slack", "friend", &format!("/link {code}"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply, "Invalid or expired link code.");
+
+        let reply = fixture.run("friend", "/unlink").await.unwrap().unwrap();
+        assert!(reply.contains("no longer linked"));
+        assert!(
+            fixture
+                .state
+                .continuity_key(Some("telegram"), Some("friend"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn transcript_keeps_user_and_assistant_text() {
+        let history = vec![
+            ChatMessage {
+                role: ChatRole::User,
+                content: MessagePart::Text("What is 2+2?".into()),
+            },
+            ChatMessage {
+                role: ChatRole::Assistant,
+                content: MessagePart::Parts(vec![ContentBlock::Text { text: "4".into() }]),
+            },
+        ];
+        let markdown = transcript_markdown("telegram-1", &history);
+        assert!(markdown.starts_with("# Conversation telegram-1"));
+        assert!(markdown.contains("**User:**\n\nWhat is 2+2?"));
+        assert!(markdown.contains("**Assistant:**\n\n4"));
+    }
+}
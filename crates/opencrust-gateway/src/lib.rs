@@ -1,11 +1,21 @@
 pub mod a2a;
+pub mod access;
+pub mod access_api;
 pub mod agent_router;
 pub mod api;
+pub mod attachments;
 pub mod bootstrap;
+pub mod commands;
 pub mod google_secrets;
+pub mod media_api;
+pub mod memory_api;
+pub mod outbox;
+pub mod outbox_api;
 pub mod router;
 pub mod server;
 pub mod state;
+pub mod turns;
+pub mod voice;
 pub mod ws;
 
 pub use server::GatewayServer;
@@ -0,0 +1,61 @@
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use tracing::warn;
+
+use crate::state::SharedState;
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+/// `GET /api/media/{id}` - serve a stored attachment. Accepts the bare id or
+/// the full `media://` reference (URL-encoded).
+pub async fn get_media(State(state): State<SharedState>, Path(id): Path<String>) -> Response {
+    let Some(store) = state.agents.media_store() else {
+        return error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "media store is unavailable",
+        );
+    };
+    let id = id
+        .strip_prefix(opencrust_media::MEDIA_SCHEME)
+        .unwrap_or(&id)
+        .to_string();
+
+    match tokio::task::spawn_blocking(move || store.get(&id)).await {
+        Ok(Ok(Some((record, data)))) => {
+            let disposition = match &record.filename {
+                Some(name) => format!(
+                    "inline; filename=\"{}\"",
+                    name.replace(['"', '\\', '\r', '\n'], "_")
+                ),
+                None => "inline".to_string(),
+            };
+            (
+                [
+                    (header::CONTENT_TYPE, record.mime_type),
+                    (header::CONTENT_DISPOSITION, disposition),
+                    // Content never changes for a given id.
+                    (
+                        header::CACHE_CONTROL,
+                        "private, max-age=31536000, immutable".to_string(),
+                    ),
+                    (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+                ],
+                data,
+            )
+                .into_response()
+        }
+        Ok(Ok(None)) => error(StatusCode::NOT_FOUND, "media not found"),
+        Ok(Err(e)) => {
+            warn!("media api error: {e}");
+            error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string())
+        }
+        Err(e) => {
+            warn!("media api task failed: {e}");
+            error(StatusCode::INTERNAL_SERVER_ERROR, "media lookup failed")
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use opencrust_db::{
+    FactKind, FactQuery, FactSource, FactUpsert, MemoryEntry, MemoryListQuery, MemoryProvider,
+    MemoryRecord, NewMemoryFact, RecallQuery,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::state::SharedState;
+
+/// Exports carry embeddings, so imports are allowed well past axum's 2 MB default.
+pub const IMPORT_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct CreateFactRequest {
+    pub continuity_key: Option<String>,
+    pub user_id: Option<String>,
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub subject: String,
+    pub content: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateFactRequest {
+    pub content: String,
+    pub kind: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    pub q: String,
+    pub session_id: Option<String>,
+    pub continuity_key: Option<String>,
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub limit: usize,
+}
+
+/// JSON `{ "error": ... }` response with a status code.
+pub struct ApiError(StatusCode, String);
+
+impl ApiError {
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self(status, message.into())
+    }
+
+    pub(crate) fn not_found(what: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, format!("{what} not found"))
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(serde_json::json!({ "error": self.1 }))).into_response()
+    }
+}
+
+fn memory(state: &SharedState) -> Result<Arc<dyn MemoryProvider>, ApiError> {
+    state
+        .agents
+        .memory_provider()
+        .ok_or_else(|| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "memory is disabled"))
+}
+
+fn internal(e: opencrust_common::Error) -> ApiError {
+    warn!("memory api error: {e}");
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// Embeddings are large and meaningless to a reader; keep them out of listings.
+fn without_embeddings(entries: Vec<MemoryEntry>) -> Vec<MemoryEntry> {
+    entries
+        .into_iter()
+        .map(|entry| MemoryEntry {
+            embedding: None,
+            ..entry
+        })
+        .collect()
+}
+
+fn parse_kind(kind: Option<&str>) -> Result<Option<FactKind>, ApiError> {
+    match kind {
+        None => Ok(None),
+        Some(raw) => FactKind::parse(raw).map(Some).ok_or_else(|| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                format!("unknown fact kind '{raw}' (expected fact, preference or commitment)"),
+            )
+        }),
+    }
+}
+
+/// GET /api/memory/facts — list distilled facts for a user or continuity key.
+pub async fn list_facts(
+    State(state): State<SharedState>,
+    Query(query): Query<FactQuery>,
+) -> Result<Response, ApiError> {
+    let facts = memory(&state)?.list_facts(query).await.map_err(internal)?;
+    Ok(Json(serde_json::json!({ "facts": facts })).into_response())
+}
+
+/// POST /api/memory/facts — add a user-authored fact.
+pub async fn create_fact(
+    State(state): State<SharedState>,
+    Json(body): Json<CreateFactRequest>,
+) -> Result<Response, ApiError> {
+    let memory = memory(&state)?;
+    if body.continuity_key.is_none() && body.user_id.is_none() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "either continuity_key or user_id is required",
+        ));
+    }
+    let kind = parse_kind(body.kind.as_deref())?.unwrap_or(FactKind::Fact);
+
+    let outcome = memory
+        .upsert_fact(NewMemoryFact {
+            continuity_key: body.continuity_key,
+            user_id: body.user_id,
+            kind,
+            subject: body.subject,
+            content: body.content,
+            confidence: 1.0,
+            source: FactSource::User,
+            source_session_id: None,
+        })
+        .await
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let status = match outcome {
+        FactUpsert::Duplicate { .. } => StatusCode::OK,
+        _ => StatusCode::CREATED,
+    };
+    Ok((status, Json(serde_json::json!(outcome))).into_response())
+}
+
+/// PATCH /api/memory/facts/:id — edit a fact. Edited facts become user-authored.
+pub async fn update_fact(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(body): Json<UpdateFactRequest>,
+) -> Result<Response, ApiError> {
+    let memory = memory(&state)?;
+    let kind = parse_kind(body.kind.as_deref())?;
+    let updated = memory
+        .update_fact(&id, &body.content, kind)
+        .await
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    if !updated {
+        return Err(ApiError::not_found("fact"));
+    }
+    let fact = memory
+        .get_fact(&id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| ApiError::not_found("fact"))?;
+    Ok(Json(serde_json::json!(fact)).into_response())
+}
+
+/// DELETE /api/memory/facts/:id — forget a fact.
+pub async fn delete_fact(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    if memory(&state)?.delete_fact(&id).await.map_err(internal)? {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Err(ApiError::not_found("fact"))
+    }
+}
+
+/// GET /api/memory — list raw memory entries by session, user or continuity key.
+pub async fn list_entries(
+    State(state): State<SharedState>,
+    Query(query): Query<MemoryListQuery>,
+) -> Result<Response, ApiError> {
+    let entries = memory(&state)?
+        .list_entries(query)
+        .await
+        .map_err(internal)?;
+    Ok(Json(serde_json::json!({ "entries": without_embeddings(entries) })).into_response())
+}
+
+/// GET /api/memory/search — run a recall query exactly as a turn would.
+pub async fn search_entries(
+    State(state): State<SharedState>,
+    Query(params): Query<SearchParams>,
+) -> Result<Response, ApiError> {
+    memory(&state)?;
+    if params.q.trim().is_empty() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "q is required"));
+    }
+    let entries = state
+        .agents
+        .search_memory(RecallQuery {
+            query_text: Some(params.q),
+            query_embedding: None,
+            session_id: params.session_id,
+            continuity_key: params.continuity_key,
+            user_id: params.user_id,
+            limit: params.limit,
+        })
+        .await
+        .map_err(internal)?;
+    Ok(Json(serde_json::json!({ "entries": without_embeddings(entries) })).into_response())
+}
+
+/// GET /api/memory/:id — fetch a single entry, including its embedding.
+pub async fn get_entry(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let entry = memory(&state)?
+        .get_entry(&id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| ApiError::not_found("memory entry"))?;
+    Ok(Json(serde_json::json!(entry)).into_response())
+}
+
+/// DELETE /api/memory/:id — forget a single entry.
+pub async fn delete_entry(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    if memory(&state)?.delete_entry(&id).await.map_err(internal)? {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    } else {
+        Err(ApiError::not_found("memory entry"))
+    }
+}
+
+/// GET /api/memory/export — entries and facts in scope as JSONL, oldest first.
+pub async fn export_memory(
+    State(state): State<SharedState>,
+    Query(scope): Query<MemoryListQuery>,
+) -> Result<Response, ApiError> {
+    let records = memory(&state)?
+        .export_records(scope)
+        .await
+        .map_err(internal)?;
+    let body = MemoryRecord::to_jsonl(&records).map_err(internal)?;
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body).into_response())
+}
+
+/// POST /api/memory/import — restore a JSONL export. Existing IDs are skipped.
+pub async fn import_memory(
+    State(state): State<SharedState>,
+    body: String,
+) -> Result<Response, ApiError> {
+    let memory = memory(&state)?;
+    let records = MemoryRecord::parse_jsonl(&body)
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let report = memory.import_records(records).await.map_err(internal)?;
+    Ok(Json(serde_json::json!(report)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::{get, post};
+    use opencrust_agents::AgentRuntime;
+    use opencrust_channels::ChannelRegistry;
+    use opencrust_config::AppConfig;
+    use opencrust_db::{MemoryRole, MemoryStore, NewMemoryEntry};
+    use tower::ServiceExt;
+
+    async fn body_text(resp: Response) -> String {
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .expect("body should read");
+        String::from_utf8(bytes.to_vec()).expect("body should be utf-8")
+    }
+
+    #[tokio::test]
+    async fn export_import_and_delete_round_trip() {
+        let store: Arc<dyn MemoryProvider> =
+            Arc::new(MemoryStore::in_memory().expect("store should open"));
+        let id = store
+            .remember(NewMemoryEntry {
+                session_id: "s1".into(),
+                channel_id: None,
+                user_id: Some("u1".into()),
+                continuity_key: None,
+                role: MemoryRole::User,
+                content: "my flat number is 12".into(),
+                embedding: None,
+                embedding_model: None,
+                metadata: serde_json::json!({}),
+            })
+            .await
+            .expect("remember should succeed");
+
+        let mut runtime = AgentRuntime::new();
+        runtime.set_memory_provider(Arc::clone(&store));
+        let state: SharedState = Arc::new(crate::state::AppState::new(
+            AppConfig::default(),
+            runtime,
+            ChannelRegistry::new(),
+        ));
+        let router = Router::new()
+            .route("/api/memory", get(list_entries))
+            .route("/api/memory/search", get(search_entries))
+            .route("/api/memory/export", get(export_memory))
+            .route("/api/memory/import", post(import_memory))
+            .route("/api/memory/{id}", get(get_entry).delete(delete_entry))
+            .with_state(state);
+        let request = |method: &str, uri: &str, body: String| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let search = router
+            .clone()
+            .oneshot(request(
+                "GET",
+                "/api/memory/search?q=flat&user_id=u1",
+                String::new(),
+            ))
+            .await
+            .unwrap();
+        assert!(body_text(search).await.contains(&id));
+
+        let export = router
+            .clone()
+            .oneshot(request(
+                "GET",
+                "/api/memory/export?user_id=u1",
+                String::new(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(export.status(), StatusCode::OK);
+        let jsonl = body_text(export).await;
+        assert_eq!(jsonl.lines().count(), 1);
+
+        let deleted = router
+            .clone()
+            .oneshot(request(
+                "DELETE",
+                &format!("/api/memory/{id}"),
+                String::new(),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+        let missing = router
+            .clone()
+            .oneshot(request("GET", &format!("/api/memory/{id}"), String::new()))
+            .await
+            .unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+        let imported = router
+            .clone()
+            .oneshot(request("POST", "/api/memory/import", jsonl))
+            .await
+            .unwrap();
+        assert!(body_text(imported).await.contains("\"entries\":1"));
+
+        let bad = router
+            .clone()
+            .oneshot(request("POST", "/api/memory/import", "not json".into()))
+            .await
+            .unwrap();
+        assert_eq!(bad.status(), StatusCode::BAD_REQUEST);
+
+        let listed = router
+            .oneshot(request("GET", "/api/memory?session_id=s1", String::new()))
+            .await
+            .unwrap();
+        assert!(body_text(listed).await.contains("my flat number is 12"));
+    }
+}
@@ -0,0 +1,228 @@
+//! Durable delivery of messages the gateway sends on its own, such as
+//! scheduled heartbeat results. Messages are written to the `outbox` table
+//! first and a background worker delivers them, retrying with backoff, so a
+//! channel that is down when a message is produced still gets it once it is
+//! back. Messages that keep failing are dead-lettered and can be inspected
+//! and requeued over `/api/outbox`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use opencrust_common::{
+    ChannelId, Error, Message, MessageContent, MessageDirection, Result, SessionId, UserId,
+};
+use opencrust_db::{NewOutboundMessage, OutboundMessage};
+use tracing::{error, info, warn};
+
+use crate::state::AppState;
+
+/// How often the worker looks for due messages when nothing wakes it.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Messages delivered per pass.
+const BATCH_SIZE: usize = 20;
+
+impl AppState {
+    /// Queue a text message for delivery to `channel`, addressed by
+    /// `metadata`. Returns `false` if `idempotency_key` was queued before.
+    /// Without a sessions database the message is sent right away, once.
+    pub async fn enqueue_outbound(
+        &self,
+        idempotency_key: &str,
+        channel: &str,
+        session_id: &str,
+        text: &str,
+        metadata: &serde_json::Value,
+    ) -> Result<bool> {
+        let Some(store) = &self.session_store else {
+            send_outbound(self, idempotency_key, channel, session_id, text, metadata)
+                .await
+                .map_err(Error::Channel)?;
+            return Ok(true);
+        };
+        let queued = store.lock().await.enqueue_outbound(&NewOutboundMessage {
+            idempotency_key: idempotency_key.to_string(),
+            channel: channel.to_string(),
+            session_id: session_id.to_string(),
+            content: text.to_string(),
+            metadata: metadata.clone(),
+            max_attempts: self.current_config().outbox.max_attempts,
+        })?;
+        if queued {
+            self.outbox_ready.notify_one();
+        }
+        Ok(queued)
+    }
+
+    /// Start the worker that delivers queued messages.
+    pub fn spawn_outbox_worker(self: &Arc<Self>) {
+        if self.session_store.is_none() {
+            return;
+        }
+        let state = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = deliver_due(&state).await {
+                    error!("outbox delivery failed: {e}");
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                    _ = state.outbox_ready.notified() => {}
+                }
+            }
+        });
+    }
+}
+
+/// Try every due message once.
+async fn deliver_due(state: &AppState) -> Result<()> {
+    let Some(store) = &state.session_store else {
+        return Ok(());
+    };
+    let due = store.lock().await.due_outbound(BATCH_SIZE)?;
+    for message in due {
+        let OutboundMessage {
+            id,
+            idempotency_key,
+            channel,
+            session_id,
+            content,
+            metadata,
+            attempts,
+            ..
+        } = message;
+        match send_outbound(
+            state,
+            &idempotency_key,
+            &channel,
+            &session_id,
+            &content,
+            &metadata,
+        )
+        .await
+        {
+            Ok(()) => store.lock().await.mark_outbound_delivered(id)?,
+            Err(e) => {
+                if store.lock().await.retry_or_dead_letter_outbound(id, &e)? {
+                    warn!(
+                        "outbound message {id} to {channel} failed (attempt {}), will retry: {e}",
+                        attempts + 1
+                    );
+                } else {
+                    error!("outbound message {id} to {channel} dead-lettered: {e}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn send_outbound(
+    state: &AppState,
+    idempotency_key: &str,
+    channel: &str,
+    session_id: &str,
+    text: &str,
+    metadata: &serde_json::Value,
+) -> std::result::Result<(), String> {
+    let sender = state
+        .channel_senders
+        .get(channel)
+        .map(|s| Arc::clone(s.value()))
+        .ok_or_else(|| format!("no channel sender registered for {channel}"))?;
+    let message = Message {
+        id: idempotency_key.to_string(),
+        session_id: SessionId::from_string(session_id),
+        channel_id: ChannelId::from_string(channel),
+        user_id: UserId::from_string("genesis"),
+        direction: MessageDirection::Outgoing,
+        content: MessageContent::Text(text.to_string()),
+        timestamp: chrono::Utc::now(),
+        metadata: metadata.clone(),
+    };
+    sender
+        .send_message(&message)
+        .await
+        .map_err(|e| e.to_string())?;
+    info!("delivered outbound message {idempotency_key} to {channel}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use opencrust_agents::AgentRuntime;
+    use opencrust_channels::{ChannelRegistry, ChannelSender};
+    use opencrust_config::AppConfig;
+    use opencrust_db::{OutboundStatus, SessionStore};
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Default)]
+    struct FlakySender {
+        up: AtomicBool,
+        sent: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ChannelSender for FlakySender {
+        fn channel_type(&self) -> &str {
+            "telegram"
+        }
+
+        async fn send_message(&self, message: &Message) -> Result<()> {
+            if !self.up.load(Ordering::SeqCst) {
+                return Err(Error::Channel("telegram is down".to_string()));
+            }
+            if let MessageContent::Text(text) = &message.content {
+                self.sent.lock().unwrap().push(text.clone());
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_deliveries_are_retried_until_the_channel_is_back() {
+        let mut state = AppState::new(
+            AppConfig::default(),
+            AgentRuntime::new(),
+            ChannelRegistry::new(),
+        );
+        let store = Arc::new(tokio::sync::Mutex::new(SessionStore::in_memory().unwrap()));
+        state.set_session_store(Arc::clone(&store)).await;
+        let sender = Arc::new(FlakySender::default());
+        state
+            .channel_senders
+            .insert("telegram".to_string(), sender.clone());
+
+        let route = serde_json::json!({"telegram_chat_id": 42});
+        let enqueue = || state.enqueue_outbound("task:1", "telegram", "telegram-42", "Hi!", &route);
+        assert!(enqueue().await.unwrap());
+        assert!(!enqueue().await.unwrap());
+
+        deliver_due(&state).await.unwrap();
+        let pending = store.lock().await.outbound_messages(None, 10).unwrap();
+        assert_eq!(pending[0].status, OutboundStatus::Pending);
+        assert_eq!(
+            pending[0].last_error.as_deref(),
+            Some("channel error: telegram is down")
+        );
+
+        // Make the retry due now instead of after the backoff.
+        store
+            .lock()
+            .await
+            .connection()
+            .execute(
+                "UPDATE outbox SET next_attempt_at = '2000-01-01T00:00:00Z'",
+                [],
+            )
+            .unwrap();
+        sender.up.store(true, Ordering::SeqCst);
+        deliver_due(&state).await.unwrap();
+        assert_eq!(*sender.sent.lock().unwrap(), vec!["Hi!".to_string()]);
+        let delivered = store.lock().await.outbound_messages(None, 10).unwrap();
+        assert_eq!(delivered[0].status, OutboundStatus::Delivered);
+        assert_eq!(delivered[0].attempts, 2);
+    }
+}
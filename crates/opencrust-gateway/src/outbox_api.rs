@@ -0,0 +1,289 @@
+use std::sync::Arc;
+
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use opencrust_db::{OutboundMessage, OutboundStatus, SessionStore};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::memory_api::ApiError;
+use crate::state::SharedState;
+
+/// Messages a listing returns when no `limit` is given.
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct OutboxQuery {
+    /// `pending`, `delivered` or `dead`.
+    pub status: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct SendOutboundRequest {
+    pub session_id: String,
+    pub text: String,
+    /// Channel and routing metadata; both default to the session's route.
+    pub channel: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    /// A key that was queued before is not sent again. Defaults to a
+    /// random key.
+    pub idempotency_key: Option<String>,
+}
+
+/// GET /api/outbox — queued, delivered and dead-lettered messages, newest first.
+pub async fn list_outbox(
+    State(state): State<SharedState>,
+    Query(query): Query<OutboxQuery>,
+) -> Result<Response, ApiError> {
+    let store = require_store(&state)?;
+    let status = query
+        .status
+        .as_deref()
+        .map(|s| {
+            OutboundStatus::parse(s).ok_or_else(|| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    "status must be pending, delivered or dead",
+                )
+            })
+        })
+        .transpose()?;
+    let messages: Vec<serde_json::Value> = store
+        .lock()
+        .await
+        .outbound_messages(status, query.limit.unwrap_or(DEFAULT_LIMIT))
+        .map_err(internal)?
+        .iter()
+        .map(outbound_json)
+        .collect();
+    Ok(Json(serde_json::json!({ "messages": messages })).into_response())
+}
+
+/// POST /api/outbox — queue a message to a chat.
+pub async fn send_outbound(
+    State(state): State<SharedState>,
+    Json(body): Json<SendOutboundRequest>,
+) -> Result<Response, ApiError> {
+    require_store(&state)?;
+    let route = state
+        .session_routes
+        .get(&body.session_id)
+        .map(|r| r.value().clone());
+    let channel = body
+        .channel
+        .or_else(|| route.as_ref().map(|r| r.channel.clone()));
+    let metadata = body.metadata.or_else(|| route.map(|r| r.metadata));
+    let (Some(channel), Some(metadata)) = (channel, metadata) else {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "unknown session route: pass channel and metadata",
+        ));
+    };
+    let key = body
+        .idempotency_key
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let queued = state
+        .enqueue_outbound(&key, &channel, &body.session_id, &body.text, &metadata)
+        .await
+        .map_err(internal)?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({ "idempotency_key": key, "queued": queued })),
+    )
+        .into_response())
+}
+
+/// GET /api/outbox/:id — one message with its delivery state.
+pub async fn get_outbound(
+    State(state): State<SharedState>,
+    Path(id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let store = require_store(&state)?;
+    let message = store
+        .lock()
+        .await
+        .outbound_message(id)
+        .map_err(internal)?
+        .ok_or_else(|| ApiError::not_found("message"))?;
+    Ok(Json(outbound_json(&message)).into_response())
+}
+
+/// POST /api/outbox/:id/retry — give a dead-lettered message new attempts.
+pub async fn retry_outbound(
+    State(state): State<SharedState>,
+    Path(id): Path<i64>,
+) -> Result<Response, ApiError> {
+    let store = require_store(&state)?;
+    let message = {
+        let guard = store.lock().await;
+        let message = guard
+            .outbound_message(id)
+            .map_err(internal)?
+            .ok_or_else(|| ApiError::not_found("message"))?;
+        if !guard.requeue_outbound(id).map_err(internal)? {
+            return Err(ApiError::new(
+                StatusCode::CONFLICT,
+                format!("message is {}, not dead", message.status.as_str()),
+            ));
+        }
+        guard
+            .outbound_message(id)
+            .map_err(internal)?
+            .unwrap_or(message)
+    };
+    state.outbox_ready.notify_one();
+    Ok(Json(outbound_json(&message)).into_response())
+}
+
+fn outbound_json(message: &OutboundMessage) -> serde_json::Value {
+    serde_json::json!({
+        "id": message.id,
+        "idempotency_key": message.idempotency_key,
+        "channel": message.channel,
+        "session_id": message.session_id,
+        "content": message.content,
+        "metadata": message.metadata,
+        "status": message.status.as_str(),
+        "attempts": message.attempts,
+        "max_attempts": message.max_attempts,
+        "next_attempt_at": message.next_attempt_at,
+        "last_error": message.last_error,
+        "created_at": message.created_at,
+        "delivered_at": message.delivered_at,
+    })
+}
+
+fn require_store(state: &SharedState) -> Result<&Arc<tokio::sync::Mutex<SessionStore>>, ApiError> {
+    state.session_store.as_ref().ok_or_else(|| {
+        ApiError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "the outbox needs the sessions database",
+        )
+    })
+}
+
+fn internal(e: opencrust_common::Error) -> ApiError {
+    warn!("outbox api error: {e}");
+    ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::{get, post};
+    use opencrust_agents::AgentRuntime;
+    use opencrust_channels::ChannelRegistry;
+    use opencrust_config::AppConfig;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn dead_letters_are_listed_and_requeued() {
+        let mut state = crate::state::AppState::new(
+            AppConfig::default(),
+            AgentRuntime::new(),
+            ChannelRegistry::new(),
+        );
+        state
+            .set_session_store(Arc::new(tokio::sync::Mutex::new(
+                SessionStore::in_memory().unwrap(),
+            )))
+            .await;
+        let state: SharedState = Arc::new(state);
+        state.set_session_route(
+            "telegram-42",
+            "telegram",
+            serde_json::json!({"telegram_chat_id": 42}),
+        );
+        let router = Router::new()
+            .route("/api/outbox", get(list_outbox).post(send_outbound))
+            .route("/api/outbox/{id}", get(get_outbound))
+            .route("/api/outbox/{id}/retry", post(retry_outbound))
+            .with_state(Arc::clone(&state));
+        let request = |method: &str, uri: &str, body: &str| {
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let json = |resp: Response| async move {
+            let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let resp = router
+            .clone()
+            .oneshot(request(
+                "POST",
+                "/api/outbox",
+                r#"{"session_id":"slack-unknown","text":"hi"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let resp = router
+            .clone()
+            .oneshot(request(
+                "POST",
+                "/api/outbox",
+                r#"{"session_id":"telegram-42","text":"Reminder!","idempotency_key":"r1"}"#,
+            ))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        assert_eq!(json(resp).await["queued"], true);
+
+        let resp = router
+            .clone()
+            .oneshot(request("GET", "/api/outbox?status=pending", ""))
+            .await
+            .unwrap();
+        let list = json(resp).await;
+        let message = &list["messages"][0];
+        assert_eq!(message["channel"], "telegram");
+        assert_eq!(message["metadata"]["telegram_chat_id"], 42);
+        let id = message["id"].as_i64().unwrap();
+
+        let uri = format!("/api/outbox/{id}/retry");
+        let resp = router
+            .clone()
+            .oneshot(request("POST", &uri, ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        {
+            let store = state.session_store.as_ref().unwrap().lock().await;
+            store.retry_or_dead_letter_outbound(id, "boom").unwrap();
+            store
+                .connection()
+                .execute("UPDATE outbox SET status = 'dead'", [])
+                .unwrap();
+        }
+        let resp = router
+            .clone()
+            .oneshot(request("POST", &uri, ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let message = json(resp).await;
+        assert_eq!(message["status"], "pending");
+        assert_eq!(message["attempts"], 0);
+
+        let resp = router
+            .oneshot(request("GET", "/api/outbox/999", ""))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
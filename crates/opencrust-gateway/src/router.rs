@@ -7,7 +7,7 @@ use axum::extract::Query;
 use axum::http::{Request, StatusCode};
 use axum::middleware::Next;
 use axum::response::{Html, IntoResponse, Redirect};
-use axum::routing::{get, post};
+use axum::routing::{delete, get, patch, post};
 use opencrust_security::credentials::vault_passphrase_available;
 use tower_governor::GovernorLayer;
 use tower_governor::governor::GovernorConfigBuilder;
@@ -15,7 +15,11 @@ use tower_http::services::ServeDir;
 use url::form_urlencoded;
 
 use crate::a2a;
+use crate::access_api;
 use crate::api;
+use crate::media_api;
+use crate::memory_api;
+use crate::outbox_api;
 use crate::state::{GoogleOAuthRuntimeConfig, SharedState};
 use crate::ws;
 
@@ -77,6 +81,50 @@ pub fn build_router(
             post(disconnect_google_integration),
         )
         .route("/api/security/vault", get(get_vault_status))
+        .route("/api/memory", get(memory_api::list_entries))
+        .route("/api/memory/search", get(memory_api::search_entries))
+        .route("/api/memory/export", get(memory_api::export_memory))
+        .route(
+            "/api/memory/import",
+            post(memory_api::import_memory).layer(axum::extract::DefaultBodyLimit::max(
+                memory_api::IMPORT_BODY_LIMIT,
+            )),
+        )
+        .route(
+            "/api/memory/{id}",
+            get(memory_api::get_entry).delete(memory_api::delete_entry),
+        )
+        .route(
+            "/api/memory/facts",
+            get(memory_api::list_facts).post(memory_api::create_fact),
+        )
+        .route(
+            "/api/memory/facts/{id}",
+            patch(memory_api::update_fact).delete(memory_api::delete_fact),
+        )
+        .route("/api/media/{id}", get(media_api::get_media))
+        .route("/api/roles", get(access_api::list_roles))
+        .route(
+            "/api/roles/{channel}/{user_id}",
+            get(access_api::get_role)
+                .put(access_api::set_role)
+                .delete(access_api::delete_role),
+        )
+        .route(
+            "/api/invites",
+            get(access_api::list_invites).post(access_api::create_invite),
+        )
+        .route("/api/invites/{code}", delete(access_api::revoke_invite))
+        .route(
+            "/api/invites/{code}/claims",
+            get(access_api::list_invite_claims),
+        )
+        .route(
+            "/api/outbox",
+            get(outbox_api::list_outbox).post(outbox_api::send_outbound),
+        )
+        .route("/api/outbox/{id}", get(outbox_api::get_outbound))
+        .route("/api/outbox/{id}/retry", post(outbox_api::retry_outbound))
         .route_layer(axum::middleware::from_fn_with_state(
             state.clone(),
             require_gateway_api_key,
@@ -169,6 +217,18 @@ async fn status(
         "sessions": state.sessions.len(),
         "llm": llm,
     });
+    if !state.whatsapp_web_status.is_empty() {
+        let whatsapp_web: serde_json::Map<String, serde_json::Value> = state
+            .whatsapp_web_status
+            .iter()
+            .map(|entry| {
+                let snapshot = serde_json::to_value(entry.value().snapshot())
+                    .unwrap_or(serde_json::Value::Null);
+                (entry.key().clone(), snapshot)
+            })
+            .collect();
+        resp["whatsapp_web"] = whatsapp_web.into();
+    }
     if let Some(latest) = latest_version {
         let current = env!("CARGO_PKG_VERSION");
         if latest.trim_start_matches('v') != current {
@@ -2,6 +2,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use notify::{EventKind, RecursiveMode, Watcher};
+use opencrust_agents::KnowledgeIndexer;
 use opencrust_channels::{ChannelLifecycle, ChannelSender};
 use opencrust_common::{
     ChannelId, Message, MessageContent, MessageDirection, Result, SessionId, UserId,
@@ -12,12 +13,14 @@ use tokio::net::TcpListener;
 use tokio::sync::Mutex;
 use tracing::{info, warn};
 
+use crate::attachments::{ChannelAttachmentSender, attachment_dirs};
 #[cfg(target_os = "macos")]
 use crate::bootstrap::build_imessage_channels;
 use crate::bootstrap::{
-    build_agent_runtime, build_channels, build_discord_channels, build_mcp_tools,
-    build_slack_channels, build_telegram_channels, build_whatsapp_channels,
-    build_whatsapp_web_channels,
+    build_agent_runtime, build_channels, build_discord_channels, build_knowledge_indexer,
+    build_mcp_tools, build_slack_channels, build_speech_to_text, build_telegram_channels,
+    build_text_to_speech, build_whatsapp_channels, build_whatsapp_web_channels, install_tool_guard,
+    load_legacy_allowlist,
 };
 use crate::router::build_router;
 use crate::state::AppState;
@@ -43,9 +46,19 @@ impl GatewayServer {
             agents.register_tool(tool);
         }
 
+        // Knowledge base: expose search to the agent, index sources in the background
+        let knowledge = build_knowledge_indexer(&self.config);
+        if let Some(indexer) = &knowledge {
+            agents.register_tool(Box::new(opencrust_agents::KnowledgeSearchTool::new(
+                Arc::clone(indexer),
+            )));
+        }
+
         let channels = build_channels(&self.config).await;
         let mut state = AppState::new(self.config, agents, channels);
         state.mcp_manager = Some(mcp_manager);
+        state.speech_to_text = build_speech_to_text(&state.config);
+        state.text_to_speech = build_text_to_speech(&state.config);
 
         // Initialize persistent session storage used by channel memory bus hydration.
         let data_dir = state
@@ -57,11 +70,43 @@ impl GatewayServer {
         if let Err(e) = std::fs::create_dir_all(&data_dir) {
             warn!("failed to create data directory: {e}");
         }
+        // Content-addressed attachment store, referenced as media:// in messages.
+        let media_config = state.config.media.clone();
+        let media_store = match opencrust_media::MediaStore::open(data_dir.join("media")) {
+            Ok(store) => {
+                let store = Arc::new(
+                    store
+                        .with_quota(
+                            media_config.max_file_mb * 1024 * 1024,
+                            media_config.max_total_mb * 1024 * 1024,
+                        )
+                        .with_retention((media_config.retention_days > 0).then(|| {
+                            std::time::Duration::from_secs(media_config.retention_days * 86_400)
+                        })),
+                );
+                state.agents.set_media_store(Arc::clone(&store));
+                Some(store)
+            }
+            Err(e) => {
+                warn!("failed to open media store: {e}");
+                None
+            }
+        };
+
+        // Lets the agent send files back through the session's channel.
+        state
+            .agents
+            .register_tool(Box::new(opencrust_agents::SendAttachmentTool::new(
+                Arc::new(ChannelAttachmentSender::new(&state)),
+                media_store.clone(),
+                Some(attachment_dirs(&media_config, &data_dir)),
+            )));
+
         let sessions_db = data_dir.join("sessions.db");
         match SessionStore::open(&sessions_db) {
             Ok(store) => {
                 let store = Arc::new(Mutex::new(store));
-                state.set_session_store(Arc::clone(&store));
+                state.set_session_store(Arc::clone(&store)).await;
                 state
                     .agents
                     .register_tool(Box::new(opencrust_agents::ScheduleHeartbeat::new(
@@ -83,6 +128,7 @@ impl GatewayServer {
                 warn!("failed to open session store: {e}");
             }
         }
+        load_legacy_allowlist(&state);
 
         // Start config hot-reload watcher
         let config_path = opencrust_config::ConfigLoader::default_config_dir().join("config.yml");
@@ -118,15 +164,24 @@ impl GatewayServer {
         }
 
         let state = Arc::new(state);
+        install_tool_guard(&state);
 
         // Spawn background tasks
         state.spawn_session_cleanup();
+        state.spawn_outbox_worker();
+        if let Some(store) = media_store {
+            spawn_media_gc(store);
+        }
         state.spawn_config_applier();
 
         // Watch dna.md for hot-reload
         let config_dir = opencrust_config::ConfigLoader::default_config_dir();
         spawn_dna_watcher(Arc::clone(&state), config_dir);
 
+        if let Some(indexer) = knowledge {
+            spawn_knowledge_indexing(indexer, &state.config.knowledge);
+        }
+
         // Spawn MCP health monitor for auto-reconnect
         if let Some(ref arc) = mcp_manager_arc {
             arc.spawn_health_monitor();
@@ -160,6 +215,19 @@ impl GatewayServer {
                     tracing::error!("Scheduler error: {e}");
                 }
                 tick_count = tick_count.wrapping_add(1);
+                // Distill pending turns into long-term facts on the configured interval.
+                let facts_every = (scheduler_state.config.memory.facts.batch_interval_secs / 5)
+                    .clamp(1, u32::MAX as u64) as u32;
+                if scheduler_state.agents.fact_extraction()
+                    == Some(opencrust_agents::FactExtractionMode::Batch)
+                    && tick_count.is_multiple_of(facts_every)
+                {
+                    match scheduler_state.agents.extract_pending_facts(200).await {
+                        Ok(n) if n > 0 => info!("fact extraction stored {n} facts"),
+                        Err(e) => warn!("batch fact extraction failed: {e}"),
+                        _ => {}
+                    }
+                }
                 // Cleanup old completed/failed/cancelled tasks every ~hour (720 * 5s)
                 if tick_count.is_multiple_of(720)
                     && let Some(store_mutex) = &scheduler_state.session_store
@@ -170,6 +238,11 @@ impl GatewayServer {
                         Err(e) => tracing::error!("task cleanup failed: {e}"),
                         _ => {}
                     }
+                    match store.cleanup_delivered_outbound(7) {
+                        Ok(n) if n > 0 => info!("cleaned up {n} delivered outbound messages"),
+                        Err(e) => tracing::error!("outbox cleanup failed: {e}"),
+                        _ => {}
+                    }
                 }
             }
         });
@@ -387,6 +460,118 @@ fn spawn_dna_watcher(state: Arc<AppState>, config_dir: PathBuf) {
     });
 }
 
+/// Expire and evict stored media at startup and then every six hours.
+fn spawn_media_gc(store: Arc<opencrust_media::MediaStore>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(6 * 3600));
+        loop {
+            interval.tick().await;
+            let store = Arc::clone(&store);
+            match tokio::task::spawn_blocking(move || store.gc()).await {
+                Ok(Ok(report)) if report.expired + report.evicted > 0 => info!(
+                    "media gc: {} expired, {} evicted, {} bytes freed",
+                    report.expired, report.evicted, report.bytes_freed
+                ),
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => warn!("media gc failed: {e}"),
+                Err(e) => warn!("media gc task failed: {e}"),
+            }
+        }
+    });
+}
+
+/// Register configured knowledge sources, bring the index up to date, then
+/// optionally watch the source directories and re-index files as they change.
+fn spawn_knowledge_indexing(
+    indexer: Arc<KnowledgeIndexer>,
+    config: &opencrust_config::KnowledgeConfig,
+) {
+    let sources = config.sources.clone();
+    let watch = config.watch;
+    tokio::spawn(async move {
+        for source in &sources {
+            match indexer.add_source(&source.name, &source.path).await {
+                Ok(report) => info!(
+                    "knowledge source '{}' indexed: {} new/changed, {} unchanged, {} removed, {} failed",
+                    source.name, report.indexed, report.unchanged, report.removed, report.failed
+                ),
+                Err(e) => warn!("failed to index knowledge source '{}': {e}", source.name),
+            }
+        }
+
+        if !watch {
+            return;
+        }
+        let roots: Vec<PathBuf> = match indexer.store().list_sources() {
+            Ok(sources) => sources.into_iter().map(|s| PathBuf::from(s.path)).collect(),
+            Err(e) => {
+                warn!("failed to list knowledge sources: {e}");
+                return;
+            }
+        };
+        spawn_knowledge_watcher(indexer, roots);
+    });
+}
+
+/// Watch knowledge source directories and re-index changed files.
+fn spawn_knowledge_watcher(indexer: Arc<KnowledgeIndexer>, roots: Vec<PathBuf>) {
+    if roots.is_empty() {
+        return;
+    }
+    let (notify_tx, mut notify_rx) = tokio::sync::mpsc::unbounded_channel::<PathBuf>();
+
+    let watcher_result =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event
+                && matches!(
+                    event.kind,
+                    EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                )
+            {
+                for path in event.paths {
+                    let _ = notify_tx.send(path);
+                }
+            }
+        });
+
+    let mut watcher = match watcher_result {
+        Ok(w) => w,
+        Err(e) => {
+            warn!("failed to create knowledge watcher: {e}");
+            return;
+        }
+    };
+
+    for root in &roots {
+        if let Err(e) = watcher.watch(root, RecursiveMode::Recursive) {
+            warn!("failed to watch knowledge source {}: {e}", root.display());
+        }
+    }
+
+    info!("watching {} knowledge source(s) for changes", roots.len());
+
+    tokio::spawn(async move {
+        let _watcher = watcher; // prevent drop
+        loop {
+            let Some(first) = notify_rx.recv().await else {
+                break;
+            };
+            // Debounce: editors often write a file several times in a row
+            tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+            let mut changed = std::collections::BTreeSet::from([first]);
+            while let Ok(path) = notify_rx.try_recv() {
+                changed.insert(path);
+            }
+
+            for path in changed {
+                if let Err(e) = indexer.refresh_path(&path).await {
+                    warn!("failed to re-index {}: {e}", path.display());
+                }
+            }
+        }
+    });
+}
+
 async fn run_scheduler(state: &AppState) -> Result<()> {
     let store_mutex = match &state.session_store {
         Some(s) => s,
@@ -450,6 +635,12 @@ async fn execute_scheduled_task(
         &task.channel_id
     };
 
+    state.set_session_route(
+        &task.session_id,
+        delivery_channel,
+        task.session_metadata.clone(),
+    );
+
     let message = Message {
         id: uuid::Uuid::new_v4().to_string(),
         session_id: SessionId::from_string(&task.session_id),
@@ -485,7 +676,7 @@ async fn execute_scheduled_task(
 
     let history = state.session_history(&task.session_id);
     let continuity_key = state
-        .continuity_key(Some(task.user_id.as_str()))
+        .continuity_key(Some(task.channel_id.as_str()), Some(task.user_id.as_str()))
         .map(|k| k.as_str().to_string());
 
     let response_text = state
@@ -500,17 +691,6 @@ async fn execute_scheduled_task(
         )
         .await?;
 
-    let response_msg = Message {
-        id: uuid::Uuid::new_v4().to_string(),
-        session_id: SessionId::from_string(&task.session_id),
-        channel_id: ChannelId::from_string(delivery_channel),
-        user_id: UserId::from_string("genesis"),
-        direction: MessageDirection::Outgoing,
-        content: MessageContent::Text(response_text.clone()),
-        timestamp: chrono::Utc::now(),
-        metadata: task.session_metadata.clone(),
-    };
-
     // 3. Persist assistant response regardless of outbound channel availability.
     {
         let store = store_mutex.lock().await;
@@ -518,22 +698,22 @@ async fn execute_scheduled_task(
             &task.session_id,
             "assistant",
             &response_text,
-            response_msg.timestamp,
+            chrono::Utc::now(),
             &task.session_metadata,
         )?;
     }
 
-    // 4. Best-effort delivery to channel adapter via sender handle.
-    if let Some(sender) = state.channel_senders.get(delivery_channel) {
-        if let Err(e) = sender.send_message(&response_msg).await {
-            tracing::error!("Failed to send scheduled response: {e}");
-        }
-    } else {
-        tracing::warn!(
-            "Scheduled response persisted but no channel sender registered for: {}",
-            delivery_channel
-        );
-    }
+    // 4. Queue the response for delivery. The task id is the idempotency key,
+    //    so a retried task does not send its result twice.
+    state
+        .enqueue_outbound(
+            &format!("task:{}", task.id),
+            delivery_channel,
+            &task.session_id,
+            &response_text,
+            &task.session_metadata,
+        )
+        .await?;
 
     // 5. Complete task and reschedule if recurring.
     //    Only reschedule if the task was still pending (not cancelled during execution).
@@ -4,18 +4,25 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
 
 use dashmap::DashMap;
+use opencrust_agents::tools::memory::PERSON_KEY_PREFIX;
 use opencrust_agents::{AgentRuntime, ChatMessage};
-use opencrust_channels::ChannelRegistry;
+use opencrust_channels::{ChannelRegistry, CommandRegistry};
 use opencrust_config::AppConfig;
 use opencrust_db::SessionStore;
+use opencrust_security::PairingManager;
 use tokio::sync::{Mutex, watch};
 use tracing::{info, warn};
 use uuid::Uuid;
 
+use crate::access::AccessControl;
+use crate::turns::TurnQueue;
+
 /// How long a disconnected session is kept for resume.
 const SESSION_TTL: Duration = Duration::from_secs(3600); // 1 hour
 /// How often the cleanup task runs.
 const CLEANUP_INTERVAL: Duration = Duration::from_secs(300); // 5 minutes
+/// How long a `/link` code stays valid.
+pub const LINK_CODE_TTL: Duration = Duration::from_secs(300);
 
 /// Shared application state accessible from all request handlers.
 pub struct AppState {
@@ -25,7 +32,12 @@ pub struct AppState {
     pub sessions: DashMap<String, SessionState>,
     /// Send-only handles for each active channel, keyed by channel type.
     /// Populated during startup and used by the scheduler for outbound delivery.
-    pub channel_senders: DashMap<String, Arc<dyn opencrust_channels::ChannelSender>>,
+    pub channel_senders: Arc<DashMap<String, Arc<dyn opencrust_channels::ChannelSender>>>,
+    /// Where each chat session's outbound messages go, keyed by session ID.
+    /// Recorded as messages arrive so tools can reach the chat mid-turn.
+    pub session_routes: Arc<DashMap<String, SessionRoute>>,
+    /// Chat commands shared by all channels.
+    pub commands: CommandRegistry,
     /// In-flight A2A tasks keyed by task ID.
     pub a2a_tasks: DashMap<String, opencrust_agents::a2a::A2ATask>,
     /// MCP server connection manager (legacy, for backward compat).
@@ -33,6 +45,25 @@ pub struct AppState {
     /// MCP manager wrapped in Arc for health monitoring.
     pub mcp_manager_arc: Option<Arc<opencrust_agents::McpManager>>,
     pub session_store: Option<Arc<Mutex<SessionStore>>>,
+    /// Speech-to-text backend for voice messages, if one is configured.
+    pub speech_to_text: Option<Arc<dyn opencrust_media::SpeechToText>>,
+    /// Text-to-speech backend for voice replies, if one is configured.
+    pub text_to_speech: Option<Arc<dyn opencrust_media::TextToSpeech>>,
+    /// Pairing and sidecar status of WhatsApp Web channels, keyed by
+    /// channel name, for `/api/status`.
+    pub whatsapp_web_status: DashMap<String, opencrust_channels::WhatsAppWebStatus>,
+    /// Pending `/link` codes, keyed by the `channel:user_id` that asked for
+    /// one. Shared by all channels so a code can be claimed on another one.
+    pub link_codes: std::sync::Mutex<PairingManager>,
+    /// Role assignments and per-role permissions for chat users.
+    pub access: AccessControl,
+    /// Runs channel messages one turn at a time per session.
+    pub turns: TurnQueue,
+    /// Wakes the outbox worker when a message is queued.
+    pub outbox_ready: tokio::sync::Notify,
+    /// Canonical identity of each linked channel account, keyed by
+    /// `channel:user_id`. Mirrors the `identity_links` table.
+    identities: DashMap<String, String>,
     /// Per-session rolling summary string used by long-context agent flows.
     session_summaries: DashMap<String, String>,
     /// Runtime connection state for Google Workspace integration.
@@ -47,6 +78,14 @@ pub struct AppState {
     config_rx: Option<watch::Receiver<AppConfig>>,
 }
 
+/// A chat session's channel type plus the routing metadata its
+/// `ChannelSender` reads (e.g. `telegram_chat_id`, `whatsapp_from`).
+#[derive(Debug, Clone)]
+pub struct SessionRoute {
+    pub channel: String,
+    pub metadata: serde_json::Value,
+}
+
 #[derive(Debug, Clone)]
 pub struct GoogleOAuthRuntimeConfig {
     pub client_id: String,
@@ -71,15 +110,25 @@ pub struct SessionState {
 impl AppState {
     pub fn new(config: AppConfig, agents: AgentRuntime, channels: ChannelRegistry) -> Self {
         Self {
+            access: AccessControl::new(&config.roles),
+            turns: TurnQueue::new(),
+            outbox_ready: tokio::sync::Notify::new(),
             config,
             channels,
             agents,
             sessions: DashMap::new(),
-            channel_senders: DashMap::new(),
+            channel_senders: Arc::new(DashMap::new()),
+            session_routes: Arc::new(DashMap::new()),
+            commands: crate::commands::builtin_commands(),
             a2a_tasks: DashMap::new(),
             mcp_manager: None,
             mcp_manager_arc: None,
             session_store: None,
+            speech_to_text: None,
+            text_to_speech: None,
+            whatsapp_web_status: DashMap::new(),
+            link_codes: std::sync::Mutex::new(PairingManager::new(LINK_CODE_TTL)),
+            identities: DashMap::new(),
             session_summaries: DashMap::new(),
             google_workspace_integration_connected: AtomicBool::new(false),
             google_workspace_email: RwLock::new(None),
@@ -89,11 +138,40 @@ impl AppState {
         }
     }
 
-    /// Attach a persistent session store used to hydrate and persist chat history.
-    pub fn set_session_store(&mut self, store: Arc<Mutex<SessionStore>>) {
+    /// Attach a persistent session store used to hydrate and persist chat
+    /// history, and load the identity links and roles it holds.
+    pub async fn set_session_store(&mut self, store: Arc<Mutex<SessionStore>>) {
+        {
+            let guard = store.lock().await;
+            match guard.identity_links(None) {
+                Ok(links) => {
+                    for link in links {
+                        self.identities
+                            .insert(account_key(&link.channel, &link.user_id), link.identity_id);
+                    }
+                }
+                Err(e) => warn!("failed to load identity links: {e}"),
+            }
+            match guard.role_assignments() {
+                Ok(assignments) => self.access.load(assignments),
+                Err(e) => warn!("failed to load roles: {e}"),
+            }
+        }
         self.session_store = Some(store);
     }
 
+    /// Remember which chat a session talks to, for deliveries made outside
+    /// the normal reply path (attachments sent by tools).
+    pub fn set_session_route(&self, session_id: &str, channel: &str, metadata: serde_json::Value) {
+        self.session_routes.insert(
+            session_id.to_string(),
+            SessionRoute {
+                channel: channel.to_string(),
+                metadata,
+            },
+        );
+    }
+
     /// Attach a config watch receiver for hot-reload support.
     pub fn set_config_watcher(&mut self, rx: watch::Receiver<AppConfig>) {
         self.config_rx = Some(rx);
@@ -197,8 +275,15 @@ impl AppState {
     }
 
     /// Resolve the continuity key used by the cross-channel memory bus.
-    /// When disabled, returns `None` and memory remains session-scoped.
-    pub fn continuity_key(&self, _user_id: Option<&str>) -> Option<String> {
+    /// Accounts linked to an identity share that identity's key. Otherwise
+    /// everyone shares one key when `memory.shared_continuity` is on, and
+    /// memory stays per session and user when it is off.
+    pub fn continuity_key(&self, channel: Option<&str>, user_id: Option<&str>) -> Option<String> {
+        if let (Some(channel), Some(user_id)) = (channel, user_id)
+            && let Some(identity) = self.linked_identity(channel, user_id)
+        {
+            return Some(format!("{PERSON_KEY_PREFIX}{identity}"));
+        }
         if self.config.memory.shared_continuity {
             Some("bus:shared-global".to_string())
         } else {
@@ -206,6 +291,114 @@ impl AppState {
         }
     }
 
+    /// The canonical identity a channel account is linked to, if any.
+    pub fn linked_identity(&self, channel: &str, user_id: &str) -> Option<String> {
+        self.identities
+            .get(&account_key(channel, user_id))
+            .map(|id| id.clone())
+    }
+
+    /// Stable key for per-person state: the linked identity, or the channel
+    /// account itself when it is not linked.
+    pub fn identity_key(&self, channel: &str, user_id: &str) -> String {
+        match self.linked_identity(channel, user_id) {
+            Some(identity) => format!("{PERSON_KEY_PREFIX}{identity}"),
+            None => account_key(channel, user_id),
+        }
+    }
+
+    /// Link the account `(channel, user_id)` to the identity of `(to_channel,
+    /// to_user_id)`, creating that identity if needed. An account that is
+    /// already linked brings every account of its old identity along, and
+    /// memory the moved accounts saved under their old scope follows them.
+    /// Returns the identity.
+    pub async fn link_identity(
+        &self,
+        channel: &str,
+        user_id: &str,
+        to_channel: &str,
+        to_user_id: &str,
+    ) -> opencrust_common::Result<String> {
+        let existing = self.linked_identity(to_channel, to_user_id);
+        let identity = existing
+            .clone()
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        let mut moving = self.linked_accounts(channel, user_id);
+        if moving.is_empty() {
+            moving.push((channel.to_string(), user_id.to_string()));
+        }
+        if existing.is_none() {
+            moving.push((to_channel.to_string(), to_user_id.to_string()));
+        }
+        let previous_keys: Vec<(String, Option<String>)> = moving
+            .iter()
+            .map(|(channel, user_id)| {
+                (
+                    user_id.clone(),
+                    self.continuity_key(Some(channel), Some(user_id)),
+                )
+            })
+            .collect();
+
+        if let Some(store) = &self.session_store {
+            let guard = store.lock().await;
+            for (channel, user_id) in &moving {
+                guard.link_identity(&identity, channel, user_id)?;
+            }
+        }
+        for (channel, user_id) in &moving {
+            self.identities
+                .insert(account_key(channel, user_id), identity.clone());
+        }
+
+        if let Some(memory) = self.agents.memory_provider() {
+            let key = format!("{PERSON_KEY_PREFIX}{identity}");
+            for (account, previous) in previous_keys {
+                if previous.as_deref() == Some(key.as_str()) {
+                    continue;
+                }
+                if let Err(e) = memory
+                    .rescope_user(&account, previous.as_deref(), &key)
+                    .await
+                {
+                    warn!("failed to move memory of {account} to {key}: {e}");
+                }
+            }
+        }
+        Ok(identity)
+    }
+
+    /// Detach an account from its identity. Returns whether it was linked.
+    pub async fn unlink_identity(
+        &self,
+        channel: &str,
+        user_id: &str,
+    ) -> opencrust_common::Result<bool> {
+        if let Some(store) = &self.session_store {
+            store.lock().await.unlink_identity(channel, user_id)?;
+        }
+        Ok(self
+            .identities
+            .remove(&account_key(channel, user_id))
+            .is_some())
+    }
+
+    /// Every account sharing an identity with `(channel, user_id)`,
+    /// including that account.
+    pub fn linked_accounts(&self, channel: &str, user_id: &str) -> Vec<(String, String)> {
+        let Some(identity) = self.linked_identity(channel, user_id) else {
+            return Vec::new();
+        };
+        let mut accounts: Vec<(String, String)> = self
+            .identities
+            .iter()
+            .filter(|entry| *entry.value() == identity)
+            .filter_map(|entry| split_account_key(entry.key()))
+            .collect();
+        accounts.sort();
+        accounts
+    }
+
     /// Return a cloned history snapshot for a session.
     pub fn session_history(&self, session_id: &str) -> Vec<ChatMessage> {
         self.sessions
@@ -266,7 +459,7 @@ impl AppState {
         let channel = channel_id.unwrap_or("web");
         let user = user_id.unwrap_or("anonymous");
         let metadata = self
-            .continuity_key(user_id)
+            .continuity_key(channel_id, user_id)
             .map(|k| serde_json::json!({ "continuity_key": k }))
             .unwrap_or_else(|| serde_json::json!({}));
 
@@ -283,10 +476,17 @@ impl AppState {
                         loaded_history = messages
                             .into_iter()
                             .filter_map(|m| match m.direction.as_str() {
-                                "user" => Some(ChatMessage {
-                                    role: opencrust_agents::ChatRole::User,
-                                    content: opencrust_agents::MessagePart::Text(m.content),
-                                }),
+                                "user" => {
+                                    let media: Vec<String> = m
+                                        .metadata
+                                        .get("media")
+                                        .and_then(|v| serde_json::from_value(v.clone()).ok())
+                                        .unwrap_or_default();
+                                    Some(ChatMessage {
+                                        role: opencrust_agents::ChatRole::User,
+                                        content: user_content(&m.content, &media),
+                                    })
+                                }
                                 "assistant" => Some(ChatMessage {
                                     role: opencrust_agents::ChatRole::Assistant,
                                     content: opencrust_agents::MessagePart::Text(m.content),
@@ -323,11 +523,44 @@ impl AppState {
         user_text: &str,
         assistant_text: &str,
         channel_metadata: Option<serde_json::Value>,
+    ) {
+        self.persist_turn_with_media(
+            session_id,
+            channel_id,
+            user_id,
+            user_text,
+            &[],
+            assistant_text,
+            channel_metadata,
+        )
+        .await;
+    }
+
+    /// Like [`persist_turn`](Self::persist_turn), for a user message that
+    /// carried images. `media` holds `media://` references; they are kept in
+    /// the message metadata so history replays show the images again.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn persist_turn_with_media(
+        &self,
+        session_id: &str,
+        channel_id: Option<&str>,
+        user_id: Option<&str>,
+        user_text: &str,
+        media: &[String],
+        assistant_text: &str,
+        channel_metadata: Option<serde_json::Value>,
     ) {
         if !self.sessions.contains_key(session_id) {
             self.create_session_with_id(session_id.to_string());
         }
 
+        if let (Some(channel), Some(metadata)) = (channel_id, &channel_metadata) {
+            self.set_session_route(session_id, channel, metadata.clone());
+        }
+        if let (Some(channel), Some(user)) = (channel_id, user_id) {
+            self.charge_session_tokens(session_id, channel, user);
+        }
+
         if let Some(mut session) = self.sessions.get_mut(session_id) {
             if let Some(channel) = channel_id {
                 session.channel_id = Some(channel.to_string());
@@ -338,7 +571,7 @@ impl AppState {
             session.last_active = Instant::now();
             session.history.push(ChatMessage {
                 role: opencrust_agents::ChatRole::User,
-                content: opencrust_agents::MessagePart::Text(user_text.to_string()),
+                content: user_content(user_text, media),
             });
             session.history.push(ChatMessage {
                 role: opencrust_agents::ChatRole::Assistant,
@@ -353,7 +586,7 @@ impl AppState {
         let channel = channel_id.unwrap_or("web");
         let user = user_id.unwrap_or("anonymous");
         let mut metadata = self
-            .continuity_key(user_id)
+            .continuity_key(channel_id, user_id)
             .map(|k| serde_json::json!({ "continuity_key": k }))
             .unwrap_or_else(|| serde_json::json!({}));
 
@@ -370,12 +603,16 @@ impl AppState {
             warn!("failed to upsert session {session_id}: {e}");
             return;
         }
+        let mut user_metadata = serde_json::json!({ "channel_id": channel, "user_id": user });
+        if !media.is_empty() {
+            user_metadata["media"] = serde_json::json!(media);
+        }
         if let Err(e) = guard.append_message(
             session_id,
             "user",
             user_text,
             chrono::Utc::now(),
-            &serde_json::json!({ "channel_id": channel, "user_id": user }),
+            &user_metadata,
         ) {
             warn!("failed to persist user message for {session_id}: {e}");
         }
@@ -478,12 +715,39 @@ impl AppState {
 
 pub type SharedState = Arc<AppState>;
 
+/// Key of a channel account in the identity map.
+pub(crate) fn account_key(channel: &str, user_id: &str) -> String {
+    format!("{channel}:{user_id}")
+}
+
+/// Split an [`account_key`]. Channel types contain no `:`, user IDs may.
+pub(crate) fn split_account_key(key: &str) -> Option<(String, String)> {
+    key.split_once(':')
+        .map(|(channel, user_id)| (channel.to_string(), user_id.to_string()))
+}
+
+/// User message content: plain text, or image references followed by the text.
+fn user_content(text: &str, media: &[String]) -> opencrust_agents::MessagePart {
+    if media.is_empty() {
+        return opencrust_agents::MessagePart::Text(text.to_string());
+    }
+    let mut blocks: Vec<opencrust_agents::ContentBlock> = media
+        .iter()
+        .map(|url| opencrust_agents::ContentBlock::Image { url: url.clone() })
+        .collect();
+    blocks.push(opencrust_agents::ContentBlock::Text {
+        text: text.to_string(),
+    });
+    opencrust_agents::MessagePart::Parts(blocks)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use opencrust_agents::AgentRuntime;
     use opencrust_channels::ChannelRegistry;
     use opencrust_config::AppConfig;
+    use opencrust_db::{MemoryListQuery, MemoryProvider, MemoryRole, MemoryStore, NewMemoryEntry};
 
     fn test_state() -> AppState {
         AppState::new(
@@ -571,7 +835,7 @@ mod tests {
         let mut config = AppConfig::default();
         config.memory.shared_continuity = true;
         let state = AppState::new(config, AgentRuntime::new(), ChannelRegistry::new());
-        let key = state.continuity_key(Some("user1"));
+        let key = state.continuity_key(Some("telegram"), Some("user1"));
         assert_eq!(key, Some("bus:shared-global".to_string()));
     }
 
@@ -580,7 +844,92 @@ mod tests {
         let mut config = AppConfig::default();
         config.memory.shared_continuity = false;
         let state = AppState::new(config, AgentRuntime::new(), ChannelRegistry::new());
-        let key = state.continuity_key(Some("user1"));
+        let key = state.continuity_key(Some("telegram"), Some("user1"));
         assert_eq!(key, None);
     }
+
+    #[tokio::test]
+    async fn linked_accounts_share_a_continuity_key() {
+        let state = test_state();
+        let identity = state
+            .link_identity("slack", "U42", "telegram", "42")
+            .await
+            .unwrap();
+        let key = Some(format!("{PERSON_KEY_PREFIX}{identity}"));
+        assert_eq!(state.continuity_key(Some("telegram"), Some("42")), key);
+        assert_eq!(state.continuity_key(Some("slack"), Some("U42")), key);
+        assert_eq!(state.continuity_key(Some("discord"), Some("42")), None);
+        assert_eq!(state.identity_key("discord", "42"), "discord:42");
+        assert_eq!(
+            state.linked_accounts("slack", "U42"),
+            [
+                ("slack".to_string(), "U42".to_string()),
+                ("telegram".to_string(), "42".to_string())
+            ]
+        );
+
+        // A third account joins the same identity.
+        let again = state
+            .link_identity("discord", "7", "slack", "U42")
+            .await
+            .unwrap();
+        assert_eq!(again, identity);
+
+        assert!(state.unlink_identity("telegram", "42").await.unwrap());
+        assert_eq!(state.continuity_key(Some("telegram"), Some("42")), None);
+        assert_eq!(state.linked_accounts("slack", "U42").len(), 2);
+
+        // Joining another identity brings the whole old identity along.
+        let other = state
+            .link_identity("telegram", "42", "imessage", "ana")
+            .await
+            .unwrap();
+        assert_ne!(other, identity);
+        let merged = state
+            .link_identity("slack", "U42", "telegram", "42")
+            .await
+            .unwrap();
+        assert_eq!(merged, other);
+        assert_eq!(state.linked_identity("discord", "7"), Some(other.clone()));
+        assert_eq!(state.linked_accounts("imessage", "ana").len(), 4);
+    }
+
+    #[tokio::test]
+    async fn linking_moves_memory_saved_before_the_link() {
+        let store: Arc<dyn MemoryProvider> = Arc::new(MemoryStore::in_memory().unwrap());
+        for user_id in ["42", "U42"] {
+            store
+                .remember(NewMemoryEntry {
+                    session_id: format!("session-{user_id}"),
+                    channel_id: None,
+                    user_id: Some(user_id.to_string()),
+                    continuity_key: None,
+                    role: MemoryRole::User,
+                    content: format!("note from {user_id}"),
+                    embedding: None,
+                    embedding_model: None,
+                    metadata: serde_json::json!({}),
+                })
+                .await
+                .unwrap();
+        }
+        let mut runtime = AgentRuntime::new();
+        runtime.set_memory_provider(Arc::clone(&store));
+        let state = AppState::new(AppConfig::default(), runtime, ChannelRegistry::new());
+
+        let identity = state
+            .link_identity("slack", "U42", "telegram", "42")
+            .await
+            .unwrap();
+        let key = state.continuity_key(Some("slack"), Some("U42")).unwrap();
+        assert_eq!(key, format!("{PERSON_KEY_PREFIX}{identity}"));
+        let entries = store
+            .list_entries(MemoryListQuery {
+                continuity_key: Some(key),
+                ..MemoryListQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+    }
 }
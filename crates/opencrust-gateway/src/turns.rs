@@ -0,0 +1,385 @@
+//! Per-session turn queue for channel messages.
+//!
+//! Channel callbacks run concurrently, so quick messages in one chat would
+//! otherwise each start an agent turn from the same history and persist
+//! their turns in any order. [`TurnQueue::begin`] makes them take turns: a
+//! session runs one turn at a time, in arrival order. With
+//! `turns.debounce_ms` set, a burst of messages from one sender becomes one
+//! turn; with `turns.busy: interrupt`, a sender's new message cancels their
+//! running turn and both are answered together. Messages of different
+//! senders are never merged, since a turn runs with its sender's role.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use opencrust_config::{BusyPolicy, TurnConfig};
+use tokio::sync::{Notify, OwnedMutexGuard};
+use tracing::info;
+
+use crate::commands::BLOCKED;
+use crate::state::AppState;
+
+/// A channel message waiting for its turn.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TurnMessage {
+    pub user_id: String,
+    pub text: String,
+    /// Images for the model's vision input.
+    pub image_urls: Vec<String>,
+    /// `media://` references to keep in history.
+    pub media: Vec<String>,
+}
+
+struct Entry {
+    seq: u64,
+    message: TurnMessage,
+    /// Part of an interrupted turn; the sender's next turn answers it.
+    resumed: bool,
+}
+
+#[derive(Default)]
+struct Slot {
+    lock: Arc<tokio::sync::Mutex<()>>,
+    state: Mutex<SlotState>,
+}
+
+#[derive(Default)]
+struct SlotState {
+    next_seq: u64,
+    /// Messages waiting for a turn, oldest first.
+    pending: Vec<Entry>,
+    /// Sender and interrupt signal of the running turn.
+    running: Option<(String, Arc<Notify>)>,
+}
+
+/// Serializes channel turns per session.
+#[derive(Default)]
+pub struct TurnQueue {
+    slots: Mutex<HashMap<String, Arc<Slot>>>,
+}
+
+impl TurnQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait until the message may run its turn. Returns `None` when the
+    /// message was merged into another turn of the same sender and needs
+    /// no reply of its own.
+    pub async fn begin(
+        &self,
+        session_id: &str,
+        message: TurnMessage,
+        config: &TurnConfig,
+    ) -> Option<Turn<'_>> {
+        let user_id = message.user_id.clone();
+        let slot = self.slot(session_id);
+        let seq = {
+            let mut state = slot.state.lock().unwrap();
+            state.next_seq += 1;
+            let seq = state.next_seq;
+            state.pending.push(Entry {
+                seq,
+                message,
+                resumed: false,
+            });
+            if config.busy == BusyPolicy::Interrupt
+                && let Some((running_user, interrupt)) = &state.running
+                && *running_user == user_id
+            {
+                interrupt.notify_one();
+            }
+            seq
+        };
+
+        let debounce = config.debounce_ms > 0;
+        if debounce {
+            tokio::time::sleep(Duration::from_millis(config.debounce_ms)).await;
+            // A newer message from the same sender restarts the wait; its
+            // turn takes ours.
+            let newer = slot
+                .state
+                .lock()
+                .unwrap()
+                .pending
+                .iter()
+                .any(|e| e.seq > seq && e.message.user_id == user_id);
+            if newer {
+                self.release(session_id, &slot);
+                return None;
+            }
+        }
+
+        let guard = Arc::clone(&slot.lock).lock_owned().await;
+        let interrupt = Arc::new(Notify::new());
+        let taken = {
+            let mut state = slot.state.lock().unwrap();
+            if !state.pending.iter().any(|e| e.seq == seq) {
+                None
+            } else {
+                let (taken, rest): (Vec<Entry>, Vec<Entry>) = std::mem::take(&mut state.pending)
+                    .into_iter()
+                    .partition(|e| {
+                        e.message.user_id == user_id && (e.seq == seq || e.resumed || debounce)
+                    });
+                state.pending = rest;
+                state.running = Some((user_id.clone(), Arc::clone(&interrupt)));
+                Some(taken)
+            }
+        };
+        let Some(taken) = taken else {
+            drop(guard);
+            self.release(session_id, &slot);
+            return None;
+        };
+
+        let mut message = TurnMessage {
+            user_id,
+            ..TurnMessage::default()
+        };
+        let mut texts = Vec::new();
+        for entry in &taken {
+            let text = entry.message.text.trim();
+            if !text.is_empty() {
+                texts.push(text);
+            }
+            message
+                .image_urls
+                .extend(entry.message.image_urls.iter().cloned());
+            message.media.extend(entry.message.media.iter().cloned());
+        }
+        message.text = texts.join("\n");
+        Some(Turn {
+            message,
+            taken,
+            interrupt,
+            queue: self,
+            session_id: session_id.to_string(),
+            slot,
+            _guard: guard,
+        })
+    }
+
+    fn slot(&self, session_id: &str) -> Arc<Slot> {
+        Arc::clone(
+            self.slots
+                .lock()
+                .unwrap()
+                .entry(session_id.to_string())
+                .or_default(),
+        )
+    }
+
+    /// Forget an idle session once nobody else holds its slot.
+    fn release(&self, session_id: &str, slot: &Arc<Slot>) {
+        let mut slots = self.slots.lock().unwrap();
+        let idle = slot.state.lock().unwrap().pending.is_empty();
+        // One reference is the map's, one the caller's.
+        if idle && Arc::strong_count(slot) <= 2 {
+            slots.remove(session_id);
+        }
+    }
+}
+
+/// A running turn. The session's next turn starts when this is dropped.
+pub struct Turn<'a> {
+    /// What to answer: one message, or several of one sender merged, with
+    /// their texts joined by newlines.
+    pub message: TurnMessage,
+    taken: Vec<Entry>,
+    interrupt: Arc<Notify>,
+    queue: &'a TurnQueue,
+    session_id: String,
+    slot: Arc<Slot>,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Turn<'_> {
+    /// Run the turn's work. If a newer message of the same sender
+    /// interrupts it, the work is cancelled, this turn's messages go to
+    /// their next turn and the caller gets `__blocked__` so that it sends
+    /// no reply.
+    pub async fn run<T>(
+        &mut self,
+        work: impl Future<Output = Result<T, String>>,
+    ) -> Result<T, String> {
+        tokio::select! {
+            result = work => result,
+            _ = self.interrupt.notified() => {
+                info!("turn in {} interrupted by a newer message", self.session_id);
+                let mut state = self.slot.state.lock().unwrap();
+                for mut entry in self.taken.drain(..) {
+                    entry.resumed = true;
+                    state.pending.push(entry);
+                }
+                state.pending.sort_by_key(|e| e.seq);
+                Err(BLOCKED.to_string())
+            }
+        }
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        self.slot.state.lock().unwrap().running = None;
+        self.queue.release(&self.session_id, &self.slot);
+    }
+}
+
+impl AppState {
+    /// Wait for a channel message's turn in its session, using the `turns:`
+    /// config. `None` means the message was merged into another turn.
+    pub async fn begin_turn(&self, session_id: &str, message: TurnMessage) -> Option<Turn<'_>> {
+        let config = self.current_config().turns;
+        self.turns.begin(session_id, message, &config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(debounce_ms: u64, busy: BusyPolicy) -> TurnConfig {
+        TurnConfig { debounce_ms, busy }
+    }
+
+    fn message(user_id: &str, text: &str) -> TurnMessage {
+        TurnMessage {
+            user_id: user_id.to_string(),
+            text: text.to_string(),
+            ..TurnMessage::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn turns_run_one_at_a_time_in_order() {
+        let queue = Arc::new(TurnQueue::new());
+        let config = config(0, BusyPolicy::Queue);
+        let first = queue
+            .begin("s", message("u", "one"), &config)
+            .await
+            .unwrap();
+        assert_eq!(first.message.text, "one");
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let second = {
+            let queue = Arc::clone(&queue);
+            let config = config.clone();
+            tokio::spawn(async move {
+                let turn = queue
+                    .begin("s", message("u", "two"), &config)
+                    .await
+                    .unwrap();
+                tx.send(turn.message.text.clone()).unwrap();
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(rx.try_recv().is_err(), "second turn started too early");
+
+        drop(first);
+        second.await.unwrap();
+        assert_eq!(rx.recv().await.as_deref(), Some("two"));
+        assert!(queue.slots.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn bursts_are_debounced_into_one_turn() {
+        let queue = Arc::new(TurnQueue::new());
+        let config = config(40, BusyPolicy::Queue);
+        let mut tasks = Vec::new();
+        for text in ["a", "b", "c"] {
+            let queue = Arc::clone(&queue);
+            let config = config.clone();
+            tasks.push(tokio::spawn(async move {
+                queue
+                    .begin("s", message("u", text), &config)
+                    .await
+                    .map(|turn| turn.message.text.clone())
+            }));
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let mut texts = Vec::new();
+        for task in tasks {
+            texts.push(task.await.unwrap());
+        }
+        assert_eq!(texts, vec![None, None, Some("a\nb\nc".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn senders_in_one_session_are_never_merged() {
+        let queue = Arc::new(TurnQueue::new());
+        let config = config(40, BusyPolicy::Interrupt);
+        let mut tasks = Vec::new();
+        for (user, text) in [("guest", "a"), ("owner", "b"), ("guest", "c")] {
+            let queue = Arc::clone(&queue);
+            let config = config.clone();
+            tasks.push(tokio::spawn(async move {
+                let mut turn = queue.begin("s", message(user, text), &config).await?;
+                // Each turn outlives the next message's debounce, so a
+                // merge across senders or an interrupt would show here.
+                let result = turn
+                    .run(async {
+                        tokio::time::sleep(Duration::from_millis(60)).await;
+                        Ok::<_, String>(())
+                    })
+                    .await;
+                Some((
+                    turn.message.user_id.clone(),
+                    turn.message.text.clone(),
+                    result,
+                ))
+            }));
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let mut turns = Vec::new();
+        for task in tasks {
+            if let Some(turn) = task.await.unwrap() {
+                turns.push(turn);
+            }
+        }
+        turns.sort();
+        assert_eq!(
+            turns,
+            vec![
+                ("guest".to_string(), "a\nc".to_string(), Ok(())),
+                ("owner".to_string(), "b".to_string(), Ok(())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_new_message_interrupts_the_running_turn() {
+        let queue = Arc::new(TurnQueue::new());
+        let config = config(0, BusyPolicy::Interrupt);
+        let photo = TurnMessage {
+            image_urls: vec!["media://photo".to_string()],
+            media: vec!["media://photo".to_string()],
+            ..message("u", "draft")
+        };
+        let mut first = queue.begin("s", photo, &config).await.unwrap();
+
+        let second = {
+            let queue = Arc::clone(&queue);
+            let config = config.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                queue
+                    .begin("s", message("u", "actually, this"), &config)
+                    .await
+                    .map(|turn| turn.message.clone())
+            })
+        };
+        let result = first
+            .run(std::future::pending::<Result<String, String>>())
+            .await;
+        assert_eq!(result, Err(BLOCKED.to_string()));
+        drop(first);
+
+        let resumed = second.await.unwrap().unwrap();
+        assert_eq!(resumed.text, "draft\nactually, this");
+        assert_eq!(resumed.media, vec!["media://photo".to_string()]);
+        assert_eq!(resumed.image_urls, vec!["media://photo".to_string()]);
+    }
+}
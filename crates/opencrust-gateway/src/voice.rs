@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use opencrust_channels::VoiceReplier;
+use opencrust_config::VoiceReplyMode;
+use tracing::warn;
+
+use crate::state::SharedState;
+
+/// Session store preference key (and channel setting) for the reply mode.
+pub const VOICE_REPLIES_KEY: &str = "voice_replies";
+
+/// Picks the effective mode: the user's `/voice` choice, then the channel's
+/// `voice_replies` setting, then `speech.voice_replies`.
+pub fn resolve_voice_reply_mode(
+    user: Option<VoiceReplyMode>,
+    channel: Option<VoiceReplyMode>,
+    global: VoiceReplyMode,
+) -> VoiceReplyMode {
+    user.or(channel).unwrap_or(global)
+}
+
+/// Load a user's voice reply preference from the session store.
+pub async fn user_voice_reply_mode(
+    state: &SharedState,
+    channel_type: &str,
+    user_id: &str,
+) -> Option<VoiceReplyMode> {
+    let store = state.session_store.as_ref()?;
+    let value = store
+        .lock()
+        .await
+        .get_user_preference(channel_type, user_id, VOICE_REPLIES_KEY)
+        .unwrap_or_else(|e| {
+            warn!("failed to load voice reply preference: {e}");
+            None
+        })?;
+    VoiceReplyMode::parse(&value)
+}
+
+/// Handle `/voice [auto|always|never]` for a chat user.
+pub async fn voice_command(
+    state: &SharedState,
+    channel_type: &str,
+    user_id: &str,
+    arg: Option<&str>,
+) -> String {
+    let Some(arg) = arg else {
+        let current = user_voice_reply_mode(state, channel_type, user_id)
+            .await
+            .unwrap_or(state.current_config().speech.voice_replies);
+        return format!(
+            "Voice replies: {}\n\nUse /voice auto (when you send voice), /voice always, or /voice never.",
+            describe(current)
+        );
+    };
+
+    let Some(mode) = VoiceReplyMode::parse(arg) else {
+        return "Usage: /voice auto | always | never".to_string();
+    };
+    let Some(store) = &state.session_store else {
+        return "Voice reply preferences are unavailable (no session store).".to_string();
+    };
+    if let Err(e) = store.lock().await.set_user_preference(
+        channel_type,
+        user_id,
+        VOICE_REPLIES_KEY,
+        mode.as_str(),
+    ) {
+        warn!("failed to save voice reply preference: {e}");
+        return "Failed to save your voice reply preference.".to_string();
+    }
+
+    let mut reply = format!("Voice replies: {}.", describe(mode));
+    if mode != VoiceReplyMode::Never && state.text_to_speech.is_none() {
+        reply.push_str(" Note: no text-to-speech backend is configured, so replies stay text.");
+    }
+    reply
+}
+
+fn describe(mode: VoiceReplyMode) -> &'static str {
+    match mode {
+        VoiceReplyMode::WhenSpoken => "when you send a voice message",
+        VoiceReplyMode::Always => "always",
+        VoiceReplyMode::Never => "never",
+    }
+}
+
+/// [`VoiceReplier`] backed by the gateway's text-to-speech backend and the
+/// session store's per-user preferences.
+pub struct GatewayVoiceReplier {
+    state: SharedState,
+    channel_type: String,
+    channel_mode: Option<VoiceReplyMode>,
+}
+
+/// Build the voice replier for a channel, or `None` when no text-to-speech
+/// backend is configured.
+pub fn voice_replier(
+    state: &SharedState,
+    channel_type: &str,
+    settings: &HashMap<String, serde_json::Value>,
+) -> Option<Arc<dyn VoiceReplier>> {
+    state.text_to_speech.as_ref()?;
+    let channel_mode = settings.get(VOICE_REPLIES_KEY).and_then(|v| {
+        let mode = v.as_str().and_then(VoiceReplyMode::parse);
+        if mode.is_none() {
+            warn!("{channel_type}: invalid voice_replies setting {v}, using the default");
+        }
+        mode
+    });
+    Some(Arc::new(GatewayVoiceReplier {
+        state: Arc::clone(state),
+        channel_type: channel_type.to_string(),
+        channel_mode,
+    }))
+}
+
+#[async_trait]
+impl VoiceReplier for GatewayVoiceReplier {
+    async fn voice_reply(&self, user_id: &str, user_spoke: bool, reply: &str) -> Option<Vec<u8>> {
+        let tts = self.state.text_to_speech.as_ref()?;
+        let mode = resolve_voice_reply_mode(
+            user_voice_reply_mode(&self.state, &self.channel_type, user_id).await,
+            self.channel_mode,
+            self.state.current_config().speech.voice_replies,
+        );
+        if !mode.applies(user_spoke) {
+            return None;
+        }
+
+        let text = opencrust_media::speakable_text(reply);
+        if text.is_empty() {
+            return None;
+        }
+        match tts.synthesize(&text).await {
+            Ok(audio) => Some(audio),
+            Err(e) => {
+                warn!(
+                    "{}: text-to-speech ({}) failed: {e}",
+                    self.channel_type,
+                    tts.name()
+                );
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_preference_overrides_channel_and_global() {
+        use VoiceReplyMode::*;
+        assert_eq!(
+            resolve_voice_reply_mode(Some(Never), Some(Always), WhenSpoken),
+            Never
+        );
+        assert_eq!(resolve_voice_reply_mode(None, Some(Always), Never), Always);
+        assert_eq!(resolve_voice_reply_mode(None, None, WhenSpoken), WhenSpoken);
+    }
+
+    #[test]
+    fn modes_apply_to_spoken_messages() {
+        assert!(VoiceReplyMode::WhenSpoken.applies(true));
+        assert!(!VoiceReplyMode::WhenSpoken.applies(false));
+        assert!(VoiceReplyMode::Always.applies(false));
+        assert!(!VoiceReplyMode::Never.applies(true));
+    }
+}
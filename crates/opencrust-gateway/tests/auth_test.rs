@@ -128,3 +128,28 @@ async fn ws_allows_access_if_no_api_key_configured() {
         .expect("Should connect without token if none configured");
     let (_ws, _) = ws.split();
 }
+
+#[tokio::test]
+async fn media_api_requires_api_key() {
+    let port = random_port();
+    let mut config = AppConfig::default();
+    config.gateway.port = port;
+    config.gateway.api_key = Some("secret-token".to_string());
+    config.memory.enabled = false;
+    config.data_dir = Some(std::env::temp_dir().join("opencrust_media_api_test"));
+
+    start_test_gateway(config).await;
+    let url = format!("http://127.0.0.1:{port}/api/media/{}", "0".repeat(64));
+    let client = reqwest::Client::new();
+
+    let resp = client.get(&url).send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+
+    let resp = client
+        .get(&url)
+        .bearer_auth("secret-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+}
@@ -0,0 +1,651 @@
+use opencrust_common::{Error, Result};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+/// Largest attachment accepted for extraction.
+pub const MAX_DOCUMENT_BYTES: usize = 20 * 1024 * 1024;
+
+/// Extracted text handed to the agent is cut off after this many characters.
+pub const MAX_EXTRACTED_CHARS: usize = 100_000;
+
+/// Cap on any single decompressed entry inside a DOCX/ODT/EPUB archive.
+const MAX_ARCHIVE_ENTRY_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Cap on everything decompressed from one archive, so an EPUB whose spine
+/// lists one entry many times cannot expand without bound.
+const MAX_ARCHIVE_TOTAL_BYTES: u64 = 100 * 1024 * 1024;
+
+/// Human-readable list used in "unsupported file" replies.
+pub const SUPPORTED_FORMATS: &str = "PDF, DOCX, ODT, EPUB, HTML, Markdown and other text files";
+
+/// Extensions read as UTF-8 text without any conversion.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "md", "markdown", "mdx", "txt", "text", "rst", "org", "adoc", "csv", "tsv", "log", "json",
+    "jsonl", "yaml", "yml", "toml", "ini", "xml", "py", "rs", "js", "ts", "go", "java", "c", "h",
+    "cpp", "sh", "sql",
+];
+
+/// Document formats [`extract_document`] knows how to read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    PlainText,
+    Pdf,
+    Docx,
+    Odt,
+    Epub,
+    Html,
+}
+
+impl DocumentFormat {
+    /// Pick a format from the file extension, falling back to the MIME type
+    /// channels report for files with no useful extension.
+    pub fn detect(filename: &str, mime_type: Option<&str>) -> Option<Self> {
+        if let Some(ext) = extension(filename) {
+            let format = match ext.as_str() {
+                "pdf" => Some(Self::Pdf),
+                "docx" => Some(Self::Docx),
+                "odt" => Some(Self::Odt),
+                "epub" => Some(Self::Epub),
+                "html" | "htm" | "xhtml" => Some(Self::Html),
+                e if TEXT_EXTENSIONS.contains(&e) => Some(Self::PlainText),
+                _ => None,
+            };
+            if format.is_some() {
+                return format;
+            }
+        }
+
+        let mime = mime_type?.split(';').next()?.trim().to_lowercase();
+        match mime.as_str() {
+            "application/pdf" => Some(Self::Pdf),
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => {
+                Some(Self::Docx)
+            }
+            "application/vnd.oasis.opendocument.text" => Some(Self::Odt),
+            "application/epub+zip" => Some(Self::Epub),
+            "text/html" | "application/xhtml+xml" => Some(Self::Html),
+            "application/json" | "application/xml" | "application/x-yaml" => Some(Self::PlainText),
+            m if m.starts_with("text/") => Some(Self::PlainText),
+            _ => None,
+        }
+    }
+}
+
+/// Text pulled out of an attachment, ready to be passed to the agent.
+#[derive(Debug, Clone)]
+pub struct ExtractedDocument {
+    pub filename: String,
+    pub format: DocumentFormat,
+    pub text: String,
+    /// Whether `text` was cut at [`MAX_EXTRACTED_CHARS`].
+    pub truncated: bool,
+}
+
+impl ExtractedDocument {
+    /// Fence the text under the file name and append the user's caption.
+    pub fn to_prompt(&self, caption: Option<&str>) -> String {
+        let mut out = format!("```{}\n{}\n```", self.filename, self.text);
+        if self.truncated {
+            out.push_str(&format!(
+                "\n(Document truncated to the first {MAX_EXTRACTED_CHARS} characters.)"
+            ));
+        }
+        if let Some(caption) = caption.map(str::trim).filter(|c| !c.is_empty()) {
+            out.push_str("\n\n");
+            out.push_str(caption);
+        }
+        out
+    }
+}
+
+fn extension(filename: &str) -> Option<String> {
+    Path::new(filename)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+/// Whether [`extract_text`] can read a file with this name.
+pub fn is_supported(filename: &str) -> bool {
+    DocumentFormat::detect(filename, None).is_some()
+}
+
+/// Extract text from an attachment received on a channel. Applies the size
+/// caps and returns a user-facing error for unsupported or empty files.
+pub fn extract_document(
+    bytes: &[u8],
+    filename: &str,
+    mime_type: Option<&str>,
+) -> Result<ExtractedDocument> {
+    if bytes.len() > MAX_DOCUMENT_BYTES {
+        return Err(Error::Media(format!(
+            "File too large. Maximum size is {}MB.",
+            MAX_DOCUMENT_BYTES / (1024 * 1024)
+        )));
+    }
+    let format = DocumentFormat::detect(filename, mime_type).ok_or_else(|| {
+        Error::Media(format!(
+            "Unsupported file type ({filename}). Supported: {SUPPORTED_FORMATS}."
+        ))
+    })?;
+
+    let text = extract_format(bytes, format)?;
+    if text.trim().is_empty() {
+        return Err(Error::Media(format!(
+            "No readable text found in {filename}. Scanned documents need OCR first."
+        )));
+    }
+
+    let (text, truncated) = match text.char_indices().nth(MAX_EXTRACTED_CHARS) {
+        Some((cut, _)) => (text[..cut].to_string(), true),
+        None => (text, false),
+    };
+    Ok(ExtractedDocument {
+        filename: filename.to_string(),
+        format,
+        text,
+        truncated,
+    })
+}
+
+/// Extract plain text from a document, choosing the parser by file extension.
+/// Returns `Ok(None)` for formats that are not supported.
+pub fn extract_text(bytes: &[u8], filename: &str) -> Result<Option<String>> {
+    match DocumentFormat::detect(filename, None) {
+        Some(format) => extract_format(bytes, format).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Read a file from disk and extract its text.
+pub fn extract_file(path: &Path) -> Result<Option<String>> {
+    let filename = path.to_string_lossy();
+    if !is_supported(&filename) {
+        return Ok(None);
+    }
+    let bytes = std::fs::read(path)?;
+    extract_text(&bytes, &filename)
+}
+
+fn extract_format(bytes: &[u8], format: DocumentFormat) -> Result<String> {
+    let text = match format {
+        DocumentFormat::PlainText => String::from_utf8_lossy(bytes).into_owned(),
+        DocumentFormat::Pdf => extract_pdf(bytes)?,
+        DocumentFormat::Docx => extract_docx(bytes)?,
+        DocumentFormat::Odt => extract_odt(bytes)?,
+        DocumentFormat::Epub => extract_epub(bytes)?,
+        DocumentFormat::Html => html_to_text(bytes)?,
+    };
+    Ok(tidy_whitespace(&text))
+}
+
+/// Trim trailing spaces and collapse runs of blank lines.
+fn tidy_whitespace(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut blank_run = 0;
+    for line in text.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_run += 1;
+            if blank_run > 1 {
+                continue;
+            }
+        } else {
+            blank_run = 0;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.trim().to_string()
+}
+
+fn extract_pdf(bytes: &[u8]) -> Result<String> {
+    // The PDF parser panics on some malformed files; keep that contained.
+    let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(bytes))
+        .map_err(|_| Error::Media("PDF parser crashed on this file".into()))?
+        .map_err(|e| Error::Media(format!("failed to extract PDF text: {e}")))?;
+
+    let mut out = String::new();
+    for (i, page) in pages.iter().enumerate() {
+        if page.trim().is_empty() {
+            continue;
+        }
+        out.push_str(&format!("--- Page {} ---\n{}\n\n", i + 1, page.trim()));
+    }
+    Ok(out)
+}
+
+/// A DOCX/ODT/EPUB archive that refuses to decompress more than its budget.
+struct Archive<'a> {
+    zip: zip::ZipArchive<Cursor<&'a [u8]>>,
+    budget: u64,
+}
+
+impl<'a> Archive<'a> {
+    fn open(bytes: &'a [u8]) -> Result<Self> {
+        let zip = zip::ZipArchive::new(Cursor::new(bytes))
+            .map_err(|e| Error::Media(format!("not a valid document archive: {e}")))?;
+        Ok(Self {
+            zip,
+            budget: MAX_ARCHIVE_TOTAL_BYTES,
+        })
+    }
+
+    fn read(&mut self, name: &str) -> Result<String> {
+        if self.is_spent() {
+            return Err(too_large_archive());
+        }
+        let entry = self
+            .zip
+            .by_name(name)
+            .map_err(|e| Error::Media(format!("missing {name} in document: {e}")))?;
+        let limit = MAX_ARCHIVE_ENTRY_BYTES.min(self.budget);
+        let mut content = Vec::new();
+        entry
+            .take(limit + 1)
+            .read_to_end(&mut content)
+            .map_err(|e| Error::Media(format!("failed to read {name}: {e}")))?;
+        if content.len() as u64 > limit {
+            self.budget = 0;
+            return Err(too_large_archive());
+        }
+        self.budget -= content.len() as u64;
+        String::from_utf8(content).map_err(|e| Error::Media(format!("failed to read {name}: {e}")))
+    }
+
+    /// Whether the budget is used up, so no further entry can be read.
+    fn is_spent(&self) -> bool {
+        self.budget == 0
+    }
+}
+
+fn too_large_archive() -> Error {
+    Error::Media(format!(
+        "document is too large once decompressed (maximum {}MB)",
+        MAX_ARCHIVE_TOTAL_BYTES / (1024 * 1024)
+    ))
+}
+
+fn xml_error(e: quick_xml::Error) -> Error {
+    Error::Media(format!("malformed document XML: {e}"))
+}
+
+fn attribute(element: &BytesStart<'_>, local_name: &[u8]) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.local_name().as_ref() == local_name)
+        .and_then(|a| a.unescape_value().ok().map(|v| v.into_owned()))
+}
+
+/// Append a finished paragraph, prefixing headings with Markdown markers so
+/// section boundaries survive extraction.
+fn push_paragraph(out: &mut String, text: &str, heading: Option<usize>) {
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+    if let Some(level) = heading {
+        out.push('\n');
+        out.push_str(&"#".repeat(level.clamp(1, 6)));
+        out.push(' ');
+    }
+    out.push_str(text);
+    out.push_str("\n\n");
+}
+
+fn extract_docx(bytes: &[u8]) -> Result<String> {
+    let xml = Archive::open(bytes)?.read("word/document.xml")?;
+    let mut reader = Reader::from_str(&xml);
+
+    let mut out = String::new();
+    let mut paragraph = String::new();
+    let mut heading = None;
+    let mut in_text = false;
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"p" => {
+                    paragraph.clear();
+                    heading = None;
+                }
+                b"t" => in_text = true,
+                _ => {}
+            },
+            Event::Empty(e) => match e.local_name().as_ref() {
+                b"tab" => paragraph.push('\t'),
+                b"br" | b"cr" => {
+                    if attribute(&e, b"type").as_deref() == Some("page") {
+                        push_paragraph(&mut out, &paragraph, heading);
+                        paragraph.clear();
+                        out.push_str("--- Page break ---\n\n");
+                    } else {
+                        paragraph.push('\n');
+                    }
+                }
+                b"pStyle" => heading = attribute(&e, b"val").and_then(|v| docx_heading_level(&v)),
+                _ => {}
+            },
+            Event::Text(t) if in_text => paragraph.push_str(&t.unescape().map_err(xml_error)?),
+            Event::End(e) => match e.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"p" => {
+                    push_paragraph(&mut out, &paragraph, heading);
+                    paragraph.clear();
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+/// Map Word paragraph styles such as `Heading2` or `Title` to a heading level.
+fn docx_heading_level(style: &str) -> Option<usize> {
+    if style.eq_ignore_ascii_case("title") {
+        return Some(1);
+    }
+    let level = style
+        .strip_prefix("Heading")
+        .or_else(|| style.strip_prefix("heading"))?;
+    level.trim().parse().ok()
+}
+
+fn extract_odt(bytes: &[u8]) -> Result<String> {
+    let xml = Archive::open(bytes)?.read("content.xml")?;
+    let mut reader = Reader::from_str(&xml);
+
+    let mut out = String::new();
+    let mut paragraph = String::new();
+    let mut heading = None;
+    let mut depth = 0usize;
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"p" => depth += 1,
+                b"h" => {
+                    depth += 1;
+                    heading = Some(
+                        attribute(&e, b"outline-level")
+                            .and_then(|l| l.parse().ok())
+                            .unwrap_or(1),
+                    );
+                }
+                _ => {}
+            },
+            Event::Empty(e) if depth > 0 => match e.local_name().as_ref() {
+                b"s" => {
+                    let count = attribute(&e, b"c")
+                        .and_then(|c| c.parse().ok())
+                        .unwrap_or(1usize);
+                    paragraph.push_str(&" ".repeat(count.min(64)));
+                }
+                b"tab" => paragraph.push('\t'),
+                b"line-break" => paragraph.push('\n'),
+                _ => {}
+            },
+            Event::Text(t) if depth > 0 => paragraph.push_str(&t.unescape().map_err(xml_error)?),
+            Event::End(e) if matches!(e.local_name().as_ref(), b"p" | b"h") => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    push_paragraph(&mut out, &paragraph, heading.take());
+                    paragraph.clear();
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(out)
+}
+
+fn extract_epub(bytes: &[u8]) -> Result<String> {
+    let mut archive = Archive::open(bytes)?;
+    let container = archive.read("META-INF/container.xml")?;
+    let opf_path = find_rootfile(&container)?;
+    let opf = archive.read(&opf_path)?;
+    let base = match opf_path.rfind('/') {
+        Some(i) => &opf_path[..=i],
+        None => "",
+    };
+
+    let mut out = String::new();
+    let mut chars = 0;
+    let mut section = 0;
+    for href in epub_spine(&opf)? {
+        // Anything past the cap is cut by `extract_document` anyway.
+        if chars > MAX_EXTRACTED_CHARS {
+            break;
+        }
+        let path = format!("{base}{}", href.replace("%20", " "));
+        let html = match archive.read(&path) {
+            Ok(html) => html,
+            Err(e) if archive.is_spent() => return Err(e),
+            Err(e) => {
+                tracing::debug!("skipping EPUB item {path}: {e}");
+                continue;
+            }
+        };
+        let text = html_to_text(html.as_bytes())?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        section += 1;
+        let block = format!("--- Section {section} ---\n{}\n\n", text.trim());
+        chars += block.chars().count();
+        out.push_str(&block);
+    }
+    Ok(out)
+}
+
+fn find_rootfile(container: &str) -> Result<String> {
+    let mut reader = Reader::from_str(container);
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"rootfile" => {
+                if let Some(path) = attribute(&e, b"full-path") {
+                    return Ok(path);
+                }
+            }
+            Event::Eof => return Err(Error::Media("EPUB has no package document".into())),
+            _ => {}
+        }
+    }
+}
+
+/// Content document paths in reading order, from the OPF manifest and spine.
+fn epub_spine(opf: &str) -> Result<Vec<String>> {
+    let mut reader = Reader::from_str(opf);
+    let mut manifest = std::collections::HashMap::new();
+    let mut spine = Vec::new();
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"item" => {
+                    if let (Some(id), Some(href)) = (attribute(&e, b"id"), attribute(&e, b"href")) {
+                        manifest.insert(id, href);
+                    }
+                }
+                b"itemref" => {
+                    if let Some(idref) = attribute(&e, b"idref") {
+                        spine.push(idref);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(spine
+        .into_iter()
+        .filter_map(|id| manifest.remove(&id))
+        .collect())
+}
+
+fn html_to_text(bytes: &[u8]) -> Result<String> {
+    html2text::config::plain()
+        .no_table_borders()
+        .string_from_read(bytes, 120)
+        .map_err(|e| Error::Media(format!("failed to convert HTML: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn archive(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            writer
+                .start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn text_formats_pass_through_and_unknown_are_skipped() {
+        assert_eq!(
+            extract_text(b"# Title\n\nBody", "notes/README.MD").unwrap(),
+            Some("# Title\n\nBody".to_string())
+        );
+        assert_eq!(extract_text(b"\x00\x01", "photo.png").unwrap(), None);
+        assert!(is_supported("paper.pdf"));
+        assert!(is_supported("report.docx"));
+        assert!(!is_supported("Makefile"));
+    }
+
+    #[test]
+    fn detects_format_from_mime_when_extension_is_missing() {
+        assert_eq!(
+            DocumentFormat::detect("upload", Some("application/pdf")),
+            Some(DocumentFormat::Pdf)
+        );
+        assert_eq!(
+            DocumentFormat::detect("upload", Some("text/plain; charset=utf-8")),
+            Some(DocumentFormat::PlainText)
+        );
+        assert_eq!(
+            DocumentFormat::detect("upload.bin", Some("image/png")),
+            None
+        );
+    }
+
+    #[test]
+    fn invalid_pdf_is_an_error() {
+        assert!(extract_text(b"not a pdf", "broken.pdf").is_err());
+    }
+
+    #[test]
+    fn docx_keeps_headings_and_paragraphs() {
+        let xml = r#"<w:document xmlns:w="w"><w:body>
+            <w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Trip plan</w:t></w:r></w:p>
+            <w:p><w:r><w:t xml:space="preserve">Fly to </w:t></w:r><w:r><w:t>Lisbon &amp; Porto</w:t></w:r></w:p>
+            </w:body></w:document>"#;
+        let bytes = archive(&[("word/document.xml", xml)]);
+        let text = extract_text(&bytes, "trip.docx").unwrap().unwrap();
+        assert_eq!(text, "# Trip plan\n\nFly to Lisbon & Porto");
+    }
+
+    #[test]
+    fn odt_reads_headings_and_spans() {
+        let xml = r#"<office:document-content xmlns:office="o" xmlns:text="t"><office:body><office:text>
+            <text:h text:outline-level="2">Budget</text:h>
+            <text:p>Total<text:s text:c="2"/><text:span>1200 EUR</text:span></text:p>
+            </office:text></office:body></office:document-content>"#;
+        let bytes = archive(&[("content.xml", xml)]);
+        let text = extract_text(&bytes, "budget.odt").unwrap().unwrap();
+        assert_eq!(text, "## Budget\n\nTotal  1200 EUR");
+    }
+
+    #[test]
+    fn epub_follows_spine_with_section_markers() {
+        let container = r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf"/></rootfiles></container>"#;
+        let opf = r#"<package><manifest>
+            <item id="c2" href="ch2.xhtml"/><item id="c1" href="ch1.xhtml"/>
+            </manifest><spine><itemref idref="c1"/><itemref idref="c2"/></spine></package>"#;
+        let bytes = archive(&[
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", opf),
+            (
+                "OEBPS/ch1.xhtml",
+                "<html><body><p>Call me Ishmael.</p></body></html>",
+            ),
+            (
+                "OEBPS/ch2.xhtml",
+                "<html><body><p>The Carpet-Bag.</p></body></html>",
+            ),
+        ]);
+        let text = extract_text(&bytes, "book.epub").unwrap().unwrap();
+        let first = text.find("--- Section 1 ---\nCall me Ishmael.").unwrap();
+        let second = text.find("--- Section 2 ---\nThe Carpet-Bag.").unwrap();
+        assert!(first < second);
+    }
+
+    #[test]
+    fn html_drops_scripts() {
+        let html = b"<html><head><script>alert(1)</script></head><body><h1>Hi</h1><p>there</p></body></html>";
+        let text = extract_text(html, "page.html").unwrap().unwrap();
+        assert!(text.contains("Hi"));
+        assert!(text.contains("there"));
+        assert!(!text.contains("alert"));
+    }
+
+    #[test]
+    fn attachments_are_capped_and_rejected_with_reasons() {
+        let long = "a".repeat(MAX_EXTRACTED_CHARS + 10);
+        let doc = extract_document(long.as_bytes(), "big.txt", None).unwrap();
+        assert!(doc.truncated);
+        assert_eq!(doc.text.len(), MAX_EXTRACTED_CHARS);
+        assert!(doc.to_prompt(Some("summarise")).ends_with("summarise"));
+
+        let err = extract_document(b"x", "song.mp3", None).unwrap_err();
+        assert!(err.to_string().contains("Unsupported file type"));
+        let err = extract_document(b"  \n", "empty.txt", None).unwrap_err();
+        assert!(err.to_string().contains("No readable text"));
+    }
+
+    #[test]
+    fn epub_spine_repeating_one_entry_stops_at_the_cap() {
+        let container =
+            r#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#;
+        // The spine lists each manifest id once, so repeat the entry under
+        // many ids.
+        let items: String = (0..10_000)
+            .map(|i| format!(r#"<item id="c{i}" href="c.xhtml"/>"#))
+            .collect();
+        let refs: String = (0..10_000)
+            .map(|i| format!(r#"<itemref idref="c{i}"/>"#))
+            .collect();
+        let opf = format!("<package><manifest>{items}</manifest><spine>{refs}</spine></package>");
+        let chapter = format!("<html><body><p>{}</p></body></html>", "word ".repeat(5_000));
+        let bytes = archive(&[
+            ("META-INF/container.xml", container),
+            ("content.opf", &opf),
+            ("c.xhtml", &chapter),
+        ]);
+
+        let doc = extract_document(&bytes, "book.epub", None).unwrap();
+        assert!(doc.truncated);
+        assert_eq!(doc.text.chars().count(), MAX_EXTRACTED_CHARS);
+        assert!(!doc.text.contains("--- Section 30 ---"));
+    }
+
+    #[test]
+    fn archives_stop_decompressing_past_the_total_budget() {
+        let bytes = archive(&[("a.xml", "aaaa"), ("b.xml", "bbbb")]);
+        let mut archive = Archive::open(&bytes).unwrap();
+        archive.budget = 6;
+        assert_eq!(archive.read("a.xml").unwrap(), "aaaa");
+        let err = archive.read("b.xml").unwrap_err();
+        assert!(err.to_string().contains("too large once decompressed"));
+        assert!(archive.is_spent());
+        assert!(archive.read("a.xml").is_err());
+    }
+}
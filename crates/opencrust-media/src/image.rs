@@ -0,0 +1,381 @@
+use opencrust_common::{Error, Result};
+use std::path::{Path, PathBuf};
+
+use crate::processing::{MediaProcessor, ScratchFiles};
+use crate::store::sniff_mime;
+
+/// Long-edge size used when a provider does not state its own limit.
+pub const DEFAULT_MAX_DIMENSION: u32 = 1568;
+
+/// Images at or under this size are passed through untouched when they
+/// already fit and need no rotation.
+const PASSTHROUGH_BYTES: usize = 1024 * 1024;
+
+/// Videos longer than this are rejected rather than sampled.
+pub const MAX_VIDEO_SECS: f64 = 600.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(Self::Jpeg),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+
+    fn codec_args(&self) -> &'static [&'static str] {
+        match self {
+            Self::Jpeg => &["-c:v", "mjpeg", "-q:v", "3"],
+            Self::Webp => &["-c:v", "libwebp", "-quality", "80"],
+        }
+    }
+}
+
+/// How to prepare an image before it is sent to a vision model.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageOptions {
+    /// Longest allowed edge in pixels.
+    pub max_dimension: u32,
+    pub format: ImageFormat,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            max_dimension: DEFAULT_MAX_DIMENSION,
+            format: ImageFormat::Jpeg,
+        }
+    }
+}
+
+/// An image ready for a provider request.
+#[derive(Debug, Clone)]
+pub struct PreparedImage {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+}
+
+/// Decode, apply EXIF orientation, downscale to `options.max_dimension` and
+/// re-encode. Images that already fit, need no rotation and are small are
+/// returned unchanged without running ffmpeg.
+pub async fn prepare_image(
+    data: &[u8],
+    options: &ImageOptions,
+    work_dir: &Path,
+) -> Result<PreparedImage> {
+    let mime = sniff_mime(data)
+        .filter(|m| m.starts_with("image/"))
+        .ok_or_else(|| Error::Media("unsupported image format".into()))?;
+    let dimensions = image_dimensions(data);
+    let orientation = exif_orientation(data).unwrap_or(1);
+
+    let fits = dimensions.is_some_and(|(w, h)| w.max(h) <= options.max_dimension);
+    if fits && orientation == 1 && data.len() <= PASSTHROUGH_BYTES && mime != "image/gif" {
+        return Ok(PreparedImage {
+            data: data.to_vec(),
+            mime_type: mime.to_string(),
+            width: dimensions.map(|d| d.0),
+            height: dimensions.map(|d| d.1),
+        });
+    }
+
+    std::fs::create_dir_all(work_dir)?;
+    let base = work_dir.join(uuid::Uuid::new_v4().to_string());
+    let input = base.with_extension("img");
+    let output = base.with_extension(options.format.extension());
+    let _scratch = ScratchFiles(vec![input.clone(), output.clone()]);
+    tokio::fs::write(&input, data).await?;
+
+    let filters = image_filters(orientation, options.max_dimension);
+    MediaProcessor::new([work_dir])
+        .transcode_image(&input, &output, &filters, options.format.codec_args())
+        .await?;
+
+    let data = tokio::fs::read(&output).await?;
+    let dimensions = image_dimensions(&data);
+    Ok(PreparedImage {
+        data,
+        mime_type: options.format.mime_type().to_string(),
+        width: dimensions.map(|d| d.0),
+        height: dimensions.map(|d| d.1),
+    })
+}
+
+/// Sample `count` evenly spaced frames from a short video so a vision model
+/// can "see" it. Frames are scaled to `max_dimension` and returned as JPEG.
+pub async fn video_keyframes(
+    data: &[u8],
+    count: usize,
+    max_dimension: u32,
+    work_dir: &Path,
+) -> Result<(f64, Vec<PreparedImage>)> {
+    std::fs::create_dir_all(work_dir)?;
+    let base = work_dir.join(uuid::Uuid::new_v4().to_string());
+    let input = base.with_extension("video");
+    let mut scratch = ScratchFiles(vec![input.clone()]);
+    tokio::fs::write(&input, data).await?;
+
+    let processor = MediaProcessor::new([work_dir]);
+    let duration = processor.probe_duration(&input).await?;
+    if duration > MAX_VIDEO_SECS {
+        return Err(Error::Media(format!(
+            "video is too long to preview ({:.0} s, limit {:.0} s)",
+            duration, MAX_VIDEO_SECS
+        )));
+    }
+
+    let frames: Vec<PathBuf> = processor
+        .extract_video_frames(
+            &input,
+            &base,
+            count.max(1),
+            duration,
+            &scale_filter(max_dimension),
+        )
+        .await?;
+    scratch.0.extend(frames.iter().cloned());
+
+    let mut images = Vec::with_capacity(frames.len());
+    for frame in &frames {
+        let data = tokio::fs::read(frame).await?;
+        let dimensions = image_dimensions(&data);
+        images.push(PreparedImage {
+            data,
+            mime_type: "image/jpeg".to_string(),
+            width: dimensions.map(|d| d.0),
+            height: dimensions.map(|d| d.1),
+        });
+    }
+    if images.is_empty() {
+        return Err(Error::Media("no frames could be extracted".into()));
+    }
+    Ok((duration, images))
+}
+
+/// ffmpeg filter chain for an EXIF orientation followed by a downscale.
+fn image_filters(orientation: u16, max_dimension: u32) -> String {
+    let rotate = match orientation {
+        2 => "hflip",
+        3 => "hflip,vflip",
+        4 => "vflip",
+        5 => "transpose=0",
+        6 => "transpose=1",
+        7 => "transpose=3",
+        8 => "transpose=2",
+        _ => "",
+    };
+    let scale = scale_filter(max_dimension);
+    if rotate.is_empty() {
+        scale
+    } else {
+        format!("{rotate},{scale}")
+    }
+}
+
+/// Shrink so the long edge is at most `max`, never enlarging.
+fn scale_filter(max: u32) -> String {
+    format!("scale=w='min(iw,{max})':h='min(ih,{max})':force_original_aspect_ratio=decrease")
+}
+
+/// Width and height from a PNG, JPEG, GIF or WebP header.
+pub fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| -> Option<u32> {
+        Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+    };
+    let le16 = |at: usize| -> Option<u32> {
+        Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+    };
+    let le24 = |at: usize| -> Option<u32> {
+        let b = data.get(at..at + 3)?;
+        Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+    };
+
+    match sniff_mime(data)? {
+        "image/png" => {
+            let w = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+            let h = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+            Some((w, h))
+        }
+        "image/gif" => Some((le16(6)?, le16(8)?)),
+        "image/webp" => match data.get(12..16)? {
+            b"VP8 " => Some((le16(26)? & 0x3fff, le16(28)? & 0x3fff)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+            }
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        },
+        "image/jpeg" => {
+            let mut pos = 2;
+            while pos + 4 <= data.len() {
+                if data[pos] != 0xff {
+                    return None;
+                }
+                let marker = data[pos + 1];
+                let len = be16(pos + 2)? as usize;
+                // SOF0..SOF15 except DHT (C4), JPG (C8) and DAC (CC)
+                if (0xc0..=0xcf).contains(&marker) && !matches!(marker, 0xc4 | 0xc8 | 0xcc) {
+                    return Some((be16(pos + 7)?, be16(pos + 5)?));
+                }
+                pos += 2 + len;
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// EXIF orientation tag (1-8) from a JPEG's APP1 segment.
+pub fn exif_orientation(data: &[u8]) -> Option<u16> {
+    if !data.starts_with(b"\xff\xd8") {
+        return None;
+    }
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xff {
+        let marker = data[pos + 1];
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if marker == 0xda {
+            return None; // start of scan: no more metadata
+        }
+        if marker == 0xe1 {
+            let segment = data.get(pos + 4..pos + 2 + len)?;
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return tiff_orientation(tiff);
+            }
+        }
+        pos += 2 + len;
+    }
+    None
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| -> Option<u16> {
+        let b: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if little {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let u32_at = |at: usize| -> Option<u32> {
+        let b: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if little {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    (0..entries).find_map(|i| {
+        let entry = ifd + 2 + i * 12;
+        (u16_at(entry)? == 0x0112)
+            .then(|| u16_at(entry + 8))
+            .flatten()
+            .filter(|o| (1..=8).contains(o))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SOI, an EXIF APP1 with the given orientation, then a SOF0 for 4000x3000.
+    fn jpeg_with_orientation(orientation: u16) -> Vec<u8> {
+        let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+        tiff.extend_from_slice(&1u16.to_be_bytes());
+        tiff.extend_from_slice(&0x0112u16.to_be_bytes());
+        tiff.extend_from_slice(&3u16.to_be_bytes());
+        tiff.extend_from_slice(&1u32.to_be_bytes());
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+        let mut app1 = b"Exif\0\0".to_vec();
+        app1.extend_from_slice(&tiff);
+
+        let mut jpeg = vec![0xff, 0xd8, 0xff, 0xe1];
+        jpeg.extend_from_slice(&((app1.len() + 2) as u16).to_be_bytes());
+        jpeg.extend_from_slice(&app1);
+        jpeg.extend_from_slice(&[0xff, 0xc0, 0x00, 0x11, 0x08]);
+        jpeg.extend_from_slice(&3000u16.to_be_bytes());
+        jpeg.extend_from_slice(&4000u16.to_be_bytes());
+        jpeg.extend_from_slice(&[0; 10]);
+        jpeg
+    }
+
+    #[test]
+    fn reads_jpeg_dimensions_and_orientation() {
+        let jpeg = jpeg_with_orientation(6);
+        assert_eq!(image_dimensions(&jpeg), Some((4000, 3000)));
+        assert_eq!(exif_orientation(&jpeg), Some(6));
+    }
+
+    #[test]
+    fn reads_png_and_gif_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&640u32.to_be_bytes());
+        png.extend_from_slice(&480u32.to_be_bytes());
+        assert_eq!(image_dimensions(&png), Some((640, 480)));
+
+        let gif = b"GIF89a\x20\x03\x58\x02";
+        assert_eq!(image_dimensions(gif), Some((800, 600)));
+    }
+
+    #[test]
+    fn filters_rotate_before_scaling() {
+        assert!(image_filters(1, 1568).starts_with("scale="));
+        assert!(image_filters(6, 1568).starts_with("transpose=1,scale="));
+        assert!(image_filters(3, 1024).starts_with("hflip,vflip,scale="));
+        assert!(image_filters(3, 1024).contains("min(iw,1024)"));
+    }
+
+    #[tokio::test]
+    async fn small_upright_images_pass_through() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend_from_slice(&64u32.to_be_bytes());
+        png.extend_from_slice(&64u32.to_be_bytes());
+        let dir = std::env::temp_dir().join("opencrust_image_passthrough");
+        let prepared = prepare_image(&png, &ImageOptions::default(), &dir)
+            .await
+            .unwrap();
+        assert_eq!(prepared.data, png);
+        assert_eq!(prepared.mime_type, "image/png");
+        assert_eq!(prepared.width, Some(64));
+    }
+
+    #[tokio::test]
+    async fn rejects_non_images() {
+        let dir = std::env::temp_dir().join("opencrust_image_reject");
+        let err = prepare_image(b"%PDF-1.7", &ImageOptions::default(), &dir).await;
+        assert!(matches!(err, Err(Error::Media(_))));
+    }
+}
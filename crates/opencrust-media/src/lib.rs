@@ -1,4 +1,15 @@
+pub mod document;
+pub mod image;
 pub mod processing;
+pub mod speech;
+pub mod store;
 pub mod types;
 
+pub use image::{ImageFormat, ImageOptions, PreparedImage, prepare_image, video_keyframes};
+pub use processing::MediaProcessor;
+pub use speech::{
+    OpenAiCompatibleStt, OpenAiCompatibleTts, PiperTts, SpeechToText, TextToSpeech, WhisperCppStt,
+    speakable_text,
+};
+pub use store::{GcReport, MEDIA_SCHEME, MediaStore, StoredMedia, parse_media_url};
 pub use types::{MediaFormat, MediaType};
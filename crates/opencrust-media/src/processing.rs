@@ -1,6 +1,17 @@
 use opencrust_common::Result;
 use std::path::{Path, PathBuf};
 
+/// Deletes scratch files when an operation finishes, successfully or not.
+pub(crate) struct ScratchFiles(pub(crate) Vec<PathBuf>);
+
+impl Drop for ScratchFiles {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
 /// Media processing pipeline for images, audio, and video.
 ///
 /// Wraps external tools (ffmpeg, etc.) and provides a consistent API
@@ -92,23 +103,51 @@ impl MediaProcessor {
         Ok(canonical_path)
     }
 
-    /// Convert audio to a target format using ffmpeg.
+    /// Convert audio to a target format using ffmpeg. `opus` produces a mono
+    /// Ogg/Opus file suitable for voice messages; other formats use ffmpeg's
+    /// defaults for the output extension.
     pub async fn convert_audio(
         &self,
         input: &std::path::Path,
         output: &std::path::Path,
-        _format: &str,
+        format: &str,
     ) -> Result<()> {
         let input_path = self.validate_path(input, false)?;
         let output_path = self.validate_path(output, true)?;
 
+        let codec_args: &[&str] = match format {
+            "opus" => &["-c:a", "libopus", "-b:a", "32k", "-ac", "1", "-f", "ogg"],
+            _ => &[],
+        };
+
         let status = tokio::process::Command::new("ffmpeg")
-            .args([
-                "-i",
-                &input_path.to_string_lossy(),
-                "-y",
-                &output_path.to_string_lossy(),
-            ])
+            .args(["-loglevel", "error", "-i"])
+            .arg(&input_path)
+            .args(codec_args)
+            .arg("-y")
+            .arg(&output_path)
+            .status()
+            .await?;
+
+        if !status.success() {
+            return Err(opencrust_common::Error::Media(
+                "ffmpeg conversion failed".into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Convert audio to the 16 kHz mono WAV that local speech models expect.
+    pub async fn convert_to_speech_wav(&self, input: &Path, output: &Path) -> Result<()> {
+        let input_path = self.validate_path(input, false)?;
+        let output_path = self.validate_path(output, true)?;
+
+        let status = tokio::process::Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-i"])
+            .arg(&input_path)
+            .args(["-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le", "-y"])
+            .arg(&output_path)
             .status()
             .await?;
 
@@ -120,6 +159,191 @@ impl MediaProcessor {
 
         Ok(())
     }
+
+    /// Run a whisper.cpp CLI over a WAV file and return the transcript.
+    /// `output_base` is the path without extension; whisper.cpp appends `.txt`.
+    pub async fn run_whisper_cpp(
+        &self,
+        binary: &str,
+        model: &Path,
+        input: &Path,
+        output_base: &Path,
+        language: Option<&str>,
+    ) -> Result<String> {
+        let model_path = self.validate_path(model, false)?;
+        let input_path = self.validate_path(input, false)?;
+        let output_path = self.validate_path(output_base, true)?;
+
+        let output = tokio::process::Command::new(binary)
+            .arg("-m")
+            .arg(&model_path)
+            .arg("-f")
+            .arg(&input_path)
+            .args(["-l", language.unwrap_or("auto"), "-nt", "-otxt", "-of"])
+            .arg(&output_path)
+            .output()
+            .await
+            .map_err(|e| opencrust_common::Error::Media(format!("failed to run {binary}: {e}")))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(opencrust_common::Error::Media(format!(
+                "{binary} exited with {}: {}",
+                output.status,
+                stderr.trim()
+            )));
+        }
+
+        let transcript_path = output_path.with_extension("txt");
+        let transcript = tokio::fs::read_to_string(&transcript_path).await?;
+        let _ = tokio::fs::remove_file(&transcript_path).await;
+        Ok(transcript.trim().to_string())
+    }
+
+    /// Decode a still image and re-encode it with ffmpeg. `filters` is an
+    /// ffmpeg `-vf` chain (rotation, scaling); `codec_args` select the output
+    /// encoder. Metadata (EXIF, GPS) is dropped. Automatic rotation is
+    /// disabled so callers apply EXIF orientation exactly once.
+    pub async fn transcode_image(
+        &self,
+        input: &Path,
+        output: &Path,
+        filters: &str,
+        codec_args: &[&str],
+    ) -> Result<()> {
+        let input_path = self.validate_path(input, false)?;
+        let output_path = self.validate_path(output, true)?;
+
+        let mut command = tokio::process::Command::new("ffmpeg");
+        command
+            .args(["-loglevel", "error", "-noautorotate", "-i"])
+            .arg(&input_path);
+        if !filters.is_empty() {
+            command.args(["-vf", filters]);
+        }
+        let status = command
+            .args(["-frames:v", "1", "-map_metadata", "-1"])
+            .args(codec_args)
+            .args(["-f", "image2", "-y"])
+            .arg(&output_path)
+            .status()
+            .await?;
+
+        if !status.success() {
+            return Err(opencrust_common::Error::Media(
+                "ffmpeg image conversion failed".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Duration of an audio or video file in seconds, via ffprobe.
+    pub async fn probe_duration(&self, input: &Path) -> Result<f64> {
+        let input_path = self.validate_path(input, false)?;
+        let output = tokio::process::Command::new("ffprobe")
+            .args([
+                "-v",
+                "error",
+                "-show_entries",
+                "format=duration",
+                "-of",
+                "default=noprint_wrappers=1:nokey=1",
+            ])
+            .arg(&input_path)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(opencrust_common::Error::Media(
+                "ffprobe could not read the file".into(),
+            ));
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| opencrust_common::Error::Media("unknown media duration".into()))
+    }
+
+    /// Sample `count` frames evenly across a video and write them as JPEGs
+    /// named `<output_base>_01.jpg`, `<output_base>_02.jpg`, ...
+    pub async fn extract_video_frames(
+        &self,
+        input: &Path,
+        output_base: &Path,
+        count: usize,
+        duration_secs: f64,
+        filters: &str,
+    ) -> Result<Vec<PathBuf>> {
+        let input_path = self.validate_path(input, false)?;
+        let output_base = self.validate_path(output_base, true)?;
+
+        let fps = count as f64 / duration_secs.max(0.1);
+        let mut chain = format!("fps={fps:.6}");
+        if !filters.is_empty() {
+            chain.push(',');
+            chain.push_str(filters);
+        }
+        let pattern = format!("{}_%02d.jpg", output_base.to_string_lossy());
+        let status = tokio::process::Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-i"])
+            .arg(&input_path)
+            .args(["-vf", &chain, "-frames:v"])
+            .arg(count.to_string())
+            .args(["-q:v", "3", "-y"])
+            .arg(&pattern)
+            .status()
+            .await?;
+        if !status.success() {
+            return Err(opencrust_common::Error::Media(
+                "ffmpeg frame extraction failed".into(),
+            ));
+        }
+
+        Ok((1..=count)
+            .map(|i| PathBuf::from(format!("{}_{i:02}.jpg", output_base.to_string_lossy())))
+            .filter(|path| path.is_file())
+            .collect())
+    }
+
+    /// Synthesize `text` to a WAV file with a piper-style CLI, which reads
+    /// the text on stdin and writes `--output_file`.
+    pub async fn run_piper(
+        &self,
+        binary: &str,
+        model: &Path,
+        text: &str,
+        output: &Path,
+    ) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        let model_path = self.validate_path(model, false)?;
+        let output_path = self.validate_path(output, true)?;
+
+        let mut child = tokio::process::Command::new(binary)
+            .arg("--model")
+            .arg(&model_path)
+            .arg("--output_file")
+            .arg(&output_path)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| opencrust_common::Error::Media(format!("failed to run {binary}: {e}")))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(opencrust_common::Error::Media(format!(
+                "{binary} exited with {}: {}",
+                output.status,
+                stderr.trim()
+            )));
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
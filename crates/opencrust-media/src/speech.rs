@@ -0,0 +1,427 @@
+use async_trait::async_trait;
+use opencrust_common::{Error, Result};
+use std::path::{Path, PathBuf};
+
+use crate::processing::{MediaProcessor, ScratchFiles};
+use crate::types::MediaFormat;
+
+const DEFAULT_OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+const DEFAULT_OPENAI_MODEL: &str = "whisper-1";
+const DEFAULT_WHISPER_CPP_BINARY: &str = "whisper-cli";
+const DEFAULT_OPENAI_TTS_MODEL: &str = "tts-1";
+const DEFAULT_OPENAI_TTS_VOICE: &str = "alloy";
+const DEFAULT_PIPER_BINARY: &str = "piper";
+
+/// Longest text sent to a speech synthesizer. OpenAI's limit is 4096.
+pub const MAX_SPEECH_CHARS: usize = 4000;
+
+/// Turns recorded speech into text.
+#[async_trait]
+pub trait SpeechToText: Send + Sync {
+    /// Short backend name for logs.
+    fn name(&self) -> &str;
+
+    /// Transcribe an audio clip. `filename` is used to tell the backend the
+    /// container format (e.g. `voice.ogg`).
+    async fn transcribe(&self, audio: &[u8], filename: &str) -> Result<String>;
+}
+
+/// Any server implementing OpenAI's `/audio/transcriptions` endpoint: OpenAI,
+/// Groq, or a local faster-whisper / whisper.cpp server.
+pub struct OpenAiCompatibleStt {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    language: Option<String>,
+}
+
+impl OpenAiCompatibleStt {
+    pub fn new(base_url: Option<String>, api_key: Option<String>, model: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url
+                .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            api_key,
+            model: model.unwrap_or_else(|| DEFAULT_OPENAI_MODEL.to_string()),
+            language: None,
+        }
+    }
+
+    pub fn with_language(mut self, language: Option<String>) -> Self {
+        self.language = language;
+        self
+    }
+}
+
+#[async_trait]
+impl SpeechToText for OpenAiCompatibleStt {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    async fn transcribe(&self, audio: &[u8], filename: &str) -> Result<String> {
+        let mime = Path::new(filename)
+            .extension()
+            .map(|ext| MediaFormat::from_extension(&ext.to_string_lossy()))
+            .unwrap_or(MediaFormat::Ogg)
+            .mime_type()
+            .to_string();
+        let file_part = reqwest::multipart::Part::bytes(audio.to_vec())
+            .file_name(filename.to_string())
+            .mime_str(&mime)
+            .map_err(|e| Error::Media(format!("failed to build multipart: {e}")))?;
+
+        let mut form = reqwest::multipart::Form::new()
+            .part("file", file_part)
+            .text("model", self.model.clone());
+        if let Some(language) = &self.language {
+            form = form.text("language", language.clone());
+        }
+
+        let mut request = self
+            .client
+            .post(format!("{}/audio/transcriptions", self.base_url))
+            .multipart(form);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Media(format!("transcription request failed: {e}")))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Media(format!(
+                "transcription API error: status={status}, body={body}"
+            )));
+        }
+
+        #[derive(serde::Deserialize)]
+        struct TranscriptionResponse {
+            text: String,
+        }
+
+        let result: TranscriptionResponse = response
+            .json()
+            .await
+            .map_err(|e| Error::Media(format!("failed to parse transcription response: {e}")))?;
+        Ok(result.text.trim().to_string())
+    }
+}
+
+/// Local transcription with the whisper.cpp CLI. Audio is converted to
+/// 16 kHz WAV with ffmpeg first; both tools run through [`MediaProcessor`],
+/// restricted to the scratch directory and the model file.
+pub struct WhisperCppStt {
+    binary: String,
+    model: PathBuf,
+    language: Option<String>,
+    work_dir: PathBuf,
+}
+
+impl WhisperCppStt {
+    pub fn new(
+        binary: Option<String>,
+        model: PathBuf,
+        language: Option<String>,
+        work_dir: PathBuf,
+    ) -> Result<Self> {
+        if !model.is_file() {
+            return Err(Error::Config(format!(
+                "whisper.cpp model not found: {}",
+                model.display()
+            )));
+        }
+        std::fs::create_dir_all(&work_dir)?;
+        Ok(Self {
+            binary: binary.unwrap_or_else(|| DEFAULT_WHISPER_CPP_BINARY.to_string()),
+            model,
+            language,
+            work_dir,
+        })
+    }
+}
+
+#[async_trait]
+impl SpeechToText for WhisperCppStt {
+    fn name(&self) -> &str {
+        "whisper-cpp"
+    }
+
+    async fn transcribe(&self, audio: &[u8], filename: &str) -> Result<String> {
+        let ext = Path::new(filename)
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .filter(|e| e.chars().all(|c| c.is_ascii_alphanumeric()))
+            .unwrap_or_else(|| "ogg".to_string());
+        let base = self.work_dir.join(uuid::Uuid::new_v4().to_string());
+        let input = base.with_extension(ext);
+        let wav = base.with_extension("wav");
+        let _scratch = ScratchFiles(vec![input.clone(), wav.clone(), base.with_extension("txt")]);
+
+        tokio::fs::write(&input, audio).await?;
+
+        let mut allowed = vec![self.work_dir.clone()];
+        if let Some(model_dir) = self.model.parent() {
+            allowed.push(model_dir.to_path_buf());
+        }
+        let processor = MediaProcessor::new(allowed);
+        processor.convert_to_speech_wav(&input, &wav).await?;
+        processor
+            .run_whisper_cpp(
+                &self.binary,
+                &self.model,
+                &wav,
+                &base,
+                self.language.as_deref(),
+            )
+            .await
+    }
+}
+
+/// Turns text into a spoken voice message.
+#[async_trait]
+pub trait TextToSpeech: Send + Sync {
+    /// Short backend name for logs.
+    fn name(&self) -> &str;
+
+    /// Synthesize `text` and return Ogg/Opus audio, ready to send as a voice note.
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>>;
+}
+
+/// Any server implementing OpenAI's `/audio/speech` endpoint.
+pub struct OpenAiCompatibleTts {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+    voice: String,
+}
+
+impl OpenAiCompatibleTts {
+    pub fn new(
+        base_url: Option<String>,
+        api_key: Option<String>,
+        model: Option<String>,
+        voice: Option<String>,
+    ) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url
+                .unwrap_or_else(|| DEFAULT_OPENAI_BASE_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            api_key,
+            model: model.unwrap_or_else(|| DEFAULT_OPENAI_TTS_MODEL.to_string()),
+            voice: voice.unwrap_or_else(|| DEFAULT_OPENAI_TTS_VOICE.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl TextToSpeech for OpenAiCompatibleTts {
+    fn name(&self) -> &str {
+        "openai"
+    }
+
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>> {
+        let body = serde_json::json!({
+            "model": self.model,
+            "voice": self.voice,
+            "input": text,
+            "response_format": "opus",
+        });
+        let mut request = self
+            .client
+            .post(format!("{}/audio/speech", self.base_url))
+            .json(&body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Error::Media(format!("speech request failed: {e}")))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Media(format!(
+                "speech API error: status={status}, body={body}"
+            )));
+        }
+
+        let audio = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Media(format!("failed to read speech response: {e}")))?;
+        Ok(audio.to_vec())
+    }
+}
+
+/// Local synthesis with the piper CLI. Its WAV output is converted to
+/// Ogg/Opus with ffmpeg through [`MediaProcessor::convert_audio`].
+pub struct PiperTts {
+    binary: String,
+    model: PathBuf,
+    work_dir: PathBuf,
+}
+
+impl PiperTts {
+    pub fn new(binary: Option<String>, model: PathBuf, work_dir: PathBuf) -> Result<Self> {
+        if !model.is_file() {
+            return Err(Error::Config(format!(
+                "piper voice model not found: {}",
+                model.display()
+            )));
+        }
+        std::fs::create_dir_all(&work_dir)?;
+        Ok(Self {
+            binary: binary.unwrap_or_else(|| DEFAULT_PIPER_BINARY.to_string()),
+            model,
+            work_dir,
+        })
+    }
+}
+
+#[async_trait]
+impl TextToSpeech for PiperTts {
+    fn name(&self) -> &str {
+        "piper"
+    }
+
+    async fn synthesize(&self, text: &str) -> Result<Vec<u8>> {
+        let base = self.work_dir.join(uuid::Uuid::new_v4().to_string());
+        let wav = base.with_extension("wav");
+        let ogg = base.with_extension("ogg");
+        let _scratch = ScratchFiles(vec![wav.clone(), ogg.clone()]);
+
+        let mut allowed = vec![self.work_dir.clone()];
+        if let Some(model_dir) = self.model.parent() {
+            allowed.push(model_dir.to_path_buf());
+        }
+        let processor = MediaProcessor::new(allowed);
+        processor
+            .run_piper(&self.binary, &self.model, text, &wav)
+            .await?;
+        processor.convert_audio(&wav, &ogg, "opus").await?;
+        Ok(tokio::fs::read(&ogg).await?)
+    }
+}
+
+/// Prepare a chat reply for reading aloud: code blocks are summarised,
+/// Markdown markup and link targets are dropped, and the result is capped
+/// at [`MAX_SPEECH_CHARS`], preferring a sentence boundary.
+pub fn speakable_text(reply: &str) -> String {
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    for line in reply.lines() {
+        if line.trim_start().starts_with("```") {
+            if !in_code {
+                lines.push("(code omitted)".to_string());
+            }
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let line = line
+            .trim_start()
+            .trim_start_matches('#')
+            .trim_start_matches("> ")
+            .trim_start();
+        let line = line
+            .strip_prefix("- ")
+            .or_else(|| line.strip_prefix("* "))
+            .unwrap_or(line);
+        lines.push(strip_inline_markdown(line));
+    }
+
+    let text = lines.join("\n").trim().to_string();
+    if text.chars().count() <= MAX_SPEECH_CHARS {
+        return text;
+    }
+    let cut: String = text.chars().take(MAX_SPEECH_CHARS).collect();
+    match cut.rfind(['.', '!', '?']) {
+        Some(end) if end > MAX_SPEECH_CHARS / 2 => cut[..=end].to_string(),
+        _ => cut,
+    }
+}
+
+/// Remove emphasis markers and inline code ticks, and keep only the label of
+/// `[label](url)` links.
+fn strip_inline_markdown(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(open) = rest.find('[') {
+        let (before, after) = rest.split_at(open);
+        out.push_str(before);
+        match after
+            .find("](")
+            .and_then(|mid| after[mid..].find(')').map(|close| (mid, mid + close)))
+        {
+            Some((mid, close)) => {
+                out.push_str(&after[1..mid]);
+                rest = &after[close + 1..];
+            }
+            None => {
+                out.push('[');
+                rest = &after[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.replace("**", "")
+        .replace("__", "")
+        .replace(['`', '*'], "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whisper_cpp_requires_model_file() {
+        let dir = std::env::temp_dir().join("opencrust_speech_test_model");
+        let err = WhisperCppStt::new(None, dir.join("missing.bin"), None, dir.clone());
+        assert!(matches!(err, Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn openai_defaults_and_trailing_slash() {
+        let stt = OpenAiCompatibleStt::new(Some("http://localhost:8000/v1/".into()), None, None);
+        assert_eq!(stt.base_url, "http://localhost:8000/v1");
+        assert_eq!(stt.model, "whisper-1");
+        assert_eq!(stt.name(), "openai");
+    }
+
+    #[test]
+    fn piper_requires_model_file() {
+        let dir = std::env::temp_dir().join("opencrust_speech_test_piper");
+        let err = PiperTts::new(None, dir.join("missing.onnx"), dir.clone());
+        assert!(matches!(err, Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn speakable_text_drops_markup_and_code() {
+        let reply = "## Result\nSee **the docs** at [the site](https://example.com).\n```rust\nfn main() {}\n```\n- `cargo build` works";
+        assert_eq!(
+            speakable_text(reply),
+            "Result\nSee the docs at the site.\n(code omitted)\ncargo build works"
+        );
+    }
+
+    #[test]
+    fn speakable_text_caps_length_at_sentence() {
+        let reply = "Short sentence. ".repeat(400);
+        let text = speakable_text(&reply);
+        assert!(text.chars().count() <= MAX_SPEECH_CHARS);
+        assert!(text.ends_with('.'));
+    }
+}
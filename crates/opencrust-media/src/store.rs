@@ -0,0 +1,467 @@
+use base64::Engine;
+use opencrust_common::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// URL scheme for references to stored media, e.g. `media://3f2a…`.
+pub const MEDIA_SCHEME: &str = "media://";
+
+const FALLBACK_MIME: &str = "application/octet-stream";
+
+/// Metadata kept next to each stored file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredMedia {
+    /// Hex SHA-256 of the content.
+    pub id: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub filename: Option<String>,
+    /// Unix seconds.
+    pub created_at: u64,
+    /// Unix seconds; drives retention and least-recently-used eviction.
+    pub accessed_at: u64,
+}
+
+impl StoredMedia {
+    /// `media://` reference for message content.
+    pub fn url(&self) -> String {
+        format!("{MEDIA_SCHEME}{}", self.id)
+    }
+}
+
+/// What a garbage collection pass removed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GcReport {
+    pub expired: usize,
+    pub evicted: usize,
+    pub bytes_freed: u64,
+}
+
+/// Content-addressed file store for attachments. Identical files are stored
+/// once; each lives at `<root>/<first two hex chars>/<sha256>` with a JSON
+/// sidecar holding its [`StoredMedia`] record.
+pub struct MediaStore {
+    root: PathBuf,
+    max_file_bytes: u64,
+    max_total_bytes: u64,
+    retention: Option<Duration>,
+    /// Serialises writes so quota checks and eviction see a consistent store,
+    /// and holds the total stored bytes once the first write has counted them.
+    write_lock: Mutex<Option<u64>>,
+}
+
+impl MediaStore {
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)?;
+        info!("opening media store at {}", root.display());
+        Ok(Self {
+            root,
+            max_file_bytes: 25 * 1024 * 1024,
+            max_total_bytes: 1024 * 1024 * 1024,
+            retention: Some(Duration::from_secs(30 * 86_400)),
+            write_lock: Mutex::new(None),
+        })
+    }
+
+    /// Set the per-file and total size limits in bytes.
+    pub fn with_quota(mut self, max_file_bytes: u64, max_total_bytes: u64) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self.max_total_bytes = max_total_bytes;
+        self
+    }
+
+    /// Remove files not accessed for this long during [`gc`](Self::gc).
+    /// `None` keeps files until the size quota evicts them.
+    pub fn with_retention(mut self, retention: Option<Duration>) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Store `data` and return its record. The MIME type is sniffed from the
+    /// content; `declared_mime` is used only when sniffing finds nothing.
+    pub fn put(
+        &self,
+        data: &[u8],
+        filename: Option<&str>,
+        declared_mime: Option<&str>,
+    ) -> Result<StoredMedia> {
+        if data.len() as u64 > self.max_file_bytes {
+            return Err(Error::Media(format!(
+                "file is too large to store ({} MB, limit {} MB)",
+                data.len() / (1024 * 1024),
+                self.max_file_bytes / (1024 * 1024)
+            )));
+        }
+
+        let id = content_id(data);
+        let mut total = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(mut existing) = self.read_meta(&id)? {
+            existing.accessed_at = now_secs();
+            self.write_meta(&existing)?;
+            return Ok(existing);
+        }
+
+        let now = now_secs();
+        let record = StoredMedia {
+            mime_type: sniff_mime(data)
+                .or(declared_mime.filter(|m| !m.trim().is_empty()))
+                .unwrap_or(FALLBACK_MIME)
+                .to_string(),
+            size: data.len() as u64,
+            filename: filename.map(str::to_string),
+            created_at: now,
+            accessed_at: now,
+            id,
+        };
+
+        let path = self.data_path(&record.id);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        // Write to a temporary name first so readers never see a partial file.
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, data)?;
+        std::fs::rename(&tmp, &path)?;
+        self.write_meta(&record)?;
+
+        // Only the first write scans the store; later ones keep a running
+        // total, and the scan repeats only when eviction is needed.
+        let stored = match *total {
+            Some(stored) => stored + record.size,
+            None => self.entries()?.iter().map(|e| e.size).sum(),
+        };
+        *total = Some(stored);
+        if stored > self.max_total_bytes {
+            let entries = self.entries()?;
+            let stored: u64 = entries.iter().map(|e| e.size).sum();
+            let (_, freed) = self.evict(entries, stored, Some(&record.id));
+            *total = Some(stored.saturating_sub(freed));
+        }
+        Ok(record)
+    }
+
+    /// Look up a record without reading the content.
+    pub fn stat(&self, id: &str) -> Result<Option<StoredMedia>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+        self.read_meta(id)
+    }
+
+    /// Read a stored file and mark it as recently used.
+    pub fn get(&self, id: &str) -> Result<Option<(StoredMedia, Vec<u8>)>> {
+        let Some(mut record) = self.stat(id)? else {
+            return Ok(None);
+        };
+        let data = match std::fs::read(self.data_path(id)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        record.accessed_at = now_secs();
+        if let Err(e) = self.write_meta(&record) {
+            warn!("failed to update media access time for {id}: {e}");
+        }
+        Ok(Some((record, data)))
+    }
+
+    /// Resolve a `media://` URL to a `data:` URI for providers that need
+    /// inline content. Returns `None` for other URLs or unknown ids.
+    pub fn to_data_uri(&self, url: &str) -> Result<Option<String>> {
+        let Some(id) = parse_media_url(url) else {
+            return Ok(None);
+        };
+        Ok(self.get(id)?.map(|(record, data)| {
+            let b64 = base64::engine::general_purpose::STANDARD.encode(&data);
+            format!("data:{};base64,{b64}", record.mime_type)
+        }))
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        if !is_valid_id(id) {
+            return Ok(false);
+        }
+        let mut total = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let size = self.read_meta(id)?.map_or(0, |record| record.size);
+        let removed = self.remove(id);
+        if removed && let Some(total) = total.as_mut() {
+            *total = total.saturating_sub(size);
+        }
+        Ok(removed)
+    }
+
+    /// Total bytes of stored content.
+    pub fn total_bytes(&self) -> Result<u64> {
+        Ok(self.entries()?.iter().map(|e| e.size).sum())
+    }
+
+    /// Remove files past the retention period, then evict least recently
+    /// used files until the store fits its quota.
+    pub fn gc(&self) -> Result<GcReport> {
+        let mut stored = self.write_lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut report = GcReport::default();
+        let mut entries = self.entries()?;
+
+        if let Some(retention) = self.retention {
+            let cutoff = now_secs().saturating_sub(retention.as_secs());
+            entries.retain(|entry| {
+                if entry.accessed_at < cutoff && self.remove(&entry.id) {
+                    report.expired += 1;
+                    report.bytes_freed += entry.size;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        let mut total: u64 = entries.iter().map(|e| e.size).sum();
+        if total > self.max_total_bytes {
+            let evicted = self.evict(entries, total, None);
+            report.evicted = evicted.0;
+            report.bytes_freed += evicted.1;
+            total = total.saturating_sub(evicted.1);
+        }
+        // The scan also corrects any drift from files removed by hand.
+        *stored = Some(total);
+        Ok(report)
+    }
+
+    /// Drop least recently used entries (never `keep`) until under quota.
+    /// Returns the number of files and bytes removed.
+    fn evict(
+        &self,
+        mut entries: Vec<StoredMedia>,
+        mut total: u64,
+        keep: Option<&str>,
+    ) -> (usize, u64) {
+        entries.sort_by_key(|e| e.accessed_at);
+        let mut removed = (0, 0);
+        for entry in entries {
+            if total <= self.max_total_bytes {
+                break;
+            }
+            if Some(entry.id.as_str()) == keep {
+                continue;
+            }
+            if self.remove(&entry.id) {
+                total = total.saturating_sub(entry.size);
+                removed.0 += 1;
+                removed.1 += entry.size;
+            }
+        }
+        if removed.0 > 0 {
+            info!(
+                "media store over quota: evicted {} files ({} bytes)",
+                removed.0, removed.1
+            );
+        }
+        removed
+    }
+
+    fn remove(&self, id: &str) -> bool {
+        let data = std::fs::remove_file(self.data_path(id)).is_ok();
+        let meta = std::fs::remove_file(self.meta_path(id)).is_ok();
+        data || meta
+    }
+
+    fn entries(&self) -> Result<Vec<StoredMedia>> {
+        let mut entries = Vec::new();
+        for shard in std::fs::read_dir(&self.root)? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for file in std::fs::read_dir(&shard)? {
+                let path = file?.path();
+                if path.extension().is_some_and(|ext| ext == "json")
+                    && let Some(record) = std::fs::read(&path)
+                        .ok()
+                        .and_then(|raw| serde_json::from_slice::<StoredMedia>(&raw).ok())
+                {
+                    entries.push(record);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    fn read_meta(&self, id: &str) -> Result<Option<StoredMedia>> {
+        match std::fs::read(self.meta_path(id)) {
+            Ok(raw) => Ok(serde_json::from_slice(&raw).ok()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_meta(&self, record: &StoredMedia) -> Result<()> {
+        std::fs::write(self.meta_path(&record.id), serde_json::to_vec(record)?)?;
+        Ok(())
+    }
+
+    fn data_path(&self, id: &str) -> PathBuf {
+        self.root.join(&id[..2]).join(id)
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.data_path(id).with_extension("json")
+    }
+}
+
+/// Extract the id from a `media://` URL.
+pub fn parse_media_url(url: &str) -> Option<&str> {
+    url.strip_prefix(MEDIA_SCHEME).filter(|id| is_valid_id(id))
+}
+
+/// Ids are lowercase hex SHA-256 digests; anything else never touches the filesystem.
+pub fn is_valid_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+fn content_id(data: &[u8]) -> String {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Identify common attachment types from their magic bytes.
+pub fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    let starts = |magic: &[u8]| data.starts_with(magic);
+    if starts(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if starts(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if starts(b"GIF87a") || starts(b"GIF89a") {
+        Some("image/gif")
+    } else if starts(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        Some("image/webp")
+    } else if starts(b"RIFF") && data.get(8..12) == Some(b"WAVE") {
+        Some("audio/wav")
+    } else if starts(b"%PDF-") {
+        Some("application/pdf")
+    } else if starts(b"OggS") {
+        Some("audio/ogg")
+    } else if starts(b"ID3") || starts(b"\xff\xfb") || starts(b"\xff\xf3") {
+        Some("audio/mpeg")
+    } else if data.get(4..8) == Some(b"ftyp") {
+        Some("video/mp4")
+    } else if starts(b"\x1a\x45\xdf\xa3") {
+        Some("video/webm")
+    } else if starts(b"PK\x03\x04") {
+        Some("application/zip")
+    } else {
+        None
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    fn temp_store(name: &str) -> MediaStore {
+        let dir = std::env::temp_dir().join(format!("opencrust_media_store_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        MediaStore::open(dir).unwrap()
+    }
+
+    #[test]
+    fn put_is_content_addressed_and_sniffs_mime() {
+        let store = temp_store("dedupe");
+        let a = store.put(PNG, Some("a.png"), Some("image/jpeg")).unwrap();
+        let b = store.put(PNG, Some("b.png"), None).unwrap();
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.mime_type, "image/png");
+        assert_eq!(store.total_bytes().unwrap(), PNG.len() as u64);
+
+        let (record, data) = store.get(&a.id).unwrap().unwrap();
+        assert_eq!(data, PNG);
+        assert_eq!(record.filename.as_deref(), Some("a.png"));
+
+        let uri = store.to_data_uri(&a.url()).unwrap().unwrap();
+        assert!(uri.starts_with("data:image/png;base64,"));
+    }
+
+    #[test]
+    fn declared_mime_used_when_sniffing_fails() {
+        let store = temp_store("declared");
+        let record = store.put(b"hello", None, Some("text/plain")).unwrap();
+        assert_eq!(record.mime_type, "text/plain");
+        let record = store.put(b"other", None, None).unwrap();
+        assert_eq!(record.mime_type, FALLBACK_MIME);
+    }
+
+    #[test]
+    fn rejects_oversized_files_and_bad_ids() {
+        let store = temp_store("limits").with_quota(4, 100);
+        assert!(matches!(
+            store.put(b"12345", None, None),
+            Err(Error::Media(_))
+        ));
+        assert!(store.get("../../etc/passwd").unwrap().is_none());
+        assert_eq!(parse_media_url("media://not-a-hash"), None);
+        assert_eq!(parse_media_url("https://example.com/a.png"), None);
+    }
+
+    #[test]
+    fn evicts_least_recently_used_over_quota() {
+        let store = temp_store("evict").with_quota(10, 12);
+        let old = store.put(b"aaaaaaaa", None, None).unwrap();
+        // Backdate the first file so it is the eviction candidate.
+        let mut meta = store.stat(&old.id).unwrap().unwrap();
+        meta.accessed_at -= 60;
+        store.write_meta(&meta).unwrap();
+
+        let new = store.put(b"bbbbbbbb", None, None).unwrap();
+        assert!(store.stat(&old.id).unwrap().is_none());
+        assert!(store.stat(&new.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn running_total_follows_writes_deletes_and_evictions() {
+        let store = temp_store("running_total").with_quota(10, 12);
+        let cached = |store: &MediaStore| *store.write_lock.lock().unwrap();
+        let a = store.put(b"aaaa", None, None).unwrap();
+        store.put(b"bbbb", None, None).unwrap();
+        assert_eq!(cached(&store), Some(8));
+        assert!(store.delete(&a.id).unwrap());
+        assert_eq!(cached(&store), Some(4));
+        store.put(b"cccccccc", None, None).unwrap();
+        assert_eq!(cached(&store), Some(store.total_bytes().unwrap()));
+        assert!(cached(&store).unwrap() <= 12);
+    }
+
+    #[test]
+    fn gc_removes_expired_files() {
+        let store = temp_store("gc").with_retention(Some(Duration::from_secs(3600)));
+        let record = store.put(b"stale", None, None).unwrap();
+        let mut meta = record.clone();
+        meta.accessed_at -= 7200;
+        store.write_meta(&meta).unwrap();
+        store.put(b"fresh", None, None).unwrap();
+
+        let report = store.gc().unwrap();
+        assert_eq!(report.expired, 1);
+        assert_eq!(report.bytes_freed, 5);
+        assert!(store.stat(&record.id).unwrap().is_none());
+    }
+}
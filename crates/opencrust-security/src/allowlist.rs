@@ -128,6 +128,19 @@ impl Allowlist {
         removed
     }
 
+    /// Remove a user, and their ownership if they are the owner.
+    pub fn forget(&mut self, user_id: &str) -> bool {
+        let was_owner = self.is_owner(user_id);
+        if was_owner {
+            self.owner = None;
+        }
+        let removed = self.allowed_users.remove(user_id) || was_owner;
+        if removed {
+            self.save();
+        }
+        removed
+    }
+
     pub fn list_users(&self) -> Vec<&str> {
         self.allowed_users.iter().map(|s| s.as_str()).collect()
     }
@@ -213,6 +226,16 @@ mod tests {
         assert!(!allowlist.is_owner("user-2"));
     }
 
+    #[test]
+    fn forgetting_the_owner_clears_ownership() {
+        let mut allowlist = Allowlist::restricted(Vec::<String>::new());
+        allowlist.claim_owner("user-1");
+        assert!(allowlist.forget("user-1"));
+        assert!(!allowlist.is_allowed("user-1"));
+        assert!(allowlist.owner().is_none());
+        assert!(!allowlist.forget("user-1"));
+    }
+
     #[test]
     fn persistence_round_trip() {
         let dir =
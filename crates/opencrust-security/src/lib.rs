@@ -1,11 +1,15 @@
 pub mod allowlist;
 pub mod credentials;
 pub mod pairing;
+pub mod rate_limit;
 pub mod redaction;
+pub mod roles;
 pub mod validation;
 
 pub use allowlist::{Allowlist, AllowlistMode};
 pub use credentials::{CredentialError, CredentialVault, try_vault_get, try_vault_set};
-pub use pairing::PairingManager;
+pub use pairing::{PairingManager, generate_invite_code, parse_invite_code};
+pub use rate_limit::{Cooldown, Rate, RateLimiter, Verdict};
 pub use redaction::{RedactingWriter, redact_secrets};
+pub use roles::Role;
 pub use validation::InputValidator;
@@ -66,9 +66,30 @@ impl PairingManager {
     }
 }
 
+/// Letters and digits that are hard to confuse when read aloud or typed.
+const INVITE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const INVITE_CODE_LEN: usize = 8;
+
+/// Generate a code for a persistent invite, e.g. `K7QMX3TP`. Unlike pairing
+/// codes these are stored by the caller and may be used more than once.
+pub fn generate_invite_code() -> String {
+    let mut rng = rand::rng();
+    (0..INVITE_CODE_LEN)
+        .map(|_| INVITE_ALPHABET[rng.random_range(0..INVITE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Normalise text that may be an invite code: trimmed and upper-cased.
+/// Returns `None` for anything that cannot be one.
+pub fn parse_invite_code(text: &str) -> Option<String> {
+    let code = text.trim().to_ascii_uppercase();
+    (code.len() == INVITE_CODE_LEN && code.bytes().all(|b| INVITE_ALPHABET.contains(&b)))
+        .then_some(code)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::PairingManager;
+    use super::{PairingManager, generate_invite_code, parse_invite_code};
     use std::thread::sleep;
     use std::time::Duration;
 
@@ -101,4 +122,16 @@ mod tests {
         let claim = manager.claim(&code, "user-1");
         assert!(claim.is_none());
     }
+
+    #[test]
+    fn invite_codes_round_trip_through_parsing() {
+        let code = generate_invite_code();
+        assert_eq!(
+            parse_invite_code(&code.to_lowercase()).as_deref(),
+            Some(code.as_str())
+        );
+        assert!(parse_invite_code("123456").is_none());
+        assert!(parse_invite_code("hello there").is_none());
+        assert!(parse_invite_code("ABCDEFG0").is_none());
+    }
 }
@@ -0,0 +1,354 @@
+//! Token-bucket rate limiting for chat messages, with cooldowns that grow
+//! when a sender keeps hitting the limit.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Past this many keys, idle buckets are forgotten so the map stays bounded.
+const MAX_TRACKED_KEYS: usize = 10_000;
+/// Strikes older than this no longer make the next cooldown longer.
+const STRIKE_MEMORY: Duration = Duration::from_secs(3600);
+
+/// Allowed message rate: `burst` messages at once, refilled at
+/// `per_minute`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+/// How long a sender is held back after running out of messages. Each
+/// repeat within an hour doubles the wait, up to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cooldown {
+    pub base: Duration,
+    pub max: Duration,
+}
+
+impl Cooldown {
+    fn after(&self, strikes: u32) -> Duration {
+        let factor = 2u32.saturating_pow(strikes.saturating_sub(1));
+        self.base.saturating_mul(factor).min(self.max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Allowed,
+    /// Rejected for `retry_after`. `notify` is set for the first rejection
+    /// of a cooldown only, so senders are told once rather than on every
+    /// message.
+    Limited {
+        retry_after: Duration,
+        notify: bool,
+    },
+}
+
+struct Bucket {
+    rate: Rate,
+    tokens: f64,
+    updated: Instant,
+    blocked_until: Option<Instant>,
+    strikes: u32,
+    last_strike: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: f64::from(rate.burst.max(1)),
+            updated: now,
+            blocked_until: None,
+            strikes: 0,
+            last_strike: None,
+        }
+    }
+
+    /// Top up the tokens earned since the last check and lift an expired
+    /// cooldown. Returns how long the bucket is still cooling down.
+    fn refill(&mut self, rate: Rate, now: Instant) -> Option<Duration> {
+        let burst = f64::from(rate.burst.max(1));
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * f64::from(rate.per_minute) / 60.0).min(burst);
+        self.rate = rate;
+        self.updated = now;
+        match self.blocked_until {
+            Some(until) if now < until => Some(until - now),
+            _ => {
+                self.blocked_until = None;
+                None
+            }
+        }
+    }
+
+    fn cooling_down(&self, now: Instant) -> bool {
+        self.blocked_until.is_some_and(|until| now < until)
+    }
+
+    /// Whether forgetting the bucket changes nothing: it would be full again
+    /// by now, and it is neither cooling down nor remembering strikes.
+    fn is_idle(&self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        let tokens = self.tokens + elapsed * f64::from(self.rate.per_minute) / 60.0;
+        tokens >= f64::from(self.rate.burst.max(1))
+            && !self.cooling_down(now)
+            && self
+                .last_strike
+                .is_none_or(|at| now.saturating_duration_since(at) >= STRIKE_MEMORY)
+    }
+
+    /// Start a cooldown for running out of messages.
+    fn strike(&mut self, cooldown: Cooldown, now: Instant) -> Duration {
+        if self
+            .last_strike
+            .is_some_and(|at| now.saturating_duration_since(at) >= STRIKE_MEMORY)
+        {
+            self.strikes = 0;
+        }
+        self.strikes += 1;
+        self.last_strike = Some(now);
+        let wait = cooldown.after(self.strikes);
+        self.blocked_until = Some(now + wait);
+        wait
+    }
+}
+
+/// Rate limiter keyed by arbitrary strings, e.g. a user or a chat.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take one message from `key`'s bucket.
+    pub fn check(&self, key: &str, rate: Rate, cooldown: Cooldown) -> Verdict {
+        self.check_at(key, rate, cooldown, Instant::now())
+    }
+
+    /// Take one message from every key's bucket, or from none of them: when
+    /// one key is limited, the others are not charged. Returns the index of
+    /// the first limited key with its verdict.
+    pub fn check_all(
+        &self,
+        limits: &[(&str, Rate)],
+        cooldown: Cooldown,
+    ) -> Option<(usize, Verdict)> {
+        self.check_all_at(limits, cooldown, Instant::now())
+    }
+
+    fn check_all_at(
+        &self,
+        limits: &[(&str, Rate)],
+        cooldown: Cooldown,
+        now: Instant,
+    ) -> Option<(usize, Verdict)> {
+        let mut buckets = self.buckets.lock().unwrap();
+        for (index, &(key, rate)) in limits.iter().enumerate() {
+            if !buckets.contains_key(key) {
+                if buckets.len() >= MAX_TRACKED_KEYS {
+                    make_room(&mut buckets, now);
+                }
+                buckets.insert(key.to_string(), Bucket::new(rate, now));
+            }
+            let bucket = buckets.get_mut(key).expect("bucket was just inserted");
+            if let Some(retry_after) = bucket.refill(rate, now) {
+                return Some((
+                    index,
+                    Verdict::Limited {
+                        retry_after,
+                        notify: false,
+                    },
+                ));
+            }
+            if bucket.tokens < 1.0 {
+                let retry_after = bucket.strike(cooldown, now);
+                return Some((
+                    index,
+                    Verdict::Limited {
+                        retry_after,
+                        notify: true,
+                    },
+                ));
+            }
+        }
+        for (key, _) in limits {
+            if let Some(bucket) = buckets.get_mut(*key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        None
+    }
+
+    fn check_at(&self, key: &str, rate: Rate, cooldown: Cooldown, now: Instant) -> Verdict {
+        match self.check_all_at(&[(key, rate)], cooldown, now) {
+            Some((_, verdict)) => verdict,
+            None => Verdict::Allowed,
+        }
+    }
+}
+
+/// Forget idle buckets. If none are idle, forget the least recently used
+/// bucket that is not cooling down; buckets mid-cooldown are always kept.
+fn make_room(buckets: &mut HashMap<String, Bucket>, now: Instant) {
+    buckets.retain(|_, bucket| !bucket.is_idle(now));
+    if buckets.len() < MAX_TRACKED_KEYS {
+        return;
+    }
+    let oldest = buckets
+        .iter()
+        .filter(|(_, bucket)| !bucket.cooling_down(now))
+        .min_by_key(|(_, bucket)| bucket.updated)
+        .map(|(key, _)| key.clone());
+    if let Some(key) = oldest {
+        buckets.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: Rate = Rate {
+        per_minute: 6,
+        burst: 2,
+    };
+    const COOLDOWN: Cooldown = Cooldown {
+        base: Duration::from_secs(30),
+        max: Duration::from_secs(90),
+    };
+
+    #[test]
+    fn bursts_are_allowed_then_refilled() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        assert_eq!(
+            limiter.check_at("u", RATE, COOLDOWN, start),
+            Verdict::Allowed
+        );
+        assert_eq!(
+            limiter.check_at("u", RATE, COOLDOWN, start),
+            Verdict::Allowed
+        );
+        assert_eq!(
+            limiter.check_at("u", RATE, COOLDOWN, start),
+            Verdict::Limited {
+                retry_after: Duration::from_secs(30),
+                notify: true
+            }
+        );
+        assert_eq!(
+            limiter.check_at("other", RATE, COOLDOWN, start),
+            Verdict::Allowed
+        );
+
+        // Still cooling down: rejected without another notice.
+        let later = start + Duration::from_secs(10);
+        assert_eq!(
+            limiter.check_at("u", RATE, COOLDOWN, later),
+            Verdict::Limited {
+                retry_after: Duration::from_secs(20),
+                notify: false
+            }
+        );
+        let after = start + Duration::from_secs(30);
+        assert_eq!(
+            limiter.check_at("u", RATE, COOLDOWN, after),
+            Verdict::Allowed
+        );
+    }
+
+    #[test]
+    fn repeated_abuse_doubles_the_cooldown_up_to_the_maximum() {
+        let limiter = RateLimiter::new();
+        let mut now = Instant::now();
+        let mut waits = Vec::new();
+        for _ in 0..4 {
+            loop {
+                match limiter.check_at("u", RATE, COOLDOWN, now) {
+                    Verdict::Allowed => {}
+                    Verdict::Limited { retry_after, .. } => {
+                        waits.push(retry_after.as_secs());
+                        now += retry_after;
+                        break;
+                    }
+                }
+            }
+        }
+        assert_eq!(waits, vec![30, 60, 90, 90]);
+
+        // An hour of good behaviour resets the escalation.
+        now += STRIKE_MEMORY;
+        let verdict = loop {
+            let verdict = limiter.check_at("u", RATE, COOLDOWN, now);
+            if verdict != Verdict::Allowed {
+                break verdict;
+            }
+        };
+        assert_eq!(
+            verdict,
+            Verdict::Limited {
+                retry_after: Duration::from_secs(30),
+                notify: true
+            }
+        );
+    }
+
+    #[test]
+    fn a_limited_chat_does_not_charge_the_user() {
+        let limiter = RateLimiter::new();
+        let now = Instant::now();
+        let chat = Rate {
+            per_minute: 6,
+            burst: 1,
+        };
+        let limits = [("user", RATE), ("chat", chat)];
+        assert_eq!(limiter.check_all_at(&limits, COOLDOWN, now), None);
+        assert_eq!(
+            limiter.check_all_at(&limits, COOLDOWN, now),
+            Some((
+                1,
+                Verdict::Limited {
+                    retry_after: Duration::from_secs(30),
+                    notify: true
+                }
+            ))
+        );
+        // The rejected message left the user's second token in place.
+        assert_eq!(
+            limiter.check_at("user", RATE, COOLDOWN, now),
+            Verdict::Allowed
+        );
+    }
+
+    #[test]
+    fn a_full_map_forgets_idle_buckets_but_not_cooldowns() {
+        let limiter = RateLimiter::new();
+        let start = Instant::now();
+        while limiter.check_at("abuser", RATE, COOLDOWN, start) == Verdict::Allowed {}
+        for i in 1..MAX_TRACKED_KEYS {
+            limiter.check_at(&format!("idle-{i}"), RATE, COOLDOWN, start);
+        }
+
+        // Ten seconds on, the idle buckets are full again and make room.
+        let later = start + Duration::from_secs(10);
+        limiter.check_at("new", RATE, COOLDOWN, later);
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(buckets.contains_key("abuser"));
+        assert!(buckets.contains_key("new"));
+        assert!(!buckets.contains_key("idle-1"));
+        drop(buckets);
+        assert_eq!(
+            limiter.check_at("abuser", RATE, COOLDOWN, later),
+            Verdict::Limited {
+                retry_after: Duration::from_secs(20),
+                notify: false
+            }
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// What a chat user may do. Roles are ordered: an owner may do everything an
+/// admin may, and so on down to guests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Limited access, e.g. someone invited to try the bot.
+    Guest,
+    /// A regular user.
+    Member,
+    /// Manages members and guests.
+    Admin,
+    /// Full control, including assigning admins.
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Owner, Role::Admin, Role::Member, Role::Guest];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Guest => "guest",
+            Role::Member => "member",
+            Role::Admin => "admin",
+            Role::Owner => "owner",
+        }
+    }
+
+    /// Whether a user with this role may give `target` to someone, or take
+    /// it away. Owners manage every role, admins only members and guests.
+    pub fn can_manage(self, target: Role) -> bool {
+        match self {
+            Role::Owner => true,
+            Role::Admin => target < Role::Admin,
+            Role::Member | Role::Guest => false,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown role '{s}' (expected owner, admin, member or guest)"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_parse_and_order() {
+        assert_eq!("Admin".parse::<Role>(), Ok(Role::Admin));
+        assert!("root".parse::<Role>().is_err());
+        assert!(Role::Owner > Role::Admin && Role::Member > Role::Guest);
+    }
+
+    #[test]
+    fn admins_manage_only_lower_roles() {
+        assert!(Role::Owner.can_manage(Role::Owner));
+        assert!(Role::Admin.can_manage(Role::Member));
+        assert!(Role::Admin.can_manage(Role::Guest));
+        assert!(!Role::Admin.can_manage(Role::Admin));
+        assert!(!Role::Member.can_manage(Role::Guest));
+    }
+}
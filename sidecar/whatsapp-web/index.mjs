@@ -2,41 +2,214 @@
  * OpenCrust WhatsApp Web Sidecar
  *
  * Bridges WhatsApp Web (via Baileys) to the Rust gateway over stdin/stdout
- * using line-delimited JSON.
+ * using line-delimited JSON. Protocol version 2; the Rust side refuses
+ * sidecars that announce a different version.
  *
  * Stdout events:
+ *   {"type":"hello","protocol":2,"version":"<sidecar-version>"}   (always first)
  *   {"type":"qr","data":"<qr-string>"}
- *   {"type":"ready"}
- *   {"type":"message","id":"<msg-id>","from":"<jid>","name":"<push-name>","text":"<body>"}
+ *   {"type":"ready","jid":"<own-jid>"}
+ *   {"type":"message","id":"<msg-id>","chat":"<jid>","sender":"<jid>","name":"<push-name>",
+ *    "text":"<body-or-caption>","group":{"id","subject","participants":[...]},
+ *    "mentions":["<jid>"],"mentions_me":false,"quoted":{"id","from_me","text"},
+ *    "media":{"kind":"image|sticker|audio|video|document","path","mime_type","filename","voice","error"}}
+ *   {"type":"reaction","chat":"<jid>","sender":"<jid>","name":"<push-name>","id":"<msg-id>","emoji":"<emoji>"}
+ *   {"type":"receipt","chat":"<jid>","id":"<msg-id>","status":"delivered|read|played"}
+ *   {"type":"error","message":"..."}
  *   {"type":"disconnected","reason":"..."}
  *
  * Stdin commands:
- *   {"type":"send","to":"<jid>","text":"<body>"}
+ *   {"type":"send","to":"<jid>","text":"<body>","mentions":["<jid>"],"quoted_id":"<msg-id>"}
+ *   {"type":"send_media","to":"<jid>","kind":"image|audio|video|document","url":"<path-or-url>",
+ *    "mime_type","caption","filename","voice":false,"delete_after":false}
+ *   {"type":"react","chat":"<jid>","id":"<msg-id>","emoji":"<emoji>","sender":"<jid>","from_me":false}
+ *   {"type":"read","chat":"<jid>","ids":["<msg-id>"],"sender":"<jid>"}
+ *   {"type":"presence","to":"<jid>","state":"available|composing|paused"}
  *   {"type":"ping"} -> responds {"type":"pong"}
+ *
+ * Media is exchanged as files: incoming media is saved under
+ * $WHATSAPP_MEDIA_DIR/in, and outgoing files are read from the given path.
  */
 
-import { makeWASocket, useMultiFileAuthState, DisconnectReason, fetchLatestBaileysVersion } from "@whiskeysockets/baileys";
+import {
+  makeWASocket,
+  useMultiFileAuthState,
+  DisconnectReason,
+  fetchLatestBaileysVersion,
+  downloadMediaMessage,
+  jidNormalizedUser,
+} from "@whiskeysockets/baileys";
 import qrcode from "qrcode-terminal";
 import pino from "pino";
 import { createInterface } from "node:readline";
+import { mkdirSync, rmSync, unlinkSync, writeFileSync } from "node:fs";
 import { join } from "node:path";
 import { homedir } from "node:os";
 
+const PROTOCOL_VERSION = 2;
+const SIDECAR_VERSION = "0.2.0";
+
 const AUTH_DIR = process.env.WHATSAPP_AUTH_DIR || join(homedir(), ".opencrust", "whatsapp-web-auth");
+const MEDIA_DIR = process.env.WHATSAPP_MEDIA_DIR || join(homedir(), ".opencrust", "whatsapp-web-media");
+const MAX_MEDIA_BYTES = Number(process.env.WHATSAPP_MAX_MEDIA_BYTES) || 20 * 1024 * 1024;
+
+/** How many recent messages are kept for quoting and reactions. */
+const RECENT_LIMIT = 500;
+
+const RECEIPT_STATUS = { 3: "delivered", 4: "read", 5: "played" };
 
 const logger = pino({ level: "silent" });
 
+/** Current socket; replaced when Baileys reconnects. */
+let sock = null;
+/** Recent incoming messages by id, for quoted replies. */
+const recent = new Map();
+/** Group metadata by jid. */
+const groups = new Map();
+
 /** Send a JSON event to stdout (one line). */
 function emit(obj) {
   process.stdout.write(JSON.stringify(obj) + "\n");
 }
 
+function remember(msg) {
+  recent.set(msg.key.id, msg);
+  if (recent.size > RECENT_LIMIT) {
+    recent.delete(recent.keys().next().value);
+  }
+}
+
+function ownJid() {
+  return sock?.user?.id ? jidNormalizedUser(sock.user.id) : "";
+}
+
+async function groupMetadata(jid) {
+  if (!groups.has(jid)) {
+    const meta = await sock.groupMetadata(jid);
+    groups.set(jid, {
+      id: jid,
+      subject: meta.subject || "",
+      participants: (meta.participants || []).map((p) => p.id),
+    });
+  }
+  return groups.get(jid);
+}
+
+/** Unwrap ephemeral and view-once containers. */
+function innerMessage(message) {
+  return (
+    message?.ephemeralMessage?.message ||
+    message?.viewOnceMessage?.message ||
+    message?.viewOnceMessageV2?.message ||
+    message
+  );
+}
+
+const MEDIA_TYPES = {
+  imageMessage: "image",
+  stickerMessage: "sticker",
+  audioMessage: "audio",
+  videoMessage: "video",
+  documentMessage: "document",
+};
+
+/** Download a media message into MEDIA_DIR/in and describe it. */
+async function saveMedia(msg, content) {
+  const [field, kind] = Object.entries(MEDIA_TYPES).find(([f]) => content[f]) || [];
+  if (!field) return null;
+  const media = content[field];
+  const info = {
+    kind,
+    mime_type: media.mimetype || null,
+    filename: media.fileName || null,
+    voice: Boolean(media.ptt),
+  };
+
+  if (Number(media.fileLength || 0) > MAX_MEDIA_BYTES) {
+    return { ...info, error: `file too large (maximum ${Math.floor(MAX_MEDIA_BYTES / (1024 * 1024))}MB)` };
+  }
+  try {
+    const data = await downloadMediaMessage(msg, "buffer", {}, { logger, reuploadRequest: sock.updateMediaMessage });
+    const dir = join(MEDIA_DIR, "in");
+    mkdirSync(dir, { recursive: true });
+    const path = join(dir, `${msg.key.id}-${kind}`);
+    writeFileSync(path, data);
+    return { ...info, path };
+  } catch (err) {
+    return { ...info, error: `download failed: ${err.message}` };
+  }
+}
+
+async function handleMessage(msg) {
+  const chat = msg.key.remoteJid || "";
+  const isGroup = chat.endsWith("@g.us");
+  const sender = jidNormalizedUser(isGroup ? msg.key.participant || "" : chat);
+  const content = innerMessage(msg.message);
+  if (!content) return;
+
+  if (content.reactionMessage) {
+    emit({
+      type: "reaction",
+      chat,
+      sender,
+      name: msg.pushName || "",
+      id: content.reactionMessage.key?.id || "",
+      emoji: content.reactionMessage.text || "",
+    });
+    return;
+  }
+
+  remember(msg);
+  const mediaField = Object.keys(MEDIA_TYPES).find((f) => content[f]);
+  const text =
+    content.conversation ||
+    content.extendedTextMessage?.text ||
+    (mediaField && content[mediaField].caption) ||
+    "";
+  const media = mediaField ? await saveMedia(msg, content) : null;
+  if (!text && !media) return;
+
+  const contextInfo = content.extendedTextMessage?.contextInfo || (mediaField && content[mediaField].contextInfo) || {};
+  const mentions = (contextInfo.mentionedJid || []).map(jidNormalizedUser);
+  const me = ownJid();
+  const quoted = contextInfo.stanzaId
+    ? {
+        id: contextInfo.stanzaId,
+        from_me: Boolean(me) && jidNormalizedUser(contextInfo.participant || "") === me,
+        text: contextInfo.quotedMessage?.conversation || contextInfo.quotedMessage?.extendedTextMessage?.text || null,
+      }
+    : null;
+
+  let group = null;
+  if (isGroup) {
+    try {
+      group = await groupMetadata(chat);
+    } catch {
+      group = { id: chat, subject: "", participants: [] };
+    }
+  }
+
+  emit({
+    type: "message",
+    id: msg.key.id || "",
+    chat,
+    sender,
+    name: msg.pushName || "",
+    text,
+    group,
+    mentions,
+    mentions_me: Boolean(me) && mentions.includes(me),
+    quoted,
+    media,
+  });
+}
+
 /** Start the Baileys socket and wire up events. */
 async function start() {
   const { state, saveCreds } = await useMultiFileAuthState(AUTH_DIR);
   const { version } = await fetchLatestBaileysVersion();
 
-  const sock = makeWASocket({
+  sock = makeWASocket({
     version,
     auth: state,
     logger,
@@ -59,7 +232,7 @@ async function start() {
     }
 
     if (connection === "open") {
-      emit({ type: "ready" });
+      emit({ type: "ready", jid: ownJid() });
     }
 
     if (connection === "close") {
@@ -67,82 +240,150 @@ async function start() {
       const reason = DisconnectReason[statusCode] || String(statusCode || "unknown");
 
       if (statusCode === DisconnectReason.loggedOut) {
+        // The stored credentials are dead; clear them so the restarted
+        // sidecar shows a fresh QR code.
         emit({ type: "disconnected", reason: "logged_out" });
+        rmSync(AUTH_DIR, { recursive: true, force: true });
         process.exit(0);
       }
 
       emit({ type: "disconnected", reason });
-      // Baileys handles reconnection internally for most disconnect reasons.
-      // Re-create the socket for a clean restart.
-      setTimeout(() => start(), 3000);
+      // Re-create the socket for a clean reconnect; stdin stays attached.
+      setTimeout(() => start().catch(fatal), 3000);
     }
   });
 
-  sock.ev.on("messages.upsert", ({ messages, type: upsertType }) => {
+  sock.ev.on("messages.upsert", async ({ messages, type: upsertType }) => {
     if (upsertType !== "notify") return;
 
     for (const msg of messages) {
-      // Ignore own messages, status broadcasts, and group messages
+      // Ignore own messages and status broadcasts
       if (msg.key.fromMe) continue;
       if (msg.key.remoteJid === "status@broadcast") continue;
-      if (msg.key.remoteJid?.endsWith("@g.us")) continue;
-
-      const text =
-        msg.message?.conversation ||
-        msg.message?.extendedTextMessage?.text ||
-        "";
-
-      if (!text) continue;
+      try {
+        await handleMessage(msg);
+      } catch (err) {
+        emit({ type: "error", message: `message ${msg.key.id}: ${err.message}` });
+      }
+    }
+  });
 
-      emit({
-        type: "message",
-        id: msg.key.id || "",
-        from: msg.key.remoteJid || "",
-        name: msg.pushName || "",
-        text,
-      });
+  sock.ev.on("messages.update", (updates) => {
+    for (const { key, update } of updates) {
+      const status = RECEIPT_STATUS[update?.status];
+      if (key.fromMe && status) {
+        emit({ type: "receipt", chat: key.remoteJid || "", id: key.id || "", status });
+      }
     }
   });
 
-  // Read commands from stdin
-  const rl = createInterface({ input: process.stdin });
+  sock.ev.on("groups.update", (updates) => {
+    for (const update of updates) groups.delete(update.id);
+  });
+  sock.ev.on("group-participants.update", ({ id }) => groups.delete(id));
+}
 
-  rl.on("line", async (line) => {
-    let cmd;
-    try {
-      cmd = JSON.parse(line);
-    } catch {
-      return;
-    }
+const MEDIA_CONTENT = {
+  image: (source, cmd) => ({ image: source, caption: cmd.caption || undefined }),
+  sticker: (source) => ({ sticker: source }),
+  video: (source, cmd) => ({ video: source, caption: cmd.caption || undefined }),
+  audio: (source, cmd) => ({ audio: source, ptt: Boolean(cmd.voice) }),
+  document: (source, cmd) => ({
+    document: source,
+    fileName: cmd.filename || "file",
+    caption: cmd.caption || undefined,
+  }),
+};
 
-    if (cmd.type === "ping") {
+async function runCommand(cmd) {
+  switch (cmd.type) {
+    case "ping":
       emit({ type: "pong" });
       return;
+
+    case "send": {
+      if (!cmd.to || !cmd.text) return;
+      const quoted = cmd.quoted_id ? recent.get(cmd.quoted_id) : undefined;
+      await sock.sendMessage(cmd.to, { text: cmd.text, mentions: cmd.mentions || [] }, { quoted });
+      return;
     }
 
-    if (cmd.type === "send" && cmd.to && cmd.text) {
+    case "send_media": {
+      const build = MEDIA_CONTENT[cmd.kind];
+      if (!cmd.to || !cmd.url || !build) return;
+      const content = build({ url: cmd.url }, cmd);
+      if (cmd.mime_type) content.mimetype = cmd.mime_type;
       try {
-        await sock.sendMessage(cmd.to, { text: cmd.text });
-      } catch (err) {
-        // Log send errors to stderr, don't crash
-        process.stderr.write(`send error: ${err.message}\n`);
+        await sock.sendMessage(cmd.to, content);
+      } finally {
+        if (cmd.delete_after && !/^https?:\/\//.test(cmd.url)) {
+          try {
+            unlinkSync(cmd.url);
+          } catch {
+            // already gone
+          }
+        }
       }
+      return;
     }
-  });
 
-  rl.on("close", () => {
-    // stdin closed - parent process is shutting down
-    process.exit(0);
-  });
+    case "react": {
+      const key = { remoteJid: cmd.chat, id: cmd.id, fromMe: Boolean(cmd.from_me), participant: cmd.sender };
+      await sock.sendMessage(cmd.chat, { react: { text: cmd.emoji || "", key } });
+      return;
+    }
 
-  // Graceful shutdown on SIGTERM
-  process.on("SIGTERM", () => {
-    sock.end(undefined);
-    process.exit(0);
-  });
+    case "read": {
+      const keys = (cmd.ids || []).map((id) => ({ remoteJid: cmd.chat, id, participant: cmd.sender }));
+      if (keys.length) await sock.readMessages(keys);
+      return;
+    }
+
+    case "presence":
+      await sock.sendPresenceUpdate(cmd.state || "available", cmd.to);
+      return;
+
+    default:
+      return;
+  }
 }
 
-start().catch((err) => {
+function fatal(err) {
   process.stderr.write(`fatal: ${err.message}\n`);
   process.exit(1);
+}
+
+emit({ type: "hello", protocol: PROTOCOL_VERSION, version: SIDECAR_VERSION });
+
+// Read commands from stdin. Set up once; survives socket reconnects.
+const rl = createInterface({ input: process.stdin });
+
+rl.on("line", async (line) => {
+  let cmd;
+  try {
+    cmd = JSON.parse(line);
+  } catch {
+    return;
+  }
+  if (!sock) return;
+
+  try {
+    await runCommand(cmd);
+  } catch (err) {
+    // Report command errors, don't crash
+    emit({ type: "error", message: `${cmd.type}: ${err.message}` });
+  }
+});
+
+rl.on("close", () => {
+  // stdin closed - parent process is shutting down
+  process.exit(0);
+});
+
+// Graceful shutdown on SIGTERM
+process.on("SIGTERM", () => {
+  sock?.end(undefined);
+  process.exit(0);
 });
+
+start().catch(fatal);